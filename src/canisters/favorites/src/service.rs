@@ -23,9 +23,7 @@ impl ManagerService {
         STATE.with(|s| {
             let user_set = s.user_favorite_store.borrow();
             let favorites = user_set.get_user_favorites(user);
-            Ok(favorites
-                .map(|favorites| favorites.clone())
-                .unwrap_or(vec![]))
+            Ok(favorites.unwrap_or(vec![]))
         })
     }
 
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use std::sync::Once;
 
 use candid::{candid_method, decode_args, encode_args, Principal};
-use ic_cdk::{api, storage};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::info;
 
@@ -12,10 +12,19 @@ use common::ic_logger::ICLogger;
 use common::named_canister_ids::{
     ensure_current_canister_id_match, update_dev_named_canister_ids, CanisterNames,
 };
-use common::state::StableState;
+use common::stable_memory::MemoryId;
+use common::state::{
+    encode_entries, restore_state, save_state, StableEntries, StableMemoryState, StableState,
+};
 
 use crate::user_favorite_store::UserFavoriteStore;
 
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const USER_FAVORITES_MEMORY_ID: MemoryId = MemoryId::new(1);
+
 thread_local! {
     pub static STATE : State = State::default();
 }
@@ -25,31 +34,45 @@ pub struct State {
     // NOTE: When adding new persistent fields here, ensure that these fields
     // are being persisted in the `replace` method below.
     pub(crate) user_favorite_store: RefCell<UserFavoriteStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    user_favorite_entries: StableEntries<UserFavoriteStore>,
 }
 
 impl State {
     pub fn replace(&self, new_state: State) {
-        self.user_favorite_store
-            .replace(new_state.user_favorite_store.take());
+        new_state
+            .user_favorite_entries
+            .import_into(&self.user_favorite_store);
     }
 }
 
 impl StableState for State {
     fn encode(&self) -> Vec<u8> {
-        encode_args((self.user_favorite_store.borrow().encode(),)).unwrap()
+        encode_args((encode_entries(&self.user_favorite_store),)).unwrap()
     }
 
     fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (user_favorite_store_bytes,) = decode_args(&bytes).unwrap();
+        let (user_favorite_store_bytes,): (Vec<u8>,) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
 
         Ok(State {
-            user_favorite_store: RefCell::new(UserFavoriteStore::decode(
-                user_favorite_store_bytes,
-            )?),
+            user_favorite_entries: StableEntries::decode(user_favorite_store_bytes)?,
+            ..State::default()
         })
     }
 }
 
+/// All stores are kept in stable memory, nothing is saved with the heap state
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args(()).unwrap()
+    }
+
+    fn decode_heap(_bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(State::default())
+    }
+}
+
 static INIT: Once = Once::new();
 
 fn guard_func() -> Result<(), String> {
@@ -87,26 +110,19 @@ fn init_function() {
 #[pre_upgrade(guard = "guard_func")]
 fn pre_upgrade() {
     STATE.with(|s| {
-        let bytes = s.encode();
-        match storage::stable_save((&bytes,)) {
-            Ok(_) => {
-                info!("Saved state before upgrade");
-                ()
-            }
-            Err(e) => api::trap(format!("Failed to save state before upgrade: {:?}", e).as_str()),
-        };
+        save_state(s);
+        info!("Saved state before upgrade");
     });
 }
 
 #[post_upgrade(guard = "guard_func")]
 fn post_upgrade() {
-    STATE.with(|s| match storage::stable_restore::<(Vec<u8>,)>() {
-        Ok(bytes) => {
-            let new_state = State::decode(bytes.0).expect("Decoding stable memory failed");
-
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
             s.replace(new_state);
             info!("Loaded state after upgrade");
-        }
+        }),
         Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
-    });
+    }
 }
@@ -0,0 +1,52 @@
+use rstest::*;
+
+use test_common::user::*;
+
+use common::stable_memory::load_heap_state;
+
+use super::*;
+use crate::user_favorite_store::UserFavorite;
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_user2: Principal, mock_now: u64) {
+    STATE.with(|s| {
+        let mut store = s.user_favorite_store.borrow_mut();
+        store.add_favorite(&mock_user1, mock_now, "a.ic");
+        store.add_favorite(&mock_user1, mock_now, "b.ic");
+        store.add_favorite(&mock_user2, mock_now, "a.ic");
+    });
+
+    pre_upgrade();
+    post_upgrade();
+
+    // nothing is saved with the heap state
+    assert_eq!(load_heap_state(), Some(encode_args(()).unwrap()));
+    STATE.with(|s| {
+        let store = s.user_favorite_store.borrow();
+        assert_eq!(store.get_favorite_count(), 3);
+        assert_eq!(
+            store.get_user_favorites(&mock_user1),
+            Some(vec!["b.ic".to_string(), "a.ic".to_string()])
+        );
+    });
+}
+
+#[rstest]
+fn test_migrate_legacy_state(mock_user1: Principal, mock_now: u64) {
+    let mut user_favorite = UserFavorite::new();
+    user_favorite.add_favorite("a.ic", mock_now);
+    user_favorite.add_favorite("b.ic", mock_now + 1);
+    let user_favorites = HashMap::from([(mock_user1, user_favorite)]);
+    let legacy_bytes = encode_args((encode_args((user_favorites,)).unwrap(),)).unwrap();
+
+    STATE.with(|s| s.replace(State::decode(legacy_bytes).unwrap()));
+
+    STATE.with(|s| {
+        let store = s.user_favorite_store.borrow();
+        assert_eq!(store.get_user_count(), 1);
+        assert_eq!(
+            store.get_user_favorites(&mock_user1),
+            Some(vec!["b.ic".to_string(), "a.ic".to_string()])
+        );
+    });
+}
@@ -12,11 +12,8 @@ impl StatsService {
         stats.cycles_balance = api::canister_balance();
         STATE.with(|s| {
             let user_set = s.user_favorite_store.borrow();
-            let user_favorites = user_set.get_favorites();
-            stats.user_count = user_favorites.len() as u64;
-            stats.favorite_count = user_favorites
-                .values()
-                .fold(0u64, |acc, favorites| acc + favorites.len() as u64);
+            stats.user_count = user_set.get_user_count();
+            stats.favorite_count = user_set.get_favorite_count();
         });
         stats
     }
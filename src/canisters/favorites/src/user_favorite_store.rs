@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize};
+use candid::{CandidType, Deserialize};
 use ic_cdk::export::Principal;
 use log::debug;
 
-use common::state::StableState;
+use common::stable_memory::{PrincipalKey, StableMap};
+use common::state::StableMemoryStore;
+
+use crate::state::USER_FAVORITES_MEMORY_ID;
 
 #[derive(CandidType, Deserialize)]
 pub(crate) struct UserFavorite {
@@ -46,58 +49,80 @@ impl UserFavorite {
     }
 }
 
-#[derive(Default)]
+/// Favorites are kept in stable memory, so that they are not serialized on upgrade.
 pub(crate) struct UserFavoriteStore {
-    user_favorites: HashMap<Principal, UserFavorite>,
+    user_favorites: StableMap<PrincipalKey, UserFavorite>,
 }
 
-impl StableState for UserFavoriteStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.user_favorites,)).unwrap()
+impl Default for UserFavoriteStore {
+    fn default() -> Self {
+        UserFavoriteStore {
+            user_favorites: StableMap::init(USER_FAVORITES_MEMORY_ID),
+        }
     }
+}
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (user_favorites,): (HashMap<Principal, UserFavorite>,) = decode_args(&bytes).unwrap();
+impl StableMemoryStore for UserFavoriteStore {
+    type Entries = (HashMap<Principal, UserFavorite>,);
 
-        Ok(UserFavoriteStore { user_favorites })
+    fn export_entries(&self) -> Self::Entries {
+        (self
+            .user_favorites
+            .iter()
+            .map(|(user, user_favorite)| (user.0, user_favorite))
+            .collect(),)
+    }
+
+    fn import_entries(&mut self, (user_favorites,): Self::Entries) {
+        self.user_favorites.clear();
+        for (user, user_favorite) in user_favorites {
+            self.user_favorites
+                .insert(PrincipalKey(user), user_favorite);
+        }
     }
 }
 
 impl UserFavoriteStore {
     pub fn new() -> Self {
-        UserFavoriteStore {
-            user_favorites: HashMap::new(),
-        }
+        UserFavoriteStore::default()
     }
 
-    pub fn get_favorites(&self) -> &HashMap<Principal, UserFavorite> {
-        &self.user_favorites
+    pub fn get_user_count(&self) -> u64 {
+        self.user_favorites.len()
     }
 
-    pub fn get_user_favorites(&self, user: &Principal) -> Option<&Vec<String>> {
-        if let Some(user_favorites) = self.user_favorites.get(user) {
-            Some(&user_favorites.favorites)
-        } else {
-            None
-        }
+    pub fn get_favorite_count(&self) -> u64 {
+        self.user_favorites
+            .values()
+            .fold(0u64, |acc, favorites| acc + favorites.len() as u64)
+    }
+
+    pub fn get_user_favorites(&self, user: &Principal) -> Option<Vec<String>> {
+        self.user_favorites
+            .get(&PrincipalKey(*user))
+            .map(|user_favorite| user_favorite.favorites)
     }
 
     pub fn add_favorite(&mut self, user: &Principal, now: u64, favorite: &str) {
-        let user_favorite = self
+        let user = PrincipalKey(*user);
+        let mut user_favorite = self
             .user_favorites
-            .entry(user.to_owned())
-            .or_insert_with(|| UserFavorite::new());
+            .get(&user)
+            .unwrap_or_else(UserFavorite::new);
         user_favorite.add_favorite(favorite, now);
+        self.user_favorites.insert(user, user_favorite);
     }
 
     pub fn remove_favorite(&mut self, user: &Principal, now: u64, favorite: &str) {
         debug!("remove favorite {}", favorite);
-        let items = self.user_favorites.get_mut(&user.to_owned());
-        if let Some(items) = items {
+        let user = PrincipalKey(*user);
+        if let Some(mut items) = self.user_favorites.get(&user) {
             items.remove_favorite(favorite, now);
             if items.is_empty() {
                 debug!("remove user favorite");
                 self.user_favorites.remove(&user);
+            } else {
+                self.user_favorites.insert(user, items);
             }
         }
     }
@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Deserialize, Principal};
+use common::dto::QuotaType;
+use common::errors::{NamingError, ServiceResult};
+
+use common::stable_memory::{Bound, PrincipalKey, StableMap, StableValue, Storable};
+use common::state::StableMemoryStore;
+
+use crate::state::{
+    BOX_CONFIG_MEMORY_ID, BUYER_BOX_IDS_MEMORY_ID, OPENED_BOXES_MEMORY_ID,
+    PENDING_BOX_IDS_MEMORY_ID, REJECTED_BOX_IDS_MEMORY_ID,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Reward of mystery box, the chance to get it is weight / total weight of all rewards
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BoxReward {
+    pub quota_type: QuotaType,
+    pub weight: u32,
+}
+
+/// Price and odds of mystery box
+#[derive(CandidType, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BoxConfig {
+    /// Price of a box in DICP e8s
+    pub price: u64,
+    pub rewards: Vec<BoxReward>,
+}
+
+impl BoxConfig {
+    pub fn validate(&self) -> ServiceResult<()> {
+        if self.price == 0 {
+            return Err(NamingError::InvalidMysteryBoxConfig {
+                reason: "price must be greater than 0".to_string(),
+            });
+        }
+        if self.rewards.is_empty() {
+            return Err(NamingError::InvalidMysteryBoxConfig {
+                reason: "rewards must not be empty".to_string(),
+            });
+        }
+        if self.rewards.iter().any(|reward| reward.weight == 0) {
+            return Err(NamingError::InvalidMysteryBoxConfig {
+                reason: "weight of reward must be greater than 0".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn is_on_sale(&self) -> bool {
+        self.price > 0 && self.get_total_weight() > 0
+    }
+
+    pub fn get_total_weight(&self) -> u64 {
+        self.rewards.iter().map(|reward| reward.weight as u64).sum()
+    }
+
+    /// Pick a reward by roll, roll must be less than total weight
+    pub fn pick_reward(&self, roll: u64) -> QuotaType {
+        let mut acc = 0u64;
+        for reward in self.rewards.iter() {
+            acc += reward.weight as u64;
+            if roll < acc {
+                return reward.quota_type;
+            }
+        }
+        panic!("roll {} is out of total weight {}", roll, acc);
+    }
+}
+
+#[derive(CandidType, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OpenedBoxStatus {
+    /// The reward is not credited to the buyer yet
+    Pending,
+    /// The reward is credited to the buyer
+    Credited,
+    /// The reward is rejected by registrar, it is not retried and left for manual handling
+    Rejected,
+}
+
+/// Details of an opened box, it is kept for auditing
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct OpenedBox {
+    pub id: u64,
+    pub buyer: Principal,
+    pub price: u64,
+    pub tx_id: String,
+    /// Random number generated from raw_rand
+    pub random: u64,
+    /// Total weight of rewards when the box is opened
+    pub total_weight: u64,
+    /// random % total_weight, it is used to pick the reward
+    pub roll: u64,
+    pub reward: QuotaType,
+    pub status: OpenedBoxStatus,
+    pub opened_at: u64,
+}
+
+/// Key of boxes opened by a buyer, boxes of a buyer are adjacent and in the order of opening
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct BuyerBoxKey {
+    buyer: PrincipalKey,
+    id: u64,
+}
+
+impl BuyerBoxKey {
+    fn new(buyer: Principal, id: u64) -> Self {
+        BuyerBoxKey {
+            buyer: PrincipalKey(buyer),
+            id,
+        }
+    }
+}
+
+impl Storable for BuyerBoxKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let buyer = self.buyer.to_bytes();
+        let mut bytes = Vec::with_capacity(1 + buyer.len() + 8);
+        bytes.push(buyer.len() as u8);
+        bytes.extend_from_slice(&buyer);
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let buyer_end = 1 + bytes[0] as usize;
+        BuyerBoxKey {
+            buyer: PrincipalKey::from_bytes(Cow::Borrowed(&bytes[1..buyer_end])),
+            id: u64::from_be_bytes(bytes[buyer_end..].try_into().unwrap()),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 38,
+        is_fixed_size: false,
+    };
+}
+
+/// Config and opened boxes are kept in stable memory, so that they are not serialized on upgrade.
+pub struct BoxStore {
+    config: StableValue<BoxConfig>,
+    /// id -> opened box, ids start from 1
+    opened_boxes: StableMap<u64, OpenedBox>,
+    // boxes opened by each buyer, it is updated whenever a box is added
+    buyer_box_ids: StableMap<BuyerBoxKey, ()>,
+    // boxes of each status except credited, they are updated whenever the status of a box is changed
+    pending_box_ids: StableMap<u64, ()>,
+    rejected_box_ids: StableMap<u64, ()>,
+}
+
+impl Default for BoxStore {
+    fn default() -> Self {
+        BoxStore {
+            config: StableValue::init(BOX_CONFIG_MEMORY_ID),
+            opened_boxes: StableMap::init(OPENED_BOXES_MEMORY_ID),
+            buyer_box_ids: StableMap::init(BUYER_BOX_IDS_MEMORY_ID),
+            pending_box_ids: StableMap::init(PENDING_BOX_IDS_MEMORY_ID),
+            rejected_box_ids: StableMap::init(REJECTED_BOX_IDS_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for BoxStore {
+    type Entries = (BoxConfig, Vec<OpenedBox>);
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.get_config().clone(), self.get_opened_boxes().collect())
+    }
+
+    fn import_entries(&mut self, (config, opened_boxes): Self::Entries) {
+        self.set_config(config);
+        self.opened_boxes.clear();
+        self.buyer_box_ids.clear();
+        self.pending_box_ids.clear();
+        self.rejected_box_ids.clear();
+        for opened_box in opened_boxes {
+            self.add_opened_box(opened_box);
+        }
+    }
+}
+
+impl BoxStore {
+    pub fn get_config(&self) -> &BoxConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&mut self, config: BoxConfig) {
+        self.config.set(config);
+    }
+
+    pub fn get_next_id(&self) -> u64 {
+        self.opened_boxes.len() + 1
+    }
+
+    pub fn add_opened_box(&mut self, opened_box: OpenedBox) {
+        assert_eq!(opened_box.id, self.get_next_id());
+        self.buyer_box_ids
+            .insert(BuyerBoxKey::new(opened_box.buyer, opened_box.id), ());
+        self.add_status_id(opened_box.id, opened_box.status);
+        self.opened_boxes.insert(opened_box.id, opened_box);
+    }
+
+    fn add_status_id(&mut self, id: u64, status: OpenedBoxStatus) {
+        match status {
+            OpenedBoxStatus::Pending => {
+                self.pending_box_ids.insert(id, ());
+            }
+            OpenedBoxStatus::Rejected => {
+                self.rejected_box_ids.insert(id, ());
+            }
+            OpenedBoxStatus::Credited => {}
+        }
+    }
+
+    fn remove_status_id(&mut self, id: u64, status: OpenedBoxStatus) {
+        match status {
+            OpenedBoxStatus::Pending => {
+                self.pending_box_ids.remove(&id);
+            }
+            OpenedBoxStatus::Rejected => {
+                self.rejected_box_ids.remove(&id);
+            }
+            OpenedBoxStatus::Credited => {}
+        }
+    }
+
+    pub fn get_opened_box(&self, id: u64) -> Option<OpenedBox> {
+        self.opened_boxes.get(&id)
+    }
+
+    /// Opened boxes in the order of opening
+    pub fn get_opened_boxes(&self) -> impl Iterator<Item = OpenedBox> + '_ {
+        self.opened_boxes.values()
+    }
+
+    pub fn get_opened_box_count(&self) -> u64 {
+        self.opened_boxes.len()
+    }
+
+    /// Get boxes opened by the buyer, in the order of opening
+    pub fn get_opened_boxes_of(
+        &self,
+        buyer: &Principal,
+        offset: usize,
+        limit: usize,
+    ) -> Vec<OpenedBox> {
+        let buyer = PrincipalKey(*buyer);
+        self.buyer_box_ids
+            .range(BuyerBoxKey::new(buyer.0, 0)..)
+            .take_while(|(key, _)| key.buyer == buyer)
+            .skip(offset)
+            .take(limit)
+            .filter_map(|(key, _)| self.get_opened_box(key.id))
+            .collect()
+    }
+
+    /// Pending boxes opened not later than `opened_before`, only pending boxes are visited
+    pub fn get_pending_boxes(&self, opened_before: u64) -> Vec<OpenedBox> {
+        // ids of boxes are in the order of opening
+        self.pending_box_ids
+            .keys()
+            .filter_map(|id| self.get_opened_box(id))
+            .take_while(|opened_box| opened_box.opened_at <= opened_before)
+            .collect()
+    }
+
+    pub fn get_pending_box_count(&self) -> u64 {
+        self.pending_box_ids.len()
+    }
+
+    /// Rejected boxes in the order of opening, they are left for manual handling
+    pub fn get_rejected_boxes(&self, offset: usize, limit: usize) -> Vec<OpenedBox> {
+        self.rejected_box_ids
+            .keys()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|id| self.get_opened_box(id))
+            .collect()
+    }
+
+    pub fn get_rejected_box_count(&self) -> u64 {
+        self.rejected_box_ids.len()
+    }
+
+    pub fn set_credited(&mut self, id: u64) {
+        self.set_status(id, OpenedBoxStatus::Credited);
+    }
+
+    pub fn set_rejected(&mut self, id: u64) {
+        self.set_status(id, OpenedBoxStatus::Rejected);
+    }
+
+    fn set_status(&mut self, id: u64, status: OpenedBoxStatus) {
+        if let Some(mut opened_box) = self.get_opened_box(id) {
+            self.remove_status_id(id, opened_box.status);
+            self.add_status_id(id, status);
+            opened_box.status = status;
+            self.opened_boxes.insert(id, opened_box);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use candid::Principal;
+use rstest::*;
+
+use common::dto::QuotaType;
+use common::errors::NamingError;
+use common::state::StableMemoryStore;
+use test_common::user::*;
+
+use crate::box_store::{BoxConfig, BoxReward, BoxStore, OpenedBox, OpenedBoxStatus};
+
+#[fixture]
+fn config() -> BoxConfig {
+    BoxConfig {
+        price: 100_000_000,
+        rewards: vec![
+            BoxReward {
+                quota_type: QuotaType::LenGte(7),
+                weight: 70,
+            },
+            BoxReward {
+                quota_type: QuotaType::LenGte(5),
+                weight: 25,
+            },
+            BoxReward {
+                quota_type: QuotaType::LenEq(4),
+                weight: 5,
+            },
+        ],
+    }
+}
+
+#[rstest]
+#[case(0, QuotaType::LenGte(7))]
+#[case(69, QuotaType::LenGte(7))]
+#[case(70, QuotaType::LenGte(5))]
+#[case(94, QuotaType::LenGte(5))]
+#[case(95, QuotaType::LenEq(4))]
+#[case(99, QuotaType::LenEq(4))]
+fn test_pick_reward(config: BoxConfig, #[case] roll: u64, #[case] expected: QuotaType) {
+    assert_eq!(config.get_total_weight(), 100);
+    assert_eq!(config.pick_reward(roll), expected);
+}
+
+#[rstest]
+fn test_validate(config: BoxConfig) {
+    assert_eq!(config.validate(), Ok(()));
+    assert!(config.is_on_sale());
+}
+
+#[rstest]
+fn test_validate_invalid_price(mut config: BoxConfig) {
+    config.price = 0;
+    assert!(matches!(
+        config.validate(),
+        Err(NamingError::InvalidMysteryBoxConfig { .. })
+    ));
+}
+
+#[rstest]
+fn test_validate_invalid_weight(mut config: BoxConfig) {
+    config.rewards[1].weight = 0;
+    assert!(matches!(
+        config.validate(),
+        Err(NamingError::InvalidMysteryBoxConfig { .. })
+    ));
+}
+
+#[rstest]
+fn test_default_not_on_sale() {
+    let config = BoxConfig::default();
+    assert!(!config.is_on_sale());
+    assert!(config.validate().is_err());
+}
+
+fn add_opened_box(store: &mut BoxStore, buyer: Principal) {
+    add_opened_box_at(store, buyer, 0);
+}
+
+fn add_opened_box_at(store: &mut BoxStore, buyer: Principal, opened_at: u64) {
+    store.add_opened_box(OpenedBox {
+        id: store.get_next_id(),
+        buyer,
+        price: 100_000_000,
+        tx_id: "1".to_string(),
+        random: 0,
+        total_weight: 100,
+        roll: 0,
+        reward: QuotaType::LenGte(7),
+        status: OpenedBoxStatus::Pending,
+        opened_at,
+    });
+}
+
+#[rstest]
+fn test_get_opened_boxes_of(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = BoxStore::default();
+    add_opened_box(&mut store, mock_user1);
+    add_opened_box(&mut store, mock_user2);
+    add_opened_box(&mut store, mock_user1);
+    add_opened_box(&mut store, mock_user1);
+
+    let ids = |boxes: Vec<OpenedBox>| boxes.iter().map(|b| b.id).collect::<Vec<u64>>();
+    assert_eq!(
+        ids(store.get_opened_boxes_of(&mock_user1, 0, 2)),
+        vec![1, 3]
+    );
+    assert_eq!(ids(store.get_opened_boxes_of(&mock_user1, 2, 2)), vec![4]);
+    assert_eq!(ids(store.get_opened_boxes_of(&mock_user2, 0, 2)), vec![2]);
+
+    // index is rebuilt on import
+    let entries = store.export_entries();
+    add_opened_box(&mut store, mock_user1);
+    store.import_entries(entries);
+    assert_eq!(store.get_next_id(), 5);
+    assert_eq!(
+        ids(store.get_opened_boxes_of(&mock_user1, 1, 10)),
+        vec![3, 4]
+    );
+}
+
+#[rstest]
+fn test_get_pending_boxes(mock_user1: Principal) {
+    let mut store = BoxStore::default();
+    add_opened_box_at(&mut store, mock_user1, 1);
+    add_opened_box_at(&mut store, mock_user1, 2);
+    add_opened_box_at(&mut store, mock_user1, 3);
+    add_opened_box_at(&mut store, mock_user1, 4);
+
+    store.set_credited(1);
+    store.set_rejected(3);
+
+    let ids = |boxes: Vec<OpenedBox>| boxes.iter().map(|b| b.id).collect::<Vec<u64>>();
+    assert_eq!(ids(store.get_pending_boxes(3)), vec![2]);
+    assert_eq!(ids(store.get_pending_boxes(4)), vec![2, 4]);
+    assert_eq!(store.get_pending_box_count(), 2);
+    assert_eq!(ids(store.get_rejected_boxes(0, 10)), vec![3]);
+    assert_eq!(store.get_rejected_box_count(), 1);
+
+    // indexes are rebuilt on import
+    let entries = store.export_entries();
+    store.set_credited(2);
+    store.import_entries(entries);
+    assert_eq!(ids(store.get_pending_boxes(4)), vec![2, 4]);
+    assert_eq!(ids(store.get_rejected_boxes(0, 10)), vec![3]);
+}
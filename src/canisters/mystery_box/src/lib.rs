@@ -1,22 +1,151 @@
-use candid::candid_method;
-use ic_cdk::api;
+mod box_store;
+mod http;
+mod service;
+mod state;
+
+#[path = "../../../common/common_actor/src/actor.rs"]
+mod shared_actor;
+mod stats_service;
+
+use crate::state::InitArgs;
+use candid::{candid_method, CandidType, Principal};
+use common::dto::*;
+use common::errors::{BooleanActorResponse, ErrorInfo, ServiceResult};
+use common::http::*;
+use common::CallContext;
 use ic_cdk_macros::*;
+use stats_service::*;
+use std::collections::HashMap;
+
+use crate::box_store::{BoxConfig, OpenedBox};
+use crate::service::MysteryBoxService;
+
+/// Get price and odds of mystery box
+#[query(name = "get_box_config")]
+#[candid_method(query)]
+fn get_box_config() -> GetBoxConfigResponse {
+    let service = MysteryBoxService::default();
+    let result = service.get_config();
+    GetBoxConfigResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetBoxConfigResponse {
+    Ok(BoxConfig),
+    Err(ErrorInfo),
+}
+
+impl GetBoxConfigResponse {
+    pub fn new(result: ServiceResult<BoxConfig>) -> GetBoxConfigResponse {
+        match result {
+            Ok(data) => GetBoxConfigResponse::Ok(data),
+            Err(err) => GetBoxConfigResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Set price and odds of mystery box, only admin is allowed to call it.
+/// Returns true if success
+///
+/// * `config` - price and rewards with weight
+#[update(name = "set_box_config")]
+#[candid_method(update)]
+fn set_box_config(config: BoxConfig) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MysteryBoxService::default();
+    let result = service.set_config(call_context, config);
+    BooleanActorResponse::new(result)
+}
+
+/// Buy a mystery box with DICP, caller should approve the price of box to this canister first.
+/// Returns the opened box with the reward
+#[update(name = "buy_box")]
+#[candid_method(update)]
+async fn buy_box() -> OpenedBoxResponse {
+    let call_context = CallContext::from_ic();
+    let service = MysteryBoxService::default();
+    let result = service.buy_box(call_context).await;
+    OpenedBoxResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum OpenedBoxResponse {
+    Ok(OpenedBox),
+    Err(ErrorInfo),
+}
 
-use common::cycles_minting_types::{IcpXdrConversionRate, IcpXdrConversionRateCertifiedResponse};
+impl OpenedBoxResponse {
+    pub fn new(result: ServiceResult<OpenedBox>) -> OpenedBoxResponse {
+        match result {
+            Ok(data) => OpenedBoxResponse::Ok(data),
+            Err(err) => OpenedBoxResponse::Err(err.into()),
+        }
+    }
+}
 
-/// Check if name is available.
-/// Returns true if name is available.
+/// Get all opened boxes
 ///
-/// * `name` - name to check, e.g. "hello.ark"
-#[query(name = "get_icp_xdr_conversion_rate")]
-#[candid_method(query, rename = "get_icp_xdr_conversion_rate")]
-pub fn get_icp_xdr_conversion_rate() -> IcpXdrConversionRateCertifiedResponse {
-    IcpXdrConversionRateCertifiedResponse {
-        certificate: Vec::new(),
-        hash_tree: Vec::new(),
-        data: IcpXdrConversionRate {
-            timestamp_seconds: api::time() / 1000_000_000,
-            xdr_permyriad_per_icp: 20000,
-        },
+/// * `page` - page offset and limit
+#[query(name = "get_opened_boxes")]
+#[candid_method(query)]
+fn get_opened_boxes(page: GetPageInput) -> GetOpenedBoxesResponse {
+    let service = MysteryBoxService::default();
+    let result = service.get_opened_boxes(page);
+    GetOpenedBoxesResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetOpenedBoxesResponse {
+    Ok(GetPageOutput<OpenedBox>),
+    Err(ErrorInfo),
+}
+
+impl GetOpenedBoxesResponse {
+    pub fn new(result: ServiceResult<GetPageOutput<OpenedBox>>) -> GetOpenedBoxesResponse {
+        match result {
+            Ok(data) => GetOpenedBoxesResponse::Ok(data),
+            Err(err) => GetOpenedBoxesResponse::Err(err.into()),
+        }
     }
 }
+
+/// Get boxes opened by buyer
+///
+/// * `buyer` - buyer of boxes
+/// * `page` - page offset and limit
+#[query(name = "get_opened_boxes_of")]
+#[candid_method(query)]
+fn get_opened_boxes_of(buyer: Principal, page: GetPageInput) -> GetOpenedBoxesResponse {
+    let service = MysteryBoxService::default();
+    let result = service.get_opened_boxes_of(&buyer, page);
+    GetOpenedBoxesResponse::new(result)
+}
+
+/// Get boxes of which rewards are rejected by registrar, they are not retried and should be handled manually
+///
+/// * `page` - page offset and limit
+#[query(name = "get_rejected_boxes")]
+#[candid_method(query)]
+fn get_rejected_boxes(page: GetPageInput) -> GetOpenedBoxesResponse {
+    let service = MysteryBoxService::default();
+    let result = service.get_rejected_boxes(page);
+    GetOpenedBoxesResponse::new(result)
+}
+
+/// Retry to credit rewards of pending boxes, only timer trigger is allowed to call it.
+#[update(name = "run_tasks")]
+#[candid_method(update)]
+async fn run_tasks() -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MysteryBoxService::default();
+    let result = service.retry_pending_rewards(call_context).await;
+    BooleanActorResponse::new(result)
+}
+
+candid::export_service!();
+
+#[query(name = "__get_candid_interface_tmp_hack")]
+#[candid_method(query)]
+fn __export_did_tmp_() -> String {
+    __export_service()
+}
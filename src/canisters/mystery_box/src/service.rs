@@ -0,0 +1,222 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use candid::{Nat, Principal};
+use log::{debug, error, info};
+
+use common::canister_api::ic_impl::{DICPApi, ManagementApi, RegistrarApi};
+use common::canister_api::{IDICPApi, IManagementApi, IRegistrarApi};
+use common::constants::{DICP_RECEIVER, NAMING_MYSTERY_BOX_RETRY_DELAY_NS};
+use common::dto::{GetPageInput, GetPageOutput};
+use common::errors::{NamingError, ServiceResult};
+use common::named_principals::PRINCIPAL_NAME_TIMER_TRIGGER;
+use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
+use common::CallContext;
+
+use crate::box_store::{BoxConfig, OpenedBox, OpenedBoxStatus};
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+pub struct MysteryBoxService {
+    pub registrar_api: Arc<dyn IRegistrarApi>,
+    pub dicp_api: Arc<dyn IDICPApi>,
+    pub management_api: Arc<dyn IManagementApi>,
+}
+
+impl Default for MysteryBoxService {
+    fn default() -> Self {
+        MysteryBoxService {
+            registrar_api: Arc::new(RegistrarApi),
+            dicp_api: Arc::new(DICPApi::default()),
+            management_api: Arc::new(ManagementApi),
+        }
+    }
+}
+
+impl MysteryBoxService {
+    pub fn get_config(&self) -> ServiceResult<BoxConfig> {
+        STATE.with(|s| {
+            let store = s.box_store.borrow();
+            Ok(store.get_config().clone())
+        })
+    }
+
+    pub fn set_config(&self, call_context: CallContext, config: BoxConfig) -> ServiceResult<bool> {
+        call_context.must_be_system_owner()?;
+        config.validate()?;
+        info!("set mystery box config: {:?}", config);
+        STATE.with(|s| {
+            let mut store = s.box_store.borrow_mut();
+            store.set_config(config);
+        });
+        Ok(true)
+    }
+
+    pub fn get_opened_boxes(&self, page: GetPageInput) -> ServiceResult<GetPageOutput<OpenedBox>> {
+        page.validate()?;
+        STATE.with(|s| {
+            let store = s.box_store.borrow();
+            let items = store
+                .get_opened_boxes()
+                .skip(page.offset)
+                .take(page.limit)
+                .collect();
+            Ok(GetPageOutput::new(items))
+        })
+    }
+
+    /// Boxes of which rewards are rejected by registrar, they are not retried and should be handled manually
+    pub fn get_rejected_boxes(
+        &self,
+        page: GetPageInput,
+    ) -> ServiceResult<GetPageOutput<OpenedBox>> {
+        page.validate()?;
+        STATE.with(|s| {
+            let store = s.box_store.borrow();
+            let items = store.get_rejected_boxes(page.offset, page.limit);
+            Ok(GetPageOutput::new(items))
+        })
+    }
+
+    pub fn get_opened_boxes_of(
+        &self,
+        buyer: &Principal,
+        page: GetPageInput,
+    ) -> ServiceResult<GetPageOutput<OpenedBox>> {
+        page.validate()?;
+        STATE.with(|s| {
+            let store = s.box_store.borrow();
+            let items = store.get_opened_boxes_of(buyer, page.offset, page.limit);
+            Ok(GetPageOutput::new(items))
+        })
+    }
+
+    /// Buy a box and open it, the reward is credited to the buyer as quota in registrar.
+    /// The box is kept as pending if it failed to credit the reward, and it will be retried later.
+    /// The box is rejected if registrar refused to credit the reward, and it is not retried.
+    pub async fn buy_box(&self, call_context: CallContext) -> ServiceResult<OpenedBox> {
+        let buyer = call_context.must_not_anonymous()?;
+        let config = self.get_config()?;
+        if !config.is_on_sale() {
+            return Err(NamingError::MysteryBoxNotOnSale);
+        }
+
+        // draw the reward before payment, it is not exposed if the payment failed
+        let random = self.get_random().await?;
+        let total_weight = config.get_total_weight();
+        let roll = random % total_weight;
+        let reward = config.pick_reward(roll);
+
+        let result = self
+            .dicp_api
+            .transfer_from(
+                None,
+                buyer.0.to_text(),
+                DICP_RECEIVER.deref().to_string(),
+                Nat::from(config.price),
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            error!("buy_box: transfer from {} failed: {:?}", buyer, e);
+            return Err(NamingError::RemoteError(e));
+        }
+        let tx_id = result.unwrap().tx_id;
+
+        let opened_box = STATE.with(|s| {
+            let mut store = s.box_store.borrow_mut();
+            let opened_box = OpenedBox {
+                id: store.get_next_id(),
+                buyer: buyer.0,
+                price: config.price,
+                tx_id,
+                random,
+                total_weight,
+                roll,
+                reward,
+                status: OpenedBoxStatus::Pending,
+                opened_at: call_context.now.0,
+            };
+            store.add_opened_box(opened_box.clone());
+            opened_box
+        });
+        info!("buy_box: box opened: {:?}", opened_box);
+
+        Ok(self.credit_reward(opened_box).await)
+    }
+
+    pub async fn retry_pending_rewards(&self, call_context: CallContext) -> ServiceResult<bool> {
+        call_context.must_be_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER)?;
+        let now = call_context.now;
+        if !try_lock_with_timeout(LockId::MysteryBoxRetryReward, now) {
+            debug!("retry_pending_rewards: already locked");
+            return Ok(false);
+        }
+        // skip boxes just opened, they may be crediting by buy_box
+        let pending_boxes: Vec<OpenedBox> = STATE.with(|s| {
+            let store = s.box_store.borrow();
+            store.get_pending_boxes(now.0.saturating_sub(NAMING_MYSTERY_BOX_RETRY_DELAY_NS))
+        });
+        debug!(
+            "retry_pending_rewards: {} pending boxes",
+            pending_boxes.len()
+        );
+        for opened_box in pending_boxes {
+            self.credit_reward(opened_box).await;
+        }
+        release_timeout_locker(LockId::MysteryBoxRetryReward);
+        Ok(true)
+    }
+
+    /// Credit the reward with id of the box as the idempotency key,
+    /// so that it is not credited twice if the box is retried after a lost response.
+    async fn credit_reward(&self, mut opened_box: OpenedBox) -> OpenedBox {
+        let result = self
+            .registrar_api
+            .add_quota_with_key(
+                opened_box.buyer,
+                opened_box.reward,
+                1,
+                opened_box.id.to_string(),
+            )
+            .await;
+        match result {
+            Ok(true) => {
+                STATE.with(|s| {
+                    let mut store = s.box_store.borrow_mut();
+                    store.set_credited(opened_box.id);
+                });
+                opened_box.status = OpenedBoxStatus::Credited;
+                info!("credit_reward: box {} credited", opened_box.id);
+            }
+            // it would be rejected again, so it is not retried
+            Ok(false) => {
+                STATE.with(|s| {
+                    let mut store = s.box_store.borrow_mut();
+                    store.set_rejected(opened_box.id);
+                });
+                opened_box.status = OpenedBoxStatus::Rejected;
+                error!(
+                    "credit_reward: box {} is rejected by registrar, it should be handled manually",
+                    opened_box.id
+                );
+            }
+            Err(e) => {
+                error!("credit_reward: box {} failed: {:?}", opened_box.id, e);
+            }
+        }
+        opened_box
+    }
+
+    async fn get_random(&self) -> ServiceResult<u64> {
+        let bytes = self
+            .management_api
+            .raw_rand()
+            .await
+            .map_err(NamingError::RemoteError)?;
+        let bytes: [u8; 8] = bytes[..8].try_into().unwrap();
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+
+use candid::Principal;
+use rstest::*;
+
+use common::canister_api::TransactionResponse;
+use common::dto::QuotaType;
+use common::errors::ErrorInfo;
+use common::named_principals::{NAME_DPRINCIPALS, PRINCIPAL_NAME_ADMIN};
+use common::TimeInNs;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::box_store::BoxReward;
+
+use super::*;
+
+const PRICE: u64 = 100_000_000;
+
+fn set_named_principal(name: &'static str, user: Principal) {
+    NAME_DPRINCIPALS.with(|m| {
+        let mut m = m.borrow_mut();
+        let mut set = HashSet::new();
+        set.insert(user);
+        m.principals.insert(name, set);
+    });
+}
+
+fn create_service(
+    registrar_api: MockRegistrarApi,
+    dicp_api: MockDICPApi,
+    management_api: MockManagementApi,
+) -> MysteryBoxService {
+    MysteryBoxService {
+        registrar_api: Arc::new(registrar_api),
+        dicp_api: Arc::new(dicp_api),
+        management_api: Arc::new(management_api),
+    }
+}
+
+fn random_bytes(value: u64) -> Vec<u8> {
+    let mut bytes = value.to_le_bytes().to_vec();
+    bytes.resize(32, 0);
+    bytes
+}
+
+#[fixture]
+fn config() -> BoxConfig {
+    BoxConfig {
+        price: PRICE,
+        rewards: vec![
+            BoxReward {
+                quota_type: QuotaType::LenGte(7),
+                weight: 70,
+            },
+            BoxReward {
+                quota_type: QuotaType::LenGte(5),
+                weight: 30,
+            },
+        ],
+    }
+}
+
+#[fixture]
+fn on_sale(_init_test: (), config: BoxConfig) {
+    STATE.with(|s| {
+        let mut store = s.box_store.borrow_mut();
+        store.set_config(config);
+    });
+}
+
+fn add_pending_box(buyer: Principal, opened_at: u64) {
+    STATE.with(|s| {
+        let mut store = s.box_store.borrow_mut();
+        store.add_opened_box(OpenedBox {
+            id: store.get_next_id(),
+            buyer,
+            price: PRICE,
+            tx_id: "1".to_string(),
+            random: 0,
+            total_weight: 100,
+            roll: 0,
+            reward: QuotaType::LenGte(7),
+            status: OpenedBoxStatus::Pending,
+            opened_at,
+        });
+    });
+}
+
+fn get_opened_box(id: u64) -> Option<OpenedBox> {
+    STATE.with(|s| {
+        let store = s.box_store.borrow();
+        store.get_opened_box(id)
+    })
+}
+
+mod set_config {
+    use super::*;
+
+    #[rstest]
+    fn test_set_config(
+        _init_test: (),
+        config: BoxConfig,
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_management_api: MockManagementApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_ADMIN, mock_user1);
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service.set_config(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            config.clone(),
+        );
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(service.get_config(), Ok(config));
+    }
+
+    #[rstest]
+    fn test_set_config_unauthorized(
+        _init_test: (),
+        config: BoxConfig,
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_management_api: MockManagementApi,
+    ) {
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service.set_config(CallContext::new(mock_user1, TimeInNs(mock_now)), config);
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+        assert_eq!(service.get_config(), Ok(BoxConfig::default()));
+    }
+}
+
+mod buy_box {
+    use super::*;
+
+    #[rstest]
+    async fn test_buy_box_not_on_sale(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_management_api: MockManagementApi,
+    ) {
+        mock_management_api.expect_raw_rand().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .buy_box(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Err(NamingError::MysteryBoxNotOnSale));
+    }
+
+    #[rstest]
+    async fn test_buy_box(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_management_api: MockManagementApi,
+    ) {
+        mock_management_api
+            .expect_raw_rand()
+            .returning(|| Ok(random_bytes(170)));
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |_, from, _, value, _| {
+                assert_eq!(from, mock_user1.to_text());
+                assert_eq!(value, Nat::from(PRICE));
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        mock_registrar_api
+            .expect_add_quota_with_key()
+            .times(1)
+            .returning(move |quota_owner, quota_type, diff, key| {
+                assert_eq!(quota_owner, mock_user1);
+                assert_eq!(quota_type, QuotaType::LenGte(5));
+                assert_eq!(diff, 1);
+                assert_eq!(key, "1");
+                Ok(true)
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .buy_box(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.id, 1);
+        assert_eq!(result.random, 170);
+        assert_eq!(result.total_weight, 100);
+        assert_eq!(result.roll, 70);
+        assert_eq!(result.reward, QuotaType::LenGte(5));
+        assert_eq!(result.status, OpenedBoxStatus::Credited);
+        assert_eq!(get_opened_box(1), Some(result));
+    }
+
+    #[rstest]
+    async fn test_buy_box_payment_failed(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_management_api: MockManagementApi,
+    ) {
+        mock_management_api
+            .expect_raw_rand()
+            .returning(|| Ok(random_bytes(1)));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        mock_registrar_api.expect_add_quota_with_key().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .buy_box(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert_eq!(get_opened_box(1), None);
+    }
+
+    #[rstest]
+    async fn test_buy_box_credit_failed(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_management_api: MockManagementApi,
+    ) {
+        mock_management_api
+            .expect_raw_rand()
+            .returning(|| Ok(random_bytes(1)));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| {
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        mock_registrar_api
+            .expect_add_quota_with_key()
+            .returning(|_, _, _, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .buy_box(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.reward, QuotaType::LenGte(7));
+        assert_eq!(result.status, OpenedBoxStatus::Pending);
+        assert_eq!(get_opened_box(1), Some(result));
+    }
+
+    #[rstest]
+    async fn test_buy_box_credit_rejected(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_management_api: MockManagementApi,
+    ) {
+        mock_management_api
+            .expect_raw_rand()
+            .returning(|| Ok(random_bytes(1)));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| {
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        mock_registrar_api
+            .expect_add_quota_with_key()
+            .returning(|_, _, _, _| Ok(false));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .buy_box(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, OpenedBoxStatus::Rejected);
+        assert_eq!(get_opened_box(1), Some(result.clone()));
+        assert_eq!(
+            service
+                .get_rejected_boxes(GetPageInput {
+                    offset: 0,
+                    limit: 10,
+                })
+                .unwrap()
+                .items,
+            vec![result]
+        );
+    }
+}
+
+mod retry_pending_rewards {
+    use common::constants::NAMING_MYSTERY_BOX_RETRY_DELAY_NS;
+    use common::named_principals::PRINCIPAL_NAME_TIMER_TRIGGER;
+
+    use super::*;
+
+    #[rstest]
+    async fn test_retry_pending_rewards(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_management_api: MockManagementApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user2);
+        add_pending_box(mock_user1, mock_now - NAMING_MYSTERY_BOX_RETRY_DELAY_NS);
+        add_pending_box(mock_user1, mock_now - NAMING_MYSTERY_BOX_RETRY_DELAY_NS);
+        add_pending_box(mock_user1, mock_now);
+        STATE.with(|s| {
+            let mut store = s.box_store.borrow_mut();
+            store.set_rejected(2);
+        });
+        mock_registrar_api
+            .expect_add_quota_with_key()
+            .times(1)
+            .returning(|_, _, _, _| Ok(true));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .retry_pending_rewards(CallContext::new(mock_user2, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_opened_box(1).unwrap().status, OpenedBoxStatus::Credited);
+        assert_eq!(get_opened_box(2).unwrap().status, OpenedBoxStatus::Rejected);
+        assert_eq!(get_opened_box(3).unwrap().status, OpenedBoxStatus::Pending);
+    }
+
+    #[rstest]
+    async fn test_retry_pending_rewards_unauthorized(
+        _on_sale: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_management_api: MockManagementApi,
+    ) {
+        mock_registrar_api.expect_add_quota_with_key().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_management_api);
+
+        let result = service
+            .retry_pending_rewards(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+}
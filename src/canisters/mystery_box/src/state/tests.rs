@@ -0,0 +1,54 @@
+use rstest::*;
+
+use common::dto::QuotaType;
+use test_common::user::*;
+
+use super::*;
+use crate::box_store::{BoxConfig, BoxReward, OpenedBox, OpenedBoxStatus};
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_user2: Principal, mock_now: u64) {
+    let config = BoxConfig {
+        price: 100_000_000,
+        rewards: vec![BoxReward {
+            quota_type: QuotaType::LenGte(7),
+            weight: 100,
+        }],
+    };
+    STATE.with(|s| {
+        let mut store = s.box_store.borrow_mut();
+        store.set_config(config.clone());
+        for buyer in [mock_user1, mock_user2, mock_user1] {
+            store.add_opened_box(OpenedBox {
+                id: store.get_next_id(),
+                buyer,
+                price: 100_000_000,
+                tx_id: "1".to_string(),
+                random: 0,
+                total_weight: 100,
+                roll: 0,
+                reward: QuotaType::LenGte(7),
+                status: OpenedBoxStatus::Pending,
+                opened_at: mock_now,
+            });
+        }
+        store.set_credited(1);
+        store.set_rejected(2);
+    });
+
+    pre_upgrade();
+    post_upgrade();
+
+    STATE.with(|s| {
+        let store = s.box_store.borrow();
+        assert_eq!(store.get_config(), &config);
+        assert_eq!(store.get_next_id(), 4);
+        let ids = |boxes: Vec<OpenedBox>| boxes.iter().map(|b| b.id).collect::<Vec<u64>>();
+        assert_eq!(
+            ids(store.get_opened_boxes_of(&mock_user1, 0, 10)),
+            vec![1, 3]
+        );
+        assert_eq!(ids(store.get_pending_boxes(mock_now)), vec![3]);
+        assert_eq!(ids(store.get_rejected_boxes(0, 10)), vec![2]);
+    });
+}
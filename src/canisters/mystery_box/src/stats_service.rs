@@ -0,0 +1,56 @@
+use crate::state::STATE;
+use candid::{CandidType, Deserialize};
+use common::metrics_encoder::MetricsEncoder;
+use ic_cdk::api;
+
+#[derive(Default)]
+pub struct StatsService {}
+
+impl StatsService {
+    pub fn get_stats(&self, _now: u64) -> Stats {
+        let mut stats = Stats::default();
+        stats.cycles_balance = api::canister_balance();
+        STATE.with(|s| {
+            let store = s.box_store.borrow();
+            stats.opened_box_count = store.get_opened_box_count();
+            stats.pending_box_count = store.get_pending_box_count();
+            stats.rejected_box_count = store.get_rejected_box_count();
+        });
+        stats
+    }
+}
+
+pub fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>, now: u64) -> std::io::Result<()> {
+    let service = StatsService::default();
+    let stats = service.get_stats(now);
+    w.encode_gauge(
+        "icnaming_mystery_box_cycles_balance",
+        stats.cycles_balance as f64,
+        "Balance in cycles",
+    )?;
+    w.encode_gauge(
+        "icnaming_mystery_box_opened_box_count",
+        stats.opened_box_count as f64,
+        "Number of opened boxes",
+    )?;
+    w.encode_gauge(
+        "icnaming_mystery_box_pending_box_count",
+        stats.pending_box_count as f64,
+        "Number of opened boxes with reward not credited",
+    )?;
+    w.encode_gauge(
+        "icnaming_mystery_box_rejected_box_count",
+        stats.rejected_box_count as f64,
+        "Number of opened boxes with reward rejected by registrar",
+    )?;
+
+    Ok(())
+}
+
+#[derive(CandidType, Deserialize, Default)]
+pub struct Stats {
+    cycles_balance: u64,
+    opened_box_count: u64,
+    pending_box_count: u64,
+    rejected_box_count: u64,
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use common::stable_memory::{StableMap, StableValue};
+use common::state::StableMemoryStore;
+
+use crate::state::{ESCROWS_MEMORY_ID, ESCROW_NEXT_ID_MEMORY_ID};
+
+#[derive(CandidType, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EscrowStatus {
+    /// Payment is received from the buyer, the name is being transferred to the buyer
+    Transferring,
+    /// The name is transferred to the buyer, the payment is being paid to the seller
+    Paying,
+    /// The name is failed to transfer, the payment is being refunded to the buyer
+    Refunding,
+}
+
+/// Payment held by marketplace until the sale is settled
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Escrow {
+    pub id: u64,
+    pub name: String,
+    pub seller: Principal,
+    pub buyer: Principal,
+    /// Amount paid by the buyer in DICP e8s
+    pub amount: u64,
+    pub tx_id: String,
+    pub status: EscrowStatus,
+    pub created_at: u64,
+    /// `created_at_time` of the payout whose result is unknown, it is reused to deduplicate the retry
+    pub payout_created_at: Option<u64>,
+}
+
+/// Escrows are kept in stable memory, so that they are not serialized on upgrade.
+pub struct EscrowStore {
+    next_id: StableValue<u64>,
+    /// id -> escrow, it is removed once it is settled
+    escrows: StableMap<u64, Escrow>,
+}
+
+impl Default for EscrowStore {
+    fn default() -> Self {
+        EscrowStore {
+            next_id: StableValue::init(ESCROW_NEXT_ID_MEMORY_ID),
+            escrows: StableMap::init(ESCROWS_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for EscrowStore {
+    type Entries = (u64, BTreeMap<u64, Escrow>);
+
+    fn export_entries(&self) -> Self::Entries {
+        (*self.next_id.get(), self.escrows.iter().collect())
+    }
+
+    fn import_entries(&mut self, (next_id, escrows): Self::Entries) {
+        self.next_id.set(next_id);
+        self.escrows.clear();
+        for (id, escrow) in escrows {
+            self.escrows.insert(id, escrow);
+        }
+    }
+}
+
+impl EscrowStore {
+    pub fn add_escrow(
+        &mut self,
+        name: String,
+        seller: Principal,
+        buyer: Principal,
+        amount: u64,
+        tx_id: String,
+        created_at: u64,
+    ) -> Escrow {
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        let escrow = Escrow {
+            id,
+            name,
+            seller,
+            buyer,
+            amount,
+            tx_id,
+            status: EscrowStatus::Transferring,
+            created_at,
+            payout_created_at: None,
+        };
+        self.escrows.insert(escrow.id, escrow.clone());
+        escrow
+    }
+
+    pub fn get_escrow(&self, id: u64) -> Option<Escrow> {
+        self.escrows.get(&id)
+    }
+
+    /// Escrows ordered by id
+    pub fn get_escrows(&self) -> impl Iterator<Item = Escrow> + '_ {
+        self.escrows.values()
+    }
+
+    pub fn get_escrow_count(&self) -> u64 {
+        self.escrows.len()
+    }
+
+    pub fn set_status(&mut self, id: u64, status: EscrowStatus) {
+        if let Some(mut escrow) = self.get_escrow(id) {
+            escrow.status = status;
+            self.escrows.insert(id, escrow);
+        }
+    }
+
+    pub fn set_payout_created_at(&mut self, id: u64, payout_created_at: Option<u64>) {
+        if let Some(mut escrow) = self.get_escrow(id) {
+            escrow.payout_created_at = payout_created_at;
+            self.escrows.insert(id, escrow);
+        }
+    }
+
+    pub fn remove_escrow(&mut self, id: u64) -> Option<Escrow> {
+        self.escrows.remove(&id)
+    }
+}
@@ -1,22 +1,305 @@
-use candid::candid_method;
-use ic_cdk::api;
+mod escrow_store;
+mod http;
+mod listing_locker;
+mod listing_store;
+mod offer_store;
+mod service;
+mod state;
+
+#[path = "../../../common/common_actor/src/actor.rs"]
+mod shared_actor;
+mod stats_service;
+
+use crate::state::InitArgs;
+use candid::{candid_method, CandidType, Principal};
+use common::dto::*;
+use common::errors::{BooleanActorResponse, ErrorInfo, ServiceResult};
+use common::http::*;
+use common::CallContext;
 use ic_cdk_macros::*;
+use stats_service::*;
+use std::collections::HashMap;
+
+use crate::escrow_store::Escrow;
+use crate::listing_store::Listing;
+use crate::offer_store::{Offer, OfferId};
+use crate::service::MarketplaceService;
+
+/// List a name for sale at a fixed price in DICP, caller should be the owner of the name
+/// and approve the name to this canister in registrar first.
+/// Returns the listing
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `price` - price of the name in DICP e8s
+#[update(name = "list_name")]
+#[candid_method(update)]
+async fn list_name(name: String, price: u64) -> ListingResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.list(call_context, name.as_str(), price).await;
+    ListingResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum ListingResponse {
+    Ok(Listing),
+    Err(ErrorInfo),
+}
+
+impl ListingResponse {
+    pub fn new(result: ServiceResult<Listing>) -> ListingResponse {
+        match result {
+            Ok(data) => ListingResponse::Ok(data),
+            Err(err) => ListingResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Update price of a listing, only seller is allowed to call it.
+/// Returns the listing
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `price` - price of the name in DICP e8s
+#[update(name = "update_listing_price")]
+#[candid_method(update)]
+fn update_listing_price(name: String, price: u64) -> ListingResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.update_price(call_context, name.as_str(), price);
+    ListingResponse::new(result)
+}
+
+/// Remove a listing, only seller is allowed to call it.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.icp`
+#[update(name = "delist_name")]
+#[candid_method(update)]
+fn delist_name(name: String) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.delist(call_context, name.as_str());
+    BooleanActorResponse::new(result)
+}
+
+/// Buy a listed name, caller should approve the price of the name to this canister in DICP first.
+/// The payment is paid to the seller after the name is transferred to the caller,
+/// and it is refunded if the transfer failed.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `expected_price` - price of the listing seen by caller, it is rejected if the price has been changed
+#[update(name = "buy_name")]
+#[candid_method(update)]
+async fn buy_name(name: String, expected_price: u64) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service
+        .buy(call_context, name.as_str(), expected_price)
+        .await;
+    BooleanActorResponse::new(result)
+}
+
+/// Get listing of a name
+///
+/// * `name` - a name. e.g. `hello.icp`
+#[query(name = "get_listing")]
+#[candid_method(query)]
+fn get_listing(name: String) -> ListingResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_listing(name.as_str());
+    ListingResponse::new(result)
+}
+
+/// Get listings ordered by name
+///
+/// * `page` - page offset and limit
+#[query(name = "get_listings")]
+#[candid_method(query)]
+fn get_listings(page: GetPageInput) -> GetListingsResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_listings(page);
+    GetListingsResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetListingsResponse {
+    Ok(GetPageOutput<Listing>),
+    Err(ErrorInfo),
+}
+
+impl GetListingsResponse {
+    pub fn new(result: ServiceResult<GetPageOutput<Listing>>) -> GetListingsResponse {
+        match result {
+            Ok(data) => GetListingsResponse::Ok(data),
+            Err(err) => GetListingsResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Get unsettled escrows
+#[query(name = "get_escrows")]
+#[candid_method(query)]
+fn get_escrows() -> GetEscrowsResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_escrows();
+    GetEscrowsResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetEscrowsResponse {
+    Ok(Vec<Escrow>),
+    Err(ErrorInfo),
+}
+
+impl GetEscrowsResponse {
+    pub fn new(result: ServiceResult<Vec<Escrow>>) -> GetEscrowsResponse {
+        match result {
+            Ok(data) => GetEscrowsResponse::Ok(data),
+            Err(err) => GetEscrowsResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Make an offer on a registered name, caller should approve the amount to this canister in DICP first.
+/// The amount is refunded when the offer is expired, rejected or canceled.
+/// Returns the offer
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `amount` - amount of the offer in DICP e8s
+/// * `duration_in_seconds` - how long the offer is valid, 30 days at most
+#[update(name = "make_offer")]
+#[candid_method(update)]
+async fn make_offer(name: String, amount: u64, duration_in_seconds: u64) -> OfferResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service
+        .make_offer(call_context, name.as_str(), amount, duration_in_seconds)
+        .await;
+    OfferResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum OfferResponse {
+    Ok(Offer),
+    Err(ErrorInfo),
+}
+
+impl OfferResponse {
+    pub fn new(result: ServiceResult<Offer>) -> OfferResponse {
+        match result {
+            Ok(data) => OfferResponse::Ok(data),
+            Err(err) => OfferResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Cancel an open offer, only buyer is allowed to call it.
+/// Returns true if the amount is refunded
+///
+/// * `id` - id of the offer
+#[update(name = "cancel_offer")]
+#[candid_method(update)]
+async fn cancel_offer(id: OfferId) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.cancel_offer(call_context, id).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Reject an open offer, only owner of the name is allowed to call it.
+/// Returns true if the amount is refunded
+///
+/// * `id` - id of the offer
+#[update(name = "reject_offer")]
+#[candid_method(update)]
+async fn reject_offer(id: OfferId) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.reject_offer(call_context, id).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Accept an open offer, only owner of the name is allowed to call it,
+/// and the name should be approved to this canister in registrar first.
+/// Returns true if the name is transferred to the buyer
+///
+/// * `id` - id of the offer
+#[update(name = "accept_offer")]
+#[candid_method(update)]
+async fn accept_offer(id: OfferId) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = MarketplaceService::default();
+    let result = service.accept_offer(call_context, id).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Get an offer
+///
+/// * `id` - id of the offer
+#[query(name = "get_offer")]
+#[candid_method(query)]
+fn get_offer(id: OfferId) -> OfferResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_offer(id);
+    OfferResponse::new(result)
+}
+
+/// Get offers on a name
+///
+/// * `name` - a name. e.g. `hello.icp`
+#[query(name = "get_offers_of_name")]
+#[candid_method(query)]
+fn get_offers_of_name(name: String) -> GetOffersResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_offers_of_name(name.as_str());
+    GetOffersResponse::new(result)
+}
+
+/// Get offers made by a buyer
+///
+/// * `buyer` - buyer of offers
+#[query(name = "get_offers_of_buyer")]
+#[candid_method(query)]
+fn get_offers_of_buyer(buyer: Principal) -> GetOffersResponse {
+    let service = MarketplaceService::default();
+    let result = service.get_offers_of_buyer(&buyer);
+    GetOffersResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetOffersResponse {
+    Ok(Vec<Offer>),
+    Err(ErrorInfo),
+}
 
-use common::cycles_minting_types::{IcpXdrConversionRate, IcpXdrConversionRateCertifiedResponse};
-
-/// Check if name is available.
-/// Returns true if name is available.
-///
-/// * `name` - name to check, e.g. "hello.ark"
-#[query(name = "get_icp_xdr_conversion_rate")]
-#[candid_method(query, rename = "get_icp_xdr_conversion_rate")]
-pub fn get_icp_xdr_conversion_rate() -> IcpXdrConversionRateCertifiedResponse {
-    IcpXdrConversionRateCertifiedResponse {
-        certificate: Vec::new(),
-        hash_tree: Vec::new(),
-        data: IcpXdrConversionRate {
-            timestamp_seconds: api::time() / 1000_000_000,
-            xdr_permyriad_per_icp: 20000,
-        },
+impl GetOffersResponse {
+    pub fn new(result: ServiceResult<Vec<Offer>>) -> GetOffersResponse {
+        match result {
+            Ok(data) => GetOffersResponse::Ok(data),
+            Err(err) => GetOffersResponse::Err(err.into()),
+        }
     }
 }
+
+/// Retry to settle unsettled escrows and refund offers expired, rejected or canceled,
+/// only timer trigger is allowed to call it.
+#[update(name = "run_tasks")]
+#[candid_method(update)]
+async fn run_tasks() -> BooleanActorResponse {
+    let service = MarketplaceService::default();
+    let result = service.retry_escrows(CallContext::from_ic()).await;
+    if result.is_err() {
+        return BooleanActorResponse::new(result);
+    }
+    let result = service.refund_offers(CallContext::from_ic()).await;
+    BooleanActorResponse::new(result)
+}
+
+candid::export_service!();
+
+#[query(name = "__get_candid_interface_tmp_hack")]
+#[candid_method(query)]
+fn __export_did_tmp_() -> String {
+    __export_service()
+}
@@ -0,0 +1,76 @@
+use log::{debug, error};
+use std::collections::HashSet;
+
+use common::errors::{NamingError, ServiceResult};
+
+use crate::state::LISTING_LOCKER;
+
+/// Listings being sold, they can not be sold, updated or delisted concurrently
+pub struct ListingLocker {
+    locks: HashSet<String>,
+}
+
+impl ListingLocker {
+    pub fn new() -> Self {
+        Self {
+            locks: HashSet::new(),
+        }
+    }
+
+    pub fn lock(&mut self, name: &str) -> bool {
+        let new_insert = self.locks.insert(name.to_string());
+        if new_insert {
+            debug!("Locked listing: {}", name);
+        } else {
+            error!("Listing already locked: {}", name);
+        }
+        new_insert
+    }
+
+    pub fn unlock(&mut self, name: &str) -> bool {
+        let removed = self.locks.remove(name);
+        if removed {
+            debug!("Unlocked listing: {}", name);
+        } else {
+            error!("Listing not locked: {}", name);
+        }
+        removed
+    }
+
+    pub fn get_count(&self) -> u32 {
+        self.locks.len() as u32
+    }
+
+    pub fn is_locked(&self, name: &str) -> bool {
+        self.locks.contains(name)
+    }
+}
+
+pub fn try_lock_listing(name: &str) -> ServiceResult<()> {
+    LISTING_LOCKER.with(|locker| {
+        let mut locker = locker.borrow_mut();
+        if locker.is_locked(name) {
+            Err(NamingError::Conflict)
+        } else {
+            locker.lock(name);
+            Ok(())
+        }
+    })
+}
+
+pub fn unlock_listing(name: &str) {
+    LISTING_LOCKER.with(|locker| {
+        let mut locker = locker.borrow_mut();
+        locker.unlock(name);
+    });
+}
+
+pub fn must_not_be_locked(name: &str) -> ServiceResult<()> {
+    LISTING_LOCKER.with(|locker| {
+        if locker.borrow().is_locked(name) {
+            Err(NamingError::Conflict)
+        } else {
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+
+use crate::state::LISTINGS_MEMORY_ID;
+
+#[cfg(test)]
+mod tests;
+
+/// A name listed for sale at a fixed price
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Listing {
+    pub name: String,
+    pub seller: Principal,
+    /// Price of the name in DICP e8s
+    pub price: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Listings are kept in stable memory, so that they are not serialized on upgrade.
+pub struct ListingStore {
+    /// name -> listing, ordered by name for paging
+    listings: StableMap<String, Listing>,
+}
+
+impl Default for ListingStore {
+    fn default() -> Self {
+        ListingStore {
+            listings: StableMap::init(LISTINGS_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for ListingStore {
+    type Entries = (BTreeMap<String, Listing>,);
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.listings.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (listings,): Self::Entries) {
+        self.listings.clear();
+        for (name, listing) in listings {
+            self.listings.insert(name, listing);
+        }
+    }
+}
+
+impl ListingStore {
+    pub fn add_listing(&mut self, listing: Listing) {
+        self.listings.insert(listing.name.clone(), listing);
+    }
+
+    pub fn get_listing(&self, name: &str) -> Option<Listing> {
+        self.listings.get(&name.to_string())
+    }
+
+    /// Listings ordered by name
+    pub fn get_listings(&self) -> impl Iterator<Item = Listing> + '_ {
+        self.listings.values()
+    }
+
+    pub fn get_listing_count(&self) -> u64 {
+        self.listings.len()
+    }
+
+    pub fn update_price(&mut self, name: &str, price: u64, now: u64) -> bool {
+        match self.get_listing(name) {
+            Some(mut listing) => {
+                listing.price = price;
+                listing.updated_at = now;
+                self.add_listing(listing);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_listing(&mut self, name: &str) -> Option<Listing> {
+        self.listings.remove(&name.to_string())
+    }
+}
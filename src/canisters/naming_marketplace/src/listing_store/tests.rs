@@ -0,0 +1,58 @@
+use rstest::*;
+use test_common::user::*;
+
+use super::*;
+
+fn create_listing(name: &str, seller: Principal, price: u64, now: u64) -> Listing {
+    Listing {
+        name: name.to_string(),
+        seller,
+        price,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+#[rstest]
+fn test_add_and_remove_listing(mock_user1: Principal, mock_now: u64) {
+    let mut store = ListingStore::default();
+    let listing = create_listing("hello.ic", mock_user1, 100, mock_now);
+
+    store.add_listing(listing.clone());
+    assert_eq!(store.get_listing("hello.ic"), Some(listing.clone()));
+
+    assert_eq!(store.remove_listing("hello.ic"), Some(listing));
+    assert_eq!(store.get_listing("hello.ic"), None);
+    assert_eq!(store.remove_listing("hello.ic"), None);
+}
+
+#[rstest]
+fn test_update_price(mock_user1: Principal, mock_now: u64) {
+    let mut store = ListingStore::default();
+    store.add_listing(create_listing("hello.ic", mock_user1, 100, mock_now));
+
+    assert!(store.update_price("hello.ic", 200, mock_now + 1));
+    assert!(!store.update_price("world.ic", 200, mock_now + 1));
+
+    let listing = store.get_listing("hello.ic").unwrap();
+    assert_eq!(listing.price, 200);
+    assert_eq!(listing.created_at, mock_now);
+    assert_eq!(listing.updated_at, mock_now + 1);
+}
+
+#[rstest]
+fn test_export_import(mock_user1: Principal, mock_now: u64) {
+    let mut store = ListingStore::default();
+    store.add_listing(create_listing("world.ic", mock_user1, 200, mock_now));
+    store.add_listing(create_listing("hello.ic", mock_user1, 100, mock_now));
+    let listings: Vec<Listing> = store.get_listings().collect();
+    let entries = store.export_entries();
+
+    store.remove_listing("hello.ic");
+    store.add_listing(create_listing("other.ic", mock_user1, 300, mock_now));
+    store.import_entries(entries);
+
+    let names: Vec<String> = store.get_listings().map(|listing| listing.name).collect();
+    assert_eq!(names, vec!["hello.ic", "world.ic"]);
+    assert_eq!(store.get_listings().collect::<Vec<_>>(), listings);
+}
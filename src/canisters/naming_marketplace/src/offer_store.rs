@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+use log::debug;
+
+use common::stable_memory::{StableMap, StableValue};
+use common::state::StableMemoryStore;
+
+use crate::state::{LAST_OFFER_ID_MEMORY_ID, OFFERS_MEMORY_ID};
+
+#[cfg(test)]
+mod tests;
+
+pub type OfferId = u64;
+
+#[derive(CandidType, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OfferStatus {
+    /// Offer is waiting for the owner of the name to accept
+    Open,
+    /// Offer is accepted, the name is being transferred to the buyer
+    Accepting,
+    /// Offer is expired, rejected or canceled, the amount is to be refunded to the buyer
+    ToBeRefunded,
+    /// The amount is being refunded to the buyer
+    Refunding,
+}
+
+/// Time-limited offer on a name, the amount is held by marketplace until it is accepted or refunded
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Offer {
+    pub id: OfferId,
+    pub name: String,
+    pub buyer: Principal,
+    /// Amount offered by the buyer in DICP e8s
+    pub amount: u64,
+    pub tx_id: String,
+    pub status: OfferStatus,
+    /// Owner of the name who accepted the offer
+    pub accepted_by: Option<Principal>,
+    pub accepted_at: Option<u64>,
+    pub created_at: u64,
+    pub expired_at: u64,
+    /// `created_at_time` of the refund whose result is unknown, it is reused to deduplicate the retry
+    pub refund_created_at: Option<u64>,
+}
+
+impl Offer {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expired_at <= now
+    }
+}
+
+/// Offers are kept in stable memory, so that they are not serialized on upgrade.
+pub struct OfferStore {
+    last_offer_id: StableValue<OfferId>,
+    /// id -> offer, it is removed once it is accepted or refunded
+    offers: StableMap<OfferId, Offer>,
+}
+
+impl Default for OfferStore {
+    fn default() -> Self {
+        OfferStore {
+            last_offer_id: StableValue::init(LAST_OFFER_ID_MEMORY_ID),
+            offers: StableMap::init(OFFERS_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for OfferStore {
+    type Entries = (OfferId, BTreeMap<OfferId, Offer>);
+
+    fn export_entries(&self) -> Self::Entries {
+        (*self.last_offer_id.get(), self.offers.iter().collect())
+    }
+
+    fn import_entries(&mut self, (last_offer_id, offers): Self::Entries) {
+        self.last_offer_id.set(last_offer_id);
+        self.offers.clear();
+        for (id, offer) in offers {
+            self.offers.insert(id, offer);
+        }
+    }
+}
+
+impl OfferStore {
+    pub fn new_offer(
+        &mut self,
+        name: String,
+        buyer: Principal,
+        amount: u64,
+        tx_id: String,
+        created_at: u64,
+        expired_at: u64,
+    ) -> Offer {
+        let id = self.last_offer_id.get() + 1;
+        self.last_offer_id.set(id);
+        let offer = Offer {
+            id,
+            name,
+            buyer,
+            amount,
+            tx_id,
+            status: OfferStatus::Open,
+            accepted_by: None,
+            accepted_at: None,
+            created_at,
+            expired_at,
+            refund_created_at: None,
+        };
+        self.offers.insert(offer.id, offer.clone());
+        offer
+    }
+
+    pub fn get_offer(&self, id: OfferId) -> Option<Offer> {
+        self.offers.get(&id)
+    }
+
+    /// Offers ordered by id
+    pub fn get_offers(&self) -> impl Iterator<Item = Offer> + '_ {
+        self.offers.values()
+    }
+
+    pub fn get_offer_count(&self) -> u64 {
+        self.offers.len()
+    }
+
+    pub fn remove_offer(&mut self, id: OfferId) -> Option<Offer> {
+        debug!("removing offer: {}", id);
+        self.offers.remove(&id)
+    }
+
+    fn update_offer(&mut self, id: OfferId, update: impl FnOnce(&mut Offer)) {
+        if let Some(mut offer) = self.get_offer(id) {
+            update(&mut offer);
+            self.offers.insert(id, offer);
+        }
+    }
+
+    pub fn mark_open(&mut self, id: OfferId) {
+        debug!("marking offer open: {}", id);
+        self.update_offer(id, |offer| {
+            offer.status = OfferStatus::Open;
+            offer.accepted_by = None;
+            offer.accepted_at = None;
+        });
+    }
+
+    pub fn mark_accepting(&mut self, id: OfferId, accepted_by: Principal, now: u64) {
+        debug!("marking offer accepting: {}", id);
+        self.update_offer(id, |offer| {
+            offer.status = OfferStatus::Accepting;
+            offer.accepted_by = Some(accepted_by);
+            offer.accepted_at = Some(now);
+        });
+    }
+
+    pub fn mark_to_be_refunded(&mut self, id: OfferId) {
+        debug!("marking offer to be refunded: {}", id);
+        self.update_offer(id, |offer| offer.status = OfferStatus::ToBeRefunded);
+    }
+
+    pub fn mark_refunding(&mut self, id: OfferId, refund_created_at: u64) {
+        debug!("marking offer refunding: {}", id);
+        self.update_offer(id, |offer| {
+            offer.status = OfferStatus::Refunding;
+            offer.refund_created_at = Some(refund_created_at);
+        });
+    }
+
+    /// Refund is failed for sure, it is retried as a new transfer
+    pub fn reset_refund_created_at(&mut self, id: OfferId) {
+        self.update_offer(id, |offer| offer.refund_created_at = None);
+    }
+
+    /// Mark open offers expired as to be refunded, returns count of them
+    pub fn mark_expired_to_be_refunded(&mut self, now: u64) -> usize {
+        let expired_offers: Vec<Offer> = self
+            .offers
+            .values()
+            .filter(|offer| offer.status == OfferStatus::Open && offer.is_expired(now))
+            .collect();
+        let count = expired_offers.len();
+        for mut offer in expired_offers {
+            offer.status = OfferStatus::ToBeRefunded;
+            self.offers.insert(offer.id, offer);
+        }
+        count
+    }
+
+    pub fn get_to_be_refunded_offers(&self, limit: u32) -> Vec<Offer> {
+        self.offers
+            .values()
+            .filter(|offer| offer.status == OfferStatus::ToBeRefunded)
+            .take(limit as usize)
+            .collect()
+    }
+}
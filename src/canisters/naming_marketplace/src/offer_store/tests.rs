@@ -0,0 +1,85 @@
+use rstest::*;
+use test_common::user::*;
+
+use super::*;
+
+const DURATION: u64 = 1_000;
+
+fn new_offer(store: &mut OfferStore, buyer: Principal, now: u64) -> Offer {
+    store.new_offer(
+        "hello.ic".to_string(),
+        buyer,
+        100,
+        "1".to_string(),
+        now,
+        now + DURATION,
+    )
+}
+
+#[rstest]
+fn test_new_offer(mock_user1: Principal, mock_now: u64) {
+    let mut store = OfferStore::default();
+
+    let offer1 = new_offer(&mut store, mock_user1, mock_now);
+    let offer2 = new_offer(&mut store, mock_user1, mock_now);
+
+    assert_eq!(offer1.id, 1);
+    assert_eq!(offer2.id, 2);
+    assert_eq!(offer1.status, OfferStatus::Open);
+    assert_eq!(store.get_offer(1), Some(offer1));
+
+    store.remove_offer(1);
+    let offer3 = new_offer(&mut store, mock_user1, mock_now);
+    assert_eq!(offer3.id, 3);
+}
+
+#[rstest]
+fn test_mark_accepting_and_open(mock_user1: Principal, mock_user2: Principal, mock_now: u64) {
+    let mut store = OfferStore::default();
+    let offer = new_offer(&mut store, mock_user1, mock_now);
+
+    store.mark_accepting(offer.id, mock_user2, mock_now);
+    let accepting = store.get_offer(offer.id).unwrap();
+    assert_eq!(accepting.status, OfferStatus::Accepting);
+    assert_eq!(accepting.accepted_by, Some(mock_user2));
+    assert_eq!(accepting.accepted_at, Some(mock_now));
+
+    store.mark_open(offer.id);
+    assert_eq!(store.get_offer(offer.id), Some(offer));
+}
+
+#[rstest]
+fn test_mark_expired_to_be_refunded(mock_user1: Principal, mock_now: u64) {
+    let mut store = OfferStore::default();
+    let expired = new_offer(&mut store, mock_user1, mock_now - DURATION);
+    let accepting = new_offer(&mut store, mock_user1, mock_now - DURATION);
+    store.mark_accepting(accepting.id, mock_user1, mock_now);
+    let open = new_offer(&mut store, mock_user1, mock_now);
+
+    assert_eq!(store.mark_expired_to_be_refunded(mock_now), 1);
+
+    let offers = store.get_to_be_refunded_offers(10);
+    assert_eq!(offers.len(), 1);
+    assert_eq!(offers[0].id, expired.id);
+    assert_eq!(
+        store.get_offer(accepting.id).unwrap().status,
+        OfferStatus::Accepting
+    );
+    assert_eq!(store.get_offer(open.id).unwrap().status, OfferStatus::Open);
+}
+
+#[rstest]
+fn test_export_import(mock_user1: Principal, mock_now: u64) {
+    let mut store = OfferStore::default();
+    new_offer(&mut store, mock_user1, mock_now);
+    new_offer(&mut store, mock_user1, mock_now);
+    store.remove_offer(2);
+    let offers: Vec<Offer> = store.get_offers().collect();
+    let entries = store.export_entries();
+
+    new_offer(&mut store, mock_user1, mock_now);
+    store.import_entries(entries);
+
+    assert_eq!(store.get_offers().collect::<Vec<_>>(), offers);
+    assert_eq!(new_offer(&mut store, mock_user1, mock_now).id, 3);
+}
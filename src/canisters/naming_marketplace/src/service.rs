@@ -0,0 +1,805 @@
+use std::sync::Arc;
+
+use candid::{Nat, Principal};
+use log::{debug, error, info, warn};
+use serde_bytes::ByteBuf;
+
+use common::canister_api::ic_impl::{DICPApi, IcrcLedgerApi, RegistrarApi};
+use common::canister_api::{IDICPApi, IIcrcLedgerApi, IRegistrarApi};
+use common::constants::{
+    NAMING_MARKETPLACE_OFFER_MAX_DURATION_NS, NAMING_MARKETPLACE_RETRY_DELAY_NS,
+};
+use common::dto::{GetPageInput, GetPageOutput};
+use common::errors::{NamingError, ServiceResult};
+use common::icrc::{Account, Icrc1TransferArg, Icrc1TransferError};
+use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+use common::named_principals::PRINCIPAL_NAME_TIMER_TRIGGER;
+use common::naming::normalize_name;
+use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
+use common::CallContext;
+
+use crate::escrow_store::{Escrow, EscrowStatus};
+use crate::listing_locker::{must_not_be_locked, try_lock_listing, unlock_listing};
+use crate::listing_store::Listing;
+use crate::offer_store::{Offer, OfferId, OfferStatus};
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+pub struct MarketplaceService {
+    pub registrar_api: Arc<dyn IRegistrarApi>,
+    pub dicp_api: Arc<dyn IDICPApi>,
+    /// Payouts are made by ICRC-1 `icrc1_transfer` of DICP, so that retries are deduplicated by the ledger
+    pub icrc_ledger_api: Arc<dyn IIcrcLedgerApi>,
+}
+
+impl Default for MarketplaceService {
+    fn default() -> Self {
+        MarketplaceService {
+            registrar_api: Arc::new(RegistrarApi),
+            dicp_api: Arc::new(DICPApi::default()),
+            icrc_ledger_api: Arc::new(IcrcLedgerApi),
+        }
+    }
+}
+
+/// Result of a payout from marketplace
+enum PayoutResult {
+    Paid,
+    /// Nothing is transferred, it is retried as a new transfer
+    Failed(NamingError),
+    /// It is not known whether it is transferred, it is retried with the same `created_at_time`
+    Unknown(NamingError),
+}
+
+impl MarketplaceService {
+    pub fn get_listing(&self, name: &str) -> ServiceResult<Listing> {
+        let name = normalize_name(name);
+        STATE.with(|s| {
+            let store = s.listing_store.borrow();
+            store
+                .get_listing(&name.0)
+                .ok_or(NamingError::ListingNotFound { name: name.0 })
+        })
+    }
+
+    pub fn get_listings(&self, page: GetPageInput) -> ServiceResult<GetPageOutput<Listing>> {
+        page.validate()?;
+        STATE.with(|s| {
+            let store = s.listing_store.borrow();
+            let items = store
+                .get_listings()
+                .skip(page.offset)
+                .take(page.limit)
+                .collect();
+            Ok(GetPageOutput::new(items))
+        })
+    }
+
+    pub fn get_escrows(&self) -> ServiceResult<Vec<Escrow>> {
+        STATE.with(|s| {
+            let store = s.escrow_store.borrow();
+            Ok(store.get_escrows().collect())
+        })
+    }
+
+    /// List a name for sale, the seller should approve the name to marketplace in registrar first.
+    pub async fn list(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        price: u64,
+    ) -> ServiceResult<Listing> {
+        let seller = call_context.must_not_anonymous()?;
+        if price == 0 {
+            return Err(NamingError::InvalidListingPrice);
+        }
+        let name = normalize_name(name).0;
+        must_not_be_locked(&name)?;
+
+        let owner = self
+            .registrar_api
+            .get_owner(name.clone())
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if owner != seller.0 {
+            return Err(NamingError::OwnerOnly);
+        }
+        // the name could not be transferred to buyer if it is not approved to marketplace
+        let approved = self
+            .registrar_api
+            .is_approved_to(
+                name.clone(),
+                get_named_get_canister_id(CanisterNames::NamingMarketplace),
+            )
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if !approved {
+            return Err(NamingError::ListingNotApproved { name });
+        }
+
+        let now = call_context.now.0;
+        let listing = STATE.with(|s| {
+            let mut store = s.listing_store.borrow_mut();
+            // listing of previous owner is replaced
+            let created_at = match store.get_listing(&name) {
+                Some(listing) if listing.seller == seller.0 => listing.created_at,
+                _ => now,
+            };
+            let listing = Listing {
+                name: name.clone(),
+                seller: seller.0,
+                price,
+                created_at,
+                updated_at: now,
+            };
+            store.add_listing(listing.clone());
+            listing
+        });
+        info!("list: {:?}", listing);
+        Ok(listing)
+    }
+
+    pub fn update_price(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        price: u64,
+    ) -> ServiceResult<Listing> {
+        let seller = call_context.must_not_anonymous()?;
+        if price == 0 {
+            return Err(NamingError::InvalidListingPrice);
+        }
+        let listing = self.get_listing(name)?;
+        if listing.seller != seller.0 {
+            return Err(NamingError::OwnerOnly);
+        }
+        must_not_be_locked(&listing.name)?;
+
+        STATE.with(|s| {
+            let mut store = s.listing_store.borrow_mut();
+            store.update_price(&listing.name, price, call_context.now.0);
+            Ok(store.get_listing(&listing.name).unwrap())
+        })
+    }
+
+    pub fn delist(&self, call_context: CallContext, name: &str) -> ServiceResult<bool> {
+        let seller = call_context.must_not_anonymous()?;
+        let listing = self.get_listing(name)?;
+        if listing.seller != seller.0 {
+            return Err(NamingError::OwnerOnly);
+        }
+        must_not_be_locked(&listing.name)?;
+
+        STATE.with(|s| {
+            let mut store = s.listing_store.borrow_mut();
+            store.remove_listing(&listing.name);
+        });
+        info!("delist: {}", listing.name);
+        Ok(true)
+    }
+
+    /// Buy a listed name, the buyer should approve the price of the name to marketplace in DICP first.
+    /// The payment is held in escrow until the name is transferred to the buyer,
+    /// it is paid to the seller if the transfer succeeded, otherwise it is refunded to the buyer.
+    /// It is rejected if the price of listing is not the expected price, since seller could update it anytime.
+    pub async fn buy(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        expected_price: u64,
+    ) -> ServiceResult<bool> {
+        let buyer = call_context.must_not_anonymous()?;
+        let listing = self.get_listing(name)?;
+        if listing.seller == buyer.0 {
+            return Err(NamingError::PermissionDenied);
+        }
+        if listing.price != expected_price {
+            return Err(NamingError::ListingPriceChanged {
+                name: listing.name,
+                price: listing.price,
+            });
+        }
+
+        try_lock_listing(&listing.name)?;
+        let result = self
+            .buy_core(buyer.0, listing.clone(), call_context.now.0)
+            .await;
+        unlock_listing(&listing.name);
+        result
+    }
+
+    async fn buy_core(&self, buyer: Principal, listing: Listing, now: u64) -> ServiceResult<bool> {
+        let owner = self
+            .registrar_api
+            .get_owner(listing.name.clone())
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if owner != listing.seller {
+            // the name is not owned by the seller anymore
+            STATE.with(|s| {
+                let mut store = s.listing_store.borrow_mut();
+                store.remove_listing(&listing.name);
+            });
+            info!("buy: listing {} is stale, removed", listing.name);
+            return Err(NamingError::ListingNotFound { name: listing.name });
+        }
+
+        let result = self
+            .dicp_api
+            .transfer_from(
+                None,
+                buyer.to_text(),
+                get_named_get_canister_id(CanisterNames::NamingMarketplace).to_text(),
+                Nat::from(listing.price),
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            error!("buy: transfer from {} failed: {:?}", buyer, e);
+            return Err(NamingError::RemoteError(e));
+        }
+        let tx_id = result.unwrap().tx_id;
+
+        let escrow = STATE.with(|s| {
+            let mut store = s.escrow_store.borrow_mut();
+            store.add_escrow(
+                listing.name.clone(),
+                listing.seller,
+                buyer,
+                listing.price,
+                tx_id,
+                now,
+            )
+        });
+        info!("buy: escrow created: {:?}", escrow);
+
+        let result = self
+            .registrar_api
+            .transfer_from(listing.name.clone(), Some(buyer))
+            .await;
+        match result {
+            Ok(true) => {
+                STATE.with(|s| {
+                    let mut store = s.listing_store.borrow_mut();
+                    store.remove_listing(&listing.name);
+                });
+                self.set_escrow_status(escrow.id, EscrowStatus::Paying);
+                self.settle_escrow(escrow.id, listing.seller, listing.price, now)
+                    .await;
+                info!("buy: {} is sold to {}", listing.name, buyer);
+                Ok(true)
+            }
+            Ok(false) => {
+                error!("buy: transfer of {} is rejected by registrar", listing.name);
+                self.set_escrow_status(escrow.id, EscrowStatus::Refunding);
+                self.settle_escrow(escrow.id, buyer, listing.price, now)
+                    .await;
+                Err(NamingError::PermissionDenied)
+            }
+            Err(e) => {
+                error!("buy: transfer of {} failed: {:?}", listing.name, e);
+                self.set_escrow_status(escrow.id, EscrowStatus::Refunding);
+                self.settle_escrow(escrow.id, buyer, listing.price, now)
+                    .await;
+                Err(NamingError::RemoteError(e))
+            }
+        }
+    }
+
+    pub fn get_offer(&self, id: OfferId) -> ServiceResult<Offer> {
+        STATE.with(|s| {
+            let store = s.offer_store.borrow();
+            store.get_offer(id).ok_or(NamingError::OfferNotFound { id })
+        })
+    }
+
+    pub fn get_offers_of_name(&self, name: &str) -> ServiceResult<Vec<Offer>> {
+        let name = normalize_name(name);
+        STATE.with(|s| {
+            let store = s.offer_store.borrow();
+            Ok(store
+                .get_offers()
+                .filter(|offer| offer.name == name.0)
+                .collect())
+        })
+    }
+
+    pub fn get_offers_of_buyer(&self, buyer: &Principal) -> ServiceResult<Vec<Offer>> {
+        STATE.with(|s| {
+            let store = s.offer_store.borrow();
+            Ok(store
+                .get_offers()
+                .filter(|offer| offer.buyer == *buyer)
+                .collect())
+        })
+    }
+
+    /// Make an offer on a registered name, the buyer should approve the amount to marketplace in DICP first.
+    /// The amount is held by marketplace until the offer is accepted, or refunded when it is expired, rejected or canceled.
+    pub async fn make_offer(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        amount: u64,
+        duration_in_seconds: u64,
+    ) -> ServiceResult<Offer> {
+        let buyer = call_context.must_not_anonymous()?;
+        if amount == 0 {
+            return Err(NamingError::InvalidOfferAmount);
+        }
+        let max_duration_in_seconds = NAMING_MARKETPLACE_OFFER_MAX_DURATION_NS / 1_000_000_000;
+        if duration_in_seconds == 0 || duration_in_seconds > max_duration_in_seconds {
+            return Err(NamingError::ValueShouldBeInRangeError {
+                field: "duration_in_seconds".to_string(),
+                min: 1,
+                max: max_duration_in_seconds as usize,
+            });
+        }
+        let name = normalize_name(name).0;
+
+        let owner = self
+            .registrar_api
+            .get_owner(name.clone())
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if owner == buyer.0 {
+            return Err(NamingError::PermissionDenied);
+        }
+
+        let result = self
+            .dicp_api
+            .transfer_from(
+                None,
+                buyer.0.to_text(),
+                get_named_get_canister_id(CanisterNames::NamingMarketplace).to_text(),
+                Nat::from(amount),
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            error!("make_offer: transfer from {} failed: {:?}", buyer, e);
+            return Err(NamingError::RemoteError(e));
+        }
+        let tx_id = result.unwrap().tx_id;
+
+        let now = call_context.now.0;
+        let offer = STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.new_offer(
+                name,
+                buyer.0,
+                amount,
+                tx_id,
+                now,
+                now + duration_in_seconds * 1_000_000_000,
+            )
+        });
+        info!("make_offer: {:?}", offer);
+        Ok(offer)
+    }
+
+    /// Cancel an open offer, only buyer is allowed to call it.
+    pub async fn cancel_offer(
+        &self,
+        call_context: CallContext,
+        id: OfferId,
+    ) -> ServiceResult<bool> {
+        let buyer = call_context.must_not_anonymous()?;
+        let offer = self.get_offer(id)?;
+        if offer.buyer != buyer.0 {
+            return Err(NamingError::OwnerOnly);
+        }
+        if offer.status != OfferStatus::Open {
+            return Err(NamingError::Conflict);
+        }
+
+        info!("cancel_offer: {}", id);
+        self.mark_offer_to_be_refunded(id);
+        self.refund_offer(offer, call_context.now.0).await?;
+        Ok(true)
+    }
+
+    /// Reject an open offer, only owner of the name is allowed to call it.
+    pub async fn reject_offer(
+        &self,
+        call_context: CallContext,
+        id: OfferId,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let offer = self.get_offer(id)?;
+        let owner = self
+            .registrar_api
+            .get_owner(offer.name.clone())
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if owner != caller.0 {
+            return Err(NamingError::OwnerOnly);
+        }
+        // status may be changed during the call to registrar
+        let offer = self.get_offer(id)?;
+        if offer.status != OfferStatus::Open {
+            return Err(NamingError::Conflict);
+        }
+
+        info!("reject_offer: {}", id);
+        self.mark_offer_to_be_refunded(id);
+        self.refund_offer(offer, call_context.now.0).await?;
+        Ok(true)
+    }
+
+    /// Accept an open offer, only owner of the name is allowed to call it,
+    /// and the owner should approve the name to marketplace in registrar first.
+    /// The amount is paid to the owner after the name is transferred to the buyer,
+    /// the offer is kept open if the transfer failed.
+    pub async fn accept_offer(
+        &self,
+        call_context: CallContext,
+        id: OfferId,
+    ) -> ServiceResult<bool> {
+        let seller = call_context.must_not_anonymous()?;
+        let offer = self.get_offer(id)?;
+
+        try_lock_listing(&offer.name)?;
+        let result = self
+            .accept_offer_core(seller.0, id, call_context.now.0)
+            .await;
+        unlock_listing(&offer.name);
+        result
+    }
+
+    async fn accept_offer_core(
+        &self,
+        seller: Principal,
+        id: OfferId,
+        now: u64,
+    ) -> ServiceResult<bool> {
+        let offer = self.get_offer(id)?;
+        let owner = self
+            .registrar_api
+            .get_owner(offer.name.clone())
+            .await
+            .map_err(NamingError::RemoteError)?;
+        if owner != seller {
+            return Err(NamingError::OwnerOnly);
+        }
+        // status may be changed during the call to registrar
+        let offer = self.get_offer(id)?;
+        if offer.status != OfferStatus::Open {
+            return Err(NamingError::Conflict);
+        }
+        if offer.is_expired(now) {
+            return Err(NamingError::OfferExpired);
+        }
+
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.mark_accepting(id, seller, now);
+        });
+        let result = self
+            .registrar_api
+            .transfer_from(offer.name.clone(), Some(offer.buyer))
+            .await;
+        match result {
+            Ok(true) => {
+                self.complete_offer(offer, seller, now).await;
+                Ok(true)
+            }
+            Ok(false) => {
+                error!(
+                    "accept_offer: transfer of {} is rejected by registrar",
+                    offer.name
+                );
+                self.mark_offer_open(id);
+                Err(NamingError::PermissionDenied)
+            }
+            Err(e) => {
+                error!("accept_offer: transfer of {} failed: {:?}", offer.name, e);
+                self.mark_offer_open(id);
+                Err(NamingError::RemoteError(e))
+            }
+        }
+    }
+
+    /// The name is transferred to the buyer, the offer is turned into an escrow to be paid to the seller
+    async fn complete_offer(&self, offer: Offer, seller: Principal, now: u64) {
+        let escrow = STATE.with(|s| {
+            let mut offer_store = s.offer_store.borrow_mut();
+            offer_store.remove_offer(offer.id);
+            let mut listing_store = s.listing_store.borrow_mut();
+            listing_store.remove_listing(&offer.name);
+            let mut escrow_store = s.escrow_store.borrow_mut();
+            let escrow = escrow_store.add_escrow(
+                offer.name.clone(),
+                seller,
+                offer.buyer,
+                offer.amount,
+                offer.tx_id.clone(),
+                now,
+            );
+            escrow_store.set_status(escrow.id, EscrowStatus::Paying);
+            escrow
+        });
+        info!(
+            "complete_offer: {} is sold to {} by offer {}",
+            offer.name, offer.buyer, offer.id
+        );
+        self.settle_escrow(escrow.id, seller, escrow.amount, now)
+            .await;
+    }
+
+    /// Refund offers expired, rejected or canceled, only timer trigger is allowed to call it.
+    pub async fn refund_offers(&self, call_context: CallContext) -> ServiceResult<bool> {
+        call_context.must_be_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER)?;
+        let now = call_context.now;
+        if !try_lock_with_timeout(LockId::MarketplaceRefundOffer, now) {
+            debug!("refund_offers: already locked");
+            return Ok(false);
+        }
+        // offers accepting for a while, the result of the name transfer is unknown
+        let accepting_offers: Vec<Offer> = STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            let count = store.mark_expired_to_be_refunded(now.0);
+            debug!("refund_offers: {} offers expired", count);
+            store
+                .get_offers()
+                .filter(|offer| {
+                    offer.status == OfferStatus::Accepting
+                        && offer.accepted_at.unwrap_or_default() + NAMING_MARKETPLACE_RETRY_DELAY_NS
+                            <= now.0
+                })
+                .collect()
+        });
+        for offer in accepting_offers {
+            self.resolve_accepting(offer, now.0).await;
+        }
+
+        let max_refund_count = 10;
+        let offers = STATE.with(|s| {
+            let store = s.offer_store.borrow();
+            store.get_to_be_refunded_offers(max_refund_count)
+        });
+        debug!("refund_offers: {} offers to be refunded", offers.len());
+        for offer in offers {
+            let _ = self.refund_offer(offer, now.0).await;
+        }
+        release_timeout_locker(LockId::MarketplaceRefundOffer);
+        Ok(true)
+    }
+
+    /// Result of the name transfer is unknown, decide it by current owner of the name
+    async fn resolve_accepting(&self, offer: Offer, now: u64) {
+        let result = self.registrar_api.get_owner(offer.name.clone()).await;
+        match (result, offer.accepted_by) {
+            (Ok(owner), Some(seller)) if owner == offer.buyer => {
+                self.complete_offer(offer, seller, now).await;
+            }
+            (Ok(_), _) => {
+                self.mark_offer_open(offer.id);
+            }
+            (Err(e), _) => {
+                error!(
+                    "resolve_accepting: get owner of {} failed: {:?}",
+                    offer.name, e
+                );
+            }
+        }
+    }
+
+    fn mark_offer_open(&self, id: OfferId) {
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.mark_open(id);
+        });
+    }
+
+    fn mark_offer_to_be_refunded(&self, id: OfferId) {
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.mark_to_be_refunded(id);
+        });
+    }
+
+    /// Refund the offer, `created_at_time` of the last unknown refund is reused,
+    /// so that the buyer is not refunded twice.
+    async fn refund_offer(&self, offer: Offer, now: u64) -> ServiceResult<()> {
+        let created_at_time = offer.refund_created_at.unwrap_or(now);
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.mark_refunding(offer.id, created_at_time);
+        });
+        let result = self
+            .payout(
+                offer.buyer,
+                offer.amount,
+                format!("offer:{}", offer.id),
+                created_at_time,
+            )
+            .await;
+        match result {
+            PayoutResult::Paid => {
+                debug!("refund_offer: refunded {}", offer.id);
+                STATE.with(|s| {
+                    let mut store = s.offer_store.borrow_mut();
+                    store.remove_offer(offer.id);
+                });
+                Ok(())
+            }
+            PayoutResult::Failed(e) => {
+                error!("refund_offer: failed to refund {}: {:?}", offer.id, e);
+                STATE.with(|s| {
+                    let mut store = s.offer_store.borrow_mut();
+                    store.mark_to_be_refunded(offer.id);
+                    store.reset_refund_created_at(offer.id);
+                });
+                Err(e)
+            }
+            PayoutResult::Unknown(e) => {
+                error!("refund_offer: refund of {} is unknown: {:?}", offer.id, e);
+                self.mark_offer_to_be_refunded(offer.id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Retry to settle escrows which are failed to pay or refund, only timer trigger is allowed to call it.
+    pub async fn retry_escrows(&self, call_context: CallContext) -> ServiceResult<bool> {
+        call_context.must_be_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER)?;
+        let now = call_context.now;
+        if !try_lock_with_timeout(LockId::MarketplaceRetryEscrow, now) {
+            debug!("retry_escrows: already locked");
+            return Ok(false);
+        }
+        // skip escrows just created, they may be settling by buy
+        let escrows: Vec<Escrow> = STATE.with(|s| {
+            let store = s.escrow_store.borrow();
+            store
+                .get_escrows()
+                .filter(|escrow| escrow.created_at + NAMING_MARKETPLACE_RETRY_DELAY_NS <= now.0)
+                .collect()
+        });
+        debug!("retry_escrows: {} escrows", escrows.len());
+        for escrow in escrows {
+            let status = match escrow.status {
+                EscrowStatus::Transferring => self.resolve_transferring(&escrow).await,
+                status => Some(status),
+            };
+            match status {
+                Some(EscrowStatus::Paying) => {
+                    self.settle_escrow(escrow.id, escrow.seller, escrow.amount, now.0)
+                        .await
+                }
+                Some(EscrowStatus::Refunding) => {
+                    self.settle_escrow(escrow.id, escrow.buyer, escrow.amount, now.0)
+                        .await
+                }
+                _ => {}
+            }
+        }
+        release_timeout_locker(LockId::MarketplaceRetryEscrow);
+        Ok(true)
+    }
+
+    /// Result of the name transfer is unknown, decide it by current owner of the name
+    async fn resolve_transferring(&self, escrow: &Escrow) -> Option<EscrowStatus> {
+        let result = self.registrar_api.get_owner(escrow.name.clone()).await;
+        let status = match result {
+            Ok(owner) if owner == escrow.buyer => EscrowStatus::Paying,
+            Ok(_) => EscrowStatus::Refunding,
+            Err(e) => {
+                error!(
+                    "resolve_transferring: get owner of {} failed: {:?}",
+                    escrow.name, e
+                );
+                return None;
+            }
+        };
+        if status == EscrowStatus::Paying {
+            STATE.with(|s| {
+                let mut store = s.listing_store.borrow_mut();
+                store.remove_listing(&escrow.name);
+            });
+        }
+        self.set_escrow_status(escrow.id, status);
+        Some(status)
+    }
+
+    fn set_escrow_status(&self, id: u64, status: EscrowStatus) {
+        STATE.with(|s| {
+            let mut store = s.escrow_store.borrow_mut();
+            store.set_status(id, status);
+        });
+    }
+
+    /// Pay the escrow to the receiver, it is removed if success.
+    /// `created_at_time` of the last unknown payout is reused, so that it is not paid twice.
+    async fn settle_escrow(&self, id: u64, to: Principal, amount: u64, now: u64) {
+        let created_at_time = STATE.with(|s| {
+            let mut store = s.escrow_store.borrow_mut();
+            let created_at_time = store
+                .get_escrow(id)
+                .and_then(|escrow| escrow.payout_created_at)
+                .unwrap_or(now);
+            store.set_payout_created_at(id, Some(created_at_time));
+            created_at_time
+        });
+        let result = self
+            .payout(to, amount, format!("escrow:{}", id), created_at_time)
+            .await;
+        match result {
+            PayoutResult::Paid => {
+                STATE.with(|s| {
+                    let mut store = s.escrow_store.borrow_mut();
+                    store.remove_escrow(id);
+                });
+                info!("settle_escrow: escrow {} is paid to {}", id, to);
+            }
+            PayoutResult::Failed(e) => {
+                error!("settle_escrow: escrow {} failed: {:?}", id, e);
+                STATE.with(|s| {
+                    let mut store = s.escrow_store.borrow_mut();
+                    store.set_payout_created_at(id, None);
+                });
+            }
+            PayoutResult::Unknown(e) => {
+                error!("settle_escrow: escrow {} is unknown: {:?}", id, e);
+            }
+        }
+    }
+
+    /// Transfer DICP from marketplace, `memo` and `created_at_time` make the transfer deduplicated by the ledger.
+    /// The ledger fee is paid from `amount`, since the marketplace only holds `amount` for each escrow.
+    async fn payout(
+        &self,
+        to: Principal,
+        amount: u64,
+        memo: String,
+        created_at_time: u64,
+    ) -> PayoutResult {
+        let ledger = get_named_get_canister_id(CanisterNames::DICP);
+        let fee = match self.icrc_ledger_api.icrc1_fee(ledger).await {
+            Ok(fee) => fee,
+            // nothing is transferred without the fee
+            Err(e) => return PayoutResult::Failed(NamingError::RemoteError(e)),
+        };
+        let amount = Nat::from(amount);
+        if amount <= fee {
+            warn!(
+                "payout: {} to {} is not enough to pay fee {}",
+                amount, to, fee
+            );
+            return PayoutResult::Paid;
+        }
+        let result = self
+            .icrc_ledger_api
+            .icrc1_transfer(
+                ledger,
+                Icrc1TransferArg {
+                    from_subaccount: None,
+                    to: Account::new(to),
+                    amount: amount - fee.clone(),
+                    fee: Some(fee),
+                    memo: Some(ByteBuf::from(memo.into_bytes())),
+                    created_at_time: Some(created_at_time),
+                },
+            )
+            .await;
+        match result {
+            Ok(Ok(_)) => PayoutResult::Paid,
+            // it has been transferred by a previous try whose response is lost
+            Ok(Err(Icrc1TransferError::Duplicate { .. })) => PayoutResult::Paid,
+            // the previous try is out of the deduplication window of the ledger,
+            // it should be checked manually since it is not known whether it is transferred
+            Ok(Err(Icrc1TransferError::TooOld)) => {
+                PayoutResult::Unknown(NamingError::LedgerTransferFailed {
+                    reason: format!("{:?}", Icrc1TransferError::TooOld),
+                })
+            }
+            Ok(Err(e)) => PayoutResult::Failed(NamingError::LedgerTransferFailed {
+                reason: format!("{:?}", e),
+            }),
+            Err(e) => PayoutResult::Unknown(NamingError::RemoteError(e)),
+        }
+    }
+}
@@ -0,0 +1,1124 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use candid::Principal;
+use num_traits::ToPrimitive;
+use rstest::*;
+
+use common::canister_api::TransactionResponse;
+use common::errors::ErrorInfo;
+use common::icrc::Icrc1TransferError;
+use common::named_principals::NAME_DPRINCIPALS;
+use common::TimeInNs;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use super::*;
+
+const NAME: &str = "hello.ic";
+const PRICE: u64 = 100_000_000;
+const FEE: u64 = 10_000;
+
+fn set_named_principal(name: &'static str, user: Principal) {
+    NAME_DPRINCIPALS.with(|m| {
+        let mut m = m.borrow_mut();
+        let mut set = HashSet::new();
+        set.insert(user);
+        m.principals.insert(name, set);
+    });
+}
+
+fn create_service(
+    registrar_api: MockRegistrarApi,
+    dicp_api: MockDICPApi,
+    mut icrc_ledger_api: MockIcrcLedgerApi,
+) -> MarketplaceService {
+    icrc_ledger_api
+        .expect_icrc1_fee()
+        .returning(|_| Ok(Nat::from(FEE)));
+    MarketplaceService {
+        registrar_api: Arc::new(registrar_api),
+        dicp_api: Arc::new(dicp_api),
+        icrc_ledger_api: Arc::new(icrc_ledger_api),
+    }
+}
+
+fn tx_response() -> TransactionResponse {
+    TransactionResponse {
+        tx_id: "1".to_string(),
+    }
+}
+
+fn add_listing(name: &str, seller: Principal, now: u64) {
+    STATE.with(|s| {
+        let mut store = s.listing_store.borrow_mut();
+        store.add_listing(Listing {
+            name: name.to_string(),
+            seller,
+            price: PRICE,
+            created_at: now,
+            updated_at: now,
+        });
+    });
+}
+
+fn add_escrow(seller: Principal, buyer: Principal, status: EscrowStatus, now: u64) -> u64 {
+    STATE.with(|s| {
+        let mut store = s.escrow_store.borrow_mut();
+        let escrow = store.add_escrow(NAME.to_string(), seller, buyer, PRICE, "1".to_string(), now);
+        store.set_status(escrow.id, status);
+        escrow.id
+    })
+}
+
+fn get_escrow(id: u64) -> Option<Escrow> {
+    STATE.with(|s| {
+        let store = s.escrow_store.borrow();
+        store.get_escrow(id)
+    })
+}
+
+#[fixture]
+fn listed(_init_test: (), mock_user1: Principal, mock_now: u64) {
+    add_listing(NAME, mock_user1, mock_now);
+}
+
+mod list {
+    use super::*;
+
+    #[rstest]
+    async fn test_list(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api
+            .expect_is_approved_to()
+            .times(1)
+            .returning(|name, spender| {
+                assert_eq!(name, NAME);
+                assert_eq!(
+                    spender,
+                    get_named_get_canister_id(CanisterNames::NamingMarketplace)
+                );
+                Ok(true)
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .list(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                "Hello.IC",
+                PRICE,
+            )
+            .await;
+
+        let listing = Listing {
+            name: NAME.to_string(),
+            seller: mock_user1,
+            price: PRICE,
+            created_at: mock_now,
+            updated_at: mock_now,
+        };
+        assert_eq!(result, Ok(listing.clone()));
+        assert_eq!(service.get_listing(NAME), Ok(listing));
+    }
+
+    #[rstest]
+    async fn test_list_not_owner(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user2));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .list(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::OwnerOnly));
+        assert!(service.get_listing(NAME).is_err());
+    }
+
+    #[rstest]
+    async fn test_list_not_approved(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api
+            .expect_is_approved_to()
+            .returning(|_, _| Ok(false));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .list(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            Err(NamingError::ListingNotApproved {
+                name: NAME.to_string()
+            })
+        );
+        assert!(service.get_listing(NAME).is_err());
+    }
+
+    #[rstest]
+    async fn test_list_zero_price(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api.expect_get_owner().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .list(CallContext::new(mock_user1, TimeInNs(mock_now)), NAME, 0)
+            .await;
+
+        assert_eq!(result, Err(NamingError::InvalidListingPrice));
+    }
+}
+
+mod update_and_delist {
+    use super::*;
+
+    #[rstest]
+    fn test_update_price(
+        _listed: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .update_price(
+                CallContext::new(mock_user1, TimeInNs(mock_now + 1)),
+                NAME,
+                2,
+            )
+            .unwrap();
+
+        assert_eq!(result.price, 2);
+        assert_eq!(result.created_at, mock_now);
+        assert_eq!(result.updated_at, mock_now + 1);
+    }
+
+    #[rstest]
+    fn test_update_price_not_seller(
+        _listed: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result =
+            service.update_price(CallContext::new(mock_user2, TimeInNs(mock_now)), NAME, 2);
+
+        assert_eq!(result, Err(NamingError::OwnerOnly));
+        assert_eq!(service.get_listing(NAME).unwrap().price, PRICE);
+    }
+
+    #[rstest]
+    fn test_delist(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service.delist(CallContext::new(mock_user2, TimeInNs(mock_now)), NAME);
+        assert_eq!(result, Err(NamingError::OwnerOnly));
+
+        let result = service.delist(CallContext::new(mock_user1, TimeInNs(mock_now)), NAME);
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            service.get_listing(NAME),
+            Err(NamingError::ListingNotFound {
+                name: NAME.to_string()
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_get_listings(
+        _listed: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        add_listing("app.ic", mock_user1, mock_now);
+        add_listing("world.ic", mock_user1, mock_now);
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .get_listings(GetPageInput {
+                offset: 1,
+                limit: 2,
+            })
+            .unwrap();
+
+        let names: Vec<String> = result.items.into_iter().map(|item| item.name).collect();
+        assert_eq!(names, vec![NAME.to_string(), "world.ic".to_string()]);
+    }
+}
+
+mod buy {
+    use super::*;
+
+    #[rstest]
+    async fn test_buy(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |_, from, to, value, _| {
+                assert_eq!(from, mock_user2.to_text());
+                assert_eq!(
+                    to,
+                    get_named_get_canister_id(CanisterNames::NamingMarketplace).to_text()
+                );
+                assert_eq!(value, Nat::from(PRICE));
+                Ok(tx_response())
+            });
+        mock_registrar_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |name, to| {
+                assert_eq!(name, NAME);
+                assert_eq!(to, Some(mock_user2));
+                Ok(true)
+            });
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user1));
+                assert_eq!(arg.amount, Nat::from(PRICE - FEE));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert!(service.get_listing(NAME).is_err());
+        assert_eq!(service.get_escrows(), Ok(vec![]));
+    }
+
+    #[rstest]
+    async fn test_buy_own_listing(
+        _listed: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api.expect_get_owner().never();
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    async fn test_buy_price_changed(
+        _listed: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api.expect_get_owner().never();
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE - 1,
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            Err(NamingError::ListingPriceChanged {
+                name: NAME.to_string(),
+                price: PRICE,
+            })
+        );
+        assert!(service.get_listing(NAME).is_ok());
+    }
+
+    #[rstest]
+    async fn test_buy_stale_listing(
+        _listed: (),
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user3));
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            Err(NamingError::ListingNotFound {
+                name: NAME.to_string()
+            })
+        );
+        assert!(service.get_listing(NAME).is_err());
+    }
+
+    #[rstest]
+    async fn test_buy_payment_failed(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        mock_registrar_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert!(service.get_listing(NAME).is_ok());
+        assert_eq!(service.get_escrows(), Ok(vec![]));
+    }
+
+    #[rstest]
+    async fn test_buy_name_transfer_failed(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| Ok(tx_response()));
+        mock_registrar_api
+            .expect_transfer_from()
+            .returning(|_, _| Err(ErrorInfo::from(NamingError::PermissionDenied)));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user2));
+                assert_eq!(arg.amount, Nat::from(PRICE - FEE));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert!(service.get_listing(NAME).is_ok());
+        assert_eq!(service.get_escrows(), Ok(vec![]));
+    }
+
+    #[rstest]
+    async fn test_buy_refund_failed(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| Ok(tx_response()));
+        mock_registrar_api
+            .expect_transfer_from()
+            .returning(|_, _| Ok(false));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .returning(|_, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .buy(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                PRICE,
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+        let escrow = get_escrow(1).unwrap();
+        assert_eq!(escrow.status, EscrowStatus::Refunding);
+        assert_eq!(escrow.buyer, mock_user2);
+        assert_eq!(escrow.amount, PRICE);
+    }
+}
+
+mod retry_escrows {
+    use common::constants::NAMING_MARKETPLACE_RETRY_DELAY_NS;
+
+    use super::*;
+
+    #[rstest]
+    async fn test_retry_escrows(
+        _listed: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        let created_at = mock_now - NAMING_MARKETPLACE_RETRY_DELAY_NS;
+        let paying = add_escrow(mock_user1, mock_user2, EscrowStatus::Paying, created_at);
+        let transferring = add_escrow(
+            mock_user1,
+            mock_user2,
+            EscrowStatus::Transferring,
+            created_at,
+        );
+        let just_created = add_escrow(mock_user1, mock_user2, EscrowStatus::Paying, mock_now);
+        mock_registrar_api
+            .expect_get_owner()
+            .times(1)
+            .returning(move |_| Ok(mock_user2));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(2)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user1));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .retry_escrows(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_escrow(paying), None);
+        assert_eq!(get_escrow(transferring), None);
+        assert!(get_escrow(just_created).is_some());
+        assert!(service.get_listing(NAME).is_err());
+    }
+
+    #[rstest]
+    async fn test_retry_escrows_deduplicated(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        let created_at = mock_now - NAMING_MARKETPLACE_RETRY_DELAY_NS;
+        let paying = add_escrow(mock_user1, mock_user2, EscrowStatus::Paying, created_at);
+        STATE.with(|s| {
+            let mut store = s.escrow_store.borrow_mut();
+            store.set_payout_created_at(paying, Some(created_at));
+        });
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |ledger, arg| {
+                assert_eq!(ledger, get_named_get_canister_id(CanisterNames::DICP));
+                assert_eq!(arg.created_at_time, Some(created_at));
+                assert_eq!(
+                    arg.memo,
+                    Some(ByteBuf::from(format!("escrow:{}", paying).into_bytes()))
+                );
+                Ok(Err(Icrc1TransferError::Duplicate {
+                    duplicate_of: Nat::from(1u64),
+                }))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .retry_escrows(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_escrow(paying), None);
+    }
+
+    #[rstest]
+    async fn test_retry_escrows_payout_unknown(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        let created_at = mock_now - NAMING_MARKETPLACE_RETRY_DELAY_NS;
+        let unknown = add_escrow(mock_user1, mock_user2, EscrowStatus::Paying, created_at);
+        let failed = add_escrow(mock_user1, mock_user2, EscrowStatus::Refunding, created_at);
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(2)
+            .returning(move |_, arg| {
+                if arg.to == Account::new(mock_user1) {
+                    Err(ErrorInfo::from(NamingError::Unknown))
+                } else {
+                    Ok(Err(Icrc1TransferError::TemporarilyUnavailable))
+                }
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .retry_escrows(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            get_escrow(unknown).unwrap().payout_created_at,
+            Some(mock_now)
+        );
+        assert_eq!(get_escrow(failed).unwrap().payout_created_at, None);
+    }
+
+    #[rstest]
+    async fn test_retry_escrows_balance_of_escrows(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        let created_at = mock_now - NAMING_MARKETPLACE_RETRY_DELAY_NS;
+        let escrows: Vec<u64> = (0..3)
+            .map(|_| add_escrow(mock_user1, mock_user2, EscrowStatus::Paying, created_at))
+            .collect();
+        // the marketplace holds nothing but the escrows
+        let balance = Arc::new(Mutex::new(PRICE * escrows.len() as u64));
+        let ledger_balance = balance.clone();
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(escrows.len())
+            .returning(move |_, arg| {
+                let mut balance = ledger_balance.lock().unwrap();
+                let debit = arg.amount + arg.fee.unwrap_or_else(|| Nat::from(0u64));
+                if Nat::from(*balance) < debit {
+                    return Ok(Err(Icrc1TransferError::InsufficientFunds {
+                        balance: Nat::from(*balance),
+                    }));
+                }
+                *balance -= debit.0.to_u64().unwrap();
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .retry_escrows(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        for id in escrows {
+            assert_eq!(get_escrow(id), None);
+        }
+        assert_eq!(*balance.lock().unwrap(), 0);
+    }
+
+    #[rstest]
+    async fn test_retry_escrows_unauthorized(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_icrc_ledger_api.expect_icrc1_transfer().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .retry_escrows(CallContext::new(mock_user1, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+}
+
+mod offer {
+    use common::constants::NAMING_MARKETPLACE_RETRY_DELAY_NS;
+
+    use super::*;
+
+    const AMOUNT: u64 = 50_000_000;
+    const DURATION_IN_SECONDS: u64 = 24 * 60 * 60;
+
+    fn add_offer(buyer: Principal, created_at: u64, expired_at: u64) -> Offer {
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.new_offer(
+                NAME.to_string(),
+                buyer,
+                AMOUNT,
+                "1".to_string(),
+                created_at,
+                expired_at,
+            )
+        })
+    }
+
+    #[fixture]
+    fn offered(_init_test: (), mock_user2: Principal, mock_now: u64) -> Offer {
+        add_offer(
+            mock_user2,
+            mock_now,
+            mock_now + DURATION_IN_SECONDS * 1_000_000_000,
+        )
+    }
+
+    #[rstest]
+    async fn test_make_offer(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |_, from, _, value, _| {
+                assert_eq!(from, mock_user2.to_text());
+                assert_eq!(value, Nat::from(AMOUNT));
+                Ok(tx_response())
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .make_offer(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                AMOUNT,
+                DURATION_IN_SECONDS,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.id, 1);
+        assert_eq!(result.buyer, mock_user2);
+        assert_eq!(result.status, OfferStatus::Open);
+        assert_eq!(
+            result.expired_at,
+            mock_now + DURATION_IN_SECONDS * 1_000_000_000
+        );
+        assert_eq!(service.get_offers_of_name(NAME), Ok(vec![result.clone()]));
+        assert_eq!(service.get_offers_of_buyer(&mock_user2), Ok(vec![result]));
+    }
+
+    #[rstest]
+    async fn test_make_offer_invalid_duration(
+        _init_test: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mut mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api.expect_get_owner().never();
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .make_offer(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                NAME,
+                AMOUNT,
+                31 * 24 * 60 * 60,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(NamingError::ValueShouldBeInRangeError { .. })
+        ));
+    }
+
+    #[rstest]
+    async fn test_accept_offer(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        add_listing(NAME, mock_user1, mock_now);
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |name, to| {
+                assert_eq!(name, NAME);
+                assert_eq!(to, Some(mock_user2));
+                Ok(true)
+            });
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user1));
+                assert_eq!(arg.amount, Nat::from(AMOUNT - FEE));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .accept_offer(CallContext::new(mock_user1, TimeInNs(mock_now)), offered.id)
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            service.get_offer(offered.id),
+            Err(NamingError::OfferNotFound { id: offered.id })
+        );
+        assert!(service.get_listing(NAME).is_err());
+        assert_eq!(service.get_escrows(), Ok(vec![]));
+    }
+
+    #[rstest]
+    async fn test_accept_offer_transfer_failed(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api
+            .expect_transfer_from()
+            .returning(|_, _| Err(ErrorInfo::from(NamingError::PermissionDenied)));
+        mock_icrc_ledger_api.expect_icrc1_transfer().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .accept_offer(CallContext::new(mock_user1, TimeInNs(mock_now)), offered.id)
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert_eq!(service.get_offer(offered.id), Ok(offered));
+    }
+
+    #[rstest]
+    async fn test_accept_offer_not_owner(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .accept_offer(CallContext::new(mock_user3, TimeInNs(mock_now)), offered.id)
+            .await;
+
+        assert_eq!(result, Err(NamingError::OwnerOnly));
+    }
+
+    #[rstest]
+    async fn test_accept_offer_expired(
+        offered: Offer,
+        mock_user1: Principal,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_registrar_api.expect_transfer_from().never();
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .accept_offer(
+                CallContext::new(mock_user1, TimeInNs(offered.expired_at)),
+                offered.id,
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::OfferExpired));
+    }
+
+    #[rstest]
+    async fn test_cancel_offer(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user2));
+                assert_eq!(arg.amount, Nat::from(AMOUNT - FEE));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .cancel_offer(CallContext::new(mock_user1, TimeInNs(mock_now)), offered.id)
+            .await;
+        assert_eq!(result, Err(NamingError::OwnerOnly));
+
+        let result = service
+            .cancel_offer(CallContext::new(mock_user2, TimeInNs(mock_now)), offered.id)
+            .await;
+        assert_eq!(result, Ok(true));
+        assert!(service.get_offer(offered.id).is_err());
+    }
+
+    #[rstest]
+    async fn test_reject_offer_refund_failed(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .returning(|_, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .reject_offer(CallContext::new(mock_user1, TimeInNs(mock_now)), offered.id)
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        let offer = service.get_offer(offered.id).unwrap();
+        assert_eq!(offer.status, OfferStatus::ToBeRefunded);
+        // the refund may be done, it is retried with the same created_at_time
+        assert_eq!(offer.refund_created_at, Some(mock_now));
+    }
+
+    #[rstest]
+    async fn test_reject_offer_refund_rejected_by_ledger(
+        offered: Offer,
+        mock_user1: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        mock_registrar_api
+            .expect_get_owner()
+            .returning(move |_| Ok(mock_user1));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .returning(|_, _| Ok(Err(Icrc1TransferError::TemporarilyUnavailable)));
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .reject_offer(CallContext::new(mock_user1, TimeInNs(mock_now)), offered.id)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(NamingError::LedgerTransferFailed { .. })
+        ));
+        let offer = service.get_offer(offered.id).unwrap();
+        assert_eq!(offer.status, OfferStatus::ToBeRefunded);
+        assert_eq!(offer.refund_created_at, None);
+    }
+
+    #[rstest]
+    async fn test_refund_offers(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mut mock_registrar_api: MockRegistrarApi,
+        mock_dicp_api: MockDICPApi,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        let expired = add_offer(mock_user2, mock_now - 1, mock_now);
+        let open = add_offer(mock_user2, mock_now, mock_now + 1);
+        let accepting = add_offer(mock_user2, mock_now - 1, mock_now + 1);
+        STATE.with(|s| {
+            let mut store = s.offer_store.borrow_mut();
+            store.mark_accepting(
+                accepting.id,
+                mock_user1,
+                mock_now - NAMING_MARKETPLACE_RETRY_DELAY_NS,
+            );
+        });
+        mock_registrar_api
+            .expect_get_owner()
+            .times(1)
+            .returning(move |_| Ok(mock_user1));
+        mock_icrc_ledger_api
+            .expect_icrc1_transfer()
+            .times(1)
+            .returning(move |_, arg| {
+                assert_eq!(arg.to, Account::new(mock_user2));
+                Ok(Ok(Nat::from(1u64)))
+            });
+        let service = create_service(mock_registrar_api, mock_dicp_api, mock_icrc_ledger_api);
+
+        let result = service
+            .refund_offers(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert!(service.get_offer(expired.id).is_err());
+        assert_eq!(service.get_offer(open.id), Ok(open));
+        assert_eq!(service.get_offer(accepting.id), Ok(accepting));
+    }
+}
@@ -0,0 +1,150 @@
+use candid::{CandidType, Deserialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Once;
+
+use candid::{candid_method, decode_args, encode_args, Principal};
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use log::info;
+
+use common::ic_logger::ICLogger;
+use common::named_canister_ids::{
+    ensure_current_canister_id_match, update_dev_named_canister_ids, CanisterNames,
+};
+use common::stable_memory::MemoryId;
+use common::state::{
+    encode_entries, restore_state, save_state, StableEntries, StableMemoryState, StableState,
+};
+
+use crate::escrow_store::EscrowStore;
+use crate::listing_locker::ListingLocker;
+use crate::listing_store::ListingStore;
+use crate::offer_store::OfferStore;
+
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const LISTINGS_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub const ESCROWS_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const ESCROW_NEXT_ID_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const OFFERS_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const LAST_OFFER_ID_MEMORY_ID: MemoryId = MemoryId::new(5);
+
+thread_local! {
+    pub static STATE : State = State::default();
+    pub static LISTING_LOCKER: RefCell<ListingLocker> = RefCell::new(ListingLocker::new());
+}
+
+#[derive(Default)]
+pub struct State {
+    // NOTE: When adding new persistent fields here, ensure that these fields
+    // are being persisted in the `replace` method below.
+    pub(crate) listing_store: RefCell<ListingStore>,
+    pub(crate) escrow_store: RefCell<EscrowStore>,
+    pub(crate) offer_store: RefCell<OfferStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    listing_entries: StableEntries<ListingStore>,
+    escrow_entries: StableEntries<EscrowStore>,
+    offer_entries: StableEntries<OfferStore>,
+}
+
+impl State {
+    pub fn replace(&self, new_state: State) {
+        new_state.listing_entries.import_into(&self.listing_store);
+        new_state.escrow_entries.import_into(&self.escrow_store);
+        new_state.offer_entries.import_into(&self.offer_store);
+    }
+}
+
+impl StableState for State {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((
+            encode_entries(&self.listing_store),
+            encode_entries(&self.escrow_store),
+            encode_entries(&self.offer_store),
+        ))
+        .unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (listing_store_bytes, escrow_store_bytes, offer_store_bytes): (
+            Vec<u8>,
+            Vec<u8>,
+            Vec<u8>,
+        ) = decode_args(&bytes).map_err(|e| e.to_string())?;
+
+        Ok(State {
+            listing_entries: StableEntries::decode(listing_store_bytes)?,
+            escrow_entries: StableEntries::decode(escrow_store_bytes)?,
+            offer_entries: StableEntries::decode(offer_store_bytes)?,
+            ..State::default()
+        })
+    }
+}
+
+/// All stores are kept in stable memory, nothing is saved with the heap state
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args(()).unwrap()
+    }
+
+    fn decode_heap(_bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(State::default())
+    }
+}
+
+static INIT: Once = Once::new();
+
+fn guard_func() -> Result<(), String> {
+    INIT.call_once(|| {
+        ICLogger::init("naming_marketplace");
+    });
+    ensure_current_canister_id_match(CanisterNames::NamingMarketplace)
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    dev_named_canister_ids: HashMap<CanisterNames, Principal>,
+}
+
+#[init]
+#[candid_method(init)]
+#[cfg(feature = "dev_env")]
+fn init_function(args: Option<InitArgs>) {
+    info!("init function called");
+    if let Some(args) = args {
+        update_dev_named_canister_ids(&args.dev_named_canister_ids);
+    }
+
+    guard_func().unwrap();
+}
+
+#[init]
+#[candid_method(init)]
+#[cfg(not(feature = "dev_env"))]
+fn init_function() {
+    info!("init function called");
+    guard_func().unwrap();
+}
+
+#[pre_upgrade(guard = "guard_func")]
+fn pre_upgrade() {
+    STATE.with(|s| {
+        save_state(s);
+        info!("Saved state before upgrade");
+    });
+}
+
+#[post_upgrade(guard = "guard_func")]
+fn post_upgrade() {
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
+            s.replace(new_state);
+            info!("Loaded state after upgrade");
+        }),
+        Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
+    }
+}
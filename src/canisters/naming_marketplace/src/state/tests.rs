@@ -0,0 +1,79 @@
+use rstest::*;
+
+use test_common::user::*;
+
+use super::*;
+use crate::escrow_store::EscrowStatus;
+use crate::listing_store::Listing;
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_user2: Principal, mock_now: u64) {
+    STATE.with(|s| {
+        s.listing_store.borrow_mut().add_listing(Listing {
+            name: "nice.ic".to_string(),
+            seller: mock_user1,
+            price: 100,
+            created_at: mock_now,
+            updated_at: mock_now,
+        });
+        let mut escrow_store = s.escrow_store.borrow_mut();
+        let escrow = escrow_store.add_escrow(
+            "nice.ic".to_string(),
+            mock_user1,
+            mock_user2,
+            100,
+            "1".to_string(),
+            mock_now,
+        );
+        escrow_store.set_status(escrow.id, EscrowStatus::Paying);
+        s.offer_store.borrow_mut().new_offer(
+            "good.ic".to_string(),
+            mock_user2,
+            100,
+            "2".to_string(),
+            mock_now,
+            mock_now + 1,
+        );
+    });
+
+    pre_upgrade();
+    post_upgrade();
+
+    STATE.with(|s| {
+        assert_eq!(
+            s.listing_store
+                .borrow()
+                .get_listing("nice.ic")
+                .unwrap()
+                .seller,
+            mock_user1
+        );
+        let mut escrow_store = s.escrow_store.borrow_mut();
+        assert_eq!(
+            escrow_store.get_escrow(1).unwrap().status,
+            EscrowStatus::Paying
+        );
+        let mut offer_store = s.offer_store.borrow_mut();
+        assert_eq!(offer_store.get_offer(1).unwrap().name, "good.ic");
+
+        // ids are not reused after upgrade
+        let escrow = escrow_store.add_escrow(
+            "nice.ic".to_string(),
+            mock_user1,
+            mock_user2,
+            100,
+            "3".to_string(),
+            mock_now,
+        );
+        assert_eq!(escrow.id, 2);
+        let offer = offer_store.new_offer(
+            "good.ic".to_string(),
+            mock_user2,
+            100,
+            "4".to_string(),
+            mock_now,
+            mock_now + 1,
+        );
+        assert_eq!(offer.id, 2);
+    });
+}
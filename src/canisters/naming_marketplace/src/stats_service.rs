@@ -0,0 +1,67 @@
+use crate::state::{LISTING_LOCKER, STATE};
+use candid::{CandidType, Deserialize};
+use common::metrics_encoder::MetricsEncoder;
+use ic_cdk::api;
+
+#[derive(Default)]
+pub struct StatsService {}
+
+impl StatsService {
+    pub fn get_stats(&self, _now: u64) -> Stats {
+        let mut stats = Stats::default();
+        stats.cycles_balance = api::canister_balance();
+        STATE.with(|s| {
+            let listing_store = s.listing_store.borrow();
+            stats.listing_count = listing_store.get_listing_count();
+            let escrow_store = s.escrow_store.borrow();
+            stats.escrow_count = escrow_store.get_escrow_count();
+            let offer_store = s.offer_store.borrow();
+            stats.offer_count = offer_store.get_offer_count();
+        });
+        LISTING_LOCKER.with(|locker| {
+            stats.listing_lock_count = locker.borrow().get_count() as u64;
+        });
+        stats
+    }
+}
+
+pub fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>, now: u64) -> std::io::Result<()> {
+    let service = StatsService::default();
+    let stats = service.get_stats(now);
+    w.encode_gauge(
+        "icnaming_naming_marketplace_cycles_balance",
+        stats.cycles_balance as f64,
+        "Balance in cycles",
+    )?;
+    w.encode_gauge(
+        "icnaming_naming_marketplace_listing_count",
+        stats.listing_count as f64,
+        "Number of listings",
+    )?;
+    w.encode_gauge(
+        "icnaming_naming_marketplace_escrow_count",
+        stats.escrow_count as f64,
+        "Number of unsettled escrows",
+    )?;
+    w.encode_gauge(
+        "icnaming_naming_marketplace_offer_count",
+        stats.offer_count as f64,
+        "Number of offers not accepted or refunded",
+    )?;
+    w.encode_gauge(
+        "icnaming_naming_marketplace_listing_lock_count",
+        stats.listing_lock_count as f64,
+        "Number of listings being sold",
+    )?;
+
+    Ok(())
+}
+
+#[derive(CandidType, Deserialize, Default)]
+pub struct Stats {
+    cycles_balance: u64,
+    listing_count: u64,
+    escrow_count: u64,
+    offer_count: u64,
+    listing_lock_count: u64,
+}
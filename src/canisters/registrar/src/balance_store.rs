@@ -1,4 +1,4 @@
-use candid::{decode_args, encode_args, Nat};
+use candid::{decode_args, encode_args, Nat, Principal};
 use candid::{CandidType, Deserialize};
 use common::state::StableState;
 use common::TimeInNs;
@@ -21,6 +21,8 @@ pub struct TokenTransaction {
     value: Nat,
     created_at: TimeInNs,
     status: TokenTransactionStatus,
+    /// ICRC ledger the token is paid in, DICP if not set
+    ledger: Option<Principal>,
 }
 
 impl TokenTransaction {
@@ -39,6 +41,9 @@ impl TokenTransaction {
     pub fn status(&self) -> TokenTransactionStatus {
         self.status
     }
+    pub fn ledger(&self) -> Option<Principal> {
+        self.ledger
+    }
 }
 
 #[derive(Default)]
@@ -72,6 +77,7 @@ impl BalanceStore {
         user: String,
         now: TimeInNs,
         value: Nat,
+        ledger: Option<Principal>,
     ) {
         assert_eq!(self.get_next_transaction_id(), local_tx_id);
         self.last_transaction_id = local_tx_id;
@@ -83,6 +89,7 @@ impl BalanceStore {
                 value,
                 created_at: now,
                 status: TokenTransactionStatus::New,
+                ledger,
             },
         );
     }
@@ -0,0 +1,193 @@
+use candid::{CandidType, Deserialize, Principal};
+
+use common::errors::{NamingError, ServiceResult};
+use common::icrc::Value;
+use common::icrc3::{hash_value, new_block, Hash};
+use common::stable_memory::{StableMap, StableValue};
+
+use crate::state::{BLOCK_ARCHIVES_MEMORY_ID, BLOCK_LOG_MEMORY_ID};
+
+#[cfg(test)]
+mod tests;
+
+/// Blocks from `start` moved to an archive canister
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Archive {
+    pub canister_id: Principal,
+    pub start: u64,
+    pub length: u64,
+    /// Time when the archive rejected blocks since it is full, None if it still takes blocks
+    pub full_at: Option<u64>,
+}
+
+/// Append-only log of ICRC-3 blocks, keyed by block index.
+/// Blocks are kept in stable memory, so that they are not serialized on upgrade.
+///
+/// Old blocks are moved to archive canisters, the archives take consecutive ranges of blocks
+/// from index 0, and the rest of the blocks are kept in the log.
+pub struct BlockLog {
+    blocks: StableMap<u64, Value>,
+    archives: StableValue<Vec<Archive>>,
+}
+
+impl Default for BlockLog {
+    fn default() -> Self {
+        BlockLog {
+            blocks: StableMap::init(BLOCK_LOG_MEMORY_ID),
+            archives: StableValue::init(BLOCK_ARCHIVES_MEMORY_ID),
+        }
+    }
+}
+
+impl BlockLog {
+    /// Append a block chained to the last one, returns the index and the hash of the new block
+    pub fn append(&mut self, btype: &str, ts: u64, tx: Vec<(String, Value)>) -> (u64, Hash) {
+        let parent_hash = self.get_tip().map(|(_, hash)| hash);
+        let block = new_block(parent_hash, btype, ts, tx);
+        let hash = hash_value(&block);
+        let index = self.len();
+        self.blocks.insert(index, block);
+        (index, hash)
+    }
+
+    /// Number of blocks including archived blocks
+    pub fn len(&self) -> u64 {
+        self.get_archived_end() + self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of blocks kept in the log
+    pub fn get_unarchived_count(&self) -> u64 {
+        self.blocks.len()
+    }
+
+    /// Index after the last archived block, it is the index of the first block kept in the log
+    pub fn get_archived_end(&self) -> u64 {
+        self.archives
+            .get()
+            .last()
+            .map(|archive| archive.start + archive.length)
+            .unwrap_or(0)
+    }
+
+    /// Block kept in the log, None if the block is archived
+    pub fn get_block(&self, index: u64) -> Option<Value> {
+        self.blocks.get(&index)
+    }
+
+    /// Blocks kept in the log from `start`, at most `length` blocks are returned
+    pub fn get_blocks(&self, start: u64, length: u64) -> impl Iterator<Item = (u64, Value)> + '_ {
+        let end = start.saturating_add(length).min(self.len());
+        self.blocks.range(start.min(end)..end)
+    }
+
+    /// Index and hash of the last block, None if the log is empty.
+    /// The last block is never archived.
+    pub fn get_tip(&self) -> Option<(u64, Hash)> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = self.len() - 1;
+        self.get_block(index)
+            .map(|block| (index, hash_value(&block)))
+    }
+
+    /// Archives in the order of blocks
+    pub fn get_archives(&self) -> &Vec<Archive> {
+        self.archives.get()
+    }
+
+    /// Add an archive which takes blocks from the end of the last archive
+    pub fn add_archive(&mut self, canister_id: Principal) -> ServiceResult<()> {
+        let mut archives = self.archives.get().clone();
+        if archives
+            .iter()
+            .any(|archive| archive.canister_id == canister_id)
+        {
+            return Err(NamingError::ArchiveAlreadyExists {
+                canister_id: canister_id.to_text(),
+            });
+        }
+        archives.push(Archive {
+            canister_id,
+            start: self.get_archived_end(),
+            length: 0,
+            full_at: None,
+        });
+        self.archives.set(archives);
+        Ok(())
+    }
+
+    /// Whether the last archive is full, blocks are kept in the log until another archive is added
+    pub fn is_last_archive_full(&self) -> bool {
+        self.archives
+            .get()
+            .last()
+            .map_or(false, |archive| archive.full_at.is_some())
+    }
+
+    /// Mark the last archive as full, so that no more blocks are moved to it.
+    /// Returns false if the archive is not the last one, e.g. another archive is added while the blocks are being archived.
+    pub fn set_archive_full(&mut self, canister_id: &Principal, now: u64) -> bool {
+        let mut archives = self.archives.get().clone();
+        match archives.last_mut() {
+            Some(archive) if archive.canister_id == *canister_id => {
+                archive.full_at = Some(now);
+            }
+            _ => return false,
+        }
+        self.archives.set(archives);
+        true
+    }
+
+    /// Oldest blocks to be moved to the last archive, at least `keep` blocks are kept in the log
+    /// and at most `max` blocks are returned.
+    /// Returns the archive, the index of the first block and the blocks,
+    /// None if there is nothing to archive or the last archive is full.
+    pub fn get_blocks_to_archive(
+        &self,
+        keep: u64,
+        max: u64,
+    ) -> Option<(Principal, u64, Vec<Value>)> {
+        let archive = self.archives.get().last()?;
+        if archive.full_at.is_some() {
+            return None;
+        }
+        let count = self.blocks.len().saturating_sub(keep.max(1)).min(max);
+        if count == 0 {
+            return None;
+        }
+        let start = self.get_archived_end();
+        let blocks = self
+            .blocks
+            .range(start..start + count)
+            .map(|(_, block)| block)
+            .collect();
+        Some((archive.canister_id, start, blocks))
+    }
+
+    /// Remove blocks before `end` from the log once they are appended to the last archive.
+    /// Returns false if the archive is not the last one or `end` is out of range,
+    /// e.g. another archive is added while the blocks are being archived.
+    pub fn set_archived(&mut self, canister_id: &Principal, end: u64) -> bool {
+        let archived_end = self.get_archived_end();
+        if end <= archived_end || end >= self.len() {
+            return false;
+        }
+        let mut archives = self.archives.get().clone();
+        match archives.last_mut() {
+            Some(archive) if archive.canister_id == *canister_id => {
+                archive.length = end - archive.start;
+            }
+            _ => return false,
+        }
+        for index in archived_end..end {
+            self.blocks.remove(&index);
+        }
+        self.archives.set(archives);
+        true
+    }
+}
@@ -0,0 +1,180 @@
+use candid::Nat;
+use serde_bytes::ByteBuf;
+
+use common::icrc3::BLOCK_PHASH_KEY;
+use rstest::*;
+
+use super::*;
+
+fn get_phash(block: &Value) -> Option<Value> {
+    match block {
+        Value::Map(entries) => entries
+            .iter()
+            .find(|(key, _)| key == BLOCK_PHASH_KEY)
+            .map(|(_, value)| value.clone()),
+        _ => panic!("block should be a map"),
+    }
+}
+
+fn tx(value: u64) -> Vec<(String, Value)> {
+    vec![("value".to_string(), Value::Nat(Nat::from(value)))]
+}
+
+#[rstest]
+fn test_append() {
+    let mut log = BlockLog::default();
+    assert_eq!(log.get_tip(), None);
+
+    let (index, hash) = log.append("7mint", 1, tx(1));
+    assert_eq!(index, 0);
+    assert_eq!(log.get_tip(), Some((0, hash)));
+    assert_eq!(get_phash(&log.get_block(0).unwrap()), None);
+
+    let (index, tip_hash) = log.append("7xfer", 2, tx(2));
+    assert_eq!(index, 1);
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.get_tip(), Some((1, tip_hash)));
+    assert_eq!(
+        get_phash(&log.get_block(1).unwrap()),
+        Some(Value::Blob(ByteBuf::from(hash.to_vec())))
+    );
+}
+
+#[rstest]
+#[case(0, 2, vec![0, 1])]
+#[case(1, 10, vec![1, 2])]
+#[case(3, 1, vec![])]
+#[case(5, 1, vec![])]
+#[case(1, 0, vec![])]
+fn test_get_blocks(#[case] start: u64, #[case] length: u64, #[case] expected: Vec<u64>) {
+    let mut log = BlockLog::default();
+    for i in 0..3 {
+        log.append("7mint", i, tx(i));
+    }
+
+    let indexes: Vec<u64> = log
+        .get_blocks(start, length)
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(indexes, expected);
+}
+
+fn archive_canister() -> Principal {
+    Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()
+}
+
+fn other_archive_canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn create_log(count: u64) -> BlockLog {
+    let mut log = BlockLog::default();
+    for i in 0..count {
+        log.append("7mint", i, tx(i));
+    }
+    log
+}
+
+#[rstest]
+fn test_get_blocks_to_archive() {
+    let mut log = create_log(10);
+    assert_eq!(log.get_blocks_to_archive(3, 5), None);
+
+    log.add_archive(archive_canister()).unwrap();
+    let (canister_id, start, blocks) = log.get_blocks_to_archive(3, 5).unwrap();
+    assert_eq!(canister_id, archive_canister());
+    assert_eq!(start, 0);
+    assert_eq!(blocks.len(), 5);
+    assert_eq!(blocks[0], log.get_block(0).unwrap());
+
+    let (_, _, blocks) = log.get_blocks_to_archive(8, 5).unwrap();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(log.get_blocks_to_archive(10, 5), None);
+}
+
+#[rstest]
+fn test_set_archived() {
+    let mut log = create_log(10);
+    let tip = log.get_tip();
+    log.add_archive(archive_canister()).unwrap();
+
+    assert!(log.set_archived(&archive_canister(), 4));
+    assert_eq!(log.len(), 10);
+    assert_eq!(log.get_archived_end(), 4);
+    assert_eq!(log.get_tip(), tip);
+    assert_eq!(log.get_block(3), None);
+    let indexes: Vec<u64> = log.get_blocks(0, 6).map(|(index, _)| index).collect();
+    assert_eq!(indexes, vec![4, 5]);
+
+    let (_, start, _) = log.get_blocks_to_archive(3, 5).unwrap();
+    assert_eq!(start, 4);
+
+    let (index, _) = log.append("7mint", 10, tx(10));
+    assert_eq!(index, 10);
+    assert_eq!(
+        log.get_archives(),
+        &vec![Archive {
+            canister_id: archive_canister(),
+            start: 0,
+            length: 4,
+            full_at: None,
+        }]
+    );
+}
+
+#[rstest]
+#[case(0)]
+#[case(10)]
+fn test_set_archived_out_of_range(#[case] end: u64) {
+    let mut log = create_log(10);
+    log.add_archive(archive_canister()).unwrap();
+
+    assert!(!log.set_archived(&archive_canister(), end));
+    assert_eq!(log.get_archived_end(), 0);
+}
+
+#[rstest]
+fn test_set_archived_not_last_archive() {
+    let mut log = create_log(10);
+    log.add_archive(archive_canister()).unwrap();
+    log.set_archived(&archive_canister(), 4);
+    log.add_archive(other_archive_canister()).unwrap();
+
+    assert!(!log.set_archived(&archive_canister(), 6));
+    assert_eq!(log.get_archived_end(), 4);
+    assert_eq!(log.get_archives()[1].start, 4);
+}
+
+#[rstest]
+fn test_set_archive_full() {
+    let mut log = create_log(10);
+    log.add_archive(archive_canister()).unwrap();
+    log.set_archived(&archive_canister(), 4);
+
+    assert!(log.set_archive_full(&archive_canister(), 20));
+    assert!(log.is_last_archive_full());
+    assert_eq!(log.get_blocks_to_archive(1, 5), None);
+    assert_eq!(log.get_unarchived_count(), 6);
+
+    // blocks are moved to the new archive
+    log.add_archive(other_archive_canister()).unwrap();
+    assert!(!log.is_last_archive_full());
+    assert!(!log.set_archive_full(&archive_canister(), 30));
+    let (canister_id, start, _) = log.get_blocks_to_archive(1, 5).unwrap();
+    assert_eq!(canister_id, other_archive_canister());
+    assert_eq!(start, 4);
+    assert_eq!(log.get_archives()[0].full_at, Some(20));
+}
+
+#[rstest]
+fn test_add_archive_already_exists() {
+    let mut log = create_log(1);
+    log.add_archive(archive_canister()).unwrap();
+
+    assert_eq!(
+        log.add_archive(archive_canister()),
+        Err(NamingError::ArchiveAlreadyExists {
+            canister_id: archive_canister().to_text(),
+        })
+    );
+}
@@ -0,0 +1,66 @@
+use common::certified_map::CertifiedValue;
+use common::icrc3::Hash;
+
+use crate::block_log::BlockLog;
+use crate::registration_store::RegistrationStore;
+use crate::state::CERTIFIED_MAP;
+
+pub fn get_owner_key(name: &str) -> String {
+    format!("owner/{}", name)
+}
+
+/// Update certified owners of names, it should be called after the store is updated.
+pub(crate) fn certify(store: &RegistrationStore, names: &[String]) {
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        for name in names {
+            match store.get_registration_by_name(name) {
+                Some(registration) => map.put(get_owner_key(name), &registration.get_owner()),
+                None => map.delete(&get_owner_key(name)),
+            }
+        }
+        map.certify();
+    });
+}
+
+/// Update the certified last block, it should be called after a block is appended to the log.
+pub(crate) fn certify_last_block(index: u64, hash: Hash) {
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        map.set_last_block(index, hash);
+        map.certify();
+    });
+}
+
+/// Rebuild the certified map from the stores
+pub(crate) fn certify_all(store: &RegistrationStore, block_log: &BlockLog) {
+    let names: Vec<String> = store
+        .get_registrations()
+        .map(|registration| registration.get_name())
+        .collect();
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        map.clear();
+        if let Some((index, hash)) = block_log.get_tip() {
+            map.set_last_block(index, hash);
+        }
+    });
+    certify(store, &names);
+}
+
+/// Certify the map restored from stable memory after upgrade, only the last block is set again from the log.
+/// It is only rebuilt from the stores if it is not kept in stable memory yet.
+pub(crate) fn certify_restored(store: &RegistrationStore, block_log: &BlockLog) {
+    let is_empty = CERTIFIED_MAP.with(|map| map.borrow().is_empty());
+    if is_empty {
+        certify_all(store, block_log);
+    } else if let Some((index, hash)) = block_log.get_tip() {
+        certify_last_block(index, hash);
+    } else {
+        CERTIFIED_MAP.with(|map| map.borrow().certify());
+    }
+}
+
+pub(crate) fn get_certified_value<T>(key: &str, value: Option<T>) -> CertifiedValue<T> {
+    CERTIFIED_MAP.with(|map| map.borrow().get_certified_value(key, value))
+}
@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+
+use candid::{decode_args, encode_args, CandidType, Deserialize, Principal};
+use common::constants::NAMING_COMMITMENT_MAX_AGE_NS;
+use sha2::{Digest, Sha256};
+
+use common::state::StableState;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Commitment {
+    pub committer: Principal,
+    pub committed_at: u64,
+}
+
+/// Commitments of names to be registered, they are revealed later to register the names.
+///
+/// Commitments of each committer are indexed in `committer_commitments`,
+/// so that commitments kept by a user are counted without visiting all commitments.
+/// The index is not encoded, it is rebuilt when the store is decoded.
+#[derive(Default)]
+pub struct CommitmentStore {
+    commitments: HashMap<String, Commitment>,
+    committer_commitments: HashMap<Principal, HashSet<String>>,
+}
+
+impl StableState for CommitmentStore {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((&self.commitments,)).unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (commitments,): (HashMap<String, Commitment>,) = decode_args(&bytes).unwrap();
+
+        let mut committer_commitments: HashMap<Principal, HashSet<String>> = HashMap::new();
+        for (commitment, value) in commitments.iter() {
+            committer_commitments
+                .entry(value.committer)
+                .or_default()
+                .insert(commitment.clone());
+        }
+        Ok(CommitmentStore {
+            commitments,
+            committer_commitments,
+        })
+    }
+}
+
+impl CommitmentStore {
+    pub fn add_commitment(&mut self, commitment: String, committer: Principal, committed_at: u64) {
+        self.remove_commitment(&commitment);
+        self.committer_commitments
+            .entry(committer)
+            .or_default()
+            .insert(commitment.clone());
+        self.commitments.insert(
+            commitment,
+            Commitment {
+                committer,
+                committed_at,
+            },
+        );
+    }
+
+    pub fn remove_commitment(&mut self, commitment: &str) -> Option<u64> {
+        let removed = self.commitments.remove(commitment)?;
+        if let Some(commitments) = self.committer_commitments.get_mut(&removed.committer) {
+            commitments.remove(commitment);
+            if commitments.is_empty() {
+                self.committer_commitments.remove(&removed.committer);
+            }
+        }
+        Some(removed.committed_at)
+    }
+
+    pub fn get_committed_at(&self, commitment: &str) -> Option<u64> {
+        self.commitments
+            .get(commitment)
+            .map(|commitment| commitment.committed_at)
+    }
+
+    pub fn get_commitment_count(&self, committer: &Principal) -> usize {
+        self.committer_commitments
+            .get(committer)
+            .map_or(0, |commitments| commitments.len())
+    }
+
+    pub fn remove_expired(&mut self, now: u64) -> usize {
+        let expired: Vec<String> = self
+            .commitments
+            .iter()
+            .filter(|(_, commitment)| is_commitment_expired(commitment.committed_at, now))
+            .map(|(commitment, _)| commitment.clone())
+            .collect();
+        for commitment in expired.iter() {
+            self.remove_commitment(commitment);
+        }
+        expired.len()
+    }
+
+    /// Remove expired commitments of the committer, only commitments of the committer are visited
+    pub fn remove_expired_of(&mut self, committer: &Principal, now: u64) -> usize {
+        let expired: Vec<String> = match self.committer_commitments.get(committer) {
+            Some(commitments) => commitments
+                .iter()
+                .filter(|commitment| {
+                    self.get_committed_at(commitment)
+                        .map_or(false, |committed_at| {
+                            is_commitment_expired(committed_at, now)
+                        })
+                })
+                .cloned()
+                .collect(),
+            None => return 0,
+        };
+        for commitment in expired.iter() {
+            self.remove_commitment(commitment);
+        }
+        expired.len()
+    }
+}
+
+pub fn is_commitment_expired(committed_at: u64, now: u64) -> bool {
+    committed_at + NAMING_COMMITMENT_MAX_AGE_NS <= now
+}
+
+/// Returns hex encoded sha256 hash of candid encoded (name, owner, secret)
+pub fn make_commitment(name: &str, owner: &Principal, secret: &[u8]) -> String {
+    let bytes = encode_args((name, owner, secret)).unwrap();
+    let mut sha256 = Sha256::new();
+    sha256.update(&bytes);
+    hex::encode(sha256.finalize())
+}
@@ -0,0 +1,122 @@
+use candid::Principal;
+use rstest::*;
+
+use common::constants::NAMING_COMMITMENT_MAX_AGE_NS;
+use common::state::StableState;
+use test_common::user::*;
+
+use crate::commitment_store::{make_commitment, CommitmentStore};
+
+#[fixture]
+fn store() -> CommitmentStore {
+    CommitmentStore::default()
+}
+
+#[rstest]
+fn test_make_commitment(mock_user1: Principal, mock_user2: Principal) {
+    let commitment = make_commitment("nice.icp", &mock_user1, b"secret");
+
+    assert_eq!(commitment.len(), 64);
+    assert_eq!(
+        commitment,
+        make_commitment("nice.icp", &mock_user1, b"secret")
+    );
+    assert_ne!(
+        commitment,
+        make_commitment("nice.icp", &mock_user2, b"secret")
+    );
+    assert_ne!(
+        commitment,
+        make_commitment("nice.icp", &mock_user1, b"secret2")
+    );
+    assert_ne!(
+        commitment,
+        make_commitment("nice2.icp", &mock_user1, b"secret")
+    );
+}
+
+#[rstest]
+fn test_remove_commitment(mut store: CommitmentStore, mock_user1: Principal, mock_now: u64) {
+    store.add_commitment("a".to_string(), mock_user1, mock_now);
+
+    assert_eq!(store.remove_commitment("a"), Some(mock_now));
+    assert_eq!(store.get_committed_at("a"), None);
+    assert_eq!(store.get_commitment_count(&mock_user1), 0);
+    assert_eq!(store.remove_commitment("a"), None);
+}
+
+#[rstest]
+fn test_add_commitment_again(
+    mut store: CommitmentStore,
+    mock_user1: Principal,
+    mock_user2: Principal,
+    mock_now: u64,
+) {
+    store.add_commitment("a".to_string(), mock_user1, mock_now - 1);
+    store.add_commitment("a".to_string(), mock_user2, mock_now);
+
+    assert_eq!(store.get_committed_at("a"), Some(mock_now));
+    assert_eq!(store.get_commitment_count(&mock_user1), 0);
+    assert_eq!(store.get_commitment_count(&mock_user2), 1);
+}
+
+#[rstest]
+fn test_remove_expired(mut store: CommitmentStore, mock_user1: Principal, mock_now: u64) {
+    store.add_commitment(
+        "a".to_string(),
+        mock_user1,
+        mock_now - NAMING_COMMITMENT_MAX_AGE_NS,
+    );
+    store.add_commitment(
+        "b".to_string(),
+        mock_user1,
+        mock_now - NAMING_COMMITMENT_MAX_AGE_NS + 1,
+    );
+    store.add_commitment("c".to_string(), mock_user1, mock_now);
+
+    let count = store.remove_expired(mock_now);
+
+    assert_eq!(count, 1);
+    assert_eq!(store.get_committed_at("a"), None);
+    assert!(store.get_committed_at("b").is_some());
+    assert!(store.get_committed_at("c").is_some());
+    assert_eq!(store.get_commitment_count(&mock_user1), 2);
+}
+
+#[rstest]
+fn test_remove_expired_of(
+    mut store: CommitmentStore,
+    mock_user1: Principal,
+    mock_user2: Principal,
+    mock_now: u64,
+) {
+    let expired_at = mock_now - NAMING_COMMITMENT_MAX_AGE_NS;
+    store.add_commitment("a".to_string(), mock_user1, expired_at);
+    store.add_commitment("b".to_string(), mock_user1, mock_now);
+    store.add_commitment("c".to_string(), mock_user2, expired_at);
+
+    let count = store.remove_expired_of(&mock_user1, mock_now);
+
+    assert_eq!(count, 1);
+    assert_eq!(store.get_committed_at("a"), None);
+    assert_eq!(store.get_commitment_count(&mock_user1), 1);
+    assert_eq!(store.get_committed_at("c"), Some(expired_at));
+}
+
+#[rstest]
+fn test_decode_rebuilds_committer_index(
+    mut store: CommitmentStore,
+    mock_user1: Principal,
+    mock_user2: Principal,
+    mock_now: u64,
+) {
+    store.add_commitment("a".to_string(), mock_user1, mock_now);
+    store.add_commitment("b".to_string(), mock_user1, mock_now);
+    store.add_commitment("c".to_string(), mock_user2, mock_now);
+
+    let decoded = CommitmentStore::decode(store.encode()).unwrap();
+
+    assert_eq!(decoded.get_commitment_count(&mock_user1), 2);
+    assert_eq!(decoded.get_commitment_count(&mock_user2), 1);
+    assert_eq!(decoded.get_committed_at("c"), Some(mock_now));
+}
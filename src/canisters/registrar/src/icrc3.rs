@@ -0,0 +1,363 @@
+//! Transaction log of names and quotas defined by ICRC-3.
+//!
+//! Every update of names and quotas appends a block to `BlockLog`. Blocks of names follow ICRC-7 and ICRC-37,
+//! where `tid` is the token index of the name, and the rest of the blocks are defined by ICNaming.
+//!
+//! Old blocks are moved to `registrar_archive` canisters by the periodic tasks,
+//! and ranges of archived blocks are returned with callbacks to the archives.
+//! Once the last archive is full, blocks are kept in the log until another archive is added by `add_block_archive`.
+
+use candid::{Func, Nat, Principal};
+use log::{debug, error, info};
+use num_traits::ToPrimitive;
+use serde_bytes::ByteBuf;
+
+use common::errors::{ErrorInfo, NamingError, ServiceResult};
+use common::icrc::Value;
+use common::icrc3::{
+    account_value, ArchiveInfo, ArchivedBlocks, BlockWithId, DataCertificate, GetArchivesArgs,
+    GetBlocksArgs, GetBlocksCallback, GetBlocksResult, SupportedBlockType,
+};
+use common::permissions::must_be_system_owner;
+use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
+use common::TimeInNs;
+
+use crate::certification::certify_last_block;
+use crate::service::RegistrarService;
+use crate::state::{CERTIFIED_MAP, STATE};
+use crate::user_quota_store::QuotaType;
+
+#[cfg(test)]
+mod tests;
+
+pub const BTYPE_MINT: &str = "7mint";
+pub const BTYPE_BURN: &str = "7burn";
+pub const BTYPE_TRANSFER: &str = "7xfer";
+pub const BTYPE_UPDATE_TOKEN: &str = "7update_token";
+pub const BTYPE_APPROVE: &str = "37approve";
+pub const BTYPE_REVOKE: &str = "37revoke";
+pub const BTYPE_TRANSFER_FROM: &str = "37xfer";
+pub const BTYPE_RECLAIM: &str = "icnaming_reclaim";
+pub const BTYPE_QUOTA_MINT: &str = "icnaming_quota_mint";
+pub const BTYPE_QUOTA_BURN: &str = "icnaming_quota_burn";
+pub const BTYPE_QUOTA_TRANSFER: &str = "icnaming_quota_xfer";
+
+pub const ICRC3_MAX_BLOCKS_PER_RESPONSE: u64 = 100;
+/// Blocks kept in the registrar, older blocks are moved to the last archive
+pub const ICRC3_ARCHIVE_KEEP_BLOCKS: u64 = 1000;
+/// Blocks moved to the archive in one call
+pub const ICRC3_ARCHIVE_CHUNK_SIZE: u64 = 500;
+
+const ICNAMING_URL: &str = "https://github.com/IC-Naming/icnaming";
+
+/// Transaction of a block, it is appended to the log by `log`
+pub(crate) struct Transaction {
+    btype: &'static str,
+    tx: Vec<(String, Value)>,
+}
+
+impl Transaction {
+    pub fn new(btype: &'static str) -> Self {
+        Transaction { btype, tx: vec![] }
+    }
+
+    /// `tid` is added if the name is indexed, so it should be called before the index of the name is removed.
+    pub fn with_name(mut self, name: &str) -> Self {
+        let index = STATE.with(|s| {
+            let store = s.token_index_store.borrow();
+            store
+                .get_registration_by_name(&name.to_string())
+                .map(|registration_name| registration_name.get_index())
+        });
+        if let Some(index) = index {
+            self.tx
+                .push(("tid".to_string(), Value::Nat(Nat::from(index.get_value()))));
+        }
+        self.with_value("name", Value::Text(name.to_string()))
+    }
+
+    pub fn with_account(self, key: &str, owner: &Principal) -> Self {
+        self.with_value(key, account_value(owner))
+    }
+
+    pub fn with_value(mut self, key: &str, value: Value) -> Self {
+        self.tx.push((key.to_string(), value));
+        self
+    }
+
+    /// Append the block to the log and certify the new last block, returns the index of the block
+    pub fn log(self) -> u64 {
+        let (index, hash) = STATE.with(|s| {
+            let mut block_log = s.block_log.borrow_mut();
+            block_log.append(self.btype, get_block_time(), self.tx)
+        });
+        certify_last_block(index, hash);
+        index
+    }
+}
+
+/// Metadata of a name in `meta` of mint and update blocks
+pub(crate) fn get_name_meta(name: &str, expired_at: u64) -> Value {
+    Value::Map(vec![
+        ("name".to_string(), Value::Text(name.to_string())),
+        ("expired_at".to_string(), Value::Nat(Nat::from(expired_at))),
+    ])
+}
+
+/// Transaction of quotas, `from` is None for minted quotas and `to` is None for burned quotas.
+pub(crate) fn get_quota_transaction(
+    from: Option<&Principal>,
+    to: Option<&Principal>,
+    quota_type: &QuotaType,
+    amount: u32,
+) -> Transaction {
+    let btype = match (from, to) {
+        (None, _) => BTYPE_QUOTA_MINT,
+        (Some(_), None) => BTYPE_QUOTA_BURN,
+        (Some(_), Some(_)) => BTYPE_QUOTA_TRANSFER,
+    };
+    let mut transaction = Transaction::new(btype);
+    if let Some(from) = from {
+        transaction = transaction.with_account("from", from);
+    }
+    if let Some(to) = to {
+        transaction = transaction.with_account("to", to);
+    }
+    transaction
+        .with_value("quota_type", Value::Text(quota_type.to_string()))
+        .with_value("amt", Value::Nat(Nat::from(amount)))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_block_time() -> u64 {
+    ic_cdk::api::time()
+}
+
+// time is only available in canister
+#[cfg(not(target_arch = "wasm32"))]
+fn get_block_time() -> u64 {
+    0
+}
+
+pub fn get_supported_block_types() -> Vec<SupportedBlockType> {
+    let icrc7_url = "https://github.com/dfinity/ICRC/ICRCs/ICRC-7";
+    let icrc37_url = "https://github.com/dfinity/ICRC/ICRCs/ICRC-37";
+    [
+        (BTYPE_MINT, icrc7_url),
+        (BTYPE_BURN, icrc7_url),
+        (BTYPE_TRANSFER, icrc7_url),
+        (BTYPE_UPDATE_TOKEN, icrc7_url),
+        (BTYPE_APPROVE, icrc37_url),
+        (BTYPE_REVOKE, icrc37_url),
+        (BTYPE_TRANSFER_FROM, icrc37_url),
+        (BTYPE_RECLAIM, ICNAMING_URL),
+        (BTYPE_QUOTA_MINT, ICNAMING_URL),
+        (BTYPE_QUOTA_BURN, ICNAMING_URL),
+        (BTYPE_QUOTA_TRANSFER, ICNAMING_URL),
+    ]
+    .iter()
+    .map(|(block_type, url)| SupportedBlockType {
+        block_type: block_type.to_string(),
+        url: url.to_string(),
+    })
+    .collect()
+}
+
+/// Add a range of archived blocks to the callback of the archive
+fn push_archived_range(
+    archived: &mut Vec<(Principal, Vec<GetBlocksArgs>)>,
+    canister_id: Principal,
+    start: u64,
+    length: u64,
+) {
+    let args = GetBlocksArgs {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    };
+    match archived.iter_mut().find(|(id, _)| *id == canister_id) {
+        Some((_, ranges)) => ranges.push(args),
+        None => archived.push((canister_id, vec![args])),
+    }
+}
+
+impl RegistrarService {
+    /// Blocks of the ranges, at most `ICRC3_MAX_BLOCKS_PER_RESPONSE` blocks are returned in total.
+    /// Archived parts of the ranges are returned as callbacks to the archives.
+    pub(crate) fn icrc3_get_blocks(&self, args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+        STATE.with(|s| {
+            let block_log = s.block_log.borrow();
+            let log_length = block_log.len();
+            let archived_end = block_log.get_archived_end();
+            let mut blocks = vec![];
+            let mut archived = vec![];
+            for arg in args {
+                let start = arg.start.0.to_u64().unwrap_or(u64::MAX);
+                let length = arg.length.0.to_u64().unwrap_or(u64::MAX);
+                let end = start.saturating_add(length).min(log_length);
+                for archive in block_log.get_archives() {
+                    let archive_start = start.max(archive.start);
+                    let archive_end = end.min(archive.start + archive.length);
+                    if archive_start < archive_end {
+                        push_archived_range(
+                            &mut archived,
+                            archive.canister_id,
+                            archive_start,
+                            archive_end - archive_start,
+                        );
+                    }
+                }
+                let remaining = ICRC3_MAX_BLOCKS_PER_RESPONSE - blocks.len() as u64;
+                let local_start = start.max(archived_end);
+                let local_length = end.saturating_sub(local_start).min(remaining);
+                blocks.extend(block_log.get_blocks(local_start, local_length).map(
+                    |(id, block)| BlockWithId {
+                        id: Nat::from(id),
+                        block,
+                    },
+                ));
+            }
+            GetBlocksResult {
+                log_length: Nat::from(log_length),
+                blocks,
+                archived_blocks: archived
+                    .into_iter()
+                    .map(|(canister_id, args)| ArchivedBlocks {
+                        args,
+                        callback: GetBlocksCallback(Func {
+                            principal: canister_id,
+                            method: "icrc3_get_blocks".to_string(),
+                        }),
+                    })
+                    .collect(),
+            }
+        })
+    }
+
+    /// Hash tree of the last block, the certificate is attached by the query endpoint.
+    /// None if the log is empty.
+    pub(crate) fn icrc3_get_tip_certificate(&self) -> Option<DataCertificate> {
+        let hash_tree = CERTIFIED_MAP.with(|map| map.borrow().last_block_witness())?;
+        Some(DataCertificate {
+            certificate: ByteBuf::new(),
+            hash_tree: ByteBuf::from(hash_tree),
+        })
+    }
+
+    /// Archives holding blocks, `end` is the index of the last block of the archive.
+    /// Archives after `from` are returned if it is set.
+    pub(crate) fn icrc3_get_archives(&self, args: GetArchivesArgs) -> Vec<ArchiveInfo> {
+        STATE.with(|s| {
+            let block_log = s.block_log.borrow();
+            let archives = block_log.get_archives();
+            let skip = args
+                .from
+                .and_then(|from| {
+                    archives
+                        .iter()
+                        .position(|archive| archive.canister_id == from)
+                })
+                .map(|position| position + 1)
+                .unwrap_or(0);
+            archives
+                .iter()
+                .skip(skip)
+                .filter(|archive| archive.length > 0)
+                .map(|archive| ArchiveInfo {
+                    canister_id: archive.canister_id,
+                    start: Nat::from(archive.start),
+                    end: Nat::from(archive.start + archive.length - 1),
+                })
+                .collect()
+        })
+    }
+
+    /// Add an archive canister, blocks are moved to the new archive from now on.
+    /// The archive should be created with the registrar as a named canister.
+    pub(crate) fn add_block_archive(
+        &self,
+        caller: &Principal,
+        canister_id: Principal,
+    ) -> ServiceResult<bool> {
+        must_be_system_owner(caller)?;
+        STATE.with(|s| {
+            let mut block_log = s.block_log.borrow_mut();
+            block_log.add_archive(canister_id)?;
+            info!(
+                "add_block_archive: {} from block {}",
+                canister_id,
+                block_log.get_archived_end()
+            );
+            Ok(true)
+        })
+    }
+
+    /// Move a chunk of old blocks to the last archive, it is called by the periodic tasks.
+    pub async fn archive_blocks(&self, now: TimeInNs) -> ServiceResult<()> {
+        if !try_lock_with_timeout(LockId::RegistrarArchiveBlocks, now) {
+            debug!("archive_blocks: already locked");
+            return Ok(());
+        }
+        let result = self.archive_blocks_core(now).await;
+        release_timeout_locker(LockId::RegistrarArchiveBlocks);
+        result
+    }
+
+    async fn archive_blocks_core(&self, now: TimeInNs) -> ServiceResult<()> {
+        let chunk = STATE.with(|s| {
+            let block_log = s.block_log.borrow();
+            block_log.get_blocks_to_archive(ICRC3_ARCHIVE_KEEP_BLOCKS, ICRC3_ARCHIVE_CHUNK_SIZE)
+        });
+        let (canister_id, start, blocks) = match chunk {
+            Some(chunk) => chunk,
+            None => {
+                debug!("archive_blocks: no blocks to archive or the last archive is full");
+                return Ok(());
+            }
+        };
+        let result = self
+            .archive_api
+            .append_blocks(canister_id, start, blocks)
+            .await;
+        let end = match result {
+            Ok(end) => end,
+            Err(e) if is_archive_full(&e) => {
+                STATE.with(|s| {
+                    let mut block_log = s.block_log.borrow_mut();
+                    block_log.set_archive_full(&canister_id, now.0)
+                });
+                error!(
+                    "archive_blocks: archive {} is full, blocks from {} are kept in the log until another archive is added",
+                    canister_id, start
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                error!(
+                    "archive_blocks: failed to append blocks to {}: {}",
+                    canister_id, e
+                );
+                return Err(NamingError::RemoteError(e));
+            }
+        };
+        let archived = STATE.with(|s| {
+            let mut block_log = s.block_log.borrow_mut();
+            block_log.set_archived(&canister_id, end)
+        });
+        if archived {
+            info!(
+                "archive_blocks: blocks {}..{} archived to {}",
+                start, end, canister_id
+            );
+        } else {
+            error!(
+                "archive_blocks: blocks {}..{} archived to {} are kept in the log",
+                start, end, canister_id
+            );
+        }
+        Ok(())
+    }
+}
+
+fn is_archive_full(error: &ErrorInfo) -> bool {
+    error.code == ErrorInfo::from(NamingError::ArchiveFull { max: 0 }).code
+}
@@ -0,0 +1,376 @@
+use std::sync::Arc;
+
+use candid::Principal;
+use rstest::*;
+
+use common::constants::NAMING_TOP_LABEL;
+use common::errors::ErrorInfo;
+use common::icrc3::{hash_value, BLOCK_PHASH_KEY, BLOCK_TX_KEY, BLOCK_TYPE_KEY};
+use common::permissions::get_admin;
+use common::AuthPrincipal;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::registration_store::Registration;
+use crate::user_quota_store::TransferQuotaDetails;
+
+use super::*;
+
+fn create_test_name(name: &str) -> String {
+    format!("{}.{}", name, NAMING_TOP_LABEL)
+}
+
+fn register_name(name: &str, owner: Principal, now: u64) {
+    STATE.with(|s| {
+        let name = create_test_name(name);
+        s.token_index_store
+            .borrow_mut()
+            .try_add_registration_name(&name)
+            .unwrap();
+        s.registration_store
+            .borrow_mut()
+            .add_registration(Registration::new(owner, name, now + 1_000_000_000, now));
+    });
+}
+
+fn get_entry(value: &Value, key: &str) -> Option<Value> {
+    match value {
+        Value::Map(entries) => entries
+            .iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value.clone()),
+        _ => panic!("value should be a map"),
+    }
+}
+
+fn get_btypes(result: &GetBlocksResult) -> Vec<Value> {
+    result
+        .blocks
+        .iter()
+        .map(|block| get_entry(&block.block, BLOCK_TYPE_KEY).unwrap())
+        .collect()
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn get_all_blocks(service: &RegistrarService) -> GetBlocksResult {
+    service.icrc3_get_blocks(vec![GetBlocksArgs {
+        start: Nat::from(0u32),
+        length: Nat::from(ICRC3_MAX_BLOCKS_PER_RESPONSE),
+    }])
+}
+
+#[fixture]
+fn service(_init_test: (), mut mock_registry_api: MockRegistryApi) -> RegistrarService {
+    mock_registry_api
+        .expect_transfer()
+        .returning(|_name, _owner, _resolver| Ok(true));
+    let mut service = RegistrarService::default();
+    service.registry_api = Arc::new(mock_registry_api);
+    service
+}
+
+#[rstest]
+async fn test_name_blocks(
+    service: RegistrarService,
+    mock_user1: Principal,
+    mock_user2: Principal,
+    mock_user3: Principal,
+    mock_now: u64,
+) {
+    register_name("hello", mock_user1, mock_now);
+    let name = create_test_name("hello");
+    service
+        .approve(&mock_user1, mock_now, &name, mock_user2)
+        .unwrap();
+
+    service
+        .transfer_from(&mock_user2, &name, Some(AuthPrincipal(mock_user3)))
+        .await
+        .unwrap();
+
+    let result = get_all_blocks(&service);
+    assert_eq!(result.log_length, Nat::from(2u32));
+    assert_eq!(
+        get_btypes(&result),
+        vec![text(BTYPE_APPROVE), text(BTYPE_TRANSFER_FROM)]
+    );
+    let tx = get_entry(&result.blocks[1].block, BLOCK_TX_KEY).unwrap();
+    assert_eq!(get_entry(&tx, "tid"), Some(Value::Nat(Nat::from(1u32))));
+    assert_eq!(get_entry(&tx, "name"), Some(text(&name)));
+    assert_eq!(get_entry(&tx, "spender"), Some(account_value(&mock_user2)));
+    assert_eq!(get_entry(&tx, "from"), Some(account_value(&mock_user1)));
+    assert_eq!(get_entry(&tx, "to"), Some(account_value(&mock_user3)));
+    assert_eq!(
+        get_entry(&result.blocks[1].block, BLOCK_PHASH_KEY),
+        Some(Value::Blob(ByteBuf::from(
+            hash_value(&result.blocks[0].block).to_vec()
+        )))
+    );
+}
+
+#[rstest]
+fn test_quota_blocks(service: RegistrarService, mock_user1: Principal, mock_user2: Principal) {
+    let quota_type = QuotaType::LenGte(4);
+    STATE.with(|s| {
+        let mut store = s.user_quota_store.borrow_mut();
+        store.add_quota(AuthPrincipal(mock_user1), quota_type.clone(), 2);
+    });
+
+    service
+        .transfer_quota(
+            mock_user1,
+            TransferQuotaDetails {
+                to: mock_user2,
+                quota_type: quota_type.clone(),
+                diff: 1,
+            },
+        )
+        .unwrap();
+
+    let result = get_all_blocks(&service);
+    assert_eq!(get_btypes(&result), vec![text(BTYPE_QUOTA_TRANSFER)]);
+    let tx = get_entry(&result.blocks[0].block, BLOCK_TX_KEY).unwrap();
+    assert_eq!(get_entry(&tx, "from"), Some(account_value(&mock_user1)));
+    assert_eq!(get_entry(&tx, "to"), Some(account_value(&mock_user2)));
+    assert_eq!(get_entry(&tx, "quota_type"), Some(text("len_gte(4)")));
+    assert_eq!(get_entry(&tx, "amt"), Some(Value::Nat(Nat::from(1u32))));
+}
+
+#[rstest]
+#[case(None, Some(true), BTYPE_QUOTA_MINT)]
+#[case(Some(true), None, BTYPE_QUOTA_BURN)]
+#[case(Some(true), Some(true), BTYPE_QUOTA_TRANSFER)]
+fn test_get_quota_transaction(
+    mock_user1: Principal,
+    #[case] from: Option<bool>,
+    #[case] to: Option<bool>,
+    #[case] btype: &str,
+) {
+    let transaction = get_quota_transaction(
+        from.map(|_| &mock_user1),
+        to.map(|_| &mock_user1),
+        &QuotaType::LenGte(1),
+        1,
+    );
+
+    assert_eq!(transaction.btype, btype);
+}
+
+#[rstest]
+fn test_get_blocks_bounded(service: RegistrarService, mock_user1: Principal) {
+    for _ in 0..150 {
+        Transaction::new(BTYPE_QUOTA_MINT)
+            .with_account("to", &mock_user1)
+            .log();
+    }
+
+    let result = service.icrc3_get_blocks(vec![
+        GetBlocksArgs {
+            start: Nat::from(0u32),
+            length: Nat::from(80u32),
+        },
+        GetBlocksArgs {
+            start: Nat::from(120u32),
+            length: Nat::from(80u32),
+        },
+    ]);
+
+    assert_eq!(result.log_length, Nat::from(150u32));
+    assert_eq!(result.blocks.len(), ICRC3_MAX_BLOCKS_PER_RESPONSE as usize);
+    assert_eq!(result.blocks[80].id, Nat::from(120u32));
+    assert!(result.archived_blocks.is_empty());
+}
+
+#[rstest]
+fn test_get_tip_certificate(service: RegistrarService, mock_user1: Principal) {
+    assert_eq!(service.icrc3_get_tip_certificate(), None);
+
+    Transaction::new(BTYPE_QUOTA_MINT)
+        .with_account("to", &mock_user1)
+        .log();
+
+    let certificate = service.icrc3_get_tip_certificate().unwrap();
+    assert!(certificate.certificate.is_empty());
+    // self-described CBOR tag
+    assert_eq!(&certificate.hash_tree[..3], &[0xd9, 0xd9, 0xf7]);
+}
+
+fn log_blocks(owner: &Principal, count: u64) {
+    for _ in 0..count {
+        Transaction::new(BTYPE_QUOTA_MINT)
+            .with_account("to", owner)
+            .log();
+    }
+}
+
+fn get_blocks_args(start: u64, length: u64) -> GetBlocksArgs {
+    GetBlocksArgs {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    }
+}
+
+mod archive_blocks {
+    use super::*;
+
+    #[rstest]
+    async fn test_archive_blocks(
+        mut service: RegistrarService,
+        mut mock_registrar_archive_api: MockRegistrarArchiveApi,
+        mock_canister1: Principal,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        log_blocks(&mock_user1, ICRC3_ARCHIVE_KEEP_BLOCKS + 10);
+        let tip = service.icrc3_get_tip_certificate();
+        mock_registrar_archive_api
+            .expect_append_blocks()
+            .withf(move |archive, start, blocks| {
+                *archive == mock_canister1 && *start == 0 && blocks.len() == 10
+            })
+            .times(1)
+            .returning(|_archive, start, blocks| Ok(start + blocks.len() as u64));
+        service.archive_api = Arc::new(mock_registrar_archive_api);
+        service
+            .add_block_archive(&get_admin(), mock_canister1)
+            .unwrap();
+
+        service.archive_blocks(TimeInNs(mock_now)).await.unwrap();
+
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(5, 10)]);
+        assert_eq!(result.log_length, Nat::from(ICRC3_ARCHIVE_KEEP_BLOCKS + 10));
+        assert_eq!(result.blocks.len(), 5);
+        assert_eq!(result.blocks[0].id, Nat::from(10u32));
+        assert_eq!(result.archived_blocks.len(), 1);
+        assert_eq!(result.archived_blocks[0].args, vec![get_blocks_args(5, 5)]);
+        assert_eq!(
+            result.archived_blocks[0].callback.0.principal,
+            mock_canister1
+        );
+        assert_eq!(service.icrc3_get_tip_certificate(), tip);
+
+        let archives = service.icrc3_get_archives(GetArchivesArgs { from: None });
+        assert_eq!(
+            archives,
+            vec![ArchiveInfo {
+                canister_id: mock_canister1,
+                start: Nat::from(0u32),
+                end: Nat::from(9u32),
+            }]
+        );
+        let archives = service.icrc3_get_archives(GetArchivesArgs {
+            from: Some(mock_canister1),
+        });
+        assert!(archives.is_empty());
+    }
+
+    #[rstest]
+    async fn test_archive_blocks_failed(
+        mut service: RegistrarService,
+        mut mock_registrar_archive_api: MockRegistrarArchiveApi,
+        mock_canister1: Principal,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        log_blocks(&mock_user1, ICRC3_ARCHIVE_KEEP_BLOCKS + 10);
+        mock_registrar_archive_api
+            .expect_append_blocks()
+            .returning(|_archive, _start, _blocks| {
+                Err(ErrorInfo::from(NamingError::InvalidBlockIndex {
+                    expected: 5,
+                }))
+            });
+        service.archive_api = Arc::new(mock_registrar_archive_api);
+        service
+            .add_block_archive(&get_admin(), mock_canister1)
+            .unwrap();
+
+        let result = service.archive_blocks(TimeInNs(mock_now)).await;
+        assert!(result.is_err());
+
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(0, 10)]);
+        assert_eq!(result.blocks.len(), 10);
+        assert!(result.archived_blocks.is_empty());
+        assert!(service
+            .icrc3_get_archives(GetArchivesArgs { from: None })
+            .is_empty());
+    }
+
+    #[rstest]
+    async fn test_archive_blocks_full(
+        mut service: RegistrarService,
+        mut mock_registrar_archive_api: MockRegistrarArchiveApi,
+        mock_canister1: Principal,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let new_archive = mock_canister(2);
+        log_blocks(&mock_user1, ICRC3_ARCHIVE_KEEP_BLOCKS + 10);
+        mock_registrar_archive_api
+            .expect_append_blocks()
+            .withf(move |archive, _start, _blocks| *archive == mock_canister1)
+            .times(1)
+            .returning(|_archive, _start, _blocks| {
+                Err(ErrorInfo::from(NamingError::ArchiveFull { max: 0 }))
+            });
+        mock_registrar_archive_api
+            .expect_append_blocks()
+            .withf(move |archive, start, _blocks| *archive == new_archive && *start == 0)
+            .times(1)
+            .returning(|_archive, start, blocks| Ok(start + blocks.len() as u64));
+        service.archive_api = Arc::new(mock_registrar_archive_api);
+        service
+            .add_block_archive(&get_admin(), mock_canister1)
+            .unwrap();
+
+        // the full archive is called only once
+        service.archive_blocks(TimeInNs(mock_now)).await.unwrap();
+        service.archive_blocks(TimeInNs(mock_now)).await.unwrap();
+
+        STATE.with(|s| {
+            let block_log = s.block_log.borrow();
+            assert!(block_log.is_last_archive_full());
+            assert_eq!(
+                block_log.get_unarchived_count(),
+                ICRC3_ARCHIVE_KEEP_BLOCKS + 10
+            );
+        });
+
+        service
+            .add_block_archive(&get_admin(), new_archive)
+            .unwrap();
+        service.archive_blocks(TimeInNs(mock_now)).await.unwrap();
+
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(0, 10)]);
+        assert!(result.blocks.is_empty());
+        assert_eq!(result.archived_blocks[0].callback.0.principal, new_archive);
+    }
+
+    #[rstest]
+    async fn test_archive_blocks_no_archive(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        log_blocks(&mock_user1, ICRC3_ARCHIVE_KEEP_BLOCKS + 10);
+
+        service.archive_blocks(TimeInNs(mock_now)).await.unwrap();
+
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(0, 10)]);
+        assert_eq!(result.blocks.len(), 10);
+    }
+
+    #[rstest]
+    fn test_add_block_archive_not_admin(
+        service: RegistrarService,
+        mock_canister1: Principal,
+        mock_user3: Principal,
+    ) {
+        let result = service.add_block_archive(&mock_user3, mock_canister1);
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+}
@@ -0,0 +1,489 @@
+use candid::{CandidType, Deserialize, Nat};
+use log::info;
+use serde_bytes::ByteBuf;
+
+use common::errors::NamingError;
+use common::icrc::{is_default_subaccount, Account};
+use common::naming::FirstLevelName;
+use common::{AuthPrincipal, CallContext};
+
+use crate::icrc3::{Transaction, BTYPE_REVOKE};
+use crate::icrc7::{
+    get_batch_size_error, get_generic_error, get_recipient, get_unexpired_registration,
+    DeduplicatedTransaction, DeduplicationError, ICRC7_MAX_QUERY_BATCH_SIZE,
+    ICRC7_MAX_UPDATE_BATCH_SIZE,
+};
+use crate::service::RegistrarService;
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+// a name can only be approved to one spender, approving again replaces the old one
+pub const ICRC37_MAX_APPROVALS_PER_TOKEN_OR_COLLECTION: u64 = 1;
+pub const ICRC37_MAX_REVOKE_APPROVALS: u64 = ICRC7_MAX_UPDATE_BATCH_SIZE as u64;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ApprovalInfo {
+    pub spender: Account,
+    pub from_subaccount: Option<ByteBuf>,
+    pub expires_at: Option<u64>,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ApproveTokenArg {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ApproveTokenError {
+    InvalidSpender,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ApproveTokenResult {
+    Ok(Nat),
+    Err(ApproveTokenError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ApproveCollectionArg {
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ApproveCollectionError {
+    InvalidSpender,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ApproveCollectionResult {
+    Ok(Nat),
+    Err(ApproveCollectionError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RevokeTokenApprovalArg {
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<ByteBuf>,
+    pub token_id: Nat,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum RevokeTokenApprovalError {
+    ApprovalDoesNotExist,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum RevokeTokenApprovalResponse {
+    Ok(Nat),
+    Err(RevokeTokenApprovalError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RevokeCollectionApprovalArg {
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<ByteBuf>,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum RevokeCollectionApprovalError {
+    ApprovalDoesNotExist,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum RevokeCollectionApprovalResult {
+    Ok(Nat),
+    Err(RevokeCollectionApprovalError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct IsApprovedArg {
+    pub spender: Account,
+    pub from_subaccount: Option<ByteBuf>,
+    pub token_id: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct TokenApproval {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+pub type CollectionApproval = ApprovalInfo;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferFromArg {
+    pub spender_subaccount: Option<ByteBuf>,
+    pub from: Account,
+    pub to: Account,
+    pub token_id: Nat,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum TransferFromError {
+    InvalidRecipient,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum TransferFromResult {
+    Ok(Nat),
+    Err(TransferFromError),
+}
+
+impl From<DeduplicationError> for TransferFromError {
+    fn from(error: DeduplicationError) -> Self {
+        match error {
+            DeduplicationError::TooOld => TransferFromError::TooOld,
+            DeduplicationError::CreatedInFuture { ledger_time } => {
+                TransferFromError::CreatedInFuture { ledger_time }
+            }
+            DeduplicationError::Duplicate { duplicate_of } => TransferFromError::Duplicate {
+                duplicate_of: Nat::from(duplicate_of),
+            },
+        }
+    }
+}
+
+/// Approvals of names are kept in RegistrationApprovalStore, which is shared with `approve` and `ext_approve`.
+/// Expiration of approvals and collection approvals are not supported.
+impl RegistrarService {
+    pub(crate) fn icrc37_approve_tokens(
+        &self,
+        call_context: &CallContext,
+        args: Vec<ApproveTokenArg>,
+    ) -> Vec<Option<ApproveTokenResult>> {
+        if args.len() > ICRC7_MAX_UPDATE_BATCH_SIZE {
+            let (error_code, message) = get_batch_size_error(ICRC7_MAX_UPDATE_BATCH_SIZE);
+            return vec![Some(ApproveTokenResult::Err(
+                ApproveTokenError::GenericBatchError {
+                    error_code,
+                    message,
+                },
+            ))];
+        }
+        args.iter()
+            .map(|arg| {
+                Some(match self.icrc37_approve_token(call_context, arg) {
+                    Ok(index) => ApproveTokenResult::Ok(Nat::from(index)),
+                    Err(e) => ApproveTokenResult::Err(e),
+                })
+            })
+            .collect()
+    }
+
+    fn icrc37_approve_token(
+        &self,
+        call_context: &CallContext,
+        arg: &ApproveTokenArg,
+    ) -> Result<u64, ApproveTokenError> {
+        let approval_info = &arg.approval_info;
+        if approval_info.expires_at.is_some() {
+            return Err(ApproveTokenError::GenericError {
+                error_code: Nat::from(1u32),
+                message: "expires_at is not supported".to_string(),
+            });
+        }
+        if !is_default_subaccount(&approval_info.from_subaccount) {
+            return Err(ApproveTokenError::Unauthorized);
+        }
+        let spender =
+            get_recipient(&approval_info.spender).ok_or(ApproveTokenError::InvalidSpender)?;
+        let registration = get_unexpired_registration(&arg.token_id, call_context.now.0)
+            .ok_or(ApproveTokenError::NonExistingTokenId)?;
+        if !registration.is_owner(&call_context.caller) {
+            return Err(ApproveTokenError::Unauthorized);
+        }
+        if spender == call_context.caller {
+            return Err(ApproveTokenError::InvalidSpender);
+        }
+
+        let index = self
+            .approve_name(
+                &call_context.caller,
+                call_context.now.0,
+                registration.get_name().as_str(),
+                spender,
+            )
+            .map_err(|e| {
+                let (error_code, message) = get_generic_error(e);
+                ApproveTokenError::GenericError {
+                    error_code,
+                    message,
+                }
+            })?;
+        info!(
+            "icrc37_approve_tokens: {} is approved to {}",
+            registration.get_name(),
+            spender
+        );
+        Ok(index)
+    }
+
+    /// Collection approvals are rejected, so no block is logged and there is no transaction index to return
+    pub(crate) fn icrc37_approve_collection(
+        &self,
+        args: Vec<ApproveCollectionArg>,
+    ) -> Vec<Option<ApproveCollectionResult>> {
+        args.iter()
+            .take(ICRC7_MAX_UPDATE_BATCH_SIZE)
+            .map(|_| {
+                Some(ApproveCollectionResult::Err(
+                    ApproveCollectionError::GenericError {
+                        error_code: Nat::from(1u32),
+                        message: "collection approval is not supported".to_string(),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    pub(crate) fn icrc37_revoke_token_approvals(
+        &self,
+        call_context: &CallContext,
+        args: Vec<RevokeTokenApprovalArg>,
+    ) -> Vec<Option<RevokeTokenApprovalResponse>> {
+        if args.len() > ICRC37_MAX_REVOKE_APPROVALS as usize {
+            let (error_code, message) = get_batch_size_error(ICRC37_MAX_REVOKE_APPROVALS as usize);
+            return vec![Some(RevokeTokenApprovalResponse::Err(
+                RevokeTokenApprovalError::GenericBatchError {
+                    error_code,
+                    message,
+                },
+            ))];
+        }
+        args.iter()
+            .map(|arg| {
+                Some(match self.icrc37_revoke_token_approval(call_context, arg) {
+                    Ok(index) => RevokeTokenApprovalResponse::Ok(Nat::from(index)),
+                    Err(e) => RevokeTokenApprovalResponse::Err(e),
+                })
+            })
+            .collect()
+    }
+
+    fn icrc37_revoke_token_approval(
+        &self,
+        call_context: &CallContext,
+        arg: &RevokeTokenApprovalArg,
+    ) -> Result<u64, RevokeTokenApprovalError> {
+        if !is_default_subaccount(&arg.from_subaccount) {
+            return Err(RevokeTokenApprovalError::Unauthorized);
+        }
+        let registration = get_unexpired_registration(&arg.token_id, call_context.now.0)
+            .ok_or(RevokeTokenApprovalError::NonExistingTokenId)?;
+        if !registration.is_owner(&call_context.caller) {
+            return Err(RevokeTokenApprovalError::Unauthorized);
+        }
+        let name: FirstLevelName = registration.get_name().into();
+        let approved_to = STATE.with(|s| {
+            let mut store = s.registration_approval_store.borrow_mut();
+            let (approved_to, _) = store
+                .get_approval(&name)
+                .ok_or(RevokeTokenApprovalError::ApprovalDoesNotExist)?;
+            if let Some(spender) = &arg.spender {
+                if get_recipient(spender) != Some(approved_to) {
+                    return Err(RevokeTokenApprovalError::ApprovalDoesNotExist);
+                }
+            }
+            store.remove_approval(&name);
+            info!(
+                "icrc37_revoke_token_approvals: approval of {} to {} is revoked",
+                name, approved_to
+            );
+            Ok(approved_to)
+        })?;
+        let index = Transaction::new(BTYPE_REVOKE)
+            .with_name(&name.to_string())
+            .with_account("from", &call_context.caller)
+            .with_account("spender", &approved_to)
+            .log();
+        Ok(index)
+    }
+
+    /// There is no collection approval to revoke, so no block is logged and there is no transaction index to return
+    pub(crate) fn icrc37_revoke_collection_approvals(
+        &self,
+        args: Vec<RevokeCollectionApprovalArg>,
+    ) -> Vec<Option<RevokeCollectionApprovalResult>> {
+        args.iter()
+            .take(ICRC37_MAX_REVOKE_APPROVALS as usize)
+            .map(|_| {
+                Some(RevokeCollectionApprovalResult::Err(
+                    RevokeCollectionApprovalError::ApprovalDoesNotExist,
+                ))
+            })
+            .collect()
+    }
+
+    pub(crate) fn icrc37_is_approved(&self, args: &[IsApprovedArg], now: u64) -> Vec<bool> {
+        args.iter()
+            .take(ICRC7_MAX_QUERY_BATCH_SIZE)
+            .map(|arg| {
+                if !is_default_subaccount(&arg.from_subaccount) {
+                    return false;
+                }
+                let spender = match get_recipient(&arg.spender) {
+                    Some(spender) => spender,
+                    None => return false,
+                };
+                match get_unexpired_registration(&arg.token_id, now) {
+                    Some(registration) => STATE.with(|s| {
+                        let store = s.registration_approval_store.borrow();
+                        store.is_approved_to(&registration.get_name().into(), &spender)
+                    }),
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    /// There is at most one approval of a name, so nothing is returned after `prev`
+    pub(crate) fn icrc37_get_token_approvals(
+        &self,
+        token_id: &Nat,
+        prev: Option<TokenApproval>,
+        take: Option<Nat>,
+        now: u64,
+    ) -> Vec<TokenApproval> {
+        if prev.is_some() || take == Some(Nat::from(0u32)) {
+            return vec![];
+        }
+        let registration = match get_unexpired_registration(token_id, now) {
+            Some(registration) => registration,
+            None => return vec![],
+        };
+        STATE.with(|s| {
+            let store = s.registration_approval_store.borrow();
+            store
+                .get_approval(&registration.get_name().into())
+                .map(|(approved_to, approval_at)| TokenApproval {
+                    token_id: token_id.clone(),
+                    approval_info: ApprovalInfo {
+                        spender: Account::new(approved_to),
+                        from_subaccount: None,
+                        expires_at: None,
+                        memo: None,
+                        created_at_time: approval_at,
+                    },
+                })
+                .into_iter()
+                .collect()
+        })
+    }
+
+    /// Transfer names approved to caller, transfers are not atomic.
+    /// The index of the transfer block in the ICRC-3 log is returned as transaction index.
+    pub(crate) async fn icrc37_transfer_from(
+        &self,
+        call_context: &CallContext,
+        args: Vec<TransferFromArg>,
+    ) -> Vec<Option<TransferFromResult>> {
+        if args.len() > ICRC7_MAX_UPDATE_BATCH_SIZE {
+            let (error_code, message) = get_batch_size_error(ICRC7_MAX_UPDATE_BATCH_SIZE);
+            return vec![Some(TransferFromResult::Err(
+                TransferFromError::GenericBatchError {
+                    error_code,
+                    message,
+                },
+            ))];
+        }
+        let mut results = Vec::with_capacity(args.len());
+        for arg in args {
+            let result = match self.icrc37_transfer_from_one(call_context, &arg).await {
+                Ok(index) => TransferFromResult::Ok(Nat::from(index)),
+                Err(e) => TransferFromResult::Err(e),
+            };
+            results.push(Some(result));
+        }
+        results
+    }
+
+    async fn icrc37_transfer_from_one(
+        &self,
+        call_context: &CallContext,
+        arg: &TransferFromArg,
+    ) -> Result<u64, TransferFromError> {
+        if !is_default_subaccount(&arg.spender_subaccount) {
+            return Err(TransferFromError::Unauthorized);
+        }
+        let transaction = DeduplicatedTransaction::new(
+            "icrc37_transfer_from",
+            &call_context.caller,
+            arg,
+            arg.created_at_time,
+        );
+        if let Some(transaction) = transaction.as_ref() {
+            transaction.check(call_context.now.0)?;
+        }
+        let from = get_recipient(&arg.from).ok_or(TransferFromError::Unauthorized)?;
+        let to = get_recipient(&arg.to).ok_or(TransferFromError::InvalidRecipient)?;
+        let registration = get_unexpired_registration(&arg.token_id, call_context.now.0)
+            .ok_or(TransferFromError::NonExistingTokenId)?;
+        if !registration.is_owner(&from) {
+            return Err(TransferFromError::Unauthorized);
+        }
+        if to == from {
+            return Err(TransferFromError::InvalidRecipient);
+        }
+
+        let name = registration.get_name();
+        let index = self
+            .transfer_name_from(&call_context.caller, name.as_str(), Some(AuthPrincipal(to)))
+            .await
+            .map_err(|e| match e {
+                NamingError::PermissionDenied => TransferFromError::Unauthorized,
+                e => {
+                    let (error_code, message) = get_generic_error(e);
+                    TransferFromError::GenericError {
+                        error_code,
+                        message,
+                    }
+                }
+            })?;
+        info!(
+            "icrc37_transfer_from: {} is transferred from {} to {} by {}",
+            name, from, to, call_context.caller
+        );
+        if let Some(transaction) = transaction {
+            transaction.applied(index, call_context.now.0);
+        }
+        Ok(index)
+    }
+}
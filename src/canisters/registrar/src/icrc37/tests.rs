@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use candid::Principal;
+use rstest::*;
+
+use common::constants::NAMING_TOP_LABEL;
+use common::icrc::Value;
+use common::icrc3::{GetBlocksArgs, BLOCK_TYPE_KEY};
+use common::TimeInNs;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::icrc3::BTYPE_APPROVE;
+use crate::registration_store::Registration;
+
+use super::*;
+
+fn create_test_name(name: &str) -> String {
+    format!("{}.{}", name, NAMING_TOP_LABEL)
+}
+
+fn register_name(name: &str, owner: Principal, now: u64) {
+    STATE.with(|s| {
+        let name = create_test_name(name);
+        s.token_index_store
+            .borrow_mut()
+            .try_add_registration_name(&name)
+            .unwrap();
+        s.registration_store
+            .borrow_mut()
+            .add_registration(Registration::new(owner, name, now + 1_000_000_000, now));
+    });
+}
+
+fn get_owner(name: &str) -> Principal {
+    STATE.with(|s| {
+        let store = s.registration_store.borrow();
+        store
+            .get_registration(&create_test_name(name).into())
+            .unwrap()
+            .get_owner()
+    })
+}
+
+/// Index of the last block in the ICRC-3 log
+fn get_last_block_index() -> u64 {
+    STATE.with(|s| s.block_log.borrow().len() - 1)
+}
+
+/// Type of the block at index, read back with `icrc3_get_blocks`
+fn get_block_type(service: &RegistrarService, index: &Nat) -> Value {
+    let result = service.icrc3_get_blocks(vec![GetBlocksArgs {
+        start: index.clone(),
+        length: Nat::from(1u32),
+    }]);
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(&result.blocks[0].id, index);
+    match &result.blocks[0].block {
+        Value::Map(entries) => entries
+            .iter()
+            .find(|(key, _)| key == BLOCK_TYPE_KEY)
+            .map(|(_, value)| value.clone())
+            .unwrap(),
+        _ => panic!("block should be a map"),
+    }
+}
+
+#[fixture]
+fn service(_init_test: (), mut mock_registry_api: MockRegistryApi) -> RegistrarService {
+    mock_registry_api
+        .expect_transfer()
+        .returning(|_name, _owner, _resolver| Ok(true));
+    let mut service = RegistrarService::default();
+    service.registry_api = Arc::new(mock_registry_api);
+    service
+}
+
+fn approve_arg(token_id: u32, spender: Principal, now: u64) -> ApproveTokenArg {
+    ApproveTokenArg {
+        token_id: Nat::from(token_id),
+        approval_info: ApprovalInfo {
+            spender: Account::new(spender),
+            from_subaccount: None,
+            expires_at: None,
+            memo: None,
+            created_at_time: now,
+        },
+    }
+}
+
+fn is_approved_arg(token_id: u32, spender: Principal) -> IsApprovedArg {
+    IsApprovedArg {
+        spender: Account::new(spender),
+        from_subaccount: None,
+        token_id: Nat::from(token_id),
+    }
+}
+
+fn transfer_from_arg(token_id: u32, from: Principal, to: Principal) -> TransferFromArg {
+    TransferFromArg {
+        spender_subaccount: None,
+        from: Account::new(from),
+        to: Account::new(to),
+        token_id: Nat::from(token_id),
+        memo: None,
+        created_at_time: None,
+    }
+}
+
+mod approve {
+    use super::*;
+
+    #[rstest]
+    fn test_approve_tokens(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+
+        let result = service
+            .icrc37_approve_tokens(&call_context, vec![approve_arg(1, mock_user2, mock_now)]);
+
+        let index = Nat::from(get_last_block_index());
+        assert_eq!(result, vec![Some(ApproveTokenResult::Ok(index.clone()))]);
+        assert_eq!(
+            get_block_type(&service, &index),
+            Value::Text(BTYPE_APPROVE.to_string())
+        );
+        assert_eq!(
+            service.icrc37_is_approved(
+                &[
+                    is_approved_arg(1, mock_user2),
+                    is_approved_arg(1, mock_user1)
+                ],
+                mock_now
+            ),
+            vec![true, false]
+        );
+        assert_eq!(
+            service.icrc37_get_token_approvals(&Nat::from(1u32), None, None, mock_now),
+            vec![TokenApproval {
+                token_id: Nat::from(1u32),
+                approval_info: ApprovalInfo {
+                    spender: Account::new(mock_user2),
+                    from_subaccount: None,
+                    expires_at: None,
+                    memo: None,
+                    created_at_time: mock_now,
+                },
+            }]
+        );
+    }
+
+    #[rstest]
+    fn test_approve_tokens_errors(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+        let mut with_expiration = approve_arg(1, mock_user3, mock_now);
+        with_expiration.approval_info.expires_at = Some(mock_now);
+
+        let result = service.icrc37_approve_tokens(
+            &call_context,
+            vec![
+                approve_arg(1, mock_user3, mock_now),
+                approve_arg(2, mock_user3, mock_now),
+                approve_arg(1, mock_user2, mock_now),
+                with_expiration,
+            ],
+        );
+
+        assert_eq!(
+            result[0],
+            Some(ApproveTokenResult::Err(ApproveTokenError::Unauthorized))
+        );
+        assert_eq!(
+            result[1],
+            Some(ApproveTokenResult::Err(
+                ApproveTokenError::NonExistingTokenId
+            ))
+        );
+        assert_eq!(
+            result[2],
+            Some(ApproveTokenResult::Err(ApproveTokenError::Unauthorized))
+        );
+        assert!(matches!(
+            result[3],
+            Some(ApproveTokenResult::Err(
+                ApproveTokenError::GenericError { .. }
+            ))
+        ));
+        assert_eq!(
+            service.icrc37_is_approved(&[is_approved_arg(1, mock_user3)], mock_now),
+            vec![false]
+        );
+    }
+
+    #[rstest]
+    fn test_revoke_token_approvals(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        service.icrc37_approve_tokens(&call_context, vec![approve_arg(1, mock_user2, mock_now)]);
+        let revoke_arg = |spender: Principal| RevokeTokenApprovalArg {
+            spender: Some(Account::new(spender)),
+            from_subaccount: None,
+            token_id: Nat::from(1u32),
+            memo: None,
+            created_at_time: None,
+        };
+
+        let result = service.icrc37_revoke_token_approvals(
+            &call_context,
+            vec![
+                revoke_arg(mock_user3),
+                revoke_arg(mock_user2),
+                revoke_arg(mock_user2),
+            ],
+        );
+
+        let index = Nat::from(get_last_block_index());
+        assert_eq!(
+            result,
+            vec![
+                Some(RevokeTokenApprovalResponse::Err(
+                    RevokeTokenApprovalError::ApprovalDoesNotExist
+                )),
+                Some(RevokeTokenApprovalResponse::Ok(index.clone())),
+                Some(RevokeTokenApprovalResponse::Err(
+                    RevokeTokenApprovalError::ApprovalDoesNotExist
+                )),
+            ]
+        );
+        assert_eq!(
+            get_block_type(&service, &index),
+            Value::Text(BTYPE_REVOKE.to_string())
+        );
+        assert_eq!(
+            service.icrc37_is_approved(&[is_approved_arg(1, mock_user2)], mock_now),
+            vec![false]
+        );
+    }
+}
+
+mod transfer_from {
+    use super::*;
+
+    #[rstest]
+    async fn test_transfer_from(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        service.icrc37_approve_tokens(
+            &CallContext::new(mock_user1, TimeInNs(mock_now)),
+            vec![approve_arg(1, mock_user2, mock_now)],
+        );
+        let call_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+
+        let result = service
+            .icrc37_transfer_from(
+                &call_context,
+                vec![transfer_from_arg(1, mock_user1, mock_user3)],
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            vec![Some(TransferFromResult::Ok(Nat::from(
+                get_last_block_index()
+            )))]
+        );
+        assert_eq!(get_owner("hello"), mock_user3);
+        assert_eq!(
+            service.icrc37_is_approved(&[is_approved_arg(1, mock_user2)], mock_now),
+            vec![false]
+        );
+    }
+
+    #[rstest]
+    async fn test_transfer_from_duplicate(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        service.icrc37_approve_tokens(
+            &CallContext::new(mock_user1, TimeInNs(mock_now)),
+            vec![approve_arg(1, mock_user2, mock_now)],
+        );
+        let call_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+        let mut arg = transfer_from_arg(1, mock_user1, mock_user3);
+        arg.created_at_time = Some(mock_now);
+
+        service
+            .icrc37_transfer_from(&call_context, vec![arg.clone()])
+            .await;
+        let index = Nat::from(get_last_block_index());
+        let result = service.icrc37_transfer_from(&call_context, vec![arg]).await;
+
+        assert_eq!(
+            result,
+            vec![Some(TransferFromResult::Err(
+                TransferFromError::Duplicate {
+                    duplicate_of: index
+                }
+            ))]
+        );
+        assert_eq!(get_owner("hello"), mock_user3);
+    }
+
+    #[rstest]
+    async fn test_transfer_from_not_approved(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_name("hello", mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+
+        let result = service
+            .icrc37_transfer_from(
+                &call_context,
+                vec![
+                    transfer_from_arg(1, mock_user1, mock_user3),
+                    transfer_from_arg(1, mock_user3, mock_user2),
+                ],
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            vec![
+                Some(TransferFromResult::Err(TransferFromError::Unauthorized)),
+                Some(TransferFromResult::Err(TransferFromError::Unauthorized)),
+            ]
+        );
+        assert_eq!(get_owner("hello"), mock_user1);
+    }
+}
@@ -0,0 +1,454 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use log::info;
+use num_traits::ToPrimitive;
+use serde_bytes::ByteBuf;
+
+use common::errors::{ErrorInfo, NamingError};
+use common::icrc::{is_default_subaccount, Account, SupportedStandard, Value};
+use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+use common::{AuthPrincipal, CallContext, CanisterId};
+
+use crate::recent_transaction_store::get_transaction_hash;
+use crate::service::{RegistrarService, RegistrationNameQueryContext};
+use crate::state::STATE;
+use crate::token_identifier::{encode_token_id, TokenIndex};
+use crate::token_index_store::UnexpiredRegistrationAggDto;
+
+#[cfg(test)]
+mod tests;
+
+pub const ICRC7_SYMBOL: &str = "ICN";
+pub const ICRC7_NAME: &str = "ICNaming";
+pub const ICRC7_DESCRIPTION: &str = "Names registered in ICNaming";
+pub const ICRC7_MAX_QUERY_BATCH_SIZE: usize = 100;
+// every transfer or approval may call registry, keep update batches small
+pub const ICRC7_MAX_UPDATE_BATCH_SIZE: usize = 20;
+pub const ICRC7_DEFAULT_TAKE_VALUE: usize = 100;
+pub const ICRC7_MAX_TAKE_VALUE: usize = 1000;
+pub const ICRC7_MAX_MEMO_SIZE: usize = 32;
+// transactions with `created_at_time` are deduplicated within the window
+pub const ICRC7_TX_WINDOW_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+pub const ICRC7_PERMITTED_DRIFT_NS: u64 = 2 * 60 * 1_000_000_000;
+
+pub type Icrc7Metadata = Vec<(String, Value)>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferArg {
+    pub from_subaccount: Option<ByteBuf>,
+    pub to: Account,
+    pub token_id: Nat,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum TransferError {
+    NonExistingTokenId,
+    InvalidRecipient,
+    Unauthorized,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum TransferResult {
+    Ok(Nat),
+    Err(TransferError),
+}
+
+/// Error code and message of a failed call to registry, reported as `GenericError`
+pub(crate) fn get_generic_error(error: NamingError) -> (Nat, String) {
+    let error: ErrorInfo = error.into();
+    (Nat::from(error.code), error.message)
+}
+
+pub(crate) fn get_batch_size_error(max: usize) -> (Nat, String) {
+    (
+        Nat::from(1u32),
+        format!("batch size should not be greater than {}", max),
+    )
+}
+
+/// Reason of a transaction with `created_at_time` to be rejected
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum DeduplicationError {
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u64 },
+}
+
+impl From<DeduplicationError> for TransferError {
+    fn from(error: DeduplicationError) -> Self {
+        match error {
+            DeduplicationError::TooOld => TransferError::TooOld,
+            DeduplicationError::CreatedInFuture { ledger_time } => {
+                TransferError::CreatedInFuture { ledger_time }
+            }
+            DeduplicationError::Duplicate { duplicate_of } => TransferError::Duplicate {
+                duplicate_of: Nat::from(duplicate_of),
+            },
+        }
+    }
+}
+
+/// Transaction submitted with `created_at_time`, which is applied at most once within the transaction window
+pub(crate) struct DeduplicatedTransaction {
+    created_at_time: u64,
+    hash: String,
+}
+
+impl DeduplicatedTransaction {
+    /// Transactions without `created_at_time` are not deduplicated
+    pub(crate) fn new<T: CandidType>(
+        method: &str,
+        caller: &Principal,
+        arg: &T,
+        created_at_time: Option<u64>,
+    ) -> Option<Self> {
+        created_at_time.map(|created_at_time| DeduplicatedTransaction {
+            created_at_time,
+            hash: get_transaction_hash(method, caller, arg),
+        })
+    }
+
+    pub(crate) fn check(&self, now: u64) -> Result<(), DeduplicationError> {
+        if self
+            .created_at_time
+            .saturating_add(ICRC7_TX_WINDOW_NS + ICRC7_PERMITTED_DRIFT_NS)
+            < now
+        {
+            return Err(DeduplicationError::TooOld);
+        }
+        if self.created_at_time > now + ICRC7_PERMITTED_DRIFT_NS {
+            return Err(DeduplicationError::CreatedInFuture { ledger_time: now });
+        }
+        STATE.with(|s| {
+            let store = s.recent_transaction_store.borrow();
+            match store.get_transaction_index(self.created_at_time, &self.hash) {
+                Some(index) => Err(DeduplicationError::Duplicate {
+                    duplicate_of: index,
+                }),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Keep the applied transaction, transactions too old to be submitted again are removed
+    pub(crate) fn applied(self, index: u64, now: u64) {
+        STATE.with(|s| {
+            let mut store = s.recent_transaction_store.borrow_mut();
+            store.remove_created_before(
+                now.saturating_sub(ICRC7_TX_WINDOW_NS + ICRC7_PERMITTED_DRIFT_NS),
+            );
+            store.add_transaction(self.created_at_time, self.hash, index);
+        });
+    }
+}
+
+/// Token id of ICRC-7 is the token index of EXT
+pub(crate) fn get_unexpired_registration(
+    token_id: &Nat,
+    now: u64,
+) -> Option<UnexpiredRegistrationAggDto> {
+    let index = token_id.0.to_u32()?;
+    let id = encode_token_id(
+        CanisterId(get_named_get_canister_id(CanisterNames::Registrar)),
+        TokenIndex(index),
+    );
+    STATE.with(|s| {
+        let token_index_store = s.token_index_store.borrow();
+        let registration_store = s.registration_store.borrow();
+        let query = RegistrationNameQueryContext::new(&token_index_store, &registration_store);
+        query.get_unexpired_registration(&id, now).ok()
+    })
+}
+
+/// Names are owned by principals, only the default subaccount of the owner is accepted
+pub(crate) fn get_recipient(account: &Account) -> Option<Principal> {
+    account
+        .get_default_owner()
+        .filter(|owner| *owner != Principal::anonymous())
+}
+
+fn get_token_metadata(registration: &UnexpiredRegistrationAggDto) -> Icrc7Metadata {
+    let image = format!(
+        "https://{}.raw.ic0.app/?tokenid={}",
+        get_named_get_canister_id(CanisterNames::Registrar),
+        registration.get_id()
+    );
+    vec![
+        ("name".to_string(), Value::Text(registration.get_name())),
+        (
+            "expired_at".to_string(),
+            Value::Nat(Nat::from(registration.get_expired_at())),
+        ),
+        ("image".to_string(), Value::Text(image)),
+    ]
+}
+
+fn get_take_value(take: Option<Nat>) -> usize {
+    take.and_then(|take| take.0.to_usize())
+        .unwrap_or(ICRC7_DEFAULT_TAKE_VALUE)
+        .min(ICRC7_MAX_TAKE_VALUE)
+}
+
+fn page_token_ids(mut token_ids: Vec<u32>, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    token_ids.sort();
+    let prev = prev.and_then(|prev| prev.0.to_u32());
+    let take = get_take_value(take);
+    token_ids
+        .into_iter()
+        .filter(|id| prev.map_or(true, |prev| *id > prev))
+        .take(take)
+        .map(Nat::from)
+        .collect()
+}
+
+pub fn get_supported_standards() -> Vec<SupportedStandard> {
+    vec![
+        SupportedStandard {
+            name: "ICRC-3".to_string(),
+            url: "https://github.com/dfinity/ICRC/ICRCs/ICRC-3".to_string(),
+        },
+        SupportedStandard {
+            name: "ICRC-7".to_string(),
+            url: "https://github.com/dfinity/ICRC/ICRCs/ICRC-7".to_string(),
+        },
+        SupportedStandard {
+            name: "ICRC-10".to_string(),
+            url: "https://github.com/dfinity/ICRC/ICRCs/ICRC-10".to_string(),
+        },
+        SupportedStandard {
+            name: "ICRC-37".to_string(),
+            url: "https://github.com/dfinity/ICRC/ICRCs/ICRC-37".to_string(),
+        },
+    ]
+}
+
+impl RegistrarService {
+    pub(crate) fn icrc7_collection_metadata(&self, now: u64) -> Icrc7Metadata {
+        vec![
+            (
+                "icrc7:symbol".to_string(),
+                Value::Text(ICRC7_SYMBOL.to_string()),
+            ),
+            (
+                "icrc7:name".to_string(),
+                Value::Text(ICRC7_NAME.to_string()),
+            ),
+            (
+                "icrc7:description".to_string(),
+                Value::Text(ICRC7_DESCRIPTION.to_string()),
+            ),
+            (
+                "icrc7:total_supply".to_string(),
+                Value::Nat(self.icrc7_total_supply(now)),
+            ),
+            (
+                "icrc7:max_query_batch_size".to_string(),
+                Value::Nat(Nat::from(ICRC7_MAX_QUERY_BATCH_SIZE as u64)),
+            ),
+            (
+                "icrc7:max_update_batch_size".to_string(),
+                Value::Nat(Nat::from(ICRC7_MAX_UPDATE_BATCH_SIZE as u64)),
+            ),
+            (
+                "icrc7:default_take_value".to_string(),
+                Value::Nat(Nat::from(ICRC7_DEFAULT_TAKE_VALUE as u64)),
+            ),
+            (
+                "icrc7:max_take_value".to_string(),
+                Value::Nat(Nat::from(ICRC7_MAX_TAKE_VALUE as u64)),
+            ),
+            (
+                "icrc7:max_memo_size".to_string(),
+                Value::Nat(Nat::from(ICRC7_MAX_MEMO_SIZE as u64)),
+            ),
+            (
+                "icrc7:tx_window".to_string(),
+                Value::Nat(Nat::from(ICRC7_TX_WINDOW_NS / 1_000_000_000)),
+            ),
+            (
+                "icrc7:permitted_drift".to_string(),
+                Value::Nat(Nat::from(ICRC7_PERMITTED_DRIFT_NS / 1_000_000_000)),
+            ),
+        ]
+    }
+
+    /// Count of names which are not expired
+    pub(crate) fn icrc7_total_supply(&self, now: u64) -> Nat {
+        STATE.with(|s| {
+            let registration_store = s.registration_store.borrow();
+            Nat::from(registration_store.get_unexpired_registration_count(now))
+        })
+    }
+
+    pub(crate) fn icrc7_token_metadata(
+        &self,
+        token_ids: &[Nat],
+        now: u64,
+    ) -> Vec<Option<Icrc7Metadata>> {
+        token_ids
+            .iter()
+            .take(ICRC7_MAX_QUERY_BATCH_SIZE)
+            .map(|token_id| {
+                get_unexpired_registration(token_id, now)
+                    .map(|registration| get_token_metadata(&registration))
+            })
+            .collect()
+    }
+
+    pub(crate) fn icrc7_owner_of(&self, token_ids: &[Nat], now: u64) -> Vec<Option<Account>> {
+        token_ids
+            .iter()
+            .take(ICRC7_MAX_QUERY_BATCH_SIZE)
+            .map(|token_id| {
+                get_unexpired_registration(token_id, now)
+                    .map(|registration| Account::new(registration.get_owner()))
+            })
+            .collect()
+    }
+
+    pub(crate) fn icrc7_balance_of(&self, accounts: &[Account], now: u64) -> Vec<Nat> {
+        accounts
+            .iter()
+            .take(ICRC7_MAX_QUERY_BATCH_SIZE)
+            .map(|account| Nat::from(self.get_unexpired_token_ids_of(account, now).len() as u64))
+            .collect()
+    }
+
+    /// Token ids of names which are not expired, names are visited by token index from `prev`
+    pub(crate) fn icrc7_tokens(&self, prev: Option<Nat>, take: Option<Nat>, now: u64) -> Vec<Nat> {
+        let prev = prev.and_then(|prev| prev.0.to_u32()).map(TokenIndex);
+        let take = get_take_value(take);
+        STATE.with(|s| {
+            let token_index_store = s.token_index_store.borrow();
+            let registration_store = s.registration_store.borrow();
+            token_index_store
+                .get_registrations_after(prev)
+                .filter(|registration_name| {
+                    registration_store
+                        .get_registration_by_name(&registration_name.get_name())
+                        .map_or(false, |registration| !registration.is_expired(now))
+                })
+                .take(take)
+                .map(|registration_name| Nat::from(registration_name.get_index().get_value()))
+                .collect()
+        })
+    }
+
+    pub(crate) fn icrc7_tokens_of(
+        &self,
+        account: &Account,
+        prev: Option<Nat>,
+        take: Option<Nat>,
+        now: u64,
+    ) -> Vec<Nat> {
+        let token_ids = self.get_unexpired_token_ids_of(account, now);
+        page_token_ids(token_ids, prev, take)
+    }
+
+    fn get_unexpired_token_ids_of(&self, account: &Account, now: u64) -> Vec<u32> {
+        let owner = match get_recipient(account) {
+            Some(owner) => owner,
+            None => return vec![],
+        };
+        STATE.with(|s| {
+            let token_index_store = s.token_index_store.borrow();
+            let registration_store = s.registration_store.borrow();
+            let query = RegistrationNameQueryContext::new(&token_index_store, &registration_store);
+            query
+                .get_unexpired_token_index_of_registrations_by_owners(
+                    &vec![AuthPrincipal(owner)],
+                    now,
+                )
+                .remove(&owner)
+                .unwrap_or_default()
+                .iter()
+                .map(|index| index.get_value())
+                .collect()
+        })
+    }
+
+    /// Transfer names owned by caller, transfers are not atomic.
+    /// The index of the transfer block in the ICRC-3 log is returned as transaction index.
+    pub(crate) async fn icrc7_transfer(
+        &self,
+        call_context: &CallContext,
+        args: Vec<TransferArg>,
+    ) -> Vec<Option<TransferResult>> {
+        if args.len() > ICRC7_MAX_UPDATE_BATCH_SIZE {
+            let (error_code, message) = get_batch_size_error(ICRC7_MAX_UPDATE_BATCH_SIZE);
+            return vec![Some(TransferResult::Err(
+                TransferError::GenericBatchError {
+                    error_code,
+                    message,
+                },
+            ))];
+        }
+        let mut results = Vec::with_capacity(args.len());
+        for arg in args {
+            let result = match self.icrc7_transfer_one(call_context, &arg).await {
+                Ok(index) => TransferResult::Ok(Nat::from(index)),
+                Err(e) => TransferResult::Err(e),
+            };
+            results.push(Some(result));
+        }
+        results
+    }
+
+    async fn icrc7_transfer_one(
+        &self,
+        call_context: &CallContext,
+        arg: &TransferArg,
+    ) -> Result<u64, TransferError> {
+        if arg.memo.as_ref().map_or(0, |memo| memo.len()) > ICRC7_MAX_MEMO_SIZE {
+            return Err(TransferError::GenericError {
+                error_code: Nat::from(1u32),
+                message: format!("memo should not be longer than {}", ICRC7_MAX_MEMO_SIZE),
+            });
+        }
+        if !is_default_subaccount(&arg.from_subaccount) {
+            return Err(TransferError::Unauthorized);
+        }
+        // checked before the owner, since the owner is changed by the transaction to be deduplicated
+        let transaction = DeduplicatedTransaction::new(
+            "icrc7_transfer",
+            &call_context.caller,
+            arg,
+            arg.created_at_time,
+        );
+        if let Some(transaction) = transaction.as_ref() {
+            transaction.check(call_context.now.0)?;
+        }
+        let to = get_recipient(&arg.to).ok_or(TransferError::InvalidRecipient)?;
+        let registration = get_unexpired_registration(&arg.token_id, call_context.now.0)
+            .ok_or(TransferError::NonExistingTokenId)?;
+        if !registration.is_owner(&call_context.caller) {
+            return Err(TransferError::Unauthorized);
+        }
+        if to == call_context.caller {
+            return Err(TransferError::InvalidRecipient);
+        }
+
+        let name = registration.get_name();
+        let index = self
+            .transfer_name(name.as_str(), &call_context.caller, to)
+            .await
+            .map_err(|e| {
+                let (error_code, message) = get_generic_error(e);
+                TransferError::GenericError {
+                    error_code,
+                    message,
+                }
+            })?;
+        info!("icrc7_transfer: {} is transferred to {}", name, to);
+        if let Some(transaction) = transaction {
+            transaction.applied(index, call_context.now.0);
+        }
+        Ok(index)
+    }
+}
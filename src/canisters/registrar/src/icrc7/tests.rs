@@ -0,0 +1,344 @@
+use std::sync::Arc;
+
+use candid::Principal;
+use rstest::*;
+
+use common::constants::NAMING_TOP_LABEL;
+use common::TimeInNs;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::registration_store::Registration;
+
+use super::*;
+
+const EXPIRED_AT_OFFSET: u64 = 1_000_000_000;
+
+fn create_test_name(name: &str) -> String {
+    format!("{}.{}", name, NAMING_TOP_LABEL)
+}
+
+/// Register names with token indexes starting from 1
+fn register_names(names: &[&str], owner: Principal, now: u64) {
+    STATE.with(|s| {
+        let mut token_index_store = s.token_index_store.borrow_mut();
+        let mut registration_store = s.registration_store.borrow_mut();
+        for name in names {
+            let name = create_test_name(name);
+            token_index_store.try_add_registration_name(&name).unwrap();
+            registration_store.add_registration(Registration::new(
+                owner,
+                name,
+                now + EXPIRED_AT_OFFSET,
+                now,
+            ));
+        }
+    });
+}
+
+fn get_owner(name: &str) -> Principal {
+    STATE.with(|s| {
+        let store = s.registration_store.borrow();
+        store
+            .get_registration(&create_test_name(name).into())
+            .unwrap()
+            .get_owner()
+    })
+}
+
+/// Index of the last block in the ICRC-3 log
+fn get_last_block_index() -> u64 {
+    STATE.with(|s| s.block_log.borrow().len() - 1)
+}
+
+#[fixture]
+fn service(_init_test: (), mut mock_registry_api: MockRegistryApi) -> RegistrarService {
+    mock_registry_api
+        .expect_transfer()
+        .returning(|_name, _owner, _resolver| Ok(true));
+    let mut service = RegistrarService::default();
+    service.registry_api = Arc::new(mock_registry_api);
+    service
+}
+
+fn transfer_arg(token_id: u32, to: Principal) -> TransferArg {
+    TransferArg {
+        from_subaccount: None,
+        to: Account::new(to),
+        token_id: Nat::from(token_id),
+        memo: None,
+        created_at_time: None,
+    }
+}
+
+mod query {
+    use super::*;
+
+    #[rstest]
+    fn test_token_metadata(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        register_names(&["hello"], mock_user1, mock_now);
+
+        let result = service.icrc7_token_metadata(&[Nat::from(1u32), Nat::from(2u32)], mock_now);
+
+        assert_eq!(result.len(), 2);
+        let metadata = result[0].as_ref().unwrap();
+        assert_eq!(
+            metadata[0],
+            ("name".to_string(), Value::Text(create_test_name("hello")))
+        );
+        assert_eq!(
+            metadata[1],
+            (
+                "expired_at".to_string(),
+                Value::Nat(Nat::from(mock_now + EXPIRED_AT_OFFSET))
+            )
+        );
+        assert!(
+            matches!(&metadata[2], (key, Value::Text(url)) if key == "image" && url.contains("?tokenid="))
+        );
+        assert_eq!(result[1], None);
+    }
+
+    #[rstest]
+    fn test_expired_name_is_not_listed(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["hello"], mock_user1, mock_now);
+        let expired = mock_now + EXPIRED_AT_OFFSET + 1;
+
+        assert_eq!(
+            service.icrc7_owner_of(&[Nat::from(1u32)], mock_now),
+            vec![Some(Account::new(mock_user1))]
+        );
+        assert_eq!(
+            service.icrc7_owner_of(&[Nat::from(1u32)], expired),
+            vec![None]
+        );
+        assert_eq!(service.icrc7_total_supply(expired), Nat::from(0u32));
+        assert!(service.icrc7_tokens(None, None, expired).is_empty());
+    }
+
+    #[rstest]
+    fn test_tokens_skip_expired_names(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["a1", "a2", "a3", "a4"], mock_user1, mock_now);
+        STATE.with(|s| {
+            s.registration_store
+                .borrow_mut()
+                .update_expired_at(&create_test_name("a2").into(), mock_now - 1);
+        });
+
+        assert_eq!(service.icrc7_total_supply(mock_now), Nat::from(3u32));
+        assert_eq!(
+            service.icrc7_tokens(None, Some(Nat::from(2u32)), mock_now),
+            vec![Nat::from(1u32), Nat::from(3u32)]
+        );
+        assert_eq!(
+            service.icrc7_tokens(Some(Nat::from(1u32)), Some(Nat::from(2u32)), mock_now),
+            vec![Nat::from(3u32), Nat::from(4u32)]
+        );
+        assert!(service
+            .icrc7_tokens(Some(Nat::from(4u32)), None, mock_now)
+            .is_empty());
+    }
+
+    #[rstest]
+    fn test_tokens_of_and_balance_of(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["a1", "a2", "a3"], mock_user1, mock_now);
+        register_names(&["b1"], mock_user2, mock_now);
+        let account = Account::new(mock_user1);
+
+        assert_eq!(
+            service.icrc7_tokens_of(&account, None, None, mock_now),
+            vec![Nat::from(1u32), Nat::from(2u32), Nat::from(3u32)]
+        );
+        assert_eq!(
+            service.icrc7_tokens_of(
+                &account,
+                Some(Nat::from(1u32)),
+                Some(Nat::from(1u32)),
+                mock_now
+            ),
+            vec![Nat::from(2u32)]
+        );
+        assert_eq!(
+            service.icrc7_tokens(Some(Nat::from(2u32)), None, mock_now),
+            vec![Nat::from(3u32), Nat::from(4u32)]
+        );
+
+        let sub_account = Account {
+            owner: mock_user1,
+            subaccount: Some(ByteBuf::from(vec![1u8; 32])),
+        };
+        assert_eq!(
+            service.icrc7_balance_of(&[account, sub_account], mock_now),
+            vec![Nat::from(3u32), Nat::from(0u32)]
+        );
+    }
+}
+
+mod transfer {
+    use super::*;
+
+    #[rstest]
+    async fn test_transfer(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["hello"], mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+
+        let result = service
+            .icrc7_transfer(&call_context, vec![transfer_arg(1, mock_user2)])
+            .await;
+
+        assert_eq!(
+            result,
+            vec![Some(TransferResult::Ok(Nat::from(get_last_block_index())))]
+        );
+        assert_eq!(get_owner("hello"), mock_user2);
+    }
+
+    #[rstest]
+    async fn test_transfer_errors(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["hello", "world"], mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        let mut to_sub_account = transfer_arg(1, mock_user2);
+        to_sub_account.to.subaccount = Some(ByteBuf::from(vec![1u8; 32]));
+
+        let result = service
+            .icrc7_transfer(
+                &call_context,
+                vec![
+                    transfer_arg(3, mock_user2),
+                    transfer_arg(1, mock_user1),
+                    to_sub_account,
+                    transfer_arg(2, mock_user3),
+                ],
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            vec![
+                Some(TransferResult::Err(TransferError::NonExistingTokenId)),
+                Some(TransferResult::Err(TransferError::InvalidRecipient)),
+                Some(TransferResult::Err(TransferError::InvalidRecipient)),
+                Some(TransferResult::Ok(Nat::from(get_last_block_index()))),
+            ]
+        );
+        assert_eq!(get_owner("hello"), mock_user1);
+        assert_eq!(get_owner("world"), mock_user3);
+    }
+
+    #[rstest]
+    async fn test_transfer_duplicate(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["hello"], mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        let mut arg = transfer_arg(1, mock_user2);
+        arg.created_at_time = Some(mock_now);
+
+        let result = service
+            .icrc7_transfer(&call_context, vec![arg.clone()])
+            .await;
+        let index = Nat::from(get_last_block_index());
+        assert_eq!(result, vec![Some(TransferResult::Ok(index.clone()))]);
+
+        let result = service.icrc7_transfer(&call_context, vec![arg]).await;
+        assert_eq!(
+            result,
+            vec![Some(TransferResult::Err(TransferError::Duplicate {
+                duplicate_of: index
+            }))]
+        );
+    }
+
+    #[rstest]
+    #[case(mock_now() - ICRC7_TX_WINDOW_NS - ICRC7_PERMITTED_DRIFT_NS - 1, TransferError::TooOld)]
+    #[case(mock_now() + ICRC7_PERMITTED_DRIFT_NS + 1, TransferError::CreatedInFuture {
+        ledger_time: mock_now()
+    })]
+    async fn test_transfer_created_at_time_out_of_window(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        #[case] created_at_time: u64,
+        #[case] error: TransferError,
+    ) {
+        register_names(&["hello"], mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        let mut arg = transfer_arg(1, mock_user2);
+        arg.created_at_time = Some(created_at_time);
+
+        let result = service.icrc7_transfer(&call_context, vec![arg]).await;
+
+        assert_eq!(result, vec![Some(TransferResult::Err(error))]);
+        assert_eq!(get_owner("hello"), mock_user1);
+    }
+
+    #[rstest]
+    async fn test_transfer_not_owner(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        register_names(&["hello"], mock_user1, mock_now);
+        let call_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+
+        let result = service
+            .icrc7_transfer(&call_context, vec![transfer_arg(1, mock_user2)])
+            .await;
+
+        assert_eq!(
+            result,
+            vec![Some(TransferResult::Err(TransferError::Unauthorized))]
+        );
+    }
+
+    #[rstest]
+    async fn test_transfer_batch_too_large(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        let args = (0..=ICRC7_MAX_UPDATE_BATCH_SIZE as u32)
+            .map(|id| transfer_arg(id, mock_user2))
+            .collect();
+
+        let result = service.icrc7_transfer(&call_context, args).await;
+
+        assert_eq!(result.len(), 1);
+        assert!(matches!(
+            result[0],
+            Some(TransferResult::Err(TransferError::GenericBatchError { .. }))
+        ));
+    }
+}
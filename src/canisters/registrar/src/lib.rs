@@ -1,9 +1,14 @@
+mod certification;
+mod commitment_store;
 mod http;
 mod name_locker;
+mod payment_token_store;
 mod periodic_tasks_runner;
 mod quota_import_store;
+mod recent_transaction_store;
 mod registration_approval_store;
 mod registration_store;
+mod released_name_store;
 mod reserved_list;
 mod service;
 mod settings;
@@ -16,13 +21,17 @@ mod shared_actor;
 mod stats_service;
 mod token_service;
 
+mod block_log;
 mod http_nft;
+mod icrc3;
+mod icrc37;
+mod icrc7;
 mod nft;
 mod token_identifier;
 mod token_index_store;
 
 use crate::state::InitArgs;
-use candid::{candid_method, CandidType, Deserialize, Principal};
+use candid::{candid_method, CandidType, Deserialize, Nat, Principal};
 use common::constants::is_env;
 use common::constants::NamingEnv::Production;
 use common::dto::*;
@@ -33,19 +42,42 @@ use log::debug;
 use stats_service::*;
 use std::collections::HashMap;
 
+use crate::icrc3::get_supported_block_types;
+use crate::icrc37::{
+    ApproveCollectionArg, ApproveCollectionResult, ApproveTokenArg, ApproveTokenResult,
+    CollectionApproval, IsApprovedArg, RevokeCollectionApprovalArg, RevokeCollectionApprovalResult,
+    RevokeTokenApprovalArg, RevokeTokenApprovalResponse, TokenApproval, TransferFromArg,
+    TransferFromResult, ICRC37_MAX_APPROVALS_PER_TOKEN_OR_COLLECTION, ICRC37_MAX_REVOKE_APPROVALS,
+};
+use crate::icrc7::{
+    get_supported_standards, Icrc7Metadata, TransferArg, TransferResult, ICRC7_DEFAULT_TAKE_VALUE,
+    ICRC7_DESCRIPTION, ICRC7_MAX_MEMO_SIZE, ICRC7_MAX_QUERY_BATCH_SIZE, ICRC7_MAX_TAKE_VALUE,
+    ICRC7_MAX_UPDATE_BATCH_SIZE, ICRC7_NAME, ICRC7_PERMITTED_DRIFT_NS, ICRC7_SYMBOL,
+    ICRC7_TX_WINDOW_NS,
+};
 use crate::nft::{
     AllowanceRequest, ApproveRequest, CommonError, Metadata, NFTServiceResult,
     NFTTransferServiceResult, TransferError, TransferRequest,
 };
 use crate::token_identifier::{TokenIdentifier, TokenIndex};
 use common::canister_api::AccountIdentifier;
-use common::dto::{GetPageInput, GetPageOutput, ImportQuotaRequest, ImportQuotaStatus};
+use common::certified_map::CertifiedValue;
+use common::dto::{
+    GetCursorPageInput, GetCursorPageOutput, GetPageInput, GetPageOutput, ImportQuotaRequest,
+    ImportQuotaStatus,
+};
 use common::errors::{BooleanActorResponse, ErrorInfo, ServiceResult};
-use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+use common::icrc::{Account, SupportedStandard};
+use common::icrc3::{
+    ArchiveInfo, DataCertificate, GetArchivesArgs, GetBlocksArgs, GetBlocksResult,
+    SupportedBlockType,
+};
+use common::named_canister_ids::{get_named_get_canister_id, is_named_canister_id, CanisterNames};
 use common::named_principals::PRINCIPAL_NAME_TIMER_TRIGGER;
-use common::permissions::must_be_named_principal;
+use common::permissions::{must_be_named_principal, must_not_anonymous};
 use common::{CallContext, TimeInNs};
 
+use crate::payment_token_store::PaymentToken;
 use crate::periodic_tasks_runner::run_periodic_tasks;
 use crate::registration_store::{RegistrationDetails, RegistrationDto};
 use crate::service::*;
@@ -68,10 +100,38 @@ pub async fn run_tasks() -> BooleanActorResponse {
 #[candid_method(update)]
 pub async fn get_price_table() -> GetPriceTableResponse {
     let service = RegistrarService::default();
-    let price_table = service.get_price_table().await;
+    let now = api::time();
+    let price_table = service.get_price_table(TimeInNs(now)).await;
     GetPriceTableResponse::new(price_table)
 }
 
+/// Premiums of released names ordered by name, the first page is also in the price table
+#[update(name = "get_release_premiums")]
+#[candid_method(update)]
+pub async fn get_release_premiums(input: GetCursorPageInput) -> GetReleasePremiumsResponse {
+    let service = RegistrarService::default();
+    let now = api::time();
+    let result = service.get_release_premiums(TimeInNs(now), &input).await;
+    GetReleasePremiumsResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetReleasePremiumsResponse {
+    Ok(GetCursorPageOutput<ReleasePremiumItem>),
+    Err(ErrorInfo),
+}
+
+impl GetReleasePremiumsResponse {
+    pub fn new(
+        result: ServiceResult<GetCursorPageOutput<ReleasePremiumItem>>,
+    ) -> GetReleasePremiumsResponse {
+        match result {
+            Ok(output) => GetReleasePremiumsResponse::Ok(output),
+            Err(err) => GetReleasePremiumsResponse::Err(err.into()),
+        }
+    }
+}
+
 #[derive(CandidType)]
 pub enum GetPriceTableResponse {
     Ok(PriceTable),
@@ -120,6 +180,7 @@ impl GetNameExpiresActorResponse {
     }
 }
 
+/// Deprecated: the name is exposed before it is registered, use `commit` and `reveal_register_with_quota` instead.
 #[update(name = "register_for")]
 #[candid_method(update)]
 pub async fn register_for(name: String, owner: Principal, years: u64) -> BooleanActorResponse {
@@ -141,6 +202,7 @@ pub async fn register_for(name: String, owner: Principal, years: u64) -> Boolean
     BooleanActorResponse::new(result)
 }
 
+/// Deprecated: the name is exposed before it is registered, use `commit` and `reveal_register_with_quota` instead.
 #[update(name = "register_with_quota")]
 #[candid_method(update)]
 pub async fn register_with_quota(name: String, quota_type: QuotaType) -> BooleanActorResponse {
@@ -176,6 +238,7 @@ pub async fn register_from_gateway(name: String, owner: Principal) -> BooleanAct
     BooleanActorResponse::new(result)
 }
 
+/// Deprecated: the name is exposed before it is registered, use `commit` and `reveal_register_with_payment` instead.
 #[update(name = "register_with_payment")]
 #[candid_method(update)]
 pub async fn register_with_payment(
@@ -187,6 +250,45 @@ pub async fn register_with_payment(
     GetDetailsActorResponse::new(result)
 }
 
+/// Commit a name to be registered without exposing it.
+/// Returns true if success
+///
+/// * `commitment` - hex encoded sha256 hash of candid encoded (name, owner, secret)
+#[update(name = "commit")]
+#[candid_method(update)]
+pub fn commit(commitment: String) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = RegistrarService::default();
+    let result = service.commit(call_context, commitment);
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "reveal_register_with_payment")]
+#[candid_method(update)]
+pub async fn reveal_register_with_payment(
+    request: RevealRegisterWithPaymentRequest,
+) -> GetDetailsActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = RegistrarService::default();
+    let result = service
+        .reveal_register_with_payment(call_context, request)
+        .await;
+    GetDetailsActorResponse::new(result)
+}
+
+#[update(name = "reveal_register_with_quota")]
+#[candid_method(update)]
+pub async fn reveal_register_with_quota(
+    request: RevealRegisterWithQuotaRequest,
+) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = RegistrarService::default();
+    let result = service
+        .reveal_register_with_quota(call_context, request)
+        .await;
+    BooleanActorResponse::new(result)
+}
+
 #[query(name = "get_names")]
 #[candid_method(query)]
 pub fn get_names(owner: Principal, input: GetPageInput) -> GetNamesActorResponse {
@@ -210,6 +312,34 @@ impl GetNamesActorResponse {
     }
 }
 
+#[query(name = "get_names_by_cursor")]
+#[candid_method(query)]
+pub fn get_names_by_cursor(
+    owner: Principal,
+    input: GetCursorPageInput,
+) -> GetNamesByCursorActorResponse {
+    let service = RegistrarService::default();
+    let result = service.get_names_by_cursor(&owner, &input);
+    GetNamesByCursorActorResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetNamesByCursorActorResponse {
+    Ok(GetCursorPageOutput<RegistrationDto>),
+    Err(ErrorInfo),
+}
+
+impl GetNamesByCursorActorResponse {
+    pub fn new(
+        result: ServiceResult<GetCursorPageOutput<RegistrationDto>>,
+    ) -> GetNamesByCursorActorResponse {
+        match result {
+            Ok(output) => GetNamesByCursorActorResponse::Ok(output),
+            Err(err) => GetNamesByCursorActorResponse::Err(err.into()),
+        }
+    }
+}
+
 #[query(name = "get_names_count")]
 #[candid_method(query)]
 pub fn get_names_count(owner: Principal) -> GetNamesCountActorResponse {
@@ -256,6 +386,35 @@ impl GetOwnerActorResponse {
     }
 }
 
+/// Get owner of name with a certificate.
+/// Returns the owner, the witness of `owner/{name}` and the certificate.
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_owner_certified")]
+#[candid_method(query)]
+pub fn get_owner_certified(name: String) -> GetOwnerCertifiedActorResponse {
+    let service = RegistrarService::default();
+    let result = service
+        .get_owner_certified(&name)
+        .map(|value| value.with_certificate());
+    GetOwnerCertifiedActorResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetOwnerCertifiedActorResponse {
+    Ok(CertifiedValue<Principal>),
+    Err(ErrorInfo),
+}
+
+impl GetOwnerCertifiedActorResponse {
+    pub fn new(result: ServiceResult<CertifiedValue<Principal>>) -> GetOwnerCertifiedActorResponse {
+        match result {
+            Ok(owner) => GetOwnerCertifiedActorResponse::Ok(owner),
+            Err(err) => GetOwnerCertifiedActorResponse::Err(err.into()),
+        }
+    }
+}
+
 #[query(name = "get_details")]
 #[candid_method(query)]
 pub fn get_details(name: String) -> GetDetailsActorResponse {
@@ -312,15 +471,47 @@ pub fn get_last_registrations() -> GetAllDetailsActorResponse {
     GetAllDetailsActorResponse::new(result)
 }
 
+/// Accept a token as payment by ICRC-2, or update its price feed
+///
+/// * `token` - ledger, symbol, decimals, fee and price feed of the token
+#[update(name = "set_payment_token")]
+#[candid_method(update)]
+pub fn set_payment_token(token: PaymentToken) -> BooleanActorResponse {
+    let caller = &api::caller();
+    let service = RegistrarService::default();
+    let result = service.set_payment_token(caller, token);
+    BooleanActorResponse::new(result)
+}
+
+/// Stop accepting a token as payment, pending refunds of the token are not affected
+///
+/// * `ledger` - ledger canister id of the token
+#[update(name = "remove_payment_token")]
+#[candid_method(update)]
+pub fn remove_payment_token(ledger: Principal) -> BooleanActorResponse {
+    let caller = &api::caller();
+    let service = RegistrarService::default();
+    let result = service.remove_payment_token(caller, ledger);
+    BooleanActorResponse::new(result)
+}
+
+/// Tokens accepted as payment besides DICP
+#[query(name = "get_payment_tokens")]
+#[candid_method(query)]
+pub fn get_payment_tokens() -> Vec<PaymentToken> {
+    let service = RegistrarService::default();
+    service.get_payment_tokens()
+}
+
 #[update(name = "add_quota")]
 #[candid_method(update)]
 pub fn add_quota(quota_owner: Principal, quota_type: QuotaType, diff: u32) -> BooleanActorResponse {
-    if is_env(Production) {
-        // it should be always false in production, no one can add quotas in production.
-        // all quotas in production should be imported by import_quota.
+    let caller = &api::caller();
+    if is_env(Production) && !is_named_canister_id(CanisterNames::MysteryBox, *caller) {
+        // it should be always false in production, only mystery box can add quotas in production.
+        // all other quotas in production should be imported by import_quota.
         BooleanActorResponse::new(Ok(false))
     } else {
-        let caller = &api::caller();
         debug!("add_quota: caller: {}", caller);
 
         let mut service = RegistrarService::default();
@@ -329,6 +520,30 @@ pub fn add_quota(quota_owner: Principal, quota_type: QuotaType, diff: u32) -> Bo
     }
 }
 
+/// Add quota with an idempotency key, the quota is added only once for the same key of the caller.
+/// Returns true if the quota is added or it has been added with the key before.
+///
+/// * `key` - idempotency key, e.g. id of the opened mystery box
+#[update(name = "add_quota_with_key")]
+#[candid_method(update)]
+pub fn add_quota_with_key(
+    quota_owner: Principal,
+    quota_type: QuotaType,
+    diff: u32,
+    key: String,
+) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if is_env(Production) && !is_named_canister_id(CanisterNames::MysteryBox, *caller) {
+        BooleanActorResponse::new(Ok(false))
+    } else {
+        debug!("add_quota_with_key: caller: {}, key: {}", caller, key);
+
+        let mut service = RegistrarService::default();
+        let result = service.add_quota_with_key(caller, quota_owner, quota_type, diff, key);
+        BooleanActorResponse::new(result)
+    }
+}
+
 #[update(name = "batch_add_quota")]
 #[candid_method(update)]
 pub fn batch_add_quota(request: BatchAddQuotaRequest) -> BooleanActorResponse {
@@ -434,14 +649,35 @@ fn approve(name: String, to: Principal) -> BooleanActorResponse {
     BooleanActorResponse::new(result)
 }
 
+/// Check if a name is approved to the spender.
+/// Returns true if the spender is able to transfer the name by `transfer_from`
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `spender` - the principal approved to
+#[query(name = "is_approved_to")]
+#[candid_method(query)]
+fn is_approved_to(name: String, spender: Principal) -> BooleanActorResponse {
+    let service = RegistrarService::default();
+    let result = service.is_approved_to(name.as_str(), &spender);
+    BooleanActorResponse::new(result)
+}
+
+/// Transfer name approved to caller
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.icp`
+/// * `to` - new owner of the name, it is the caller if not specified
 #[update(name = "transfer_from")]
 #[candid_method(update)]
-async fn transfer_from(name: String) -> BooleanActorResponse {
+async fn transfer_from(name: String, to: Option<Principal>) -> BooleanActorResponse {
     let caller = &api::caller();
     let _now = api::time();
 
     let service = RegistrarService::default();
-    let result = service.transfer_from(caller, name.as_str(), None).await;
+    let result = match to.map(|to| must_not_anonymous(&to)).transpose() {
+        Ok(to) => service.transfer_from(caller, name.as_str(), to).await,
+        Err(e) => Err(e),
+    };
     BooleanActorResponse::new(result)
 }
 
@@ -561,7 +797,8 @@ async fn import_registrations(request: ImportNameRegistrationRequest) -> Boolean
 #[candid_method(query)]
 fn get_name_status(name: String) -> GetNameStatueActorResponse {
     let service = RegistrarService::default();
-    let result = service.get_name_status(name.as_str());
+    let now = api::time();
+    let result = service.get_name_status(name.as_str(), now);
     GetNameStatueActorResponse::new(result)
 }
 
@@ -833,6 +1070,293 @@ pub fn ext_batch_tokens_of(principals: Vec<Principal>) -> EXTBatchTokensOfRespon
     EXTBatchTokensOfResponse::new(result)
 }
 
+#[query(name = "icrc7_collection_metadata")]
+#[candid_method(query)]
+pub fn icrc7_collection_metadata() -> Icrc7Metadata {
+    let service = RegistrarService::default();
+    service.icrc7_collection_metadata(api::time())
+}
+
+#[query(name = "icrc7_symbol")]
+#[candid_method(query)]
+pub fn icrc7_symbol() -> String {
+    ICRC7_SYMBOL.to_string()
+}
+
+#[query(name = "icrc7_name")]
+#[candid_method(query)]
+pub fn icrc7_name() -> String {
+    ICRC7_NAME.to_string()
+}
+
+#[query(name = "icrc7_description")]
+#[candid_method(query)]
+pub fn icrc7_description() -> Option<String> {
+    Some(ICRC7_DESCRIPTION.to_string())
+}
+
+#[query(name = "icrc7_logo")]
+#[candid_method(query)]
+pub fn icrc7_logo() -> Option<String> {
+    None
+}
+
+#[query(name = "icrc7_total_supply")]
+#[candid_method(query)]
+pub fn icrc7_total_supply() -> Nat {
+    let service = RegistrarService::default();
+    service.icrc7_total_supply(api::time())
+}
+
+#[query(name = "icrc7_supply_cap")]
+#[candid_method(query)]
+pub fn icrc7_supply_cap() -> Option<Nat> {
+    None
+}
+
+#[query(name = "icrc7_max_query_batch_size")]
+#[candid_method(query)]
+pub fn icrc7_max_query_batch_size() -> Option<Nat> {
+    Some(Nat::from(ICRC7_MAX_QUERY_BATCH_SIZE as u64))
+}
+
+#[query(name = "icrc7_max_update_batch_size")]
+#[candid_method(query)]
+pub fn icrc7_max_update_batch_size() -> Option<Nat> {
+    Some(Nat::from(ICRC7_MAX_UPDATE_BATCH_SIZE as u64))
+}
+
+#[query(name = "icrc7_default_take_value")]
+#[candid_method(query)]
+pub fn icrc7_default_take_value() -> Option<Nat> {
+    Some(Nat::from(ICRC7_DEFAULT_TAKE_VALUE as u64))
+}
+
+#[query(name = "icrc7_max_take_value")]
+#[candid_method(query)]
+pub fn icrc7_max_take_value() -> Option<Nat> {
+    Some(Nat::from(ICRC7_MAX_TAKE_VALUE as u64))
+}
+
+#[query(name = "icrc7_max_memo_size")]
+#[candid_method(query)]
+pub fn icrc7_max_memo_size() -> Option<Nat> {
+    Some(Nat::from(ICRC7_MAX_MEMO_SIZE as u64))
+}
+
+#[query(name = "icrc7_atomic_batch_transfers")]
+#[candid_method(query)]
+pub fn icrc7_atomic_batch_transfers() -> Option<bool> {
+    Some(false)
+}
+
+#[query(name = "icrc7_tx_window")]
+#[candid_method(query)]
+pub fn icrc7_tx_window() -> Option<Nat> {
+    Some(Nat::from(ICRC7_TX_WINDOW_NS / 1_000_000_000))
+}
+
+#[query(name = "icrc7_permitted_drift")]
+#[candid_method(query)]
+pub fn icrc7_permitted_drift() -> Option<Nat> {
+    Some(Nat::from(ICRC7_PERMITTED_DRIFT_NS / 1_000_000_000))
+}
+
+/// Get metadata of names, includes name, expiration time and image url
+///
+/// * `token_ids` - token indexes of names
+#[query(name = "icrc7_token_metadata")]
+#[candid_method(query)]
+pub fn icrc7_token_metadata(token_ids: Vec<Nat>) -> Vec<Option<Icrc7Metadata>> {
+    let service = RegistrarService::default();
+    service.icrc7_token_metadata(&token_ids, api::time())
+}
+
+#[query(name = "icrc7_owner_of")]
+#[candid_method(query)]
+pub fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
+    let service = RegistrarService::default();
+    service.icrc7_owner_of(&token_ids, api::time())
+}
+
+#[query(name = "icrc7_balance_of")]
+#[candid_method(query)]
+pub fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+    let service = RegistrarService::default();
+    service.icrc7_balance_of(&accounts, api::time())
+}
+
+#[query(name = "icrc7_tokens")]
+#[candid_method(query)]
+pub fn icrc7_tokens(prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    let service = RegistrarService::default();
+    service.icrc7_tokens(prev, take, api::time())
+}
+
+#[query(name = "icrc7_tokens_of")]
+#[candid_method(query)]
+pub fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    let service = RegistrarService::default();
+    service.icrc7_tokens_of(&account, prev, take, api::time())
+}
+
+/// Transfer names owned by caller
+///
+/// * `args` - transfers, at most `icrc7_max_update_batch_size` items
+#[update(name = "icrc7_transfer")]
+#[candid_method(update)]
+pub async fn icrc7_transfer(args: Vec<TransferArg>) -> Vec<Option<TransferResult>> {
+    let service = RegistrarService::default();
+    let call_context = CallContext::from_ic();
+    service.icrc7_transfer(&call_context, args).await
+}
+
+/// Blocks of the ICRC-3 log of names and quotas
+///
+/// * `args` - ranges of blocks, at most 100 blocks are returned in total,
+///   archived blocks are returned as callbacks to the archives
+#[query(name = "icrc3_get_blocks")]
+#[candid_method(query)]
+pub fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    let service = RegistrarService::default();
+    service.icrc3_get_blocks(args)
+}
+
+/// Certificate of the index and the hash of the last block, None if there is no block
+#[query(name = "icrc3_get_tip_certificate")]
+#[candid_method(query)]
+pub fn icrc3_get_tip_certificate() -> Option<DataCertificate> {
+    let service = RegistrarService::default();
+    service
+        .icrc3_get_tip_certificate()
+        .map(|certificate| certificate.with_certificate())
+}
+
+#[query(name = "icrc3_supported_block_types")]
+#[candid_method(query)]
+pub fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    get_supported_block_types()
+}
+
+/// Archive canisters holding old blocks of the ICRC-3 log
+///
+/// * `args` - archives after `from` are returned if it is set
+#[query(name = "icrc3_get_archives")]
+#[candid_method(query)]
+pub fn icrc3_get_archives(args: GetArchivesArgs) -> Vec<ArchiveInfo> {
+    let service = RegistrarService::default();
+    service.icrc3_get_archives(args)
+}
+
+/// Add an archive canister of the ICRC-3 log, old blocks are moved to the last added archive
+///
+/// * `canister_id` - a `registrar_archive` canister
+#[update(name = "add_block_archive")]
+#[candid_method(update)]
+pub fn add_block_archive(canister_id: Principal) -> BooleanActorResponse {
+    let caller = &api::caller();
+    let service = RegistrarService::default();
+    let result = service.add_block_archive(caller, canister_id);
+    BooleanActorResponse::new(result)
+}
+
+#[query(name = "icrc10_supported_standards")]
+#[candid_method(query)]
+pub fn icrc10_supported_standards() -> Vec<SupportedStandard> {
+    get_supported_standards()
+}
+
+#[query(name = "icrc37_max_approvals_per_token_or_collection")]
+#[candid_method(query)]
+pub fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
+    Some(Nat::from(ICRC37_MAX_APPROVALS_PER_TOKEN_OR_COLLECTION))
+}
+
+#[query(name = "icrc37_max_revoke_approvals")]
+#[candid_method(query)]
+pub fn icrc37_max_revoke_approvals() -> Option<Nat> {
+    Some(Nat::from(ICRC37_MAX_REVOKE_APPROVALS))
+}
+
+/// Approve names to spenders, a name can only be approved to one spender
+///
+/// * `args` - approvals, expires_at is not supported
+#[update(name = "icrc37_approve_tokens")]
+#[candid_method(update)]
+pub fn icrc37_approve_tokens(args: Vec<ApproveTokenArg>) -> Vec<Option<ApproveTokenResult>> {
+    let service = RegistrarService::default();
+    let call_context = CallContext::from_ic();
+    service.icrc37_approve_tokens(&call_context, args)
+}
+
+/// Collection approval is not supported, every item fails with `GenericError`
+#[update(name = "icrc37_approve_collection")]
+#[candid_method(update)]
+pub fn icrc37_approve_collection(
+    args: Vec<ApproveCollectionArg>,
+) -> Vec<Option<ApproveCollectionResult>> {
+    let service = RegistrarService::default();
+    service.icrc37_approve_collection(args)
+}
+
+#[update(name = "icrc37_revoke_token_approvals")]
+#[candid_method(update)]
+pub fn icrc37_revoke_token_approvals(
+    args: Vec<RevokeTokenApprovalArg>,
+) -> Vec<Option<RevokeTokenApprovalResponse>> {
+    let service = RegistrarService::default();
+    let call_context = CallContext::from_ic();
+    service.icrc37_revoke_token_approvals(&call_context, args)
+}
+
+#[update(name = "icrc37_revoke_collection_approvals")]
+#[candid_method(update)]
+pub fn icrc37_revoke_collection_approvals(
+    args: Vec<RevokeCollectionApprovalArg>,
+) -> Vec<Option<RevokeCollectionApprovalResult>> {
+    let service = RegistrarService::default();
+    service.icrc37_revoke_collection_approvals(args)
+}
+
+#[query(name = "icrc37_is_approved")]
+#[candid_method(query)]
+pub fn icrc37_is_approved(args: Vec<IsApprovedArg>) -> Vec<bool> {
+    let service = RegistrarService::default();
+    service.icrc37_is_approved(&args, api::time())
+}
+
+#[query(name = "icrc37_get_token_approvals")]
+#[candid_method(query)]
+pub fn icrc37_get_token_approvals(
+    token_id: Nat,
+    prev: Option<TokenApproval>,
+    take: Option<Nat>,
+) -> Vec<TokenApproval> {
+    let service = RegistrarService::default();
+    service.icrc37_get_token_approvals(&token_id, prev, take, api::time())
+}
+
+#[query(name = "icrc37_get_collection_approvals")]
+#[candid_method(query)]
+pub fn icrc37_get_collection_approvals(
+    _owner: Account,
+    _prev: Option<CollectionApproval>,
+    _take: Option<Nat>,
+) -> Vec<CollectionApproval> {
+    vec![]
+}
+
+/// Transfer names approved to caller
+///
+/// * `args` - transfers, at most `icrc7_max_update_batch_size` items
+#[update(name = "icrc37_transfer_from")]
+#[candid_method(update)]
+pub async fn icrc37_transfer_from(args: Vec<TransferFromArg>) -> Vec<Option<TransferFromResult>> {
+    let service = RegistrarService::default();
+    let call_context = CallContext::from_ic();
+    service.icrc37_transfer_from(&call_context, args).await
+}
+
 #[update(name = "batch_extend_expired_at")]
 #[candid_method(update)]
 pub fn batch_extend_expired_at(names: Vec<String>, years: u32) -> BooleanActorResponse {
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use candid::{decode_args, encode_args, CandidType, Deserialize, Nat, Principal};
+use num_bigint::BigUint;
+
+use common::errors::{NamingError, ServiceResult};
+use common::state::StableState;
+
+#[cfg(test)]
+mod tests;
+
+const MAX_DECIMALS: u8 = 18;
+
+/// How the price of a token in XDR is known
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum PriceFeed {
+    /// The token is pegged to ICP, rate from cycles minting canister is used
+    IcpXdrConversionRate,
+    /// Rate set by admin, it should be updated when the market moves
+    XdrPermyriadPerToken(u64),
+}
+
+/// A token accepted by ICRC-2 `icrc2_transfer_from` as payment
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct PaymentToken {
+    pub ledger: Principal,
+    pub symbol: String,
+    pub decimals: u8,
+    /// Transfer fee of the ledger, it is paid by registrar when refunding
+    pub fee: Nat,
+    pub price_feed: PriceFeed,
+}
+
+impl PaymentToken {
+    pub fn validate(&self) -> ServiceResult<()> {
+        if self.ledger == Principal::anonymous() {
+            return Err(NamingError::InvalidPaymentToken {
+                reason: "ledger must not be anonymous".to_string(),
+            });
+        }
+        if self.symbol.is_empty() {
+            return Err(NamingError::InvalidPaymentToken {
+                reason: "symbol must not be empty".to_string(),
+            });
+        }
+        if self.decimals > MAX_DECIMALS {
+            return Err(NamingError::InvalidPaymentToken {
+                reason: format!("decimals must not be greater than {}", MAX_DECIMALS),
+            });
+        }
+        if self.price_feed == PriceFeed::XdrPermyriadPerToken(0) {
+            return Err(NamingError::InvalidPaymentToken {
+                reason: "rate of price feed must be greater than 0".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Convert a price in XDR permyriad to the smallest unit of the token
+    pub fn convert_xdr_permyriad(&self, xdr_permyriad: u64, xdr_permyriad_per_token: u64) -> Nat {
+        assert!(xdr_permyriad_per_token > 0);
+        let units = BigUint::from(xdr_permyriad) * BigUint::from(10u32).pow(self.decimals as u32)
+            / BigUint::from(xdr_permyriad_per_token);
+        Nat(units)
+    }
+}
+
+/// Tokens accepted as payment besides DICP, keyed by ledger canister id
+#[derive(Default)]
+pub struct PaymentTokenStore {
+    tokens: HashMap<Principal, PaymentToken>,
+}
+
+impl StableState for PaymentTokenStore {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((&self.tokens,)).unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (tokens,): (HashMap<Principal, PaymentToken>,) = decode_args(&bytes).unwrap();
+
+        Ok(PaymentTokenStore { tokens })
+    }
+}
+
+impl PaymentTokenStore {
+    pub fn set_token(&mut self, token: PaymentToken) {
+        self.tokens.insert(token.ledger, token);
+    }
+
+    pub fn remove_token(&mut self, ledger: &Principal) -> Option<PaymentToken> {
+        self.tokens.remove(ledger)
+    }
+
+    pub fn get_token(&self, ledger: &Principal) -> Option<&PaymentToken> {
+        self.tokens.get(ledger)
+    }
+
+    /// Returns tokens ordered by symbol
+    pub fn get_tokens(&self) -> Vec<PaymentToken> {
+        let mut tokens: Vec<PaymentToken> = self.tokens.values().cloned().collect();
+        tokens.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        tokens
+    }
+}
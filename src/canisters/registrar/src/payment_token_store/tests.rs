@@ -0,0 +1,78 @@
+use candid::{Nat, Principal};
+use rstest::*;
+
+use test_common::user::*;
+
+use crate::payment_token_store::{PaymentToken, PaymentTokenStore, PriceFeed};
+
+fn create_token(ledger: Principal, symbol: &str, decimals: u8) -> PaymentToken {
+    PaymentToken {
+        ledger,
+        symbol: symbol.to_string(),
+        decimals,
+        fee: Nat::from(10_000u32),
+        price_feed: PriceFeed::XdrPermyriadPerToken(20_000),
+    }
+}
+
+#[fixture]
+fn store() -> PaymentTokenStore {
+    PaymentTokenStore::default()
+}
+
+#[rstest]
+fn test_set_and_remove_token(
+    mut store: PaymentTokenStore,
+    mock_user1: Principal,
+    mock_user2: Principal,
+) {
+    store.set_token(create_token(mock_user1, "ckBTC", 8));
+    store.set_token(create_token(mock_user2, "ICP", 8));
+    store.set_token(create_token(mock_user1, "ckETH", 18));
+
+    let symbols: Vec<String> = store.get_tokens().into_iter().map(|t| t.symbol).collect();
+    assert_eq!(symbols, vec!["ICP".to_string(), "ckETH".to_string()]);
+
+    assert!(store.remove_token(&mock_user2).is_some());
+    assert!(store.get_token(&mock_user2).is_none());
+    assert_eq!(store.get_token(&mock_user1).unwrap().decimals, 18);
+}
+
+#[rstest]
+#[case(create_token(Principal::anonymous(), "ICP", 8), false)]
+#[case(create_token(mock_user1(), "", 8), false)]
+#[case(create_token(mock_user1(), "ICP", 19), false)]
+#[case(PaymentToken { price_feed: PriceFeed::XdrPermyriadPerToken(0), ..create_token(mock_user1(), "ICP", 8) }, false)]
+#[case(PaymentToken { price_feed: PriceFeed::IcpXdrConversionRate, ..create_token(mock_user1(), "ICP", 8) }, true)]
+#[case(create_token(mock_user1(), "ckETH", 18), true)]
+fn test_validate(#[case] token: PaymentToken, #[case] is_ok: bool) {
+    assert_eq!(token.validate().is_ok(), is_ok);
+}
+
+#[rstest]
+#[case(8, 20_000, 20_000, 100_000_000u64)]
+#[case(8, 22_000, 20_000, 110_000_000u64)]
+#[case(6, 10_000, 40_000, 250_000u64)]
+fn test_convert_xdr_permyriad(
+    mock_user1: Principal,
+    #[case] decimals: u8,
+    #[case] xdr_permyriad: u64,
+    #[case] xdr_permyriad_per_token: u64,
+    #[case] expected: u64,
+) {
+    let token = create_token(mock_user1, "TKN", decimals);
+
+    assert_eq!(
+        token.convert_xdr_permyriad(xdr_permyriad, xdr_permyriad_per_token),
+        Nat::from(expected)
+    );
+}
+
+#[rstest]
+fn test_convert_xdr_permyriad_with_18_decimals(mock_user1: Principal) {
+    let token = create_token(mock_user1, "ckETH", 18);
+
+    let result = token.convert_xdr_permyriad(20_000, 10_000);
+
+    assert_eq!(result, Nat::from(2_000_000_000_000_000_000u128));
+}
@@ -1,6 +1,7 @@
 use common::TimeInNs;
 use ic_cdk::api;
 
+use crate::service::RegistrarService;
 use crate::token_service::TokenService;
 
 pub async fn run_periodic_tasks() {
@@ -9,4 +10,16 @@ pub async fn run_periodic_tasks() {
         let service = TokenService::default();
         let _result = service.retry_refund(TimeInNs(now));
     }
+    {
+        let service = RegistrarService::default();
+        let _result = service.clean_expired(TimeInNs(now)).await;
+    }
+    {
+        let service = RegistrarService::default();
+        service.clean_expired_commitments(TimeInNs(now));
+    }
+    {
+        let service = RegistrarService::default();
+        let _result = service.archive_blocks(TimeInNs(now)).await;
+    }
 }
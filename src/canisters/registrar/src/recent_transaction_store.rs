@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+use candid::{decode_args, encode_args, CandidType, Principal};
+use sha2::{Digest, Sha256};
+
+use common::state::StableState;
+
+#[cfg(test)]
+mod tests;
+
+/// Transactions submitted with `created_at_time`, so that a transaction submitted again is not applied twice.
+/// They are ordered by `created_at_time`, and removed once they are too old to be submitted again.
+#[derive(Default)]
+pub struct RecentTransactionStore {
+    /// (created_at_time, hash of transaction) -> index of the block of the transaction
+    transactions: BTreeMap<(u64, String), u64>,
+}
+
+impl StableState for RecentTransactionStore {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((&self.transactions,)).unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (transactions,): (BTreeMap<(u64, String), u64>,) = decode_args(&bytes).unwrap();
+
+        Ok(RecentTransactionStore { transactions })
+    }
+}
+
+impl RecentTransactionStore {
+    pub fn add_transaction(&mut self, created_at_time: u64, hash: String, index: u64) {
+        self.transactions.insert((created_at_time, hash), index);
+    }
+
+    pub fn get_transaction_index(&self, created_at_time: u64, hash: &str) -> Option<u64> {
+        self.transactions
+            .get(&(created_at_time, hash.to_string()))
+            .cloned()
+    }
+
+    /// Remove transactions created before `created_before`, only the removed part is visited
+    pub fn remove_created_before(&mut self, created_before: u64) {
+        self.transactions = self
+            .transactions
+            .split_off(&(created_before, String::new()));
+    }
+}
+
+/// Returns hex encoded sha256 hash of candid encoded (method, caller, arg)
+pub fn get_transaction_hash<T: CandidType>(method: &str, caller: &Principal, arg: &T) -> String {
+    let bytes = encode_args((method, caller, arg)).unwrap();
+    let mut sha256 = Sha256::new();
+    sha256.update(&bytes);
+    hex::encode(sha256.finalize())
+}
@@ -0,0 +1,56 @@
+use candid::Principal;
+use rstest::*;
+
+use test_common::user::*;
+
+use crate::recent_transaction_store::{get_transaction_hash, RecentTransactionStore};
+
+#[fixture]
+fn store() -> RecentTransactionStore {
+    RecentTransactionStore::default()
+}
+
+#[rstest]
+fn test_get_transaction_hash(mock_user1: Principal, mock_user2: Principal) {
+    let hash = get_transaction_hash("icrc7_transfer", &mock_user1, &1u32);
+
+    assert_eq!(hash.len(), 64);
+    assert_eq!(
+        hash,
+        get_transaction_hash("icrc7_transfer", &mock_user1, &1u32)
+    );
+    assert_ne!(
+        hash,
+        get_transaction_hash("icrc7_transfer", &mock_user2, &1u32)
+    );
+    assert_ne!(
+        hash,
+        get_transaction_hash("icrc7_transfer", &mock_user1, &2u32)
+    );
+    assert_ne!(
+        hash,
+        get_transaction_hash("icrc37_transfer_from", &mock_user1, &1u32)
+    );
+}
+
+#[rstest]
+fn test_get_transaction_index(mut store: RecentTransactionStore, mock_now: u64) {
+    store.add_transaction(mock_now, "a".to_string(), 1);
+
+    assert_eq!(store.get_transaction_index(mock_now, "a"), Some(1));
+    assert_eq!(store.get_transaction_index(mock_now, "b"), None);
+    assert_eq!(store.get_transaction_index(mock_now + 1, "a"), None);
+}
+
+#[rstest]
+fn test_remove_created_before(mut store: RecentTransactionStore, mock_now: u64) {
+    store.add_transaction(mock_now - 1, "a".to_string(), 1);
+    store.add_transaction(mock_now, "b".to_string(), 2);
+    store.add_transaction(mock_now + 1, "c".to_string(), 3);
+
+    store.remove_created_before(mock_now);
+
+    assert_eq!(store.get_transaction_index(mock_now - 1, "a"), None);
+    assert_eq!(store.get_transaction_index(mock_now, "b"), Some(2));
+    assert_eq!(store.get_transaction_index(mock_now + 1, "c"), Some(3));
+}
@@ -64,4 +64,11 @@ impl RegistrationApprovalStore {
     pub fn has_approved_to(&self, name: &FirstLevelName) -> bool {
         self.approvals.contains_key(name.0.get_name())
     }
+
+    /// Returns the principal approved to and the time of approval
+    pub fn get_approval(&self, name: &FirstLevelName) -> Option<(Principal, u64)> {
+        self.approvals
+            .get(name.0.get_name())
+            .map(|approval| (approval.approved_to, approval.approval_at))
+    }
 }
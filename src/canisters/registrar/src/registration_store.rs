@@ -1,10 +1,36 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::ops;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize, Principal};
+use candid::{CandidType, Deserialize, Principal};
+use common::constants::{NAMING_GRACE_PERIOD_NS, NAMING_REDEMPTION_PERIOD_NS};
 use common::naming::FirstLevelName;
+use common::stable_memory::{
+    take_owned_names, Bound, OwnerNameKey, PrincipalKey, StableMap, Storable,
+};
+use common::state::StableMemoryStore;
 
-use common::state::StableState;
+use crate::state::{
+    EXPIRED_AT_NAMES_MEMORY_ID, OWNER_NAMES_MEMORY_ID, OWNER_NAME_COUNTS_MEMORY_ID,
+    REGISTRATION_STORE_MEMORY_ID,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Lifecycle of a registration after it is expired
+#[derive(CandidType, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RegistrationLifecycle {
+    /// The name is not expired
+    Active,
+    /// The name is expired, only the owner is able to renew it
+    GracePeriod,
+    /// Grace period is over, the owner is able to renew it with redemption fee
+    Redemption,
+    /// The name is waiting to be released
+    Released,
+}
 
 /// Name registration
 #[derive(CandidType, Deserialize, Eq, PartialEq, Clone)]
@@ -50,6 +76,23 @@ impl Registration {
     pub fn is_expired(&self, now: u64) -> bool {
         self.expired_at < now
     }
+    pub fn get_grace_period_end_at(&self) -> u64 {
+        self.expired_at + NAMING_GRACE_PERIOD_NS
+    }
+    pub fn get_redemption_end_at(&self) -> u64 {
+        self.get_grace_period_end_at() + NAMING_REDEMPTION_PERIOD_NS
+    }
+    pub fn get_lifecycle(&self, now: u64) -> RegistrationLifecycle {
+        if !self.is_expired(now) {
+            RegistrationLifecycle::Active
+        } else if now <= self.get_grace_period_end_at() {
+            RegistrationLifecycle::GracePeriod
+        } else if now <= self.get_redemption_end_at() {
+            RegistrationLifecycle::Redemption
+        } else {
+            RegistrationLifecycle::Released
+        }
+    }
 }
 
 impl Debug for Registration {
@@ -62,28 +105,167 @@ impl Debug for Registration {
     }
 }
 
-#[derive(Default)]
+/// Key of the expiry index, registrations are ordered by `expired_at` and then by name
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct ExpiredAtNameKey {
+    expired_at: u64,
+    name: String,
+}
+
+impl ExpiredAtNameKey {
+    fn new(expired_at: u64, name: &str) -> Self {
+        ExpiredAtNameKey {
+            expired_at,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Storable for ExpiredAtNameKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(8 + self.name.len());
+        bytes.extend_from_slice(&self.expired_at.to_be_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut expired_at = [0u8; 8];
+        expired_at.copy_from_slice(&bytes[..8]);
+        ExpiredAtNameKey {
+            expired_at: u64::from_be_bytes(expired_at),
+            name: String::from_utf8(bytes[8..].to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Registrations are kept in stable memory, so that they are not serialized on upgrade.
+///
+/// Names of each owner are indexed in `owner_names`, and counted in `owner_name_counts`,
+/// so that queries of an owner do not scan all registrations.
+/// Both of them are updated whenever a registration is added, transferred or removed.
+///
+/// Names are also indexed by `expired_at` in `expired_at_names`,
+/// so that released registrations are found without scanning all registrations.
 pub struct RegistrationStore {
-    pub registrations: HashMap<String, Registration>,
+    registrations: StableMap<String, Registration>,
+    owner_names: StableMap<OwnerNameKey, ()>,
+    owner_name_counts: StableMap<PrincipalKey, u32>,
+    expired_at_names: StableMap<ExpiredAtNameKey, ()>,
+}
+
+impl Default for RegistrationStore {
+    fn default() -> Self {
+        RegistrationStore {
+            registrations: StableMap::init(REGISTRATION_STORE_MEMORY_ID),
+            owner_names: StableMap::init(OWNER_NAMES_MEMORY_ID),
+            owner_name_counts: StableMap::init(OWNER_NAME_COUNTS_MEMORY_ID),
+            expired_at_names: StableMap::init(EXPIRED_AT_NAMES_MEMORY_ID),
+        }
+    }
 }
 
 impl RegistrationStore {
-    pub fn get_registrations(&self) -> &HashMap<String, Registration> {
-        &self.registrations
+    /// All registrations, ordered by name
+    pub fn get_registrations(&self) -> impl Iterator<Item = Registration> + '_ {
+        self.registrations.values()
     }
 
-    pub fn get_registration(&self, name: &FirstLevelName) -> Option<&Registration> {
-        self.registrations.get(name.0.get_name())
+    pub fn get_registration_count(&self) -> u64 {
+        self.registrations.len()
+    }
+
+    pub fn get_registration(&self, name: &FirstLevelName) -> Option<Registration> {
+        self.get_registration_by_name(name.0.get_name())
+    }
+
+    pub fn get_registration_by_name(&self, name: &str) -> Option<Registration> {
+        self.registrations.get(&name.to_string())
     }
 
     pub fn add_registration(&mut self, registration: Registration) {
-        self.registrations
-            .insert(registration.name.clone(), registration);
+        let name = registration.name.clone();
+        let owner = registration.owner;
+        let expired_at = registration.expired_at;
+        if let Some(old_registration) = self.registrations.insert(name.clone(), registration) {
+            self.remove_owner_name(old_registration.owner, &name);
+            self.expired_at_names
+                .remove(&ExpiredAtNameKey::new(old_registration.expired_at, &name));
+        }
+        self.add_owner_name(owner, &name);
+        self.expired_at_names
+            .insert(ExpiredAtNameKey::new(expired_at, &name), ());
     }
     pub fn transfer_registration(&mut self, name: String, owner: Principal) {
-        self.registrations.entry(name).and_modify(|registration| {
+        if let Some(mut registration) = self.registrations.get(&name) {
+            self.remove_owner_name(registration.owner, &name);
             registration.set_owner(owner);
-        });
+            self.registrations.insert(name.clone(), registration);
+            self.add_owner_name(owner, &name);
+        }
+    }
+
+    pub fn remove_registration(&mut self, name: &str) -> Option<Registration> {
+        let registration = self.registrations.remove(&name.to_string());
+        if let Some(registration) = registration.as_ref() {
+            self.remove_owner_name(registration.owner, name);
+            self.expired_at_names
+                .remove(&ExpiredAtNameKey::new(registration.expired_at, name));
+        }
+        registration
+    }
+
+    fn add_owner_name(&mut self, owner: Principal, name: &str) {
+        if self
+            .owner_names
+            .insert(OwnerNameKey::new(owner, name), ())
+            .is_none()
+        {
+            let owner = PrincipalKey(owner);
+            let count = self.owner_name_counts.get(&owner).unwrap_or_default();
+            self.owner_name_counts.insert(owner, count + 1);
+        }
+    }
+
+    fn remove_owner_name(&mut self, owner: Principal, name: &str) {
+        if self
+            .owner_names
+            .remove(&OwnerNameKey::new(owner, name))
+            .is_some()
+        {
+            let owner = PrincipalKey(owner);
+            match self.owner_name_counts.get(&owner).unwrap_or_default() {
+                0 | 1 => {
+                    self.owner_name_counts.remove(&owner);
+                }
+                count => {
+                    self.owner_name_counts.insert(owner, count - 1);
+                }
+            }
+        }
+    }
+
+    /// Names of released registrations, only the expired part of the expiry index is visited
+    pub fn get_released_registration_names(&self, now: u64, limit: usize) -> Vec<String> {
+        // released when `expired_at + grace period + redemption period < now`
+        let released_before =
+            now.saturating_sub(NAMING_GRACE_PERIOD_NS + NAMING_REDEMPTION_PERIOD_NS);
+        let end = ExpiredAtNameKey::new(released_before, "");
+        self.expired_at_names
+            .range(..end)
+            .take(limit)
+            .map(|(key, _)| key.name)
+            .collect()
+    }
+
+    /// Count of registrations not expired, only the expired part of the expiry index is visited
+    pub fn get_unexpired_registration_count(&self, now: u64) -> u64 {
+        // expired when `expired_at < now`
+        let end = ExpiredAtNameKey::new(now, "");
+        let expired_count = self.expired_at_names.range(..end).count() as u64;
+        self.registrations.len() - expired_count
     }
 
     pub fn has_registration(&self, name: &FirstLevelName) -> bool {
@@ -91,37 +273,78 @@ impl RegistrationStore {
     }
 
     pub fn update_expired_at(&mut self, name: &FirstLevelName, expired_at: u64) {
-        self.registrations
-            .entry(name.0.get_name().clone())
-            .and_modify(|registration| {
-                registration.expired_at = expired_at;
-            });
+        let name = name.0.get_name();
+        if let Some(mut registration) = self.registrations.get(name) {
+            self.expired_at_names
+                .remove(&ExpiredAtNameKey::new(registration.expired_at, name));
+            self.expired_at_names
+                .insert(ExpiredAtNameKey::new(expired_at, name), ());
+            registration.expired_at = expired_at;
+            self.registrations.insert(name.clone(), registration);
+        }
+    }
+
+    /// Names owned by the user after the cursor, ordered by name
+    pub fn get_user_owned_names<'a>(
+        &'a self,
+        owner: &Principal,
+        cursor: Option<&str>,
+    ) -> impl Iterator<Item = String> + 'a {
+        take_owned_names(self.get_owner_name_entries(owner, cursor), *owner)
+    }
+
+    /// Entries of the owner index from the owner and the cursor, followed by entries of other owners
+    fn get_owner_name_entries(
+        &self,
+        owner: &Principal,
+        cursor: Option<&str>,
+    ) -> impl Iterator<Item = (OwnerNameKey, ())> + '_ {
+        let start = OwnerNameKey::start_after(*owner, cursor);
+        self.owner_names.range((start, ops::Bound::Unbounded))
     }
 
-    pub fn get_user_owned_registrations(&self, owner: &Principal) -> Vec<&Registration> {
-        self.registrations
-            .values()
-            .filter(|registration| registration.is_owner(owner))
+    /// Registrations owned by the user after the cursor, ordered by name
+    pub fn get_user_owned_registrations_after<'a>(
+        &'a self,
+        owner: &Principal,
+        cursor: Option<&str>,
+    ) -> impl Iterator<Item = Registration> + 'a {
+        self.get_user_owned_names(owner, cursor)
+            .filter_map(move |name| self.registrations.get(&name))
+    }
+
+    pub fn get_user_owned_registrations(&self, owner: &Principal) -> Vec<Registration> {
+        self.get_user_owned_registrations_after(owner, None)
             .collect()
     }
 
     pub fn get_user_owned_registrations_count(&self, user: &Principal) -> usize {
-        self.registrations
-            .values()
-            .filter(|registration| registration.is_owner(user))
-            .count()
+        self.owner_name_counts
+            .get(&PrincipalKey(*user))
+            .unwrap_or_default() as usize
     }
-}
 
-impl StableState for RegistrationStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.registrations,)).unwrap()
+    /// Count of distinct owners of registrations
+    pub fn get_owner_count(&self) -> u64 {
+        self.owner_name_counts.len()
     }
+}
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (registrations,): (HashMap<String, Registration>,) = decode_args(&bytes).unwrap();
+impl StableMemoryStore for RegistrationStore {
+    type Entries = (HashMap<String, Registration>,);
 
-        Ok(RegistrationStore { registrations })
+    fn export_entries(&self) -> Self::Entries {
+        (self.registrations.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (registrations,): Self::Entries) {
+        self.registrations.clear();
+        self.owner_names.clear();
+        self.owner_name_counts.clear();
+        self.expired_at_names.clear();
+        for (_, registration) in registrations {
+            self.add_registration(registration);
+        }
     }
 }
 
@@ -0,0 +1,161 @@
+use std::cell::Cell;
+
+use rstest::*;
+
+use test_common::user::*;
+
+use super::*;
+
+const OWNER_NAME_COUNT: usize = 20;
+
+fn registration(owner: Principal, name: &str, now: u64) -> Registration {
+    Registration::new(owner, name.to_string(), now + 1, now)
+}
+
+fn get_names(store: &RegistrationStore, owner: &Principal, cursor: Option<&str>) -> Vec<String> {
+    store.get_user_owned_names(owner, cursor).collect()
+}
+
+#[rstest]
+fn test_owner_index(mock_user1: Principal, mock_user2: Principal, mock_now: u64) {
+    let mut store = RegistrationStore::default();
+    store.add_registration(registration(mock_user1, "b.ic", mock_now));
+    store.add_registration(registration(mock_user2, "c.ic", mock_now));
+    store.add_registration(registration(mock_user1, "a.ic", mock_now));
+
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["a.ic", "b.ic"]);
+    assert_eq!(get_names(&store, &mock_user2, None), vec!["c.ic"]);
+    assert_eq!(store.get_user_owned_registrations_count(&mock_user1), 2);
+    assert_eq!(store.get_owner_count(), 2);
+
+    store.transfer_registration("c.ic".to_string(), mock_user1);
+    assert_eq!(
+        get_names(&store, &mock_user1, None),
+        vec!["a.ic", "b.ic", "c.ic"]
+    );
+    assert!(get_names(&store, &mock_user2, None).is_empty());
+    assert_eq!(store.get_user_owned_registrations_count(&mock_user2), 0);
+    assert_eq!(store.get_owner_count(), 1);
+
+    // registered again by another user after released
+    store.add_registration(registration(mock_user2, "a.ic", mock_now));
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["b.ic", "c.ic"]);
+    assert_eq!(get_names(&store, &mock_user2, None), vec!["a.ic"]);
+
+    store.remove_registration("b.ic");
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["c.ic"]);
+    assert_eq!(store.get_user_owned_registrations_count(&mock_user1), 1);
+    assert_eq!(store.get_owner_count(), 2);
+}
+
+#[rstest]
+fn test_get_user_owned_registrations_after(
+    mock_user1: Principal,
+    mock_user2: Principal,
+    mock_now: u64,
+) {
+    let mut store = RegistrationStore::default();
+    for name in ["a.ic", "b.ic", "c.ic"] {
+        store.add_registration(registration(mock_user1, name, mock_now));
+    }
+    store.add_registration(registration(mock_user2, "d.ic", mock_now));
+
+    let names: Vec<String> = store
+        .get_user_owned_registrations_after(&mock_user1, Some("a.ic"))
+        .map(|registration| registration.get_name())
+        .collect();
+    assert_eq!(names, vec!["b.ic", "c.ic"]);
+    assert!(get_names(&store, &mock_user1, Some("c.ic")).is_empty());
+}
+
+#[rstest]
+fn test_import_rebuilds_owner_index(mock_user1: Principal, mock_now: u64) {
+    let mut store = RegistrationStore::default();
+    store.add_registration(registration(mock_user1, "a.ic", mock_now));
+    store.add_registration(registration(mock_user1, "b.ic", mock_now));
+    let entries = store.export_entries();
+    store.remove_registration("a.ic");
+
+    store.import_entries(entries);
+
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["a.ic", "b.ic"]);
+    assert_eq!(store.get_user_owned_registrations_count(&mock_user1), 2);
+    assert_eq!(store.get_owner_count(), 1);
+}
+
+#[rstest]
+fn test_get_released_registration_names(mock_user1: Principal, mock_now: u64) {
+    let released_at = mock_now + 1 + NAMING_GRACE_PERIOD_NS + NAMING_REDEMPTION_PERIOD_NS;
+    let mut store = RegistrationStore::default();
+    store.add_registration(registration(mock_user1, "b.ic", mock_now));
+    store.add_registration(registration(mock_user1, "a.ic", mock_now));
+    store.add_registration(registration(mock_user1, "c.ic", mock_now + 10));
+
+    assert!(store
+        .get_released_registration_names(released_at, 10)
+        .is_empty());
+    assert_eq!(
+        store.get_released_registration_names(released_at + 1, 10),
+        vec!["a.ic", "b.ic"]
+    );
+    assert_eq!(
+        store.get_released_registration_names(released_at + 1, 1),
+        vec!["a.ic"]
+    );
+
+    // renewed names leave the released range
+    let name = FirstLevelName::from("a.ic");
+    store.update_expired_at(&name, mock_now + 20);
+    store.remove_registration("b.ic");
+    assert!(store
+        .get_released_registration_names(released_at + 1, 10)
+        .is_empty());
+    assert_eq!(
+        store.get_released_registration_names(released_at + 20, 10),
+        vec!["c.ic", "a.ic"]
+    );
+}
+
+fn add_other_names(store: &mut RegistrationStore, range: std::ops::Range<u32>, now: u64) {
+    for i in range {
+        // 10 names of each user
+        let owner = mock_user(100 + i / 10);
+        store.add_registration(registration(owner, &format!("name{}.ic", i), now));
+    }
+}
+
+/// Entries of the owner index visited by the query of names of the owner
+fn count_visited_owner_entries(store: &RegistrationStore, owner: &Principal) -> usize {
+    let visited = Cell::new(0);
+    let entries = store
+        .get_owner_name_entries(owner, None)
+        .inspect(|_| visited.set(visited.get() + 1));
+    assert_eq!(take_owned_names(entries, *owner).count(), OWNER_NAME_COUNT);
+    visited.get()
+}
+
+#[rstest]
+fn test_owner_queries_do_not_visit_other_names(mock_user1: Principal, mock_now: u64) {
+    let mut store = RegistrationStore::default();
+    for i in 0..OWNER_NAME_COUNT {
+        store.add_registration(registration(
+            mock_user1,
+            &format!("owned{}.ic", i),
+            mock_now,
+        ));
+    }
+    add_other_names(&mut store, 0..100, mock_now);
+    let small = count_visited_owner_entries(&store, &mock_user1);
+
+    add_other_names(&mut store, 100..2_000, mock_now);
+    let large = count_visited_owner_entries(&store, &mock_user1);
+
+    // at most the first entry of the next owner is visited besides names of the owner
+    assert!(small <= OWNER_NAME_COUNT + 1);
+    assert_eq!(large, small);
+    assert_eq!(
+        store.get_user_owned_registrations_count(&mock_user1),
+        OWNER_NAME_COUNT
+    );
+    assert_eq!(store.get_owner_count(), 201);
+}
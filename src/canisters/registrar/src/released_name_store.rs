@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use candid::{decode_args, encode_args};
+use common::constants::{
+    NAMING_RELEASE_PREMIUM_HALF_LIFE_NS, NAMING_RELEASE_PREMIUM_PERIOD_NS,
+    NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD,
+};
+
+use common::state::StableState;
+
+#[cfg(test)]
+mod tests;
+
+/// Names released from the expiry lifecycle, they are sold with a premium decaying over time
+#[derive(Default)]
+pub struct ReleasedNameStore {
+    /// name -> when the name is released, ordered by name for paging
+    released_names: BTreeMap<String, u64>,
+}
+
+impl StableState for ReleasedNameStore {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((&self.released_names,)).unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (released_names,): (BTreeMap<String, u64>,) = decode_args(&bytes).unwrap();
+
+        Ok(ReleasedNameStore { released_names })
+    }
+}
+
+impl ReleasedNameStore {
+    pub fn add_released_name(&mut self, name: String, released_at: u64) {
+        self.released_names.insert(name, released_at);
+    }
+
+    pub fn remove_released_name(&mut self, name: &str) {
+        self.released_names.remove(name);
+    }
+
+    pub fn get_released_at(&self, name: &str) -> Option<u64> {
+        self.released_names.get(name).cloned()
+    }
+
+    pub fn get_premium_in_xdr_permyriad(&self, name: &str, now: u64) -> u64 {
+        self.get_released_at(name)
+            .map(|released_at| get_release_premium_in_xdr_permyriad(released_at, now))
+            .unwrap_or(0)
+    }
+
+    /// Premiums of names after the cursor ordered by name, at most `limit` items are returned
+    pub fn get_premiums_in_xdr_permyriad(
+        &self,
+        now: u64,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Vec<(String, u64)> {
+        let start = match cursor {
+            Some(cursor) => Bound::Excluded(cursor.to_string()),
+            None => Bound::Unbounded,
+        };
+        self.released_names
+            .range((start, Bound::Unbounded))
+            .map(|(name, released_at)| {
+                (
+                    name.clone(),
+                    get_release_premium_in_xdr_permyriad(*released_at, now),
+                )
+            })
+            .filter(|(_, premium)| *premium > 0)
+            .take(limit)
+            .collect()
+    }
+
+    pub fn remove_premium_ended(&mut self, now: u64) -> usize {
+        let count = self.released_names.len();
+        self.released_names
+            .retain(|_, released_at| get_release_premium_in_xdr_permyriad(*released_at, now) > 0);
+        count - self.released_names.len()
+    }
+}
+
+/// Premium of a released name, it halves every half life and drops to zero at the end of the period.
+pub fn get_release_premium_in_xdr_permyriad(released_at: u64, now: u64) -> u64 {
+    let elapsed = now.saturating_sub(released_at);
+    if elapsed >= NAMING_RELEASE_PREMIUM_PERIOD_NS {
+        return 0;
+    }
+    let half_lives = elapsed / NAMING_RELEASE_PREMIUM_HALF_LIFE_NS;
+    let start = (NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD >> half_lives) as u128;
+    // linear interpolation between two half lives
+    let remainder = (elapsed % NAMING_RELEASE_PREMIUM_HALF_LIFE_NS) as u128;
+    let premium = start - start / 2 * remainder / NAMING_RELEASE_PREMIUM_HALF_LIFE_NS as u128;
+    // premium at the end of the period, subtract it to make the premium drop to zero
+    let end = NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD
+        >> (NAMING_RELEASE_PREMIUM_PERIOD_NS / NAMING_RELEASE_PREMIUM_HALF_LIFE_NS);
+    (premium as u64).saturating_sub(end)
+}
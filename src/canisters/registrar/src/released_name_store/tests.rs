@@ -0,0 +1,81 @@
+use rstest::*;
+
+use common::constants::{
+    NAMING_RELEASE_PREMIUM_HALF_LIFE_NS, NAMING_RELEASE_PREMIUM_PERIOD_NS,
+    NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD,
+};
+use test_common::user::*;
+
+use crate::released_name_store::{get_release_premium_in_xdr_permyriad, ReleasedNameStore};
+
+const END_PREMIUM: u64 = NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD >> 21;
+
+#[fixture]
+fn store() -> ReleasedNameStore {
+    ReleasedNameStore::default()
+}
+
+#[rstest]
+#[case(0, NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD - END_PREMIUM)]
+#[case(
+    NAMING_RELEASE_PREMIUM_HALF_LIFE_NS / 2,
+    NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD * 3 / 4 - END_PREMIUM
+)]
+#[case(
+    NAMING_RELEASE_PREMIUM_HALF_LIFE_NS,
+    NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD / 2 - END_PREMIUM
+)]
+#[case(
+    NAMING_RELEASE_PREMIUM_HALF_LIFE_NS * 3,
+    NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD / 8 - END_PREMIUM
+)]
+#[case(NAMING_RELEASE_PREMIUM_PERIOD_NS, 0)]
+fn test_get_release_premium(mock_now: u64, #[case] elapsed: u64, #[case] expected: u64) {
+    let premium = get_release_premium_in_xdr_permyriad(mock_now, mock_now + elapsed);
+    assert_eq!(premium, expected);
+}
+
+#[rstest]
+fn test_get_premium_not_released(store: ReleasedNameStore, mock_now: u64) {
+    assert_eq!(store.get_premium_in_xdr_permyriad("nice.icp", mock_now), 0);
+}
+
+#[rstest]
+fn test_remove_premium_ended(mut store: ReleasedNameStore, mock_now: u64) {
+    store.add_released_name(
+        "ended.icp".to_string(),
+        mock_now - NAMING_RELEASE_PREMIUM_PERIOD_NS,
+    );
+    store.add_released_name("nice.icp".to_string(), mock_now);
+
+    let removed = store.remove_premium_ended(mock_now);
+
+    assert_eq!(removed, 1);
+    assert_eq!(store.get_released_at("ended.icp"), None);
+    assert_eq!(
+        store.get_premiums_in_xdr_permyriad(mock_now, None, 10),
+        vec![(
+            "nice.icp".to_string(),
+            NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD - END_PREMIUM
+        )]
+    );
+}
+
+#[rstest]
+fn test_get_premiums_after_cursor(mut store: ReleasedNameStore, mock_now: u64) {
+    for name in ["c.icp", "a.icp", "b.icp"] {
+        store.add_released_name(name.to_string(), mock_now);
+    }
+
+    let names = |premiums: Vec<(String, u64)>| -> Vec<String> {
+        premiums.into_iter().map(|(name, _)| name).collect()
+    };
+    assert_eq!(
+        names(store.get_premiums_in_xdr_permyriad(mock_now, None, 2)),
+        vec!["a.icp", "b.icp"]
+    );
+    assert_eq!(
+        names(store.get_premiums_in_xdr_permyriad(mock_now, Some("b.icp"), 2)),
+        vec!["c.icp"]
+    );
+}
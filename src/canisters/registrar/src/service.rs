@@ -17,11 +17,17 @@ use crate::nft::{
 use crate::token_identifier::{
     encode_token_id, get_valid_token_index, TokenIdentifier, TokenIndex,
 };
-use common::canister_api::ic_impl::{CyclesMintingApi, RegistryApi, ResolverApi};
-use common::canister_api::{AccountIdentifier, ICyclesMintingApi, IRegistryApi, IResolverApi};
+use common::canister_api::ic_impl::{
+    CyclesMintingApi, RegistrarArchiveApi, RegistryApi, ResolverApi,
+};
+use common::canister_api::{
+    AccountIdentifier, ICyclesMintingApi, IRegistrarArchiveApi, IRegistryApi, IResolverApi,
+};
+use common::certified_map::CertifiedValue;
 use common::constants::*;
 use common::dto::{
-    BatchAddQuotaRequest, GetPageInput, GetPageOutput, ImportQuotaRequest, ImportQuotaStatus,
+    BatchAddQuotaRequest, GetCursorPageInput, GetCursorPageOutput, GetPageInput, GetPageOutput,
+    ImportQuotaRequest, ImportQuotaStatus,
 };
 use common::errors::{NamingError, ServiceResult};
 use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
@@ -31,11 +37,20 @@ use common::permissions::{
     must_be_in_named_canister, must_be_named_canister, must_be_system_owner,
 };
 use common::permissions::{must_be_named_principal, must_not_anonymous};
+use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
 use common::{AuthPrincipal, CallContext, CanisterId, TimeInNs};
 
+use crate::balance_store::LocalTransactionId;
+use crate::certification::{certify, get_certified_value, get_owner_key};
+use crate::commitment_store::{is_commitment_expired, make_commitment};
+use crate::icrc3::{
+    get_name_meta, get_quota_transaction, Transaction, BTYPE_APPROVE, BTYPE_BURN, BTYPE_MINT,
+    BTYPE_RECLAIM, BTYPE_TRANSFER, BTYPE_TRANSFER_FROM, BTYPE_UPDATE_TOKEN,
+};
 use crate::name_locker::{try_lock_name, unlock_name};
+use crate::payment_token_store::{PaymentToken, PriceFeed};
 use crate::registration_store::{
-    Registration, RegistrationDetails, RegistrationDto, RegistrationStore,
+    Registration, RegistrationDetails, RegistrationDto, RegistrationLifecycle, RegistrationStore,
 };
 use crate::reserved_list::RESERVED_NAMES;
 use crate::state::*;
@@ -48,6 +63,26 @@ pub struct RegisterNameWithPaymentRequest {
     pub name: String,
     pub years: u32,
     pub approve_amount: Nat,
+    /// ICRC-2 ledger of the token to pay with, DICP is used if not set
+    pub ledger: Option<Principal>,
+}
+
+#[derive(Deserialize, CandidType, Debug)]
+pub struct RevealRegisterWithPaymentRequest {
+    pub name: String,
+    pub years: u32,
+    pub approve_amount: Nat,
+    pub secret: Vec<u8>,
+    /// ICRC-2 ledger of the token to pay with, DICP is used if not set
+    pub ledger: Option<Principal>,
+}
+
+#[derive(Deserialize, CandidType, Debug)]
+pub struct RevealRegisterWithQuotaRequest {
+    pub name: String,
+    pub years: u32,
+    pub quota_type: QuotaType,
+    pub secret: Vec<u8>,
 }
 
 pub struct RegistrarService {
@@ -55,6 +90,7 @@ pub struct RegistrarService {
     pub cycles_minting_api: Arc<dyn ICyclesMintingApi>,
     pub token_service: TokenService,
     pub resolver_api: Arc<dyn IResolverApi>,
+    pub archive_api: Arc<dyn IRegistrarArchiveApi>,
 }
 
 impl Debug for RegistrarService {
@@ -70,6 +106,7 @@ impl Default for RegistrarService {
             cycles_minting_api: Arc::new(CyclesMintingApi),
             token_service: TokenService::default(),
             resolver_api: Arc::new(ResolverApi),
+            archive_api: Arc::new(RegistrarArchiveApi),
         }
     }
 }
@@ -87,12 +124,10 @@ impl RegistrarService {
             let store = s.registration_store.borrow();
 
             let items = store
-                .get_registrations()
-                .values()
-                .filter(|registration| registration.is_owner(owner))
+                .get_user_owned_registrations_after(owner, None)
                 .skip(input.offset)
                 .take(input.limit)
-                .map(|registration| registration.into())
+                .map(|registration| (&registration).into())
                 .collect();
             GetPageOutput::new(items)
         });
@@ -100,16 +135,37 @@ impl RegistrarService {
         Ok(result)
     }
 
+    /// Names of the owner ordered by name, the cursor is the last name of the previous page.
+    pub(crate) fn get_names_by_cursor(
+        &self,
+        owner: &Principal,
+        input: &GetCursorPageInput,
+    ) -> ServiceResult<GetCursorPageOutput<RegistrationDto>> {
+        input.validate()?;
+        must_not_anonymous(owner)?;
+
+        let registrations: Vec<Registration> = STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            store
+                .get_user_owned_registrations_after(owner, input.cursor.as_deref())
+                .take(input.limit + 1)
+                .collect()
+        });
+
+        Ok(
+            GetCursorPageOutput::new(registrations, input.limit, |registration| {
+                registration.get_name()
+            })
+            .map(|registration| (&registration).into()),
+        )
+    }
+
     pub(crate) fn get_names_count(&self, owner: &Principal) -> ServiceResult<u32> {
         must_not_anonymous(owner)?;
 
         STATE.with(|s| {
             let store = s.registration_store.borrow();
-            let total = store
-                .get_registrations()
-                .values()
-                .filter(|registration| registration.is_owner(owner))
-                .count() as u32;
+            let total = store.get_user_owned_registrations_count(owner) as u32;
             Ok(total)
         })
     }
@@ -118,12 +174,11 @@ impl RegistrarService {
         let name = normalize_name(name);
         STATE.with(|s| {
             let store = s.registration_store.borrow();
-            let registrations = store.get_registrations();
-            let registration = registrations.get(&name.0);
+            let registration = store.get_registration_by_name(&name.0);
             if registration.is_none() {
                 return Err(NamingError::RegistrationNotFound);
             }
-            Ok(RegistrationDetails::from(registration.unwrap()))
+            Ok(RegistrationDetails::from(&registration.unwrap()))
         })
     }
 
@@ -138,10 +193,9 @@ impl RegistrarService {
             let store = s.registration_store.borrow();
             store
                 .get_registrations()
-                .values()
                 .skip(input.offset)
                 .take(input.limit)
-                .map(|registration| registration.into())
+                .map(|registration| (&registration).into())
                 .collect()
         });
 
@@ -160,6 +214,25 @@ impl RegistrarService {
         })
     }
 
+    /// Owner of the name with the witness of `owner/{name}` in the certified map.
+    /// It is None if the name is not registered.
+    pub(crate) fn get_owner_certified(
+        &self,
+        name: &str,
+    ) -> ServiceResult<CertifiedValue<Principal>> {
+        let name = validate_name(name)?;
+        let owner = STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            store
+                .get_registration(&name)
+                .map(|registration| registration.get_owner())
+        });
+        Ok(get_certified_value(
+            &get_owner_key(&name.to_string()),
+            owner,
+        ))
+    }
+
     pub(crate) fn get_name_expires(&self, name: &str) -> ServiceResult<u64> {
         let name = validate_name(name)?;
         STATE.with(|s| {
@@ -284,6 +357,9 @@ impl RegistrarService {
             let own_registration_count = STATE.with(|s| {
                 let mut store = s.registration_store.borrow_mut();
                 store.add_registration(registration.clone());
+                certify(&store, &[registration.get_name()]);
+                let mut released_name_store = s.released_name_store.borrow_mut();
+                released_name_store.remove_released_name(&name);
                 let mut token_index_store = s.token_index_store.borrow_mut();
 
                 match token_index_store.try_add_registration_name(&registration.get_name()) {
@@ -299,6 +375,11 @@ impl RegistrarService {
                 }
                 store.get_user_owned_registrations_count(&owner.0)
             });
+            Transaction::new(BTYPE_MINT)
+                .with_name(&name)
+                .with_account("to", &owner.0)
+                .with_value("meta", get_name_meta(&name, expired_at.0))
+                .log();
             MERTRICS_COUNTER.with(|c| {
                 let mut counter = c.borrow_mut();
                 counter.push_registration(registration.clone());
@@ -371,6 +452,12 @@ impl RegistrarService {
         quota_type: QuotaType,
     ) -> ServiceResult<bool> {
         let name_result = context.validate()?;
+        if self.get_release_premium_in_xdr_permyriad(&name_result, context.now) > 0 {
+            return Err(NamingError::NameUnavailable {
+                reason: "the name is released recently, please register it with payment"
+                    .to_string(),
+            });
+        }
         // validate quota
         let years = context.years;
         let quota_result = self.validate_quota(&name_result, quota_owner, &quota_type, years);
@@ -385,6 +472,7 @@ impl RegistrarService {
             let mut user_quota_manager = s.user_quota_store.borrow_mut();
             user_quota_manager.sub_quota(&quota_owner, &quota_type, years)
         })?;
+        get_quota_transaction(Some(&quota_owner.0), None, &quota_type, years).log();
 
         let result = self.register_core(context).await;
 
@@ -394,8 +482,9 @@ impl RegistrarService {
             // rollback quota
             STATE.with(|s| {
                 let mut user_quota_manager = s.user_quota_store.borrow_mut();
-                user_quota_manager.add_quota(*quota_owner, quota_type, years);
+                user_quota_manager.add_quota(*quota_owner, quota_type.clone(), years);
             });
+            get_quota_transaction(None, Some(&quota_owner.0), &quota_type, years).log();
             Err(result.err().unwrap())
         }
     }
@@ -418,8 +507,71 @@ impl RegistrarService {
         })
     }
 
-    pub fn clean_expired(&mut self, _now_in_ms: u64) -> ServiceResult<()> {
-        todo!("clean up")
+    pub async fn clean_expired(&self, now: TimeInNs) -> ServiceResult<()> {
+        if !try_lock_with_timeout(LockId::RegistrarCleanExpired, now) {
+            debug!("clean_expired: already locked");
+            return Ok(());
+        }
+        let names = STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            store.get_released_registration_names(now.0, NAMING_CLEAN_EXPIRED_BATCH_SIZE)
+        });
+        if names.is_empty() {
+            debug!("clean_expired: no released registrations");
+        }
+        for name in names {
+            let _ = self.release_name(name.as_str(), now).await;
+        }
+        STATE.with(|s| {
+            let mut store = s.released_name_store.borrow_mut();
+            let count = store.remove_premium_ended(now.0);
+            debug!("clean_expired: {} released names premium ended", count);
+        });
+        release_timeout_locker(LockId::RegistrarCleanExpired);
+        Ok(())
+    }
+
+    async fn release_name(&self, name: &str, now: TimeInNs) -> ServiceResult<()> {
+        let first_level_name = validate_name(name)?;
+        try_lock_name(&first_level_name)?;
+        let remove_result = self.registry_api.remove_name(name.to_string()).await;
+        unlock_name(&first_level_name);
+        if let Err(e) = remove_result {
+            error!("release name: {} failed: {}", name, e.message);
+            return Err(NamingError::RemoteError(e));
+        }
+
+        // the token index of the name is removed below
+        let owner = STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            store
+                .get_registration(&first_level_name)
+                .map(|registration| registration.get_owner())
+        });
+        let burn = owner.map(|owner| {
+            Transaction::new(BTYPE_BURN)
+                .with_name(name)
+                .with_account("from", &owner)
+        });
+        STATE.with(|s| {
+            let mut store = s.registration_store.borrow_mut();
+            store.remove_registration(name);
+            certify(&store, &[name.to_string()]);
+
+            let mut token_index_store = s.token_index_store.borrow_mut();
+            token_index_store.remove_registration_name(&name.to_string());
+
+            let mut approval_store = s.registration_approval_store.borrow_mut();
+            approval_store.remove_approval(&first_level_name);
+
+            let mut released_name_store = s.released_name_store.borrow_mut();
+            released_name_store.add_released_name(name.to_string(), now.0);
+        });
+        if let Some(burn) = burn {
+            burn.log();
+        }
+        info!("release name: {} success", name);
+        Ok(())
     }
 
     pub async fn register_with_payment(
@@ -433,7 +585,11 @@ impl RegistrarService {
         validate_year(request.years)?;
         let name_len = name_result.get_name_len();
         let length_limit = 6;
-        if name_len < length_limit {
+        // names in the premium period after released can only be registered with payment,
+        // so short ones are not left to whoever registers them with quota once the premium ends
+        if name_len < length_limit
+            && self.get_release_premium_in_xdr_permyriad(&name_result, call_context.now) == 0
+        {
             return Err(NamingError::InvalidName {
                 reason: format!(
                     "the name need to be at least {} characters long",
@@ -443,23 +599,45 @@ impl RegistrarService {
         }
         let years = request.years;
         let quota_type_len = name_result.0.get_quota_type_len();
-        let amount = self.get_name_price(years, quota_type_len).await?;
+        let payment_token = self.get_payment_token(&request.ledger)?;
+        let amount = match &payment_token {
+            Some(token) => {
+                let xdr_permyriad = get_price_in_xdr_permyriad(quota_type_len).to_u64().unwrap()
+                    * years as u64
+                    + self.get_release_premium_in_xdr_permyriad(&name_result, call_context.now);
+                let xdr_permyriad_per_token = self.get_xdr_permyriad_per_token(token).await?;
+                token.convert_xdr_permyriad(xdr_permyriad, xdr_permyriad_per_token)
+            }
+            None => {
+                let mut amount = self.get_name_price(years, quota_type_len).await?;
+                let premium = self
+                    .get_release_premium(&name_result, call_context.now)
+                    .await?;
+                if premium > 0 {
+                    debug!(
+                        "register_with_payment: {} is released recently, premium: {}",
+                        name_result, premium
+                    );
+                    amount += premium;
+                }
+                Nat::from(amount)
+            }
+        };
 
         // validate request.approve_price is within the range of register_price 5%
-        if request.approve_amount < amount * 95 / 100 {
+        let min_amount = get_min_approve_amount(amount);
+        if request.approve_amount < min_amount {
             debug!(
                 "register_with_payment: approve_amount is too low: {} < {}",
-                request.approve_amount,
-                amount * 95 / 100
+                request.approve_amount, min_amount
             );
             return Err(NamingError::InvalidApproveAmount);
         }
 
         let result = self
-            .token_service
-            .transfer_from(
-                caller.0.to_text().as_str(),
-                DICP_RECEIVER.deref(),
+            .receive_payment(
+                &caller.0,
+                &payment_token,
                 request.approve_amount.clone(),
                 call_context.now,
             )
@@ -494,6 +672,141 @@ impl RegistrarService {
         }
     }
 
+    /// Commit a name to be registered, the commitment is generated by `make_commitment`
+    /// so that the name is not exposed until it is revealed.
+    /// A user keeps at most `NAMING_MAX_COMMITMENTS_PER_USER` unexpired commitments at the same time.
+    pub fn commit(&self, call_context: CallContext, commitment: String) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let commitment = commitment.to_lowercase();
+        if commitment.len() != 64 {
+            return Err(NamingError::InvalidCommitment {
+                reason: "commitment must be 64 hex characters".to_string(),
+            });
+        }
+        if hex::decode(&commitment).is_err() {
+            return Err(NamingError::InvalidCommitment {
+                reason: "commitment must be hex encoded".to_string(),
+            });
+        }
+        STATE.with(|s| {
+            let mut store = s.commitment_store.borrow_mut();
+            if let Some(committed_at) = store.get_committed_at(&commitment) {
+                if !is_commitment_expired(committed_at, call_context.now.0) {
+                    return Err(NamingError::CommitmentAlreadyExists);
+                }
+            }
+            store.remove_expired_of(&caller.0, call_context.now.0);
+            if store.get_commitment_count(&caller.0) >= NAMING_MAX_COMMITMENTS_PER_USER {
+                return Err(NamingError::TooManyCommitments {
+                    max: NAMING_MAX_COMMITMENTS_PER_USER,
+                });
+            }
+            store.add_commitment(commitment, caller.0, call_context.now.0);
+            Ok(true)
+        })
+    }
+
+    fn validate_commitment(
+        &self,
+        name: &str,
+        owner: &Principal,
+        secret: &[u8],
+        now: TimeInNs,
+    ) -> ServiceResult<String> {
+        let commitment = make_commitment(name, owner, secret);
+        STATE.with(|s| {
+            let store = s.commitment_store.borrow();
+            let committed_at = store
+                .get_committed_at(&commitment)
+                .ok_or(NamingError::CommitmentNotFound)?;
+            if is_commitment_expired(committed_at, now.0) {
+                return Err(NamingError::CommitmentExpired);
+            }
+            let revealable_at = committed_at + NAMING_COMMITMENT_MIN_AGE_NS;
+            if now.0 < revealable_at {
+                return Err(NamingError::CommitmentTooNew {
+                    seconds: (revealable_at - now.0 + 999_999_999) / 1_000_000_000,
+                });
+            }
+            Ok(commitment)
+        })
+    }
+
+    fn remove_commitment(&self, commitment: &str) {
+        STATE.with(|s| {
+            let mut store = s.commitment_store.borrow_mut();
+            store.remove_commitment(commitment);
+        });
+    }
+
+    pub async fn reveal_register_with_payment(
+        &self,
+        call_context: CallContext,
+        request: RevealRegisterWithPaymentRequest,
+    ) -> ServiceResult<RegistrationDetails> {
+        let caller = call_context.must_not_anonymous()?;
+        let commitment = self.validate_commitment(
+            request.name.as_str(),
+            &caller.0,
+            request.secret.as_slice(),
+            call_context.now,
+        )?;
+        let result = self
+            .register_with_payment(
+                call_context,
+                RegisterNameWithPaymentRequest {
+                    name: request.name,
+                    years: request.years,
+                    approve_amount: request.approve_amount,
+                    ledger: request.ledger,
+                },
+            )
+            .await;
+        // keep the commitment to retry if registration failed
+        if result.is_ok() {
+            self.remove_commitment(commitment.as_str());
+        }
+        result
+    }
+
+    pub async fn reveal_register_with_quota(
+        &mut self,
+        call_context: CallContext,
+        request: RevealRegisterWithQuotaRequest,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let commitment = self.validate_commitment(
+            request.name.as_str(),
+            &caller.0,
+            request.secret.as_slice(),
+            call_context.now,
+        )?;
+        let result = self
+            .register_with_quota(
+                request.name,
+                caller.0,
+                request.years,
+                call_context.now,
+                &caller.0,
+                request.quota_type,
+                false,
+            )
+            .await;
+        // keep the commitment to retry if registration failed
+        if result.is_ok() {
+            self.remove_commitment(commitment.as_str());
+        }
+        result
+    }
+
+    pub fn clean_expired_commitments(&self, now: TimeInNs) {
+        STATE.with(|s| {
+            let mut store = s.commitment_store.borrow_mut();
+            let count = store.remove_expired(now.0);
+            debug!("clean_expired_commitments: {} commitments removed", count);
+        });
+    }
+
     async fn get_name_price(&self, years: u32, quota_type_len: u8) -> ServiceResult<u64> {
         let response = self
             .cycles_minting_api
@@ -530,11 +843,44 @@ impl RegistrarService {
         quota_type: QuotaType,
         diff: u32,
     ) -> ServiceResult<bool> {
-        must_be_system_owner(caller)?;
+        if must_be_system_owner(caller).is_err() {
+            must_be_named_canister(*caller, CanisterNames::MysteryBox)?;
+        }
         let quota_owner = must_not_anonymous(&quota_owner)?;
         STATE.with(|s| {
             let mut user_quota_manager = s.user_quota_store.borrow_mut();
-            user_quota_manager.add_quota(quota_owner, quota_type, diff);
+            user_quota_manager.add_quota(quota_owner, quota_type.clone(), diff);
+        });
+        get_quota_transaction(None, Some(&quota_owner.0), &quota_type, diff).log();
+        Ok(true)
+    }
+
+    /// Add quota only once for the same key of the caller, so that the caller can retry it safely.
+    /// Returns true if the quota is added or it has been added with the key before.
+    pub fn add_quota_with_key(
+        &mut self,
+        caller: &Principal,
+        quota_owner: Principal,
+        quota_type: QuotaType,
+        diff: u32,
+        key: String,
+    ) -> ServiceResult<bool> {
+        if must_be_system_owner(caller).is_err() {
+            must_be_named_canister(*caller, CanisterNames::MysteryBox)?;
+        }
+        let credit_key = format!("{}:{}", caller.to_text(), key);
+        let credited = STATE.with(|s| {
+            let user_quota_manager = s.user_quota_store.borrow();
+            user_quota_manager.is_credited(&credit_key)
+        });
+        if credited {
+            info!("add_quota_with_key: {} has been credited", credit_key);
+            return Ok(true);
+        }
+        self.add_quota(caller, quota_owner, quota_type, diff)?;
+        STATE.with(|s| {
+            let mut user_quota_manager = s.user_quota_store.borrow_mut();
+            user_quota_manager.add_credit_key(credit_key);
         });
         Ok(true)
     }
@@ -552,6 +898,7 @@ impl RegistrarService {
             let mut user_quota_manager = s.user_quota_store.borrow_mut();
             user_quota_manager.sub_quota(&quota_owner, &quota_type, diff)
         })?;
+        get_quota_transaction(Some(&quota_owner.0), None, &quota_type, diff).log();
         Ok(true)
     }
 
@@ -571,7 +918,43 @@ impl RegistrarService {
         })
     }
 
-    pub async fn get_price_table(&self) -> ServiceResult<PriceTable> {
+    fn get_release_premium_in_xdr_permyriad(&self, name: &FirstLevelName, now: TimeInNs) -> u64 {
+        STATE.with(|s| {
+            let store = s.released_name_store.borrow();
+            store.get_premium_in_xdr_permyriad(name.0.get_name(), now.0)
+        })
+    }
+
+    async fn get_release_premium(
+        &self,
+        name: &FirstLevelName,
+        now: TimeInNs,
+    ) -> ServiceResult<u64> {
+        let premium_in_xdr_permyriad = self.get_release_premium_in_xdr_permyriad(name, now);
+        if premium_in_xdr_permyriad == 0 {
+            return Ok(0);
+        }
+        let response = self
+            .cycles_minting_api
+            .get_icp_xdr_conversion_rate()
+            .await?;
+        Ok(convert_xdr_permyriad_to_icp_e8s(
+            premium_in_xdr_permyriad,
+            response.data.xdr_permyriad_per_icp,
+        ))
+    }
+
+    async fn get_redemption_fee(&self) -> ServiceResult<u64> {
+        let response = self
+            .cycles_minting_api
+            .get_icp_xdr_conversion_rate()
+            .await?;
+        Ok(get_redemption_fee_in_icp_e8s(
+            response.data.xdr_permyriad_per_icp,
+        ))
+    }
+
+    pub async fn get_price_table(&self, now: TimeInNs) -> ServiceResult<PriceTable> {
         let response = self
             .cycles_minting_api
             .get_icp_xdr_conversion_rate()
@@ -587,12 +970,159 @@ impl RegistrarService {
                 price_in_icp_e8s: get_price_in_icp_e8s(x, icp_xdr_conversion_rate),
             });
         }
+        let release_premiums = get_release_premium_page(
+            now,
+            &GetCursorPageInput {
+                cursor: None,
+                limit: NAMING_PRICE_TABLE_RELEASE_PREMIUM_LIMIT,
+            },
+            icp_xdr_conversion_rate,
+        );
+        let token_prices = STATE.with(|s| {
+            let store = s.payment_token_store.borrow();
+            store
+                .get_tokens()
+                .into_iter()
+                .map(|token| {
+                    let xdr_permyriad_per_token = match token.price_feed {
+                        PriceFeed::IcpXdrConversionRate => icp_xdr_conversion_rate,
+                        PriceFeed::XdrPermyriadPerToken(rate) => rate,
+                    };
+                    let items = (1..=7)
+                        .map(|len| TokenPriceItem {
+                            len,
+                            price: token.convert_xdr_permyriad(
+                                get_price_in_xdr_permyriad(len).to_u64().unwrap(),
+                                xdr_permyriad_per_token,
+                            ),
+                        })
+                        .collect();
+                    TokenPriceTable {
+                        ledger: token.ledger,
+                        symbol: token.symbol,
+                        decimals: token.decimals,
+                        xdr_permyriad_per_token,
+                        items,
+                    }
+                })
+                .collect()
+        });
         Ok(PriceTable {
             items,
             icp_xdr_conversion_rate,
+            release_premiums: release_premiums.items,
+            release_premiums_next_cursor: release_premiums.next_cursor,
+            token_prices,
         })
     }
 
+    /// Premiums of released names after the cursor, ordered by name
+    pub async fn get_release_premiums(
+        &self,
+        now: TimeInNs,
+        input: &GetCursorPageInput,
+    ) -> ServiceResult<GetCursorPageOutput<ReleasePremiumItem>> {
+        input.validate()?;
+        let response = self
+            .cycles_minting_api
+            .get_icp_xdr_conversion_rate()
+            .await?;
+        Ok(get_release_premium_page(
+            now,
+            input,
+            response.data.xdr_permyriad_per_icp,
+        ))
+    }
+
+    pub fn set_payment_token(
+        &self,
+        caller: &Principal,
+        token: PaymentToken,
+    ) -> ServiceResult<bool> {
+        must_be_system_owner(caller)?;
+        token.validate()?;
+        STATE.with(|s| {
+            let mut store = s.payment_token_store.borrow_mut();
+            info!("set_payment_token: {:?}", token);
+            store.set_token(token);
+        });
+        Ok(true)
+    }
+
+    pub fn remove_payment_token(
+        &self,
+        caller: &Principal,
+        ledger: Principal,
+    ) -> ServiceResult<bool> {
+        must_be_system_owner(caller)?;
+        STATE.with(|s| {
+            let mut store = s.payment_token_store.borrow_mut();
+            store
+                .remove_token(&ledger)
+                .ok_or(NamingError::PaymentTokenNotSupported {
+                    ledger: ledger.to_text(),
+                })?;
+            info!("remove_payment_token: {}", ledger);
+            Ok(true)
+        })
+    }
+
+    pub fn get_payment_tokens(&self) -> Vec<PaymentToken> {
+        STATE.with(|s| {
+            let store = s.payment_token_store.borrow();
+            store.get_tokens()
+        })
+    }
+
+    /// Returns the token to pay with, DICP is used when ledger is not set
+    fn get_payment_token(&self, ledger: &Option<Principal>) -> ServiceResult<Option<PaymentToken>> {
+        match ledger {
+            Some(ledger) => STATE.with(|s| {
+                let store = s.payment_token_store.borrow();
+                store.get_token(ledger).cloned().map(Some).ok_or(
+                    NamingError::PaymentTokenNotSupported {
+                        ledger: ledger.to_text(),
+                    },
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_xdr_permyriad_per_token(&self, token: &PaymentToken) -> ServiceResult<u64> {
+        match token.price_feed {
+            PriceFeed::IcpXdrConversionRate => {
+                let response = self
+                    .cycles_minting_api
+                    .get_icp_xdr_conversion_rate()
+                    .await?;
+                Ok(response.data.xdr_permyriad_per_icp)
+            }
+            PriceFeed::XdrPermyriadPerToken(rate) => Ok(rate),
+        }
+    }
+
+    async fn receive_payment(
+        &self,
+        from: &Principal,
+        payment_token: &Option<PaymentToken>,
+        amount: Nat,
+        now: TimeInNs,
+    ) -> ServiceResult<LocalTransactionId> {
+        match payment_token {
+            Some(token) => {
+                self.token_service
+                    .transfer_from_ledger(token, from, amount, now)
+                    .await
+            }
+            None => {
+                self.token_service
+                    .transfer_from(from.to_text().as_str(), DICP_RECEIVER.deref(), amount, now)
+                    .await
+            }
+        }
+    }
+
     pub fn import_quota(
         &self,
         caller: &Principal,
@@ -613,11 +1143,9 @@ impl RegistrarService {
             let mut store = s.user_quota_store.borrow_mut();
             for item in items.iter() {
                 let quota_to = must_not_anonymous(&item.owner)?;
-                store.add_quota(
-                    quota_to,
-                    QuotaType::from_str(item.quota_type.as_str()).unwrap(),
-                    item.diff,
-                );
+                let quota_type = QuotaType::from_str(item.quota_type.as_str()).unwrap();
+                store.add_quota(quota_to, quota_type.clone(), item.diff);
+                get_quota_transaction(None, Some(&quota_to.0), &quota_type, item.diff).log();
             }
 
             let hash = request.hash;
@@ -666,6 +1194,10 @@ impl RegistrarService {
                     "reclaim name: {} to user {} success",
                     name, &registration_owner
                 );
+                Transaction::new(BTYPE_RECLAIM)
+                    .with_name(&name.to_string())
+                    .with_account("owner", &registration_owner)
+                    .log();
                 Ok(result)
             }
             Err(e) => {
@@ -679,17 +1211,20 @@ impl RegistrarService {
         result
     }
 
+    /// Transfer the name to the new owner, `spender` is the caller transferring names of others.
+    /// Returns the index of the transfer block in the ICRC-3 log.
     async fn transfer_core(
         &self,
         name: &FirstLevelName,
         new_owner: &Principal,
-    ) -> ServiceResult<bool> {
-        STATE.with(|s| {
+        spender: Option<&Principal>,
+    ) -> ServiceResult<u64> {
+        let from = STATE.with(|s| {
             let store = s.registration_store.borrow();
-            if !store.has_registration(name) {
-                return Err(NamingError::RegistrationNotFound);
+            match store.get_registration(name) {
+                Some(registration) => Ok(registration.get_owner()),
+                None => Err(NamingError::RegistrationNotFound),
             }
-            Ok(())
         })?;
         try_lock_name(&name)?;
         let registry_result = self
@@ -706,13 +1241,23 @@ impl RegistrarService {
         STATE.with(|s| {
             let mut store = s.registration_store.borrow_mut();
             store.transfer_registration(name.to_string(), *new_owner);
+            certify(&store, &[name.to_string()]);
 
             let mut store = s.registration_approval_store.borrow_mut();
             store.remove_approval(name);
 
             info!("transfer name: {} to user {}", name, &new_owner);
-            Ok(true)
-        })
+        });
+        let transaction = match spender {
+            Some(spender) => Transaction::new(BTYPE_TRANSFER_FROM).with_account("spender", spender),
+            None => Transaction::new(BTYPE_TRANSFER),
+        };
+        let index = transaction
+            .with_name(&name.to_string())
+            .with_account("from", &from)
+            .with_account("to", new_owner)
+            .log();
+        Ok(index)
     }
 
     pub(crate) async fn transfer(
@@ -721,13 +1266,25 @@ impl RegistrarService {
         caller: &Principal,
         new_owner: Principal,
     ) -> ServiceResult<bool> {
+        self.transfer_name(name, caller, new_owner)
+            .await
+            .map(|_| true)
+    }
+
+    /// Transfer the name owned by caller, returns the index of the transfer block
+    pub(crate) async fn transfer_name(
+        &self,
+        name: &str,
+        caller: &Principal,
+        new_owner: Principal,
+    ) -> ServiceResult<u64> {
         let name = validate_name(&name)?;
         must_not_anonymous(caller)?;
         must_not_anonymous(&new_owner)?;
         self.is_name_owner(&name, caller)?;
         assert_ne!(caller, &new_owner);
 
-        self.transfer_core(&name, &new_owner).await
+        self.transfer_core(&name, &new_owner, None).await
     }
 
     // TODO: remove this function when all assignment is done
@@ -744,7 +1301,9 @@ impl RegistrarService {
             .any(|n| n == name.0.get_current_level().unwrap()));
         must_not_anonymous(&new_owner)?;
 
-        self.transfer_core(&name, &new_owner).await
+        self.transfer_core(&name, &new_owner, Some(caller))
+            .await
+            .map(|_| true)
     }
 
     pub fn approve(
@@ -754,6 +1313,17 @@ impl RegistrarService {
         name: &str,
         to: Principal,
     ) -> ServiceResult<bool> {
+        self.approve_name(caller, now, name, to).map(|_| true)
+    }
+
+    /// Approve the name owned by caller, returns the index of the approve block
+    pub(crate) fn approve_name(
+        &self,
+        caller: &Principal,
+        now: u64,
+        name: &str,
+        to: Principal,
+    ) -> ServiceResult<u64> {
         let name = validate_name(name)?;
         must_not_anonymous(caller)?;
         let _ = self.is_name_owner(&name, caller)?;
@@ -762,7 +1332,20 @@ impl RegistrarService {
         STATE.with(|s| {
             let mut store = s.registration_approval_store.borrow_mut();
             store.set_approval(&name, &to, now);
-            Ok(true)
+        });
+        let index = Transaction::new(BTYPE_APPROVE)
+            .with_name(&name.to_string())
+            .with_account("from", caller)
+            .with_account("spender", &to)
+            .log();
+        Ok(index)
+    }
+
+    pub fn is_approved_to(&self, name: &str, spender: &Principal) -> ServiceResult<bool> {
+        let name = validate_name(name)?;
+        STATE.with(|s| {
+            let store = s.registration_approval_store.borrow();
+            Ok(store.is_approved_to(&name, spender))
         })
     }
 
@@ -772,6 +1355,18 @@ impl RegistrarService {
         name: &str,
         to: Option<AuthPrincipal>,
     ) -> ServiceResult<bool> {
+        self.transfer_name_from(caller, name, to)
+            .await
+            .map(|_| true)
+    }
+
+    /// Transfer the name approved to caller, returns the index of the transfer block
+    pub(crate) async fn transfer_name_from(
+        &self,
+        caller: &Principal,
+        name: &str,
+        to: Option<AuthPrincipal>,
+    ) -> ServiceResult<u64> {
         let name = validate_name(name)?;
         must_not_anonymous(caller)?;
         STATE.with(|s| {
@@ -783,8 +1378,8 @@ impl RegistrarService {
             Ok(())
         })?;
         match to {
-            Some(to) => self.transfer_core(&name, &to.0).await,
-            None => self.transfer_core(&name, &caller).await,
+            Some(to) => self.transfer_core(&name, &to.0, Some(caller)).await,
+            None => self.transfer_core(&name, &caller, Some(caller)).await,
         }
     }
 
@@ -817,13 +1412,15 @@ impl RegistrarService {
             }
 
             store.sub_quota(&from, &quota_type, diff)?;
-            store.add_quota(to, quota_type, diff);
+            store.add_quota(to, quota_type.clone(), diff);
             info!(
                 "transfer quota: {} from user {} to user {}, diff: {}",
                 quota_type, &from, &to, diff
             );
-            Ok(true)
-        })
+            Ok(())
+        })?;
+        get_quota_transaction(Some(&from.0), Some(&to.0), &quota_type, diff).log();
+        Ok(true)
     }
 
     pub fn transfer_quota(
@@ -838,8 +1435,16 @@ impl RegistrarService {
         STATE.with(|s| {
             let mut store = s.user_quota_store.borrow_mut();
             store.transfer_quota(&caller, &details)?;
-            Ok(true)
-        })
+            Ok(())
+        })?;
+        get_quota_transaction(
+            Some(&caller.0),
+            Some(&details.to),
+            &details.quota_type,
+            details.diff,
+        )
+        .log();
+        Ok(true)
     }
 
     pub fn batch_transfer_quota(
@@ -856,8 +1461,13 @@ impl RegistrarService {
         STATE.with(|s| {
             let mut store = s.user_quota_store.borrow_mut();
             store.batch_transfer_quota(caller, request.items.as_slice())?;
-            Ok(true)
-        })
+            Ok(())
+        })?;
+        for item in request.items.iter() {
+            get_quota_transaction(Some(&caller.0), Some(&item.to), &item.quota_type, item.diff)
+                .log();
+        }
+        Ok(true)
     }
 
     pub fn unlock_names(&self, caller: &Principal, names: Vec<&str>) -> ServiceResult<bool> {
@@ -900,22 +1510,19 @@ impl RegistrarService {
         assert!(request.approve_amount > 0);
         must_not_anonymous(&caller)?;
         let first_level_name = validate_name(&request.name)?;
-        let renew_price = self
-            .get_name_price(request.years, first_level_name.0.get_quota_type_len())
-            .await?;
-
-        // validate request.approve_price is within the range of renew_price 10%
-        let approve_amount = request.approve_amount;
-        if approve_amount < renew_price * 95 / 100 {
-            return Err(NamingError::InvalidApproveAmount);
-        }
 
-        let new_expired_at = STATE.with(|s| {
+        let (new_expired_at, lifecycle) = STATE.with(|s| {
             let registration_store = s.registration_store.borrow();
             if let Some(registration) = registration_store.get_registration(&first_level_name) {
                 if !registration.is_owner(&caller) {
                     return Err(NamingError::InvalidOwner);
                 }
+                let lifecycle = registration.get_lifecycle(now.0);
+                if lifecycle == RegistrationLifecycle::Released {
+                    return Err(NamingError::RegistrationReleased {
+                        name: registration.get_name(),
+                    });
+                }
                 let new_expired_at =
                     get_expired_at(request.years, TimeInNs(registration.get_expired_at()));
                 if new_expired_at > get_expired_at(NAMING_MAX_REGISTRATION_YEAR, now) {
@@ -923,7 +1530,7 @@ impl RegistrarService {
                         years: NAMING_MAX_REGISTRATION_YEAR,
                     });
                 }
-                Ok(new_expired_at)
+                Ok((new_expired_at, lifecycle))
             } else {
                 Err(NamingError::InvalidName {
                     reason: "name not registered".to_string(),
@@ -931,14 +1538,41 @@ impl RegistrarService {
             }
         })?;
 
+        let quota_type_len = first_level_name.0.get_quota_type_len();
+        let payment_token = self.get_payment_token(&request.ledger)?;
+        let renew_price = match &payment_token {
+            Some(token) => {
+                let mut xdr_permyriad =
+                    get_price_in_xdr_permyriad(quota_type_len).to_u64().unwrap()
+                        * request.years as u64;
+                if lifecycle == RegistrationLifecycle::Redemption {
+                    xdr_permyriad += NAMING_REDEMPTION_FEE_IN_XDR_PERMYRIAD;
+                }
+                let xdr_permyriad_per_token = self.get_xdr_permyriad_per_token(token).await?;
+                token.convert_xdr_permyriad(xdr_permyriad, xdr_permyriad_per_token)
+            }
+            None => {
+                let mut renew_price = self.get_name_price(request.years, quota_type_len).await?;
+                if lifecycle == RegistrationLifecycle::Redemption {
+                    let redemption_fee = self.get_redemption_fee().await?;
+                    debug!(
+                        "renew_name: {} is in redemption, redemption_fee: {}",
+                        first_level_name, redemption_fee
+                    );
+                    renew_price += redemption_fee;
+                }
+                Nat::from(renew_price)
+            }
+        };
+
+        // validate request.approve_price is within the range of renew_price 10%
+        let approve_amount = Nat::from(request.approve_amount);
+        if approve_amount < get_min_approve_amount(renew_price) {
+            return Err(NamingError::InvalidApproveAmount);
+        }
+
         let result = self
-            .token_service
-            .transfer_from(
-                caller.to_text().as_str(),
-                DICP_RECEIVER.deref(),
-                Nat::from(request.approve_amount),
-                now,
-            )
+            .receive_payment(&caller, &payment_token, approve_amount, now)
             .await;
         if let Err(e) = result {
             error!("error transferring: {:?}", e);
@@ -949,6 +1583,12 @@ impl RegistrarService {
             let mut registration_store = s.registration_store.borrow_mut();
             registration_store.update_expired_at(&first_level_name, new_expired_at.0);
         });
+        let name = first_level_name.to_string();
+        Transaction::new(BTYPE_UPDATE_TOKEN)
+            .with_name(&name)
+            .with_account("from", &caller)
+            .with_value("meta", get_name_meta(&name, new_expired_at.0))
+            .log();
         let local_tx_id = result.unwrap();
         self.token_service.complete_transaction(local_tx_id);
         Ok(true)
@@ -964,12 +1604,18 @@ impl RegistrarService {
                 let new_expired_at =
                     get_expired_at(years, TimeInNs(registration.get_expired_at()));
                 registration_store.update_expired_at(&name, new_expired_at.0);
+                let name = name.to_string();
+                Transaction::new(BTYPE_UPDATE_TOKEN)
+                    .with_name(&name)
+                    .with_account("from", &caller)
+                    .with_value("meta", get_name_meta(&name, new_expired_at.0))
+                    .log();
             }
             Ok(())
         })
     }
 
-    pub fn get_name_status(&self, name: &str) -> ServiceResult<NameStatus> {
+    pub fn get_name_status(&self, name: &str, now: u64) -> ServiceResult<NameStatus> {
         let name = validate_name(name)?;
         if let Some(status) = STATE.with(|s| {
             let registration_store = s.registration_store.borrow();
@@ -978,7 +1624,9 @@ impl RegistrarService {
                     registered: true,
                     available: false,
                     kept: false,
-                    details: Some(registration.into()),
+                    details: Some((&registration).into()),
+                    lifecycle: Some(registration.get_lifecycle(now)),
+                    release_premium_in_xdr_permyriad: 0,
                 });
             }
             return None;
@@ -993,6 +1641,8 @@ impl RegistrarService {
                 registered: false,
                 available: false,
                 details: None,
+                lifecycle: None,
+                release_premium_in_xdr_permyriad: 0,
             });
         }
 
@@ -1001,6 +1651,9 @@ impl RegistrarService {
             available: true,
             kept: false,
             details: None,
+            lifecycle: None,
+            release_premium_in_xdr_permyriad: self
+                .get_release_premium_in_xdr_permyriad(&name, TimeInNs(now)),
         });
     }
 
@@ -1099,7 +1752,6 @@ impl RegistrarService {
             let mut token_index_store = s.token_index_store.borrow_mut();
             let registrations = registration_store
                 .get_registrations()
-                .values()
                 .map(|registration| registration.get_name())
                 .collect::<Vec<_>>();
             let success_count = token_index_store.import_from_registration_store(&registrations);
@@ -1294,6 +1946,24 @@ fn get_price_in_icp_e8s(len: u8, xdr_permyriad_per_icp: u64) -> u64 {
     result
 }
 
+fn convert_xdr_permyriad_to_icp_e8s(xdr_permyriad: u64, xdr_permyriad_per_icp: u64) -> u64 {
+    let xdr_permyriad = BigUint::from(xdr_permyriad) * BigUint::from(10_000u32);
+    let e8s = xdr_permyriad / BigUint::from(xdr_permyriad_per_icp) * BigUint::from(10_000u32);
+    e8s.to_u64().unwrap()
+}
+
+/// Approved amount is accepted if it is not less than 95% of the price
+fn get_min_approve_amount(price: Nat) -> Nat {
+    price * Nat::from(95u32) / Nat::from(100u32)
+}
+
+fn get_redemption_fee_in_icp_e8s(xdr_permyriad_per_icp: u64) -> u64 {
+    convert_xdr_permyriad_to_icp_e8s(
+        NAMING_REDEMPTION_FEE_IN_XDR_PERMYRIAD,
+        xdr_permyriad_per_icp,
+    )
+}
+
 #[derive(CandidType)]
 pub struct PriceTableItem {
     pub len: u8,
@@ -1301,10 +1971,62 @@ pub struct PriceTableItem {
     pub price_in_xdr_permyriad: u64,
 }
 
+#[derive(CandidType)]
+pub struct ReleasePremiumItem {
+    pub name: String,
+    pub premium_in_icp_e8s: u64,
+    pub premium_in_xdr_permyriad: u64,
+}
+
+#[derive(CandidType)]
+pub struct TokenPriceItem {
+    pub len: u8,
+    /// Price per year in the smallest unit of the token
+    pub price: Nat,
+}
+
+#[derive(CandidType)]
+pub struct TokenPriceTable {
+    pub ledger: Principal,
+    pub symbol: String,
+    pub decimals: u8,
+    pub xdr_permyriad_per_token: u64,
+    pub items: Vec<TokenPriceItem>,
+}
+
 #[derive(CandidType)]
 pub struct PriceTable {
     pub icp_xdr_conversion_rate: u64,
     pub items: Vec<PriceTableItem>,
+    /// The first page of release premiums, the rest are returned by `get_release_premiums`
+    pub release_premiums: Vec<ReleasePremiumItem>,
+    /// None if there is no more release premium
+    pub release_premiums_next_cursor: Option<String>,
+    /// Prices of tokens accepted by ICRC-2 besides DICP
+    pub token_prices: Vec<TokenPriceTable>,
+}
+
+fn get_release_premium_page(
+    now: TimeInNs,
+    input: &GetCursorPageInput,
+    icp_xdr_conversion_rate: u64,
+) -> GetCursorPageOutput<ReleasePremiumItem> {
+    let premiums = STATE.with(|s| {
+        let store = s.released_name_store.borrow();
+        store.get_premiums_in_xdr_permyriad(now.0, input.cursor.as_deref(), input.limit + 1)
+    });
+    let items = premiums
+        .into_iter()
+        .map(|(name, premium_in_xdr_permyriad)| ReleasePremiumItem {
+            name,
+            premium_in_xdr_permyriad,
+            premium_in_icp_e8s: convert_xdr_permyriad_to_icp_e8s(
+                premium_in_xdr_permyriad,
+                icp_xdr_conversion_rate,
+            ),
+        })
+        .collect();
+    GetCursorPageOutput::new(items, input.limit, |item| item.name.clone())
 }
 
 fn validate_name(name: &str) -> ServiceResult<FirstLevelName> {
@@ -1429,11 +2151,9 @@ impl<'a> RegistrationNameQueryContext<'a> {
     }
 
     pub fn get_all_unexpired_registrations(&self, now: u64) -> Vec<UnexpiredRegistrationAggDto> {
-        let registration_names = self.token_index_store.get_registrations();
         let mut valid_registration_names = Vec::new();
 
-        registration_names.iter().for_each(|registration_name_ref| {
-            let registration_name = registration_name_ref.borrow();
+        for registration_name in self.token_index_store.get_registrations() {
             let registration_result =
                 self.get_unexpired_registration_by_name(&registration_name.get_name(), now);
             match registration_result {
@@ -1452,7 +2172,7 @@ impl<'a> RegistrationNameQueryContext<'a> {
                     // ignore error
                 }
             }
-        });
+        }
         valid_registration_names
     }
     pub fn get_unexpired_registration_agg_by_names(
@@ -1510,7 +2230,7 @@ impl<'a> RegistrationNameQueryContext<'a> {
 
         let registration_name = self.token_index_store.get_registration(&index);
         if let Some(registration_name) = registration_name {
-            return Ok(registration_name.borrow().clone());
+            return Ok(registration_name);
         }
         Err(CommonError::InvalidToken(token_id.to_owned()))
     }
@@ -1520,7 +2240,7 @@ impl<'a> RegistrationNameQueryContext<'a> {
     ) -> NFTServiceResult<RegistrationName> {
         let registration_name = self.token_index_store.get_registration_by_name(name);
         if let Some(registration_name) = registration_name {
-            return Ok(registration_name.borrow().clone());
+            return Ok(registration_name);
         }
         Err(NamingError::RegistrationNotFound.into())
     }
@@ -1535,7 +2255,7 @@ impl<'a> RegistrationNameQueryContext<'a> {
             .get_registration(&name.to_owned().into());
         if let Some(registration) = registration {
             if !registration.is_expired(now) {
-                return Ok(registration.to_owned());
+                return Ok(registration);
             }
         }
         Err(NamingError::RegistrationNotFound.into())
@@ -1558,7 +2278,7 @@ impl<'a> RegistrationNameQueryContext<'a> {
                         .token_index_store
                         .get_registration_by_name(&registration.get_name());
                     if let Some(token_index) = token_index {
-                        token_indexes.push(token_index.borrow().get_index());
+                        token_indexes.push(token_index.get_index());
                     }
                 });
             result.insert(owner.0.to_owned(), token_indexes.to_owned());
@@ -1582,6 +2302,8 @@ pub struct RenewNameRequest {
     pub name: String,
     pub years: u32,
     pub approve_amount: u64,
+    /// ICRC-2 ledger of the token to pay with, DICP is used if not set
+    pub ledger: Option<Principal>,
 }
 
 #[derive(Debug, Deserialize, CandidType)]
@@ -1602,6 +2324,8 @@ pub struct NameStatus {
     pub kept: bool,
     pub registered: bool,
     pub details: Option<RegistrationDetails>,
+    pub lifecycle: Option<RegistrationLifecycle>,
+    pub release_premium_in_xdr_permyriad: u64,
 }
 
 #[cfg(test)]
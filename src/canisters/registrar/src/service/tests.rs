@@ -265,7 +265,7 @@ mod get_name_status {
     #[rstest]
     fn test_get_name_status_available(service: RegistrarService) {
         let result = service
-            .get_name_status(create_test_name("nice").as_str())
+            .get_name_status(create_test_name("nice").as_str(), 0)
             .unwrap();
         assert_eq!(result.available, true);
         assert_eq!(result.registered, false);
@@ -276,7 +276,7 @@ mod get_name_status {
     #[rstest]
     fn test_get_name_status_reserved(service: RegistrarService) {
         let result = service
-            .get_name_status(create_test_name("icnaming").as_str())
+            .get_name_status(create_test_name("icnaming").as_str(), 0)
             .unwrap();
         assert_eq!(result.available, false);
         assert_eq!(result.registered, false);
@@ -292,7 +292,7 @@ mod get_name_status {
             let mut store = s.registration_store.borrow_mut();
             store.add_registration(registration.clone());
         });
-        let result = service.get_name_status(name.as_str()).unwrap();
+        let result = service.get_name_status(name.as_str(), 0).unwrap();
         assert_eq!(result.available, false);
         assert_eq!(result.registered, true);
         assert_eq!(result.kept, false);
@@ -349,6 +349,47 @@ mod get_names {
             }
         }
     }
+
+    #[rstest]
+    fn test_get_names_by_cursor(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        STATE.with(|s| {
+            let mut store = s.registration_store.borrow_mut();
+            for (owner, name) in [
+                (mock_user1, "c"),
+                (mock_user2, "b"),
+                (mock_user1, "a"),
+                (mock_user1, "d"),
+            ] {
+                store.add_registration(Registration::new(
+                    owner,
+                    create_test_name(name),
+                    mock_now + 1,
+                    mock_now,
+                ));
+            }
+        });
+
+        let mut input = GetCursorPageInput {
+            cursor: None,
+            limit: 2,
+        };
+        let page = service.get_names_by_cursor(&mock_user1, &input).unwrap();
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_cursor, Some(create_test_name("c")));
+
+        input.cursor = page.next_cursor;
+        let page = service.get_names_by_cursor(&mock_user1, &input).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.next_cursor, None);
+
+        assert_eq!(service.get_names_count(&mock_user1), Ok(3));
+        assert_eq!(service.get_names_count(&mock_user2), Ok(1));
+    }
 }
 
 mod register {
@@ -534,7 +575,7 @@ mod register {
         assert_quota_count(&quota_owner, register_years);
         STATE.with(|s| {
             let store = s.registration_store.borrow();
-            assert_eq!(store.get_registrations().borrow().len(), 0);
+            assert_eq!(store.get_registration_count(), 0);
         });
         assert_eq!(
             result,
@@ -589,10 +630,9 @@ mod register {
         assert_quota_count(&quota_owner, 0);
         STATE.with(|s| {
             let store = s.registration_store.borrow();
-            let registrations = store.get_registrations();
             assert_eq!(
-                registrations.borrow().get(&name),
-                Some(&Registration::new(
+                store.get_registration_by_name(&name),
+                Some(Registration::new(
                     owner.0,
                     name.clone(),
                     get_expired_at(register_years, TimeInNs(mock_now)).0,
@@ -744,6 +784,8 @@ mod transfer {
             assert_eq!(registration.get_owner(), mock_user2);
             assert_eq!(registration.get_created_at(), mock_now);
             assert_eq!(registration.get_expired_at(), mock_now + 1);
+            assert_eq!(store.get_user_owned_registrations_count(&mock_user1), 0);
+            assert_eq!(store.get_user_owned_registrations_count(&mock_user2), 1);
 
             let store = s.registration_approval_store.borrow();
             assert_eq!(store.has_approved_to(&test_name), false);
@@ -973,6 +1015,14 @@ mod approve {
             let store = s.registration_approval_store.borrow();
             assert_eq!(store.is_approved_to(&test_name, &mock_user2), true);
         });
+        assert_eq!(
+            service.is_approved_to(&test_name_str, &mock_user2),
+            Ok(true)
+        );
+        assert_eq!(
+            service.is_approved_to(&test_name_str, &mock_user1),
+            Ok(false)
+        );
     }
 
     #[rstest]
@@ -2122,10 +2172,9 @@ mod batch_extend_expired_at {
         assert_eq!(result, Ok(()));
         STATE.with(|s| {
             let store = s.registration_store.borrow();
-            let registrations = store.get_registrations();
             assert_eq!(
-                registrations.borrow().get(&name),
-                Some(&Registration::new(
+                store.get_registration_by_name(&name),
+                Some(Registration::new(
                     owner.0,
                     name.clone(),
                     get_expired_at(register_years + extend_years, TimeInNs(mock_now)).0,
@@ -2136,31 +2185,1233 @@ mod batch_extend_expired_at {
     }
 }
 
-// mod load_state {
-//     use super::*;
-//     use common::dto::decode_zlib;
-//     use common::state::StableState;
-//     use std::fs::File;
-//     use std::io::Write;
-//
-//     #[rstest]
-//     fn get_registration_owners(service: RegistrarService) {
-//         let zlib = include_bytes!("../../../../local_state_data/registrar/latest.zlib");
-//         let candi = decode_zlib(zlib);
-//         let state = State::decode(candi).unwrap();
-//         let store = state.registration_store.borrow();
-//         // out to file registrar.csv
-//         let mut wtr = csv::Writer::from_writer(vec![]);
-//         wtr.serialize(("name", "owner")).unwrap();
-//         for (name, registration) in store.get_registrations() {
-//             wtr.serialize((name, registration.get_owner().to_string()))
-//                 .unwrap();
-//         }
-//         wtr.flush().unwrap();
-//         let csv = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
-//         File::create("../../scripts/features/data/RegistrarRecords.csv")
-//             .unwrap()
-//             .write_all(csv.as_bytes())
-//             .unwrap();
-//     }
-// }
+mod clean_expired {
+    use common::errors::ErrorInfo;
+
+    use super::*;
+
+    fn add_test_registration(name: &str, owner: Principal, expired_at: u64) {
+        STATE.with(|s| {
+            let mut store = s.registration_store.borrow_mut();
+            store.add_registration(Registration::new(owner, name.to_string(), expired_at, 0));
+            let mut token_index_store = s.token_index_store.borrow_mut();
+            token_index_store
+                .try_add_registration_name(&name.to_string())
+                .unwrap();
+        });
+    }
+
+    #[rstest]
+    async fn test_clean_expired_release_name(
+        mut service: RegistrarService,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        add_test_registration(
+            name.as_str(),
+            mock_user1,
+            mock_now - NAMING_GRACE_PERIOD_NS - NAMING_REDEMPTION_PERIOD_NS - 1,
+        );
+        let expected_name = name.clone();
+        mock_registry_api
+            .expect_remove_name()
+            .times(1)
+            .returning(move |name| {
+                assert_eq!(name, expected_name);
+                Ok(true)
+            });
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service.clean_expired(TimeInNs(mock_now)).await;
+
+        // assert
+        assert_eq!(result, Ok(()));
+        STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            assert!(store.get_registration_by_name(&name).is_none());
+            let token_index_store = s.token_index_store.borrow();
+            assert!(token_index_store.get_registration_by_name(&name).is_none());
+            assert_eq!(token_index_store.get_registration_count(), 0);
+            let released_name_store = s.released_name_store.borrow();
+            assert_eq!(released_name_store.get_released_at(&name), Some(mock_now));
+        });
+        assert!(service.available(name.as_str()).is_ok());
+    }
+
+    #[rstest]
+    #[case(0, RegistrationLifecycle::GracePeriod)]
+    #[case(NAMING_GRACE_PERIOD_NS, RegistrationLifecycle::Redemption)]
+    async fn test_clean_expired_keep_name_not_released(
+        mut service: RegistrarService,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_now: u64,
+        #[case] expired_before: u64,
+        #[case] lifecycle: RegistrationLifecycle,
+    ) {
+        let name = create_test_name("nice");
+        add_test_registration(name.as_str(), mock_user1, mock_now - expired_before - 1);
+        mock_registry_api.expect_remove_name().never();
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service.clean_expired(TimeInNs(mock_now)).await;
+
+        // assert
+        assert_eq!(result, Ok(()));
+        let status = service.get_name_status(name.as_str(), mock_now).unwrap();
+        assert_eq!(status.registered, true);
+        assert_eq!(status.lifecycle, Some(lifecycle));
+    }
+
+    #[rstest]
+    async fn test_clean_expired_remote_error(
+        mut service: RegistrarService,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        add_test_registration(name.as_str(), mock_user1, 0);
+        mock_registry_api
+            .expect_remove_name()
+            .returning(|_name| Err(ErrorInfo::from(NamingError::Unknown)));
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service.clean_expired(TimeInNs(mock_now)).await;
+
+        // assert
+        assert_eq!(result, Ok(()));
+        STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            assert!(store.get_registration_by_name(&name).is_some());
+            let token_index_store = s.token_index_store.borrow();
+            assert!(token_index_store.get_registration_by_name(&name).is_some());
+        });
+    }
+}
+
+mod renew_name {
+    use common::canister_api::TransactionResponse;
+
+    use super::*;
+
+    // price of 4 chars name for 1 year with xdr_permyriad_per_icp 20000
+    const RENEW_PRICE: u64 = 133_000_000;
+    // 5 XDR with xdr_permyriad_per_icp 20000
+    const REDEMPTION_FEE: u64 = 250_000_000;
+
+    fn setup(
+        service: &mut RegistrarService,
+        mut mock_dicp_api: MockDICPApi,
+        owner: Principal,
+        name: &str,
+        expired_at: u64,
+    ) {
+        STATE.with(|s| {
+            let mut store = s.registration_store.borrow_mut();
+            store.add_registration(Registration::new(owner, name.to_string(), expired_at, 0));
+        });
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| {
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        service.token_service = TokenService {
+            dicp_api: Arc::new(mock_dicp_api),
+            ..TokenService::default()
+        };
+    }
+
+    #[rstest]
+    fn test_redemption_fee() {
+        assert_eq!(get_redemption_fee_in_icp_e8s(20000), REDEMPTION_FEE);
+    }
+
+    #[rstest]
+    async fn test_renew_name_in_grace_period(
+        mut service: RegistrarService,
+        mock_dicp_api: MockDICPApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        let expired_at = mock_now - 1;
+        setup(&mut service, mock_dicp_api, mock_user1, &name, expired_at);
+
+        // act
+        let result = service
+            .renew_name(
+                mock_user1,
+                TimeInNs(mock_now),
+                RenewNameRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: RENEW_PRICE,
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            service.get_name_expires(&name).unwrap(),
+            get_expired_at(1, TimeInNs(expired_at)).0 / 1_000_000
+        );
+    }
+
+    #[rstest]
+    async fn test_renew_name_in_grace_period_not_owner(
+        mut service: RegistrarService,
+        mock_dicp_api: MockDICPApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        setup(&mut service, mock_dicp_api, mock_user1, &name, mock_now - 1);
+
+        // act
+        let result = service
+            .renew_name(
+                mock_user2,
+                TimeInNs(mock_now),
+                RenewNameRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: RENEW_PRICE,
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert_eq!(result, Err(NamingError::InvalidOwner));
+    }
+
+    #[rstest]
+    async fn test_renew_name_in_redemption(
+        mut service: RegistrarService,
+        mock_dicp_api: MockDICPApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        let expired_at = mock_now - NAMING_GRACE_PERIOD_NS - 1;
+        setup(&mut service, mock_dicp_api, mock_user1, &name, expired_at);
+
+        // act
+        let without_fee = service
+            .renew_name(
+                mock_user1,
+                TimeInNs(mock_now),
+                RenewNameRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: RENEW_PRICE,
+                    ledger: None,
+                },
+            )
+            .await;
+        let with_fee = service
+            .renew_name(
+                mock_user1,
+                TimeInNs(mock_now),
+                RenewNameRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: RENEW_PRICE + REDEMPTION_FEE,
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert_eq!(without_fee, Err(NamingError::InvalidApproveAmount));
+        assert_eq!(with_fee, Ok(true));
+    }
+
+    #[rstest]
+    async fn test_renew_name_released(
+        mut service: RegistrarService,
+        mock_dicp_api: MockDICPApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        let expired_at = mock_now - NAMING_GRACE_PERIOD_NS - NAMING_REDEMPTION_PERIOD_NS - 1;
+        setup(&mut service, mock_dicp_api, mock_user1, &name, expired_at);
+
+        // act
+        let result = service
+            .renew_name(
+                mock_user1,
+                TimeInNs(mock_now),
+                RenewNameRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: RENEW_PRICE + REDEMPTION_FEE,
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert_eq!(
+            result,
+            Err(NamingError::RegistrationReleased { name: name.clone() })
+        );
+    }
+}
+
+mod release_premium {
+    use common::canister_api::TransactionResponse;
+    use common::dto::RegistryDto;
+
+    use crate::released_name_store::get_release_premium_in_xdr_permyriad;
+
+    use super::*;
+
+    // price of 7+ chars name for 1 year with xdr_permyriad_per_icp 20000
+    const PRICE: u64 = 100_000_000;
+    // premium right after the name is released with xdr_permyriad_per_icp 20000
+    const PREMIUM: u64 = 499_999_760_000;
+
+    fn add_released_name(name: &str, released_at: u64) {
+        STATE.with(|s| {
+            let mut store = s.released_name_store.borrow_mut();
+            store.add_released_name(name.to_string(), released_at);
+        });
+    }
+
+    #[rstest]
+    fn test_get_name_status_with_release_premium(service: RegistrarService, mock_now: u64) {
+        let name = create_test_name("nice-name");
+        add_released_name(&name, mock_now);
+
+        let result = service.get_name_status(name.as_str(), mock_now).unwrap();
+
+        assert_eq!(result.available, true);
+        assert_eq!(
+            result.release_premium_in_xdr_permyriad,
+            get_release_premium_in_xdr_permyriad(mock_now, mock_now)
+        );
+    }
+
+    #[rstest]
+    async fn test_get_price_table_with_release_premium(service: RegistrarService, mock_now: u64) {
+        let name = create_test_name("nice-name");
+        add_released_name(&name, mock_now);
+
+        let result = service.get_price_table(TimeInNs(mock_now)).await.unwrap();
+
+        assert_eq!(result.release_premiums.len(), 1);
+        let item = result.release_premiums.first().unwrap();
+        assert_eq!(item.name, name);
+        assert_eq!(item.premium_in_icp_e8s, PREMIUM);
+    }
+
+    #[rstest]
+    async fn test_get_price_table_release_premiums_paged(service: RegistrarService, mock_now: u64) {
+        for i in 0..NAMING_PRICE_TABLE_RELEASE_PREMIUM_LIMIT + 2 {
+            add_released_name(&create_test_name(&format!("name{:03}", i)), mock_now);
+        }
+
+        let result = service.get_price_table(TimeInNs(mock_now)).await.unwrap();
+        assert_eq!(
+            result.release_premiums.len(),
+            NAMING_PRICE_TABLE_RELEASE_PREMIUM_LIMIT
+        );
+        let cursor = result.release_premiums_next_cursor.unwrap();
+        assert_eq!(cursor, result.release_premiums.last().unwrap().name);
+
+        let input = GetCursorPageInput {
+            cursor: Some(cursor),
+            limit: 10,
+        };
+        let page = service
+            .get_release_premiums(TimeInNs(mock_now), &input)
+            .await
+            .unwrap();
+        let names: Vec<String> = page.items.iter().map(|item| item.name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![create_test_name("name050"), create_test_name("name051")]
+        );
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[rstest]
+    async fn test_register_with_payment_without_premium(
+        mut service: RegistrarService,
+        mut mock_dicp_api: MockDICPApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice-name");
+        add_released_name(&name, mock_now);
+        mock_dicp_api.expect_transfer_from().never();
+        service.token_service = TokenService {
+            dicp_api: Arc::new(mock_dicp_api),
+            ..TokenService::default()
+        };
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: Nat::from(PRICE),
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert_eq!(result, Err(NamingError::InvalidApproveAmount));
+    }
+
+    #[rstest]
+    async fn test_register_with_payment_with_premium(
+        mut service: RegistrarService,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice-name");
+        add_released_name(&name, mock_now);
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(|_, _, _, value, _| {
+                assert_eq!(value, Nat::from(PRICE + PREMIUM));
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        service.token_service = TokenService {
+            dicp_api: Arc::new(mock_dicp_api),
+            ..TokenService::default()
+        };
+        let api_name = name.clone();
+        mock_registry_api.expect_set_subdomain_owner().returning(
+            move |_label, _parent_name, sub_owner, ttl, resolver| {
+                Ok(RegistryDto {
+                    owner: sub_owner,
+                    name: api_name.clone(),
+                    ttl,
+                    resolver,
+                })
+            },
+        );
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: Nat::from(PRICE + PREMIUM),
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert!(result.is_ok());
+        STATE.with(|s| {
+            let store = s.released_name_store.borrow();
+            assert_eq!(store.get_released_at(&name), None);
+        });
+    }
+
+    #[rstest]
+    #[case("abc", 146_000_000)]
+    #[case("abcd", 133_000_000)]
+    #[case("abcde", 121_000_000)]
+    async fn test_register_short_name_with_payment_with_premium(
+        mut service: RegistrarService,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_now: u64,
+        #[case] label: &str,
+        #[case] price: u64,
+    ) {
+        let name = create_test_name(label);
+        add_released_name(&name, mock_now);
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |_, _, _, value, _| {
+                assert_eq!(value, Nat::from(price + PREMIUM));
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        service.token_service = TokenService {
+            dicp_api: Arc::new(mock_dicp_api),
+            ..TokenService::default()
+        };
+        let api_name = name.clone();
+        mock_registry_api.expect_set_subdomain_owner().returning(
+            move |_label, _parent_name, sub_owner, ttl, resolver| {
+                Ok(RegistryDto {
+                    owner: sub_owner,
+                    name: api_name.clone(),
+                    ttl,
+                    resolver,
+                })
+            },
+        );
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: Nat::from(price + PREMIUM),
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert!(result.is_ok());
+        STATE.with(|s| {
+            let store = s.registration_store.borrow();
+            let registration = store.get_registration(&name.into()).unwrap();
+            assert_eq!(registration.get_owner(), mock_user1);
+        });
+    }
+
+    #[rstest]
+    async fn test_register_short_name_with_payment_after_premium(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("abc");
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name,
+                    years: 1,
+                    approve_amount: Nat::from(PRICE),
+                    ledger: None,
+                },
+            )
+            .await;
+
+        // assert
+        assert!(matches!(result, Err(NamingError::InvalidName { .. })));
+    }
+
+    #[rstest]
+    async fn test_register_with_quota_in_premium_period(
+        mut service: RegistrarService,
+        owner: AuthPrincipal,
+        quota_owner: AuthPrincipal,
+        register_years: u32,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice-name");
+        add_released_name(&name, mock_now);
+
+        // act
+        let result = service
+            .register_with_quota(
+                name.clone(),
+                owner.0,
+                1,
+                TimeInNs(mock_now),
+                &quota_owner.0,
+                TEST_QUOTA,
+                false,
+            )
+            .await;
+
+        // assert
+        assert!(matches!(result, Err(NamingError::NameUnavailable { .. })));
+        assert_quota_count(&quota_owner, register_years);
+    }
+}
+
+mod commitment {
+    use common::dto::RegistryDto;
+
+    use crate::commitment_store::make_commitment;
+
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn add_commitment(commitment: &str, committer: Principal, committed_at: u64) {
+        STATE.with(|s| {
+            let mut store = s.commitment_store.borrow_mut();
+            store.add_commitment(commitment.to_string(), committer, committed_at);
+        });
+    }
+
+    fn get_committed_at(commitment: &str) -> Option<u64> {
+        STATE.with(|s| {
+            let store = s.commitment_store.borrow();
+            store.get_committed_at(commitment)
+        })
+    }
+
+    fn reveal_request(name: &str) -> RevealRegisterWithQuotaRequest {
+        RevealRegisterWithQuotaRequest {
+            name: name.to_string(),
+            years: 1,
+            quota_type: TEST_QUOTA,
+            secret: SECRET.to_vec(),
+        }
+    }
+
+    #[rstest]
+    fn test_commit(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        let commitment = make_commitment("nice.icp", &mock_user1, SECRET);
+
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            commitment.clone(),
+        );
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_committed_at(&commitment), Some(mock_now));
+    }
+
+    #[rstest]
+    fn test_commit_invalid(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            "nice.icp".to_string(),
+        );
+
+        assert!(matches!(result, Err(NamingError::InvalidCommitment { .. })));
+    }
+
+    #[rstest]
+    fn test_commit_not_hex(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            "z".repeat(64),
+        );
+
+        assert!(matches!(result, Err(NamingError::InvalidCommitment { .. })));
+    }
+
+    #[rstest]
+    fn test_commit_already_exists(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        let commitment = make_commitment("nice.icp", &mock_user1, SECRET);
+        add_commitment(&commitment, mock_user1, mock_now - 1);
+
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            commitment.clone(),
+        );
+
+        assert_eq!(result, Err(NamingError::CommitmentAlreadyExists));
+        assert_eq!(get_committed_at(&commitment), Some(mock_now - 1));
+    }
+
+    #[rstest]
+    fn test_commit_again_after_expired(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        let commitment = make_commitment("nice.icp", &mock_user1, SECRET);
+        add_commitment(
+            &commitment,
+            mock_user1,
+            mock_now - NAMING_COMMITMENT_MAX_AGE_NS,
+        );
+
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            commitment.clone(),
+        );
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_committed_at(&commitment), Some(mock_now));
+    }
+
+    #[rstest]
+    fn test_commit_too_many(service: RegistrarService, mock_user1: Principal, mock_now: u64) {
+        for i in 0..NAMING_MAX_COMMITMENTS_PER_USER {
+            let commitment = make_commitment(&format!("nice{}.icp", i), &mock_user1, SECRET);
+            add_commitment(&commitment, mock_user1, mock_now);
+        }
+        let commitment = make_commitment("nice.icp", &mock_user1, SECRET);
+
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            commitment.clone(),
+        );
+
+        assert_eq!(
+            result,
+            Err(NamingError::TooManyCommitments {
+                max: NAMING_MAX_COMMITMENTS_PER_USER
+            })
+        );
+        assert_eq!(get_committed_at(&commitment), None);
+    }
+
+    #[rstest]
+    fn test_commit_after_own_commitments_expired(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        for i in 0..NAMING_MAX_COMMITMENTS_PER_USER {
+            let commitment = make_commitment(&format!("nice{}.icp", i), &mock_user1, SECRET);
+            add_commitment(
+                &commitment,
+                mock_user1,
+                mock_now - NAMING_COMMITMENT_MAX_AGE_NS,
+            );
+        }
+        let other = make_commitment("other.icp", &mock_user2, SECRET);
+        add_commitment(&other, mock_user2, mock_now - NAMING_COMMITMENT_MAX_AGE_NS);
+        let commitment = make_commitment("nice.icp", &mock_user1, SECRET);
+
+        let result = service.commit(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            commitment.clone(),
+        );
+
+        assert_eq!(result, Ok(true));
+        STATE.with(|s| {
+            let store = s.commitment_store.borrow();
+            assert_eq!(store.get_commitment_count(&mock_user1), 1);
+            // commitments of other users are left to the periodic task
+            assert_eq!(store.get_commitment_count(&mock_user2), 1);
+        });
+    }
+
+    #[rstest]
+    async fn test_reveal_not_found(
+        mut service: RegistrarService,
+        quota_owner: AuthPrincipal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+
+        let result = service
+            .reveal_register_with_quota(
+                CallContext::new(quota_owner.0, TimeInNs(mock_now)),
+                reveal_request(&name),
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::CommitmentNotFound));
+    }
+
+    #[rstest]
+    async fn test_reveal_too_new(
+        mut service: RegistrarService,
+        quota_owner: AuthPrincipal,
+        register_years: u32,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        add_commitment(
+            &make_commitment(&name, &quota_owner.0, SECRET),
+            quota_owner.0,
+            mock_now - 1_000_000_000,
+        );
+
+        let result = service
+            .reveal_register_with_quota(
+                CallContext::new(quota_owner.0, TimeInNs(mock_now)),
+                reveal_request(&name),
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::CommitmentTooNew { seconds: 59 }));
+        assert_quota_count(&quota_owner, register_years);
+    }
+
+    #[rstest]
+    async fn test_reveal_expired(
+        mut service: RegistrarService,
+        quota_owner: AuthPrincipal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        add_commitment(
+            &make_commitment(&name, &quota_owner.0, SECRET),
+            quota_owner.0,
+            mock_now - NAMING_COMMITMENT_MAX_AGE_NS,
+        );
+
+        let result = service
+            .reveal_register_with_quota(
+                CallContext::new(quota_owner.0, TimeInNs(mock_now)),
+                reveal_request(&name),
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::CommitmentExpired));
+    }
+
+    #[rstest]
+    async fn test_reveal_by_others(
+        mut service: RegistrarService,
+        quota_owner: AuthPrincipal,
+        mock_user3: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        add_commitment(
+            &make_commitment(&name, &quota_owner.0, SECRET),
+            quota_owner.0,
+            mock_now - NAMING_COMMITMENT_MIN_AGE_NS,
+        );
+
+        let result = service
+            .reveal_register_with_quota(
+                CallContext::new(mock_user3, TimeInNs(mock_now)),
+                reveal_request(&name),
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::CommitmentNotFound));
+    }
+
+    #[rstest]
+    async fn test_reveal_register_with_quota(
+        mut service: RegistrarService,
+        quota_owner: AuthPrincipal,
+        register_years: u32,
+        mut mock_registry_api: MockRegistryApi,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice");
+        let commitment = make_commitment(&name, &quota_owner.0, SECRET);
+        add_commitment(
+            &commitment,
+            quota_owner.0,
+            mock_now - NAMING_COMMITMENT_MIN_AGE_NS,
+        );
+        let api_name = name.clone();
+        mock_registry_api.expect_set_subdomain_owner().returning(
+            move |_label, _parent_name, sub_owner, ttl, resolver| {
+                Ok(RegistryDto {
+                    owner: sub_owner,
+                    name: api_name.clone(),
+                    ttl,
+                    resolver,
+                })
+            },
+        );
+        service.registry_api = Arc::new(mock_registry_api);
+
+        let years = 2;
+
+        let result = service
+            .reveal_register_with_quota(
+                CallContext::new(quota_owner.0, TimeInNs(mock_now)),
+                RevealRegisterWithQuotaRequest {
+                    years,
+                    ..reveal_request(&name)
+                },
+            )
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_quota_count(&quota_owner, register_years - years);
+        assert_eq!(get_committed_at(&commitment), None);
+        let owner = service.get_owner(&name).unwrap();
+        assert_eq!(owner, quota_owner.0);
+        assert_eq!(
+            service.get_name_expires(&name),
+            Ok(get_expired_at(years, TimeInNs(mock_now)).0 / 1_000_000)
+        );
+    }
+
+    #[rstest]
+    fn test_clean_expired_commitments(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_now: u64,
+    ) {
+        add_commitment("a", mock_user1, mock_now - NAMING_COMMITMENT_MAX_AGE_NS);
+        add_commitment("b", mock_user1, mock_now);
+
+        service.clean_expired_commitments(TimeInNs(mock_now));
+
+        assert_eq!(get_committed_at("a"), None);
+        assert_eq!(get_committed_at("b"), Some(mock_now));
+    }
+}
+
+mod add_quota {
+    use super::*;
+
+    #[rstest]
+    fn test_add_quota_by_mystery_box(mut service: RegistrarService, mock_user3: Principal) {
+        let mystery_box = get_named_get_canister_id(CanisterNames::MysteryBox);
+
+        let result = service.add_quota(&mystery_box, mock_user3, TEST_QUOTA, 1);
+
+        assert_eq!(result, Ok(true));
+        assert_quota_type_count(&AuthPrincipal(mock_user3), &TEST_QUOTA, 1);
+    }
+
+    #[rstest]
+    fn test_add_quota_unauthorized(mut service: RegistrarService, mock_user3: Principal) {
+        let result = service.add_quota(&mock_user3, mock_user3, TEST_QUOTA, 1);
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+
+    #[rstest]
+    fn test_add_quota_with_key_only_once(mut service: RegistrarService, mock_user3: Principal) {
+        let mystery_box = get_named_get_canister_id(CanisterNames::MysteryBox);
+
+        let result =
+            service.add_quota_with_key(&mystery_box, mock_user3, TEST_QUOTA, 1, "1".to_string());
+        assert_eq!(result, Ok(true));
+        let result =
+            service.add_quota_with_key(&mystery_box, mock_user3, TEST_QUOTA, 1, "1".to_string());
+        assert_eq!(result, Ok(true));
+        assert_quota_type_count(&AuthPrincipal(mock_user3), &TEST_QUOTA, 1);
+
+        let result =
+            service.add_quota_with_key(&mystery_box, mock_user3, TEST_QUOTA, 1, "2".to_string());
+        assert_eq!(result, Ok(true));
+        assert_quota_type_count(&AuthPrincipal(mock_user3), &TEST_QUOTA, 2);
+    }
+
+    #[rstest]
+    fn test_add_quota_with_key_unauthorized(mut service: RegistrarService, mock_user3: Principal) {
+        let result =
+            service.add_quota_with_key(&mock_user3, mock_user3, TEST_QUOTA, 1, "1".to_string());
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+        assert_quota_type_count(&AuthPrincipal(mock_user3), &TEST_QUOTA, 0);
+    }
+}
+
+mod payment_token {
+    use common::dto::RegistryDto;
+    use common::icrc::Icrc2TransferFromError;
+
+    use crate::payment_token_store::{PaymentToken, PriceFeed};
+
+    use super::*;
+
+    // 1 token = 1 XDR, so a 7+ chars name costs 2 tokens per year
+    fn create_token(ledger: Principal) -> PaymentToken {
+        PaymentToken {
+            ledger,
+            symbol: "TKN".to_string(),
+            decimals: 8,
+            fee: Nat::from(10_000u32),
+            price_feed: PriceFeed::XdrPermyriadPerToken(10_000),
+        }
+    }
+
+    fn setup_ledger(
+        service: &mut RegistrarService,
+        mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+        result: Result<Nat, Icrc2TransferFromError>,
+    ) {
+        mock_icrc_ledger_api
+            .expect_icrc2_transfer_from()
+            .times(1)
+            .returning(move |_, args| {
+                assert_eq!(args.amount, Nat::from(200_000_000u64));
+                Ok(result.clone())
+            });
+        service.token_service = TokenService {
+            icrc_ledger_api: Arc::new(mock_icrc_ledger_api),
+            ..TokenService::default()
+        };
+    }
+
+    #[rstest]
+    fn test_set_payment_token(
+        service: RegistrarService,
+        system_admin: AuthPrincipal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+    ) {
+        let result = service.set_payment_token(&mock_user3, create_token(mock_user2));
+        assert_eq!(result, Err(NamingError::Unauthorized));
+
+        let result = service.set_payment_token(&system_admin.0, create_token(mock_user2));
+        assert_eq!(result, Ok(true));
+        assert_eq!(service.get_payment_tokens(), vec![create_token(mock_user2)]);
+
+        let result = service.remove_payment_token(&system_admin.0, mock_user2);
+        assert_eq!(result, Ok(true));
+        assert!(service.get_payment_tokens().is_empty());
+    }
+
+    #[rstest]
+    async fn test_get_price_table_with_token(
+        service: RegistrarService,
+        system_admin: AuthPrincipal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let token = PaymentToken {
+            price_feed: PriceFeed::IcpXdrConversionRate,
+            ..create_token(mock_user2)
+        };
+        service.set_payment_token(&system_admin.0, token).unwrap();
+
+        let result = service.get_price_table(TimeInNs(mock_now)).await.unwrap();
+
+        assert_eq!(result.token_prices.len(), 1);
+        let token_price = result.token_prices.first().unwrap();
+        assert_eq!(token_price.ledger, mock_user2);
+        assert_eq!(token_price.xdr_permyriad_per_token, 20_000);
+        assert_eq!(token_price.items.len(), 7);
+        // 2 XDR with xdr_permyriad_per_icp 20000
+        assert_eq!(
+            token_price.items.last().unwrap().price,
+            Nat::from(100_000_000u64)
+        );
+    }
+
+    #[rstest]
+    async fn test_register_with_payment_token(
+        mut service: RegistrarService,
+        system_admin: AuthPrincipal,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice-name");
+        service
+            .set_payment_token(&system_admin.0, create_token(mock_user2))
+            .unwrap();
+        setup_ledger(&mut service, mock_icrc_ledger_api, Ok(Nat::from(1u32)));
+        let api_name = name.clone();
+        mock_registry_api.expect_set_subdomain_owner().returning(
+            move |_label, _parent_name, sub_owner, ttl, resolver| {
+                Ok(RegistryDto {
+                    owner: sub_owner,
+                    name: api_name.clone(),
+                    ttl,
+                    resolver,
+                })
+            },
+        );
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: Nat::from(200_000_000u64),
+                    ledger: Some(mock_user2),
+                },
+            )
+            .await;
+
+        // assert
+        assert!(result.is_ok());
+        assert_eq!(service.get_owner(&name).unwrap(), mock_user1);
+        STATE.with(|s| {
+            let store = s.balance_store.borrow();
+            assert!(store.get_transaction(1).is_none());
+        });
+    }
+
+    #[rstest]
+    async fn test_register_with_payment_token_transfer_failed(
+        mut service: RegistrarService,
+        system_admin: AuthPrincipal,
+        mock_icrc_ledger_api: MockIcrcLedgerApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let name = create_test_name("nice-name");
+        service
+            .set_payment_token(&system_admin.0, create_token(mock_user2))
+            .unwrap();
+        setup_ledger(
+            &mut service,
+            mock_icrc_ledger_api,
+            Err(Icrc2TransferFromError::InsufficientAllowance {
+                allowance: Nat::from(0u32),
+            }),
+        );
+
+        // act
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: name.clone(),
+                    years: 1,
+                    approve_amount: Nat::from(200_000_000u64),
+                    ledger: Some(mock_user2),
+                },
+            )
+            .await;
+
+        // assert
+        assert!(matches!(
+            result,
+            Err(NamingError::LedgerTransferFailed { .. })
+        ));
+        assert!(service.get_owner(&name).is_err());
+    }
+
+    #[rstest]
+    async fn test_register_with_unsupported_token(
+        service: RegistrarService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let result = service
+            .register_with_payment(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                RegisterNameWithPaymentRequest {
+                    name: create_test_name("nice-name"),
+                    years: 1,
+                    approve_amount: Nat::from(200_000_000u64),
+                    ledger: Some(mock_user2),
+                },
+            )
+            .await;
+
+        assert_eq!(
+            result,
+            Err(NamingError::PaymentTokenNotSupported {
+                ledger: mock_user2.to_text()
+            })
+        );
+    }
+}
+
+// mod load_state {
+//     use super::*;
+//     use common::dto::decode_zlib;
+//     use common::state::StableState;
+//     use std::fs::File;
+//     use std::io::Write;
+//
+//     #[rstest]
+//     fn get_registration_owners(service: RegistrarService) {
+//         let zlib = include_bytes!("../../../../local_state_data/registrar/latest.zlib");
+//         let candi = decode_zlib(zlib);
+//         let state = State::decode(candi).unwrap();
+//         let store = state.registration_store.borrow();
+//         // out to file registrar.csv
+//         let mut wtr = csv::Writer::from_writer(vec![]);
+//         wtr.serialize(("name", "owner")).unwrap();
+//         for (name, registration) in store.get_registrations() {
+//             wtr.serialize((name, registration.get_owner().to_string()))
+//                 .unwrap();
+//         }
+//         wtr.flush().unwrap();
+//         let csv = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+//         File::create("../../scripts/features/data/RegistrarRecords.csv")
+//             .unwrap()
+//             .write_all(csv.as_bytes())
+//             .unwrap();
+//     }
+// }
+
+mod certified_query {
+    use common::certified_map::get_value_hash;
+
+    use super::*;
+    use crate::certification::get_owner_key;
+
+    fn get_leaf(key: &str) -> Option<[u8; 32]> {
+        CERTIFIED_MAP.with(|map| map.borrow().get(key).cloned())
+    }
+
+    #[rstest]
+    async fn test_transfer_certified(
+        mut service: RegistrarService,
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        let test_name = create_test_name("icnaming");
+        STATE.with(|s| {
+            let mut store = s.registration_store.borrow_mut();
+            store.add_registration(Registration::new(
+                mock_user1,
+                test_name.clone(),
+                mock_now + 1,
+                mock_now,
+            ));
+        });
+        mock_registry_api
+            .expect_transfer()
+            .returning(|_name, _new_owner, _resolver| Ok(true));
+        service.registry_api = Arc::new(mock_registry_api);
+
+        // act
+        service
+            .transfer(test_name.as_str(), &mock_user1, mock_user2)
+            .await
+            .unwrap();
+
+        // assert
+        let result = service.get_owner_certified(test_name.as_str()).unwrap();
+        assert_eq!(result.value, Some(mock_user2));
+        assert!(!result.witness.is_empty());
+        assert_eq!(
+            get_leaf(&get_owner_key(&test_name)),
+            Some(get_value_hash(&mock_user2))
+        );
+    }
+
+    #[rstest]
+    fn test_get_owner_certified_not_found(service: RegistrarService) {
+        let test_name = create_test_name("icnaming");
+
+        let result = service.get_owner_certified(test_name.as_str()).unwrap();
+
+        assert_eq!(result.value, None);
+        assert!(!result.witness.is_empty());
+        assert_eq!(get_leaf(&get_owner_key(&test_name)), None);
+    }
+
+    #[rstest]
+    fn test_get_owner_certified_invalid_name(service: RegistrarService) {
+        let result = service.get_owner_certified("icnaming.com");
+
+        assert!(result.is_err());
+    }
+}
@@ -1,16 +1,27 @@
-use candid::encode_args;
+use candid::{decode_args, encode_args, CandidType, Deserialize};
 
-use common::state::StableState;
+use common::state::{StableState, StateMigration};
 
-#[derive(Default)]
-pub struct Settings;
+#[cfg(test)]
+mod stable_tests;
+
+#[derive(CandidType, Deserialize, Default, Debug, Eq, PartialEq)]
+pub struct Settings {}
+
+// settings were encoded as a placeholder `0` before versioning
+fn migrate_v0(_: Vec<u8>) -> Result<Vec<u8>, String> {
+    encode_args((Settings::default(),)).map_err(|e| e.to_string())
+}
 
 impl StableState for Settings {
+    const MIGRATIONS: &'static [StateMigration] = &[migrate_v0];
+
     fn encode(&self) -> Vec<u8> {
-        encode_args((0,)).unwrap()
+        encode_args((self,)).unwrap()
     }
 
-    fn decode(_: Vec<u8>) -> Result<Self, String> {
-        Ok(Settings {})
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (settings,): (Settings,) = decode_args(&bytes).map_err(|e| e.to_string())?;
+        Ok(settings)
     }
 }
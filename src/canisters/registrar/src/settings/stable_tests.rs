@@ -0,0 +1,35 @@
+use candid::encode_args;
+use rstest::*;
+
+use common::state::{decode_store, encode_store, get_current_version};
+
+use super::*;
+
+#[rstest]
+fn test_decode_snapshot_before_versioning() {
+    let snapshot = encode_args((0,)).unwrap();
+
+    let store = decode_store::<Settings>(snapshot).unwrap();
+
+    assert_eq!(*store.borrow(), Settings {});
+}
+
+#[rstest]
+fn test_decode_unversioned_snapshot_without_migration() {
+    let snapshot = encode_args((0,)).unwrap();
+
+    let result = Settings::decode(snapshot);
+
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn test_encode_decode_current_version() {
+    let store = decode_store::<Settings>(encode_args((0,)).unwrap()).unwrap();
+
+    let bytes = encode_store(&store);
+
+    assert_eq!(get_current_version::<Settings>(), 1);
+    let decoded = decode_store::<Settings>(bytes).unwrap();
+    assert_eq!(*decoded.borrow(), Settings {});
+}
@@ -3,30 +3,59 @@ use std::collections::{HashMap, VecDeque};
 use std::sync::Once;
 
 use candid::{candid_method, decode_args, encode_args, Principal};
-use ic_cdk::{api, storage};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::info;
 
 use crate::balance_store::BalanceStore;
+use crate::block_log::BlockLog;
 use candid::{CandidType, Deserialize};
+use common::certified_map::CertifiedMap;
 use common::ic_logger::ICLogger;
 use common::named_canister_ids::{
     ensure_current_canister_id_match, update_dev_named_canister_ids, CanisterNames,
 };
-use common::state::{decode_store, decode_store_or_default, StableState};
+use common::stable_memory::MemoryId;
+use common::state::{
+    decode_store, decode_store_or_default, encode_entries, encode_store, restore_state, save_state,
+    StableEntries, StableMemoryState, StableState,
+};
 
+use crate::certification::certify_restored;
+use crate::commitment_store::CommitmentStore;
 use crate::name_locker::NameLocker;
+use crate::payment_token_store::PaymentTokenStore;
 use crate::quota_import_store::QuotaImportStore;
+use crate::recent_transaction_store::RecentTransactionStore;
 use crate::registration_approval_store::RegistrationApprovalStore;
 use crate::registration_store::{Registration, RegistrationStore};
+use crate::released_name_store::ReleasedNameStore;
 use crate::settings::Settings;
 use crate::token_index_store::TokenIndexStore;
 use crate::user_quota_store::UserQuotaStore;
 
+#[cfg(test)]
+mod tests;
+
+// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const REGISTRATION_STORE_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub const TOKEN_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const TOKEN_INDEX_NAMES_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const NAME_TOKEN_INDEXES_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const OWNER_NAMES_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub const OWNER_NAME_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(6);
+pub const BLOCK_LOG_MEMORY_ID: MemoryId = MemoryId::new(7);
+pub const BLOCK_ARCHIVES_MEMORY_ID: MemoryId = MemoryId::new(8);
+pub const EXPIRED_AT_NAMES_MEMORY_ID: MemoryId = MemoryId::new(9);
+pub const QUOTA_CREDIT_KEYS_MEMORY_ID: MemoryId = MemoryId::new(10);
+pub const CERTIFIED_MAP_MEMORY_ID: MemoryId = MemoryId::new(11);
+
 thread_local! {
     pub static STATE : State = State::default();
     pub static MERTRICS_COUNTER: RefCell<MetricsCounter> = RefCell::new(MetricsCounter::default());
     pub static NAME_LOCKER: RefCell<NameLocker> = RefCell::new(NameLocker::new());
+    pub static CERTIFIED_MAP: RefCell<CertifiedMap> =
+        RefCell::new(CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID));
 }
 
 #[derive(Default)]
@@ -61,13 +90,22 @@ pub struct State {
     pub registration_approval_store: RefCell<RegistrationApprovalStore>,
     pub balance_store: RefCell<BalanceStore>,
     pub token_index_store: RefCell<TokenIndexStore>,
+    pub released_name_store: RefCell<ReleasedNameStore>,
+    pub commitment_store: RefCell<CommitmentStore>,
+    pub payment_token_store: RefCell<PaymentTokenStore>,
+    pub recent_transaction_store: RefCell<RecentTransactionStore>,
+    pub block_log: RefCell<BlockLog>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    registration_entries: StableEntries<RegistrationStore>,
+    token_index_entries: StableEntries<TokenIndexStore>,
 }
 
 impl State {
     pub fn replace(&self, new_state: State) {
         self.settings.replace(new_state.settings.take());
-        self.registration_store
-            .replace(new_state.registration_store.take());
+        new_state
+            .registration_entries
+            .import_into(&self.registration_store);
         self.user_quota_store
             .replace(new_state.user_quota_store.take());
         self.quota_import_store
@@ -75,8 +113,18 @@ impl State {
         self.registration_approval_store
             .replace(new_state.registration_approval_store.take());
         self.balance_store.replace(new_state.balance_store.take());
-        self.token_index_store
-            .replace(new_state.token_index_store.take());
+        new_state
+            .token_index_entries
+            .import_into(&self.token_index_store);
+        self.released_name_store
+            .replace(new_state.released_name_store.take());
+        self.commitment_store
+            .replace(new_state.commitment_store.take());
+        self.payment_token_store
+            .replace(new_state.payment_token_store.take());
+        self.recent_transaction_store
+            .replace(new_state.recent_transaction_store.take());
+        self.block_log.replace(new_state.block_log.take());
     }
 }
 
@@ -88,18 +136,24 @@ pub type EncodedState = (
     Vec<u8>,
     Vec<u8>,
     Option<Vec<u8>>,
+    Option<Vec<u8>>,
+    Option<Vec<u8>>,
+    Option<Vec<u8>>,
 );
 
 impl StableState for State {
     fn encode(&self) -> Vec<u8> {
         encode_args((
-            self.settings.borrow().encode(),
-            self.registration_store.borrow().encode(),
-            self.user_quota_store.borrow().encode(),
-            self.quota_import_store.borrow().encode(),
-            self.registration_approval_store.borrow().encode(),
-            self.balance_store.borrow().encode(),
-            self.token_index_store.borrow().encode(),
+            encode_store(&self.settings),
+            encode_entries(&self.registration_store),
+            encode_store(&self.user_quota_store),
+            encode_store(&self.quota_import_store),
+            encode_store(&self.registration_approval_store),
+            encode_store(&self.balance_store),
+            encode_entries(&self.token_index_store),
+            encode_store(&self.released_name_store),
+            encode_store(&self.commitment_store),
+            encode_store(&self.payment_token_store),
         ))
         .unwrap()
     }
@@ -113,17 +167,80 @@ impl StableState for State {
             registration_approval_store_bytes,
             balance_store_bytes,
             token_index_store_bytes,
-        ): EncodedState = decode_args(&bytes).unwrap();
+            released_name_store_bytes,
+            commitment_store_bytes,
+            payment_token_store_bytes,
+        ): EncodedState = decode_args(&bytes).map_err(|e| e.to_string())?;
 
-        return Ok(State {
+        Ok(State {
             settings: decode_store(settings_bytes)?,
             user_quota_store: decode_store(user_quota_store_bytes)?,
-            registration_store: decode_store(registration_store_bytes)?,
             quota_import_store: decode_store(quota_import_store_bytes)?,
             registration_approval_store: decode_store(registration_approval_store_bytes)?,
             balance_store: decode_store(balance_store_bytes)?,
-            token_index_store: decode_store_or_default(token_index_store_bytes)?,
-        });
+            released_name_store: decode_store_or_default(released_name_store_bytes)?,
+            commitment_store: decode_store_or_default(commitment_store_bytes)?,
+            payment_token_store: decode_store_or_default(payment_token_store_bytes)?,
+            registration_entries: StableEntries::decode(registration_store_bytes)?,
+            token_index_entries: StableEntries::decode_or_default(token_index_store_bytes)?,
+            ..State::default()
+        })
+    }
+}
+
+pub type EncodedHeapState = (
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Option<Vec<u8>>,
+);
+
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args((
+            encode_store(&self.settings),
+            encode_store(&self.user_quota_store),
+            encode_store(&self.quota_import_store),
+            encode_store(&self.registration_approval_store),
+            encode_store(&self.balance_store),
+            encode_store(&self.released_name_store),
+            encode_store(&self.commitment_store),
+            encode_store(&self.payment_token_store),
+            Some(encode_store(&self.recent_transaction_store)),
+        ))
+        .unwrap()
+    }
+
+    fn decode_heap(bytes: Vec<u8>) -> Result<Self, String> {
+        let (
+            settings_bytes,
+            user_quota_store_bytes,
+            quota_import_store_bytes,
+            registration_approval_store_bytes,
+            balance_store_bytes,
+            released_name_store_bytes,
+            commitment_store_bytes,
+            payment_token_store_bytes,
+            recent_transaction_store_bytes,
+        ): EncodedHeapState = decode_args(&bytes).map_err(|e| e.to_string())?;
+
+        Ok(State {
+            settings: decode_store(settings_bytes)?,
+            user_quota_store: decode_store(user_quota_store_bytes)?,
+            quota_import_store: decode_store(quota_import_store_bytes)?,
+            registration_approval_store: decode_store(registration_approval_store_bytes)?,
+            balance_store: decode_store(balance_store_bytes)?,
+            released_name_store: decode_store(released_name_store_bytes)?,
+            commitment_store: decode_store(commitment_store_bytes)?,
+            payment_token_store: decode_store(payment_token_store_bytes)?,
+            recent_transaction_store: decode_store_or_default(recent_transaction_store_bytes)?,
+            ..State::default()
+        })
     }
 }
 
@@ -164,26 +281,20 @@ fn init_function() {
 #[pre_upgrade(guard = "guard_func")]
 fn pre_upgrade() {
     STATE.with(|s| {
-        let bytes = s.encode();
-        match storage::stable_save((&bytes,)) {
-            Ok(_) => {
-                info!("Saved state before upgrade");
-                ()
-            }
-            Err(e) => api::trap(format!("Failed to save state before upgrade: {:?}", e).as_str()),
-        };
+        save_state(s);
+        info!("Saved state before upgrade");
     });
 }
 
 #[post_upgrade(guard = "guard_func")]
 fn post_upgrade() {
-    STATE.with(|s| match storage::stable_restore::<(Vec<u8>,)>() {
-        Ok(bytes) => {
-            let new_state = State::decode(bytes.0).expect("Decoding stable memory failed");
-
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
             s.replace(new_state);
+            certify_restored(&s.registration_store.borrow(), &s.block_log.borrow());
             info!("Loaded state after upgrade");
-        }
+        }),
         Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
-    });
+    }
 }
@@ -0,0 +1,99 @@
+use rstest::*;
+
+use common::constants::NAMING_TOP_LABEL;
+use common::stable_memory::load_heap_state;
+use common::state::StableMemoryStore;
+use test_common::user::*;
+
+use crate::certification::{certify_all, get_owner_key};
+use crate::token_identifier::TokenIndex;
+
+use super::*;
+
+fn register_names(range: std::ops::Range<u32>, owner: Principal, now: u64) {
+    STATE.with(|s| {
+        let mut registration_store = s.registration_store.borrow_mut();
+        let mut token_index_store = s.token_index_store.borrow_mut();
+        for i in range {
+            let name = format!("name{}.{}", i, NAMING_TOP_LABEL);
+            token_index_store.try_add_registration_name(&name).unwrap();
+            registration_store.add_registration(Registration::new(owner, name, now + 1, now));
+        }
+    });
+}
+
+fn assert_names_registered(count: u32, owner: Principal) {
+    STATE.with(|s| {
+        let registration_store = s.registration_store.borrow();
+        assert_eq!(
+            registration_store.get_user_owned_registrations_count(&owner),
+            count as usize
+        );
+        let token_index_store = s.token_index_store.borrow();
+        assert_eq!(
+            token_index_store.get_current_token_index(),
+            TokenIndex(count)
+        );
+        let name = format!("name{}.{}", count - 1, NAMING_TOP_LABEL);
+        assert_eq!(
+            token_index_store
+                .get_registration_by_name(&name)
+                .map(|registration_name| registration_name.get_index()),
+            Some(TokenIndex(count))
+        );
+    });
+}
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_now: u64) {
+    register_names(0..1, mock_user1, mock_now);
+    pre_upgrade();
+    let size = load_heap_state().unwrap().len();
+
+    register_names(1..100, mock_user1, mock_now);
+    pre_upgrade();
+    post_upgrade();
+
+    // registrations and token indexes are not saved with the heap state
+    assert_eq!(load_heap_state().unwrap().len(), size);
+    assert_names_registered(100, mock_user1);
+}
+
+#[rstest]
+fn test_migrate_legacy_state(mock_user1: Principal, mock_now: u64) {
+    register_names(0..10, mock_user1, mock_now);
+    let legacy_bytes = STATE.with(|s| s.encode());
+    // stable memory is empty when the canister is upgraded from the legacy version
+    STATE.with(|s| {
+        s.registration_store
+            .borrow_mut()
+            .import_entries(Default::default());
+        s.token_index_store
+            .borrow_mut()
+            .import_entries(Default::default());
+    });
+
+    STATE.with(|s| s.replace(State::decode(legacy_bytes).unwrap()));
+
+    assert_names_registered(10, mock_user1);
+}
+
+#[rstest]
+fn test_upgrade_restores_certified_map(mock_user1: Principal, mock_now: u64) {
+    register_names(0..1, mock_user1, mock_now);
+    STATE.with(|s| certify_all(&s.registration_store.borrow(), &s.block_log.borrow()));
+    let root_hash = CERTIFIED_MAP.with(|map| map.borrow().root_hash());
+    // names registered without being certified, they are not certified by upgrade either
+    register_names(1..10, mock_user1, mock_now);
+
+    pre_upgrade();
+    CERTIFIED_MAP.with(|map| map.replace(CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID)));
+    post_upgrade();
+
+    CERTIFIED_MAP.with(|map| {
+        let map = map.borrow();
+        assert_eq!(map.root_hash(), root_hash);
+        let name = format!("name1.{}", NAMING_TOP_LABEL);
+        assert!(map.get(&get_owner_key(&name)).is_none());
+    });
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use candid::{CandidType, Deserialize};
 use ic_cdk::api;
@@ -21,12 +21,7 @@ impl StatsService {
         STATE.with(|s| {
             {
                 let store = s.registration_store.borrow();
-                // count distinct owners of registered names
-                let mut owners = HashSet::new();
-                for registration in store.get_registrations().values() {
-                    owners.insert(registration.get_owner());
-                }
-                stats.user_count = owners.len() as u64;
+                stats.user_count = store.get_owner_count();
             }
             {
                 let mut user_quota_count = HashMap::new();
@@ -66,9 +61,12 @@ impl StatsService {
             }
             {
                 let store = s.registration_store.borrow();
-                let count = store.get_registrations().len();
-
-                stats.registration_count = count as u64;
+                stats.registration_count = store.get_registration_count();
+            }
+            {
+                let block_log = s.block_log.borrow();
+                stats.unarchived_block_count = block_log.get_unarchived_count();
+                stats.block_archive_full = block_log.is_last_archive_full();
             }
         });
         MERTRICS_COUNTER.with(|c| {
@@ -126,6 +124,16 @@ pub fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>, now: u64) -> std::io::Res
         stats.new_registered_name_count as f64,
         "Number of new registered names",
     )?;
+    w.encode_gauge(
+        "icnaming_registrar_unarchived_block_count",
+        stats.unarchived_block_count as f64,
+        "Number of blocks kept in the log",
+    )?;
+    w.encode_gauge(
+        "icnaming_registrar_block_archive_full",
+        if stats.block_archive_full { 1.0 } else { 0.0 },
+        "Whether the last block archive is full",
+    )?;
     w.encode_gauge(
         "icnaming_registrar_cycles_balance",
         stats.cycles_balance as f64,
@@ -146,4 +154,6 @@ pub struct Stats {
     name_order_paid_count: u64,
     new_registered_name_count: u64,
     name_lock_count: u64,
+    unarchived_block_count: u64,
+    block_archive_full: bool,
 }
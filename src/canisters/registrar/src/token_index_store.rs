@@ -1,17 +1,19 @@
-use candid::{decode_args, encode_args, CandidType, Deserialize};
+use candid::{encode_args, CandidType, Deserialize};
 use common::errors::NamingError;
 
 use crate::registration_store::Registration;
 use crate::Principal;
 
+use crate::state::{
+    NAME_TOKEN_INDEXES_MEMORY_ID, TOKEN_INDEX_MEMORY_ID, TOKEN_INDEX_NAMES_MEMORY_ID,
+};
 use crate::token_identifier::{TokenIdentifier, TokenIndex};
-use common::state::StableState;
+use common::stable_memory::{StableMap, StableValue};
+use common::state::StableMemoryStore;
 use log::error;
-use std::cell::RefCell;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::HashMap;
 use std::hash::Hash;
-use std::ops::Deref;
-use std::rc::Rc;
+use std::ops;
 use std::vec::Vec;
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug, Ord, PartialOrd)]
@@ -45,15 +47,21 @@ impl RegistrationName {
     }
 }
 
-pub type RegistrationNameRef = Rc<RefCell<RegistrationName>>;
-pub type RegistrationNames = BinaryHeap<RegistrationNameRef>;
-
-#[derive(Default)]
+/// Token indexes are kept in stable memory, so that they are not serialized on upgrade.
 pub struct TokenIndexStore {
-    index: TokenIndex,
-    registrations: RegistrationNames,
-    token_indexes: HashMap<TokenIndex, RegistrationNameRef>,
-    name_indexes: HashMap<String, RegistrationNameRef>,
+    index: StableValue<TokenIndex>,
+    token_indexes: StableMap<u32, String>,
+    name_indexes: StableMap<String, u32>,
+}
+
+impl Default for TokenIndexStore {
+    fn default() -> Self {
+        TokenIndexStore {
+            index: StableValue::init(TOKEN_INDEX_MEMORY_ID),
+            token_indexes: StableMap::init(TOKEN_INDEX_NAMES_MEMORY_ID),
+            name_indexes: StableMap::init(NAME_TOKEN_INDEXES_MEMORY_ID),
+        }
+    }
 }
 
 impl TokenIndexStore {
@@ -86,36 +94,62 @@ impl TokenIndexStore {
     pub fn add_registration_name(&mut self, registration_name: &RegistrationName) -> TokenIndex {
         let token_id = registration_name.index.to_owned();
         let name = registration_name.name.to_owned();
-        let registration_name_ref = Rc::new(RefCell::new(registration_name.to_owned()));
         self.token_indexes
-            .insert(token_id, registration_name_ref.to_owned());
-        self.name_indexes
-            .insert(name, registration_name_ref.to_owned());
-        self.registrations.push(registration_name_ref);
+            .insert(token_id.get_value(), name.to_owned());
+        self.name_indexes.insert(name, token_id.get_value());
         token_id
     }
 
-    pub fn get_registrations(&self) -> &RegistrationNames {
-        &self.registrations
+    pub fn remove_registration_name(&mut self, name: &String) -> Option<TokenIndex> {
+        let token_id = self.name_indexes.remove(name)?;
+        self.token_indexes.remove(&token_id);
+        Some(TokenIndex(token_id))
     }
-    pub fn get_registration(&self, index: &TokenIndex) -> Option<&RegistrationNameRef> {
-        self.token_indexes.get(index)
+
+    /// All indexed names, ordered by token index
+    pub fn get_registrations(&self) -> impl Iterator<Item = RegistrationName> + '_ {
+        self.token_indexes
+            .iter()
+            .map(|(index, name)| RegistrationName::new(TokenIndex(index), name))
     }
-    pub fn get_registration_by_name(&self, name: &String) -> Option<&RegistrationNameRef> {
-        self.name_indexes.get(name)
+    /// Indexed names after the token index, ordered by token index
+    pub fn get_registrations_after(
+        &self,
+        prev: Option<TokenIndex>,
+    ) -> impl Iterator<Item = RegistrationName> + '_ {
+        let start = match prev {
+            Some(prev) => ops::Bound::Excluded(prev.get_value()),
+            None => ops::Bound::Unbounded,
+        };
+        self.token_indexes
+            .range((start, ops::Bound::Unbounded))
+            .map(|(index, name)| RegistrationName::new(TokenIndex(index), name))
+    }
+    pub fn get_registration_count(&self) -> u64 {
+        self.token_indexes.len()
+    }
+    pub fn get_registration(&self, index: &TokenIndex) -> Option<RegistrationName> {
+        self.token_indexes
+            .get(&index.get_value())
+            .map(|name| RegistrationName::new(*index, name))
+    }
+    pub fn get_registration_by_name(&self, name: &String) -> Option<RegistrationName> {
+        self.name_indexes
+            .get(name)
+            .map(|index| RegistrationName::new(TokenIndex(index), name.to_owned()))
     }
     fn next_token_index(&mut self) -> TokenIndex {
-        let new_index = TokenIndex(self.index.get_value() + 1);
-        self.index = new_index.clone();
+        let new_index = TokenIndex(self.index.get().get_value() + 1);
+        self.index.set(new_index);
         new_index
     }
     pub fn get_current_token_index(&self) -> TokenIndex {
-        self.index.clone()
+        *self.index.get()
     }
 }
 
 #[derive(Clone, CandidType, Deserialize)]
-struct StableRegistrationName {
+pub struct StableRegistrationName {
     index: TokenIndex,
     name: String,
 }
@@ -138,30 +172,24 @@ impl From<&StableRegistrationName> for RegistrationName {
     }
 }
 
-impl StableState for TokenIndexStore {
-    fn encode(&self) -> Vec<u8> {
-        let stable_registrations: Vec<StableRegistrationName> = self
-            .token_indexes
-            .values()
-            .map(|registration| {
-                let registration = registration.borrow();
-                StableRegistrationName::from(registration.deref())
-            })
+impl StableMemoryStore for TokenIndexStore {
+    type Entries = (TokenIndex, Vec<StableRegistrationName>);
+
+    fn export_entries(&self) -> Self::Entries {
+        let stable_registrations = self
+            .get_registrations()
+            .map(|registration| StableRegistrationName::from(&registration))
             .collect();
-        let token_index = self.index;
-        encode_args((token_index, stable_registrations)).unwrap()
+        (self.get_current_token_index(), stable_registrations)
     }
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (token_index, stable_registrations): (TokenIndex, Vec<StableRegistrationName>) =
-            decode_args(&bytes).unwrap();
-
-        let mut token_index_store = TokenIndexStore::default();
+    fn import_entries(&mut self, (token_index, stable_registrations): Self::Entries) {
+        self.token_indexes.clear();
+        self.name_indexes.clear();
         for registration in stable_registrations {
-            token_index_store.add_registration_name(&RegistrationName::from(&registration));
+            self.add_registration_name(&RegistrationName::from(&registration));
         }
-        token_index_store.index = token_index;
-        Ok(token_index_store)
+        self.index.set(token_index);
     }
 }
 
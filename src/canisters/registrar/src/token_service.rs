@@ -1,22 +1,30 @@
 use crate::balance_store::{LocalTransactionId, TokenTransaction};
+use crate::payment_token_store::PaymentToken;
 use crate::state::STATE;
-use candid::Nat;
-use common::canister_api::ic_impl::DICPApi;
-use common::canister_api::IDICPApi;
+use candid::{Nat, Principal};
+use common::canister_api::ic_impl::{DICPApi, IcrcLedgerApi};
+use common::canister_api::{IDICPApi, IIcrcLedgerApi};
 use common::errors::{NamingError, ServiceResult};
+use common::icrc::{Account, Icrc1TransferArg, Icrc2TransferFromArgs};
+use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
 use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
 use common::TimeInNs;
 use log::{debug, error, info};
 use std::sync::Arc;
 
+#[cfg(test)]
+mod tests;
+
 pub struct TokenService {
     pub dicp_api: Arc<dyn IDICPApi>,
+    pub icrc_ledger_api: Arc<dyn IIcrcLedgerApi>,
 }
 
 impl Default for TokenService {
     fn default() -> Self {
         TokenService {
             dicp_api: Arc::new(DICPApi::default()),
+            icrc_ledger_api: Arc::new(IcrcLedgerApi::default()),
         }
     }
 }
@@ -38,7 +46,7 @@ impl TokenService {
             let tx_id = STATE.with(|s| {
                 let mut balance_store = s.balance_store.borrow_mut();
                 let tx_id = balance_store.get_next_transaction_id();
-                balance_store.new_transaction(tx_id, from.to_string(), now, amount.clone());
+                balance_store.new_transaction(tx_id, from.to_string(), now, amount.clone(), None);
                 tx_id
             });
             info!(
@@ -51,6 +59,55 @@ impl TokenService {
         }
     }
 
+    /// Transfer tokens approved by `from` to registrar by ICRC-2 `icrc2_transfer_from`
+    pub async fn transfer_from_ledger(
+        &self,
+        token: &PaymentToken,
+        from: &Principal,
+        amount: Nat,
+        now: TimeInNs,
+    ) -> ServiceResult<LocalTransactionId> {
+        let result = self
+            .icrc_ledger_api
+            .icrc2_transfer_from(
+                token.ledger,
+                Icrc2TransferFromArgs {
+                    spender_subaccount: None,
+                    from: Account::new(*from),
+                    to: Account::new(get_named_get_canister_id(CanisterNames::Registrar)),
+                    amount: amount.clone(),
+                    fee: None,
+                    memo: None,
+                    created_at_time: None,
+                },
+            )
+            .await?;
+        match result {
+            Ok(block_index) => {
+                let tx_id = STATE.with(|s| {
+                    let mut balance_store = s.balance_store.borrow_mut();
+                    let tx_id = balance_store.get_next_transaction_id();
+                    balance_store.new_transaction(
+                        tx_id,
+                        from.to_text(),
+                        now,
+                        amount.clone(),
+                        Some(token.ledger),
+                    );
+                    tx_id
+                });
+                info!(
+                    "Transfer {} {} from {}, block_index: {}, local_tx_id: {}",
+                    amount, token.symbol, from, block_index, tx_id
+                );
+                Ok(tx_id)
+            }
+            Err(e) => Err(NamingError::LedgerTransferFailed {
+                reason: format!("{:?}", e),
+            }),
+        }
+    }
+
     pub fn complete_transaction(&self, tx_id: LocalTransactionId) {
         debug!("Complete transaction: {}", tx_id);
         STATE.with(|s| {
@@ -100,15 +157,20 @@ impl TokenService {
             let mut balance_store = s.balance_store.borrow_mut();
             balance_store.mark_refunding(transaction.id());
         });
-        let result = self
-            .dicp_api
-            .transfer(
-                None,
-                transaction.user().to_string(),
-                transaction.value().clone(),
-                None,
-            )
-            .await;
+        let result = match transaction.ledger() {
+            Some(ledger) => self.refund_to_ledger(ledger, &transaction).await,
+            None => self
+                .dicp_api
+                .transfer(
+                    None,
+                    transaction.user().to_string(),
+                    transaction.value().clone(),
+                    None,
+                )
+                .await
+                .map(|_| ())
+                .map_err(NamingError::RemoteError),
+        };
         if result.is_ok() {
             debug!("TokenService::try_refund: refunded {}", transaction.id());
             STATE.with(|s| {
@@ -123,7 +185,56 @@ impl TokenService {
                 let mut balance_store = s.balance_store.borrow_mut();
                 balance_store.mark_to_be_refunded(transaction.id());
             });
-            Err(NamingError::RemoteError(error))
+            Err(error)
+        }
+    }
+
+    /// Refund by ICRC-1 `icrc1_transfer`, the ledger fee is deducted from the refunded amount.
+    /// The fee is queried from the ledger if the payment token has been removed.
+    async fn refund_to_ledger(
+        &self,
+        ledger: Principal,
+        transaction: &TokenTransaction,
+    ) -> ServiceResult<()> {
+        let fee = STATE.with(|s| {
+            let store = s.payment_token_store.borrow();
+            store.get_token(&ledger).map(|token| token.fee.clone())
+        });
+        let fee = match fee {
+            Some(fee) => fee,
+            None => self.icrc_ledger_api.icrc1_fee(ledger).await?,
+        };
+        let value = transaction.value().clone();
+        if value <= fee {
+            debug!(
+                "TokenService::refund_to_ledger: {} is not enough to pay fee {}",
+                value, fee
+            );
+            return Ok(());
         }
+        let to = Principal::from_text(transaction.user()).map_err(|e| {
+            NamingError::LedgerTransferFailed {
+                reason: e.to_string(),
+            }
+        })?;
+        let result = self
+            .icrc_ledger_api
+            .icrc1_transfer(
+                ledger,
+                Icrc1TransferArg {
+                    from_subaccount: None,
+                    to: Account::new(to),
+                    amount: value - fee,
+                    fee: None,
+                    memo: None,
+                    created_at_time: None,
+                },
+            )
+            .await?;
+        result
+            .map(|_| ())
+            .map_err(|e| NamingError::LedgerTransferFailed {
+                reason: format!("{:?}", e),
+            })
     }
 }
@@ -0,0 +1,100 @@
+use candid::{Nat, Principal};
+use rstest::*;
+
+use common::icrc::Account;
+use test_common::canister_api::*;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::payment_token_store::{PaymentToken, PriceFeed};
+
+use super::*;
+
+const PAID: u64 = 1_000_000;
+
+fn add_transaction(user: Principal, ledger: Principal) -> LocalTransactionId {
+    STATE.with(|s| {
+        let mut balance_store = s.balance_store.borrow_mut();
+        let tx_id = balance_store.get_next_transaction_id();
+        balance_store.new_transaction(
+            tx_id,
+            user.to_text(),
+            TimeInNs(0),
+            Nat::from(PAID),
+            Some(ledger),
+        );
+        tx_id
+    })
+}
+
+fn is_transaction_removed(tx_id: LocalTransactionId) -> bool {
+    STATE.with(|s| s.balance_store.borrow().get_transaction(tx_id).is_none())
+}
+
+fn expect_refund(mock_icrc_ledger_api: &mut MockIcrcLedgerApi, to: Principal, amount: u64) {
+    mock_icrc_ledger_api
+        .expect_icrc1_transfer()
+        .times(1)
+        .returning(move |_, arg| {
+            assert_eq!(arg.to, Account::new(to));
+            assert_eq!(arg.amount, Nat::from(amount));
+            Ok(Ok(Nat::from(1u32)))
+        });
+}
+
+#[rstest]
+async fn test_refund_with_token_fee(
+    _init_test: (),
+    mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    mock_user1: Principal,
+    mock_user2: Principal,
+) {
+    STATE.with(|s| {
+        s.payment_token_store.borrow_mut().set_token(PaymentToken {
+            ledger: mock_user2,
+            symbol: "ckBTC".to_string(),
+            decimals: 8,
+            fee: Nat::from(10u32),
+            price_feed: PriceFeed::XdrPermyriadPerToken(20_000),
+        });
+    });
+    let tx_id = add_transaction(mock_user1, mock_user2);
+    mock_icrc_ledger_api.expect_icrc1_fee().never();
+    expect_refund(&mut mock_icrc_ledger_api, mock_user1, PAID - 10);
+    let service = TokenService {
+        icrc_ledger_api: Arc::new(mock_icrc_ledger_api),
+        ..TokenService::default()
+    };
+
+    let result = service.refund(tx_id).await;
+
+    assert!(result.is_ok());
+    assert!(is_transaction_removed(tx_id));
+}
+
+#[rstest]
+async fn test_refund_with_ledger_fee_after_token_removed(
+    _init_test: (),
+    mut mock_icrc_ledger_api: MockIcrcLedgerApi,
+    mock_user1: Principal,
+    mock_user2: Principal,
+) {
+    let tx_id = add_transaction(mock_user1, mock_user2);
+    mock_icrc_ledger_api
+        .expect_icrc1_fee()
+        .times(1)
+        .returning(move |ledger| {
+            assert_eq!(ledger, mock_user2);
+            Ok(Nat::from(20u32))
+        });
+    expect_refund(&mut mock_icrc_ledger_api, mock_user1, PAID - 20);
+    let service = TokenService {
+        icrc_ledger_api: Arc::new(mock_icrc_ledger_api),
+        ..TokenService::default()
+    };
+
+    let result = service.refund(tx_id).await;
+
+    assert!(result.is_ok());
+    assert!(is_transaction_removed(tx_id));
+}
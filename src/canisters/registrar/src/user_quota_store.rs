@@ -1,56 +1,28 @@
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-use std::str::FromStr;
 
 use candid::{decode_args, encode_args, CandidType, Deserialize, Principal};
+pub use common::dto::QuotaType;
 use common::errors::{NamingError, ServiceResult};
+use common::stable_memory::StableMap;
 use common::AuthPrincipal;
 use log::{debug, info};
 
 use common::state::StableState;
 
-/// Quota type to be used for registration
-#[derive(Deserialize, Copy, CandidType, Clone, Hash, Eq, PartialEq, Debug)]
-pub enum QuotaType {
-    /// The length of name's the first part in chars must be equal to the value.
-    /// e.g. LenEq(3) means that the first part of the name must be 3 chars long.
-    LenEq(u8),
-    /// The length of name's the first part in chars must be more than or equal to the value.
-    /// e.g. LenGt(3) means that the first part of the name must be at least 3 chars long.
-    LenGte(u8),
-}
+use crate::state::QUOTA_CREDIT_KEYS_MEMORY_ID;
 
-impl FromStr for QuotaType {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // find () in s
-        let mut iter = s.splitn(2, '(');
-        let name = iter.next().unwrap();
-        let args = iter.next().unwrap();
-        let args = args.trim_end_matches(')');
-        match name {
-            "LenEq" => Ok(QuotaType::LenEq(u8::from_str(args).unwrap())),
-            "LenGte" => Ok(QuotaType::LenGte(u8::from_str(args).unwrap())),
-            _ => Err(()),
-        }
-    }
+pub struct UserQuotaStore {
+    user_quotas: HashMap<Principal, HashMap<QuotaType, u32>>,
+    // keys of quotas added by `add_quota_with_key`, it is kept in stable memory
+    credit_keys: StableMap<String, ()>,
 }
 
-impl Display for QuotaType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            QuotaType::LenEq(len) => write!(f, "len_eq({})", len),
-            QuotaType::LenGte(len) => write!(f, "len_gte({})", len),
-        }
+impl Default for UserQuotaStore {
+    fn default() -> Self {
+        UserQuotaStore::new()
     }
 }
 
-#[derive(Default)]
-pub struct UserQuotaStore {
-    user_quotas: HashMap<Principal, HashMap<QuotaType, u32>>,
-}
-
 impl StableState for UserQuotaStore {
     fn encode(&self) -> Vec<u8> {
         encode_args((&self.user_quotas,)).unwrap()
@@ -60,7 +32,10 @@ impl StableState for UserQuotaStore {
         let (user_quotas,): (HashMap<Principal, HashMap<QuotaType, u32>>,) =
             decode_args(&bytes).unwrap();
 
-        Ok(UserQuotaStore { user_quotas })
+        Ok(UserQuotaStore {
+            user_quotas,
+            ..UserQuotaStore::default()
+        })
     }
 }
 
@@ -68,9 +43,18 @@ impl UserQuotaStore {
     pub fn new() -> UserQuotaStore {
         UserQuotaStore {
             user_quotas: HashMap::new(),
+            credit_keys: StableMap::init(QUOTA_CREDIT_KEYS_MEMORY_ID),
         }
     }
 
+    pub fn is_credited(&self, key: &String) -> bool {
+        self.credit_keys.contains_key(key)
+    }
+
+    pub fn add_credit_key(&mut self, key: String) {
+        self.credit_keys.insert(key, ());
+    }
+
     pub fn get_quota(&self, principal: &AuthPrincipal, quota_type: &QuotaType) -> Option<u32> {
         self.user_quotas
             .get(&principal.0)
@@ -0,0 +1,91 @@
+use common::errors::{NamingError, ServiceResult};
+use common::icrc::Value;
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+
+use crate::state::ARCHIVE_BLOCKS_MEMORY_ID;
+
+#[cfg(test)]
+mod tests;
+
+/// Blocks moved from the block log of the registrar, keyed by block index.
+/// Blocks are kept in stable memory, so that they are not serialized on upgrade.
+pub struct ArchiveStore {
+    blocks: StableMap<u64, Value>,
+}
+
+impl Default for ArchiveStore {
+    fn default() -> Self {
+        ArchiveStore {
+            blocks: StableMap::init(ARCHIVE_BLOCKS_MEMORY_ID),
+        }
+    }
+}
+
+impl ArchiveStore {
+    pub fn len(&self) -> u64 {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Index of the first archived block, None if no block is archived
+    pub fn get_start(&self) -> Option<u64> {
+        self.blocks.keys().next()
+    }
+
+    /// Index after the last archived block, None if no block is archived
+    pub fn get_end(&self) -> Option<u64> {
+        self.get_start().map(|start| start + self.len())
+    }
+
+    /// Append blocks from index `start`, returns the index after the last archived block.
+    /// Blocks which are already archived are skipped, so that a chunk can be sent again
+    /// when the response of the previous call is lost.
+    pub fn append_blocks(
+        &mut self,
+        start: u64,
+        blocks: Vec<Value>,
+        max_blocks: u64,
+    ) -> ServiceResult<u64> {
+        let next = self.get_end().unwrap_or(start);
+        if start > next {
+            return Err(NamingError::InvalidBlockIndex { expected: next });
+        }
+        let skip = next - start;
+        let count = (blocks.len() as u64).saturating_sub(skip);
+        if self.len() + count > max_blocks {
+            return Err(NamingError::ArchiveFull { max: max_blocks });
+        }
+        for (index, block) in (next..).zip(blocks.into_iter().skip(skip as usize)) {
+            self.blocks.insert(index, block);
+        }
+        Ok(next + count)
+    }
+
+    pub fn get_block(&self, index: u64) -> Option<Value> {
+        self.blocks.get(&index)
+    }
+
+    /// Blocks from `start`, at most `length` blocks are returned
+    pub fn get_blocks(&self, start: u64, length: u64) -> impl Iterator<Item = (u64, Value)> + '_ {
+        self.blocks.range(start..start.saturating_add(length))
+    }
+}
+
+impl StableMemoryStore for ArchiveStore {
+    type Entries = (Vec<(u64, Value)>,);
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.blocks.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (blocks,): Self::Entries) {
+        self.blocks.clear();
+        for (index, block) in blocks {
+            self.blocks.insert(index, block);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use candid::Nat;
+use rstest::*;
+
+use super::*;
+
+fn block(value: u64) -> Value {
+    Value::Nat(Nat::from(value))
+}
+
+fn blocks(start: u64, end: u64) -> Vec<Value> {
+    (start..end).map(block).collect()
+}
+
+#[rstest]
+fn test_append_blocks() {
+    let mut store = ArchiveStore::default();
+    assert_eq!(store.get_end(), None);
+
+    let end = store.append_blocks(10, blocks(10, 15), 100).unwrap();
+    assert_eq!(end, 15);
+    assert_eq!(store.get_start(), Some(10));
+    assert_eq!(store.get_end(), Some(15));
+    assert_eq!(store.get_block(10), Some(block(10)));
+    assert_eq!(store.get_block(14), Some(block(14)));
+
+    let end = store.append_blocks(15, blocks(15, 20), 100).unwrap();
+    assert_eq!(end, 20);
+    assert_eq!(store.len(), 10);
+}
+
+#[rstest]
+fn test_append_blocks_skip_archived() {
+    let mut store = ArchiveStore::default();
+    store.append_blocks(0, blocks(0, 5), 100).unwrap();
+
+    let end = store.append_blocks(3, blocks(3, 8), 100).unwrap();
+    assert_eq!(end, 8);
+    assert_eq!(store.len(), 8);
+    assert_eq!(store.get_block(4), Some(block(4)));
+
+    let end = store.append_blocks(0, blocks(0, 5), 100).unwrap();
+    assert_eq!(end, 8);
+    assert_eq!(store.len(), 8);
+}
+
+#[rstest]
+fn test_append_blocks_gap() {
+    let mut store = ArchiveStore::default();
+    store.append_blocks(0, blocks(0, 5), 100).unwrap();
+
+    let result = store.append_blocks(6, blocks(6, 8), 100);
+    assert_eq!(result, Err(NamingError::InvalidBlockIndex { expected: 5 }));
+    assert_eq!(store.len(), 5);
+}
+
+#[rstest]
+fn test_append_blocks_full() {
+    let mut store = ArchiveStore::default();
+    store.append_blocks(0, blocks(0, 5), 8).unwrap();
+
+    let result = store.append_blocks(5, blocks(5, 9), 8);
+    assert_eq!(result, Err(NamingError::ArchiveFull { max: 8 }));
+    assert_eq!(store.len(), 5);
+}
+
+#[rstest]
+fn test_get_blocks() {
+    let mut store = ArchiveStore::default();
+    store.append_blocks(10, blocks(10, 20), 100).unwrap();
+
+    let items: Vec<u64> = store.get_blocks(5, 8).map(|(index, _)| index).collect();
+    assert_eq!(items, vec![10, 11, 12]);
+    let items: Vec<u64> = store.get_blocks(18, 100).map(|(index, _)| index).collect();
+    assert_eq!(items, vec![18, 19]);
+    assert_eq!(store.get_blocks(20, 10).count(), 0);
+}
+
+#[rstest]
+fn test_export_import() {
+    let mut store = ArchiveStore::default();
+    store.append_blocks(10, blocks(10, 15), 100).unwrap();
+    let entries = store.export_entries();
+
+    store.append_blocks(15, blocks(15, 20), 100).unwrap();
+    store.import_entries(entries);
+
+    assert_eq!(store.get_start(), Some(10));
+    assert_eq!(store.get_end(), Some(15));
+    assert_eq!(store.get_block(12), Some(block(12)));
+}
@@ -0,0 +1,72 @@
+mod archive_store;
+mod http;
+mod service;
+mod state;
+
+#[path = "../../../common/common_actor/src/actor.rs"]
+mod shared_actor;
+mod stats_service;
+
+use crate::state::InitArgs;
+use common::dto::*;
+use common::http::*;
+use stats_service::*;
+use std::collections::HashMap;
+
+use candid::candid_method;
+use candid::CandidType;
+
+use ic_cdk::api;
+use ic_cdk_macros::*;
+
+use common::errors::{BooleanActorResponse, ErrorInfo, ServiceResult};
+use common::icrc::Value;
+use common::icrc3::{GetBlocksArgs, GetBlocksResult};
+
+use crate::service::ArchiveService;
+
+/// Append blocks moved from the block log of the registrar, only the registrar can call it.
+///
+/// * `start` - index of the first block
+/// * `blocks` - blocks in order, blocks which are already archived are skipped
+#[update(name = "append_blocks")]
+#[candid_method(update, rename = "append_blocks")]
+fn append_blocks(start: u64, blocks: Vec<Value>) -> AppendBlocksResponse {
+    let caller = api::caller();
+    let service = ArchiveService::new();
+    let result = service.append_blocks(&caller, start, blocks);
+    AppendBlocksResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum AppendBlocksResponse {
+    Ok(u64),
+    Err(ErrorInfo),
+}
+
+impl AppendBlocksResponse {
+    pub fn new(result: ServiceResult<u64>) -> AppendBlocksResponse {
+        match result {
+            Ok(data) => AppendBlocksResponse::Ok(data),
+            Err(err) => AppendBlocksResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Archived blocks of the ICRC-3 log of the registrar
+///
+/// * `args` - ranges of blocks, at most 1000 blocks are returned in total
+#[query(name = "icrc3_get_blocks")]
+#[candid_method(query)]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    let service = ArchiveService::new();
+    service.icrc3_get_blocks(args)
+}
+
+candid::export_service!();
+
+#[query(name = "__get_candid_interface_tmp_hack")]
+#[candid_method(query, rename = "__get_candid_interface_tmp_hack")]
+fn __export_did_tmp_() -> String {
+    __export_service()
+}
@@ -0,0 +1,68 @@
+use candid::{Nat, Principal};
+use num_traits::ToPrimitive;
+
+use common::errors::ServiceResult;
+use common::icrc::Value;
+use common::icrc3::{BlockWithId, GetBlocksArgs, GetBlocksResult};
+use common::named_canister_ids::CanisterNames;
+use common::permissions::must_be_named_canister;
+
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+/// Blocks an archive can hold, the registrar should create a new archive when it is full
+pub const ARCHIVE_MAX_BLOCKS: u64 = 5_000_000;
+pub const ARCHIVE_MAX_BLOCKS_PER_RESPONSE: u64 = 1000;
+
+pub(crate) struct ArchiveService {}
+
+impl ArchiveService {
+    pub fn new() -> Self {
+        ArchiveService {}
+    }
+}
+
+impl ArchiveService {
+    /// Blocks are only appended by the registrar, returns the index after the last archived block
+    pub fn append_blocks(
+        &self,
+        caller: &Principal,
+        start: u64,
+        blocks: Vec<Value>,
+    ) -> ServiceResult<u64> {
+        must_be_named_canister(*caller, CanisterNames::Registrar)?;
+        STATE.with(|s| {
+            let mut store = s.archive_store.borrow_mut();
+            store.append_blocks(start, blocks, ARCHIVE_MAX_BLOCKS)
+        })
+    }
+
+    /// Blocks of the ranges, at most `ARCHIVE_MAX_BLOCKS_PER_RESPONSE` blocks are returned in total.
+    /// `log_length` is the index after the last archived block.
+    pub fn icrc3_get_blocks(&self, args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+        STATE.with(|s| {
+            let store = s.archive_store.borrow();
+            let mut blocks = vec![];
+            for arg in args {
+                let remaining = ARCHIVE_MAX_BLOCKS_PER_RESPONSE - blocks.len() as u64;
+                let start = arg.start.0.to_u64().unwrap_or(u64::MAX);
+                let length = arg.length.0.to_u64().unwrap_or(u64::MAX).min(remaining);
+                blocks.extend(
+                    store
+                        .get_blocks(start, length)
+                        .map(|(id, block)| BlockWithId {
+                            id: Nat::from(id),
+                            block,
+                        }),
+                );
+            }
+            GetBlocksResult {
+                log_length: Nat::from(store.get_end().unwrap_or(0)),
+                blocks,
+                archived_blocks: vec![],
+            }
+        })
+    }
+}
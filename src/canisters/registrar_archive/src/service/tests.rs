@@ -0,0 +1,97 @@
+use rstest::*;
+
+use common::errors::NamingError;
+use common::named_canister_ids::get_named_get_canister_id;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use super::*;
+
+#[fixture]
+fn service() -> ArchiveService {
+    init_test();
+    ArchiveService::new()
+}
+
+#[fixture]
+fn registrar() -> Principal {
+    get_named_get_canister_id(CanisterNames::Registrar)
+}
+
+fn blocks(start: u64, end: u64) -> Vec<Value> {
+    (start..end)
+        .map(|value| Value::Nat(Nat::from(value)))
+        .collect()
+}
+
+fn get_blocks_args(start: u64, length: u64) -> GetBlocksArgs {
+    GetBlocksArgs {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    }
+}
+
+fn get_ids(result: &GetBlocksResult) -> Vec<Nat> {
+    result.blocks.iter().map(|block| block.id.clone()).collect()
+}
+
+mod append_blocks {
+    use super::*;
+
+    #[rstest]
+    fn test_append_blocks(service: ArchiveService, registrar: Principal) {
+        let end = service.append_blocks(&registrar, 0, blocks(0, 5)).unwrap();
+        assert_eq!(end, 5);
+
+        let end = service.append_blocks(&registrar, 5, blocks(5, 8)).unwrap();
+        assert_eq!(end, 8);
+    }
+
+    #[rstest]
+    fn test_append_blocks_not_registrar(service: ArchiveService, mock_user1: Principal) {
+        let result = service.append_blocks(&mock_user1, 0, blocks(0, 5));
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+}
+
+mod icrc3_get_blocks {
+    use super::*;
+
+    #[rstest]
+    fn test_get_blocks(service: ArchiveService, registrar: Principal) {
+        service
+            .append_blocks(&registrar, 10, blocks(10, 20))
+            .unwrap();
+
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(8, 4), get_blocks_args(18, 5)]);
+        assert_eq!(result.log_length, Nat::from(20u64));
+        assert_eq!(
+            get_ids(&result),
+            vec![10u64, 11, 18, 19]
+                .into_iter()
+                .map(Nat::from)
+                .collect::<Vec<_>>()
+        );
+        assert!(result.archived_blocks.is_empty());
+    }
+
+    #[rstest]
+    fn test_get_blocks_max_per_response(service: ArchiveService, registrar: Principal) {
+        let count = ARCHIVE_MAX_BLOCKS_PER_RESPONSE + 10;
+        service
+            .append_blocks(&registrar, 0, blocks(0, count))
+            .unwrap();
+
+        let result =
+            service.icrc3_get_blocks(vec![get_blocks_args(0, count), get_blocks_args(0, count)]);
+        assert_eq!(result.log_length, Nat::from(count));
+        assert_eq!(result.blocks.len() as u64, ARCHIVE_MAX_BLOCKS_PER_RESPONSE);
+    }
+
+    #[rstest]
+    fn test_get_blocks_empty(service: ArchiveService) {
+        let result = service.icrc3_get_blocks(vec![get_blocks_args(0, 10)]);
+        assert_eq!(result.log_length, Nat::from(0u64));
+        assert!(result.blocks.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Once;
+
+use candid::{candid_method, decode_args, encode_args, CandidType, Deserialize, Principal};
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use log::info;
+
+use common::ic_logger::ICLogger;
+use common::named_canister_ids::{update_dev_named_canister_ids, CanisterNames};
+use common::stable_memory::MemoryId;
+use common::state::{
+    encode_entries, restore_state, save_state, StableEntries, StableMemoryState, StableState,
+};
+
+use crate::archive_store::ArchiveStore;
+
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const ARCHIVE_BLOCKS_MEMORY_ID: MemoryId = MemoryId::new(1);
+
+thread_local! {
+    pub static STATE : State = State::default();
+}
+
+#[derive(Default)]
+pub struct State {
+    // NOTE: When adding new persistent fields here, ensure that these fields
+    // are being persisted in the `replace` method below.
+    pub(crate) archive_store: RefCell<ArchiveStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    archive_entries: StableEntries<ArchiveStore>,
+}
+
+impl State {
+    pub fn replace(&self, new_state: State) {
+        new_state.archive_entries.import_into(&self.archive_store);
+    }
+}
+
+impl StableState for State {
+    fn encode(&self) -> Vec<u8> {
+        encode_args((encode_entries(&self.archive_store),)).unwrap()
+    }
+
+    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let (archive_store_bytes,): (Vec<u8>,) = decode_args(&bytes).map_err(|e| e.to_string())?;
+
+        Ok(State {
+            archive_entries: StableEntries::decode(archive_store_bytes)?,
+            ..State::default()
+        })
+    }
+}
+
+/// All stores are kept in stable memory, nothing is saved with the heap state
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args(()).unwrap()
+    }
+
+    fn decode_heap(_bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(State::default())
+    }
+}
+
+static INIT: Once = Once::new();
+
+// more than one archive could be created, so there is no named canister id to check
+fn guard_func() -> Result<(), String> {
+    INIT.call_once(|| {
+        ICLogger::init("registrar_archive");
+    });
+    Ok(())
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    dev_named_canister_ids: HashMap<CanisterNames, Principal>,
+}
+
+#[init]
+#[candid_method(init)]
+#[cfg(feature = "dev_env")]
+fn init_function(args: Option<InitArgs>) {
+    info!("init function called");
+    if let Some(args) = args {
+        update_dev_named_canister_ids(&args.dev_named_canister_ids);
+    }
+
+    guard_func().unwrap();
+}
+
+#[init]
+#[candid_method(init)]
+#[cfg(not(feature = "dev_env"))]
+fn init_function() {
+    info!("init function called");
+    guard_func().unwrap();
+}
+
+#[pre_upgrade(guard = "guard_func")]
+fn pre_upgrade() {
+    STATE.with(|s| {
+        save_state(s);
+        info!("Saved state before upgrade");
+    });
+}
+
+#[post_upgrade(guard = "guard_func")]
+fn post_upgrade() {
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
+            s.replace(new_state);
+            info!("Loaded state after upgrade");
+        }),
+        Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
+    }
+}
@@ -0,0 +1,60 @@
+use candid::Nat;
+use rstest::*;
+
+use common::icrc::Value;
+use common::stable_memory::load_heap_state;
+
+use super::*;
+
+const LARGE_BLOCK_COUNT: u64 = 5_000;
+
+fn append_test_blocks(start: u64, end: u64) {
+    STATE.with(|s| {
+        let mut store = s.archive_store.borrow_mut();
+        let blocks = (start..end)
+            .map(|index| Value::Nat(Nat::from(index)))
+            .collect();
+        store
+            .append_blocks(start, blocks, LARGE_BLOCK_COUNT)
+            .unwrap();
+    });
+}
+
+fn get_heap_state_size() -> usize {
+    load_heap_state().unwrap().len()
+}
+
+#[rstest]
+fn test_upgrade_with_large_archive_store() {
+    append_test_blocks(0, 1);
+    pre_upgrade();
+    let size = get_heap_state_size();
+
+    append_test_blocks(1, LARGE_BLOCK_COUNT);
+    pre_upgrade();
+    post_upgrade();
+
+    // blocks are not saved with the heap state
+    assert_eq!(get_heap_state_size(), size);
+    STATE.with(|s| {
+        let store = s.archive_store.borrow();
+        assert_eq!(store.len(), LARGE_BLOCK_COUNT);
+        assert_eq!(store.get_block(0), Some(Value::Nat(Nat::from(0u64))));
+    });
+}
+
+#[rstest]
+fn test_load_exported_state() {
+    append_test_blocks(0, 10);
+    let bytes = STATE.with(|s| s.encode());
+    append_test_blocks(10, 20);
+
+    let new_state = State::decode(bytes).unwrap();
+    // decoding has no effect on the stores in use
+    STATE.with(|s| assert_eq!(s.archive_store.borrow().len(), 20));
+
+    STATE.with(|s| {
+        s.replace(new_state);
+        assert_eq!(s.archive_store.borrow().len(), 10);
+    });
+}
@@ -0,0 +1,49 @@
+use crate::state::STATE;
+use candid::{CandidType, Deserialize};
+use common::metrics_encoder::MetricsEncoder;
+use ic_cdk::api;
+
+#[derive(Default)]
+pub struct StatsService {}
+
+impl StatsService {
+    pub fn get_stats(&self, _now: u64) -> Stats {
+        let mut stats = Stats::default();
+        stats.cycles_balance = api::canister_balance();
+        STATE.with(|s| {
+            let store = s.archive_store.borrow();
+            stats.block_count = store.len();
+            stats.first_block_index = store.get_start().unwrap_or(0);
+        });
+        stats
+    }
+}
+
+pub fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>, now: u64) -> std::io::Result<()> {
+    let service = StatsService::default();
+    let stats = service.get_stats(now);
+    w.encode_gauge(
+        "icnaming_registrar_archive_cycles_balance",
+        stats.cycles_balance as f64,
+        "Balance in cycles",
+    )?;
+    w.encode_gauge(
+        "icnaming_registrar_archive_block_count",
+        stats.block_count as f64,
+        "Number of archived blocks",
+    )?;
+    w.encode_gauge(
+        "icnaming_registrar_archive_first_block_index",
+        stats.first_block_index as f64,
+        "Index of the first archived block",
+    )?;
+
+    Ok(())
+}
+
+#[derive(CandidType, Deserialize, Default)]
+pub struct Stats {
+    cycles_balance: u64,
+    block_count: u64,
+    first_block_index: u64,
+}
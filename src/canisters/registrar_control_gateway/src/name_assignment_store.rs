@@ -1,40 +1,53 @@
 use std::collections::HashMap;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize, Principal};
+use candid::{CandidType, Deserialize, Principal};
 
-use common::state::StableState;
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+
+use crate::state::NAME_ASSIGNMENTS_MEMORY_ID;
 
 #[derive(CandidType, Deserialize)]
-pub(crate) struct AssignmentRecord {
+pub struct AssignmentRecord {
     owner: Principal,
     assigned_at: u64,
 }
 
-#[derive(Default)]
+/// Assignments are kept in stable memory, so that they are not serialized on upgrade.
 pub struct NameAssignmentStore {
-    assignments: HashMap<String, AssignmentRecord>,
+    assignments: StableMap<String, AssignmentRecord>,
+}
+
+impl Default for NameAssignmentStore {
+    fn default() -> Self {
+        NameAssignmentStore {
+            assignments: StableMap::init(NAME_ASSIGNMENTS_MEMORY_ID),
+        }
+    }
 }
 
-impl StableState for NameAssignmentStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.assignments,)).unwrap()
+impl StableMemoryStore for NameAssignmentStore {
+    type Entries = (HashMap<String, AssignmentRecord>,);
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.assignments.iter().collect(),)
     }
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (assignments,): (HashMap<String, AssignmentRecord>,) = decode_args(&bytes).unwrap();
-        Ok(NameAssignmentStore { assignments })
+    fn import_entries(&mut self, (assignments,): Self::Entries) {
+        self.assignments.clear();
+        for (name, record) in assignments {
+            self.assignments.insert(name, record);
+        }
     }
 }
 
 impl NameAssignmentStore {
     pub fn new() -> NameAssignmentStore {
-        NameAssignmentStore {
-            assignments: HashMap::new(),
-        }
+        NameAssignmentStore::default()
     }
 
     pub fn name_assigned(&self, name: &str) -> bool {
-        self.assignments.contains_key(name)
+        self.assignments.contains_key(&name.to_string())
     }
 
     pub fn add_assignment(&mut self, name: &str, owner: Principal, assigned_at: u64) {
@@ -42,7 +55,7 @@ impl NameAssignmentStore {
             .insert(name.to_string(), AssignmentRecord { owner, assigned_at });
     }
 
-    pub(crate) fn get_assignments(&self) -> &HashMap<String, AssignmentRecord> {
-        &self.assignments
+    pub fn get_assignment_count(&self) -> u64 {
+        self.assignments.len()
     }
 }
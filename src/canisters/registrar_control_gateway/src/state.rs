@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use std::sync::Once;
 
 use candid::{candid_method, decode_args, encode_args, Principal};
-use ic_cdk::{api, storage};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::info;
 
@@ -13,12 +13,22 @@ use common::ic_logger::ICLogger;
 use common::named_canister_ids::{
     ensure_current_canister_id_match, update_dev_named_canister_ids, CanisterNames,
 };
-use common::state::StableState;
+use common::stable_memory::MemoryId;
+use common::state::{
+    decode_store, encode_entries, encode_store, restore_state, save_state, StableEntries,
+    StableMemoryState, StableState,
+};
 
 use crate::build_gen::ACCEPTABLE_HASHES;
 use crate::name_assignment_store::NameAssignmentStore;
 use crate::quota_import_store::QuotaImportStore;
 
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const NAME_ASSIGNMENTS_MEMORY_ID: MemoryId = MemoryId::new(1);
+
 thread_local! {
     pub static STATE : State = State::default();
     pub static MERTRICS_COUNTER: RefCell<MetricsCounter> = RefCell::new(MetricsCounter::default());
@@ -38,34 +48,53 @@ pub struct MetricsCounter {
 pub struct State {
     pub quota_import_store: RefCell<QuotaImportStore>,
     pub name_assignment_store: RefCell<NameAssignmentStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    name_assignment_entries: StableEntries<NameAssignmentStore>,
 }
 
 impl State {
     pub fn replace(&self, new_state: State) {
         self.quota_import_store
             .replace(new_state.quota_import_store.take());
-        self.name_assignment_store
-            .replace(new_state.name_assignment_store.take());
+        new_state
+            .name_assignment_entries
+            .import_into(&self.name_assignment_store);
     }
 }
 
 impl StableState for State {
     fn encode(&self) -> Vec<u8> {
         encode_args((
-            self.quota_import_store.borrow().encode(),
-            self.name_assignment_store.borrow().encode(),
+            encode_store(&self.quota_import_store),
+            encode_entries(&self.name_assignment_store),
         ))
         .unwrap()
     }
 
     fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (quota_import_store_bytes, name_assignment_store_bytes) = decode_args(&bytes).unwrap();
+        let (quota_import_store_bytes, name_assignment_store_bytes): (Vec<u8>, Vec<u8>) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
 
         Ok(State {
-            quota_import_store: RefCell::new(QuotaImportStore::decode(quota_import_store_bytes)?),
-            name_assignment_store: RefCell::new(NameAssignmentStore::decode(
-                name_assignment_store_bytes,
-            )?),
+            quota_import_store: decode_store(quota_import_store_bytes)?,
+            name_assignment_entries: StableEntries::decode(name_assignment_store_bytes)?,
+            ..State::default()
+        })
+    }
+}
+
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args((encode_store(&self.quota_import_store),)).unwrap()
+    }
+
+    fn decode_heap(bytes: Vec<u8>) -> Result<Self, String> {
+        let (quota_import_store_bytes,): (Vec<u8>,) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
+
+        Ok(State {
+            quota_import_store: decode_store(quota_import_store_bytes)?,
+            ..State::default()
         })
     }
 }
@@ -145,27 +174,20 @@ fn init_function() {
 #[pre_upgrade(guard = "guard_func")]
 fn pre_upgrade() {
     STATE.with(|s| {
-        let bytes = s.encode();
-        match storage::stable_save((&bytes,)) {
-            Ok(_) => {
-                info!("Saved state before upgrade");
-                ()
-            }
-            Err(e) => api::trap(format!("Failed to save state before upgrade: {:?}", e).as_str()),
-        };
+        save_state(s);
+        info!("Saved state before upgrade");
     });
 }
 
 #[post_upgrade(guard = "guard_func")]
 fn post_upgrade() {
-    STATE.with(|s| match storage::stable_restore::<(Vec<u8>,)>() {
-        Ok(bytes) => {
-            let new_state = State::decode(bytes.0).expect("Decoding stable memory failed");
-
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
             s.replace(new_state);
             canister_module_init();
             info!("Loaded state after upgrade");
-        }
+        }),
         Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
-    });
+    }
 }
@@ -0,0 +1,69 @@
+use rstest::*;
+
+use common::stable_memory::load_heap_state;
+use common::state::StableMemoryStore;
+use test_common::user::*;
+
+use super::*;
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_now: u64) {
+    STATE.with(|s| {
+        s.quota_import_store
+            .borrow_mut()
+            .add_imported_file_hash(vec![1, 2, 3]);
+    });
+    pre_upgrade();
+    let size = load_heap_state().unwrap().len();
+
+    STATE.with(|s| {
+        let mut store = s.name_assignment_store.borrow_mut();
+        for i in 0..10 {
+            store.add_assignment(&format!("name{}.ic", i), mock_user1, mock_now);
+        }
+    });
+    pre_upgrade();
+    post_upgrade();
+
+    // only imported file hashes are saved with the heap state
+    assert_eq!(load_heap_state().unwrap().len(), size);
+    STATE.with(|s| {
+        assert_eq!(s.name_assignment_store.borrow().get_assignment_count(), 10);
+        assert!(s
+            .quota_import_store
+            .borrow()
+            .get_imported_file_hashes()
+            .contains(&vec![1, 2, 3]));
+    });
+}
+
+#[rstest]
+fn test_migrate_legacy_state(mock_user1: Principal, mock_now: u64) {
+    // both stores were encoded without version before
+    let legacy_bytes = STATE.with(|s| {
+        s.quota_import_store
+            .borrow_mut()
+            .add_imported_file_hash(vec![1, 2, 3]);
+        let mut store = s.name_assignment_store.borrow_mut();
+        store.add_assignment("nice.ic", mock_user1, mock_now);
+        let bytes = encode_args((
+            s.quota_import_store.borrow().encode(),
+            encode_args(store.export_entries()).unwrap(),
+        ))
+        .unwrap();
+        s.quota_import_store.replace(QuotaImportStore::default());
+        store.import_entries(Default::default());
+        bytes
+    });
+
+    STATE.with(|s| s.replace(State::decode(legacy_bytes).unwrap()));
+
+    STATE.with(|s| {
+        assert!(s.name_assignment_store.borrow().name_assigned("nice.ic"));
+        assert!(s
+            .quota_import_store
+            .borrow()
+            .get_imported_file_hashes()
+            .contains(&vec![1, 2, 3]));
+    });
+}
@@ -13,8 +13,7 @@ impl StatsService {
         STATE.with(|s| {
             {
                 let store = s.name_assignment_store.borrow();
-                let assignments = store.get_assignments();
-                stats.name_assignments_count = assignments.len() as u64;
+                stats.name_assignments_count = store.get_assignment_count();
             }
             {
                 let store = s.quota_import_store.borrow();
@@ -0,0 +1,54 @@
+use common::certified_map::CertifiedValue;
+
+use crate::registry_store::RegistryStore;
+use crate::state::CERTIFIED_MAP;
+
+pub fn get_owner_key(name: &str) -> String {
+    format!("owner/{}", name)
+}
+
+pub fn get_resolver_key(name: &str) -> String {
+    format!("resolver/{}", name)
+}
+
+/// Update certified owners and resolvers of names, it should be called after the store is updated.
+pub(crate) fn certify(store: &RegistryStore, names: &[String]) {
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        for name in names {
+            match store.get_registry(name) {
+                Some(registry) => {
+                    map.put(get_owner_key(name), registry.get_owner());
+                    map.put(get_resolver_key(name), &registry.get_resolver());
+                }
+                None => {
+                    map.delete(&get_owner_key(name));
+                    map.delete(&get_resolver_key(name));
+                }
+            }
+        }
+        map.certify();
+    });
+}
+
+/// Rebuild the certified map from the store
+pub(crate) fn certify_all(store: &RegistryStore) {
+    let names = store.get_names();
+    CERTIFIED_MAP.with(|map| map.borrow_mut().clear());
+    certify(store, &names);
+}
+
+/// Certify the map restored from stable memory after upgrade.
+/// It is only rebuilt from the store if it is not kept in stable memory yet.
+pub(crate) fn certify_restored(store: &RegistryStore) {
+    let is_empty = CERTIFIED_MAP.with(|map| map.borrow().is_empty());
+    if is_empty {
+        certify_all(store);
+    } else {
+        CERTIFIED_MAP.with(|map| map.borrow().certify());
+    }
+}
+
+pub(crate) fn get_certified_value<T>(key: &str, value: Option<T>) -> CertifiedValue<T> {
+    CERTIFIED_MAP.with(|map| map.borrow().get_certified_value(key, value))
+}
@@ -0,0 +1,406 @@
+use std::sync::Arc;
+
+use candid::{Nat, Principal};
+use log::{debug, error, info};
+
+use common::canister_api::ic_impl::{DICPApi, ResolverApi};
+use common::canister_api::{IDICPApi, IResolverApi};
+use common::constants::{
+    DEFAULT_TTL, NAMING_CLEAN_EXPIRED_BATCH_SIZE, NAMING_MAX_REGISTRATION_YEAR,
+    NAMING_MIN_REGISTRATION_YEAR, NAMING_SUBDOMAIN_LEASE_YEAR_NS, NAMING_TOP_LABEL,
+};
+use common::dto::RegistryDto;
+use common::errors::{NamingError, ServiceResult};
+use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+use common::named_principals::PRINCIPAL_NAME_TIMER_TRIGGER;
+use common::naming::{parse_subdomain_name, NameParseResult};
+use common::timeout_lock::{release_timeout_locker, try_lock_with_timeout, LockId};
+use common::CallContext;
+
+use crate::certification::certify;
+use crate::lease_store::{SubdomainLease, SubdomainSale};
+use crate::name_locker::{try_lock_name, unlock_name};
+use crate::registry_store::Registry;
+use crate::service::{get_registry, sync_ttl};
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+pub struct SubdomainLeaseService {
+    pub dicp_api: Arc<dyn IDICPApi>,
+    pub resolver_api: Arc<dyn IResolverApi>,
+}
+
+impl Default for SubdomainLeaseService {
+    fn default() -> Self {
+        SubdomainLeaseService {
+            dicp_api: Arc::new(DICPApi::default()),
+            resolver_api: Arc::new(ResolverApi::default()),
+        }
+    }
+}
+
+fn validate_years(years: u32) -> ServiceResult<()> {
+    if years < NAMING_MIN_REGISTRATION_YEAR || years > NAMING_MAX_REGISTRATION_YEAR {
+        return Err(NamingError::YearsRangeError {
+            min: NAMING_MIN_REGISTRATION_YEAR,
+            max: NAMING_MAX_REGISTRATION_YEAR,
+        });
+    }
+    Ok(())
+}
+
+fn parse_leasing_name(name: &str) -> ServiceResult<NameParseResult> {
+    parse_subdomain_name(name).map_err(|reason| NamingError::InvalidName { reason })
+}
+
+impl SubdomainLeaseService {
+    /// Put subdomains of a name on sale, caller should be the owner of the name.
+    /// The sale is replaced if it exists.
+    pub fn set_subdomain_sale(
+        &self,
+        call_context: CallContext,
+        parent_name: &str,
+        sale: SubdomainSale,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        if parent_name == NAMING_TOP_LABEL {
+            return Err(NamingError::PermissionDenied);
+        }
+        sale.validate()?;
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let parent = get_registry(&store, parent_name)?;
+            if !parent.is_owner(&caller.0) {
+                return Err(NamingError::PermissionDenied);
+            }
+            info!("set_subdomain_sale: {} {:?}", parent_name, sale);
+            let mut lease_store = s.lease_store.borrow_mut();
+            lease_store.set_sale(parent_name.to_string(), sale);
+            Ok(true)
+        })
+    }
+
+    /// Stop selling subdomains of a name, leased subdomains are not affected.
+    pub fn remove_subdomain_sale(
+        &self,
+        call_context: CallContext,
+        parent_name: &str,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let parent = get_registry(&store, parent_name)?;
+            if !parent.is_owner(&caller.0) {
+                return Err(NamingError::PermissionDenied);
+            }
+            let mut lease_store = s.lease_store.borrow_mut();
+            if lease_store.remove_sale(parent_name).is_none() {
+                return Err(NamingError::SubdomainNotForSale {
+                    name: parent_name.to_string(),
+                });
+            }
+            info!("remove_subdomain_sale: {}", parent_name);
+            Ok(true)
+        })
+    }
+
+    pub fn get_subdomain_sale(&self, parent_name: &str) -> ServiceResult<SubdomainSale> {
+        STATE.with(|s| {
+            let lease_store = s.lease_store.borrow();
+            lease_store
+                .get_sale(parent_name)
+                .ok_or_else(|| NamingError::SubdomainNotForSale {
+                    name: parent_name.to_string(),
+                })
+        })
+    }
+
+    /// Price in DICP e8s to lease or renew a subdomain for years
+    pub fn get_subdomain_price(&self, name: &str, years: u32) -> ServiceResult<u64> {
+        validate_years(years)?;
+        let result = parse_leasing_name(name)?;
+        let sale = self.get_subdomain_sale(&result.get_parent_name().unwrap())?;
+        Ok(sale.get_price_per_year(result.get_name_len()) * years as u64)
+    }
+
+    pub fn get_subdomain_lease(&self, name: &str) -> ServiceResult<SubdomainLease> {
+        STATE.with(|s| {
+            let lease_store = s.lease_store.borrow();
+            lease_store
+                .get_lease(name)
+                .ok_or_else(|| NamingError::LeaseNotFound {
+                    name: name.to_string(),
+                })
+        })
+    }
+
+    /// Lease a subdomain from the owner of its parent, payment is transferred to the owner of the parent.
+    /// A subdomain with an expired lease can be leased by anyone.
+    pub async fn lease_subdomain(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        years: u32,
+        approve_amount: u64,
+    ) -> ServiceResult<RegistryDto> {
+        let caller = call_context.must_not_anonymous()?;
+        validate_years(years)?;
+        let result = parse_leasing_name(name)?;
+        let name = result.get_name().clone();
+        try_lock_name(&name)?;
+        let lease_result = self
+            .lease_subdomain_core(call_context, caller.0, &result, years, approve_amount)
+            .await;
+        unlock_name(&name);
+        lease_result
+    }
+
+    async fn lease_subdomain_core(
+        &self,
+        call_context: CallContext,
+        buyer: Principal,
+        result: &NameParseResult,
+        years: u32,
+        approve_amount: u64,
+    ) -> ServiceResult<RegistryDto> {
+        let name = result.get_name();
+        let parent_name = result.get_parent_name().unwrap();
+        let now = call_context.now.0;
+        let (parent_owner, amount, is_expired_lease) = STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let lease_store = s.lease_store.borrow();
+            let parent = get_registry(&store, &parent_name)?;
+            let sale = lease_store.get_sale(&parent_name).ok_or_else(|| {
+                NamingError::SubdomainNotForSale {
+                    name: parent_name.clone(),
+                }
+            })?;
+            let is_expired_lease = match lease_store.get_lease(name) {
+                Some(lease) if lease.is_expired(now) => true,
+                Some(_) => {
+                    return Err(NamingError::RegistryAlreadyExists {
+                        name: name.to_string(),
+                    })
+                }
+                None => false,
+            };
+            if !is_expired_lease && store.has_registry(name) {
+                return Err(NamingError::RegistryAlreadyExists {
+                    name: name.to_string(),
+                });
+            }
+            let amount = sale.get_price_per_year(result.get_name_len()) * years as u64;
+            Ok((*parent.get_owner(), amount, is_expired_lease))
+        })?;
+        if approve_amount < amount {
+            return Err(NamingError::InvalidApproveAmount);
+        }
+
+        if is_expired_lease {
+            self.remove_leased_name(name).await?;
+        }
+
+        self.pay_parent_owner(buyer, parent_owner, amount).await?;
+
+        let registry = STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            let registry = Registry::new(
+                name.to_string(),
+                buyer,
+                DEFAULT_TTL,
+                get_named_get_canister_id(CanisterNames::Resolver),
+            );
+            store.add_registry(registry.clone());
+            certify(&store, &[name.to_string()]);
+            let mut lease_store = s.lease_store.borrow_mut();
+            lease_store.add_lease(SubdomainLease {
+                name: name.to_string(),
+                parent_name: parent_name.clone(),
+                created_at: now,
+                expired_at: now + years as u64 * NAMING_SUBDOMAIN_LEASE_YEAR_NS,
+            });
+            registry
+        });
+        info!("lease_subdomain: {} is leased by {}", name, buyer);
+
+        let result = self
+            .resolver_api
+            .ensure_resolver_created(name.to_string())
+            .await;
+        info!("ensure_resolver_created: {:?}", result);
+        let result = sync_ttl(
+            &*self.resolver_api,
+            name,
+            registry.get_ttl(),
+            &registry.get_resolver(),
+        )
+        .await;
+        info!("sync_ttl: {:?}", result);
+        Ok(RegistryDto::from(&registry))
+    }
+
+    /// Renew the lease of a subdomain, caller should be the owner of the subdomain.
+    /// Expired lease can not be renewed, it should be leased again.
+    pub async fn renew_subdomain_lease(
+        &self,
+        call_context: CallContext,
+        name: &str,
+        years: u32,
+        approve_amount: u64,
+    ) -> ServiceResult<u64> {
+        let caller = call_context.must_not_anonymous()?;
+        validate_years(years)?;
+        let result = parse_leasing_name(name)?;
+        let name = result.get_name().clone();
+        try_lock_name(&name)?;
+        let renew_result = self
+            .renew_subdomain_lease_core(call_context, caller.0, &result, years, approve_amount)
+            .await;
+        unlock_name(&name);
+        renew_result
+    }
+
+    async fn renew_subdomain_lease_core(
+        &self,
+        call_context: CallContext,
+        caller: Principal,
+        result: &NameParseResult,
+        years: u32,
+        approve_amount: u64,
+    ) -> ServiceResult<u64> {
+        let name = result.get_name();
+        let parent_name = result.get_parent_name().unwrap();
+        let now = call_context.now.0;
+        let (parent_owner, amount, new_expired_at) = STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let lease_store = s.lease_store.borrow();
+            let lease = lease_store
+                .get_lease(name)
+                .filter(|lease| !lease.is_expired(now))
+                .ok_or_else(|| NamingError::LeaseNotFound {
+                    name: name.to_string(),
+                })?;
+            let registry = get_registry(&store, name)?;
+            if !registry.is_owner(&caller) {
+                return Err(NamingError::PermissionDenied);
+            }
+            let new_expired_at = lease.expired_at + years as u64 * NAMING_SUBDOMAIN_LEASE_YEAR_NS;
+            if new_expired_at
+                > now + NAMING_MAX_REGISTRATION_YEAR as u64 * NAMING_SUBDOMAIN_LEASE_YEAR_NS
+            {
+                return Err(NamingError::RenewalYearsError {
+                    years: NAMING_MAX_REGISTRATION_YEAR,
+                });
+            }
+            let parent = get_registry(&store, &parent_name)?;
+            let sale = lease_store.get_sale(&parent_name).ok_or_else(|| {
+                NamingError::SubdomainNotForSale {
+                    name: parent_name.clone(),
+                }
+            })?;
+            let amount = sale.get_price_per_year(result.get_name_len()) * years as u64;
+            Ok((*parent.get_owner(), amount, new_expired_at))
+        })?;
+        if approve_amount < amount {
+            return Err(NamingError::InvalidApproveAmount);
+        }
+
+        self.pay_parent_owner(caller, parent_owner, amount).await?;
+
+        STATE.with(|s| {
+            let mut lease_store = s.lease_store.borrow_mut();
+            lease_store.renew_lease(name, new_expired_at);
+        });
+        info!(
+            "renew_subdomain_lease: {} is renewed to {}",
+            name, new_expired_at
+        );
+        Ok(new_expired_at)
+    }
+
+    /// Remove subdomains which lease is expired, names under them are removed too.
+    pub async fn clean_expired_leases(&self, call_context: CallContext) -> ServiceResult<bool> {
+        call_context.must_be_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER)?;
+        let now = call_context.now;
+        if !try_lock_with_timeout(LockId::RegistryCleanExpiredLease, now) {
+            debug!("clean_expired_leases: already locked");
+            return Ok(false);
+        }
+        let leases = STATE.with(|s| {
+            let lease_store = s.lease_store.borrow();
+            lease_store.get_expired_leases(now.0, NAMING_CLEAN_EXPIRED_BATCH_SIZE)
+        });
+        debug!("clean_expired_leases: {} expired leases", leases.len());
+        for lease in leases {
+            if try_lock_name(&lease.name).is_err() {
+                continue;
+            }
+            let result = self.remove_leased_name(&lease.name).await;
+            unlock_name(&lease.name);
+            if let Err(e) = result {
+                error!("clean_expired_leases: {} failed: {:?}", lease.name, e);
+            }
+        }
+        release_timeout_locker(LockId::RegistryCleanExpiredLease);
+        Ok(true)
+    }
+
+    async fn remove_leased_name(&self, name: &str) -> ServiceResult<()> {
+        let removing_names = STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let mut removing_names = store.get_sub_names(name);
+            removing_names.push(name.to_string());
+            removing_names
+        });
+
+        // remove resolvers for current and sub names
+        self.resolver_api
+            .remove_resolvers(removing_names.clone())
+            .await?;
+
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            store.remove_names(&removing_names);
+            certify(&store, &removing_names);
+            let mut lease_store = s.lease_store.borrow_mut();
+            lease_store.remove_names(&removing_names);
+        });
+        info!(
+            "remove_leased_name: removed registries for names: {:?}",
+            &removing_names
+        );
+        Ok(())
+    }
+
+    /// Subdomains are free when the price is 0 or the owner of the parent leases them to itself
+    async fn pay_parent_owner(
+        &self,
+        from: Principal,
+        parent_owner: Principal,
+        amount: u64,
+    ) -> ServiceResult<()> {
+        if amount == 0 || from == parent_owner {
+            return Ok(());
+        }
+        let result = self
+            .dicp_api
+            .transfer_from(
+                None,
+                from.to_text(),
+                parent_owner.to_text(),
+                Nat::from(amount),
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            error!(
+                "pay_parent_owner: transfer from {} to {} failed: {:?}",
+                from, parent_owner, e
+            );
+            return Err(NamingError::RemoteError(e));
+        }
+        Ok(())
+    }
+}
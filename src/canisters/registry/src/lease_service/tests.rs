@@ -0,0 +1,522 @@
+use std::collections::HashSet;
+
+use candid::Principal;
+use rstest::*;
+
+use common::canister_api::TransactionResponse;
+use common::errors::ErrorInfo;
+use common::named_principals::NAME_DPRINCIPALS;
+use common::TimeInNs;
+use test_common::canister_api::*;
+use test_common::create_test_name;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use crate::lease_store::SubdomainPriceItem;
+use crate::service::RegistriesService;
+
+use super::*;
+
+const PRICE_PER_YEAR: u64 = 100_000_000;
+
+fn set_named_principal(name: &'static str, user: Principal) {
+    NAME_DPRINCIPALS.with(|m| {
+        let mut m = m.borrow_mut();
+        let mut set = HashSet::new();
+        set.insert(user);
+        m.principals.insert(name, set);
+    });
+}
+
+fn create_service(dicp_api: MockDICPApi, resolver_api: MockResolverApi) -> SubdomainLeaseService {
+    SubdomainLeaseService {
+        dicp_api: Arc::new(dicp_api),
+        resolver_api: Arc::new(resolver_api),
+    }
+}
+
+fn add_registries(names: &[&str], owner: Principal) {
+    STATE.with(|s| {
+        let mut store = s.registry_store.borrow_mut();
+        for name in names.iter() {
+            store.add_registry(Registry::new(
+                create_test_name(name),
+                owner,
+                DEFAULT_TTL,
+                get_named_get_canister_id(CanisterNames::Resolver),
+            ));
+        }
+    });
+}
+
+fn get_test_registry(name: &str) -> Option<Registry> {
+    STATE.with(|s| {
+        let store = s.registry_store.borrow();
+        store.get_registry(&create_test_name(name))
+    })
+}
+
+fn add_lease(name: &str, created_at: u64, expired_at: u64) {
+    let name = create_test_name(name);
+    let parent_name = name.split_once('.').unwrap().1.to_string();
+    STATE.with(|s| {
+        let mut lease_store = s.lease_store.borrow_mut();
+        lease_store.add_lease(SubdomainLease {
+            name,
+            parent_name,
+            created_at,
+            expired_at,
+        });
+    });
+}
+
+/// `nice` is owned by user1 and its subdomains are on sale,
+/// 2 chars labels are sold for 5 times of the default price
+#[fixture]
+fn nice_on_sale(_init_test: (), mock_user1: Principal) {
+    add_registries(&["nice"], mock_user1);
+    STATE.with(|s| {
+        let mut lease_store = s.lease_store.borrow_mut();
+        lease_store.set_sale(
+            create_test_name("nice"),
+            SubdomainSale {
+                price_per_year: PRICE_PER_YEAR,
+                price_table: vec![SubdomainPriceItem {
+                    len: 2,
+                    price_per_year: PRICE_PER_YEAR * 5,
+                }],
+            },
+        );
+    });
+}
+
+mod sale {
+    use super::*;
+
+    #[rstest]
+    fn test_set_subdomain_sale(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_now: u64,
+        mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["nice"], mock_user1);
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+        let sale = SubdomainSale {
+            price_per_year: 0,
+            price_table: vec![],
+        };
+
+        let result = service.set_subdomain_sale(
+            CallContext::new(mock_user1, TimeInNs(mock_now)),
+            &create_test_name("nice"),
+            sale.clone(),
+        );
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            service.get_subdomain_sale(&create_test_name("nice")),
+            Ok(sale)
+        );
+    }
+
+    #[rstest]
+    fn test_set_subdomain_sale_not_owner(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service.set_subdomain_sale(
+            CallContext::new(mock_user2, TimeInNs(mock_now)),
+            &create_test_name("nice"),
+            SubdomainSale::default(),
+        );
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    fn test_get_subdomain_price(
+        _nice_on_sale: (),
+        mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        assert_eq!(
+            service.get_subdomain_price(&create_test_name("ab.nice"), 2),
+            Ok(PRICE_PER_YEAR * 10)
+        );
+        assert_eq!(
+            service.get_subdomain_price(&create_test_name("abc.nice"), 2),
+            Ok(PRICE_PER_YEAR * 2)
+        );
+        assert!(matches!(
+            service.get_subdomain_price(&create_test_name("abc.hello"), 2),
+            Err(NamingError::SubdomainNotForSale { .. })
+        ));
+    }
+}
+
+mod lease {
+    use super::*;
+
+    #[rstest]
+    async fn test_lease_subdomain(
+        _nice_on_sale: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(move |_, from, to, value, _| {
+                assert_eq!(from, mock_user2.to_text());
+                assert_eq!(to, mock_user1.to_text());
+                assert_eq!(value, Nat::from(PRICE_PER_YEAR * 2));
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        mock_resolver_api
+            .expect_ensure_resolver_created()
+            .times(1)
+            .returning(|_| Ok(true));
+        mock_resolver_api
+            .expect_set_ttl()
+            .times(1)
+            .returning(|name, ttl| {
+                assert_eq!(name, create_test_name("alice.nice"));
+                assert_eq!(ttl, DEFAULT_TTL);
+                Ok(true)
+            });
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                2,
+                PRICE_PER_YEAR * 2,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.owner, mock_user2);
+        let lease = service
+            .get_subdomain_lease(&create_test_name("alice.nice"))
+            .unwrap();
+        assert_eq!(lease.created_at, mock_now);
+        assert_eq!(
+            lease.expired_at,
+            mock_now + 2 * NAMING_SUBDOMAIN_LEASE_YEAR_NS
+        );
+    }
+
+    #[rstest]
+    async fn test_lease_subdomain_free(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["nice"], mock_user1);
+        mock_dicp_api.expect_transfer_from().never();
+        mock_resolver_api
+            .expect_ensure_resolver_created()
+            .returning(|_| Ok(true));
+        mock_resolver_api
+            .expect_set_ttl()
+            .returning(|_, _| Ok(true));
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+        service
+            .set_subdomain_sale(
+                CallContext::new(mock_user1, TimeInNs(mock_now)),
+                &create_test_name("nice"),
+                SubdomainSale::default(),
+            )
+            .unwrap();
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                0,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            get_test_registry("alice.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+    }
+
+    #[rstest]
+    async fn test_lease_subdomain_approve_amount_too_low(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("ab.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        assert_eq!(result, Err(NamingError::InvalidApproveAmount));
+        assert!(get_test_registry("ab.nice").is_none());
+    }
+
+    #[rstest]
+    async fn test_lease_subdomain_already_exists(
+        _nice_on_sale: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+        mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["alice.nice"], mock_user1);
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(NamingError::RegistryAlreadyExists { .. })
+        ));
+    }
+
+    #[rstest]
+    async fn test_lease_subdomain_payment_failed(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| Err(ErrorInfo::from(NamingError::Unknown)));
+        mock_resolver_api.expect_ensure_resolver_created().never();
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert!(get_test_registry("alice.nice").is_none());
+        assert!(service
+            .get_subdomain_lease(&create_test_name("alice.nice"))
+            .is_err());
+    }
+
+    #[rstest]
+    async fn test_lease_subdomain_expired_lease(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["alice.nice", "www.alice.nice"], mock_user3);
+        add_lease("alice.nice", mock_now - 2, mock_now - 1);
+        mock_dicp_api
+            .expect_transfer_from()
+            .returning(|_, _, _, _, _| {
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .times(1)
+            .returning(|names| {
+                assert_eq!(names.len(), 2);
+                Ok(true)
+            });
+        mock_resolver_api
+            .expect_ensure_resolver_created()
+            .returning(|_| Ok(true));
+        mock_resolver_api
+            .expect_set_ttl()
+            .returning(|_, _| Ok(true));
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .lease_subdomain(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            get_test_registry("alice.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+        assert!(get_test_registry("www.alice.nice").is_none());
+    }
+
+    #[rstest]
+    async fn test_renew_subdomain_lease(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["alice.nice"], mock_user2);
+        add_lease("alice.nice", mock_now, mock_now + 1);
+        mock_dicp_api
+            .expect_transfer_from()
+            .times(1)
+            .returning(|_, _, _, value, _| {
+                assert_eq!(value, Nat::from(PRICE_PER_YEAR));
+                Ok(TransactionResponse {
+                    tx_id: "1".to_string(),
+                })
+            });
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .renew_subdomain_lease(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        let expired_at = mock_now + 1 + NAMING_SUBDOMAIN_LEASE_YEAR_NS;
+        assert_eq!(result, Ok(expired_at));
+        assert_eq!(
+            service
+                .get_subdomain_lease(&create_test_name("alice.nice"))
+                .unwrap()
+                .expired_at,
+            expired_at
+        );
+    }
+
+    #[rstest]
+    async fn test_renew_subdomain_lease_expired(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_now: u64,
+        mut mock_dicp_api: MockDICPApi,
+        mock_resolver_api: MockResolverApi,
+    ) {
+        add_registries(&["alice.nice"], mock_user2);
+        add_lease("alice.nice", mock_now - 2, mock_now - 1);
+        mock_dicp_api.expect_transfer_from().never();
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .renew_subdomain_lease(
+                CallContext::new(mock_user2, TimeInNs(mock_now)),
+                &create_test_name("alice.nice"),
+                1,
+                PRICE_PER_YEAR,
+            )
+            .await;
+
+        assert!(matches!(result, Err(NamingError::LeaseNotFound { .. })));
+    }
+}
+
+mod lifecycle {
+    use super::*;
+
+    #[rstest]
+    async fn test_clean_expired_leases(
+        _nice_on_sale: (),
+        mock_user2: Principal,
+        mock_user3: Principal,
+        mock_now: u64,
+        mock_dicp_api: MockDICPApi,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        set_named_principal(PRINCIPAL_NAME_TIMER_TRIGGER, mock_user3);
+        add_registries(&["alice.nice", "bob.nice"], mock_user2);
+        add_lease("alice.nice", mock_now - 2, mock_now - 1);
+        add_lease("bob.nice", mock_now - 2, mock_now + 1);
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .times(1)
+            .returning(|names| {
+                assert_eq!(names, vec![create_test_name("alice.nice")]);
+                Ok(true)
+            });
+        let service = create_service(mock_dicp_api, mock_resolver_api);
+
+        let result = service
+            .clean_expired_leases(CallContext::new(mock_user3, TimeInNs(mock_now)))
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert!(get_test_registry("alice.nice").is_none());
+        assert!(get_test_registry("bob.nice").is_some());
+        assert!(service
+            .get_subdomain_lease(&create_test_name("alice.nice"))
+            .is_err());
+    }
+
+    #[rstest]
+    async fn test_parent_can_not_revoke_leased_subdomain(
+        _nice_on_sale: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_now: u64,
+    ) {
+        add_registries(&["alice.nice"], mock_user2);
+        add_lease("alice.nice", mock_now, mock_now + 1);
+        let mut service = RegistriesService::new();
+
+        let result = service
+            .revoke_subdomain(&mock_user1, &create_test_name("alice.nice"))
+            .await;
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+
+        let result = service
+            .transfer_subdomain(&mock_user1, &create_test_name("alice.nice"), mock_user1)
+            .await;
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+}
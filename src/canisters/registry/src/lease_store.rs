@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize};
+
+use common::errors::{NamingError, ServiceResult};
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+
+use crate::state::{SUBDOMAIN_LEASES_MEMORY_ID, SUBDOMAIN_SALES_MEMORY_ID};
+
+#[cfg(test)]
+mod tests;
+
+/// Yearly price of subdomains whose label has the length
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SubdomainPriceItem {
+    pub len: u8,
+    pub price_per_year: u64,
+}
+
+/// Subdomains of a name for sale, set by the owner of the name
+#[derive(CandidType, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SubdomainSale {
+    /// Yearly price in DICP e8s of subdomains whose label length is not in price table,
+    /// 0 to give subdomains away for free
+    pub price_per_year: u64,
+    pub price_table: Vec<SubdomainPriceItem>,
+}
+
+impl SubdomainSale {
+    pub fn validate(&self) -> ServiceResult<()> {
+        for (index, item) in self.price_table.iter().enumerate() {
+            if item.len == 0 {
+                return Err(NamingError::InvalidSubdomainSale {
+                    reason: "len of price item must be greater than 0".to_string(),
+                });
+            }
+            if self.price_table[..index]
+                .iter()
+                .any(|other| other.len == item.len)
+            {
+                return Err(NamingError::InvalidSubdomainSale {
+                    reason: format!("duplicated len {} in price table", item.len),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_price_per_year(&self, label_len: u8) -> u64 {
+        self.price_table
+            .iter()
+            .find(|item| item.len == label_len)
+            .map(|item| item.price_per_year)
+            .unwrap_or(self.price_per_year)
+    }
+}
+
+/// Subdomain leased from the owner of its parent
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SubdomainLease {
+    pub name: String,
+    pub parent_name: String,
+    pub created_at: u64,
+    pub expired_at: u64,
+}
+
+impl SubdomainLease {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expired_at <= now
+    }
+}
+
+/// Sales and leases are kept in stable memory, so that they are not serialized on upgrade.
+pub struct LeaseStore {
+    /// parent name -> sale
+    sales: StableMap<String, SubdomainSale>,
+    /// subdomain name -> lease
+    leases: StableMap<String, SubdomainLease>,
+}
+
+impl Default for LeaseStore {
+    fn default() -> Self {
+        LeaseStore {
+            sales: StableMap::init(SUBDOMAIN_SALES_MEMORY_ID),
+            leases: StableMap::init(SUBDOMAIN_LEASES_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for LeaseStore {
+    type Entries = (
+        HashMap<String, SubdomainSale>,
+        HashMap<String, SubdomainLease>,
+    );
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.sales.iter().collect(), self.leases.iter().collect())
+    }
+
+    fn import_entries(&mut self, (sales, leases): Self::Entries) {
+        self.sales.clear();
+        self.leases.clear();
+        for (parent_name, sale) in sales {
+            self.sales.insert(parent_name, sale);
+        }
+        for (name, lease) in leases {
+            self.leases.insert(name, lease);
+        }
+    }
+}
+
+impl LeaseStore {
+    pub fn set_sale(&mut self, parent_name: String, sale: SubdomainSale) {
+        self.sales.insert(parent_name, sale);
+    }
+
+    pub fn remove_sale(&mut self, parent_name: &str) -> Option<SubdomainSale> {
+        self.sales.remove(&parent_name.to_string())
+    }
+
+    pub fn get_sale(&self, parent_name: &str) -> Option<SubdomainSale> {
+        self.sales.get(&parent_name.to_string())
+    }
+
+    pub fn add_lease(&mut self, lease: SubdomainLease) {
+        self.leases.insert(lease.name.clone(), lease);
+    }
+
+    pub fn get_lease(&self, name: &str) -> Option<SubdomainLease> {
+        self.leases.get(&name.to_string())
+    }
+
+    pub fn has_lease(&self, name: &str) -> bool {
+        self.leases.contains_key(&name.to_string())
+    }
+
+    pub fn renew_lease(&mut self, name: &str, expired_at: u64) {
+        if let Some(mut lease) = self.get_lease(name) {
+            lease.expired_at = expired_at;
+            self.add_lease(lease);
+        }
+    }
+
+    pub fn get_expired_leases(&self, now: u64, limit: usize) -> Vec<SubdomainLease> {
+        self.leases
+            .values()
+            .filter(|lease| lease.is_expired(now))
+            .take(limit)
+            .collect()
+    }
+
+    /// Returns true if the name is under a leased subdomain of root,
+    /// such name is kept when root is reset.
+    pub fn is_protected_from(&self, name: &str, root: &str) -> bool {
+        let end_parts = format!(".{}", root);
+        let mut current = name;
+        while current.ends_with(end_parts.as_str()) {
+            if self.has_lease(current) {
+                return true;
+            }
+            match current.split_once('.') {
+                Some((_, parent)) => current = parent,
+                None => break,
+            }
+        }
+        false
+    }
+
+    /// Remove sales and leases of names removed from registries
+    pub fn remove_names(&mut self, names: &Vec<String>) {
+        for name in names {
+            self.sales.remove(name);
+            self.leases.remove(name);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use rstest::*;
+use test_common::user::*;
+
+use super::*;
+
+#[fixture]
+fn sale() -> SubdomainSale {
+    SubdomainSale {
+        price_per_year: 100,
+        price_table: vec![
+            SubdomainPriceItem {
+                len: 1,
+                price_per_year: 1_000,
+            },
+            SubdomainPriceItem {
+                len: 2,
+                price_per_year: 500,
+            },
+        ],
+    }
+}
+
+#[rstest]
+fn test_get_price_per_year(sale: SubdomainSale) {
+    assert_eq!(sale.validate(), Ok(()));
+    assert_eq!(sale.get_price_per_year(1), 1_000);
+    assert_eq!(sale.get_price_per_year(2), 500);
+    assert_eq!(sale.get_price_per_year(3), 100);
+}
+
+#[rstest]
+fn test_validate_duplicated_len(mut sale: SubdomainSale) {
+    sale.price_table.push(SubdomainPriceItem {
+        len: 2,
+        price_per_year: 1,
+    });
+    assert!(matches!(
+        sale.validate(),
+        Err(NamingError::InvalidSubdomainSale { .. })
+    ));
+}
+
+#[rstest]
+fn test_get_expired_leases_and_remove_names(mock_now: u64, sale: SubdomainSale) {
+    let mut store = LeaseStore::default();
+    store.set_sale("a.ic".to_string(), sale);
+    for (name, expired_at) in [("a.a.ic", mock_now), ("b.a.ic", mock_now + 1)] {
+        store.add_lease(SubdomainLease {
+            name: name.to_string(),
+            parent_name: "a.ic".to_string(),
+            created_at: mock_now - 1,
+            expired_at,
+        });
+    }
+
+    let expired = store.get_expired_leases(mock_now, 10);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].name, "a.a.ic");
+
+    assert!(store.is_protected_from("www.a.a.ic", "a.ic"));
+    assert!(!store.is_protected_from("www.c.a.ic", "a.ic"));
+    assert!(!store.is_protected_from("a.a.ic", "a.a.ic"));
+
+    store.remove_names(&vec!["a.ic".to_string(), "a.a.ic".to_string()]);
+    assert!(store.get_sale("a.ic").is_none());
+    assert!(!store.has_lease("a.a.ic"));
+    assert!(store.has_lease("b.a.ic"));
+}
@@ -1,4 +1,8 @@
+mod certification;
 mod http;
+mod lease_service;
+mod lease_store;
+mod name_locker;
 mod registry_store;
 mod service;
 mod state;
@@ -9,14 +13,18 @@ mod stats_service;
 
 use crate::state::InitArgs;
 use candid::{candid_method, CandidType, Principal};
+use common::certified_map::CertifiedValue;
 use common::dto::*;
 use common::errors::{BooleanActorResponse, ErrorInfo, NamingError, ServiceResult};
 use common::http::*;
 use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+use common::CallContext;
 use ic_cdk_macros::*;
 use stats_service::*;
 use std::collections::HashMap;
 
+use crate::lease_service::SubdomainLeaseService;
+use crate::lease_store::{SubdomainLease, SubdomainSale};
 use crate::service::RegistriesService;
 
 /// Set owner of subdomain
@@ -64,18 +72,267 @@ impl SetSubdomainOwnerResponse {
     }
 }
 
-/// Set full info of subdomain
+/// Create a subdomain under a name owned by caller
+/// Returns details of the new registry
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `sub_owner` - owner of subdomain
+/// * `fuses` - fuses to burn, e.g. 1 to give up the control of the subdomain
+#[update(name = "create_subdomain")]
+#[candid_method(update)]
+async fn create_subdomain(
+    name: String,
+    sub_owner: Principal,
+    fuses: u32,
+) -> SetSubdomainOwnerResponse {
+    let caller = &ic_cdk::api::caller();
+    let mut service = RegistriesService::new();
+    let result = service
+        .create_subdomain(caller, name.as_str(), sub_owner, fuses)
+        .await;
+    SetSubdomainOwnerResponse::new(result)
+}
+
+/// Transfer a subdomain, caller should be the owner of the subdomain,
+/// or the owner of its parent if the parent has not given up the control.
+/// Primary names of the subdomain set by the previous owner are reset in the resolver.
+/// Returns true if success
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `new_owner` - new owner of subdomain
+#[update(name = "transfer_subdomain")]
+#[candid_method(update)]
+async fn transfer_subdomain(name: String, new_owner: Principal) -> BooleanActorResponse {
+    let caller = &ic_cdk::api::caller();
+    let mut service = RegistriesService::new();
+    let result = service
+        .transfer_subdomain(caller, name.as_str(), new_owner)
+        .await;
+    BooleanActorResponse::new(result)
+}
+
+/// Revoke a subdomain and names under it, caller should be the owner of its parent
+/// and the parent has not given up the control.
+/// Returns true if success
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+#[update(name = "revoke_subdomain")]
+#[candid_method(update)]
+async fn revoke_subdomain(name: String) -> BooleanActorResponse {
+    let caller = &ic_cdk::api::caller();
+    let mut service = RegistriesService::new();
+    let result = service.revoke_subdomain(caller, name.as_str()).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Burn fuses of a subdomain, caller should be the owner of its parent.
 /// Returns true if success
 ///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `fuses` - fuses to burn, e.g. 1 to give up the control of the subdomain
+#[update(name = "burn_subdomain_fuses")]
+#[candid_method(update)]
+fn burn_subdomain_fuses(name: String, fuses: u32) -> BooleanActorResponse {
+    let caller = &ic_cdk::api::caller();
+    let mut service = RegistriesService::new();
+    let result = service.burn_subdomain_fuses(caller, name.as_str(), fuses);
+    BooleanActorResponse::new(result)
+}
+
+/// Get fuses burned of a subdomain
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+#[query(name = "get_fuses")]
+#[candid_method(query)]
+fn get_fuses(name: String) -> GetFusesResponse {
+    let service = RegistriesService::new();
+    let result = service.get_fuses(name.as_str());
+    GetFusesResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetFusesResponse {
+    Ok(u32),
+    Err(ErrorInfo),
+}
+
+impl GetFusesResponse {
+    pub fn new(result: ServiceResult<u32>) -> GetFusesResponse {
+        match result {
+            Ok(data) => GetFusesResponse::Ok(data),
+            Err(err) => GetFusesResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Put subdomains of a name on sale, caller should be the owner of the name.
+/// Subdomains are leased to anyone who pays the owner of the name.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `sale` - yearly prices of subdomains, 0 to give subdomains away for free
+#[update(name = "set_subdomain_sale")]
+#[candid_method(update)]
+fn set_subdomain_sale(name: String, sale: SubdomainSale) -> BooleanActorResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.set_subdomain_sale(CallContext::from_ic(), name.as_str(), sale);
+    BooleanActorResponse::new(result)
+}
+
+/// Stop selling subdomains of a name, leased subdomains are not affected.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[update(name = "remove_subdomain_sale")]
+#[candid_method(update)]
+fn remove_subdomain_sale(name: String) -> BooleanActorResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.remove_subdomain_sale(CallContext::from_ic(), name.as_str());
+    BooleanActorResponse::new(result)
+}
+
+/// Get sale of subdomains of a name
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_subdomain_sale")]
+#[candid_method(query)]
+fn get_subdomain_sale(name: String) -> GetSubdomainSaleResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.get_subdomain_sale(name.as_str());
+    GetSubdomainSaleResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetSubdomainSaleResponse {
+    Ok(SubdomainSale),
+    Err(ErrorInfo),
+}
+
+impl GetSubdomainSaleResponse {
+    pub fn new(result: ServiceResult<SubdomainSale>) -> GetSubdomainSaleResponse {
+        match result {
+            Ok(data) => GetSubdomainSaleResponse::Ok(data),
+            Err(err) => GetSubdomainSaleResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Get price in DICP e8s to lease or renew a subdomain
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `years` - years to lease
+#[query(name = "get_subdomain_price")]
+#[candid_method(query)]
+fn get_subdomain_price(name: String, years: u32) -> GetSubdomainPriceResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.get_subdomain_price(name.as_str(), years);
+    GetSubdomainPriceResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetSubdomainPriceResponse {
+    Ok(u64),
+    Err(ErrorInfo),
+}
+
+impl GetSubdomainPriceResponse {
+    pub fn new(result: ServiceResult<u64>) -> GetSubdomainPriceResponse {
+        match result {
+            Ok(data) => GetSubdomainPriceResponse::Ok(data),
+            Err(err) => GetSubdomainPriceResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Lease a subdomain from the owner of its parent, DICP should be approved to registry before calling.
+/// Returns details of the new registry
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `years` - years to lease
+/// * `approve_amount` - DICP e8s approved, it should not be less than the price
+#[update(name = "lease_subdomain")]
+#[candid_method(update)]
+async fn lease_subdomain(
+    name: String,
+    years: u32,
+    approve_amount: u64,
+) -> SetSubdomainOwnerResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service
+        .lease_subdomain(CallContext::from_ic(), name.as_str(), years, approve_amount)
+        .await;
+    SetSubdomainOwnerResponse::new(result)
+}
+
+/// Renew the lease of a subdomain, caller should be the owner of the subdomain.
+/// Returns new expiration time of the lease in ns
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+/// * `years` - years to renew
+/// * `approve_amount` - DICP e8s approved, it should not be less than the price
+#[update(name = "renew_subdomain_lease")]
+#[candid_method(update)]
+async fn renew_subdomain_lease(
+    name: String,
+    years: u32,
+    approve_amount: u64,
+) -> GetSubdomainPriceResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service
+        .renew_subdomain_lease(CallContext::from_ic(), name.as_str(), years, approve_amount)
+        .await;
+    GetSubdomainPriceResponse::new(result)
+}
+
+/// Get lease of a subdomain
+///
+/// * `name` - name of subdomain. e.g. `www.hello.ic`
+#[query(name = "get_subdomain_lease")]
+#[candid_method(query)]
+fn get_subdomain_lease(name: String) -> GetSubdomainLeaseResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.get_subdomain_lease(name.as_str());
+    GetSubdomainLeaseResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetSubdomainLeaseResponse {
+    Ok(SubdomainLease),
+    Err(ErrorInfo),
+}
+
+impl GetSubdomainLeaseResponse {
+    pub fn new(result: ServiceResult<SubdomainLease>) -> GetSubdomainLeaseResponse {
+        match result {
+            Ok(data) => GetSubdomainLeaseResponse::Ok(data),
+            Err(err) => GetSubdomainLeaseResponse::Err(err.into()),
+        }
+    }
+}
+
+#[update(name = "run_tasks")]
+#[candid_method(update)]
+async fn run_tasks() -> BooleanActorResponse {
+    let service = SubdomainLeaseService::default();
+    let result = service.clean_expired_leases(CallContext::from_ic()).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Set full info of subdomain
+/// Returns true if success. The ttl is synced to the default resolver, an error is returned if it fails,
+/// and the record is saved so that it can be set again.
+///
 /// * `name` - a name. e.g. `hello.ic`
 /// * `ttl` - ttl of name
 /// * `resolver` - resolver of name
 #[update(name = "set_record")]
 #[candid_method(update)]
-fn set_record(name: String, ttl: u64, resolver: Principal) -> BooleanActorResponse {
+async fn set_record(name: String, ttl: u64, resolver: Principal) -> BooleanActorResponse {
     let caller = ic_cdk::api::caller();
     let mut service = RegistriesService::new();
-    let result = service.set_record(&caller, name.as_str(), ttl, &resolver);
+    let result = service
+        .set_record(&caller, name.as_str(), ttl, &resolver)
+        .await;
     BooleanActorResponse::new(result)
 }
 
@@ -105,6 +362,35 @@ impl GetResolverResponse {
     }
 }
 
+/// Get resolver with a certificate.
+/// Returns the resolver, the witness of `resolver/{name}` and the certificate.
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_resolver_certified")]
+#[candid_method(query)]
+fn get_resolver_certified(name: String) -> GetCertifiedPrincipalResponse {
+    let service = RegistriesService::new();
+    let result = service
+        .get_resolver_certified(&name)
+        .map(|value| value.with_certificate());
+    GetCertifiedPrincipalResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetCertifiedPrincipalResponse {
+    Ok(CertifiedValue<Principal>),
+    Err(ErrorInfo),
+}
+
+impl GetCertifiedPrincipalResponse {
+    pub fn new(result: ServiceResult<CertifiedValue<Principal>>) -> GetCertifiedPrincipalResponse {
+        match result {
+            Ok(data) => GetCertifiedPrincipalResponse::Ok(data),
+            Err(err) => GetCertifiedPrincipalResponse::Err(err.into()),
+        }
+    }
+}
+
 #[update(name = "set_resolver")]
 #[candid_method(update)]
 fn set_resolver(name: String, resolver: Principal) -> BooleanActorResponse {
@@ -162,6 +448,38 @@ impl GetControlledNamesResponse {
     }
 }
 
+/// Get names owned by owner, ordered by name
+///
+/// * `owner` - owner of names
+/// * `page` - `next_cursor` of the previous page, or none for the first page, and limit
+#[query(name = "get_controlled_names_by_cursor")]
+#[candid_method(query)]
+fn get_controlled_names_by_cursor(
+    owner: Principal,
+    page: GetCursorPageInput,
+) -> GetControlledNamesByCursorResponse {
+    let service = RegistriesService::new();
+    let result = service.get_controlled_names_by_cursor(owner, page);
+    GetControlledNamesByCursorResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetControlledNamesByCursorResponse {
+    Ok(GetCursorPageOutput<String>),
+    Err(ErrorInfo),
+}
+
+impl GetControlledNamesByCursorResponse {
+    pub fn new(
+        result: ServiceResult<GetCursorPageOutput<String>>,
+    ) -> GetControlledNamesByCursorResponse {
+        match result {
+            Ok(data) => GetControlledNamesByCursorResponse::Ok(data),
+            Err(err) => GetControlledNamesByCursorResponse::Err(err.into()),
+        }
+    }
+}
+
 #[query(name = "get_controlled_names_count")]
 #[candid_method(query)]
 fn get_controlled_names_count(owner: Principal) -> GetControlledNamesCountResponse {
@@ -236,12 +554,32 @@ impl GetOwnerResponse {
     }
 }
 
+/// Get owner of name with a certificate.
+/// Returns the owner, the witness of `owner/{name}` and the certificate.
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_owner_certified")]
+#[candid_method(query)]
+fn get_owner_certified(name: String) -> GetCertifiedPrincipalResponse {
+    let service = RegistriesService::new();
+    let result = service
+        .get_owner_certified(&name)
+        .map(|value| value.with_certificate());
+    GetCertifiedPrincipalResponse::new(result)
+}
+
+/// Set the owner of the name, caller should be the owner of the name.
+/// Primary names of the name set by the previous owner are reset in the resolver.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `new_owner` - new owner of the name
 #[update(name = "set_owner")]
 #[candid_method(update)]
-fn set_owner(name: String, new_owner: Principal) -> BooleanActorResponse {
+async fn set_owner(name: String, new_owner: Principal) -> BooleanActorResponse {
     let caller = ic_cdk::api::caller();
     let service = RegistriesService::new();
-    let result = service.set_owner(caller, name.as_str(), new_owner);
+    let result = service.set_owner(caller, name.as_str(), new_owner).await;
     BooleanActorResponse::new(result)
 }
 
@@ -319,6 +657,20 @@ async fn transfer(name: String, new_owner: Principal, resolver: Principal) -> Bo
     BooleanActorResponse::new(result)
 }
 
+/// Remove name and all sub names of it, only registrar is allowed to call it.
+/// Returns true if success
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[update(name = "remove_name")]
+#[candid_method(update)]
+async fn remove_name(name: String) -> BooleanActorResponse {
+    let caller = &ic_cdk::api::caller();
+
+    let mut service = RegistriesService::new();
+    let result = service.remove_name(name.as_str(), caller).await;
+    BooleanActorResponse::new(result)
+}
+
 candid::export_service!();
 
 #[query(name = "__get_candid_interface_tmp_hack")]
@@ -1,10 +1,21 @@
 use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize, Principal};
+use candid::{CandidType, Deserialize, Principal};
 
 use common::constants::DEFAULT_TTL;
 use common::dto::{IRegistryUsers, RegistryDto, RegistryUsers};
-use common::state::StableState;
+use common::stable_memory::{take_owned_names, OwnerNameKey, PrincipalKey, StableMap};
+use common::state::StableMemoryStore;
+
+use crate::state::{OWNER_NAMES_MEMORY_ID, OWNER_NAME_COUNTS_MEMORY_ID, REGISTRIES_MEMORY_ID};
+
+#[cfg(test)]
+mod tests;
+
+// parent of the subdomain can not transfer or revoke it anymore
+pub const FUSE_PARENT_CANNOT_CONTROL: u32 = 1;
+pub const ALL_FUSES: u32 = FUSE_PARENT_CANNOT_CONTROL;
 
 #[derive(CandidType, Deserialize, Debug, Clone)]
 pub struct Registry {
@@ -13,6 +24,8 @@ pub struct Registry {
     ttl: u64,
     resolver: Principal,
     operators: HashSet<Principal>,
+    /// fuses burned by parent, it is only used by subdomains
+    fuses: Option<u32>,
 }
 
 impl IRegistryUsers for Registry {
@@ -45,6 +58,7 @@ impl Default for Registry {
             ttl: DEFAULT_TTL,
             resolver: Principal::anonymous(),
             operators: HashSet::new(),
+            fuses: None,
         }
     }
 }
@@ -57,6 +71,7 @@ impl Registry {
             ttl,
             resolver,
             operators: HashSet::new(),
+            fuses: None,
         }
     }
     pub(crate) fn set_operators(&mut self, operators: HashSet<Principal>) {
@@ -92,6 +107,16 @@ impl Registry {
     pub fn remove_operator(&mut self, operator: &Principal) {
         self.operators.remove(operator);
     }
+    pub fn get_fuses(&self) -> u32 {
+        self.fuses.unwrap_or_default()
+    }
+    /// fuses can only be burned, they can not be restored
+    pub fn burn_fuses(&mut self, fuses: u32) {
+        self.fuses = Some(self.get_fuses() | fuses);
+    }
+    pub fn is_fuse_burned(&self, fuse: u32) -> bool {
+        self.get_fuses() & fuse == fuse
+    }
 
     pub(crate) fn get_users(&self) -> RegistryUsers {
         RegistryUsers {
@@ -101,66 +126,173 @@ impl Registry {
     }
 }
 
-#[derive(Default)]
+/// Registries are kept in stable memory, so that they are not serialized on upgrade.
+///
+/// Names of each owner are indexed in `owner_names`, and counted in `owner_name_counts`,
+/// both of them are updated whenever a registry is added, transferred or removed.
 pub struct RegistryStore {
-    registries: HashMap<String, Registry>,
+    registries: StableMap<String, Registry>,
+    owner_names: StableMap<OwnerNameKey, ()>,
+    owner_name_counts: StableMap<PrincipalKey, u32>,
 }
 
-impl StableState for RegistryStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.registries,)).unwrap()
+impl Default for RegistryStore {
+    fn default() -> Self {
+        RegistryStore {
+            registries: StableMap::init(REGISTRIES_MEMORY_ID),
+            owner_names: StableMap::init(OWNER_NAMES_MEMORY_ID),
+            owner_name_counts: StableMap::init(OWNER_NAME_COUNTS_MEMORY_ID),
+        }
     }
+}
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (registries,): (HashMap<String, Registry>,) = decode_args(&bytes).unwrap();
+impl StableMemoryStore for RegistryStore {
+    type Entries = (HashMap<String, Registry>,);
 
-        Ok(RegistryStore { registries })
+    fn export_entries(&self) -> Self::Entries {
+        (self.registries.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (registries,): Self::Entries) {
+        self.registries.clear();
+        self.owner_names.clear();
+        self.owner_name_counts.clear();
+        for (_, registry) in registries {
+            self.add_registry(registry);
+        }
     }
 }
 
 impl RegistryStore {
     pub fn new() -> Self {
-        RegistryStore {
-            registries: HashMap::new(),
-        }
+        RegistryStore::default()
     }
 
-    pub fn get_registries(&self) -> &HashMap<String, Registry> {
-        &self.registries
+    pub fn get_registry_count(&self) -> u64 {
+        self.registries.len()
     }
 
-    pub fn get_registries_mut(&mut self) -> &mut HashMap<String, Registry> {
-        &mut self.registries
+    /// All names, ordered by name
+    pub fn get_names(&self) -> Vec<String> {
+        self.registries.keys().collect()
     }
 
     pub fn get_sub_names(&self, parent_name: &str) -> Vec<String> {
         let end_parts = format!(".{}", parent_name);
-        let mut sub_names = vec![];
-        for (name, _) in self.registries.iter() {
-            if name.ends_with(end_parts.as_str()) {
-                sub_names.push(name.to_string());
-            }
-        }
-        sub_names
+        self.registries
+            .keys()
+            .filter(|name| name.ends_with(end_parts.as_str()))
+            .collect()
     }
 
     pub fn remove_names(&mut self, names: &Vec<String>) {
         for name in names {
-            self.registries.remove(name);
+            if let Some(registry) = self.registries.remove(name) {
+                self.remove_owner_name(registry.owner, name);
+            }
         }
     }
 
+    /// Add or replace the registry of the name, the owner index is updated if the owner is changed.
     pub fn add_registry(&mut self, registry: Registry) {
-        self.registries.insert(registry.name.clone(), registry);
+        let name = registry.name.clone();
+        let owner = registry.owner;
+        match self.registries.insert(name.clone(), registry) {
+            Some(old_registry) if old_registry.owner == owner => {}
+            Some(old_registry) => {
+                self.remove_owner_name(old_registry.owner, &name);
+                self.add_owner_name(owner, &name);
+            }
+            None => self.add_owner_name(owner, &name),
+        }
     }
 
-    pub fn get_registry(&self, name: &str) -> Option<&Registry> {
-        self.registries.get(name)
+    fn add_owner_name(&mut self, owner: Principal, name: &str) {
+        if self
+            .owner_names
+            .insert(OwnerNameKey::new(owner, name), ())
+            .is_none()
+        {
+            let owner = PrincipalKey(owner);
+            let count = self.owner_name_counts.get(&owner).unwrap_or_default();
+            self.owner_name_counts.insert(owner, count + 1);
+        }
+    }
+
+    fn remove_owner_name(&mut self, owner: Principal, name: &str) {
+        if self
+            .owner_names
+            .remove(&OwnerNameKey::new(owner, name))
+            .is_some()
+        {
+            let owner = PrincipalKey(owner);
+            match self.owner_name_counts.get(&owner).unwrap_or_default() {
+                0 | 1 => {
+                    self.owner_name_counts.remove(&owner);
+                }
+                count => {
+                    self.owner_name_counts.insert(owner, count - 1);
+                }
+            }
+        }
+    }
+
+    /// Names owned by the owner after the cursor, ordered by name
+    pub fn get_owned_names<'a>(
+        &'a self,
+        owner: &Principal,
+        cursor: Option<&str>,
+    ) -> impl Iterator<Item = String> + 'a {
+        take_owned_names(self.get_owner_name_entries(owner, cursor), *owner)
+    }
+
+    /// Entries of the owner index from the owner and the cursor, followed by entries of other owners
+    fn get_owner_name_entries(
+        &self,
+        owner: &Principal,
+        cursor: Option<&str>,
+    ) -> impl Iterator<Item = (OwnerNameKey, ())> + '_ {
+        let start = OwnerNameKey::start_after(*owner, cursor);
+        self.owner_names.range((start, Bound::Unbounded))
+    }
+
+    pub fn get_owned_name_count(&self, owner: &Principal) -> usize {
+        self.owner_name_counts
+            .get(&PrincipalKey(*owner))
+            .unwrap_or_default() as usize
+    }
+
+    pub fn get_registry(&self, name: &str) -> Option<Registry> {
+        self.registries.get(&name.to_string())
+    }
+
+    pub fn has_registry(&self, name: &str) -> bool {
+        self.registries.contains_key(&name.to_string())
+    }
+
+    /// Returns true if the name is under a subdomain of root which parent can not control,
+    /// such name is kept when root is reset.
+    pub fn is_protected_from(&self, name: &str, root: &str) -> bool {
+        let end_parts = format!(".{}", root);
+        let mut current = name;
+        while current.ends_with(end_parts.as_str()) {
+            if let Some(registry) = self.get_registry(current) {
+                if registry.is_fuse_burned(FUSE_PARENT_CANNOT_CONTROL) {
+                    return true;
+                }
+            }
+            match current.split_once('.') {
+                Some((_, parent)) => current = parent,
+                None => break,
+            }
+        }
+        false
     }
 
     pub fn update_owner(&mut self, name: &str, owner: Principal) {
-        if let Some(registry) = self.registries.get_mut(name) {
+        if let Some(mut registry) = self.get_registry(name) {
             registry.set_owner(owner);
+            self.add_registry(registry);
         }
     }
 }
@@ -0,0 +1,93 @@
+use std::cell::Cell;
+
+use rstest::*;
+
+use test_common::user::*;
+
+use super::*;
+
+const OWNER_NAME_COUNT: usize = 20;
+
+fn registry(name: &str, owner: Principal) -> Registry {
+    Registry::new(name.to_string(), owner, DEFAULT_TTL, Principal::anonymous())
+}
+
+fn get_names(store: &RegistryStore, owner: &Principal, cursor: Option<&str>) -> Vec<String> {
+    store.get_owned_names(owner, cursor).collect()
+}
+
+#[rstest]
+fn test_owner_index(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = RegistryStore::new();
+    store.add_registry(registry("b.ic", mock_user1));
+    store.add_registry(registry("a.ic", mock_user1));
+    store.add_registry(registry("c.ic", mock_user2));
+
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["a.ic", "b.ic"]);
+    assert_eq!(get_names(&store, &mock_user1, Some("a.ic")), vec!["b.ic"]);
+    assert_eq!(store.get_owned_name_count(&mock_user2), 1);
+
+    store.update_owner("a.ic", mock_user2);
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["b.ic"]);
+    assert_eq!(get_names(&store, &mock_user2, None), vec!["a.ic", "c.ic"]);
+
+    // replaced by a registry of another owner
+    store.add_registry(registry("b.ic", mock_user2));
+    assert!(get_names(&store, &mock_user1, None).is_empty());
+    assert_eq!(store.get_owned_name_count(&mock_user2), 3);
+
+    store.remove_names(&vec!["a.ic".to_string(), "c.ic".to_string()]);
+    assert_eq!(get_names(&store, &mock_user2, None), vec!["b.ic"]);
+}
+
+#[rstest]
+fn test_import_rebuilds_owner_index(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = RegistryStore::new();
+    store.add_registry(registry("a.ic", mock_user1));
+    store.add_registry(registry("b.ic", mock_user1));
+    let entries = store.export_entries();
+
+    store.add_registry(registry("c.ic", mock_user2));
+    store.import_entries(entries);
+
+    assert_eq!(store.get_registry_count(), 2);
+    assert_eq!(get_names(&store, &mock_user1, None), vec!["a.ic", "b.ic"]);
+    assert_eq!(store.get_owned_name_count(&mock_user1), 2);
+    assert_eq!(store.get_owned_name_count(&mock_user2), 0);
+}
+
+fn add_other_names(store: &mut RegistryStore, range: std::ops::Range<u32>) {
+    for i in range {
+        // 10 names of each user
+        let owner = mock_user(100 + i / 10);
+        store.add_registry(registry(&format!("name{}.ic", i), owner));
+    }
+}
+
+/// Entries of the owner index visited by the query of names of the owner
+fn count_visited_owner_entries(store: &RegistryStore, owner: &Principal) -> usize {
+    let visited = Cell::new(0);
+    let entries = store
+        .get_owner_name_entries(owner, None)
+        .inspect(|_| visited.set(visited.get() + 1));
+    assert_eq!(take_owned_names(entries, *owner).count(), OWNER_NAME_COUNT);
+    visited.get()
+}
+
+#[rstest]
+fn test_owner_queries_do_not_visit_other_names(mock_user1: Principal) {
+    let mut store = RegistryStore::new();
+    for i in 0..OWNER_NAME_COUNT {
+        store.add_registry(registry(&format!("owned{}.ic", i), mock_user1));
+    }
+    add_other_names(&mut store, 0..100);
+    let small = count_visited_owner_entries(&store, &mock_user1);
+
+    add_other_names(&mut store, 100..2_000);
+    let large = count_visited_owner_entries(&store, &mock_user1);
+
+    // at most the first entry of the next owner is visited besides names of the owner
+    assert!(small <= OWNER_NAME_COUNT + 1);
+    assert_eq!(large, small);
+    assert_eq!(store.get_owned_name_count(&mock_user1), OWNER_NAME_COUNT);
+}
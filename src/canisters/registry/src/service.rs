@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use candid::Principal;
@@ -7,49 +7,131 @@ use log::{debug, error, info};
 
 use common::canister_api::ic_impl::ResolverApi;
 use common::canister_api::IResolverApi;
+use common::certified_map::CertifiedValue;
 use common::constants::{DEFAULT_TTL, MAX_REGISTRY_OPERATOR_COUNT, NAMING_TOP_LABEL};
-use common::dto::{GetPageInput, GetPageOutput, IRegistryUsers, RegistryDto, RegistryUsers};
+use common::dto::{
+    GetCursorPageInput, GetCursorPageOutput, GetPageInput, GetPageOutput, IRegistryUsers,
+    RegistryDto, RegistryUsers,
+};
 use common::errors::{NamingError, ServiceResult};
-use common::named_canister_ids::CanisterNames;
+use common::named_canister_ids::{get_named_get_canister_id, is_named_canister_id, CanisterNames};
+use common::naming::{parse_subdomain_name, NameParseResult};
 
 use common::permissions::{must_be_named_canister, must_not_anonymous};
 
+use crate::certification::{certify, get_certified_value, get_owner_key, get_resolver_key};
+use crate::lease_store::LeaseStore;
+use crate::name_locker::must_not_locked;
 use crate::registry_store::*;
-use crate::state::STATE;
+use crate::state::{State, STATE};
 
 #[cfg(test)]
 mod tests;
 
+/// Keep ttl of the name in the resolver in sync, it is used by DNS answers.
+/// Only the default resolver is synced, a custom resolver keeps ttl by itself.
+pub(crate) async fn sync_ttl(
+    resolver_api: &dyn IResolverApi,
+    name: &str,
+    ttl: u64,
+    resolver: &Principal,
+) -> ServiceResult<()> {
+    if !is_named_canister_id(CanisterNames::Resolver, *resolver) {
+        debug!("sync_ttl: skip custom resolver {} of {}", resolver, name);
+        return Ok(());
+    }
+    resolver_api.set_ttl(name.to_string(), ttl).await?;
+    Ok(())
+}
+
 pub struct RegistriesService {
     pub resolver_api: Arc<dyn IResolverApi>,
 }
 
-pub fn get_registry<'a>(
-    registries: &'a HashMap<String, Registry>,
-    name: &str,
-) -> ServiceResult<&'a Registry> {
-    let registry = registries.get(name);
-    if let Some(registry) = registry {
-        Ok(registry)
-    } else {
-        Err(NamingError::RegistryNotFoundError {
+pub fn get_registry(store: &RegistryStore, name: &str) -> ServiceResult<Registry> {
+    store
+        .get_registry(name)
+        .ok_or_else(|| NamingError::RegistryNotFoundError {
             name: name.to_string(),
         })
+}
+
+/// Parent of a subdomain can not control it after the fuse is burned or the subdomain is leased
+fn must_be_controlled_by_parent(
+    store: &RegistryStore,
+    leases: &LeaseStore,
+    name: &NameParseResult,
+    caller: &Principal,
+) -> ServiceResult<()> {
+    let parent = get_registry(store, &name.get_parent_name().unwrap())?;
+    if !parent.is_owner(caller) {
+        return Err(NamingError::PermissionDenied);
+    }
+    let registry = get_registry(store, name.get_name())?;
+    if registry.is_fuse_burned(FUSE_PARENT_CANNOT_CONTROL) {
+        return Err(NamingError::FuseBurned);
+    }
+    if leases.has_lease(name.get_name()) {
+        return Err(NamingError::PermissionDenied);
+    }
+    Ok(())
+}
+
+/// Caller should be the owner of the subdomain, or the owner of its parent if the parent has not given up the control.
+fn must_control_subdomain(
+    state: &State,
+    name: &NameParseResult,
+    caller: &Principal,
+) -> ServiceResult<()> {
+    let store = state.registry_store.borrow();
+    let registry = get_registry(&store, name.get_name())?;
+    if !registry.is_owner(caller) {
+        let leases = state.lease_store.borrow();
+        must_be_controlled_by_parent(&store, &leases, name, caller)?;
     }
+    Ok(())
 }
 
-pub fn get_registry_mut<'a>(
-    registries: &'a mut HashMap<String, Registry>,
+/// Only the owner can set a new owner of the name
+fn validate_new_owner(
+    store: &RegistryStore,
+    caller: &Principal,
     name: &str,
-) -> ServiceResult<&'a mut Registry> {
-    let registry = registries.get_mut(name);
-    if let Some(registry) = registry {
-        Ok(registry)
-    } else {
-        Err(NamingError::RegistryNotFoundError {
-            name: name.to_string(),
-        })
+    owner: &Principal,
+) -> ServiceResult<()> {
+    let registry = get_registry(store, name)?;
+    let old_owner = registry.get_owner();
+    if old_owner != caller {
+        error!("{} is not the owner of {}", caller, name);
+        return Err(NamingError::PermissionDenied);
+    }
+    if old_owner == owner {
+        error!("{} is already the owner of {}", owner, name);
+        return Err(NamingError::InvalidOwner);
+    }
+    Ok(())
+}
+
+/// Parent can give up the control of a subdomain only if it can not be controlled by its own parent,
+/// otherwise the subdomain could be taken back through the grandparent.
+fn validate_fuses(parent: &Registry, parent_level_count: usize, fuses: u32) -> ServiceResult<()> {
+    if fuses & !ALL_FUSES != 0 {
+        return Err(NamingError::InvalidFuses {
+            reason: format!("unknown fuses {}", fuses & !ALL_FUSES),
+        });
+    }
+    if fuses & FUSE_PARENT_CANNOT_CONTROL != 0
+        && parent_level_count > 2
+        && !parent.is_fuse_burned(FUSE_PARENT_CANNOT_CONTROL)
+    {
+        return Err(NamingError::InvalidFuses {
+            reason: format!(
+                "parent {} can be controlled by its own parent",
+                parent.get_name()
+            ),
+        });
     }
+    Ok(())
 }
 
 impl RegistriesService {
@@ -62,8 +144,7 @@ impl RegistriesService {
     pub fn set_top_icp_name(&mut self, registrar: Principal) -> ServiceResult<bool> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            if registries.contains_key(NAMING_TOP_LABEL) {
+            if store.has_registry(NAMING_TOP_LABEL) {
                 Err(NamingError::TopNameAlreadyExists)
             } else {
                 Ok(true)
@@ -81,11 +162,12 @@ impl RegistriesService {
     fn set_top_name(&mut self, registry: Registry) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            if !registries.is_empty() {
+            if store.get_registry_count() > 0 {
                 return Err(NamingError::TopNameAlreadyExists);
             }
-            registries.insert(registry.get_name().to_string(), registry);
+            let name = registry.get_name().to_string();
+            store.add_registry(registry);
+            certify(&store, &[name]);
             Ok(true)
         })
     }
@@ -103,8 +185,7 @@ impl RegistriesService {
 
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, &parent_name)?;
+            let registry = get_registry(&store, &parent_name)?;
             if !registry.is_owner(&owner) {
                 Err(NamingError::PermissionDenied)
             } else {
@@ -116,22 +197,22 @@ impl RegistriesService {
         // find old registry
         let updated_registry = STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let old_registry = registries.get_mut(&subdomain_name);
-            if let Some(old_registry) = old_registry {
+            let old_registry = store.get_registry(&subdomain_name);
+            let updated_registry = if let Some(old_registry) = old_registry {
                 info!("old_registry: {:?}", old_registry);
                 // update owner of old registry
-                old_registry.set_owner(sub_owner);
-                old_registry.set_ttl(ttl);
-                old_registry.set_resolver(resolver);
-                old_registry.clone()
+                let mut updated_registry = old_registry;
+                updated_registry.set_owner(sub_owner);
+                updated_registry.set_ttl(ttl);
+                updated_registry.set_resolver(resolver);
+                updated_registry
             } else {
                 // create new registry
-                let new_registry = Registry::new(subdomain_name.clone(), sub_owner, ttl, resolver);
-                let subdomain_name = format!("{}.{}", label, parent_name);
-                registries.insert(subdomain_name, new_registry.clone());
-                new_registry
-            }
+                Registry::new(subdomain_name.clone(), sub_owner, ttl, resolver)
+            };
+            store.add_registry(updated_registry.clone());
+            certify(&store, &[subdomain_name.clone()]);
+            updated_registry
         });
 
         let result = self
@@ -139,27 +220,206 @@ impl RegistriesService {
             .ensure_resolver_created(subdomain_name.clone())
             .await;
         info!("ensure_resolver_created: {:?}", result);
+        let result = sync_ttl(&*self.resolver_api, &subdomain_name, ttl, &resolver).await;
+        info!("sync_ttl: {:?}", result);
         Ok(RegistryDto::from(&updated_registry))
     }
 
+    /// Create a subdomain under a name owned by caller, e.g. `www.hello.ic` under `hello.ic`
+    pub async fn create_subdomain(
+        &mut self,
+        caller: &Principal,
+        name: &str,
+        sub_owner: Principal,
+        fuses: u32,
+    ) -> ServiceResult<RegistryDto> {
+        must_not_anonymous(caller)?;
+        must_not_anonymous(&sub_owner)?;
+        let result =
+            parse_subdomain_name(name).map_err(|reason| NamingError::InvalidName { reason })?;
+        let name = result.get_name().clone();
+        let parent_name = result.get_parent_name().unwrap();
+        // the name may be in the middle of being leased
+        must_not_locked(&name)?;
+
+        let registry = STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            let parent = get_registry(&store, &parent_name)?;
+            if !parent.is_owner(caller) {
+                return Err(NamingError::PermissionDenied);
+            }
+            validate_fuses(&parent, result.get_level_count() - 1, fuses)?;
+            if store.has_registry(&name) {
+                return Err(NamingError::RegistryAlreadyExists { name: name.clone() });
+            }
+
+            let mut registry = Registry::new(
+                name.clone(),
+                sub_owner,
+                DEFAULT_TTL,
+                get_named_get_canister_id(CanisterNames::Resolver),
+            );
+            if fuses != 0 {
+                registry.burn_fuses(fuses);
+            }
+            store.add_registry(registry.clone());
+            certify(&store, &[name.clone()]);
+            Ok(registry)
+        })?;
+        info!("create_subdomain: {:?}", registry);
+
+        let result = self
+            .resolver_api
+            .ensure_resolver_created(name.clone())
+            .await;
+        info!("ensure_resolver_created: {:?}", result);
+        // the resolver may be created before with another ttl
+        let result = sync_ttl(
+            &*self.resolver_api,
+            &name,
+            registry.get_ttl(),
+            &registry.get_resolver(),
+        )
+        .await;
+        info!("sync_ttl: {:?}", result);
+        Ok(RegistryDto::from(&registry))
+    }
+
+    /// Transfer a subdomain, caller should be the owner of the subdomain,
+    /// or the owner of its parent if the parent has not given up the control.
+    pub async fn transfer_subdomain(
+        &mut self,
+        caller: &Principal,
+        name: &str,
+        new_owner: Principal,
+    ) -> ServiceResult<bool> {
+        must_not_anonymous(caller)?;
+        must_not_anonymous(&new_owner)?;
+        let result =
+            parse_subdomain_name(name).map_err(|reason| NamingError::InvalidName { reason })?;
+        let name = result.get_name();
+        STATE.with(|s| must_control_subdomain(s, &result, caller))?;
+
+        // primary names set by the previous owner should not be verified for the new owner
+        self.resolver_api
+            .reset_primary_names(vec![name.to_string()])
+            .await?;
+
+        STATE.with(|s| {
+            // the name may be changed while resetting primary names
+            must_control_subdomain(s, &result, caller)?;
+            let mut store = s.registry_store.borrow_mut();
+            let mut registry = get_registry(&store, name)?;
+            registry.set_owner(new_owner);
+            registry.set_operators(HashSet::new());
+            store.add_registry(registry);
+            info!(
+                "transfer_subdomain: {} is transferred to {}",
+                name, new_owner
+            );
+            certify(&store, &[name.to_string()]);
+            Ok(true)
+        })
+    }
+
+    /// Revoke a subdomain and names under it, caller should be the owner of its parent
+    /// and the parent has not given up the control.
+    pub async fn revoke_subdomain(
+        &mut self,
+        caller: &Principal,
+        name: &str,
+    ) -> ServiceResult<bool> {
+        must_not_anonymous(caller)?;
+        let result =
+            parse_subdomain_name(name).map_err(|reason| NamingError::InvalidName { reason })?;
+        let name = result.get_name();
+        let removing_names = STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let leases = s.lease_store.borrow();
+            must_be_controlled_by_parent(&store, &leases, &result, caller)?;
+            let mut removing_names = store.get_sub_names(name);
+            removing_names.push(name.to_string());
+            Ok(removing_names)
+        })?;
+
+        // remove resolvers for current and sub names
+        self.resolver_api
+            .remove_resolvers(removing_names.clone())
+            .await?;
+
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            store.remove_names(&removing_names);
+            s.lease_store.borrow_mut().remove_names(&removing_names);
+            certify(&store, &removing_names);
+            info!(
+                "revoke_subdomain: removed registries for names: {:?}",
+                &removing_names
+            );
+            Ok(true)
+        })
+    }
+
+    /// Burn fuses of a subdomain, caller should be the owner of its parent.
+    /// Fuses can not be restored once they are burned.
+    pub fn burn_subdomain_fuses(
+        &mut self,
+        caller: &Principal,
+        name: &str,
+        fuses: u32,
+    ) -> ServiceResult<bool> {
+        must_not_anonymous(caller)?;
+        let result =
+            parse_subdomain_name(name).map_err(|reason| NamingError::InvalidName { reason })?;
+        let name = result.get_name();
+        let parent_name = result.get_parent_name().unwrap();
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            let parent = get_registry(&store, &parent_name)?;
+            if !parent.is_owner(caller) {
+                return Err(NamingError::PermissionDenied);
+            }
+            validate_fuses(&parent, result.get_level_count() - 1, fuses)?;
+
+            let mut registry = get_registry(&store, name)?;
+            registry.burn_fuses(fuses);
+            store.add_registry(registry);
+            info!("burn_subdomain_fuses: {} burned {}", name, fuses);
+            Ok(true)
+        })
+    }
+
+    pub fn get_fuses(&self, name: &str) -> ServiceResult<u32> {
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let registry = get_registry(&store, name)?;
+            Ok(registry.get_fuses())
+        })
+    }
+
     pub fn check_exist(&self, name: &str) -> bool {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            registries.contains_key(name)
+            store.has_registry(name)
         })
     }
 
     pub fn get_resolver(&self, name: &str) -> ServiceResult<Principal> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, name)?;
+            let registry = get_registry(&store, name)?;
             Ok(registry.get_resolver())
         })
     }
 
-    pub fn set_record(
+    /// Resolver of the name with the witness of `resolver/{name}` in the certified map.
+    /// It is None if the name is not found.
+    pub fn get_resolver_certified(&self, name: &str) -> ServiceResult<CertifiedValue<Principal>> {
+        let resolver = self.get_resolver(name).ok();
+        Ok(get_certified_value(&get_resolver_key(name), resolver))
+    }
+
+    pub async fn set_record(
         &mut self,
         caller: &Principal,
         name: &str,
@@ -168,15 +428,20 @@ impl RegistriesService {
     ) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let registry = get_registry_mut(registries, name)?;
+            let mut registry = get_registry(&store, name)?;
             if !registry.can_operate(caller) {
                 return Err(NamingError::PermissionDenied);
             }
             registry.set_ttl(ttl);
             registry.set_resolver(*resolver);
+            store.add_registry(registry);
+            certify(&store, &[name.to_string()]);
             Ok(true)
-        })
+        })?;
+
+        // the record is saved, so it is fine to set it again if syncing ttl fails
+        sync_ttl(&*self.resolver_api, name, ttl, resolver).await?;
+        Ok(true)
     }
 
     pub fn set_resolver(
@@ -187,12 +452,13 @@ impl RegistriesService {
     ) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let registry = get_registry_mut(registries, name)?;
+            let mut registry = get_registry(&store, name)?;
             if !registry.can_operate(&caller) {
                 return Err(NamingError::PermissionDenied);
             }
             registry.set_resolver(resolver);
+            store.add_registry(registry);
+            certify(&store, &[name.to_string()]);
             Ok(true)
         })
     }
@@ -204,16 +470,8 @@ impl RegistriesService {
     ) -> ServiceResult<GetPageOutput<String>> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let items = registries
-                .iter()
-                .filter_map(|(name, registry)| {
-                    if registry.is_owner(&owner) {
-                        Some(name.clone())
-                    } else {
-                        None
-                    }
-                })
+            let items = store
+                .get_owned_names(&owner, None)
                 .skip(page.offset)
                 .take(page.limit)
                 .collect::<Vec<_>>();
@@ -221,20 +479,29 @@ impl RegistriesService {
         })
     }
 
+    /// Names owned by the owner ordered by name, the cursor is the last name of the previous page.
+    pub(crate) fn get_controlled_names_by_cursor(
+        &self,
+        owner: Principal,
+        page: GetCursorPageInput,
+    ) -> ServiceResult<GetCursorPageOutput<String>> {
+        page.validate()?;
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let items = store
+                .get_owned_names(&owner, page.cursor.as_deref())
+                .take(page.limit + 1)
+                .collect::<Vec<_>>();
+            Ok(GetCursorPageOutput::new(items, page.limit, |name| {
+                name.clone()
+            }))
+        })
+    }
+
     pub fn get_controlled_names_count(&self, owner: Principal) -> ServiceResult<u32> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let total = registries
-                .iter()
-                .filter_map(|(name, registry)| {
-                    if registry.is_owner(&owner) {
-                        Some(name.clone())
-                    } else {
-                        None
-                    }
-                })
-                .count() as u32;
+            let total = store.get_owned_name_count(&owner) as u32;
             Ok(total)
         })
     }
@@ -247,8 +514,7 @@ impl RegistriesService {
     ) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let registry = get_registry_mut(registries, name)?;
+            let mut registry = get_registry(&store, name)?;
             if !registry.is_owner(caller) {
                 return Err(NamingError::PermissionDenied);
             }
@@ -260,6 +526,7 @@ impl RegistriesService {
                 return Err(NamingError::OperatorCountExceeded);
             }
             registry.add_operator(operator);
+            store.add_registry(registry);
             Ok(true)
         })
     }
@@ -272,12 +539,12 @@ impl RegistriesService {
     ) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let registry = get_registry_mut(registries, name)?;
+            let mut registry = get_registry(&store, name)?;
             if !registry.is_owner(caller) {
                 return Err(NamingError::PermissionDenied);
             }
             registry.remove_operator(operator);
+            store.add_registry(registry);
             Ok(true)
         })
     }
@@ -285,8 +552,7 @@ impl RegistriesService {
     pub(crate) fn get_users(&self, name: &str) -> ServiceResult<RegistryUsers> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, name)?;
+            let registry = get_registry(&store, name)?;
             Ok(registry.get_users())
         })
     }
@@ -294,13 +560,22 @@ impl RegistriesService {
     pub(crate) fn get_owner(&self, name: &str) -> ServiceResult<Principal> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, name)?;
+            let registry = get_registry(&store, name)?;
             Ok(registry.get_owner().to_owned())
         })
     }
 
-    pub(crate) fn set_owner(
+    /// Owner of the name with the witness of `owner/{name}` in the certified map.
+    /// It is None if the name is not found.
+    pub(crate) fn get_owner_certified(
+        &self,
+        name: &str,
+    ) -> ServiceResult<CertifiedValue<Principal>> {
+        let owner = self.get_owner(name).ok();
+        Ok(get_certified_value(&get_owner_key(name), owner))
+    }
+
+    pub(crate) async fn set_owner(
         &self,
         caller: Principal,
         name: &str,
@@ -308,22 +583,20 @@ impl RegistriesService {
     ) -> ServiceResult<bool> {
         must_not_anonymous(&caller)?;
         must_not_anonymous(&owner)?;
+        STATE.with(|s| validate_new_owner(&s.registry_store.borrow(), &caller, name, &owner))?;
+
+        // primary names set by the previous owner should not be verified for the new owner
+        self.resolver_api
+            .reset_primary_names(vec![name.to_string()])
+            .await?;
+
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            let registry = get_registry_mut(registries, name)?;
-            let old_owner = registry.get_owner();
-            if *old_owner != caller {
-                error!("{} is not the owner of {}", caller, name);
-                return Err(NamingError::PermissionDenied);
-            }
-
-            if *old_owner == owner {
-                error!("{} is already the owner of {}", owner, name);
-                return Err(NamingError::InvalidOwner);
-            }
-            registry.set_owner(owner);
+            // the owner may be changed while resetting primary names
+            validate_new_owner(&store, &caller, name, &owner)?;
+            store.update_owner(name, owner);
             info!("{} is set as the owner of {}", owner, name);
+            certify(&store, &[name.to_string()]);
             Ok(true)
         })
     }
@@ -331,8 +604,7 @@ impl RegistriesService {
     pub(crate) fn get_ttl(&self, name: &str) -> ServiceResult<u64> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, name)?;
+            let registry = get_registry(&store, name)?;
             Ok(registry.get_ttl())
         })
     }
@@ -340,9 +612,8 @@ impl RegistriesService {
     pub(crate) fn get_details(&self, name: &str) -> ServiceResult<RegistryDto> {
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = get_registry(registries, name)?;
-            Ok(RegistryDto::from(registry))
+            let registry = get_registry(&store, name)?;
+            Ok(RegistryDto::from(&registry))
         })
     }
 
@@ -356,14 +627,14 @@ impl RegistriesService {
         must_be_named_canister(*caller, CanisterNames::Registrar)?;
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
 
-            let registry = registries.get_mut(name);
-            if let Some(registry) = registry {
+            let registry = store.get_registry(name);
+            if let Some(mut registry) = registry {
                 registry.set_owner(new_owner.to_owned());
                 registry.set_ttl(DEFAULT_TTL);
                 registry.set_resolver(resolver.to_owned());
                 registry.set_operators(HashSet::new());
+                store.add_registry(registry);
             } else {
                 let registry = Registry::new(
                     name.to_string(),
@@ -371,8 +642,9 @@ impl RegistriesService {
                     DEFAULT_TTL,
                     *resolver,
                 );
-                registries.insert(name.to_string(), registry);
+                store.add_registry(registry);
             }
+            certify(&store, &[name.to_string()]);
         });
         Ok(true)
     }
@@ -389,14 +661,23 @@ impl RegistriesService {
         assert_ne!(name, NAMING_TOP_LABEL);
         let (sub_names, registry) = STATE.with(|s| {
             let store = s.registry_store.borrow();
+            let leases = s.lease_store.borrow();
             let registry = store.get_registry(name);
             if registry.is_none() {
                 return Err(NamingError::RegistryNotFoundError {
                     name: name.to_string(),
                 });
             }
-            let registry = registry.unwrap().clone();
-            let sub_names = store.get_sub_names(name);
+            let registry = registry.unwrap();
+            // subdomains which parent has given up the control or leased are kept
+            let sub_names: Vec<String> = store
+                .get_sub_names(name)
+                .into_iter()
+                .filter(|sub_name| {
+                    !store.is_protected_from(sub_name, name)
+                        && !leases.is_protected_from(sub_name, name)
+                })
+                .collect();
             debug!("reset_name: sub_names: {:?}", sub_names);
 
             Ok((sub_names, registry))
@@ -418,6 +699,7 @@ impl RegistriesService {
             let mut store = s.registry_store.borrow_mut();
             // remove registries
             store.remove_names(&removing_names);
+            s.lease_store.borrow_mut().remove_names(&removing_names);
             debug!(
                 "reset_name: removed registries for sub_names: {:?}",
                 &removing_names
@@ -430,6 +712,46 @@ impl RegistriesService {
                 DEFAULT_TTL,
                 resolver,
             ));
+            certify(&store, &removing_names);
+            Ok(true)
+        })
+    }
+
+    pub async fn remove_name(&mut self, name: &str, caller: &Principal) -> ServiceResult<bool> {
+        must_be_named_canister(*caller, CanisterNames::Registrar)?;
+        // prevent remove top level name
+        assert_ne!(name, NAMING_TOP_LABEL);
+        let removing_names = STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            let mut removing_names = store.get_sub_names(name);
+            if store.get_registry(name).is_some() {
+                removing_names.push(name.to_string());
+            }
+            removing_names
+        });
+        if removing_names.is_empty() {
+            info!("remove_name: {} is already removed", name);
+            return Ok(true);
+        }
+
+        // remove resolvers for current and sub names
+        self.resolver_api
+            .remove_resolvers(removing_names.clone())
+            .await?;
+        debug!(
+            "remove_name: removed resolvers for names: {:?}",
+            &removing_names
+        );
+
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            store.remove_names(&removing_names);
+            s.lease_store.borrow_mut().remove_names(&removing_names);
+            certify(&store, &removing_names);
+            info!(
+                "remove_name: removed registries for names: {:?}",
+                &removing_names
+            );
             Ok(true)
         })
     }
@@ -449,6 +771,7 @@ impl RegistriesService {
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
             store.update_owner(name, new_owner.to_owned());
+            certify(&store, &[name.to_string()]);
             info!(
                 "transfer: updated owner for name: {} to: {}",
                 name, new_owner
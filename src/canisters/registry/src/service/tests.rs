@@ -43,9 +43,8 @@ pub(crate) fn create_registry(_name: String, _owner: Principal) -> Registry {
 fn add_test_registry() -> Registry {
     STATE.with(|s| {
         let mut store = s.registry_store.borrow_mut();
-        let registries = store.get_registries_mut();
         let registry = create_registry(NAMING_TOP_LABEL.to_string(), top_owner());
-        registries.insert(NAMING_TOP_LABEL.to_string(), registry.clone());
+        store.add_registry(registry.clone());
         registry
     })
 }
@@ -64,9 +63,8 @@ mod add_top_name {
 
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            assert_eq!(registries.len(), 1);
-            let item = get_registry(&registries, &NAMING_TOP_LABEL.to_string()).unwrap();
+            assert_eq!(store.get_registry_count(), 1);
+            let item = get_registry(&store, NAMING_TOP_LABEL).unwrap();
             info!("{:?}", item);
             assert_eq!(item.get_name(), NAMING_TOP_LABEL.to_string());
             assert_eq!(item.get_owner(), &top_owner);
@@ -84,12 +82,21 @@ mod add_subdomain_to_registries {
     async fn test_add_subdomain_to_registries(
         _init_test: (),
         top_owner: Principal,
+        resolver: Principal,
         mut service: RegistriesService,
         mut mock_resolver_api: MockResolverApi,
     ) {
         let _ctx = mock_resolver_api
             .expect_ensure_resolver_created()
             .returning(|_name| Ok(true));
+        mock_resolver_api
+            .expect_set_ttl()
+            .times(1)
+            .returning(|name, ttl| {
+                assert_eq!(name, create_test_name("test"));
+                assert_eq!(ttl, 128);
+                Ok(true)
+            });
         service.resolver_api = Arc::new(mock_resolver_api);
         service
             .set_top_name(create_registry(NAMING_TOP_LABEL.to_string(), top_owner))
@@ -103,7 +110,7 @@ mod add_subdomain_to_registries {
                 top_owner,
                 sub_owner,
                 128,
-                Principal::anonymous(),
+                resolver,
             )
             .await;
         println!("{:?}", result);
@@ -113,14 +120,13 @@ mod add_subdomain_to_registries {
 
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            assert_eq!(registries.len(), 2);
-            let item = get_registry(&registries, &name).unwrap();
+            assert_eq!(store.get_registry_count(), 2);
+            let item = get_registry(&store, &name).unwrap();
             info!("{:?}", item);
             assert_eq!(item.get_name(), name);
             assert_eq!(item.get_owner(), &sub_owner);
             assert_eq!(item.get_ttl(), 128);
-            assert_eq!(item.get_resolver(), Principal::anonymous());
+            assert_eq!(item.get_resolver(), resolver);
         });
     }
 }
@@ -143,8 +149,7 @@ mod approval {
         assert!(result.is_ok());
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let item = get_registry(&registries, &registry.get_name()).unwrap();
+            let item = get_registry(&store, registry.get_name()).unwrap();
             let operators = item.get_operators().unwrap();
             assert_eq!(operators.len(), 1);
             assert!(operators.contains(&operator))
@@ -186,8 +191,7 @@ mod approval {
         assert!(result.is_ok());
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let item = get_registry(&registries, &registry.get_name()).unwrap();
+            let item = get_registry(&store, registry.get_name()).unwrap();
             let operators = item.get_operators().unwrap();
             assert_eq!(operators.len(), 0);
         });
@@ -212,31 +216,52 @@ mod approval {
 }
 
 mod set_record {
+    use common::errors::ErrorInfo;
+
     use super::*;
 
+    fn add_icp_registry(caller: Principal) {
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            let registry = Registry::new("icp".to_string(), caller, 0, Principal::anonymous());
+            store.add_registry(registry);
+        });
+    }
+
     #[rstest]
-    fn test_set_record(_init_test: (), mut service: RegistriesService) {
+    async fn test_set_record(
+        _init_test: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        resolver: Principal,
+    ) {
+        mock_resolver_api
+            .expect_set_ttl()
+            .times(1)
+            .returning(|name, ttl| {
+                assert_eq!(name, "icp");
+                assert_eq!(ttl, 123);
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
         let caller = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
-        let resolver = Principal::from_text("xzrh4-zyaaa-aaaaj-qagaa-cai").unwrap();
         let name = "icp";
         let ttl = 123;
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
             let registry =
                 Registry::new(name.to_string(), caller.clone(), 0, Principal::anonymous());
-            registries.insert(name.to_string(), registry);
+            store.add_registry(registry);
         });
 
         // act
-        let result = service.set_record(&caller, name, ttl, &resolver);
+        let result = service.set_record(&caller, name, ttl, &resolver).await;
 
         // assert
         assert!(result.is_ok());
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let item = get_registry(&registries, &name.to_string()).unwrap();
+            let item = get_registry(&store, name).unwrap();
             assert_eq!(item.get_name(), name.to_string());
             assert_eq!(item.get_ttl(), ttl);
             assert_eq!(item.get_resolver(), resolver);
@@ -244,14 +269,58 @@ mod set_record {
     }
 
     #[rstest]
-    fn test_set_record_resolver_not_found(_init_test: (), mut service: RegistriesService) {
+    async fn test_set_record_custom_resolver(
+        _init_test: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        auth_caller: Principal,
+    ) {
+        mock_resolver_api.expect_set_ttl().never();
+        service.resolver_api = Arc::new(mock_resolver_api);
+        add_icp_registry(auth_caller);
+        let custom_resolver = Principal::from_text("xzrh4-zyaaa-aaaaj-qagaa-cai").unwrap();
+
+        let result = service
+            .set_record(&auth_caller, "icp", 123, &custom_resolver)
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(service.get_resolver("icp"), Ok(custom_resolver));
+    }
+
+    #[rstest]
+    async fn test_set_record_sync_ttl_failed(
+        _init_test: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        auth_caller: Principal,
+        resolver: Principal,
+    ) {
+        mock_resolver_api
+            .expect_set_ttl()
+            .times(1)
+            .returning(|name, _| Err(ErrorInfo::from(NamingError::ResolverNotFoundError { name })));
+        service.resolver_api = Arc::new(mock_resolver_api);
+        add_icp_registry(auth_caller);
+
+        let result = service
+            .set_record(&auth_caller, "icp", 123, &resolver)
+            .await;
+
+        assert!(result.is_err());
+        // the record is saved, it can be set again to retry
+        assert_eq!(service.get_resolver("icp"), Ok(resolver));
+    }
+
+    #[rstest]
+    async fn test_set_record_resolver_not_found(_init_test: (), mut service: RegistriesService) {
         let caller = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
         let resolver = Principal::from_text("xzrh4-zyaaa-aaaaj-qagaa-cai").unwrap();
         let name = "icp";
         let ttl = 123;
 
         // act
-        let result = service.set_record(&caller, name, ttl, &resolver);
+        let result = service.set_record(&caller, name, ttl, &resolver).await;
 
         // assert
         assert!(result.is_err());
@@ -264,25 +333,26 @@ mod set_record {
     }
 
     #[rstest]
-    fn test_set_record_permission_deny(_init_test: (), mut service: RegistriesService) {
+    async fn test_set_record_permission_deny(_init_test: (), mut service: RegistriesService) {
         let _owner = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
         let resolver = Principal::from_text("xzrh4-zyaaa-aaaaj-qagaa-cai").unwrap();
         let name = "icp";
         let ttl = 123;
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
             let registry = Registry::new(
                 name.to_string(),
                 resolver.clone(),
                 0,
                 Principal::anonymous(),
             );
-            registries.insert(name.to_string(), registry);
+            store.add_registry(registry);
         });
 
         // act
-        let result = service.set_record(&Principal::anonymous(), name, ttl, &resolver);
+        let result = service
+            .set_record(&Principal::anonymous(), name, ttl, &resolver)
+            .await;
 
         // assert
         assert!(result.is_err());
@@ -311,8 +381,7 @@ mod reclaim {
         // assert
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = registries.get("nice.ic").unwrap();
+            let registry = store.get_registry("nice.ic").unwrap();
             assert_eq!(registry.get_name(), "nice.ic");
             assert_eq!(registry.get_resolver(), resolver);
             assert_eq!(registry.get_owner(), &mock_user1);
@@ -331,16 +400,12 @@ mod reclaim {
         let caller = get_named_get_canister_id(CanisterNames::Registrar);
         STATE.with(|s| {
             let mut store = s.registry_store.borrow_mut();
-            let registries = store.get_registries_mut();
-            registries.insert(
+            store.add_registry(Registry::new(
                 "nice.ic".to_string(),
-                Registry::new(
-                    "nice.ic".to_string(),
-                    resolver.clone(),
-                    0,
-                    mock_user2.clone(),
-                ),
-            );
+                resolver.clone(),
+                0,
+                mock_user2.clone(),
+            ));
         });
         // act
         service
@@ -350,8 +415,7 @@ mod reclaim {
         // assert
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            let registry = registries.get("nice.ic").unwrap();
+            let registry = store.get_registry("nice.ic").unwrap();
             assert_eq!(registry.get_name(), "nice.ic");
             assert_eq!(registry.get_resolver(), resolver);
             assert_eq!(registry.get_owner(), &mock_user1);
@@ -432,11 +496,10 @@ mod reset_name {
         assert!(result.is_ok());
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            assert_eq!(registries.len(), 3);
-            registries.get("ic").unwrap();
-            registries.get("wownice.ic").unwrap();
-            let registry = registries.get("nice.ic").unwrap();
+            assert_eq!(store.get_registry_count(), 3);
+            store.get_registry("ic").unwrap();
+            store.get_registry("wownice.ic").unwrap();
+            let registry = store.get_registry("nice.ic").unwrap();
             assert_eq!(registry.get_name(), "nice.ic");
             assert_eq!(registry.get_resolver(), resolver);
             assert_eq!(registry.get_owner(), &mock_user1);
@@ -495,10 +558,116 @@ mod reset_name {
         assert!(result.is_err());
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            assert_eq!(registries.len(), names.len());
+            assert_eq!(store.get_registry_count(), names.len() as u64);
+        })
+    }
+}
+
+mod remove_name {
+    use common::errors::ErrorInfo;
+
+    use super::*;
+
+    #[rstest]
+    async fn test_remove_name_success(
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+    ) {
+        let names = vec!["sub1.nice.ic", "nice.ic", "wownice.ic", "ic"];
+        let resolver = get_named_get_canister_id(CanisterNames::Resolver);
+
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            for name in names.iter() {
+                let registry =
+                    Registry::new(name.to_string(), mock_user1.clone(), DEFAULT_TTL, resolver);
+                store.add_registry(registry);
+            }
+        });
+
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .returning(|mut names| {
+                names.sort();
+                assert_eq!(names, vec!["nice.ic", "sub1.nice.ic"]);
+                Ok(true)
+            });
+
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // act
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        let result = service.remove_name("nice.ic", &caller).await;
+
+        // assert
+        assert_eq!(result, Ok(true));
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            assert_eq!(store.get_registry_count(), 2);
+            store.get_registry("ic").unwrap();
+            store.get_registry("wownice.ic").unwrap();
+        })
+    }
+
+    #[rstest]
+    async fn test_remove_name_already_removed(
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+    ) {
+        mock_resolver_api.expect_remove_resolvers().never();
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // act
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        let result = service.remove_name("nice.ic", &caller).await;
+
+        // assert
+        assert_eq!(result, Ok(true));
+    }
+
+    #[rstest]
+    async fn test_remove_name_failed_api_error(
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+    ) {
+        let resolver = get_named_get_canister_id(CanisterNames::Resolver);
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            store.add_registry(Registry::new(
+                "nice.ic".to_string(),
+                mock_user1.clone(),
+                DEFAULT_TTL,
+                resolver,
+            ));
+        });
+
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .returning(|_names| Err(ErrorInfo::from(NamingError::PermissionDenied)));
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // act
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        let result = service.remove_name("nice.ic", &caller).await;
+
+        // assert
+        assert!(result.is_err());
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            assert!(store.get_registry("nice.ic").is_some());
         })
     }
+
+    #[rstest]
+    async fn test_remove_name_permission_denied(
+        mut service: RegistriesService,
+        mock_user1: Principal,
+    ) {
+        let result = service.remove_name("nice.ic", &mock_user1).await;
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
 }
 
 // mod load_state {
@@ -527,3 +696,499 @@ mod reset_name {
 //         File::create("registry.csv").unwrap().write_all(csv.as_bytes()).unwrap();
 //     }
 // }
+
+mod subdomain {
+    use common::errors::ErrorInfo;
+    use test_common::create_test_name;
+
+    use super::*;
+    use crate::name_locker::{try_lock_name, unlock_name};
+
+    fn add_registries(names: &[&str], owner: Principal) {
+        STATE.with(|s| {
+            let mut store = s.registry_store.borrow_mut();
+            for name in names.iter() {
+                store.add_registry(Registry::new(
+                    create_test_name(name),
+                    owner,
+                    DEFAULT_TTL,
+                    resolver(),
+                ));
+            }
+        });
+    }
+
+    fn get_test_registry(name: &str) -> Option<Registry> {
+        STATE.with(|s| {
+            let store = s.registry_store.borrow();
+            store.get_registry(&create_test_name(name))
+        })
+    }
+
+    #[fixture]
+    fn nice(_init_test: (), mock_user1: Principal) {
+        add_registries(&["nice"], mock_user1);
+    }
+
+    #[rstest]
+    async fn test_create_subdomain(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        mock_resolver_api
+            .expect_ensure_resolver_created()
+            .times(1)
+            .returning(|name| {
+                assert_eq!(name, create_test_name("www.nice"));
+                Ok(true)
+            });
+        mock_resolver_api
+            .expect_set_ttl()
+            .times(1)
+            .returning(|name, ttl| {
+                assert_eq!(name, create_test_name("www.nice"));
+                assert_eq!(ttl, DEFAULT_TTL);
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        let result = service
+            .create_subdomain(&mock_user1, &create_test_name("WWW.nice"), mock_user2, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(result.name, create_test_name("www.nice"));
+        assert_eq!(result.owner, mock_user2);
+        assert_eq!(result.resolver, resolver());
+        assert_eq!(service.get_fuses(&create_test_name("www.nice")), Ok(0));
+    }
+
+    #[rstest]
+    async fn test_create_subdomain_not_parent_owner(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user2: Principal,
+    ) {
+        mock_resolver_api.expect_ensure_resolver_created().never();
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        let result = service
+            .create_subdomain(&mock_user2, &create_test_name("www.nice"), mock_user2, 0)
+            .await;
+
+        assert_eq!(result.err(), Some(NamingError::PermissionDenied));
+        assert!(get_test_registry("www.nice").is_none());
+    }
+
+    #[rstest]
+    async fn test_create_subdomain_already_exists(
+        _nice: (),
+        mut service: RegistriesService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        add_registries(&["www.nice"], mock_user2);
+
+        let result = service
+            .create_subdomain(&mock_user1, &create_test_name("www.nice"), mock_user1, 0)
+            .await;
+
+        assert_eq!(
+            result.err(),
+            Some(NamingError::RegistryAlreadyExists {
+                name: create_test_name("www.nice")
+            })
+        );
+        assert_eq!(
+            get_test_registry("www.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+    }
+
+    #[rstest]
+    async fn test_create_subdomain_locked(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+    ) {
+        mock_resolver_api.expect_ensure_resolver_created().never();
+        service.resolver_api = Arc::new(mock_resolver_api);
+        let name = create_test_name("www.nice");
+        try_lock_name(&name).unwrap();
+
+        let result = service
+            .create_subdomain(&mock_user1, &name, mock_user1, 0)
+            .await;
+        unlock_name(&name);
+
+        assert_eq!(result.err(), Some(NamingError::Conflict));
+        assert!(get_test_registry("www.nice").is_none());
+    }
+
+    #[rstest]
+    fn test_burn_fuses_of_deeper_subdomain(
+        _nice: (),
+        mut service: RegistriesService,
+        mock_user1: Principal,
+    ) {
+        add_registries(&["a.nice", "b.a.nice"], mock_user1);
+
+        let result = service.burn_subdomain_fuses(&mock_user1, &create_test_name("b.a.nice"), 1);
+        assert!(matches!(result, Err(NamingError::InvalidFuses { .. })));
+
+        let result = service.burn_subdomain_fuses(&mock_user1, &create_test_name("a.nice"), 1);
+        assert_eq!(result, Ok(true));
+        let result = service.burn_subdomain_fuses(&mock_user1, &create_test_name("b.a.nice"), 1);
+        assert_eq!(result, Ok(true));
+        assert_eq!(service.get_fuses(&create_test_name("b.a.nice")), Ok(1));
+
+        let result = service.burn_subdomain_fuses(&mock_user1, &create_test_name("a.nice"), 2);
+        assert!(matches!(result, Err(NamingError::InvalidFuses { .. })));
+    }
+
+    #[rstest]
+    async fn test_transfer_subdomain(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        mock_user3: Principal,
+    ) {
+        add_registries(&["www.nice"], mock_user2);
+        let name = create_test_name("www.nice");
+        let reset_name = name.clone();
+        mock_resolver_api
+            .expect_reset_primary_names()
+            .times(2)
+            .returning(move |names| {
+                assert_eq!(names, vec![reset_name.clone()]);
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // by owner of subdomain
+        let result = service
+            .transfer_subdomain(&mock_user2, &name, mock_user3)
+            .await;
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            get_test_registry("www.nice").unwrap().get_owner(),
+            &mock_user3
+        );
+
+        // by owner of parent
+        let result = service
+            .transfer_subdomain(&mock_user1, &name, mock_user2)
+            .await;
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            get_test_registry("www.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+
+        // by others
+        let result = service
+            .transfer_subdomain(&mock_user3, &name, mock_user3)
+            .await;
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    async fn test_transfer_subdomain_reset_primary_names_failed(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user2: Principal,
+        mock_user3: Principal,
+    ) {
+        add_registries(&["www.nice"], mock_user2);
+        mock_resolver_api
+            .expect_reset_primary_names()
+            .returning(|_| Err(ErrorInfo::from(NamingError::Unknown)));
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        let result = service
+            .transfer_subdomain(&mock_user2, &create_test_name("www.nice"), mock_user3)
+            .await;
+
+        assert!(matches!(result, Err(NamingError::RemoteError(_))));
+        assert_eq!(
+            get_test_registry("www.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+    }
+
+    #[rstest]
+    async fn test_controlled_names_after_transfer(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        add_registries(&["www.nice", "a.nice"], mock_user1);
+        mock_resolver_api
+            .expect_reset_primary_names()
+            .returning(|_| Ok(true));
+        service.resolver_api = Arc::new(mock_resolver_api);
+        service
+            .transfer_subdomain(&mock_user1, &create_test_name("www.nice"), mock_user2)
+            .await
+            .unwrap();
+
+        let page = service
+            .get_controlled_names_by_cursor(
+                mock_user1,
+                GetCursorPageInput {
+                    cursor: None,
+                    limit: 1,
+                },
+            )
+            .unwrap();
+        assert_eq!(page.items, vec![create_test_name("a.nice")]);
+        assert_eq!(page.next_cursor, Some(create_test_name("a.nice")));
+        let page = service
+            .get_controlled_names_by_cursor(
+                mock_user1,
+                GetCursorPageInput {
+                    cursor: page.next_cursor,
+                    limit: 1,
+                },
+            )
+            .unwrap();
+        assert_eq!(page.items, vec![create_test_name("nice")]);
+        assert_eq!(page.next_cursor, None);
+
+        assert_eq!(service.get_controlled_names_count(mock_user1), Ok(2));
+        let page = service
+            .get_controlled_names(
+                mock_user2,
+                GetPageInput {
+                    offset: 0,
+                    limit: 10,
+                },
+            )
+            .unwrap();
+        assert_eq!(page.items, vec![create_test_name("www.nice")]);
+    }
+
+    #[rstest]
+    async fn test_transfer_subdomain_fuse_burned(
+        _nice: (),
+        mut service: RegistriesService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        add_registries(&["www.nice"], mock_user2);
+        let name = create_test_name("www.nice");
+        service
+            .burn_subdomain_fuses(&mock_user1, &name, FUSE_PARENT_CANNOT_CONTROL)
+            .unwrap();
+
+        let result = service
+            .transfer_subdomain(&mock_user1, &name, mock_user1)
+            .await;
+
+        assert_eq!(result, Err(NamingError::FuseBurned));
+        assert_eq!(
+            get_test_registry("www.nice").unwrap().get_owner(),
+            &mock_user2
+        );
+    }
+
+    #[rstest]
+    async fn test_revoke_subdomain(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        add_registries(&["www.nice", "a.www.nice", "b.nice"], mock_user2);
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .times(1)
+            .returning(|mut names| {
+                names.sort();
+                assert_eq!(
+                    names,
+                    vec![create_test_name("a.www.nice"), create_test_name("www.nice")]
+                );
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        let result = service
+            .revoke_subdomain(&mock_user2, &create_test_name("www.nice"))
+            .await;
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+
+        let result = service
+            .revoke_subdomain(&mock_user1, &create_test_name("www.nice"))
+            .await;
+        assert_eq!(result, Ok(true));
+        assert!(get_test_registry("www.nice").is_none());
+        assert!(get_test_registry("a.www.nice").is_none());
+        assert!(get_test_registry("b.nice").is_some());
+    }
+
+    #[rstest]
+    async fn test_reset_name_keeps_fused_subdomains(
+        _nice: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        add_registries(&["www.nice", "a.www.nice", "b.nice"], mock_user2);
+        service
+            .burn_subdomain_fuses(
+                &mock_user1,
+                &create_test_name("www.nice"),
+                FUSE_PARENT_CANNOT_CONTROL,
+            )
+            .unwrap();
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .returning(|mut names| {
+                names.sort();
+                assert_eq!(
+                    names,
+                    vec![create_test_name("b.nice"), create_test_name("nice")]
+                );
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        let result = service
+            .reset_name(&create_test_name("nice"), &caller, resolver())
+            .await;
+
+        assert_eq!(result, Ok(true));
+        assert!(get_test_registry("www.nice").is_some());
+        assert!(get_test_registry("a.www.nice").is_some());
+        assert!(get_test_registry("b.nice").is_none());
+    }
+}
+
+mod certified_query {
+    use common::certified_map::get_value_hash;
+
+    use super::*;
+    use crate::certification::{get_owner_key, get_resolver_key};
+    use crate::state::CERTIFIED_MAP;
+
+    fn get_leaf(key: &str) -> Option<[u8; 32]> {
+        CERTIFIED_MAP.with(|map| map.borrow().get(key).cloned())
+    }
+
+    #[rstest]
+    fn test_reclaim_name_certified(
+        _init_test: (),
+        mut service: RegistriesService,
+        mock_user1: Principal,
+        resolver: Principal,
+    ) {
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+
+        // act
+        service
+            .reclaim_name("nice.ic", &caller, &mock_user1, &resolver)
+            .unwrap();
+
+        // assert
+        let owner = service.get_owner_certified("nice.ic").unwrap();
+        assert_eq!(owner.value, Some(mock_user1));
+        assert!(!owner.witness.is_empty());
+        assert_eq!(
+            get_leaf(&get_owner_key("nice.ic")),
+            Some(get_value_hash(&mock_user1))
+        );
+        let result = service.get_resolver_certified("nice.ic").unwrap();
+        assert_eq!(result.value, Some(resolver));
+        assert_eq!(
+            get_leaf(&get_resolver_key("nice.ic")),
+            Some(get_value_hash(&resolver))
+        );
+    }
+
+    #[rstest]
+    async fn test_set_owner_certified(
+        _init_test: (),
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+        resolver: Principal,
+    ) {
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        service
+            .reclaim_name("nice.ic", &caller, &mock_user1, &resolver)
+            .unwrap();
+        mock_resolver_api
+            .expect_reset_primary_names()
+            .times(1)
+            .returning(|names| {
+                assert_eq!(names, vec!["nice.ic".to_string()]);
+                Ok(true)
+            });
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // act
+        service
+            .set_owner(mock_user1, "nice.ic", mock_user2)
+            .await
+            .unwrap();
+
+        // assert
+        let owner = service.get_owner_certified("nice.ic").unwrap();
+        assert_eq!(owner.value, Some(mock_user2));
+        assert_eq!(
+            get_leaf(&get_owner_key("nice.ic")),
+            Some(get_value_hash(&mock_user2))
+        );
+    }
+
+    #[rstest]
+    fn test_get_certified_not_found(_init_test: (), service: RegistriesService) {
+        let owner = service.get_owner_certified("nice.ic").unwrap();
+        assert_eq!(owner.value, None);
+        assert!(!owner.witness.is_empty());
+        let result = service.get_resolver_certified("nice.ic").unwrap();
+        assert_eq!(result.value, None);
+        assert_eq!(get_leaf(&get_owner_key("nice.ic")), None);
+    }
+
+    #[rstest]
+    async fn test_remove_name_uncertified(
+        mut service: RegistriesService,
+        mut mock_resolver_api: MockResolverApi,
+        mock_user1: Principal,
+        resolver: Principal,
+    ) {
+        let caller = get_named_get_canister_id(CanisterNames::Registrar);
+        service
+            .reclaim_name("nice.ic", &caller, &mock_user1, &resolver)
+            .unwrap();
+        mock_resolver_api
+            .expect_remove_resolvers()
+            .returning(|_| Ok(true));
+        service.resolver_api = Arc::new(mock_resolver_api);
+
+        // act
+        let result = service.remove_name("nice.ic", &caller).await;
+
+        // assert
+        assert_eq!(result, Ok(true));
+        assert_eq!(get_leaf(&get_owner_key("nice.ic")), None);
+        assert_eq!(get_leaf(&get_resolver_key("nice.ic")), None);
+        assert_eq!(service.get_owner_certified("nice.ic").unwrap().value, None);
+    }
+}
@@ -5,22 +5,43 @@ use std::collections::HashMap;
 use std::sync::Once;
 
 use candid::{candid_method, decode_args, encode_args, Principal};
-use ic_cdk::{api, storage};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::info;
 
+use common::certified_map::CertifiedMap;
 use common::ic_logger::ICLogger;
 use common::named_canister_ids::{
     ensure_current_canister_id_match, get_named_get_canister_id, update_dev_named_canister_ids,
     CanisterNames,
 };
-use common::state::StableState;
+use common::stable_memory::MemoryId;
+use common::state::{
+    encode_entries, restore_state, save_state, StableEntries, StableMemoryState, StableState,
+};
 
+use crate::certification::certify_restored;
+use crate::lease_store::LeaseStore;
+use crate::name_locker::NameLocker;
 use crate::registry_store::RegistryStore;
 use crate::service::RegistriesService;
 
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const REGISTRIES_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub const OWNER_NAMES_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const OWNER_NAME_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const SUBDOMAIN_SALES_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const SUBDOMAIN_LEASES_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub const CERTIFIED_MAP_MEMORY_ID: MemoryId = MemoryId::new(6);
+
 thread_local! {
     pub static STATE : State = State::default();
+    pub static NAME_LOCKER: RefCell<NameLocker> = RefCell::new(NameLocker::new());
+    pub static CERTIFIED_MAP: RefCell<CertifiedMap> =
+        RefCell::new(CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID));
 }
 
 #[derive(Default)]
@@ -28,28 +49,51 @@ pub struct State {
     // NOTE: When adding new persistent fields here, ensure that these fields
     // are being persisted in the `replace` method below.
     pub(crate) registry_store: RefCell<RegistryStore>,
+    pub(crate) lease_store: RefCell<LeaseStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    registry_entries: StableEntries<RegistryStore>,
+    lease_entries: StableEntries<LeaseStore>,
 }
 
 impl State {
     pub fn replace(&self, new_state: State) {
-        self.registry_store.replace(new_state.registry_store.take());
+        new_state.registry_entries.import_into(&self.registry_store);
+        new_state.lease_entries.import_into(&self.lease_store);
     }
 }
 
 impl StableState for State {
     fn encode(&self) -> Vec<u8> {
-        encode_args((self.registry_store.borrow().encode(),)).unwrap()
+        encode_args((
+            encode_entries(&self.registry_store),
+            encode_entries(&self.lease_store),
+        ))
+        .unwrap()
     }
 
     fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (registry_store_bytes,) = decode_args(&bytes).unwrap();
+        let (registry_store_bytes, lease_store_bytes): (Vec<u8>, Option<Vec<u8>>) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
 
         Ok(State {
-            registry_store: RefCell::new(RegistryStore::decode(registry_store_bytes)?),
+            registry_entries: StableEntries::decode(registry_store_bytes)?,
+            lease_entries: StableEntries::decode_or_default(lease_store_bytes)?,
+            ..State::default()
         })
     }
 }
 
+/// All stores are kept in stable memory, nothing is saved with the heap state
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args(()).unwrap()
+    }
+
+    fn decode_heap(_bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(State::default())
+    }
+}
+
 static INIT: Once = Once::new();
 
 pub(crate) fn canister_module_init() {
@@ -95,26 +139,20 @@ fn init_function() {
 #[pre_upgrade(guard = "guard_func")]
 fn pre_upgrade() {
     STATE.with(|s| {
-        let bytes = s.encode();
-        match storage::stable_save((&bytes,)) {
-            Ok(_) => {
-                info!("Saved state before upgrade");
-                ()
-            }
-            Err(e) => api::trap(format!("Failed to save state before upgrade: {:?}", e).as_str()),
-        };
+        save_state(s);
+        info!("Saved state before upgrade");
     });
 }
 
 #[post_upgrade(guard = "guard_func")]
 fn post_upgrade() {
-    STATE.with(|s| match storage::stable_restore::<(Vec<u8>,)>() {
-        Ok(bytes) => {
-            let new_state = State::decode(bytes.0).expect("Decoding stable memory failed");
-
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
             s.replace(new_state);
+            certify_restored(&s.registry_store.borrow());
             info!("Loaded state after upgrade");
-        }
+        }),
         Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
-    });
+    }
 }
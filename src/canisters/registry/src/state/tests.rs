@@ -0,0 +1,89 @@
+use rstest::*;
+
+use test_common::user::*;
+
+use common::constants::DEFAULT_TTL;
+use common::stable_memory::load_heap_state;
+
+use super::*;
+use crate::certification::{certify_all, get_owner_key};
+use crate::lease_store::SubdomainSale;
+use crate::registry_store::Registry;
+
+fn new_registry(name: &str, owner: Principal) -> Registry {
+    Registry::new(name.to_string(), owner, DEFAULT_TTL, Principal::anonymous())
+}
+
+fn add_test_registries(count: usize, owner: Principal) {
+    STATE.with(|s| {
+        let mut store = s.registry_store.borrow_mut();
+        for i in 0..count {
+            store.add_registry(new_registry(&format!("name{}.ic", i), owner));
+        }
+    });
+}
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal) {
+    add_test_registries(10, mock_user1);
+    STATE.with(|s| {
+        s.lease_store
+            .borrow_mut()
+            .set_sale("name0.ic".to_string(), SubdomainSale::default());
+    });
+
+    pre_upgrade();
+    post_upgrade();
+
+    // nothing is saved with the heap state
+    assert_eq!(load_heap_state(), Some(encode_args(()).unwrap()));
+    STATE.with(|s| {
+        assert_eq!(
+            s.registry_store.borrow().get_owned_name_count(&mock_user1),
+            10
+        );
+        assert!(s.lease_store.borrow().get_sale("name0.ic").is_some());
+    });
+}
+
+#[rstest]
+fn test_migrate_legacy_state(mock_user1: Principal, mock_user2: Principal) {
+    // registries were encoded without owner index, and there was no lease store
+    let registries: HashMap<String, Registry> = [
+        new_registry("a.ic", mock_user1),
+        new_registry("b.ic", mock_user1),
+        new_registry("c.ic", mock_user2),
+    ]
+    .into_iter()
+    .map(|registry| (registry.get_name().to_string(), registry))
+    .collect();
+    let legacy_bytes = encode_args((encode_args((registries,)).unwrap(),)).unwrap();
+
+    STATE.with(|s| s.replace(State::decode(legacy_bytes).unwrap()));
+
+    STATE.with(|s| {
+        let store = s.registry_store.borrow();
+        assert_eq!(store.get_registry_count(), 3);
+        assert_eq!(store.get_owned_name_count(&mock_user1), 2);
+        assert_eq!(store.get_owned_name_count(&mock_user2), 1);
+    });
+}
+
+#[rstest]
+fn test_upgrade_restores_certified_map(mock_user1: Principal) {
+    add_test_registries(1, mock_user1);
+    STATE.with(|s| certify_all(&s.registry_store.borrow()));
+    let root_hash = CERTIFIED_MAP.with(|map| map.borrow().root_hash());
+    // registries added without being certified, they are not certified by upgrade either
+    add_test_registries(10, mock_user1);
+
+    pre_upgrade();
+    CERTIFIED_MAP.with(|map| map.replace(CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID)));
+    post_upgrade();
+
+    CERTIFIED_MAP.with(|map| {
+        let map = map.borrow();
+        assert_eq!(map.root_hash(), root_hash);
+        assert!(map.get(&get_owner_key("name1.ic")).is_none());
+    });
+}
@@ -12,8 +12,7 @@ impl StatsService {
         stats.cycles_balance = api::canister_balance();
         STATE.with(|s| {
             let store = s.registry_store.borrow();
-            let registries = store.get_registries();
-            stats.registry_count = registries.len() as u64;
+            stats.registry_count = store.get_registry_count();
         });
 
         stats
@@ -0,0 +1,87 @@
+use candid::Principal;
+use itertools::Itertools;
+
+use common::certified_map::CertifiedValue;
+use common::constants::RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL;
+
+use crate::profile::{certify_profile, get_not_found_response};
+use crate::reverse_resolver_store::get_verified_primary_name;
+use crate::state::{State, CERTIFIED_MAP};
+
+pub type RecordValues = Vec<(String, String)>;
+
+pub fn get_record_value_key(name: &str) -> String {
+    format!("record_value/{}", name)
+}
+
+pub fn get_reverse_resolve_key(principal: &Principal) -> String {
+    format!("reverse_resolve/{}", principal)
+}
+
+/// Record values of a name sorted by key, including the reverse resolution principal.
+/// Returns None if the resolver of the name is not created.
+pub(crate) fn get_record_values(state: &State, name: &str) -> Option<RecordValues> {
+    let store = state.resolver_store.borrow();
+    let resolver = store.get_resolver(name)?;
+    let mut values = resolver.get_record_value().clone();
+    let reverse_store = state.reverse_resolver_store.borrow();
+    if let Some(principal) = reverse_store.get_primary_name_reverse(&name.to_string()) {
+        values.insert(
+            RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL.to_string(),
+            principal.to_string(),
+        );
+    }
+    Some(values.into_iter().sorted().collect())
+}
+
+/// Update certified record values and profiles of names and reverse resolution of principals,
+/// it should be called after the stores are updated. Only verified primary names are certified.
+pub(crate) fn certify(state: &State, names: &[String], principals: &[Principal]) {
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        for name in names {
+            let key = get_record_value_key(name);
+            let values = get_record_values(state, name);
+            match &values {
+                Some(values) => map.put(key, values),
+                None => map.delete(&key),
+            }
+            certify_profile(&mut map, name, values.as_ref());
+        }
+        for principal in principals {
+            let key = get_reverse_resolve_key(principal);
+            match get_verified_primary_name(state, principal, None) {
+                Ok(name) => map.put(key, &name),
+                Err(_) => map.delete(&key),
+            }
+        }
+        map.certify();
+    });
+}
+
+/// Rebuild the certified map from the stores, it renders the profiles of all names
+pub(crate) fn certify_all(state: &State) {
+    let names: Vec<String> = state.resolver_store.borrow().get_names();
+    let principals = state.reverse_resolver_store.borrow().get_principals();
+    CERTIFIED_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        map.clear();
+        map.put_http_fallback_response(&get_not_found_response());
+    });
+    certify(state, &names, &principals);
+}
+
+/// Certify the map restored from stable memory after upgrade, profiles are not rendered again.
+/// It is only rebuilt from the stores if it is not kept in stable memory yet.
+pub(crate) fn certify_restored(state: &State) {
+    let is_empty = CERTIFIED_MAP.with(|map| map.borrow().is_empty());
+    if is_empty {
+        certify_all(state);
+    } else {
+        CERTIFIED_MAP.with(|map| map.borrow().certify());
+    }
+}
+
+pub(crate) fn get_certified_value<T>(key: &str, value: Option<T>) -> CertifiedValue<T> {
+    CERTIFIED_MAP.with(|map| map.borrow().get_certified_value(key, value))
+}
@@ -1,12 +1,16 @@
 //! Functions for validating the base58 hash checksums, including specifically
 //! the bitcoin and litecoin addresses.
 //! source from https://github.com/viraptor/coinaddress/blob/master/src/lib.rs
+//!
+//! Validators of EVM, bech32 and Solana addresses are added for other chains.
 
+use bech32::{FromBase32, Variant};
 use num_bigint::BigUint;
 use num_integer::Integer;
 use num_traits::{ToPrimitive, Zero};
 use sha2::Digest;
 use sha2::Sha256;
+use sha3::Keccak256;
 use std::io::Write;
 
 #[derive(PartialEq, Debug)]
@@ -25,6 +29,12 @@ pub enum ValidationError {
     /// This address is not a litecoin address.
     /// May happen when attempting to validate ltc address
     NotLitecoin,
+    /// This address is not a dogecoin address.
+    NotDogecoin,
+    /// Length of the decoded address is not expected
+    InvalidLength,
+    /// Prefix or human readable part of the address is not expected
+    InvalidPrefix,
 }
 
 static BASE58_CHARS: &'static str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
@@ -126,3 +136,90 @@ pub fn validate_ltc_address(addr: &str) -> Result<usize, ValidationError> {
         Err(x) => Err(x),
     }
 }
+
+/// Validate dogecoin address checksum.
+/// Returns the hash version/type if correct and an error otherwise.
+pub fn validate_doge_address(addr: &str) -> Result<usize, ValidationError> {
+    match validate_base58_hash(addr) {
+        Ok(30) => Ok(30),   // real address
+        Ok(22) => Ok(22),   // script hash
+        Ok(113) => Ok(113), // testnet address
+        Ok(_) => Err(ValidationError::NotDogecoin),
+        Err(x) => Err(x),
+    }
+}
+
+/// Validate bech32 encoded segwit address of bitcoin, see BIP-173 and BIP-350.
+/// Returns the witness version if correct and an error otherwise.
+pub fn validate_btc_segwit_address(addr: &str) -> Result<u8, ValidationError> {
+    let (hrp, data, variant) =
+        bech32::decode(addr).map_err(|_| ValidationError::InvalidEncoding)?;
+    if hrp != "bc" && hrp != "tb" {
+        return Err(ValidationError::InvalidPrefix);
+    }
+    let version = data.first().ok_or(ValidationError::TooShort)?.to_u8();
+    let program =
+        Vec::<u8>::from_base32(&data[1..]).map_err(|_| ValidationError::InvalidEncoding)?;
+    match (version, variant) {
+        // segwit v0 uses bech32
+        (0, Variant::Bech32) if program.len() == 20 || program.len() == 32 => Ok(0),
+        // segwit v1+ such as taproot uses bech32m
+        (1..=16, Variant::Bech32m) if (2..=40).contains(&program.len()) => Ok(version),
+        _ => Err(ValidationError::NotBitcoin),
+    }
+}
+
+/// Validate bech32 address of chains in Cosmos ecosystem, e.g. `cosmos1...` for cosmos hub.
+pub fn validate_cosmos_address(hrp: &str, addr: &str) -> Result<(), ValidationError> {
+    let (decoded_hrp, data, variant) =
+        bech32::decode(addr).map_err(|_| ValidationError::InvalidEncoding)?;
+    if decoded_hrp != hrp || variant != Variant::Bech32 {
+        return Err(ValidationError::InvalidPrefix);
+    }
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|_| ValidationError::InvalidEncoding)?;
+    // 20 bytes for accounts and 32 bytes for module or contract accounts
+    if bytes.len() != 20 && bytes.len() != 32 {
+        return Err(ValidationError::InvalidLength);
+    }
+    Ok(())
+}
+
+/// Validate base58 encoded ed25519 public key of Solana.
+pub fn validate_solana_address(addr: &str) -> Result<(), ValidationError> {
+    if addr.len() < 32 || addr.len() > 44 {
+        return Err(ValidationError::InvalidLength);
+    }
+    let big = decode_base58(addr).ok_or(ValidationError::InvalidEncoding)?;
+    // leading 1s are leading zero bytes
+    let leading_zeros = addr.chars().take_while(|c| *c == '1').count();
+    if leading_zeros + biguint_to_bytes(big).len() != 32 {
+        return Err(ValidationError::InvalidLength);
+    }
+    Ok(())
+}
+
+/// Validate EVM address, mixed case address should be checksummed as EIP-55.
+pub fn validate_evm_address(addr: &str) -> Result<(), ValidationError> {
+    let hex = addr
+        .strip_prefix("0x")
+        .ok_or(ValidationError::InvalidPrefix)?;
+    if hex.len() != 40 {
+        return Err(ValidationError::InvalidLength);
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ValidationError::InvalidEncoding);
+    }
+    let is_lowercase = !hex.chars().any(|c| c.is_ascii_uppercase());
+    let is_uppercase = !hex.chars().any(|c| c.is_ascii_lowercase());
+    if is_lowercase || is_uppercase {
+        return Ok(());
+    }
+    let hash = Keccak256::digest(hex.to_ascii_lowercase().as_bytes());
+    for (i, c) in hex.chars().enumerate() {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        if c.is_ascii_alphabetic() && c.is_ascii_uppercase() != (nibble >= 8) {
+            return Err(ValidationError::HashMismatch);
+        }
+    }
+    Ok(())
+}
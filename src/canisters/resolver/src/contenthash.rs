@@ -0,0 +1,143 @@
+//! Content hash of a name, the website bound to the name, encoded as ENSIP-7:
+//! `0x` + hex of `<multicodec of namespace><content id>`.
+//!
+//! * `ipfs-ns` (`0xe3`) - binary CID of IPFS, CIDv0 or CIDv1
+//! * `ipns-ns` (`0xe5`) - binary CID of IPNS name
+//! * `arweave-ns` (`0xb29910`) - 32 bytes transaction id of Arweave
+//! * `icp-ns` (`0x300000`) - principal bytes of an IC asset canister,
+//!   there is no registered namespace of IC in multicodec, so a code of the private use range is taken.
+
+use candid::Principal;
+
+use common::icrc::encode_base32;
+
+use crate::dns::ICP_CANISTER_DOMAIN;
+
+#[cfg(test)]
+mod tests;
+
+pub const MULTICODEC_IPFS_NS: u64 = 0xe3;
+pub const MULTICODEC_IPNS_NS: u64 = 0xe5;
+pub const MULTICODEC_ARWEAVE_NS: u64 = 0xb29910;
+pub const MULTICODEC_ICP_NS: u64 = 0x300000;
+
+const CONTENTHASH_PREFIX: &str = "0x";
+const MULTIHASH_SHA2_256: u64 = 0x12;
+const ARWEAVE_TX_ID_LENGTH: usize = 32;
+const IPFS_GATEWAY: &str = "https://ipfs.io";
+const ARWEAVE_GATEWAY: &str = "https://arweave.net";
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ContentHash {
+    /// textual CID, base58btc for CIDv0 and base32 for CIDv1
+    Ipfs(String),
+    Ipns(String),
+    /// base64url transaction id
+    Arweave(String),
+    IcpCanister(Principal),
+}
+
+impl ContentHash {
+    pub fn get_gateway_url(&self) -> String {
+        match self {
+            ContentHash::Ipfs(cid) => format!("{}/ipfs/{}", IPFS_GATEWAY, cid),
+            ContentHash::Ipns(cid) => format!("{}/ipns/{}", IPFS_GATEWAY, cid),
+            ContentHash::Arweave(tx_id) => format!("{}/{}", ARWEAVE_GATEWAY, tx_id),
+            ContentHash::IcpCanister(canister_id) => {
+                format!("https://{}.{}", canister_id, ICP_CANISTER_DOMAIN)
+            }
+        }
+    }
+}
+
+/// Unsigned varint of multiformats, returns the value and the rest bytes
+fn decode_varint(bytes: &[u8]) -> Result<(u64, &[u8]), String> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+    Err("invalid varint".to_string())
+}
+
+fn encode_base58(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for byte in bytes {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    std::iter::repeat('1')
+        .take(zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|digit| BASE58_ALPHABET[*digit as usize] as char),
+        )
+        .collect()
+}
+
+fn validate_multihash(bytes: &[u8]) -> Result<(), String> {
+    let (_code, rest) = decode_varint(bytes)?;
+    let (length, digest) = decode_varint(rest)?;
+    if digest.is_empty() || digest.len() as u64 != length {
+        return Err("invalid multihash digest length".to_string());
+    }
+    Ok(())
+}
+
+/// Textual CID of the binary CID
+fn decode_cid(bytes: &[u8]) -> Result<String, String> {
+    // CIDv0 is a sha2-256 multihash of dag-pb
+    if bytes.len() == 34 && bytes[0] as u64 == MULTIHASH_SHA2_256 && bytes[1] == 32 {
+        return Ok(encode_base58(bytes));
+    }
+    let (version, rest) = decode_varint(bytes)?;
+    if version != 1 {
+        return Err(format!("unsupported CID version {}", version));
+    }
+    let (_codec, multihash) = decode_varint(rest)?;
+    validate_multihash(multihash)?;
+    Ok(format!("b{}", encode_base32(bytes)))
+}
+
+pub fn decode_contenthash(value: &str) -> Result<ContentHash, String> {
+    let hex_value = value
+        .strip_prefix(CONTENTHASH_PREFIX)
+        .ok_or_else(|| format!("contenthash should start with {}", CONTENTHASH_PREFIX))?;
+    let bytes = hex::decode(hex_value).map_err(|e| e.to_string())?;
+    let (codec, content) = decode_varint(&bytes)?;
+    match codec {
+        MULTICODEC_IPFS_NS => Ok(ContentHash::Ipfs(decode_cid(content)?)),
+        MULTICODEC_IPNS_NS => Ok(ContentHash::Ipns(decode_cid(content)?)),
+        MULTICODEC_ARWEAVE_NS => {
+            if content.len() != ARWEAVE_TX_ID_LENGTH {
+                return Err("invalid Arweave transaction id length".to_string());
+            }
+            Ok(ContentHash::Arweave(base64::encode_config(
+                content,
+                base64::URL_SAFE_NO_PAD,
+            )))
+        }
+        MULTICODEC_ICP_NS => {
+            if content.is_empty() {
+                return Err("canister id is empty".to_string());
+            }
+            let canister_id = Principal::try_from_slice(content).map_err(|e| e.to_string())?;
+            Ok(ContentHash::IcpCanister(canister_id))
+        }
+        _ => Err(format!("unsupported namespace {:#x}", codec)),
+    }
+}
@@ -0,0 +1,70 @@
+use rstest::*;
+
+use super::*;
+
+const IPFS_CID_V0: &str = "QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4";
+const IPFS_CID_V1: &str = "bafybeibj6lixxzqtsb45ysdjnupvqkufgdvzqbnvmhw2kf7cfkesy7r7d4";
+
+#[rstest]
+#[case(
+    "0xe3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f",
+    ContentHash::Ipfs(IPFS_CID_V1.to_string())
+)]
+#[case(
+    "0xe301122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f",
+    ContentHash::Ipfs(IPFS_CID_V0.to_string())
+)]
+#[case(
+    "0xe501017200240801122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f",
+    ContentHash::Ipns("bafzaajaiaejcakps2f56me4qphoeq2lnd5mcvbjq5omalnlb5wsrpyrkrewh4py7".to_string())
+)]
+#[case(
+    "0x90b2ca05000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+    ContentHash::Arweave("AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8".to_string())
+)]
+#[case(
+    "0x8080c00100000000000000020101",
+    ContentHash::IcpCanister(Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap())
+)]
+fn test_decode_contenthash(#[case] value: &str, #[case] expected: ContentHash) {
+    assert_eq!(decode_contenthash(value), Ok(expected));
+}
+
+#[rstest]
+#[case("e3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f")]
+#[case("0xhello")]
+#[case("0x")]
+#[case("0xe40101701220")]
+#[case("0xe3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f")]
+#[case("0xe3020170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f")]
+#[case("0x90b2ca050001020304")]
+#[case("0x8080c001")]
+fn test_decode_contenthash_invalid(#[case] value: &str) {
+    assert!(decode_contenthash(value).is_err());
+}
+
+#[rstest]
+#[case(
+    ContentHash::Ipfs(IPFS_CID_V0.to_string()),
+    "https://ipfs.io/ipfs/QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4"
+)]
+#[case(
+    ContentHash::Ipns("k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8".to_string()),
+    "https://ipfs.io/ipns/k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8"
+)]
+#[case(
+    ContentHash::Arweave("AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8".to_string()),
+    "https://arweave.net/AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8"
+)]
+#[case(
+    ContentHash::IcpCanister(Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()),
+    "https://ryjl3-tyaaa-aaaaa-aaaba-cai.icp0.io"
+)]
+fn test_get_gateway_url(#[case] content_hash: ContentHash, #[case] expected: &str) {
+    assert_eq!(content_hash.get_gateway_url(), expected);
+}
+
+#[rstest]
+fn test_encode_base58_leading_zeros() {
+    assert_eq!(encode_base58(&[0, 0, 1]), "112");
+}
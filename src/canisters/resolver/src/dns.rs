@@ -0,0 +1,458 @@
+//! DNS over HTTPS (RFC 8484) endpoint of the resolver, so that a local resolver daemon
+//! can forward lookups of names to the canister.
+//!
+//! Typed records of a name are answered as they are, and record values are mapped to DNS records:
+//! * `TXT` - every record value as a `key=value` string
+//! * `CNAME` - `canister.icp` as `<canister_id>.icp0.io`
+//! * `URI` - `url`
+//! * `ICP_CANISTER` (private use type `65280`) - `canister.icp` as text
+//!
+//! Ttl of answers is the ttl of the record, or the ttl of the name in registry.
+//! If a name has no record of the type but a `CNAME`, the `CNAME` is answered, and it is followed
+//! when the target is a name of this resolver.
+//!
+//! Responses are NOT certified, because they depend on the id and the question of each query.
+//! They are only served by the raw domain of the canister, e.g. `<canister_id>.raw.icp0.io`,
+//! so a client should trust the boundary node, or verify the records with `get_record_value_certified`.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use serde_bytes::ByteBuf;
+
+use common::constants::{RESOLVER_KEY_ICP_CANISTER, RESOLVER_KEY_URL};
+use common::http::{HeaderField, HttpRequest, HttpResponse};
+
+use crate::certification::{get_record_values, RecordValues};
+use crate::resolver_store::{DnsRecord, DnsRecordData};
+use crate::state::STATE;
+
+#[cfg(test)]
+mod tests;
+
+pub const DNS_QUERY_PATH: &str = "/dns-query";
+pub const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
+
+pub const DNS_TYPE_A: u16 = 1;
+pub const DNS_TYPE_CNAME: u16 = 5;
+pub const DNS_TYPE_MX: u16 = 15;
+pub const DNS_TYPE_TXT: u16 = 16;
+pub const DNS_TYPE_AAAA: u16 = 28;
+pub const DNS_TYPE_SRV: u16 = 33;
+pub const DNS_TYPE_URI: u16 = 256;
+pub const DNS_TYPE_CAA: u16 = 257;
+pub const DNS_TYPE_ICP_CANISTER: u16 = 65280;
+pub const DNS_CLASS_IN: u16 = 1;
+
+pub const DNS_RCODE_NO_ERROR: u8 = 0;
+pub const DNS_RCODE_NAME_ERROR: u8 = 3;
+pub const DNS_RCODE_NOT_IMPLEMENTED: u8 = 4;
+
+const DNS_HEADER_LENGTH: usize = 12;
+const DNS_MAX_NAME_LENGTH: usize = 255;
+const DNS_MAX_STRING_LENGTH: usize = 255;
+const DNS_FLAG_RESPONSE: u16 = 0x8000;
+const DNS_FLAG_AUTHORITATIVE: u16 = 0x0400;
+const DNS_FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const DNS_OPCODE_MASK: u16 = 0x7800;
+// pointer to the name of the question, which is right after the header
+const DNS_QUESTION_NAME_POINTER: u16 = 0xc000 | DNS_HEADER_LENGTH as u16;
+// a loop of CNAME is stopped by the limit
+const DNS_MAX_CNAME_CHAIN: usize = 8;
+const URI_PRIORITY: u16 = 10;
+const URI_WEIGHT: u16 = 1;
+pub(crate) const ICP_CANISTER_DOMAIN: &str = "icp0.io";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DnsQuery {
+    pub id: u16,
+    pub flags: u16,
+    pub question: DnsQuestion,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DnsAnswer {
+    /// Owner of the answer if it is not the name of the question, e.g. the target of a `CNAME`
+    pub name: Option<String>,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: Vec<u8>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "unexpected end of message".to_string())
+}
+
+/// Parse the question of a query, only one question is supported.
+/// Additional records such as EDNS are ignored.
+pub fn parse_dns_query(bytes: &[u8]) -> Result<DnsQuery, String> {
+    if bytes.len() < DNS_HEADER_LENGTH {
+        return Err("message is shorter than header".to_string());
+    }
+    let id = read_u16(bytes, 0)?;
+    let flags = read_u16(bytes, 2)?;
+    if flags & DNS_FLAG_RESPONSE != 0 {
+        return Err("message is not a query".to_string());
+    }
+    let question_count = read_u16(bytes, 4)?;
+    if question_count != 1 {
+        return Err(format!("expect 1 question, got {}", question_count));
+    }
+
+    let mut offset = DNS_HEADER_LENGTH;
+    let mut labels = vec![];
+    loop {
+        let length = *bytes
+            .get(offset)
+            .ok_or_else(|| "unexpected end of message".to_string())? as usize;
+        offset += 1;
+        if length == 0 {
+            break;
+        }
+        // compression is not expected in the question of a query
+        if length > 63 {
+            return Err("invalid label length".to_string());
+        }
+        let label = bytes
+            .get(offset..offset + length)
+            .ok_or_else(|| "unexpected end of message".to_string())?;
+        let label = std::str::from_utf8(label).map_err(|_| "invalid label".to_string())?;
+        labels.push(label.to_ascii_lowercase());
+        offset += length;
+    }
+    let name = labels.join(".");
+    if name.is_empty() || name.len() > DNS_MAX_NAME_LENGTH {
+        return Err("invalid name length".to_string());
+    }
+    let qtype = read_u16(bytes, offset)?;
+    let qclass = read_u16(bytes, offset + 2)?;
+    Ok(DnsQuery {
+        id,
+        flags,
+        question: DnsQuestion {
+            name,
+            qtype,
+            qclass,
+        },
+    })
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+    bytes.push(0);
+    bytes
+}
+
+/// Character strings of the value, split into chunks of 255 bytes
+fn encode_strings(value: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    for chunk in value.as_bytes().chunks(DNS_MAX_STRING_LENGTH) {
+        bytes.push(chunk.len() as u8);
+        bytes.extend_from_slice(chunk);
+    }
+    if bytes.is_empty() {
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn get_value<'a>(values: &'a RecordValues, key: &str) -> Option<&'a str> {
+    values
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+fn get_typed_record_type(data: &DnsRecordData) -> u16 {
+    match data {
+        DnsRecordData::A(_) => DNS_TYPE_A,
+        DnsRecordData::AAAA(_) => DNS_TYPE_AAAA,
+        DnsRecordData::CNAME(_) => DNS_TYPE_CNAME,
+        DnsRecordData::TXT(_) => DNS_TYPE_TXT,
+        DnsRecordData::MX { .. } => DNS_TYPE_MX,
+        DnsRecordData::SRV { .. } => DNS_TYPE_SRV,
+        DnsRecordData::CAA { .. } => DNS_TYPE_CAA,
+    }
+}
+
+/// Wire format of the data, the data is validated when the record is set
+fn encode_typed_record_data(data: &DnsRecordData) -> Vec<u8> {
+    match data {
+        DnsRecordData::A(address) => Ipv4Addr::from_str(address)
+            .map(|address| address.octets().to_vec())
+            .unwrap_or_default(),
+        DnsRecordData::AAAA(address) => Ipv6Addr::from_str(address)
+            .map(|address| address.octets().to_vec())
+            .unwrap_or_default(),
+        DnsRecordData::CNAME(name) => encode_name(name),
+        DnsRecordData::TXT(value) => encode_strings(value),
+        DnsRecordData::MX {
+            preference,
+            exchange,
+        } => {
+            let mut data = preference.to_be_bytes().to_vec();
+            data.extend(encode_name(exchange));
+            data
+        }
+        DnsRecordData::SRV {
+            priority,
+            weight,
+            port,
+            target,
+        } => {
+            let mut data = vec![];
+            data.extend_from_slice(&priority.to_be_bytes());
+            data.extend_from_slice(&weight.to_be_bytes());
+            data.extend_from_slice(&port.to_be_bytes());
+            data.extend(encode_name(target));
+            data
+        }
+        DnsRecordData::CAA { flags, tag, value } => {
+            let mut data = vec![*flags, tag.len() as u8];
+            data.extend_from_slice(tag.as_bytes());
+            data.extend_from_slice(value.as_bytes());
+            data
+        }
+    }
+}
+
+fn to_dns_ttl(ttl: u64) -> u32 {
+    ttl.min(u32::MAX as u64) as u32
+}
+
+/// Answers of the type from typed records and record values of a name,
+/// `CNAME` mapped from `canister.icp` is only used if there is no typed `CNAME` record.
+pub fn get_dns_answers(
+    qtype: u16,
+    values: &RecordValues,
+    records: &[DnsRecord],
+    ttl: u64,
+) -> Vec<DnsAnswer> {
+    let mut answers: Vec<DnsAnswer> = records
+        .iter()
+        .filter(|record| get_typed_record_type(&record.data) == qtype)
+        .map(|record| DnsAnswer {
+            name: None,
+            rtype: qtype,
+            ttl: to_dns_ttl(record.ttl.unwrap_or(ttl)),
+            data: encode_typed_record_data(&record.data),
+        })
+        .collect();
+    let new_answer = |data: Vec<u8>| DnsAnswer {
+        name: None,
+        rtype: qtype,
+        ttl: to_dns_ttl(ttl),
+        data,
+    };
+    match qtype {
+        DNS_TYPE_TXT => answers.extend(
+            values
+                .iter()
+                .map(|(key, value)| new_answer(encode_strings(&format!("{}={}", key, value)))),
+        ),
+        DNS_TYPE_CNAME if answers.is_empty() => answers.extend(
+            get_value(values, RESOLVER_KEY_ICP_CANISTER).map(|canister| {
+                new_answer(encode_name(&format!(
+                    "{}.{}",
+                    canister.to_ascii_lowercase(),
+                    ICP_CANISTER_DOMAIN
+                )))
+            }),
+        ),
+        DNS_TYPE_URI => answers.extend(get_value(values, RESOLVER_KEY_URL).map(|url| {
+            let mut data = vec![];
+            data.extend_from_slice(&URI_PRIORITY.to_be_bytes());
+            data.extend_from_slice(&URI_WEIGHT.to_be_bytes());
+            data.extend_from_slice(url.as_bytes());
+            new_answer(data)
+        })),
+        DNS_TYPE_ICP_CANISTER => answers.extend(
+            get_value(values, RESOLVER_KEY_ICP_CANISTER)
+                .map(|canister| new_answer(encode_strings(canister))),
+        ),
+        _ => {}
+    }
+    answers
+}
+
+/// Encode the response of the query, answers are only available with `DNS_RCODE_NO_ERROR`
+pub fn encode_dns_response(query: &DnsQuery, rcode: u8, answers: &[DnsAnswer]) -> Vec<u8> {
+    let flags = DNS_FLAG_RESPONSE
+        | (query.flags & (DNS_OPCODE_MASK | DNS_FLAG_RECURSION_DESIRED))
+        | DNS_FLAG_AUTHORITATIVE
+        | rcode as u16;
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&query.id.to_be_bytes());
+    bytes.extend_from_slice(&flags.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes());
+
+    bytes.extend(encode_name(&query.question.name));
+    bytes.extend_from_slice(&query.question.qtype.to_be_bytes());
+    bytes.extend_from_slice(&query.question.qclass.to_be_bytes());
+
+    for answer in answers {
+        match &answer.name {
+            Some(name) => bytes.extend(encode_name(name)),
+            None => bytes.extend_from_slice(&DNS_QUESTION_NAME_POINTER.to_be_bytes()),
+        }
+        bytes.extend_from_slice(&answer.rtype.to_be_bytes());
+        bytes.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        bytes.extend_from_slice(&answer.ttl.to_be_bytes());
+        bytes.extend_from_slice(&(answer.data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&answer.data);
+    }
+    bytes
+}
+
+/// Record values, typed records and ttl of a name, None if the resolver of the name is not created
+fn get_dns_zone(name: &str) -> Option<(RecordValues, Vec<DnsRecord>, u64)> {
+    STATE.with(|s| {
+        let values = get_record_values(s, name)?;
+        let store = s.resolver_store.borrow();
+        let resolver = store.get_resolver(name)?;
+        Some((
+            values,
+            resolver.get_dns_records().to_vec(),
+            resolver.get_ttl(),
+        ))
+    })
+}
+
+/// Target of the `CNAME` of a name, a typed `CNAME` record takes the place of `canister.icp`
+fn get_cname_target(values: &RecordValues, records: &[DnsRecord]) -> Option<String> {
+    records
+        .iter()
+        .find_map(|record| match &record.data {
+            DnsRecordData::CNAME(target) => Some(target.to_ascii_lowercase()),
+            _ => None,
+        })
+        .or_else(|| {
+            get_value(values, RESOLVER_KEY_ICP_CANISTER).map(|canister| {
+                format!("{}.{}", canister.to_ascii_lowercase(), ICP_CANISTER_DOMAIN)
+            })
+        })
+}
+
+/// Answers of the question, None if the name is not found.
+/// The `CNAME` of a name without records of the type is answered and followed in this resolver.
+fn get_question_answers(question: &DnsQuestion) -> Option<Vec<DnsAnswer>> {
+    let (mut values, mut records, mut ttl) = get_dns_zone(&question.name)?;
+    if question.qclass != DNS_CLASS_IN {
+        return Some(vec![]);
+    }
+    let mut owner: Option<String> = None;
+    let mut answers = vec![];
+    for _ in 0..DNS_MAX_CNAME_CHAIN {
+        let with_owner = |mut answer: DnsAnswer| {
+            answer.name = owner.clone();
+            answer
+        };
+        let typed_answers = get_dns_answers(question.qtype, &values, &records, ttl);
+        if !typed_answers.is_empty() || question.qtype == DNS_TYPE_CNAME {
+            answers.extend(typed_answers.into_iter().map(with_owner));
+            break;
+        }
+        let cname_answers = get_dns_answers(DNS_TYPE_CNAME, &values, &records, ttl);
+        if cname_answers.is_empty() {
+            break;
+        }
+        answers.extend(cname_answers.into_iter().map(with_owner));
+        let target = get_cname_target(&values, &records).unwrap();
+        match get_dns_zone(&target) {
+            Some(zone) => {
+                (values, records, ttl) = zone;
+                owner = Some(target);
+            }
+            None => break,
+        }
+    }
+    Some(answers)
+}
+
+/// Answer the query with record values and ttl of the name
+pub(crate) fn resolve_dns_query(query: &DnsQuery) -> (Vec<u8>, Option<u32>) {
+    if query.flags & DNS_OPCODE_MASK != 0 {
+        return (
+            encode_dns_response(query, DNS_RCODE_NOT_IMPLEMENTED, &[]),
+            None,
+        );
+    }
+    match get_question_answers(&query.question) {
+        Some(answers) => {
+            let max_age = answers.iter().map(|answer| answer.ttl).min();
+            (
+                encode_dns_response(query, DNS_RCODE_NO_ERROR, &answers),
+                max_age,
+            )
+        }
+        None => (encode_dns_response(query, DNS_RCODE_NAME_ERROR, &[]), None),
+    }
+}
+
+fn get_dns_message(request: &HttpRequest) -> Result<Vec<u8>, HttpResponse> {
+    match request.method.to_uppercase().as_str() {
+        "GET" => {
+            let dns = request
+                .get_query_value("dns")
+                .ok_or_else(|| HttpResponse::string(400, "Missing dns parameter"))?;
+            base64::decode_config(dns.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+                .map_err(|_| HttpResponse::string(400, "Invalid dns parameter"))
+        }
+        "POST" => {
+            let is_dns_message = request.headers.iter().any(|HeaderField(name, value)| {
+                name.eq_ignore_ascii_case("content-type") && value == DNS_MESSAGE_CONTENT_TYPE
+            });
+            if is_dns_message {
+                Ok(request.body.clone())
+            } else {
+                Err(HttpResponse::string(415, "Unsupported content type"))
+            }
+        }
+        _ => Err(HttpResponse::string(405, "Method not allowed")),
+    }
+}
+
+/// Response of a DNS over HTTPS request, both GET with `dns` parameter and POST are supported
+pub(crate) fn get_dns_http_response(request: &HttpRequest) -> HttpResponse {
+    let message = match get_dns_message(request) {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+    let (body, max_age) = match parse_dns_query(&message) {
+        Ok(query) => resolve_dns_query(&query),
+        Err(err) => return HttpResponse::string(400, &format!("Invalid DNS query: {}", err)),
+    };
+    let mut headers = vec![HeaderField(
+        "Content-Type".to_string(),
+        DNS_MESSAGE_CONTENT_TYPE.to_string(),
+    )];
+    if let Some(max_age) = max_age {
+        headers.push(HeaderField(
+            "Cache-Control".to_string(),
+            format!("max-age={}", max_age),
+        ));
+    }
+    HttpResponse {
+        status_code: 200,
+        headers,
+        body: ByteBuf::from(body),
+        streaming_strategy: None,
+    }
+}
@@ -0,0 +1,338 @@
+use rstest::*;
+
+use common::constants::{DEFAULT_TTL, RESOLVER_KEY_GITHUB};
+use test_common::ic_api::init_test;
+
+use super::*;
+
+const CANISTER_ID: &str = "qoctq-giaaa-aaaaa-aaaea-cai";
+
+fn encode_query(name: &str, qtype: u16) -> Vec<u8> {
+    let mut bytes = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    bytes.extend(encode_name(name));
+    bytes.extend_from_slice(&qtype.to_be_bytes());
+    bytes.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    bytes
+}
+
+fn get_rcode(response: &[u8]) -> u8 {
+    response[3] & 0x0f
+}
+
+fn get_answer_count(response: &[u8]) -> u16 {
+    u16::from_be_bytes([response[6], response[7]])
+}
+
+fn add_test_resolver(name: &str, ttl: Option<u64>) {
+    STATE.with(|s| {
+        let mut store = s.resolver_store.borrow_mut();
+        store.ensure_created(name);
+        let mut resolver = store.get_resolver(name).unwrap();
+        resolver.set_record_value(
+            RESOLVER_KEY_ICP_CANISTER.to_string(),
+            CANISTER_ID.to_string(),
+        );
+        resolver.set_record_value(RESOLVER_KEY_URL.to_string(), "https://hello.ic".to_string());
+        resolver.set_record_value(RESOLVER_KEY_GITHUB.to_string(), "icnaming".to_string());
+        if let Some(ttl) = ttl {
+            resolver.set_ttl(ttl);
+        }
+        store.set_resolver(resolver);
+    });
+}
+
+fn dns_request(method: &str, url: &str, headers: Vec<HeaderField>, body: Vec<u8>) -> HttpRequest {
+    HttpRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        headers,
+        body,
+    }
+}
+
+#[rstest]
+fn test_parse_dns_query() {
+    let query = parse_dns_query(&encode_query("Hello.IC", DNS_TYPE_TXT)).unwrap();
+
+    assert_eq!(query.id, 0x1234);
+    assert_eq!(
+        query.question,
+        DnsQuestion {
+            name: "hello.ic".to_string(),
+            qtype: DNS_TYPE_TXT,
+            qclass: DNS_CLASS_IN,
+        }
+    );
+}
+
+#[rstest]
+#[case(vec![0x12, 0x34])]
+#[case({
+    let mut bytes = encode_query("hello.ic", DNS_TYPE_TXT);
+    bytes[2] |= 0x80;
+    bytes
+})]
+#[case({
+    let mut bytes = encode_query("hello.ic", DNS_TYPE_TXT);
+    bytes[5] = 2;
+    bytes
+})]
+#[case({
+    let mut bytes = encode_query("hello.ic", DNS_TYPE_TXT);
+    bytes.truncate(bytes.len() - 3);
+    bytes
+})]
+fn test_parse_dns_query_invalid(#[case] bytes: Vec<u8>) {
+    assert!(parse_dns_query(&bytes).is_err());
+}
+
+#[rstest]
+fn test_get_dns_answers() {
+    let values = vec![
+        (
+            RESOLVER_KEY_ICP_CANISTER.to_string(),
+            CANISTER_ID.to_string(),
+        ),
+        (RESOLVER_KEY_URL.to_string(), "https://hello.ic".to_string()),
+    ];
+
+    let records = get_dns_answers(DNS_TYPE_TXT, &values, &[], 300);
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].ttl, 300);
+    assert_eq!(
+        records[0].data,
+        encode_strings(&format!("canister.icp={}", CANISTER_ID))
+    );
+
+    let records = get_dns_answers(DNS_TYPE_CNAME, &values, &[], 300);
+    assert_eq!(
+        records[0].data,
+        encode_name(&format!("{}.icp0.io", CANISTER_ID))
+    );
+
+    let records = get_dns_answers(DNS_TYPE_URI, &values, &[], 300);
+    assert_eq!(&records[0].data[..4], &[0, 10, 0, 1]);
+    assert_eq!(&records[0].data[4..], b"https://hello.ic");
+
+    let records = get_dns_answers(DNS_TYPE_ICP_CANISTER, &values, &[], 300);
+    assert_eq!(records[0].data, encode_strings(CANISTER_ID));
+
+    assert!(get_dns_answers(1, &values, &[], 300).is_empty());
+}
+
+#[rstest]
+fn test_get_dns_answers_typed_records() {
+    let values = vec![(
+        RESOLVER_KEY_ICP_CANISTER.to_string(),
+        CANISTER_ID.to_string(),
+    )];
+    let records = vec![
+        DnsRecord {
+            ttl: Some(60),
+            data: DnsRecordData::A("127.0.0.1".to_string()),
+        },
+        DnsRecord {
+            ttl: None,
+            data: DnsRecordData::A("127.0.0.2".to_string()),
+        },
+        DnsRecord {
+            ttl: None,
+            data: DnsRecordData::CNAME("hello.example.com".to_string()),
+        },
+        DnsRecord {
+            ttl: None,
+            data: DnsRecordData::MX {
+                preference: 10,
+                exchange: "mail.example.com".to_string(),
+            },
+        },
+        DnsRecord {
+            ttl: None,
+            data: DnsRecordData::CAA {
+                flags: 0,
+                tag: "issue".to_string(),
+                value: "letsencrypt.org".to_string(),
+            },
+        },
+    ];
+
+    let answers = get_dns_answers(DNS_TYPE_A, &values, &records, 300);
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0].ttl, 60);
+    assert_eq!(answers[0].data, vec![127, 0, 0, 1]);
+    assert_eq!(answers[1].ttl, 300);
+
+    // typed CNAME takes the place of the one mapped from canister.icp
+    let answers = get_dns_answers(DNS_TYPE_CNAME, &values, &records, 300);
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].data, encode_name("hello.example.com"));
+
+    let answers = get_dns_answers(DNS_TYPE_MX, &values, &records, 300);
+    assert_eq!(&answers[0].data[..2], &[0, 10]);
+    assert_eq!(&answers[0].data[2..], encode_name("mail.example.com"));
+
+    let answers = get_dns_answers(DNS_TYPE_CAA, &values, &records, 300);
+    assert_eq!(answers[0].data, b"\x00\x05issueletsencrypt.org".to_vec());
+
+    assert!(get_dns_answers(DNS_TYPE_AAAA, &values, &records, 300).is_empty());
+}
+
+#[rstest]
+fn test_encode_strings_long_value() {
+    let value = "a".repeat(300);
+
+    let bytes = encode_strings(&value);
+
+    assert_eq!(bytes.len(), 302);
+    assert_eq!(bytes[0], 255);
+    assert_eq!(bytes[256], 45);
+}
+
+#[rstest]
+fn test_resolve_dns_query(_init_test: ()) {
+    add_test_resolver("hello.ic", Some(300));
+    let query = parse_dns_query(&encode_query("hello.ic", DNS_TYPE_TXT)).unwrap();
+
+    let (response, max_age) = resolve_dns_query(&query);
+
+    assert_eq!(max_age, Some(300));
+    assert_eq!(&response[..2], &[0x12, 0x34]);
+    assert_eq!(response[2] & 0x80, 0x80);
+    assert_eq!(get_rcode(&response), DNS_RCODE_NO_ERROR);
+    assert_eq!(get_answer_count(&response), 3);
+}
+
+#[rstest]
+fn test_resolve_dns_query_default_ttl(_init_test: ()) {
+    add_test_resolver("hello.ic", None);
+    let query = parse_dns_query(&encode_query("hello.ic", DNS_TYPE_CNAME)).unwrap();
+
+    let (response, max_age) = resolve_dns_query(&query);
+
+    assert_eq!(max_age, Some(DEFAULT_TTL as u32));
+    assert_eq!(get_answer_count(&response), 1);
+}
+
+#[rstest]
+fn test_resolve_dns_query_name_not_found(_init_test: ()) {
+    let query = parse_dns_query(&encode_query("hello.ic", DNS_TYPE_TXT)).unwrap();
+
+    let (response, max_age) = resolve_dns_query(&query);
+
+    assert_eq!(max_age, None);
+    assert_eq!(get_rcode(&response), DNS_RCODE_NAME_ERROR);
+    assert_eq!(get_answer_count(&response), 0);
+}
+
+fn add_typed_records(name: &str, records: Vec<DnsRecordData>) {
+    STATE.with(|s| {
+        let mut store = s.resolver_store.borrow_mut();
+        store.ensure_created(name);
+        store.update_resolver(name, |resolver| {
+            resolver.set_dns_records(
+                records
+                    .into_iter()
+                    .map(|data| DnsRecord { ttl: None, data })
+                    .collect(),
+            )
+        });
+    });
+}
+
+#[rstest]
+fn test_resolve_dns_query_follow_cname(_init_test: ()) {
+    add_typed_records(
+        "www.hello.ic",
+        vec![DnsRecordData::CNAME("Hello.ic".to_string())],
+    );
+    add_typed_records("hello.ic", vec![DnsRecordData::A("127.0.0.1".to_string())]);
+    let query = parse_dns_query(&encode_query("www.hello.ic", DNS_TYPE_A)).unwrap();
+
+    let answers = get_question_answers(&query.question).unwrap();
+
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0].name, None);
+    assert_eq!(answers[0].rtype, DNS_TYPE_CNAME);
+    assert_eq!(answers[1].name, Some("hello.ic".to_string()));
+    assert_eq!(answers[1].rtype, DNS_TYPE_A);
+    assert_eq!(answers[1].data, vec![127, 0, 0, 1]);
+    let (response, _) = resolve_dns_query(&query);
+    assert_eq!(get_answer_count(&response), 2);
+    assert!(response.ends_with(&[127, 0, 0, 1]));
+}
+
+#[rstest]
+fn test_resolve_dns_query_cname_out_of_resolver(_init_test: ()) {
+    add_test_resolver("hello.ic", None);
+    let query = parse_dns_query(&encode_query("hello.ic", DNS_TYPE_A)).unwrap();
+
+    let answers = get_question_answers(&query.question).unwrap();
+
+    // canister.icp is answered as CNAME, and the client follows it
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].rtype, DNS_TYPE_CNAME);
+}
+
+#[rstest]
+fn test_resolve_dns_query_cname_loop(_init_test: ()) {
+    add_typed_records("a.ic", vec![DnsRecordData::CNAME("b.ic".to_string())]);
+    add_typed_records("b.ic", vec![DnsRecordData::CNAME("a.ic".to_string())]);
+    let query = parse_dns_query(&encode_query("a.ic", DNS_TYPE_A)).unwrap();
+
+    let answers = get_question_answers(&query.question).unwrap();
+
+    assert_eq!(answers.len(), DNS_MAX_CNAME_CHAIN);
+    assert!(answers.iter().all(|answer| answer.rtype == DNS_TYPE_CNAME));
+}
+
+#[rstest]
+fn test_get_dns_http_response_get(_init_test: ()) {
+    add_test_resolver("hello.ic", Some(300));
+    let dns = base64::encode_config(
+        encode_query("hello.ic", DNS_TYPE_URI),
+        base64::URL_SAFE_NO_PAD,
+    );
+    let request = dns_request("GET", &format!("/dns-query?dns={}", dns), vec![], vec![]);
+
+    let response = get_dns_http_response(&request);
+
+    assert_eq!(response.status_code, 200);
+    assert_eq!(response.headers[0].1, DNS_MESSAGE_CONTENT_TYPE);
+    assert_eq!(response.headers[1].1, "max-age=300");
+    assert_eq!(get_answer_count(&response.body), 1);
+}
+
+#[rstest]
+fn test_get_dns_http_response_post(_init_test: ()) {
+    add_test_resolver("hello.ic", Some(300));
+    let request = dns_request(
+        "POST",
+        "/dns-query",
+        vec![HeaderField(
+            "content-type".to_string(),
+            DNS_MESSAGE_CONTENT_TYPE.to_string(),
+        )],
+        encode_query("hello.ic", DNS_TYPE_ICP_CANISTER),
+    );
+
+    let response = get_dns_http_response(&request);
+
+    assert_eq!(response.status_code, 200);
+    assert_eq!(get_rcode(&response.body), DNS_RCODE_NO_ERROR);
+    assert_eq!(get_answer_count(&response.body), 1);
+}
+
+#[rstest]
+#[case(dns_request("GET", "/dns-query", vec![], vec![]), 400)]
+#[case(dns_request("GET", "/dns-query?dns=AAAA", vec![], vec![]), 400)]
+#[case(dns_request("POST", "/dns-query", vec![], encode_query("hello.ic", DNS_TYPE_TXT)), 415)]
+#[case(dns_request("PUT", "/dns-query", vec![], vec![]), 405)]
+fn test_get_dns_http_response_invalid(
+    _init_test: (),
+    #[case] request: HttpRequest,
+    #[case] status_code: u16,
+) {
+    let response = get_dns_http_response(&request);
+
+    assert_eq!(response.status_code, status_code);
+}
@@ -5,6 +5,8 @@ use serde_bytes::ByteBuf;
 use common::http::{HeaderField, HttpRequest, HttpResponse};
 use common::metrics_encoder::MetricsEncoder;
 
+use crate::dns::{get_dns_http_response, DNS_QUERY_PATH};
+use crate::profile::get_profile_http_response;
 use crate::stats_service::encode_metrics;
 
 #[query]
@@ -40,11 +42,10 @@ fn http_request(req: HttpRequest) -> HttpResponse {
                 },
             }
         }
-        request_path => HttpResponse {
-            status_code: 404,
-            headers: vec![],
-            body: ByteBuf::from(format!("Asset {} not found.", request_path)),
-            streaming_strategy: None,
-        },
+        DNS_QUERY_PATH => get_dns_http_response(&req),
+        request_path => {
+            let certificate = ic_cdk::api::data_certificate().unwrap_or_default();
+            get_profile_http_response(request_path, &certificate)
+        }
     }
 }
@@ -1,5 +1,11 @@
+mod certification;
 mod coinaddress;
+mod contenthash;
+mod dns;
 mod http;
+mod offchain;
+mod profile;
+mod record_history_store;
 mod resolver_store;
 mod service;
 mod set_record_value_input;
@@ -16,13 +22,20 @@ use stats_service::*;
 use std::collections::HashMap;
 
 use candid::{candid_method, CandidType, Principal};
+use common::certified_map::CertifiedValue;
 use common::CallContext;
 
 use ic_cdk_macros::*;
 
 use common::errors::{BooleanActorResponse, ErrorInfo, ServiceResult};
+use common::icrc::Account;
 use common::named_canister_ids::CanisterNames;
 
+use crate::certification::RecordValues;
+use crate::offchain::{OffchainResponse, ResolvedRecord};
+use crate::record_history_store::RecordHistoryEntry;
+use crate::resolver_store::DnsRecord;
+use crate::reverse_resolver_store::ReverseResolution;
 use crate::service::{ImportRecordValueRequest, ResolverService};
 
 use crate::state::InitArgs;
@@ -90,6 +103,49 @@ impl GetRecordValueResponse {
     }
 }
 
+/// Get the values for the name with a certificate.
+/// Returns sorted values, the witness of `record_value/{name}` and the certificate.
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_record_value_certified")]
+#[candid_method(query)]
+fn get_record_value_certified(name: String) -> GetRecordValueCertifiedResponse {
+    let service = ResolverService::default();
+    let result = service
+        .get_record_value_certified(name.as_str())
+        .map(|value| value.with_certificate());
+    GetRecordValueCertifiedResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetRecordValueCertifiedResponse {
+    Ok(CertifiedValue<RecordValues>),
+    Err(ErrorInfo),
+}
+
+impl GetRecordValueCertifiedResponse {
+    pub fn new(result: ServiceResult<CertifiedValue<RecordValues>>) -> Self {
+        match result {
+            Ok(value) => GetRecordValueCertifiedResponse::Ok(value),
+            Err(err) => GetRecordValueCertifiedResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Set ttl of the name, only registry can call it.
+/// Returns true if the ttl is set.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `ttl` - ttl of the name in seconds
+#[update(name = "set_ttl")]
+#[candid_method(update)]
+fn set_ttl(name: String, ttl: u64) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service.set_ttl(call_context, &name, ttl);
+    BooleanActorResponse::new(result)
+}
+
 #[update(name = "remove_resolvers")]
 #[candid_method(update)]
 fn remove_resolvers(names: Vec<String>) -> BooleanActorResponse {
@@ -99,6 +155,19 @@ fn remove_resolvers(names: Vec<String>) -> BooleanActorResponse {
     BooleanActorResponse::new(result)
 }
 
+/// Remove primary names of the names once their owner changed, only the registry is allowed.
+/// Returns true if primary names are removed.
+///
+/// * `names` - names. e.g. `hello.ic`
+#[update(name = "reset_primary_names")]
+#[candid_method(update)]
+fn reset_primary_names(names: Vec<String>) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let service = ResolverService::default();
+    let result = service.reset_primary_names(call_context, names);
+    BooleanActorResponse::new(result)
+}
+
 #[query(name = "reverse_resolve_principal")]
 #[candid_method(query)]
 fn reverse_resolve_principal(principal: Principal) -> ReverseResolvePrincipalResponse {
@@ -107,6 +176,134 @@ fn reverse_resolve_principal(principal: Principal) -> ReverseResolvePrincipalRes
     ReverseResolvePrincipalResponse::new(result)
 }
 
+/// Get the address of the name for a coin type.
+/// Returns the address, or none if it is not set or the coin type is not supported.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `coin_type` - SLIP-44 coin type, e.g. `60` for ETH, see ENSIP-9
+#[query(name = "get_address")]
+#[candid_method(query)]
+fn get_address(name: String, coin_type: u32) -> GetAddressResponse {
+    let service = ResolverService::default();
+    let result = service.get_address(&name, coin_type);
+    GetAddressResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetAddressResponse {
+    Ok(Option<String>),
+    Err(ErrorInfo),
+}
+
+impl GetAddressResponse {
+    pub fn new(result: ServiceResult<Option<String>>) -> Self {
+        match result {
+            Ok(address) => GetAddressResponse::Ok(address),
+            Err(err) => GetAddressResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Get gateway url of the website bound to the name by `contenthash`, e.g. `https://ipfs.io/ipfs/<cid>`
+///
+/// * `name` - a name. e.g. `hello.ic`
+#[query(name = "get_content_url")]
+#[candid_method(query)]
+fn get_content_url(name: String) -> GetAddressResponse {
+    let service = ResolverService::default();
+    let result = service.get_content_url(&name);
+    GetAddressResponse::new(result)
+}
+
+/// Resolve the ICRC-1 account of a name to receive tokens of a ledger.
+/// Returns `icrc1.<ledger>` if set, otherwise `icrc1_account.icp`, otherwise default account of `principal.icp`.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `ledger` - canister id of the ICRC-1 ledger
+#[query(name = "resolve_payment_account")]
+#[candid_method(query)]
+fn resolve_payment_account(name: String, ledger: Principal) -> ResolvePaymentAccountResponse {
+    let service = ResolverService::default();
+    let result = service.resolve_payment_account(&name, &ledger);
+    ResolvePaymentAccountResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum ResolvePaymentAccountResponse {
+    Ok(Option<Account>),
+    Err(ErrorInfo),
+}
+
+impl ResolvePaymentAccountResponse {
+    pub fn new(result: ServiceResult<Option<Account>>) -> Self {
+        match result {
+            Ok(account) => ResolvePaymentAccountResponse::Ok(account),
+            Err(err) => ResolvePaymentAccountResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Resolve a record of a name, returns where to look it up if it is not stored on chain but the name has an offchain gateway.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `key` - a resolver key. e.g. `description`
+#[query(name = "resolve_record")]
+#[candid_method(query)]
+fn resolve_record(name: String, key: String) -> ResolveRecordResponse {
+    let service = ResolverService::default();
+    let result = service.resolve_record(&name, &key);
+    ResolveRecordResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum ResolveRecordResponse {
+    Ok(Option<ResolvedRecord>),
+    Err(ErrorInfo),
+}
+
+impl ResolveRecordResponse {
+    pub fn new(result: ServiceResult<Option<ResolvedRecord>>) -> Self {
+        match result {
+            Ok(record) => ResolveRecordResponse::Ok(record),
+            Err(err) => ResolveRecordResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Verify the response of the offchain gateway against the signer stored on chain.
+/// Returns the value if the signature is valid and the response is not expired.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `key` - a resolver key. e.g. `description`
+/// * `response` - response of the gateway
+#[query(name = "verify_offchain_response")]
+#[candid_method(query)]
+fn verify_offchain_response(
+    name: String,
+    key: String,
+    response: OffchainResponse,
+) -> VerifyOffchainResponseResponse {
+    let call_context = CallContext::from_ic();
+    let service = ResolverService::default();
+    let result = service.verify_offchain_response(&call_context, &name, &key, &response);
+    VerifyOffchainResponseResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum VerifyOffchainResponseResponse {
+    Ok(String),
+    Err(ErrorInfo),
+}
+
+impl VerifyOffchainResponseResponse {
+    pub fn new(result: ServiceResult<String>) -> Self {
+        match result {
+            Ok(value) => VerifyOffchainResponseResponse::Ok(value),
+            Err(err) => VerifyOffchainResponseResponse::Err(err.into()),
+        }
+    }
+}
+
 #[derive(CandidType)]
 pub enum ReverseResolvePrincipalResponse {
     Ok(Option<String>),
@@ -122,6 +319,37 @@ impl ReverseResolvePrincipalResponse {
     }
 }
 
+/// Get the primary name of the principal with a certificate.
+/// Returns the name, the witness of `reverse_resolve/{principal}` and the certificate.
+///
+/// * `principal` - a principal.
+#[query(name = "reverse_resolve_principal_certified")]
+#[candid_method(query)]
+fn reverse_resolve_principal_certified(
+    principal: Principal,
+) -> ReverseResolvePrincipalCertifiedResponse {
+    let service = ResolverService::default();
+    let result = service
+        .reverse_resolve_principal_certified(principal)
+        .map(|value| value.with_certificate());
+    ReverseResolvePrincipalCertifiedResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum ReverseResolvePrincipalCertifiedResponse {
+    Ok(CertifiedValue<String>),
+    Err(ErrorInfo),
+}
+
+impl ReverseResolvePrincipalCertifiedResponse {
+    pub fn new(result: ServiceResult<CertifiedValue<String>>) -> Self {
+        match result {
+            Ok(value) => ReverseResolvePrincipalCertifiedResponse::Ok(value),
+            Err(err) => ReverseResolvePrincipalCertifiedResponse::Err(err.into()),
+        }
+    }
+}
+
 #[derive(CandidType)]
 pub enum BatchGetReverseResolvePrincipalResponse {
     Ok(HashMap<Principal, Option<String>>),
@@ -147,6 +375,197 @@ fn batch_get_reverse_resolve_principal(
     BatchGetReverseResolvePrincipalResponse::new(result)
 }
 
+/// Get the primary name of the principal used in the context, e.g. a dApp canister.
+/// Returns the verified primary name in the context, or the default one if there is no such name.
+///
+/// * `principal` - a principal.
+/// * `context` - a context canister.
+#[query(name = "reverse_resolve_principal_in_context")]
+#[candid_method(query)]
+fn reverse_resolve_principal_in_context(
+    principal: Principal,
+    context: Principal,
+) -> ReverseResolvePrincipalResponse {
+    let service = ResolverService::default();
+    let result = service.reverse_resolve_principal_in_context(principal, Some(context));
+    ReverseResolvePrincipalResponse::new(result)
+}
+
+/// Verify the primary names of the principals against `principal.icp` of the names.
+/// Returns the verified primary name or the reason of the failure for each principal.
+///
+/// * `principals` - principals to verify.
+/// * `context` - a context canister, the default primary names are verified if it is none.
+#[query(name = "batch_verify_reverse_resolve")]
+#[candid_method(query)]
+fn batch_verify_reverse_resolve(
+    principals: Vec<Principal>,
+    context: Option<Principal>,
+) -> BatchVerifyReverseResolveResponse {
+    let service = ResolverService::default();
+    let result = service.batch_verify_reverse_resolve(principals, context);
+    BatchVerifyReverseResolveResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum BatchVerifyReverseResolveResponse {
+    Ok(HashMap<Principal, ReverseResolution>),
+    Err(ErrorInfo),
+}
+
+impl BatchVerifyReverseResolveResponse {
+    pub fn new(result: ServiceResult<HashMap<Principal, ReverseResolution>>) -> Self {
+        match result {
+            Ok(value) => BatchVerifyReverseResolveResponse::Ok(value),
+            Err(err) => BatchVerifyReverseResolveResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Set the primary name of the caller used in the context, only the owner of the name is allowed
+/// and `principal.icp` of the name should be the caller.
+/// Returns true if the primary name is set.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `context` - a context canister, e.g. a dApp canister.
+#[update(name = "set_context_primary_name")]
+#[candid_method(update)]
+async fn set_context_primary_name(name: String, context: Principal) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service
+        .set_context_primary_name(call_context, &name, context)
+        .await;
+    BooleanActorResponse::new(result)
+}
+
+/// Remove the primary name of the caller used in the context.
+/// Returns true if the primary name is removed, false if it is not set.
+///
+/// * `context` - a context canister.
+#[update(name = "remove_context_primary_name")]
+#[candid_method(update)]
+fn remove_context_primary_name(context: Principal) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service.remove_context_primary_name(call_context, context);
+    BooleanActorResponse::new(result)
+}
+
+/// Replace the typed DNS records of the name
+/// Returns true if the records are set.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `records` - all DNS records of the name, an empty list removes the records
+#[update(name = "set_dns_records")]
+#[candid_method(update)]
+async fn set_dns_records(name: String, records: Vec<DnsRecord>) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service.set_dns_records(call_context, &name, records).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Replace the typed DNS records of names in a batch
+/// Returns the result of each name, a failed name does not stop the others.
+///
+/// * `items` - names and all their DNS records, an empty list removes the records of the name
+#[update(name = "batch_set_dns_records")]
+#[candid_method(update)]
+async fn batch_set_dns_records(items: Vec<(String, Vec<DnsRecord>)>) -> BatchSetDnsRecordsResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service.batch_set_dns_records(call_context, items).await;
+    BatchSetDnsRecordsResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum BatchSetDnsRecordsResponse {
+    Ok(Vec<(String, BooleanActorResponse)>),
+    Err(ErrorInfo),
+}
+
+impl BatchSetDnsRecordsResponse {
+    pub fn new(result: ServiceResult<Vec<(String, ServiceResult<bool>)>>) -> Self {
+        match result {
+            Ok(results) => BatchSetDnsRecordsResponse::Ok(
+                results
+                    .into_iter()
+                    .map(|(name, result)| (name, BooleanActorResponse::new(result)))
+                    .collect(),
+            ),
+            Err(err) => BatchSetDnsRecordsResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Restore the record values of the name to a version in the record history, only the owner of the name is allowed.
+/// Returns true if the records are restored.
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `version` - a version of the record history, see `get_record_history`
+#[update(name = "rollback_records")]
+#[candid_method(update)]
+async fn rollback_records(name: String, version: u64) -> BooleanActorResponse {
+    let call_context = CallContext::from_ic();
+    let mut service = ResolverService::default();
+    let result = service.rollback_records(call_context, &name, version).await;
+    BooleanActorResponse::new(result)
+}
+
+/// Get the record history of the name, newest first
+///
+/// * `name` - a name. e.g. `hello.ic`
+/// * `input` - page of the history
+#[query(name = "get_record_history")]
+#[candid_method(query)]
+fn get_record_history(name: String, input: GetPageInput) -> GetRecordHistoryResponse {
+    let service = ResolverService::default();
+    let result = service.get_record_history(&name, &input);
+    GetRecordHistoryResponse::new(result)
+}
+
+#[derive(CandidType)]
+pub enum GetRecordHistoryResponse {
+    Ok(GetPageOutput<RecordHistoryEntry>),
+    Err(ErrorInfo),
+}
+
+impl GetRecordHistoryResponse {
+    pub fn new(result: ServiceResult<GetPageOutput<RecordHistoryEntry>>) -> Self {
+        match result {
+            Ok(output) => GetRecordHistoryResponse::Ok(output),
+            Err(err) => GetRecordHistoryResponse::Err(err.into()),
+        }
+    }
+}
+
+#[derive(CandidType)]
+pub enum BatchGetDnsRecordsResponse {
+    Ok(HashMap<String, Vec<DnsRecord>>),
+    Err(ErrorInfo),
+}
+
+impl BatchGetDnsRecordsResponse {
+    pub fn new(result: ServiceResult<HashMap<String, Vec<DnsRecord>>>) -> Self {
+        match result {
+            Ok(records) => BatchGetDnsRecordsResponse::Ok(records),
+            Err(err) => BatchGetDnsRecordsResponse::Err(err.into()),
+        }
+    }
+}
+
+/// Get the typed DNS records of names
+///
+/// * `names` - names. e.g. `hello.ic`
+#[query(name = "batch_get_dns_records")]
+#[candid_method(query)]
+fn batch_get_dns_records(names: Vec<String>) -> BatchGetDnsRecordsResponse {
+    let service = ResolverService::default();
+    let result = service.batch_get_dns_records(names);
+    BatchGetDnsRecordsResponse::new(result)
+}
+
 #[update(name = "import_record_value")]
 #[candid_method(update)]
 fn import_record_value(request: ImportRecordValueRequest) -> BooleanActorResponse {
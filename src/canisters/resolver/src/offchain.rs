@@ -0,0 +1,112 @@
+use std::convert::TryFrom;
+
+use candid::{CandidType, Deserialize};
+use ed25519_dalek::{PublicKey, Signature};
+use serde_bytes::ByteBuf;
+use url::form_urlencoded::byte_serialize;
+use url::Url;
+
+use common::errors::{NamingError, ServiceResult};
+use common::TimeInNs;
+
+#[cfg(test)]
+mod tests;
+
+pub const OFFCHAIN_URL_NAME_PLACEHOLDER: &str = "{name}";
+pub const OFFCHAIN_URL_KEY_PLACEHOLDER: &str = "{key}";
+const OFFCHAIN_RESPONSE_DOMAIN: &[u8] = b"icnaming-offchain-response";
+
+/// Record of a name looked up from resolver, it is either stored on chain or should be fetched from the gateway
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ResolvedRecord {
+    OnChain(String),
+    OffchainLookup(OffchainLookup),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct OffchainLookup {
+    pub name: String,
+    pub key: String,
+    /// gateway url with placeholders replaced
+    pub url: String,
+    /// hex encoded ed25519 public key expected to sign the response
+    pub signer: String,
+}
+
+/// Response of the gateway, `signature` is signed by the signer over `get_offchain_response_message`
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct OffchainResponse {
+    pub value: String,
+    pub expires_at: u64,
+    pub signature: ByteBuf,
+}
+
+pub fn validate_gateway_url(url: &str) -> Result<(), String> {
+    if !url.contains(OFFCHAIN_URL_NAME_PLACEHOLDER) {
+        return Err(format!(
+            "gateway url should contain {}",
+            OFFCHAIN_URL_NAME_PLACEHOLDER
+        ));
+    }
+    let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+    if parsed.scheme() != "https" {
+        return Err("gateway url should be https".to_string());
+    }
+    Ok(())
+}
+
+pub fn parse_signer(signer: &str) -> Result<PublicKey, String> {
+    let bytes = hex::decode(signer).map_err(|e| e.to_string())?;
+    PublicKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+pub fn get_gateway_url(gateway: &str, name: &str, key: &str) -> String {
+    let name: String = byte_serialize(name.as_bytes()).collect();
+    let key: String = byte_serialize(key.as_bytes()).collect();
+    gateway
+        .replace(OFFCHAIN_URL_NAME_PLACEHOLDER, &name)
+        .replace(OFFCHAIN_URL_KEY_PLACEHOLDER, &key)
+}
+
+/// Message to be signed by the gateway, fields are length prefixed so that they can not be shifted into each other.
+pub fn get_offchain_response_message(
+    name: &str,
+    key: &str,
+    value: &str,
+    expires_at: u64,
+) -> Vec<u8> {
+    let mut message = Vec::new();
+    for field in [
+        OFFCHAIN_RESPONSE_DOMAIN,
+        name.as_bytes(),
+        key.as_bytes(),
+        value.as_bytes(),
+    ] {
+        message.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        message.extend_from_slice(field);
+    }
+    message.extend_from_slice(&expires_at.to_be_bytes());
+    message
+}
+
+/// Verify the response of the gateway against the signer, returns the value if it is valid.
+pub fn verify_offchain_response(
+    signer: &str,
+    name: &str,
+    key: &str,
+    response: &OffchainResponse,
+    now: TimeInNs,
+) -> ServiceResult<String> {
+    let invalid = |reason: String| NamingError::InvalidOffchainResponse { reason };
+    let public_key = parse_signer(signer).map_err(invalid)?;
+    let signature =
+        Signature::try_from(response.signature.as_slice()).map_err(|e| invalid(e.to_string()))?;
+    let message = get_offchain_response_message(name, key, &response.value, response.expires_at);
+    public_key
+        .verify_strict(&message, &signature)
+        .map_err(|e| invalid(e.to_string()))?;
+    if response.expires_at <= now.0 {
+        return Err(NamingError::OffchainResponseExpired);
+    }
+    Ok(response.value.clone())
+}
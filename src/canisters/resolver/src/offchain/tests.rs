@@ -0,0 +1,114 @@
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+use rstest::*;
+
+use super::*;
+
+const NOW: u64 = 1651571294_000_000_000;
+
+#[fixture]
+fn keypair() -> Keypair {
+    let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+fn sign_response(keypair: &Keypair, name: &str, key: &str, value: &str) -> OffchainResponse {
+    let expires_at = NOW + 60_000_000_000;
+    let message = get_offchain_response_message(name, key, value, expires_at);
+    OffchainResponse {
+        value: value.to_string(),
+        expires_at,
+        signature: ByteBuf::from(keypair.sign(&message).to_bytes().to_vec()),
+    }
+}
+
+#[rstest]
+#[case("https://gateway.example.com/{name}/{key}", true)]
+#[case("https://gateway.example.com/{name}", true)]
+#[case("https://gateway.example.com/{key}", false)]
+#[case("http://gateway.example.com/{name}/{key}", false)]
+#[case("gateway.example.com/{name}/{key}", false)]
+fn test_validate_gateway_url(#[case] url: &str, #[case] expected: bool) {
+    assert_eq!(validate_gateway_url(url).is_ok(), expected);
+}
+
+#[rstest]
+fn test_parse_signer(keypair: Keypair) {
+    let signer = hex::encode(keypair.public.as_bytes());
+
+    assert_eq!(parse_signer(&signer).unwrap(), keypair.public);
+    assert!(parse_signer("hello").is_err());
+    assert!(parse_signer(&signer[..62]).is_err());
+}
+
+#[rstest]
+fn test_get_gateway_url() {
+    let url = get_gateway_url(
+        "https://gateway.example.com/{name}/{key}",
+        "hello.ic",
+        "bio text",
+    );
+
+    assert_eq!(url, "https://gateway.example.com/hello.ic/bio+text");
+}
+
+#[rstest]
+fn test_get_offchain_response_message_is_not_ambiguous() {
+    assert_ne!(
+        get_offchain_response_message("hello.ic", "ab", "c", 1),
+        get_offchain_response_message("hello.ic", "a", "bc", 1)
+    );
+}
+
+#[rstest]
+fn test_verify_offchain_response(keypair: Keypair) {
+    let signer = hex::encode(keypair.public.as_bytes());
+    let response = sign_response(&keypair, "hello.ic", "bio", "hello world");
+
+    let result = verify_offchain_response(&signer, "hello.ic", "bio", &response, TimeInNs(NOW));
+
+    assert_eq!(result, Ok("hello world".to_string()));
+}
+
+#[rstest]
+fn test_verify_offchain_response_tampered(keypair: Keypair) {
+    let signer = hex::encode(keypair.public.as_bytes());
+    let mut response = sign_response(&keypair, "hello.ic", "bio", "hello world");
+    response.value = "hello there".to_string();
+
+    let result = verify_offchain_response(&signer, "hello.ic", "bio", &response, TimeInNs(NOW));
+
+    assert!(matches!(
+        result,
+        Err(NamingError::InvalidOffchainResponse { .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_offchain_response_of_other_key(keypair: Keypair) {
+    let signer = hex::encode(keypair.public.as_bytes());
+    let response = sign_response(&keypair, "hello.ic", "bio", "hello world");
+
+    let result = verify_offchain_response(&signer, "hello.ic", "avatar", &response, TimeInNs(NOW));
+
+    assert!(matches!(
+        result,
+        Err(NamingError::InvalidOffchainResponse { .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_offchain_response_expired(keypair: Keypair) {
+    let signer = hex::encode(keypair.public.as_bytes());
+    let response = sign_response(&keypair, "hello.ic", "bio", "hello world");
+
+    let result = verify_offchain_response(
+        &signer,
+        "hello.ic",
+        "bio",
+        &response,
+        TimeInNs(response.expires_at),
+    );
+
+    assert_eq!(result, Err(NamingError::OffchainResponseExpired));
+}
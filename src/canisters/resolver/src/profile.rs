@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+
+use common::certified_map::CertifiedMap;
+use common::constants::*;
+use common::http::HttpResponse;
+use common::http_certification::{get_certifiable_redirect_response, get_certifiable_response};
+
+use crate::certification::{get_record_values, RecordValues};
+use crate::contenthash::decode_contenthash;
+use crate::state::{CERTIFIED_MAP, STATE};
+
+#[cfg(test)]
+mod tests;
+
+const PROFILE_JSON_SUFFIX: &str = ".json";
+
+const SOCIAL_KEYS: [&str; 12] = [
+    RESOLVER_KEY_TWITTER,
+    RESOLVER_KEY_GITHUB,
+    RESOLVER_KEY_FACEBOOK,
+    RESOLVER_KEY_MEDIUM,
+    RESOLVER_KEY_DISCORD,
+    RESOLVER_KEY_TELEGRAM,
+    RESOLVER_KEY_INSTAGRAM,
+    RESOLVER_KEY_REDDIT,
+    RESOLVER_KEY_DSCVR,
+    RESOLVER_KEY_DISTRIKT,
+    RESOLVER_KEY_RELATION,
+    RESOLVER_KEY_OPENCHAT,
+];
+
+#[derive(Serialize)]
+struct NameProfile<'a> {
+    name: &'a str,
+    display_name: Option<&'a str>,
+    avatar: Option<&'a str>,
+    description: Option<&'a str>,
+    url: Option<&'a str>,
+    email: Option<&'a str>,
+    location: Option<&'a str>,
+    socials: BTreeMap<&'a str, &'a str>,
+    records: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> NameProfile<'a> {
+    fn new(name: &'a str, values: &'a RecordValues) -> Self {
+        let records: BTreeMap<&str, &str> = values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let socials = records
+            .iter()
+            .filter(|(key, _)| SOCIAL_KEYS.contains(key))
+            .map(|(key, value)| (*key, *value))
+            .collect();
+        NameProfile {
+            name,
+            display_name: records.get(RESOLVER_KEY_DISPLAY_NAME).copied(),
+            avatar: records.get(RESOLVER_KEY_AVATAR).copied(),
+            description: records.get(RESOLVER_KEY_DESCRIPTION).copied(),
+            url: records.get(RESOLVER_KEY_URL).copied(),
+            email: records.get(RESOLVER_KEY_EMAIL).copied(),
+            location: records.get(RESOLVER_KEY_LOCATION).copied(),
+            socials,
+            records,
+        }
+    }
+}
+
+pub fn get_profile_path(name: &str) -> String {
+    format!("/{}", name)
+}
+
+pub fn get_profile_json_path(name: &str) -> String {
+    format!("/{}{}", name, PROFILE_JSON_SUFFIX)
+}
+
+/// Name of the profile and whether JSON is requested, e.g. `/hello.ic.json` -> (`hello.ic`, true)
+pub(crate) fn parse_profile_path(path: &str) -> Option<(String, bool)> {
+    let name = path.strip_prefix('/')?;
+    let (name, is_json) = match name.strip_suffix(PROFILE_JSON_SUFFIX) {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+    Some((name.to_string(), is_json))
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub(crate) fn get_profile_html_response(name: &str, values: &RecordValues) -> HttpResponse {
+    let profile = NameProfile::new(name, values);
+    let avatar = profile
+        .avatar
+        .map(|avatar| {
+            format!(
+                r#"<img class="avatar" src="{}" alt="avatar">"#,
+                escape_html(avatar)
+            )
+        })
+        .unwrap_or_default();
+    let records = profile
+        .records
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                r#"<li><span class="key">{}</span>{}</li>"#,
+                escape_html(key),
+                escape_html(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n        ");
+    let html = include_str!("../../../../asset/name_profile.html")
+        .replace("{{avatar}}", avatar.as_str())
+        .replace(
+            "{{display_name}}",
+            escape_html(profile.display_name.unwrap_or(name)).as_str(),
+        )
+        .replace(
+            "{{description}}",
+            escape_html(profile.description.unwrap_or_default()).as_str(),
+        )
+        .replace("{{records}}", records.as_str())
+        .replace("{{name}}", escape_html(name).as_str());
+    get_certifiable_response(200, "text/html; charset=utf-8", html.into_bytes())
+}
+
+pub(crate) fn get_profile_json_response(name: &str, values: &RecordValues) -> HttpResponse {
+    let profile = NameProfile::new(name, values);
+    let json = serde_json::to_vec(&profile).unwrap();
+    get_certifiable_response(200, "application/json", json)
+}
+
+/// Redirect to the website of the name if `contenthash` is set, otherwise the html profile
+pub(crate) fn get_profile_page_response(name: &str, values: &RecordValues) -> HttpResponse {
+    values
+        .iter()
+        .find(|(key, _)| key == RESOLVER_KEY_CONTENTHASH)
+        .and_then(|(_, value)| decode_contenthash(value).ok())
+        .map(|content_hash| get_certifiable_redirect_response(&content_hash.get_gateway_url()))
+        .unwrap_or_else(|| get_profile_html_response(name, values))
+}
+
+pub(crate) fn get_not_found_response() -> HttpResponse {
+    get_certifiable_response(404, "text/plain", b"Not found".to_vec())
+}
+
+/// Put or remove the certified profile responses of the name
+pub(crate) fn certify_profile(map: &mut CertifiedMap, name: &str, values: Option<&RecordValues>) {
+    match values {
+        Some(values) => {
+            map.put_http_response(
+                &get_profile_path(name),
+                &get_profile_page_response(name, values),
+            );
+            map.put_http_response(
+                &get_profile_json_path(name),
+                &get_profile_json_response(name, values),
+            );
+        }
+        None => {
+            map.delete_http_response(&get_profile_path(name));
+            map.delete_http_response(&get_profile_json_path(name));
+        }
+    }
+}
+
+/// Certified profile of the name in the path, or a certified 404 response.
+/// The path is percent-decoded as the gateway does when verifying the response, e.g. `/%E4%BD%A0.ic`.
+pub(crate) fn get_profile_http_response(path: &str, certificate: &[u8]) -> HttpResponse {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let path = path.as_ref();
+    let response = parse_profile_path(path)
+        .and_then(|(name, is_json)| {
+            let values = STATE.with(|s| get_record_values(s, &name))?;
+            if is_json {
+                Some(get_profile_json_response(&name, &values))
+            } else {
+                Some(get_profile_page_response(&name, &values))
+            }
+        })
+        .unwrap_or_else(get_not_found_response);
+    CERTIFIED_MAP.with(|map| {
+        map.borrow()
+            .certify_http_response(path, response, certificate)
+    })
+}
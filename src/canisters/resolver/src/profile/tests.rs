@@ -0,0 +1,178 @@
+use candid::Principal;
+use rstest::*;
+use serde_json::Value;
+
+use common::http::HeaderField;
+use common::http_certification::IC_CERTIFICATE_HEADER;
+use test_common::ic_api::init_test;
+use test_common::user::*;
+
+use super::*;
+use crate::certification::{certify, certify_all};
+use crate::state::CERTIFIED_MAP_MEMORY_ID;
+
+fn add_test_resolver(name: &str, values: Vec<(&str, &str)>) {
+    STATE.with(|s| {
+        let mut store = s.resolver_store.borrow_mut();
+        store.ensure_created(name);
+        let mut resolver = store.get_resolver(name).unwrap();
+        for (key, value) in values {
+            resolver.set_record_value(key.to_string(), value.to_string());
+        }
+        store.set_resolver(resolver);
+    });
+    STATE.with(|s| certify(s, &[name.to_string()], &[]));
+}
+
+fn get_body(response: &HttpResponse) -> String {
+    String::from_utf8(response.body.to_vec()).unwrap()
+}
+
+#[rstest]
+#[case("/hello.ic", Some(("hello.ic", false)))]
+#[case("/hello.ic.json", Some(("hello.ic", true)))]
+#[case("/", None)]
+#[case("/.json", None)]
+#[case("/a/hello.ic", None)]
+fn test_parse_profile_path(#[case] path: &str, #[case] expected: Option<(&str, bool)>) {
+    assert_eq!(
+        parse_profile_path(path),
+        expected.map(|(name, is_json)| (name.to_string(), is_json))
+    );
+}
+
+#[rstest]
+fn test_get_profile_json_response(_init_test: ()) {
+    let values = vec![
+        (
+            RESOLVER_KEY_AVATAR.to_string(),
+            "https://a.b/c.png".to_string(),
+        ),
+        (RESOLVER_KEY_TWITTER.to_string(), "icnaming".to_string()),
+        (RESOLVER_KEY_ETH.to_string(), "0x00".to_string()),
+    ];
+
+    let response = get_profile_json_response("hello.ic", &values);
+
+    assert_eq!(response.status_code, 200);
+    let json: Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(json["name"], "hello.ic");
+    assert_eq!(json["avatar"], "https://a.b/c.png");
+    assert_eq!(json["display_name"], Value::Null);
+    assert_eq!(json["socials"][RESOLVER_KEY_TWITTER], "icnaming");
+    assert_eq!(json["socials"][RESOLVER_KEY_ETH], Value::Null);
+    assert_eq!(json["records"][RESOLVER_KEY_ETH], "0x00");
+}
+
+#[rstest]
+fn test_get_profile_html_response_escaped(_init_test: ()) {
+    let values = vec![(
+        RESOLVER_KEY_DESCRIPTION.to_string(),
+        "<script>alert(1)</script>".to_string(),
+    )];
+
+    let response = get_profile_html_response("hello.ic", &values);
+
+    let body = get_body(&response);
+    assert!(body.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!body.contains("<script>"));
+    assert!(body.contains("<h2>hello.ic</h2>"));
+}
+
+#[rstest]
+fn test_profile_certified(_init_test: ()) {
+    add_test_resolver("hello.ic", vec![(RESOLVER_KEY_DISPLAY_NAME, "Hello")]);
+
+    CERTIFIED_MAP.with(|map| {
+        let map = map.borrow();
+        assert!(map.contains_http_response("/hello.ic"));
+        assert!(map.contains_http_response("/hello.ic.json"));
+    });
+
+    let response = get_profile_http_response("/hello.ic.json", &[]);
+    assert_eq!(response.status_code, 200);
+    let json: Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(json["display_name"], "Hello");
+    let header = response.headers.last().unwrap();
+    assert_eq!(header.0, IC_CERTIFICATE_HEADER);
+}
+
+#[rstest]
+fn test_profile_redirect_to_contenthash(_init_test: ()) {
+    add_test_resolver(
+        "hello.ic",
+        vec![
+            (RESOLVER_KEY_DISPLAY_NAME, "Hello"),
+            (RESOLVER_KEY_CONTENTHASH, "0x8080c00100000000000000020101"),
+        ],
+    );
+
+    let response = get_profile_http_response("/hello.ic", &[]);
+    assert_eq!(response.status_code, 307);
+    let location = response
+        .headers
+        .iter()
+        .find(|HeaderField(name, _)| name == "Location")
+        .map(|HeaderField(_, value)| value.as_str());
+    assert_eq!(
+        location,
+        Some("https://ryjl3-tyaaa-aaaaa-aaaba-cai.icp0.io")
+    );
+    assert_eq!(response.headers.last().unwrap().0, IC_CERTIFICATE_HEADER);
+
+    // profile json is still served
+    let response = get_profile_http_response("/hello.ic.json", &[]);
+    assert_eq!(response.status_code, 200);
+}
+
+#[rstest]
+fn test_profile_uncertified_after_removed(_init_test: ()) {
+    add_test_resolver("hello.ic", vec![]);
+    STATE.with(|s| {
+        s.resolver_store.borrow_mut().remove_resolver("hello.ic");
+        certify(s, &["hello.ic".to_string()], &[]);
+    });
+
+    CERTIFIED_MAP.with(|map| {
+        let map = map.borrow();
+        assert!(!map.contains_http_response("/hello.ic"));
+        assert!(!map.contains_http_response("/hello.ic.json"));
+    });
+}
+
+#[rstest]
+fn test_profile_not_found(_init_test: (), mock_user1: Principal) {
+    STATE.with(|s| certify_all(s));
+
+    let response = get_profile_http_response("/hello.ic", &[]);
+
+    assert_eq!(response.status_code, 404);
+    assert_eq!(get_body(&response), "Not found");
+    let response = get_profile_http_response(&format!("/{}", mock_user1), &[]);
+    assert_eq!(response.status_code, 404);
+}
+
+#[rstest]
+fn test_profile_of_percent_encoded_path(_init_test: ()) {
+    add_test_resolver("你好.ic", vec![(RESOLVER_KEY_DISPLAY_NAME, "Hello")]);
+
+    let response = get_profile_http_response("/%E4%BD%A0%E5%A5%BD.ic.json", &[]);
+
+    assert_eq!(response.status_code, 200);
+    let json: Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(json["name"], "你好.ic");
+    CERTIFIED_MAP.with(|map| assert!(map.borrow().contains_http_response("/你好.ic.json")));
+}
+
+#[rstest]
+fn test_profile_restored_from_stable_memory(_init_test: ()) {
+    STATE.with(|s| certify_all(s));
+    add_test_resolver("hello.ic", vec![(RESOLVER_KEY_DISPLAY_NAME, "Hello")]);
+    let root_hash = CERTIFIED_MAP.with(|map| map.borrow().root_hash());
+
+    let restored = CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID);
+
+    assert_eq!(restored.root_hash(), root_hash);
+    assert!(restored.contains_http_response("/hello.ic"));
+    assert!(restored.contains_http_response("/hello.ic.json"));
+}
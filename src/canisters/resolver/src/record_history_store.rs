@@ -0,0 +1,144 @@
+use std::collections::{HashMap, VecDeque};
+
+use candid::{CandidType, Deserialize, Principal};
+
+use common::constants::RESOLVER_HISTORY_MAX_COUNT;
+use common::errors::{NamingError, ServiceResult};
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+use common::TimeInNs;
+
+use crate::state::RECORD_HISTORY_MEMORY_ID;
+
+#[cfg(test)]
+mod tests;
+
+/// Change of a record value, `None` means the key is not set
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct RecordChange {
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// Record values of a name changed at once, `version` is the version of the records after the change
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct RecordHistoryEntry {
+    pub version: u64,
+    pub caller: Principal,
+    pub created_at: u64,
+    pub changes: Vec<RecordChange>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct RecordHistory {
+    version: u64,
+    // oldest first, at most RESOLVER_HISTORY_MAX_COUNT entries
+    entries: VecDeque<RecordHistoryEntry>,
+}
+
+/// Histories are kept in stable memory, so that they are not serialized on upgrade.
+pub struct RecordHistoryStore {
+    histories: StableMap<String, RecordHistory>,
+}
+
+impl Default for RecordHistoryStore {
+    fn default() -> Self {
+        RecordHistoryStore {
+            histories: StableMap::init(RECORD_HISTORY_MEMORY_ID),
+        }
+    }
+}
+
+impl StableMemoryStore for RecordHistoryStore {
+    type Entries = (HashMap<String, RecordHistory>,);
+
+    fn export_entries(&self) -> Self::Entries {
+        (self.histories.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (histories,): Self::Entries) {
+        self.histories.clear();
+        for (name, history) in histories {
+            self.histories.insert(name, history);
+        }
+    }
+}
+
+impl RecordHistoryStore {
+    /// Add the changes as a new version of the name, returns the new version.
+    /// Nothing is added if there is no change.
+    pub fn add_entry(
+        &mut self,
+        name: &str,
+        caller: Principal,
+        now: TimeInNs,
+        changes: Vec<RecordChange>,
+    ) -> Option<u64> {
+        if changes.is_empty() {
+            return None;
+        }
+        let mut history = self.histories.get(&name.to_string()).unwrap_or_default();
+        history.version += 1;
+        history.entries.push_back(RecordHistoryEntry {
+            version: history.version,
+            caller,
+            created_at: now.0,
+            changes,
+        });
+        while history.entries.len() > RESOLVER_HISTORY_MAX_COUNT {
+            history.entries.pop_front();
+        }
+        let version = history.version;
+        self.histories.insert(name.to_string(), history);
+        Some(version)
+    }
+
+    pub fn get_version(&self, name: &str) -> u64 {
+        self.histories
+            .get(&name.to_string())
+            .map(|history| history.version)
+            .unwrap_or_default()
+    }
+
+    /// History entries of the name, newest first
+    pub fn get_entries(&self, name: &str) -> Vec<RecordHistoryEntry> {
+        self.histories
+            .get(&name.to_string())
+            .map(|history| history.entries.into_iter().rev().collect())
+            .unwrap_or_default()
+    }
+
+    /// Values of keys changed after the version, as they were at the version.
+    /// The version should be the current one or the one before any entry still kept.
+    pub fn get_values_at(
+        &self,
+        name: &str,
+        version: u64,
+    ) -> ServiceResult<HashMap<String, Option<String>>> {
+        let history = self.histories.get(&name.to_string()).unwrap_or_default();
+        let current_version = history.version;
+        let entries = history.entries;
+        let oldest_version = entries
+            .front()
+            .map(|entry| entry.version - 1)
+            .unwrap_or(current_version);
+        if version > current_version || version < oldest_version {
+            return Err(NamingError::RecordVersionNotFound { version });
+        }
+        let mut values = HashMap::new();
+        for entry in entries {
+            if entry.version <= version {
+                continue;
+            }
+            for change in entry.changes {
+                values.entry(change.key).or_insert(change.old_value);
+            }
+        }
+        Ok(values)
+    }
+
+    pub fn remove_history(&mut self, name: &str) {
+        self.histories.remove(&name.to_string());
+    }
+}
@@ -0,0 +1,123 @@
+use rstest::*;
+
+use test_common::user::*;
+
+use super::*;
+
+const NAME: &str = "hello.ic";
+
+fn change(key: &str, old_value: Option<&str>, new_value: Option<&str>) -> RecordChange {
+    RecordChange {
+        key: key.to_string(),
+        old_value: old_value.map(|value| value.to_string()),
+        new_value: new_value.map(|value| value.to_string()),
+    }
+}
+
+fn get_store(mock_user1: Principal) -> RecordHistoryStore {
+    let mut store = RecordHistoryStore::default();
+    let now = TimeInNs(1);
+    store.add_entry(NAME, mock_user1, now, vec![change("a", None, Some("1"))]);
+    store.add_entry(
+        NAME,
+        mock_user1,
+        now,
+        vec![
+            change("a", Some("1"), Some("2")),
+            change("b", None, Some("x")),
+        ],
+    );
+    store.add_entry(NAME, mock_user1, now, vec![change("a", Some("2"), None)]);
+    store
+}
+
+#[rstest]
+fn test_add_entry(mock_user1: Principal) {
+    let mut store = get_store(mock_user1);
+
+    assert_eq!(store.get_version(NAME), 3);
+    assert_eq!(store.add_entry(NAME, mock_user1, TimeInNs(1), vec![]), None);
+    assert_eq!(store.get_version(NAME), 3);
+    let entries = store.get_entries(NAME);
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.version)
+            .collect::<Vec<_>>(),
+        vec![3, 2, 1]
+    );
+    assert_eq!(entries[0].caller, mock_user1);
+    assert_eq!(store.get_version("world.ic"), 0);
+    assert!(store.get_entries("world.ic").is_empty());
+}
+
+#[rstest]
+fn test_add_entry_bounded(mock_user1: Principal) {
+    let mut store = RecordHistoryStore::default();
+    for i in 0..(RESOLVER_HISTORY_MAX_COUNT + 5) {
+        store.add_entry(
+            NAME,
+            mock_user1,
+            TimeInNs(i as u64),
+            vec![change("a", None, Some(&i.to_string()))],
+        );
+    }
+
+    let entries = store.get_entries(NAME);
+    assert_eq!(entries.len(), RESOLVER_HISTORY_MAX_COUNT);
+    assert_eq!(entries[0].version, (RESOLVER_HISTORY_MAX_COUNT + 5) as u64);
+    assert_eq!(entries.last().unwrap().version, 6);
+    assert!(store.get_values_at(NAME, 5).is_ok());
+    assert_eq!(
+        store.get_values_at(NAME, 4),
+        Err(NamingError::RecordVersionNotFound { version: 4 })
+    );
+}
+
+#[rstest]
+#[case(3, vec![])]
+#[case(2, vec![("a", Some("2"))])]
+#[case(1, vec![("a", Some("1")), ("b", None)])]
+#[case(0, vec![("a", None), ("b", None)])]
+fn test_get_values_at(
+    mock_user1: Principal,
+    #[case] version: u64,
+    #[case] expected: Vec<(&str, Option<&str>)>,
+) {
+    let store = get_store(mock_user1);
+
+    let values = store.get_values_at(NAME, version).unwrap();
+
+    let expected: HashMap<String, Option<String>> = expected
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.map(|value| value.to_string())))
+        .collect();
+    assert_eq!(values, expected);
+}
+
+#[rstest]
+fn test_get_values_at_not_found(mock_user1: Principal) {
+    let mut store = get_store(mock_user1);
+
+    assert_eq!(
+        store.get_values_at(NAME, 4),
+        Err(NamingError::RecordVersionNotFound { version: 4 })
+    );
+
+    store.remove_history(NAME);
+    assert_eq!(store.get_version(NAME), 0);
+    assert!(store.get_values_at(NAME, 0).unwrap().is_empty());
+}
+
+#[rstest]
+fn test_export_import(mock_user1: Principal) {
+    let mut store = get_store(mock_user1);
+    let entries = store.export_entries();
+    let expected = store.get_entries(NAME);
+    store.remove_history(NAME);
+
+    store.import_entries(entries);
+
+    assert_eq!(store.get_version(NAME), 3);
+    assert_eq!(store.get_entries(NAME), expected);
+}
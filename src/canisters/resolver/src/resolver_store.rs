@@ -1,14 +1,55 @@
 use std::collections::HashMap;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize};
+use candid::{CandidType, Deserialize};
 use log::debug;
 
-use common::state::StableState;
+use common::constants::DEFAULT_TTL;
+use common::stable_memory::StableMap;
+use common::state::StableMemoryStore;
+
+use crate::state::RESOLVER_STORE_MEMORY_ID;
+
+#[cfg(test)]
+mod tests;
+
+/// Data of a typed DNS record, domain names are stored without the trailing dot
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum DnsRecordData {
+    A(String),
+    AAAA(String),
+    CNAME(String),
+    TXT(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    CAA {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+}
+
+/// Typed DNS record of a name, ttl of the name is used if `ttl` is not set
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct DnsRecord {
+    pub ttl: Option<u64>,
+    pub data: DnsRecordData,
+}
 
 #[derive(CandidType, Deserialize, Clone)]
 pub struct Resolver {
     name: String,
     string_value_map: HashMap<String, String>,
+    // ttl of the name in registry, in seconds
+    ttl: Option<u64>,
+    dns_records: Option<Vec<DnsRecord>>,
 }
 
 impl Resolver {
@@ -16,6 +57,8 @@ impl Resolver {
         Resolver {
             name,
             string_value_map: HashMap::new(),
+            ttl: None,
+            dns_records: None,
         }
     }
     pub(crate) fn get_name(&self) -> &String {
@@ -50,47 +93,95 @@ impl Resolver {
     pub fn string_value_map(&self) -> &HashMap<String, String> {
         &self.string_value_map
     }
+
+    pub fn set_ttl(&mut self, ttl: u64) {
+        self.ttl = Some(ttl);
+    }
+    pub fn get_ttl(&self) -> u64 {
+        self.ttl.unwrap_or(DEFAULT_TTL)
+    }
+
+    pub fn set_dns_records(&mut self, records: Vec<DnsRecord>) {
+        self.dns_records = if records.is_empty() {
+            None
+        } else {
+            Some(records)
+        };
+    }
+    pub fn get_dns_records(&self) -> &[DnsRecord] {
+        self.dns_records.as_deref().unwrap_or_default()
+    }
 }
 
-#[derive(Default)]
+/// Resolvers are kept in stable memory, so that they are not serialized on upgrade.
 pub struct ResolverStore {
-    resolvers: HashMap<String, Resolver>,
+    resolvers: StableMap<String, Resolver>,
 }
 
-impl StableState for ResolverStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.resolvers,)).unwrap()
+impl Default for ResolverStore {
+    fn default() -> Self {
+        ResolverStore {
+            resolvers: StableMap::init(RESOLVER_STORE_MEMORY_ID),
+        }
     }
+}
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (resolvers,): (HashMap<String, Resolver>,) = decode_args(&bytes).unwrap();
+impl StableMemoryStore for ResolverStore {
+    type Entries = (HashMap<String, Resolver>,);
 
-        Ok(ResolverStore { resolvers })
+    fn export_entries(&self) -> Self::Entries {
+        (self.resolvers.iter().collect(),)
+    }
+
+    fn import_entries(&mut self, (resolvers,): Self::Entries) {
+        self.resolvers.clear();
+        for (name, resolver) in resolvers {
+            self.resolvers.insert(name, resolver);
+        }
     }
 }
 
 impl ResolverStore {
-    pub fn new() -> ResolverStore {
-        ResolverStore {
-            resolvers: HashMap::new(),
-        }
+    pub fn get_resolver(&self, name: &str) -> Option<Resolver> {
+        self.resolvers.get(&name.to_string())
     }
 
-    pub fn get_resolvers(&self) -> &HashMap<String, Resolver> {
-        &self.resolvers
+    pub fn has_resolver(&self, name: &str) -> bool {
+        self.resolvers.contains_key(&name.to_string())
     }
 
-    pub fn get_resolvers_mut(&mut self) -> &mut HashMap<String, Resolver> {
-        &mut self.resolvers
+    pub fn set_resolver(&mut self, resolver: Resolver) {
+        self.resolvers.insert(resolver.get_name().clone(), resolver);
     }
+
+    /// Update the resolver of the name in place, returns None if it is not created.
+    pub fn update_resolver<R>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut Resolver) -> R,
+    ) -> Option<R> {
+        let mut resolver = self.get_resolver(name)?;
+        let result = f(&mut resolver);
+        self.set_resolver(resolver);
+        Some(result)
+    }
+
     pub fn ensure_created(&mut self, name: &str) {
-        if !self.resolvers.contains_key(name) {
-            self.resolvers
-                .insert(name.to_string(), Resolver::new(name.to_string()));
+        if !self.has_resolver(name) {
+            self.set_resolver(Resolver::new(name.to_string()));
         }
     }
 
     pub fn remove_resolver(&mut self, name: &str) -> Option<Resolver> {
-        self.resolvers.remove(name)
+        self.resolvers.remove(&name.to_string())
+    }
+
+    /// Names of all resolvers, ordered by name
+    pub fn get_names(&self) -> Vec<String> {
+        self.resolvers.keys().collect()
+    }
+
+    pub fn get_resolver_count(&self) -> u64 {
+        self.resolvers.len()
     }
 }
@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+
+use rstest::*;
+
+use common::constants::*;
+use common::state::{encode_entries, StableEntries};
+
+use super::*;
+
+const NAME: &str = "hello.ic";
+
+#[rstest]
+fn test_update_resolver() {
+    let mut store = ResolverStore::default();
+    assert_eq!(
+        store.update_resolver(NAME, |resolver| resolver.set_ttl(300)),
+        None
+    );
+
+    store.ensure_created(NAME);
+    store.update_resolver(NAME, |resolver| {
+        resolver.set_record_value(RESOLVER_KEY_GITHUB.to_string(), "icnaming".to_string());
+        resolver.set_ttl(300)
+    });
+
+    let resolver = store.get_resolver(NAME).unwrap();
+    assert_eq!(resolver.get_ttl(), 300);
+    assert!(resolver.contains_key(RESOLVER_KEY_GITHUB));
+    assert_eq!(store.get_resolver_count(), 1);
+}
+
+#[rstest]
+fn test_resolvers_kept_in_stable_memory() {
+    {
+        let mut store = ResolverStore::default();
+        store.ensure_created(NAME);
+        store.ensure_created("app.hello.ic");
+    }
+
+    let mut store = ResolverStore::default();
+
+    assert_eq!(
+        store.get_names(),
+        vec!["app.hello.ic".to_string(), NAME.to_string()]
+    );
+    assert!(store.remove_resolver(NAME).is_some());
+    assert!(!store.has_resolver(NAME));
+}
+
+#[rstest]
+fn test_import_replaces_stable_memory() {
+    let store = RefCell::new(ResolverStore::default());
+    store.borrow_mut().ensure_created(NAME);
+    store
+        .borrow_mut()
+        .update_resolver(NAME, |resolver| resolver.set_ttl(300));
+    let bytes = encode_entries(&store);
+    store.borrow_mut().remove_resolver(NAME);
+    store.borrow_mut().ensure_created("world.ic");
+
+    let entries = StableEntries::<ResolverStore>::decode(bytes).unwrap();
+    // decoding has no effect on the store in use
+    assert_eq!(store.borrow().get_names(), vec!["world.ic".to_string()]);
+    entries.import_into(&store);
+
+    let store = store.borrow();
+    assert_eq!(store.get_names(), vec![NAME.to_string()]);
+    assert_eq!(store.get_resolver(NAME).unwrap().get_ttl(), 300);
+}
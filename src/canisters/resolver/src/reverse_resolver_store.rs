@@ -1,44 +1,172 @@
-use candid::{decode_args, encode_args, Principal};
-use common::state::StableState;
+use candid::{CandidType, Deserialize, Principal};
+use common::constants::RESOLVER_KEY_ICP_PRINCIPAL;
+use common::stable_memory::{Bound, PrincipalKey, StableMap, Storable};
+use common::state::StableMemoryStore;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-#[derive(Default)]
+use crate::resolver_store::ResolverStore;
+use crate::state::{
+    State, CONTEXT_PRIMARY_NAMES_MEMORY_ID, PRIMARY_NAMES_MEMORY_ID,
+    PRIMARY_NAMES_REVERSE_MEMORY_ID,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Why a primary name of a principal is not verified by the forward record of the name
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ReverseResolutionFailure {
+    NotSet,
+    ResolverNotFound {
+        name: String,
+    },
+    /// `principal.icp` of the name is not the principal, `principal` is the current value
+    PrincipalMismatch {
+        name: String,
+        principal: Option<String>,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ReverseResolution {
+    Verified(String),
+    Unverified(ReverseResolutionFailure),
+}
+
+impl From<Result<String, ReverseResolutionFailure>> for ReverseResolution {
+    fn from(result: Result<String, ReverseResolutionFailure>) -> Self {
+        match result {
+            Ok(name) => ReverseResolution::Verified(name),
+            Err(failure) => ReverseResolution::Unverified(failure),
+        }
+    }
+}
+
+/// Key of primary names in contexts, names of a principal are adjacent
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct ContextKey {
+    principal: PrincipalKey,
+    context: PrincipalKey,
+}
+
+impl ContextKey {
+    fn new(principal: Principal, context: Principal) -> Self {
+        ContextKey {
+            principal: PrincipalKey(principal),
+            context: PrincipalKey(context),
+        }
+    }
+}
+
+impl Storable for ContextKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let principal = self.principal.to_bytes();
+        let context = self.context.to_bytes();
+        let mut bytes = Vec::with_capacity(1 + principal.len() + context.len());
+        bytes.push(principal.len() as u8);
+        bytes.extend_from_slice(&principal);
+        bytes.extend_from_slice(&context);
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let principal_end = 1 + bytes[0] as usize;
+        ContextKey {
+            principal: PrincipalKey::from_bytes(Cow::Borrowed(&bytes[1..principal_end])),
+            context: PrincipalKey::from_bytes(Cow::Borrowed(&bytes[principal_end..])),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 59,
+        is_fixed_size: false,
+    };
+}
+
+/// Primary names are kept in stable memory, so that they are not serialized on upgrade.
 pub struct ReverseResolverStore {
-    primary_names: HashMap<Principal, String>,
-    primary_names_reverse: HashMap<String, Principal>,
+    primary_names: StableMap<PrincipalKey, String>,
+    primary_names_reverse: StableMap<String, Principal>,
+    // principal and context canister, e.g. a dApp -> primary name used in the context
+    context_primary_names: StableMap<ContextKey, String>,
 }
 
-impl StableState for ReverseResolverStore {
-    fn encode(&self) -> Vec<u8> {
-        encode_args((&self.primary_names, &self.primary_names_reverse)).unwrap()
+impl Default for ReverseResolverStore {
+    fn default() -> Self {
+        ReverseResolverStore {
+            primary_names: StableMap::init(PRIMARY_NAMES_MEMORY_ID),
+            primary_names_reverse: StableMap::init(PRIMARY_NAMES_REVERSE_MEMORY_ID),
+            context_primary_names: StableMap::init(CONTEXT_PRIMARY_NAMES_MEMORY_ID),
+        }
     }
+}
+
+type ContextPrimaryNames = HashMap<Principal, HashMap<Principal, String>>;
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (primary_names, primary_names_reverse): (
-            HashMap<Principal, String>,
-            HashMap<String, Principal>,
-        ) = decode_args(&bytes).unwrap();
+impl StableMemoryStore for ReverseResolverStore {
+    type Entries = (
+        HashMap<Principal, String>,
+        HashMap<String, Principal>,
+        Option<ContextPrimaryNames>,
+    );
 
-        Ok(ReverseResolverStore {
-            primary_names,
-            primary_names_reverse,
-        })
+    fn export_entries(&self) -> Self::Entries {
+        let mut context_primary_names = ContextPrimaryNames::new();
+        for (key, name) in self.context_primary_names.iter() {
+            context_primary_names
+                .entry(key.principal.0)
+                .or_default()
+                .insert(key.context.0, name);
+        }
+        (
+            self.primary_names
+                .iter()
+                .map(|(principal, name)| (principal.0, name))
+                .collect(),
+            self.primary_names_reverse.iter().collect(),
+            Some(context_primary_names),
+        )
+    }
+
+    fn import_entries(
+        &mut self,
+        (primary_names, primary_names_reverse, context_primary_names): Self::Entries,
+    ) {
+        self.primary_names.clear();
+        self.primary_names_reverse.clear();
+        self.context_primary_names.clear();
+        for (principal, name) in primary_names {
+            self.primary_names.insert(PrincipalKey(principal), name);
+        }
+        for (name, principal) in primary_names_reverse {
+            self.primary_names_reverse.insert(name, principal);
+        }
+        for (principal, names) in context_primary_names.unwrap_or_default() {
+            for (context, name) in names {
+                self.context_primary_names
+                    .insert(ContextKey::new(principal, context), name);
+            }
+        }
     }
 }
 
 impl ReverseResolverStore {
     pub fn set_primary_name(&mut self, principal: Principal, name: String) {
-        if let Some(old_name) = self.primary_names.insert(principal, name.clone()) {
+        if let Some(old_name) = self
+            .primary_names
+            .insert(PrincipalKey(principal), name.clone())
+        {
             self.primary_names_reverse.remove(&old_name);
         }
         self.primary_names_reverse.insert(name, principal);
     }
     pub fn has_primary_name_reverse(&self, name: String) -> bool {
-        self.primary_names_reverse.contains_key(name.as_str())
+        self.primary_names_reverse.contains_key(&name)
     }
 
     pub fn remove_primary_name(&mut self, principal: Principal) -> Option<String> {
-        if let Some(name) = self.primary_names.remove(&principal) {
+        if let Some(name) = self.primary_names.remove(&PrincipalKey(principal)) {
             self.primary_names_reverse.remove(&name);
             Some(name)
         } else {
@@ -47,23 +175,144 @@ impl ReverseResolverStore {
     }
     pub fn remove_primary_name_by_name(&mut self, name: &String) -> Option<Principal> {
         if let Some(principal) = self.primary_names_reverse.remove(name) {
-            self.primary_names.remove(&principal);
+            self.primary_names.remove(&PrincipalKey(principal));
             Some(principal)
         } else {
             None
         }
     }
-    pub fn get_primary_name(&self, principal: &Principal) -> Option<&String> {
-        self.primary_names.get(principal)
+    pub fn get_primary_name(&self, principal: &Principal) -> Option<String> {
+        self.primary_names.get(&PrincipalKey(*principal))
     }
-    pub fn get_primary_name_reverse(&self, name: &String) -> Option<&Principal> {
+    pub fn get_primary_name_reverse(&self, name: &String) -> Option<Principal> {
         self.primary_names_reverse.get(name)
     }
 
-    pub fn primary_names(&self) -> &HashMap<Principal, String> {
-        &self.primary_names
+    /// Principals which have a primary name, ordered by principal
+    pub fn get_principals(&self) -> Vec<Principal> {
+        self.primary_names
+            .keys()
+            .map(|principal| principal.0)
+            .collect()
+    }
+
+    pub fn set_context_primary_name(
+        &mut self,
+        principal: Principal,
+        context: Principal,
+        name: String,
+    ) {
+        self.context_primary_names
+            .insert(ContextKey::new(principal, context), name);
+    }
+    pub fn remove_context_primary_name(
+        &mut self,
+        principal: &Principal,
+        context: &Principal,
+    ) -> Option<String> {
+        self.context_primary_names
+            .remove(&ContextKey::new(*principal, *context))
+    }
+    pub fn get_context_primary_name(
+        &self,
+        principal: &Principal,
+        context: &Principal,
+    ) -> Option<String> {
+        self.context_primary_names
+            .get(&ContextKey::new(*principal, *context))
+    }
+
+    /// Primary names of the principal in contexts, ordered by context
+    fn get_context_primary_names(&self, principal: &Principal) -> Vec<(ContextKey, String)> {
+        let principal = PrincipalKey(*principal);
+        let start = ContextKey {
+            principal,
+            context: PrincipalKey(Principal::management_canister()),
+        };
+        self.context_primary_names
+            .range(start..)
+            .take_while(|(key, _)| key.principal == principal)
+            .collect()
+    }
+
+    /// Keep the primary names in contexts of the principal which `f` returns true for
+    pub fn retain_context_primary_names<F>(&mut self, principal: &Principal, f: F)
+    where
+        F: Fn(&String) -> bool,
+    {
+        for (key, name) in self.get_context_primary_names(principal) {
+            if !f(&name) {
+                self.context_primary_names.remove(&key);
+            }
+        }
     }
-    pub fn primary_names_reverse(&self) -> &HashMap<String, Principal> {
-        &self.primary_names_reverse
+
+    /// Remove the name from primary names of the principal, both the default one and the ones in contexts.
+    /// Returns true if any of them is removed.
+    pub fn remove_primary_names_of_name(&mut self, principal: &Principal, name: &str) -> bool {
+        let mut removed = false;
+        let key = PrincipalKey(*principal);
+        if self.primary_names.get(&key).as_deref() == Some(name) {
+            self.primary_names.remove(&key);
+            if self.primary_names_reverse.get(&name.to_string()) == Some(*principal) {
+                self.primary_names_reverse.remove(&name.to_string());
+            }
+            removed = true;
+        }
+        for (key, value) in self.get_context_primary_names(principal) {
+            if value == name {
+                self.context_primary_names.remove(&key);
+                removed = true;
+            }
+        }
+        removed
+    }
+}
+
+/// A primary name is verified only if `principal.icp` of the name is the principal.
+/// Only the owner in the registry can set a primary name, and the registry resets primary names of a name
+/// once its owner changed, see `reset_primary_names`. So the name of the previous owner is not verified.
+pub(crate) fn verify_primary_name(
+    resolver_store: &ResolverStore,
+    principal: &Principal,
+    name: &str,
+) -> Result<(), ReverseResolutionFailure> {
+    let resolver = resolver_store.get_resolver(name).ok_or_else(|| {
+        ReverseResolutionFailure::ResolverNotFound {
+            name: name.to_string(),
+        }
+    })?;
+    let value = resolver.get_record_value().get(RESOLVER_KEY_ICP_PRINCIPAL);
+    match value.map(Principal::from_text) {
+        Some(Ok(value)) if &value == principal => Ok(()),
+        _ => Err(ReverseResolutionFailure::PrincipalMismatch {
+            name: name.to_string(),
+            principal: value.cloned(),
+        }),
+    }
+}
+
+/// Verified primary name of the principal in the context, the default primary name is used
+/// if there is no verified one in the context.
+pub(crate) fn get_verified_primary_name(
+    state: &State,
+    principal: &Principal,
+    context: Option<&Principal>,
+) -> Result<String, ReverseResolutionFailure> {
+    let resolver_store = state.resolver_store.borrow();
+    let reverse_store = state.reverse_resolver_store.borrow();
+    let mut context_failure = None;
+    if let Some(name) =
+        context.and_then(|context| reverse_store.get_context_primary_name(principal, context))
+    {
+        match verify_primary_name(&resolver_store, principal, &name) {
+            Ok(_) => return Ok(name),
+            Err(failure) => context_failure = Some(failure),
+        }
     }
+    let result = match reverse_store.get_primary_name(principal) {
+        Some(name) => verify_primary_name(&resolver_store, principal, &name).map(|_| name),
+        None => Err(ReverseResolutionFailure::NotSet),
+    };
+    result.map_err(|failure| context_failure.unwrap_or(failure))
 }
@@ -0,0 +1,155 @@
+use candid::{decode_args, encode_args};
+use rstest::*;
+
+use test_common::user::*;
+
+use super::*;
+
+const NAME: &str = "hello.ic";
+
+fn get_resolver_store(principal: Option<&Principal>) -> ResolverStore {
+    let mut store = ResolverStore::default();
+    store.ensure_created(NAME);
+    if let Some(principal) = principal {
+        store.update_resolver(NAME, |resolver| {
+            resolver.set_record_value(RESOLVER_KEY_ICP_PRINCIPAL.to_string(), principal.to_text())
+        });
+    }
+    store
+}
+
+#[rstest]
+fn test_verify_primary_name(mock_user1: Principal) {
+    let store = get_resolver_store(Some(&mock_user1));
+
+    assert_eq!(verify_primary_name(&store, &mock_user1, NAME), Ok(()));
+}
+
+#[rstest]
+fn test_verify_primary_name_resolver_not_found(mock_user1: Principal) {
+    let store = get_resolver_store(Some(&mock_user1));
+
+    assert_eq!(
+        verify_primary_name(&store, &mock_user1, "world.ic"),
+        Err(ReverseResolutionFailure::ResolverNotFound {
+            name: "world.ic".to_string()
+        })
+    );
+}
+
+#[rstest]
+#[case(None)]
+#[case(Some(mock_user(2)))]
+fn test_verify_primary_name_principal_mismatch(
+    mock_user1: Principal,
+    #[case] principal: Option<Principal>,
+) {
+    let store = get_resolver_store(principal.as_ref());
+
+    assert_eq!(
+        verify_primary_name(&store, &mock_user1, NAME),
+        Err(ReverseResolutionFailure::PrincipalMismatch {
+            name: NAME.to_string(),
+            principal: principal.map(|principal| principal.to_text()),
+        })
+    );
+}
+
+#[rstest]
+fn test_remove_primary_names_of_name(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = ReverseResolverStore::default();
+    store.set_primary_name(mock_user1, NAME.to_string());
+    store.set_context_primary_name(mock_user1, mock_user2, NAME.to_string());
+    store.set_context_primary_name(mock_user1, mock_user1, "world.ic".to_string());
+
+    assert!(store.remove_primary_names_of_name(&mock_user1, NAME));
+
+    assert_eq!(store.get_primary_name(&mock_user1), None);
+    assert_eq!(store.get_primary_name_reverse(&NAME.to_string()), None);
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user2),
+        None
+    );
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user1),
+        Some("world.ic".to_string())
+    );
+    assert!(!store.remove_primary_names_of_name(&mock_user1, NAME));
+}
+
+#[rstest]
+fn test_remove_primary_names_of_name_moved(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = ReverseResolverStore::default();
+    store.set_primary_name(mock_user1, NAME.to_string());
+    store.set_primary_name(mock_user2, NAME.to_string());
+
+    assert!(store.remove_primary_names_of_name(&mock_user1, NAME));
+
+    assert_eq!(
+        store.get_primary_name_reverse(&NAME.to_string()),
+        Some(mock_user2)
+    );
+}
+
+#[rstest]
+fn test_export_import(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = ReverseResolverStore::default();
+    store.set_primary_name(mock_user1, NAME.to_string());
+    store.set_context_primary_name(mock_user1, mock_user2, "world.ic".to_string());
+    let entries = store.export_entries();
+    store.remove_primary_name(mock_user1);
+    store.remove_context_primary_name(&mock_user1, &mock_user2);
+
+    store.import_entries(entries);
+
+    assert_eq!(store.get_primary_name(&mock_user1), Some(NAME.to_string()));
+    assert_eq!(
+        store.get_primary_name_reverse(&NAME.to_string()),
+        Some(mock_user1)
+    );
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user2),
+        Some("world.ic".to_string())
+    );
+}
+
+#[rstest]
+fn test_import_without_context_primary_names(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = ReverseResolverStore::default();
+    store.set_context_primary_name(mock_user1, mock_user2, "world.ic".to_string());
+    let primary_names = HashMap::from([(mock_user1, NAME.to_string())]);
+    let primary_names_reverse = HashMap::from([(NAME.to_string(), mock_user1)]);
+    let bytes = encode_args((&primary_names, &primary_names_reverse)).unwrap();
+
+    store.import_entries(decode_args(&bytes).unwrap());
+
+    assert_eq!(store.get_primary_name(&mock_user1), Some(NAME.to_string()));
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user2),
+        None
+    );
+}
+
+#[rstest]
+fn test_retain_context_primary_names(mock_user1: Principal, mock_user2: Principal) {
+    let mut store = ReverseResolverStore::default();
+    store.set_context_primary_name(mock_user1, mock_user1, NAME.to_string());
+    store.set_context_primary_name(mock_user1, mock_user2, "world.ic".to_string());
+    store.set_context_primary_name(mock_user2, mock_user1, "world.ic".to_string());
+
+    store.retain_context_primary_names(&mock_user1, |name| name == NAME);
+
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user1),
+        Some(NAME.to_string())
+    );
+    assert_eq!(
+        store.get_context_primary_name(&mock_user1, &mock_user2),
+        None
+    );
+    // names of other principals are kept
+    assert_eq!(
+        store.get_context_primary_name(&mock_user2, &mock_user1),
+        Some("world.ic".to_string())
+    );
+}
@@ -1,26 +1,45 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
 use std::vec::Vec;
 
 use candid::{CandidType, Deserialize, Principal};
 use itertools::Itertools;
 
+use common::certified_map::CertifiedValue;
 use common::CallContext;
 use log::{debug, info};
 
-use common::constants::RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL;
+use common::constants::{
+    get_address_key, get_icrc1_ledger_key, RESOLVER_KEY_CONTENTHASH, RESOLVER_KEY_ICP_PRINCIPAL,
+    RESOLVER_KEY_ICRC1_ACCOUNT, RESOLVER_KEY_OFFCHAIN_GATEWAY, RESOLVER_KEY_OFFCHAIN_SIGNER,
+    RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL,
+};
 
+use common::dto::{GetPageInput, GetPageOutput};
 use common::errors::*;
+use common::icrc::Account;
 
 use common::named_canister_ids::CanisterNames;
 use common::permissions::must_not_anonymous;
 
+use crate::certification::{
+    certify, get_certified_value, get_record_value_key, get_record_values, get_reverse_resolve_key,
+    RecordValues,
+};
+use crate::contenthash::decode_contenthash;
+use crate::offchain::{
+    get_gateway_url, verify_offchain_response, OffchainLookup, OffchainResponse, ResolvedRecord,
+};
+use crate::record_history_store::RecordHistoryEntry;
 use crate::resolver_store::*;
+use crate::reverse_resolver_store::{get_verified_primary_name, ReverseResolution};
 use crate::set_record_value_input::{
-    PatchValueOperation, PatchValuesInput, PatchValuesValidator, ResolverValueImportGroup,
-    ResolverValueImportItem,
+    BatchSetDnsRecordsInput, PatchValueOperation, PatchValuesInput, PatchValuesValidator,
+    ResolverValueImportGroup, ResolverValueImportItem, RollbackRecordsInput,
+    SetContextPrimaryNameInput, SetDnsRecordsInput,
 };
-use crate::state::STATE;
+use crate::state::{State, STATE};
 
 #[cfg(test)]
 mod tests;
@@ -28,11 +47,8 @@ mod tests;
 #[derive(Default)]
 pub struct ResolverService {}
 
-fn get_resolver<'a>(
-    resolvers: &'a HashMap<String, Resolver>,
-    name: &'a str,
-) -> ServiceResult<&'a Resolver> {
-    match resolvers.get(name) {
+fn get_resolver(store: &ResolverStore, name: &str) -> ServiceResult<Resolver> {
+    match store.get_resolver(name) {
         Some(resolver) => Ok(resolver),
         None => Err(NamingError::ResolverNotFoundError {
             name: name.to_string(),
@@ -51,9 +67,8 @@ impl ResolverService {
 
         let resolver = STATE.with(|s| {
             let resolver_store = s.resolver_store.borrow();
-            let resolvers = resolver_store.get_resolvers();
-            if let Ok(resolver) = get_resolver(resolvers, name) {
-                resolver.clone()
+            if let Ok(resolver) = get_resolver(&resolver_store, name) {
+                resolver
             } else {
                 Resolver::new(name.to_string())
             }
@@ -66,33 +81,239 @@ impl ResolverService {
             patch_value_validator.validate_and_generate_owner_validator(caller)?;
 
         let input = owner_validator.validate().await?;
-        input.update_state()?;
+        input.update_state(&call_context)?;
+
+        Ok(true)
+    }
+
+    /// Restore record values of the name to the version, by the owner of the name
+    pub async fn rollback_records(
+        &mut self,
+        call_context: CallContext,
+        name: &str,
+        version: u64,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let input = RollbackRecordsInput::new(name.to_string(), version);
+        input.validate_owner(&caller).await?;
+        input.update_state(&call_context)?;
+        Ok(true)
+    }
+
+    /// Record history of the name, newest first
+    pub fn get_record_history(
+        &self,
+        name: &str,
+        input: &GetPageInput,
+    ) -> ServiceResult<GetPageOutput<RecordHistoryEntry>> {
+        input.validate()?;
+        STATE.with(|s| {
+            let store = s.record_history_store.borrow();
+            let items = store
+                .get_entries(name)
+                .into_iter()
+                .skip(input.offset)
+                .take(input.limit)
+                .collect();
+            Ok(GetPageOutput::new(items))
+        })
+    }
 
+    /// Replace typed DNS records of the name, empty `records` removes all of them
+    pub async fn set_dns_records(
+        &mut self,
+        call_context: CallContext,
+        name: &str,
+        records: Vec<DnsRecord>,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let input = SetDnsRecordsInput::new(name.to_string(), records)?;
+        input.validate_owner(&caller).await?;
+        input.update_state()?;
         Ok(true)
     }
 
+    pub async fn batch_set_dns_records(
+        &mut self,
+        call_context: CallContext,
+        items: Vec<(String, Vec<DnsRecord>)>,
+    ) -> ServiceResult<Vec<(String, ServiceResult<bool>)>> {
+        let caller = call_context.must_not_anonymous()?;
+        let input = BatchSetDnsRecordsInput::new(items)?;
+        Ok(input.update(&caller).await)
+    }
+
+    pub fn batch_get_dns_records(
+        &self,
+        names: Vec<String>,
+    ) -> ServiceResult<HashMap<String, Vec<DnsRecord>>> {
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let result = names
+                .into_iter()
+                .map(|name| {
+                    let records = store
+                        .get_resolver(&name)
+                        .map(|resolver| resolver.get_dns_records().to_vec())
+                        .unwrap_or_default();
+                    (name, records)
+                })
+                .collect();
+            Ok(result)
+        })
+    }
+
     pub fn ensure_resolver_created(&mut self, name: &str) -> ServiceResult<bool> {
         STATE.with(|s| {
             let mut store = s.resolver_store.borrow_mut();
-            let resolvers = store.get_resolvers_mut();
             let name = name.to_string();
-            if !resolvers.contains_key(&name) {
-                resolvers.insert(name.clone(), Resolver::new(name.clone()));
+            if !store.has_resolver(&name) {
+                store.set_resolver(Resolver::new(name.clone()));
                 info!("Created resolver {}", name);
             } else {
                 info!("Resolver {} already exists", name);
             }
+            drop(store);
+            certify(s, &[name], &[]);
             Ok(true)
         })
     }
+
+    /// Keep ttl of the name in sync with registry, it is used by DNS answers
+    pub fn set_ttl(&mut self, caller: CallContext, name: &str, ttl: u64) -> ServiceResult<bool> {
+        caller.must_be_named_canister(CanisterNames::Registry)?;
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store
+                .update_resolver(name, |resolver| resolver.set_ttl(ttl))
+                .ok_or_else(|| NamingError::ResolverNotFoundError {
+                    name: name.to_string(),
+                })?;
+            info!("Set ttl of resolver {} to {}", name, ttl);
+            Ok(true)
+        })
+    }
+
+    /// Address of the name for the SLIP-44 coin type, see ENSIP-9
+    pub fn get_address(&self, name: &str, coin_type: u32) -> ServiceResult<Option<String>> {
+        let key = match get_address_key(coin_type) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let value = store
+                .get_resolver(name)
+                .and_then(|resolver| resolver.get_record_value().get(key).cloned());
+            Ok(value)
+        })
+    }
+
+    /// Gateway url of the website bound to the name by `contenthash`
+    pub fn get_content_url(&self, name: &str) -> ServiceResult<Option<String>> {
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let url = store
+                .get_resolver(name)
+                .and_then(|resolver| {
+                    resolver
+                        .get_record_value()
+                        .get(RESOLVER_KEY_CONTENTHASH)
+                        .cloned()
+                })
+                .and_then(|value| decode_contenthash(&value).ok())
+                .map(|content_hash| content_hash.get_gateway_url());
+            Ok(url)
+        })
+    }
+
+    /// Account to receive tokens of the ledger, fallback to `icrc1_account.icp` and then `principal.icp`
+    pub fn resolve_payment_account(
+        &self,
+        name: &str,
+        ledger: &Principal,
+    ) -> ServiceResult<Option<Account>> {
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let values = match store.get_resolver(name) {
+                Some(resolver) => resolver.get_record_value().clone(),
+                None => return Ok(None),
+            };
+            let ledger_key = get_icrc1_ledger_key(ledger);
+            let account = [ledger_key.as_str(), RESOLVER_KEY_ICRC1_ACCOUNT]
+                .iter()
+                .filter_map(|key| values.get(*key))
+                .find_map(|value| Account::from_str(value).ok())
+                .or_else(|| {
+                    values
+                        .get(RESOLVER_KEY_ICP_PRINCIPAL)
+                        .and_then(|value| Principal::from_text(value).ok())
+                        .map(Account::new)
+                });
+            Ok(account)
+        })
+    }
+
+    /// Value of the key stored on chain, or where to look it up when the name has an offchain gateway.
+    pub fn resolve_record(&self, name: &str, key: &str) -> ServiceResult<Option<ResolvedRecord>> {
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let values = match store.get_resolver(name) {
+                Some(resolver) => resolver.get_record_value().clone(),
+                None => return Ok(None),
+            };
+            if let Some(value) = values.get(key) {
+                return Ok(Some(ResolvedRecord::OnChain(value.clone())));
+            }
+            let gateway = values.get(RESOLVER_KEY_OFFCHAIN_GATEWAY);
+            let signer = values.get(RESOLVER_KEY_OFFCHAIN_SIGNER);
+            let result = match (gateway, signer) {
+                (Some(gateway), Some(signer)) => {
+                    Some(ResolvedRecord::OffchainLookup(OffchainLookup {
+                        name: name.to_string(),
+                        key: key.to_string(),
+                        url: get_gateway_url(gateway, name, key),
+                        signer: signer.clone(),
+                    }))
+                }
+                _ => None,
+            };
+            Ok(result)
+        })
+    }
+
+    /// Verify the response of the offchain gateway against the signer stored on chain.
+    pub fn verify_offchain_response(
+        &self,
+        call_context: &CallContext,
+        name: &str,
+        key: &str,
+        response: &OffchainResponse,
+    ) -> ServiceResult<String> {
+        let signer = STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            store
+                .get_resolver(name)
+                .and_then(|resolver| {
+                    resolver
+                        .get_record_value()
+                        .get(RESOLVER_KEY_OFFCHAIN_SIGNER)
+                        .cloned()
+                })
+                .ok_or_else(|| NamingError::OffchainGatewayNotFound {
+                    name: name.to_string(),
+                })
+        })?;
+        verify_offchain_response(&signer, name, key, response, call_context.now)
+    }
+
     pub fn get_record_value(&self, name: &str) -> ServiceResult<HashMap<String, String>> {
         STATE.with(|s| {
             let store = s.resolver_store.borrow();
-            let resolvers = store.get_resolvers();
-            if resolvers.get(name).is_none() {
+            if !store.has_resolver(name) {
                 Ok(HashMap::new())
             } else {
-                let resolver = get_resolver(&resolvers, &name)?;
+                let resolver = get_resolver(&store, name)?;
                 let mut values = resolver.get_record_value().clone();
 
                 let store = s.reverse_resolver_store.borrow();
@@ -107,6 +328,16 @@ impl ResolverService {
         })
     }
 
+    /// Record values of the name with the witness of `record_value/{name}` in the certified map.
+    /// Values are sorted by key, and it is None if the resolver of the name is not created.
+    pub fn get_record_value_certified(
+        &self,
+        name: &str,
+    ) -> ServiceResult<CertifiedValue<RecordValues>> {
+        let values = STATE.with(|s| get_record_values(s, name));
+        Ok(get_certified_value(&get_record_value_key(name), values))
+    }
+
     pub fn remove_resolvers(&self, caller: CallContext, names: Vec<String>) -> ServiceResult<bool> {
         caller.must_be_named_canister(CanisterNames::Registry)?;
         STATE.with(|s| {
@@ -120,56 +351,138 @@ impl ResolverService {
                 }
                 info!("Removing resolvers {}", &names.join(", "));
             }
-
-            // remove primary names
             {
-                let mut store = s.reverse_resolver_store.borrow_mut();
+                let mut store = s.record_history_store.borrow_mut();
                 for name in names.iter() {
-                    if let Some(principal) = store.remove_primary_name_by_name(name) {
-                        debug!("Removing reverse resolution principal {}", principal);
-                    }
+                    store.remove_history(name);
                 }
             }
+
+            let principals = remove_primary_names(s, &names, &resolvers);
+            certify(s, &names, &principals);
             Ok(true)
         })
     }
 
+    /// Remove primary names of the names once their owner changed, record values are kept.
+    /// A primary name is set by the owner of the name, so it is not verified for the new owner.
+    pub fn reset_primary_names(
+        &self,
+        caller: CallContext,
+        names: Vec<String>,
+    ) -> ServiceResult<bool> {
+        caller.must_be_named_canister(CanisterNames::Registry)?;
+        STATE.with(|s| {
+            let resolvers = {
+                let store = s.resolver_store.borrow();
+                names
+                    .iter()
+                    .filter_map(|name| store.get_resolver(name))
+                    .collect::<Vec<_>>()
+            };
+            info!("Resetting primary names of {}", &names.join(", "));
+            let principals = remove_primary_names(s, &names, &resolvers);
+            certify(s, &names, &principals);
+            Ok(true)
+        })
+    }
+
+    /// Verified primary name of the principal, it is none if `principal.icp` of the name is not the principal
     pub fn reverse_resolve_principal(&self, principal: Principal) -> ServiceResult<Option<String>> {
+        self.reverse_resolve_principal_in_context(principal, None)
+    }
+
+    /// Verified primary name of the principal in the context, falls back to the default primary name
+    pub fn reverse_resolve_principal_in_context(
+        &self,
+        principal: Principal,
+        context: Option<Principal>,
+    ) -> ServiceResult<Option<String>> {
         let auth_principal = must_not_anonymous(&principal)?;
 
         STATE.with(|s| {
-            let reverse_resolver_store = s.reverse_resolver_store.borrow();
-            let value = reverse_resolver_store.get_primary_name(&auth_principal.0);
-            value
-                .map(|value| Ok(Some(value.clone())))
-                .unwrap_or(Ok(None))
+            let name = get_verified_primary_name(s, &auth_principal.0, context.as_ref());
+            Ok(name.ok())
         })
     }
 
+    /// Primary name of the principal with the witness of `reverse_resolve/{principal}` in the certified map.
+    pub fn reverse_resolve_principal_certified(
+        &self,
+        principal: Principal,
+    ) -> ServiceResult<CertifiedValue<String>> {
+        let name = self.reverse_resolve_principal(principal)?;
+        Ok(get_certified_value(
+            &get_reverse_resolve_key(&principal),
+            name,
+        ))
+    }
+
     pub fn batch_get_reverse_resolve_principal(
         &self,
         principals: Vec<Principal>,
     ) -> ServiceResult<HashMap<Principal, Option<String>>> {
+        let result = self.batch_verify_reverse_resolve(principals, None)?;
+        Ok(result
+            .into_iter()
+            .map(|(principal, resolution)| match resolution {
+                ReverseResolution::Verified(name) => (principal, Some(name)),
+                ReverseResolution::Unverified(_) => (principal, None),
+            })
+            .collect())
+    }
+
+    /// Verified primary names of the principals in the context, or the reason why it is not verified
+    pub fn batch_verify_reverse_resolve(
+        &self,
+        principals: Vec<Principal>,
+        context: Option<Principal>,
+    ) -> ServiceResult<HashMap<Principal, ReverseResolution>> {
         let mut auth_principals = Vec::new();
         for principal in principals {
             auth_principals.push(must_not_anonymous(&principal)?);
         }
         STATE.with(|s| {
-            let reverse_resolver_store = s.reverse_resolver_store.borrow();
             let result = auth_principals
                 .iter()
                 .map(|auth_principal| {
-                    let value = reverse_resolver_store.get_primary_name(&auth_principal.0);
-                    match value {
-                        Some(value) => (auth_principal.0, Some(value.clone())),
-                        None => (auth_principal.0, None),
-                    }
+                    let name = get_verified_primary_name(s, &auth_principal.0, context.as_ref());
+                    (auth_principal.0, name.into())
                 })
                 .collect();
             Ok(result)
         })
     }
 
+    /// Set the primary name of the caller used in the context, by the owner of the name
+    pub async fn set_context_primary_name(
+        &mut self,
+        call_context: CallContext,
+        name: &str,
+        context: Principal,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        let input = SetContextPrimaryNameInput::new(name.to_string(), context);
+        input.validate_owner(&caller).await?;
+        input.update_state(&caller)?;
+        Ok(true)
+    }
+
+    /// Remove the primary name of the caller used in the context, returns false if it is not set
+    pub fn remove_context_primary_name(
+        &mut self,
+        call_context: CallContext,
+        context: Principal,
+    ) -> ServiceResult<bool> {
+        let caller = call_context.must_not_anonymous()?;
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            Ok(store
+                .remove_context_primary_name(&caller.0, &context)
+                .is_some())
+        })
+    }
+
     pub fn import_record_value(
         &self,
         call_context: &CallContext,
@@ -182,11 +495,10 @@ impl ResolverService {
 
         let items: Vec<(ResolverValueImportGroup, Resolver)> = STATE.with(|s| {
             let resolvers_store = s.resolver_store.borrow();
-            let resolvers = resolvers_store.get_resolvers();
             group
                 .iter()
-                .map(|item| match get_resolver(resolvers, &item.name) {
-                    Ok(resolver) => (item.clone(), resolver.clone()),
+                .map(|item| match get_resolver(&resolvers_store, &item.name) {
+                    Ok(resolver) => (item.clone(), resolver),
                     Err(_) => (item.clone(), Resolver::new(item.name.clone())),
                 })
                 .collect::<Vec<_>>()
@@ -206,7 +518,7 @@ impl ResolverService {
         }
 
         for input in list {
-            input.update_state()?;
+            input.update_state(call_context)?;
         }
         Ok(true)
     }
@@ -250,3 +562,24 @@ impl ImportRecordValueRequest {
         result
     }
 }
+
+/// Remove primary names of the names, both the default ones and the ones in contexts.
+/// Returns principals whose default primary name is removed.
+fn remove_primary_names(state: &State, names: &[String], resolvers: &[Resolver]) -> Vec<Principal> {
+    let mut principals = vec![];
+    let mut store = state.reverse_resolver_store.borrow_mut();
+    for name in names.iter() {
+        if let Some(principal) = store.remove_primary_name_by_name(name) {
+            debug!("Removing reverse resolution principal {}", principal);
+            principals.push(principal);
+        }
+    }
+    // primary names in contexts are only verified for principal.icp of the name
+    for resolver in resolvers.iter() {
+        let value = resolver.get_record_value().get(RESOLVER_KEY_ICP_PRINCIPAL);
+        if let Some(Ok(principal)) = value.map(Principal::from_text) {
+            store.remove_primary_names_of_name(&principal, resolver.get_name());
+        }
+    }
+    principals
+}
@@ -12,11 +12,10 @@ use super::*;
 fn add_test_resolver(name: &str) -> Resolver {
     STATE.with(|s| {
         let mut store = s.resolver_store.borrow_mut();
-        let resolvers = store.get_resolvers_mut();
         let mut resolver = Resolver::new(name.to_string());
         resolver.set_record_value(RESOLVER_KEY_GITHUB.to_string(), "icns".to_string());
         resolver.set_record_value(RESOLVER_KEY_TWITTER.to_string(), "twitter".to_string());
-        resolvers.insert(name.to_string(), resolver.clone());
+        store.set_resolver(resolver.clone());
         resolver
     })
 }
@@ -41,9 +40,8 @@ mod ensure_resolver_created {
         // assert
         STATE.with(|s| {
             let store = s.resolver_store.borrow();
-            let resolvers = store.get_resolvers();
-            assert_eq!(resolvers.len(), 1);
-            assert_eq!(resolvers.get(name).unwrap().get_name(), name);
+            assert_eq!(store.get_resolver_count(), 1);
+            assert_eq!(store.get_resolver(name).unwrap().get_name(), name);
         });
     }
 
@@ -61,9 +59,8 @@ mod ensure_resolver_created {
         assert!(result.is_ok());
         STATE.with(|s| {
             let store = s.resolver_store.borrow();
-            let resolvers = store.get_resolvers();
-            assert_eq!(resolvers.len(), 1);
-            assert_eq!(resolvers.get(name).unwrap().get_name(), name);
+            assert_eq!(store.get_resolver_count(), 1);
+            assert_eq!(store.get_resolver(name).unwrap().get_name(), name);
         });
     }
 }
@@ -132,10 +129,9 @@ mod remove_resolvers {
 
         STATE.with(|s| {
             let store = s.resolver_store.borrow();
-            let resolvers = store.get_resolvers();
-            assert_eq!(resolvers.len(), 2);
-            resolvers.get("test1.ic").unwrap();
-            resolvers.get("app.nice.ic").unwrap();
+            assert_eq!(store.get_resolver_count(), 2);
+            assert!(store.has_resolver("test1.ic"));
+            assert!(store.has_resolver("app.nice.ic"));
 
             let store = s.reverse_resolver_store.borrow();
             assert_eq!(
@@ -169,6 +165,523 @@ mod remove_resolvers {
     }
 }
 
+mod reset_primary_names {
+    use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+
+    use super::*;
+
+    #[rstest]
+    fn test_reset_primary_names(
+        _init_test: (),
+        service: ResolverService,
+        mock_now: u64,
+        mock_user1: Principal,
+        mock_canister1: Principal,
+    ) {
+        let name = "nice.ic";
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            let mut resolver = Resolver::new(name.to_string());
+            resolver.set_record_value(RESOLVER_KEY_ICP_PRINCIPAL.to_string(), mock_user1.to_text());
+            store.set_resolver(resolver);
+
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_primary_name(mock_user1, name.to_string());
+            store.set_context_primary_name(mock_user1, mock_canister1, name.to_string());
+        });
+
+        // act
+        let caller = get_named_get_canister_id(CanisterNames::Registry);
+        let call_context = CallContext::new(caller, TimeInNs(mock_now));
+        let result = service.reset_primary_names(call_context, vec![name.to_string()]);
+
+        // assert
+        assert_eq!(result, Ok(true));
+        STATE.with(|s| {
+            let store = s.reverse_resolver_store.borrow();
+            assert_eq!(store.get_primary_name(&mock_user1), None);
+            assert_eq!(store.get_primary_name_reverse(&name.to_string()), None);
+            assert_eq!(
+                store.get_context_primary_name(&mock_user1, &mock_canister1),
+                None
+            );
+
+            // record values are kept
+            let store = s.resolver_store.borrow();
+            assert!(store
+                .get_resolver(name)
+                .unwrap()
+                .contains_key(RESOLVER_KEY_ICP_PRINCIPAL));
+        });
+    }
+
+    #[rstest]
+    fn test_reset_primary_names_failed_not_registry(service: ResolverService) {
+        let result = service.reset_primary_names(CallContext::anonymous(), vec![]);
+
+        assert_eq!(result, Err(NamingError::Unauthorized));
+    }
+}
+
+mod set_ttl {
+    use common::constants::DEFAULT_TTL;
+    use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+
+    use super::*;
+
+    #[rstest]
+    fn test_set_ttl(mut service: ResolverService, mock_now: u64) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created("test1.ic");
+            let resolver = store.get_resolver("test1.ic").unwrap();
+            assert_eq!(resolver.get_ttl(), DEFAULT_TTL);
+        });
+
+        // act
+        let caller = get_named_get_canister_id(CanisterNames::Registry);
+        let call_context = CallContext::new(caller, TimeInNs(mock_now));
+        let result = service.set_ttl(call_context, "test1.ic", 300);
+
+        // assert
+        assert!(result.is_ok());
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let resolver = store.get_resolver("test1.ic").unwrap();
+            assert_eq!(resolver.get_ttl(), 300);
+        });
+    }
+
+    #[rstest]
+    fn test_set_ttl_resolver_not_found(mut service: ResolverService, mock_now: u64) {
+        let caller = get_named_get_canister_id(CanisterNames::Registry);
+        let call_context = CallContext::new(caller, TimeInNs(mock_now));
+        let result = service.set_ttl(call_context, "test1.ic", 300);
+
+        assert_eq!(
+            result.unwrap_err(),
+            NamingError::ResolverNotFoundError {
+                name: "test1.ic".to_string()
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_set_ttl_failed_not_registry(mut service: ResolverService) {
+        let result = service.set_ttl(CallContext::anonymous(), "test1.ic", 300);
+
+        assert_eq!(result.unwrap_err(), NamingError::Unauthorized);
+    }
+}
+
+mod get_record_history {
+    use super::*;
+    use crate::record_history_store::RecordChange;
+    use common::named_canister_ids::get_named_get_canister_id;
+
+    fn add_history(name: &str, caller: Principal, count: usize) {
+        STATE.with(|s| {
+            let mut store = s.record_history_store.borrow_mut();
+            for i in 0..count {
+                store.add_entry(
+                    name,
+                    caller,
+                    TimeInNs(i as u64),
+                    vec![RecordChange {
+                        key: RESOLVER_KEY_URL.to_string(),
+                        old_value: None,
+                        new_value: Some(i.to_string()),
+                    }],
+                );
+            }
+        });
+    }
+
+    #[rstest]
+    fn test_get_record_history(_init_test: (), service: ResolverService, mock_user1: Principal) {
+        add_history("test1.ic", mock_user1, 5);
+
+        let result = service
+            .get_record_history(
+                "test1.ic",
+                &GetPageInput {
+                    offset: 1,
+                    limit: 2,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            result
+                .items
+                .iter()
+                .map(|entry| entry.version)
+                .collect::<Vec<_>>(),
+            vec![4, 3]
+        );
+        let result = service
+            .get_record_history(
+                "test2.ic",
+                &GetPageInput {
+                    offset: 0,
+                    limit: 2,
+                },
+            )
+            .unwrap();
+        assert!(result.items.is_empty());
+    }
+
+    #[rstest]
+    fn test_get_record_history_invalid_page(_init_test: (), service: ResolverService) {
+        let result = service.get_record_history(
+            "test1.ic",
+            &GetPageInput {
+                offset: 0,
+                limit: 0,
+            },
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    fn test_remove_resolvers_remove_history(
+        _init_test: (),
+        service: ResolverService,
+        mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        STATE.with(|s| s.resolver_store.borrow_mut().ensure_created("test1.ic"));
+        add_history("test1.ic", mock_user1, 2);
+
+        let caller = get_named_get_canister_id(CanisterNames::Registry);
+        let call_context = CallContext::new(caller, TimeInNs(mock_now));
+        service
+            .remove_resolvers(call_context, vec!["test1.ic".to_string()])
+            .unwrap();
+
+        STATE.with(|s| {
+            assert_eq!(s.record_history_store.borrow().get_version("test1.ic"), 0);
+        });
+    }
+}
+
+mod get_address {
+    use super::*;
+
+    #[rstest]
+    fn test_get_address(_init_test: (), service: ResolverService) {
+        let eth = "0xb436ef6cc9f24193ccb42f98be2b1db764484514";
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created("test1.ic");
+            let mut resolver = store.get_resolver("test1.ic").unwrap();
+            resolver.set_record_value(RESOLVER_KEY_ETH.to_string(), eth.to_string());
+            store.set_resolver(resolver);
+        });
+
+        assert_eq!(
+            service.get_address("test1.ic", 60).unwrap(),
+            Some(eth.to_string())
+        );
+        assert_eq!(service.get_address("test1.ic", 0).unwrap(), None);
+        assert_eq!(service.get_address("test1.ic", 9999).unwrap(), None);
+        assert_eq!(service.get_address("test2.ic", 60).unwrap(), None);
+    }
+}
+
+mod get_content_url {
+    use super::*;
+
+    #[rstest]
+    fn test_get_content_url(_init_test: (), service: ResolverService) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created("test1.ic");
+            store.ensure_created("test2.ic");
+            let mut resolver = store.get_resolver("test1.ic").unwrap();
+            resolver.set_record_value(
+                RESOLVER_KEY_CONTENTHASH.to_string(),
+                "0xe301122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f"
+                    .to_string(),
+            );
+            store.set_resolver(resolver);
+        });
+
+        assert_eq!(
+            service.get_content_url("test1.ic").unwrap(),
+            Some("https://ipfs.io/ipfs/QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4".to_string())
+        );
+        assert_eq!(service.get_content_url("test2.ic").unwrap(), None);
+        assert_eq!(service.get_content_url("test3.ic").unwrap(), None);
+    }
+}
+
+mod resolve_payment_account {
+    use super::*;
+
+    const ACCOUNT: &str =
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627i.1";
+    const PRINCIPAL: &str = "uqf5b-uk33j-b72z7-uoz2o-hmhl2-lw63v-zwh5f-cmnii-k4pzi-jbomw-nae";
+
+    fn ledger() -> Principal {
+        Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()
+    }
+
+    fn set_values(name: &str, values: Vec<(String, &str)>) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created(name);
+            let mut resolver = store.get_resolver(name).unwrap();
+            for (key, value) in values {
+                resolver.set_record_value(key, value.to_string());
+            }
+            store.set_resolver(resolver);
+        });
+    }
+
+    #[rstest]
+    fn test_resolve_payment_account_of_ledger(_init_test: (), service: ResolverService) {
+        set_values(
+            "test1.ic",
+            vec![
+                (get_icrc1_ledger_key(&ledger()), ACCOUNT),
+                (RESOLVER_KEY_ICP_PRINCIPAL.to_string(), PRINCIPAL),
+            ],
+        );
+
+        let result = service
+            .resolve_payment_account("test1.ic", &ledger())
+            .unwrap();
+
+        assert_eq!(result, Some(Account::from_str(ACCOUNT).unwrap()));
+    }
+
+    #[rstest]
+    fn test_resolve_payment_account_fallback_icrc1_account(
+        _init_test: (),
+        service: ResolverService,
+    ) {
+        let other_ledger = Principal::from_text("mxzaz-hqaaa-aaaar-qaada-cai").unwrap();
+        set_values(
+            "test1.ic",
+            vec![
+                (get_icrc1_ledger_key(&other_ledger), PRINCIPAL),
+                (RESOLVER_KEY_ICRC1_ACCOUNT.to_string(), ACCOUNT),
+                (RESOLVER_KEY_ICP_PRINCIPAL.to_string(), PRINCIPAL),
+            ],
+        );
+
+        let result = service
+            .resolve_payment_account("test1.ic", &ledger())
+            .unwrap();
+
+        assert_eq!(result, Some(Account::from_str(ACCOUNT).unwrap()));
+    }
+
+    #[rstest]
+    fn test_resolve_payment_account_fallback_principal(_init_test: (), service: ResolverService) {
+        set_values(
+            "test1.ic",
+            vec![(RESOLVER_KEY_ICP_PRINCIPAL.to_string(), PRINCIPAL)],
+        );
+
+        let result = service
+            .resolve_payment_account("test1.ic", &ledger())
+            .unwrap();
+
+        assert_eq!(
+            result,
+            Some(Account::new(Principal::from_text(PRINCIPAL).unwrap()))
+        );
+    }
+
+    #[rstest]
+    fn test_resolve_payment_account_not_found(_init_test: (), service: ResolverService) {
+        set_values(
+            "test1.ic",
+            vec![(RESOLVER_KEY_GITHUB.to_string(), "icnaming")],
+        );
+
+        assert_eq!(
+            service
+                .resolve_payment_account("test1.ic", &ledger())
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            service
+                .resolve_payment_account("test2.ic", &ledger())
+                .unwrap(),
+            None
+        );
+    }
+}
+
+mod offchain {
+    use super::*;
+    use crate::offchain::get_offchain_response_message;
+    use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+    use serde_bytes::ByteBuf;
+
+    const GATEWAY: &str = "https://gateway.example.com/{name}/{key}.json";
+
+    fn keypair() -> Keypair {
+        let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+
+    fn add_offchain_resolver(name: &str) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created(name);
+            let mut resolver = store.get_resolver(name).unwrap();
+            resolver.set_record_value(
+                RESOLVER_KEY_OFFCHAIN_GATEWAY.to_string(),
+                GATEWAY.to_string(),
+            );
+            resolver.set_record_value(
+                RESOLVER_KEY_OFFCHAIN_SIGNER.to_string(),
+                hex::encode(keypair().public.as_bytes()),
+            );
+            resolver.set_record_value(RESOLVER_KEY_GITHUB.to_string(), "icnaming".to_string());
+            store.set_resolver(resolver);
+        });
+    }
+
+    #[rstest]
+    fn test_resolve_record_on_chain(_init_test: (), service: ResolverService) {
+        add_offchain_resolver("test1.ic");
+
+        let result = service
+            .resolve_record("test1.ic", RESOLVER_KEY_GITHUB)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            Some(ResolvedRecord::OnChain("icnaming".to_string()))
+        );
+    }
+
+    #[rstest]
+    fn test_resolve_record_offchain_lookup(_init_test: (), service: ResolverService) {
+        add_offchain_resolver("test1.ic");
+
+        let result = service
+            .resolve_record("test1.ic", RESOLVER_KEY_DESCRIPTION)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            Some(ResolvedRecord::OffchainLookup(OffchainLookup {
+                name: "test1.ic".to_string(),
+                key: RESOLVER_KEY_DESCRIPTION.to_string(),
+                url: "https://gateway.example.com/test1.ic/description.json".to_string(),
+                signer: hex::encode(keypair().public.as_bytes()),
+            }))
+        );
+    }
+
+    #[rstest]
+    fn test_resolve_record_not_found(_init_test: (), service: ResolverService) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created("test1.ic");
+        });
+
+        assert_eq!(
+            service
+                .resolve_record("test1.ic", RESOLVER_KEY_DESCRIPTION)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            service
+                .resolve_record("test2.ic", RESOLVER_KEY_DESCRIPTION)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[rstest]
+    fn test_verify_offchain_response(
+        _init_test: (),
+        service: ResolverService,
+        mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        add_offchain_resolver("test1.ic");
+        let expires_at = mock_now + 60_000_000_000;
+        let message = get_offchain_response_message(
+            "test1.ic",
+            RESOLVER_KEY_DESCRIPTION,
+            "hello",
+            expires_at,
+        );
+        let response = OffchainResponse {
+            value: "hello".to_string(),
+            expires_at,
+            signature: ByteBuf::from(keypair().sign(&message).to_bytes().to_vec()),
+        };
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+
+        let result = service.verify_offchain_response(
+            &call_context,
+            "test1.ic",
+            RESOLVER_KEY_DESCRIPTION,
+            &response,
+        );
+        assert_eq!(result, Ok("hello".to_string()));
+
+        let result = service.verify_offchain_response(
+            &call_context,
+            "test2.ic",
+            RESOLVER_KEY_DESCRIPTION,
+            &response,
+        );
+        assert_eq!(
+            result,
+            Err(NamingError::OffchainGatewayNotFound {
+                name: "test2.ic".to_string()
+            })
+        );
+    }
+}
+
+mod batch_get_dns_records {
+    use super::*;
+
+    #[rstest]
+    fn test_batch_get_dns_records(_init_test: (), service: ResolverService) {
+        let records = vec![DnsRecord {
+            ttl: Some(60),
+            data: DnsRecordData::A("127.0.0.1".to_string()),
+        }];
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created("test1.ic");
+            store.ensure_created("test2.ic");
+            let mut resolver = store.get_resolver("test1.ic").unwrap();
+            resolver.set_dns_records(records.clone());
+            store.set_resolver(resolver);
+        });
+
+        let result = service
+            .batch_get_dns_records(vec![
+                "test1.ic".to_string(),
+                "test2.ic".to_string(),
+                "test3.ic".to_string(),
+            ])
+            .unwrap();
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result["test1.ic"], records);
+        assert!(result["test2.ic"].is_empty());
+        assert!(result["test3.ic"].is_empty());
+    }
+}
+
 mod batch_get_reverse_resolver {
     use super::*;
 
@@ -232,6 +745,143 @@ mod batch_get_reverse_resolver {
     }
 }
 
+mod batch_verify_reverse_resolve {
+    use super::*;
+    use crate::reverse_resolver_store::ReverseResolutionFailure;
+
+    fn set_principal(name: &str, principal: &Principal) {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created(name);
+            let mut resolver = store.get_resolver(name).unwrap();
+            resolver.set_record_value(RESOLVER_KEY_ICP_PRINCIPAL.to_string(), principal.to_text());
+            store.set_resolver(resolver);
+        });
+    }
+
+    #[rstest]
+    fn test_batch_verify_reverse_resolve(
+        _init_test: (),
+        service: ResolverService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let mock_user3 = mock_user(3);
+        let mock_user4 = mock_user(4);
+        set_principal("test1.ic", &mock_user1);
+        set_principal("test2.ic", &mock_user1);
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_primary_name(mock_user1, "test1.ic".to_string());
+            store.set_primary_name(mock_user2, "test2.ic".to_string());
+            store.set_primary_name(mock_user3, "test3.ic".to_string());
+        });
+
+        // act
+        let result = service
+            .batch_verify_reverse_resolve(
+                vec![mock_user1, mock_user2, mock_user3, mock_user4],
+                None,
+            )
+            .unwrap();
+
+        // assert
+        assert_eq!(
+            result[&mock_user1],
+            ReverseResolution::Verified("test1.ic".to_string())
+        );
+        assert_eq!(
+            result[&mock_user2],
+            ReverseResolution::Unverified(ReverseResolutionFailure::PrincipalMismatch {
+                name: "test2.ic".to_string(),
+                principal: Some(mock_user1.to_text()),
+            })
+        );
+        assert_eq!(
+            result[&mock_user3],
+            ReverseResolution::Unverified(ReverseResolutionFailure::ResolverNotFound {
+                name: "test3.ic".to_string(),
+            })
+        );
+        assert_eq!(
+            result[&mock_user4],
+            ReverseResolution::Unverified(ReverseResolutionFailure::NotSet)
+        );
+        let result = service
+            .batch_get_reverse_resolve_principal(vec![mock_user1, mock_user2])
+            .unwrap();
+        assert_eq!(result[&mock_user1], Some("test1.ic".to_string()));
+        assert_eq!(result[&mock_user2], None);
+    }
+
+    #[rstest]
+    fn test_batch_verify_reverse_resolve_in_context(
+        _init_test: (),
+        service: ResolverService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let context = mock_canister(1);
+        set_principal("test1.ic", &mock_user1);
+        set_principal("app.test1.ic", &mock_user1);
+        set_principal("test2.ic", &mock_user2);
+        set_principal("app.test2.ic", &mock_user1);
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_primary_name(mock_user1, "test1.ic".to_string());
+            store.set_context_primary_name(mock_user1, context, "app.test1.ic".to_string());
+            store.set_primary_name(mock_user2, "test2.ic".to_string());
+            store.set_context_primary_name(mock_user2, context, "app.test2.ic".to_string());
+        });
+
+        // act
+        let result = service
+            .batch_verify_reverse_resolve(vec![mock_user1, mock_user2], Some(context))
+            .unwrap();
+
+        // assert
+        assert_eq!(
+            result[&mock_user1],
+            ReverseResolution::Verified("app.test1.ic".to_string())
+        );
+        // unverified name in the context falls back to the default one
+        assert_eq!(
+            result[&mock_user2],
+            ReverseResolution::Verified("test2.ic".to_string())
+        );
+        assert_eq!(
+            service
+                .reverse_resolve_principal_in_context(mock_user1, Some(mock_canister(2)))
+                .unwrap(),
+            Some("test1.ic".to_string())
+        );
+    }
+
+    #[rstest]
+    fn test_remove_context_primary_name(
+        _init_test: (),
+        mut service: ResolverService,
+        mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        let context = mock_canister(1);
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_context_primary_name(mock_user1, context, "app.test1.ic".to_string());
+        });
+        let call_context = || CallContext::new(mock_user1, TimeInNs(mock_now));
+
+        assert_eq!(
+            service.remove_context_primary_name(call_context(), context),
+            Ok(true)
+        );
+        assert_eq!(
+            service.remove_context_primary_name(call_context(), context),
+            Ok(false)
+        );
+    }
+}
+
 mod import_record_value {
     use super::*;
     use crate::set_record_value_input::{
@@ -433,6 +1083,181 @@ mod import_record_value {
     }
 }
 
+mod certified_query {
+    use common::certified_map::get_value_hash;
+    use common::named_canister_ids::{get_named_get_canister_id, CanisterNames};
+    use common::permissions::get_admin;
+
+    use super::*;
+    use crate::certification::{get_record_value_key, get_reverse_resolve_key};
+    use crate::set_record_value_input::PatchValueOperation;
+    use crate::state::CERTIFIED_MAP;
+
+    fn get_leaf(key: &str) -> Option<[u8; 32]> {
+        CERTIFIED_MAP.with(|map| map.borrow().get(key).cloned())
+    }
+
+    fn import_value(service: &ResolverService, name: &str, key: &str, principal: &Principal) {
+        let call_context = CallContext::new(get_admin(), TimeInNs(0));
+        let request = ImportRecordValueRequest {
+            items: vec![ResolverValueImportItem {
+                name: name.to_string(),
+                key: key.to_string(),
+                value_and_operation: PatchValueOperation::Upsert(principal.to_text()),
+            }],
+        };
+        service
+            .import_record_value(&call_context, &request)
+            .unwrap();
+    }
+
+    fn import_primary_name(service: &ResolverService, name: &str, principal: &Principal) {
+        import_value(
+            service,
+            name,
+            RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL,
+            principal,
+        );
+    }
+
+    fn import_principal(service: &ResolverService, name: &str, principal: &Principal) {
+        import_value(service, name, RESOLVER_KEY_ICP_PRINCIPAL, principal);
+    }
+
+    #[rstest]
+    fn test_get_record_value_certified_not_found(_init_test: (), service: ResolverService) {
+        let result = service.get_record_value_certified("nice.ic").unwrap();
+
+        assert_eq!(result.value, None);
+        assert!(!result.witness.is_empty());
+        assert_eq!(get_leaf(&get_record_value_key("nice.ic")), None);
+    }
+
+    #[rstest]
+    fn test_get_record_value_certified_created(_init_test: (), mut service: ResolverService) {
+        service.ensure_resolver_created("nice.ic").unwrap();
+
+        let result = service.get_record_value_certified("nice.ic").unwrap();
+
+        assert_eq!(result.value, Some(vec![]));
+        assert_eq!(
+            get_leaf(&get_record_value_key("nice.ic")),
+            Some(get_value_hash(&result.value.unwrap()))
+        );
+    }
+
+    #[rstest]
+    fn test_get_record_value_certified_with_primary_name(
+        _init_test: (),
+        service: ResolverService,
+        mock_user1: Principal,
+    ) {
+        import_principal(&service, "nice.ic", &mock_user1);
+        import_primary_name(&service, "nice.ic", &mock_user1);
+
+        let result = service.get_record_value_certified("nice.ic").unwrap();
+
+        let values = result.value.unwrap();
+        assert_eq!(
+            values,
+            vec![
+                (RESOLVER_KEY_ICP_PRINCIPAL.to_string(), mock_user1.to_text()),
+                (
+                    RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL.to_string(),
+                    mock_user1.to_text()
+                )
+            ]
+        );
+        assert_eq!(
+            get_leaf(&get_record_value_key("nice.ic")),
+            Some(get_value_hash(&values))
+        );
+
+        let result = service
+            .reverse_resolve_principal_certified(mock_user1)
+            .unwrap();
+        assert_eq!(result.value, Some("nice.ic".to_string()));
+        assert_eq!(
+            get_leaf(&get_reverse_resolve_key(&mock_user1)),
+            Some(get_value_hash(&"nice.ic".to_string()))
+        );
+    }
+
+    #[rstest]
+    fn test_primary_name_moved(_init_test: (), service: ResolverService, mock_user1: Principal) {
+        import_primary_name(&service, "nice.ic", &mock_user1);
+        import_principal(&service, "app.nice.ic", &mock_user1);
+        import_primary_name(&service, "app.nice.ic", &mock_user1);
+
+        let result = service.get_record_value_certified("nice.ic").unwrap();
+        assert_eq!(result.value, Some(vec![]));
+        assert_eq!(
+            get_leaf(&get_record_value_key("nice.ic")),
+            Some(get_value_hash(&result.value.unwrap()))
+        );
+        assert_eq!(
+            get_leaf(&get_reverse_resolve_key(&mock_user1)),
+            Some(get_value_hash(&"app.nice.ic".to_string()))
+        );
+    }
+
+    #[rstest]
+    fn test_remove_resolvers_uncertified(
+        _init_test: (),
+        service: ResolverService,
+        mock_user1: Principal,
+    ) {
+        import_primary_name(&service, "nice.ic", &mock_user1);
+
+        let caller = get_named_get_canister_id(CanisterNames::Registry);
+        let call_context = CallContext::new(caller, TimeInNs(0));
+        service
+            .remove_resolvers(call_context, vec!["nice.ic".to_string()])
+            .unwrap();
+
+        assert_eq!(get_leaf(&get_record_value_key("nice.ic")), None);
+        assert_eq!(get_leaf(&get_reverse_resolve_key(&mock_user1)), None);
+        let result = service
+            .reverse_resolve_principal_certified(mock_user1)
+            .unwrap();
+        assert_eq!(result.value, None);
+    }
+
+    #[rstest]
+    fn test_primary_name_not_verified(
+        _init_test: (),
+        service: ResolverService,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        import_principal(&service, "nice.ic", &mock_user1);
+        import_primary_name(&service, "nice.ic", &mock_user1);
+        assert_eq!(
+            get_leaf(&get_reverse_resolve_key(&mock_user1)),
+            Some(get_value_hash(&"nice.ic".to_string()))
+        );
+
+        // principal.icp changed by an operator
+        import_principal(&service, "nice.ic", &mock_user2);
+
+        assert_eq!(get_leaf(&get_reverse_resolve_key(&mock_user1)), None);
+        let result = service
+            .reverse_resolve_principal_certified(mock_user1)
+            .unwrap();
+        assert_eq!(result.value, None);
+    }
+
+    #[rstest]
+    fn test_reverse_resolve_principal_certified_anonymous(
+        _init_test: (),
+        service: ResolverService,
+    ) {
+        let result = service.reverse_resolve_principal_certified(Principal::anonymous());
+
+        assert_eq!(result.unwrap_err(), NamingError::Unauthorized);
+    }
+}
+
 // mod load_state {
 //     use super::*;
 //     use crate::state::State;
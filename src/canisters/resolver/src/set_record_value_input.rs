@@ -1,31 +1,40 @@
-use crate::coinaddress::{validate_btc_address, validate_ltc_address};
-use crate::resolver_store::Resolver;
-use crate::state::STATE;
+use crate::certification::certify;
+use crate::coinaddress::{
+    validate_btc_address, validate_btc_segwit_address, validate_cosmos_address,
+    validate_doge_address, validate_evm_address, validate_ltc_address, validate_solana_address,
+};
+use crate::contenthash::decode_contenthash;
+use crate::offchain::{parse_signer, validate_gateway_url};
+use crate::record_history_store::RecordChange;
+use crate::resolver_store::{DnsRecord, DnsRecordData, Resolver, ResolverStore};
+use crate::reverse_resolver_store::verify_primary_name;
+use crate::state::{State, STATE};
 use candid::{CandidType, Deserialize, Principal};
 use common::canister_api::ic_impl::RegistryApi;
 use common::canister_api::IRegistryApi;
 use common::constants::{
-    WellKnownResolverKey, RESOLVER_ITEM_MAX_COUNT, RESOLVER_KEY_MAX_LENGTH,
+    get_icrc1_ledger_key, get_icrc1_ledger_of_key, WellKnownResolverKey,
+    RESOLVER_DNS_BATCH_MAX_COUNT, RESOLVER_DNS_RECORD_MAX_COUNT, RESOLVER_ITEM_MAX_COUNT,
+    RESOLVER_KEY_ICP_PRINCIPAL, RESOLVER_KEY_ICRC1_LEDGER_PREFIX, RESOLVER_KEY_MAX_LENGTH,
     RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL, RESOLVER_VALUE_MAX_LENGTH,
 };
 use common::dto::IRegistryUsers;
 use common::errors::{NamingError, ServiceResult};
+use common::icrc::Account;
 use common::named_canister_ids::{is_named_canister_id, CanisterNames};
-use common::AuthPrincipal;
+use common::{AuthPrincipal, CallContext};
 
 use log::{debug, info};
 use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 use std::sync::Arc;
 
 #[cfg(test)]
 mod tests;
 
-fn get_resolver_mut<'a>(
-    resolvers: &'a mut HashMap<String, Resolver>,
-    name: &'a str,
-) -> ServiceResult<&'a mut Resolver> {
-    match resolvers.get_mut(name) {
+fn get_resolver(store: &ResolverStore, name: &str) -> ServiceResult<Resolver> {
+    match store.get_resolver(name) {
         Some(resolver) => Ok(resolver),
         None => Err(NamingError::ResolverNotFoundError {
             name: name.to_string(),
@@ -113,21 +122,23 @@ impl PatchValuesValidator {
 
         for (key, value) in self.patch_values.0.iter() {
             if key != RESOLVER_KEY_SETTING_REVERSE_RESOLUTION_PRINCIPAL {
+                let key = normalize_key(key)?;
+                if patch_values.contains_key(&key) {
+                    // two keys of the same ledger in different text
+                    return Err(NamingError::InvalidResolverKey { key });
+                }
                 match value {
                     PatchValueOperation::Upsert(value) => {
-                        let value = self.validate_key_value(key, value)?;
-                        patch_values.insert(key.clone(), UpdateRecordInput::Set(value.clone()));
+                        let value = self.validate_key_value(&key, value)?;
+                        patch_values.insert(key, UpdateRecordInput::Set(value.clone()));
                     }
                     PatchValueOperation::InsertOrIgnore(value) => {
-                        let value = self.validate_key_value(key, value)?;
-                        patch_values.insert(
-                            key.clone(),
-                            UpdateRecordInput::InsertOrIgnore(value.clone()),
-                        );
+                        let value = self.validate_key_value(&key, value)?;
+                        patch_values.insert(key, UpdateRecordInput::InsertOrIgnore(value.clone()));
                     }
                     PatchValueOperation::Remove => {
-                        let _ = self.validate_key_value(key, &"")?;
-                        patch_values.insert(key.clone(), UpdateRecordInput::Remove);
+                        let _ = self.validate_key_value(&key, &"")?;
+                        patch_values.insert(key, UpdateRecordInput::Remove);
                     }
                 }
             }
@@ -163,9 +174,9 @@ impl PatchValuesValidator {
             }
 
             if let Some(resolver_key) = WellKnownResolverKey::parse(key) {
-                let normalized_value = normalize_value(&resolver_key, value);
-                validate_well_known_value(&resolver_key, &normalized_value)?;
-                Ok(normalized_value)
+                // validate before normalization since case of EVM address is its checksum
+                validate_well_known_value(&resolver_key, value)?;
+                Ok(normalize_value(&resolver_key, value))
             } else {
                 debug!("Not well-Unknown resolver key {}", key);
                 Ok(value.to_string())
@@ -333,11 +344,28 @@ impl SetRecordValueInput {
         }
     }
 
-    pub fn update_state(self) -> ServiceResult<()> {
+    /// Apply the changes and add them to the record history of the name as a new version
+    pub fn update_state(self, call_context: &CallContext) -> ServiceResult<()> {
         STATE.with(|s| {
+            // names and principals which certified values may be changed
+            let mut names = vec![self.name.clone()];
+            let mut principals = vec![];
             // set primary name
             {
                 let mut store = s.reverse_resolver_store.borrow_mut();
+                if let Some(principal) = store.get_primary_name_reverse(&self.name) {
+                    principals.push(principal);
+                }
+                match &self.update_primary_name_input {
+                    UpdatePrimaryNameInput::Set(value)
+                    | UpdatePrimaryNameInput::InsertOrIgnore(value) => {
+                        if let Some(name) = store.get_primary_name(value) {
+                            names.push(name);
+                        }
+                        principals.push(*value);
+                    }
+                    _ => {}
+                }
                 match self.update_primary_name_input {
                     UpdatePrimaryNameInput::DoNothing => {
                         info!("Doing nothing for reverse resolution principal");
@@ -368,12 +396,13 @@ impl SetRecordValueInput {
                 }
             }
             // set record value
+            let mut changes = vec![];
             {
                 let mut store = s.resolver_store.borrow_mut();
                 store.ensure_created(&self.name);
-                let mut resolvers = store.get_resolvers_mut();
-                let resolver = get_resolver_mut(&mut resolvers, &self.name)?;
+                let mut resolver = get_resolver(&store, &self.name)?;
                 for (key, value) in self.update_records_input.iter() {
+                    let old_value = resolver.get_record_value().get(key).cloned();
                     match value {
                         UpdateRecordInput::Remove => {
                             resolver.remove_record_value(key.clone());
@@ -387,16 +416,51 @@ impl SetRecordValueInput {
                             }
                         }
                     }
+                    let new_value = resolver.get_record_value().get(key).cloned();
+                    if old_value != new_value {
+                        changes.push(RecordChange {
+                            key: key.clone(),
+                            old_value,
+                            new_value,
+                        });
+                    }
                 }
+                store.set_resolver(resolver);
             }
+            changes.sort_by(|a, b| a.key.cmp(&b.key));
+            if let Some(principal) = remove_unverified_primary_names(s, &self.name, &changes) {
+                principals.push(principal);
+            }
+            s.record_history_store.borrow_mut().add_entry(
+                &self.name,
+                call_context.caller,
+                call_context.now,
+                changes,
+            );
+            certify(s, &names, &principals);
             Ok(())
         })
     }
 }
 
+/// Keys of a ledger are stored with the canonical text of the ledger principal,
+/// so that `icrc1.<ledger>` resolves no matter how the key was written.
+fn normalize_key(key: &str) -> ServiceResult<String> {
+    if key.starts_with(RESOLVER_KEY_ICRC1_LEDGER_PREFIX) {
+        return match get_icrc1_ledger_of_key(key) {
+            Some(ledger) => Ok(get_icrc1_ledger_key(&ledger)),
+            None => Err(NamingError::InvalidResolverKey {
+                key: key.to_string(),
+            }),
+        };
+    }
+    Ok(key.to_string())
+}
+
 fn normalize_value(key: &WellKnownResolverKey, value: &str) -> String {
     match key {
         WellKnownResolverKey::Eth => value.to_lowercase(),
+        WellKnownResolverKey::ContentHash => value.to_lowercase(),
         _ => value.to_string(),
     }
 }
@@ -439,6 +503,39 @@ fn validate_well_known_value(key: &WellKnownResolverKey, value: &str) -> Service
                 });
             }
         }
+        WellKnownResolverKey::Doge => {
+            if validate_doge_address(value).is_err() {
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "DOGE".to_string(),
+                });
+            }
+        }
+        WellKnownResolverKey::Sol => {
+            if validate_solana_address(value).is_err() {
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "base58 encoded Solana address".to_string(),
+                });
+            }
+        }
+        WellKnownResolverKey::Cosmos { hrp } => {
+            if validate_cosmos_address(hrp, value).is_err() {
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: format!("bech32 address starts with {}1", hrp),
+                });
+            }
+        }
+        WellKnownResolverKey::Icrc1Account | WellKnownResolverKey::Icrc1LedgerAccount => {
+            if Account::from_str(value).is_err() {
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "ICRC-1 account text, e.g. <principal>-<checksum>.<subaccount>"
+                        .to_string(),
+                });
+            }
+        }
         WellKnownResolverKey::IcpCanister => {
             // do nothing validate since, it would be able to set custom domain for canister
         }
@@ -466,6 +563,34 @@ fn validate_well_known_value(key: &WellKnownResolverKey, value: &str) -> Service
                 });
             }
         }
+        WellKnownResolverKey::ContentHash => {
+            if let Err(reason) = decode_contenthash(value) {
+                debug!("Invalid contenthash {}: {}", value, reason);
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "0x prefixed hex of ENSIP-7 contenthash of ipfs, ipns, arweave or icp"
+                        .to_string(),
+                });
+            }
+        }
+        WellKnownResolverKey::OffchainGateway => {
+            if let Err(reason) = validate_gateway_url(value) {
+                debug!("Invalid offchain gateway {}: {}", value, reason);
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "https url contains {name}, e.g. https://example.com/{name}/{key}"
+                        .to_string(),
+                });
+            }
+        }
+        WellKnownResolverKey::OffchainSigner => {
+            if parse_signer(value).is_err() {
+                return Err(NamingError::InvalidResolverValueFormat {
+                    value: value.to_string(),
+                    format: "hex encoded ed25519 public key".to_string(),
+                });
+            }
+        }
         WellKnownResolverKey::Email => {
             // do nothing
         }
@@ -530,26 +655,14 @@ fn validate_well_known_value(key: &WellKnownResolverKey, value: &str) -> Service
     Ok(())
 }
 
-// impl is_valid_eth_address
+// impl is_valid_eth_address, mixed case address should be checksummed as EIP-55
 fn is_valid_eth_address(address: &str) -> bool {
-    if address.len() != 42 {
-        return false;
-    }
-    let mut chars = address.chars();
-    if chars.next() != Some('0') || chars.next() != Some('x') {
-        return false;
-    }
-    for c in chars {
-        if !c.is_ascii_hexdigit() {
-            return false;
-        }
-    }
-    true
+    validate_evm_address(address).is_ok()
 }
 
-// impl is_valid_btc_address BASE58
+// impl is_valid_btc_address BASE58 or bech32 segwit
 fn is_valid_btc_address(address: &str) -> bool {
-    validate_btc_address(address).is_ok()
+    validate_btc_address(address).is_ok() || validate_btc_segwit_address(address).is_ok()
 }
 
 // impl is_valid_icp_address
@@ -579,3 +692,356 @@ fn is_valid_icp_account_id(address: &str) -> bool {
 fn is_valid_ltc_address(address: &str) -> bool {
     validate_ltc_address(address).is_ok()
 }
+
+/// Replace typed DNS records of a name, records are validated and normalized on creation
+pub struct SetDnsRecordsInput {
+    pub name: String,
+    pub records: Vec<DnsRecord>,
+    pub registry_api: Arc<dyn IRegistryApi>,
+}
+
+impl SetDnsRecordsInput {
+    pub fn new(name: String, records: Vec<DnsRecord>) -> ServiceResult<Self> {
+        if records.len() > RESOLVER_DNS_RECORD_MAX_COUNT {
+            return Err(NamingError::TooManyDnsRecords {
+                max: RESOLVER_DNS_RECORD_MAX_COUNT as u32,
+            });
+        }
+        let records = records
+            .into_iter()
+            .map(validate_dns_record)
+            .collect::<ServiceResult<Vec<_>>>()?;
+        let has_cname = records
+            .iter()
+            .any(|record| matches!(record.data, DnsRecordData::CNAME(_)));
+        if has_cname && records.len() > 1 {
+            return Err(NamingError::InvalidDnsRecord {
+                reason: "CNAME record can not coexist with other records".to_string(),
+            });
+        }
+        Ok(Self {
+            name,
+            records,
+            registry_api: Arc::new(RegistryApi::default()),
+        })
+    }
+
+    pub async fn validate_owner(&self, caller: &AuthPrincipal) -> ServiceResult<()> {
+        let users = self.registry_api.get_users(&self.name).await?;
+        if !is_named_canister_id(CanisterNames::Registrar, caller.0)
+            && !users.can_operate(&caller.0)
+        {
+            debug!("Permission denied for {}", caller.0);
+            return Err(NamingError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    pub fn update_state(self) -> ServiceResult<()> {
+        STATE.with(|s| {
+            let mut store = s.resolver_store.borrow_mut();
+            store.ensure_created(&self.name);
+            let mut resolver = get_resolver(&store, &self.name)?;
+            info!(
+                "Setting {} dns records of {}",
+                self.records.len(),
+                self.name
+            );
+            resolver.set_dns_records(self.records);
+            store.set_resolver(resolver);
+            Ok(())
+        })
+    }
+}
+
+/// Replace typed DNS records of names in a batch.
+/// Each name is validated and updated independently, so a failed name does not stop the others.
+pub struct BatchSetDnsRecordsInput {
+    pub items: Vec<(String, Vec<DnsRecord>)>,
+    pub registry_api: Arc<dyn IRegistryApi>,
+}
+
+impl BatchSetDnsRecordsInput {
+    pub fn new(items: Vec<(String, Vec<DnsRecord>)>) -> ServiceResult<Self> {
+        if items.len() > RESOLVER_DNS_BATCH_MAX_COUNT {
+            return Err(NamingError::TooManyNames {
+                max: RESOLVER_DNS_BATCH_MAX_COUNT as u32,
+            });
+        }
+        Ok(Self {
+            items,
+            registry_api: Arc::new(RegistryApi::default()),
+        })
+    }
+
+    /// Returns the result of each name, in the order of the items.
+    pub async fn update(self, caller: &AuthPrincipal) -> Vec<(String, ServiceResult<bool>)> {
+        let mut results = Vec::with_capacity(self.items.len());
+        for (name, records) in self.items {
+            let result = set_dns_records(self.registry_api.clone(), caller, &name, records).await;
+            results.push((name, result));
+        }
+        results
+    }
+}
+
+async fn set_dns_records(
+    registry_api: Arc<dyn IRegistryApi>,
+    caller: &AuthPrincipal,
+    name: &str,
+    records: Vec<DnsRecord>,
+) -> ServiceResult<bool> {
+    let mut input = SetDnsRecordsInput::new(name.to_string(), records)?;
+    input.registry_api = registry_api;
+    input.validate_owner(caller).await?;
+    input.update_state()?;
+    Ok(true)
+}
+
+/// Restore record values of a name to a version in the record history, only the owner of the name is allowed,
+/// so that changes made by a compromised operator can be reverted.
+pub struct RollbackRecordsInput {
+    pub name: String,
+    pub version: u64,
+    pub registry_api: Arc<dyn IRegistryApi>,
+}
+
+impl RollbackRecordsInput {
+    pub fn new(name: String, version: u64) -> Self {
+        Self {
+            name,
+            version,
+            registry_api: Arc::new(RegistryApi::default()),
+        }
+    }
+
+    pub async fn validate_owner(&self, caller: &AuthPrincipal) -> ServiceResult<()> {
+        let users = self.registry_api.get_users(&self.name).await?;
+        if !users.is_owner(&caller.0) {
+            debug!("Permission denied for {}", caller.0);
+            return Err(NamingError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    /// Revert changes after the version, the rollback is added to the record history as a new version
+    pub fn update_state(self, call_context: &CallContext) -> ServiceResult<()> {
+        STATE.with(|s| {
+            let values = s
+                .record_history_store
+                .borrow()
+                .get_values_at(&self.name, self.version)?;
+            let mut changes = vec![];
+            {
+                let mut store = s.resolver_store.borrow_mut();
+                let mut resolver = get_resolver(&store, &self.name)?;
+                for (key, value) in values {
+                    let old_value = resolver.get_record_value().get(&key).cloned();
+                    if old_value == value {
+                        continue;
+                    }
+                    match &value {
+                        Some(value) => resolver.set_record_value(key.clone(), value.clone()),
+                        None => resolver.remove_record_value(key.clone()),
+                    }
+                    changes.push(RecordChange {
+                        key,
+                        old_value,
+                        new_value: value,
+                    });
+                }
+                store.set_resolver(resolver);
+            }
+            info!(
+                "Rollback {} records of {} to version {}",
+                changes.len(),
+                self.name,
+                self.version
+            );
+            changes.sort_by(|a, b| a.key.cmp(&b.key));
+            let principals: Vec<Principal> =
+                remove_unverified_primary_names(s, &self.name, &changes)
+                    .into_iter()
+                    .collect();
+            s.record_history_store.borrow_mut().add_entry(
+                &self.name,
+                call_context.caller,
+                call_context.now,
+                changes,
+            );
+            certify(s, &[self.name.clone()], &principals);
+            Ok(())
+        })
+    }
+}
+
+/// Primary names of the previous `principal.icp` of the name are not verified once it is changed,
+/// returns the previous principal if any of its primary names is removed.
+fn remove_unverified_primary_names(
+    state: &State,
+    name: &str,
+    changes: &[RecordChange],
+) -> Option<Principal> {
+    let change = changes
+        .iter()
+        .find(|change| change.key == RESOLVER_KEY_ICP_PRINCIPAL)?;
+    let principal = Principal::from_text(change.old_value.as_ref()?).ok()?;
+    let mut store = state.reverse_resolver_store.borrow_mut();
+    if store.remove_primary_names_of_name(&principal, name) {
+        info!(
+            "Removing unverified primary names {} of {}",
+            name, principal
+        );
+        Some(principal)
+    } else {
+        None
+    }
+}
+
+/// Set the primary name of the caller used in a context, e.g. a dApp canister.
+/// Only the owner of the name is allowed, and `principal.icp` of the name should be the owner.
+pub struct SetContextPrimaryNameInput {
+    pub name: String,
+    pub context: Principal,
+    pub registry_api: Arc<dyn IRegistryApi>,
+}
+
+impl SetContextPrimaryNameInput {
+    pub fn new(name: String, context: Principal) -> Self {
+        Self {
+            name,
+            context,
+            registry_api: Arc::new(RegistryApi::default()),
+        }
+    }
+
+    pub async fn validate_owner(&self, caller: &AuthPrincipal) -> ServiceResult<()> {
+        let users = self.registry_api.get_users(&self.name).await?;
+        if !users.is_owner(&caller.0) {
+            debug!("Permission denied for {}", caller.0);
+            return Err(NamingError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    /// Set the primary name in the context, unverified primary names of the caller in other contexts are removed
+    pub fn update_state(self, caller: &AuthPrincipal) -> ServiceResult<()> {
+        STATE.with(|s| {
+            let resolver_store = s.resolver_store.borrow();
+            if verify_primary_name(&resolver_store, &caller.0, &self.name).is_err() {
+                return Err(NamingError::PrimaryNameNotVerified { name: self.name });
+            }
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.retain_context_primary_names(&caller.0, |name| {
+                verify_primary_name(&resolver_store, &caller.0, name).is_ok()
+            });
+            info!(
+                "Setting primary name {} of {} in context {}",
+                self.name, caller.0, self.context
+            );
+            store.set_context_primary_name(caller.0, self.context, self.name);
+            Ok(())
+        })
+    }
+}
+
+fn invalid_dns_record(reason: String) -> NamingError {
+    NamingError::InvalidDnsRecord { reason }
+}
+
+/// Domain name without the trailing dot, e.g. `mail.example.com`
+fn normalize_domain_name(name: &str) -> ServiceResult<String> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    if name.is_empty() || name.len() > 253 {
+        return Err(invalid_dns_record(format!("invalid domain name {}", name)));
+    }
+    for label in name.split('.') {
+        let is_valid = !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !is_valid {
+            return Err(invalid_dns_record(format!("invalid domain name {}", name)));
+        }
+    }
+    Ok(name)
+}
+
+fn validate_text(field: &str, value: &str) -> ServiceResult<()> {
+    if value.is_empty() || value.len() > RESOLVER_VALUE_MAX_LENGTH {
+        return Err(invalid_dns_record(format!(
+            "length of {} must be in range [1, {}]",
+            field, RESOLVER_VALUE_MAX_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+// ttl should be a 31 bits value, see RFC 2181
+const DNS_MAX_TTL: u64 = i32::MAX as u64;
+const CAA_FLAG_CRITICAL: u8 = 128;
+const CAA_TAGS: [&str; 3] = ["issue", "issuewild", "iodef"];
+
+pub(crate) fn validate_dns_record(record: DnsRecord) -> ServiceResult<DnsRecord> {
+    if let Some(ttl) = record.ttl {
+        if ttl > DNS_MAX_TTL {
+            return Err(invalid_dns_record(format!(
+                "ttl must be less than {}",
+                DNS_MAX_TTL
+            )));
+        }
+    }
+    let data = match record.data {
+        DnsRecordData::A(address) => {
+            let address = Ipv4Addr::from_str(&address)
+                .map_err(|_| invalid_dns_record(format!("invalid ipv4 address {}", address)))?;
+            DnsRecordData::A(address.to_string())
+        }
+        DnsRecordData::AAAA(address) => {
+            let address = Ipv6Addr::from_str(&address)
+                .map_err(|_| invalid_dns_record(format!("invalid ipv6 address {}", address)))?;
+            DnsRecordData::AAAA(address.to_string())
+        }
+        DnsRecordData::CNAME(name) => DnsRecordData::CNAME(normalize_domain_name(&name)?),
+        DnsRecordData::TXT(value) => {
+            validate_text("TXT", &value)?;
+            DnsRecordData::TXT(value)
+        }
+        DnsRecordData::MX {
+            preference,
+            exchange,
+        } => DnsRecordData::MX {
+            preference,
+            exchange: normalize_domain_name(&exchange)?,
+        },
+        DnsRecordData::SRV {
+            priority,
+            weight,
+            port,
+            target,
+        } => DnsRecordData::SRV {
+            priority,
+            weight,
+            port,
+            target: normalize_domain_name(&target)?,
+        },
+        DnsRecordData::CAA { flags, tag, value } => {
+            if flags != 0 && flags != CAA_FLAG_CRITICAL {
+                return Err(invalid_dns_record(format!("invalid CAA flags {}", flags)));
+            }
+            let tag = tag.to_ascii_lowercase();
+            if !CAA_TAGS.contains(&tag.as_str()) {
+                return Err(invalid_dns_record(format!("invalid CAA tag {}", tag)));
+            }
+            validate_text("CAA value", &value)?;
+            DnsRecordData::CAA { flags, tag, value }
+        }
+    };
+    Ok(DnsRecord {
+        ttl: record.ttl,
+        data,
+    })
+}
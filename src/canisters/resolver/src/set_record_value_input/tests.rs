@@ -16,11 +16,10 @@ use super::*;
 fn add_test_resolver(name: &str) -> Resolver {
     STATE.with(|s| {
         let mut store = s.resolver_store.borrow_mut();
-        let resolvers = store.get_resolvers_mut();
         let mut resolver = Resolver::new(name.to_string());
         resolver.set_record_value(RESOLVER_KEY_GITHUB.to_string(), "icns".to_string());
         resolver.set_record_value(RESOLVER_KEY_TWITTER.to_string(), "twitter".to_string());
-        resolvers.insert(name.to_string(), resolver.clone());
+        store.set_resolver(resolver.clone());
         resolver
     })
 }
@@ -46,6 +45,134 @@ mod validate_value {
         assert_eq!(expected, result.is_ok());
     }
 
+    #[rstest]
+    #[case("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", true)]
+    #[case("0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359", true)]
+    #[case("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED", true)]
+    #[case("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", false)]
+    fn test_eth_checksum_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::Eth, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", true)]
+    #[case("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", true)]
+    #[case("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", false)]
+    #[case("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", false)]
+    #[case("ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9", false)]
+    fn test_btc_segwit_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::Btc, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case("DH5yaieqoZN36fDVciNyRueRGvGLR3mr7L", true)]
+    #[case("DH5yaieqoZN36fDVciNyRueRGvGLR3mr7M", false)]
+    #[case("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", false)]
+    fn test_doge_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::Doge, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", true)]
+    #[case("11111111111111111111111111111111", true)]
+    #[case("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5D", false)]
+    #[case("0okenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", false)]
+    fn test_sol_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::Sol, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        RESOLVER_KEY_ATOM,
+        "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02",
+        true
+    )]
+    #[case(
+        RESOLVER_KEY_ATOM,
+        "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd03",
+        false
+    )]
+    #[case(RESOLVER_KEY_ATOM, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", false)]
+    #[case(RESOLVER_KEY_OSMO, "osmo1hsk6jryyqjfhp5dhc55tc9jtckygx0eplp7aec", true)]
+    #[case(
+        RESOLVER_KEY_OSMO,
+        "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02",
+        false
+    )]
+    #[case(
+        RESOLVER_KEY_SCRT,
+        "secret1hsk6jryyqjfhp5dhc55tc9jtckygx0ep4leyjk",
+        true
+    )]
+    fn test_cosmos_valid_value(#[case] key: &str, #[case] value: String, #[case] expected: bool) {
+        let well_known_key = WellKnownResolverKey::parse(key).unwrap();
+        let result = validate_well_known_value(&well_known_key, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae",
+        true
+    )]
+    #[case(
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627i.1",
+        true
+    )]
+    #[case(
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627j.1",
+        false
+    )]
+    fn test_icrc1_account_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::Icrc1Account, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        "0xe3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f",
+        true
+    )]
+    #[case(
+        "0x90b2ca05000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        true
+    )]
+    #[case("0x8080c00100000000000000020101", true)]
+    #[case("ipfs://QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4", false)]
+    #[case("0xe40101701220", false)]
+    fn test_contenthash_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::ContentHash, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case("https://gateway.example.com/{name}/{key}", true)]
+    #[case("https://gateway.example.com/{key}", false)]
+    #[case("ftp://gateway.example.com/{name}/{key}", false)]
+    fn test_offchain_gateway_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::OffchainGateway, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        true
+    )]
+    #[case(
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f70751",
+        false
+    )]
+    #[case("hello", false)]
+    fn test_offchain_signer_valid_value(#[case] value: String, #[case] expected: bool) {
+        let result = validate_well_known_value(&WellKnownResolverKey::OffchainSigner, &value);
+        assert_eq!(expected, result.is_ok());
+    }
+
     #[rstest]
     #[case("LUwxSibYhxq2u6RfhQmkuTPZRk2wNjwLbE", true)]
     #[case("LMDPD5BLE2G7GGZbboAArBRSXvFBrTC12d", true)]
@@ -273,6 +400,145 @@ mod set_record_validation {
         }
     }
 
+    #[rstest]
+    #[case(
+        "icrc1.hello",
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae"
+    )]
+    #[case(
+        "icrc1.",
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae"
+    )]
+    #[case("icrc1.hello", "")]
+    async fn test_set_record_validation_icrc1_ledger_key_invalid(
+        _init_test: (),
+        _mock_now: u64,
+        mock_user1: Principal,
+        #[case] key: &str,
+        #[case] value: &str,
+    ) {
+        let name = "nice.ic";
+        let mut patch_values: HashMap<String, String> = HashMap::new();
+        patch_values.insert(key.to_string(), value.to_string());
+        let resolver = add_test_resolver(name);
+
+        // act
+        let patch_values: PatchValuesInput = patch_values.into();
+        let patch_value_validator =
+            PatchValuesValidator::new(name.to_string(), patch_values, resolver);
+        let owner_validator = patch_value_validator
+            .validate_and_generate_owner_validator(must_not_anonymous(&mock_user1).unwrap());
+
+        // assert
+        assert_eq!(
+            owner_validator.err(),
+            Some(NamingError::InvalidResolverKey {
+                key: key.to_string()
+            })
+        );
+    }
+
+    #[rstest]
+    async fn test_set_record_icrc1_ledger_key_normalized(
+        _init_test: (),
+        _mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        let name = "nice.ic";
+        let ledger = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+        let value = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+        let mut patch_values: HashMap<String, String> = HashMap::new();
+        patch_values.insert(
+            "icrc1.RYJL3-TYAAA-AAAAA-AAABA-CAI".to_string(),
+            value.to_string(),
+        );
+        let resolver = add_test_resolver(name);
+
+        // act
+        let patch_values: PatchValuesInput = patch_values.into();
+        let patch_value_validator =
+            PatchValuesValidator::new(name.to_string(), patch_values, resolver);
+        let owner_validator = patch_value_validator
+            .validate_and_generate_owner_validator(must_not_anonymous(&mock_user1).unwrap())
+            .unwrap();
+
+        // assert
+        assert_eq!(owner_validator.patch_values.len(), 1);
+        assert_eq!(
+            owner_validator
+                .patch_values
+                .get(&get_icrc1_ledger_key(&ledger)),
+            Some(&UpdateRecordInput::Set(value.to_string()))
+        );
+    }
+
+    #[rstest]
+    async fn test_set_record_icrc1_ledger_key_duplicated(
+        _init_test: (),
+        _mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        let name = "nice.ic";
+        let ledger = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+        let value = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+        let mut patch_values: HashMap<String, String> = HashMap::new();
+        patch_values.insert(
+            "icrc1.RYJL3-TYAAA-AAAAA-AAABA-CAI".to_string(),
+            value.to_string(),
+        );
+        patch_values.insert(get_icrc1_ledger_key(&ledger), value.to_string());
+        let resolver = add_test_resolver(name);
+
+        // act
+        let patch_values: PatchValuesInput = patch_values.into();
+        let patch_value_validator =
+            PatchValuesValidator::new(name.to_string(), patch_values, resolver);
+        let owner_validator = patch_value_validator
+            .validate_and_generate_owner_validator(must_not_anonymous(&mock_user1).unwrap());
+
+        // assert
+        assert_eq!(
+            owner_validator.err(),
+            Some(NamingError::InvalidResolverKey {
+                key: get_icrc1_ledger_key(&ledger)
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627i.1",
+        true
+    )]
+    #[case(
+        "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627j.1",
+        false
+    )]
+    #[case("0xb436ef6cc9f24193ccb42f98be2b1db764484514", false)]
+    async fn test_set_record_validation_icrc1_ledger_account(
+        _init_test: (),
+        _mock_now: u64,
+        mock_user1: Principal,
+        #[case] value: &str,
+        #[case] expected: bool,
+    ) {
+        let name = "nice.ic";
+        let ledger = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+        let mut patch_values: HashMap<String, String> = HashMap::new();
+        patch_values.insert(get_icrc1_ledger_key(&ledger), value.to_string());
+        let resolver = add_test_resolver(name);
+
+        // act
+        let patch_values: PatchValuesInput = patch_values.into();
+        let patch_value_validator =
+            PatchValuesValidator::new(name.to_string(), patch_values, resolver);
+        let owner_validator = patch_value_validator
+            .validate_and_generate_owner_validator(must_not_anonymous(&mock_user1).unwrap());
+
+        // assert
+        assert_eq!(owner_validator.is_ok(), expected);
+    }
+
     #[rstest]
     async fn test_set_record_validation_value_invalid(
         _init_test: (),
@@ -1022,3 +1288,528 @@ mod import_record_value {
         }
     }
 }
+
+mod set_dns_records {
+    use super::*;
+    use crate::resolver_store::{DnsRecord, DnsRecordData};
+    use common::permissions::must_not_anonymous;
+
+    fn record(data: DnsRecordData) -> DnsRecord {
+        DnsRecord { ttl: None, data }
+    }
+
+    #[rstest]
+    #[case(DnsRecordData::A("127.0.0.1".to_string()), Some(DnsRecordData::A("127.0.0.1".to_string())))]
+    #[case(DnsRecordData::A("127.0.0.256".to_string()), None)]
+    #[case(DnsRecordData::A("::1".to_string()), None)]
+    #[case(DnsRecordData::AAAA("2001:DB8:0:0:0:0:0:1".to_string()), Some(DnsRecordData::AAAA("2001:db8::1".to_string())))]
+    #[case(DnsRecordData::AAAA("127.0.0.1".to_string()), None)]
+    #[case(DnsRecordData::CNAME("Hello.Example.com.".to_string()), Some(DnsRecordData::CNAME("hello.example.com".to_string())))]
+    #[case(DnsRecordData::CNAME("-hello.example.com".to_string()), None)]
+    #[case(DnsRecordData::CNAME("hello..com".to_string()), None)]
+    #[case(DnsRecordData::TXT("v=spf1 -all".to_string()), Some(DnsRecordData::TXT("v=spf1 -all".to_string())))]
+    #[case(DnsRecordData::TXT("".to_string()), None)]
+    #[case(DnsRecordData::TXT("a".repeat(RESOLVER_VALUE_MAX_LENGTH + 1)), None)]
+    #[case(
+        DnsRecordData::SRV { priority: 0, weight: 5, port: 5060, target: "sip.example.com".to_string() },
+        Some(DnsRecordData::SRV { priority: 0, weight: 5, port: 5060, target: "sip.example.com".to_string() })
+    )]
+    #[case(DnsRecordData::MX { preference: 10, exchange: "mail example".to_string() }, None)]
+    #[case(
+        DnsRecordData::CAA { flags: 128, tag: "Issue".to_string(), value: "letsencrypt.org".to_string() },
+        Some(DnsRecordData::CAA { flags: 128, tag: "issue".to_string(), value: "letsencrypt.org".to_string() })
+    )]
+    #[case(DnsRecordData::CAA { flags: 1, tag: "issue".to_string(), value: "letsencrypt.org".to_string() }, None)]
+    #[case(DnsRecordData::CAA { flags: 0, tag: "unknown".to_string(), value: "letsencrypt.org".to_string() }, None)]
+    fn test_validate_dns_record(
+        #[case] data: DnsRecordData,
+        #[case] expected: Option<DnsRecordData>,
+    ) {
+        let result = validate_dns_record(record(data));
+        match expected {
+            Some(expected) => assert_eq!(result.unwrap().data, expected),
+            None => assert!(matches!(result, Err(NamingError::InvalidDnsRecord { .. }))),
+        }
+    }
+
+    #[rstest]
+    fn test_validate_dns_record_ttl() {
+        let result = validate_dns_record(DnsRecord {
+            ttl: Some(u64::MAX),
+            data: DnsRecordData::A("127.0.0.1".to_string()),
+        });
+        assert!(matches!(result, Err(NamingError::InvalidDnsRecord { .. })));
+    }
+
+    #[rstest]
+    fn test_cname_can_not_coexist(_init_test: ()) {
+        let records = vec![
+            record(DnsRecordData::CNAME("hello.example.com".to_string())),
+            record(DnsRecordData::TXT("hello".to_string())),
+        ];
+
+        let result = SetDnsRecordsInput::new("nice.ic".to_string(), records);
+
+        assert!(matches!(result, Err(NamingError::InvalidDnsRecord { .. })));
+    }
+
+    #[rstest]
+    fn test_too_many_records(_init_test: ()) {
+        let records = vec![
+            record(DnsRecordData::TXT("hello".to_string()));
+            RESOLVER_DNS_RECORD_MAX_COUNT + 1
+        ];
+
+        let result = SetDnsRecordsInput::new("nice.ic".to_string(), records);
+
+        assert!(matches!(result, Err(NamingError::TooManyDnsRecords { .. })));
+    }
+
+    #[rstest]
+    async fn test_set_dns_records(
+        _init_test: (),
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+    ) {
+        let name = "nice.ic";
+        add_test_resolver(name);
+        mock_registry_api
+            .expect_get_users()
+            .returning(move |_name| {
+                Ok(RegistryUsers {
+                    owner: mock_user1,
+                    operators: HashSet::new(),
+                })
+            });
+        let records = vec![
+            record(DnsRecordData::A("127.0.0.1".to_string())),
+            record(DnsRecordData::TXT("hello".to_string())),
+        ];
+
+        let mut input = SetDnsRecordsInput::new(name.to_string(), records.clone()).unwrap();
+        input.registry_api = Arc::new(mock_registry_api);
+        input
+            .validate_owner(&must_not_anonymous(&mock_user1).unwrap())
+            .await
+            .unwrap();
+        input.update_state().unwrap();
+
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            let resolver = store.get_resolver(name).unwrap();
+            assert_eq!(resolver.get_dns_records(), records.as_slice());
+            // record values are kept
+            assert!(resolver.contains_key(RESOLVER_KEY_GITHUB));
+        });
+    }
+
+    #[rstest]
+    async fn test_set_dns_records_permission_denied(
+        _init_test: (),
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        mock_registry_api
+            .expect_get_users()
+            .returning(move |_name| {
+                Ok(RegistryUsers {
+                    owner: mock_user1,
+                    operators: HashSet::new(),
+                })
+            });
+
+        let mut input = SetDnsRecordsInput::new("nice.ic".to_string(), vec![]).unwrap();
+        input.registry_api = Arc::new(mock_registry_api);
+        let result = input
+            .validate_owner(&must_not_anonymous(&mock_user2).unwrap())
+            .await;
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    async fn test_batch_set_dns_records(
+        _init_test: (),
+        mut mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        mock_registry_api.expect_get_users().returning(move |name| {
+            Ok(RegistryUsers {
+                owner: if name == "other.ic" {
+                    mock_user2
+                } else {
+                    mock_user1
+                },
+                operators: HashSet::new(),
+            })
+        });
+        let records = vec![record(DnsRecordData::A("127.0.0.1".to_string()))];
+        let items = vec![
+            ("nice.ic".to_string(), records.clone()),
+            ("other.ic".to_string(), records.clone()),
+            (
+                "invalid.ic".to_string(),
+                vec![record(DnsRecordData::TXT("".to_string()))],
+            ),
+            ("hello.nice.ic".to_string(), records.clone()),
+        ];
+
+        let mut input = BatchSetDnsRecordsInput::new(items).unwrap();
+        input.registry_api = Arc::new(mock_registry_api);
+        let results = input
+            .update(&must_not_anonymous(&mock_user1).unwrap())
+            .await;
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0], ("nice.ic".to_string(), Ok(true)));
+        assert_eq!(
+            results[1],
+            ("other.ic".to_string(), Err(NamingError::PermissionDenied))
+        );
+        assert!(matches!(
+            results[2].1,
+            Err(NamingError::InvalidDnsRecord { .. })
+        ));
+        assert_eq!(results[3], ("hello.nice.ic".to_string(), Ok(true)));
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            assert_eq!(
+                store.get_resolver("nice.ic").unwrap().get_dns_records(),
+                records.as_slice()
+            );
+            assert_eq!(
+                store
+                    .get_resolver("hello.nice.ic")
+                    .unwrap()
+                    .get_dns_records(),
+                records.as_slice()
+            );
+            assert!(store.get_resolver("other.ic").is_none());
+            assert!(store.get_resolver("invalid.ic").is_none());
+        });
+    }
+
+    #[rstest]
+    fn test_batch_set_too_many_names(_init_test: ()) {
+        let items = vec![("nice.ic".to_string(), vec![]); RESOLVER_DNS_BATCH_MAX_COUNT + 1];
+
+        let result = BatchSetDnsRecordsInput::new(items);
+
+        assert!(matches!(result, Err(NamingError::TooManyNames { .. })));
+    }
+}
+
+mod rollback_records {
+    use super::*;
+    use common::permissions::must_not_anonymous;
+    use common::TimeInNs;
+
+    fn set_values(call_context: &CallContext, name: &str, values: Vec<(&str, UpdateRecordInput)>) {
+        let input = SetRecordValueInput::new(
+            name.to_string(),
+            values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+            UpdatePrimaryNameInput::DoNothing,
+        );
+        input.update_state(call_context).unwrap();
+    }
+
+    fn get_value(name: &str, key: &str) -> Option<String> {
+        STATE.with(|s| {
+            let store = s.resolver_store.borrow();
+            store
+                .get_resolver(name)
+                .unwrap()
+                .get_record_value()
+                .get(key)
+                .cloned()
+        })
+    }
+
+    fn registry_api_of_owner(
+        mut mock_registry_api: MockRegistryApi,
+        owner: Principal,
+        operator: Principal,
+    ) -> Arc<dyn IRegistryApi> {
+        mock_registry_api
+            .expect_get_users()
+            .returning(move |_name| {
+                Ok(RegistryUsers {
+                    owner,
+                    operators: HashSet::from([operator]),
+                })
+            });
+        Arc::new(mock_registry_api)
+    }
+
+    #[rstest]
+    async fn test_rollback_records(
+        _init_test: (),
+        mock_registry_api: MockRegistryApi,
+        mock_now: u64,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let name = "nice.ic";
+        add_test_resolver(name);
+        let owner_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        let operator_context = CallContext::new(mock_user2, TimeInNs(mock_now));
+        set_values(
+            &owner_context,
+            name,
+            vec![(
+                RESOLVER_KEY_URL,
+                UpdateRecordInput::Set("https://nice.ic".to_string()),
+            )],
+        );
+        // compromised operator
+        set_values(
+            &operator_context,
+            name,
+            vec![
+                (
+                    RESOLVER_KEY_URL,
+                    UpdateRecordInput::Set("https://evil.com".to_string()),
+                ),
+                (RESOLVER_KEY_GITHUB, UpdateRecordInput::Remove),
+                (
+                    RESOLVER_KEY_TWITTER,
+                    UpdateRecordInput::InsertOrIgnore("ignored".to_string()),
+                ),
+            ],
+        );
+        STATE.with(|s| {
+            let store = s.record_history_store.borrow();
+            let entries = store.get_entries(name);
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].version, 2);
+            assert_eq!(entries[0].caller, mock_user2);
+            assert_eq!(entries[0].created_at, mock_now);
+            // unchanged values are not recorded
+            assert_eq!(
+                entries[0].changes,
+                vec![
+                    RecordChange {
+                        key: RESOLVER_KEY_GITHUB.to_string(),
+                        old_value: Some("icns".to_string()),
+                        new_value: None,
+                    },
+                    RecordChange {
+                        key: RESOLVER_KEY_URL.to_string(),
+                        old_value: Some("https://nice.ic".to_string()),
+                        new_value: Some("https://evil.com".to_string()),
+                    },
+                ]
+            );
+        });
+
+        // act
+        let mut input = RollbackRecordsInput::new(name.to_string(), 1);
+        input.registry_api = registry_api_of_owner(mock_registry_api, mock_user1, mock_user2);
+        input
+            .validate_owner(&must_not_anonymous(&mock_user1).unwrap())
+            .await
+            .unwrap();
+        input.update_state(&owner_context).unwrap();
+
+        // assert
+        assert_eq!(
+            get_value(name, RESOLVER_KEY_URL),
+            Some("https://nice.ic".to_string())
+        );
+        assert_eq!(
+            get_value(name, RESOLVER_KEY_GITHUB),
+            Some("icns".to_string())
+        );
+        assert_eq!(
+            get_value(name, RESOLVER_KEY_TWITTER),
+            Some("twitter".to_string())
+        );
+        STATE.with(|s| {
+            let store = s.record_history_store.borrow();
+            assert_eq!(store.get_version(name), 3);
+            assert_eq!(store.get_entries(name)[0].changes.len(), 2);
+        });
+    }
+
+    #[rstest]
+    async fn test_rollback_records_operator_permission_denied(
+        _init_test: (),
+        mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let mut input = RollbackRecordsInput::new("nice.ic".to_string(), 0);
+        input.registry_api = registry_api_of_owner(mock_registry_api, mock_user1, mock_user2);
+
+        let result = input
+            .validate_owner(&must_not_anonymous(&mock_user2).unwrap())
+            .await;
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    async fn test_rollback_records_version_not_found(
+        _init_test: (),
+        mock_now: u64,
+        mock_user1: Principal,
+    ) {
+        let name = "nice.ic";
+        add_test_resolver(name);
+        let call_context = CallContext::new(mock_user1, TimeInNs(mock_now));
+        set_values(
+            &call_context,
+            name,
+            vec![(
+                RESOLVER_KEY_URL,
+                UpdateRecordInput::Set("https://nice.ic".to_string()),
+            )],
+        );
+
+        let result = RollbackRecordsInput::new(name.to_string(), 2).update_state(&call_context);
+
+        assert_eq!(
+            result,
+            Err(NamingError::RecordVersionNotFound { version: 2 })
+        );
+    }
+}
+
+mod context_primary_name {
+    use super::*;
+    use crate::reverse_resolver_store::get_verified_primary_name;
+    use common::permissions::must_not_anonymous;
+    use common::TimeInNs;
+
+    fn set_principal(name: &str, principal: &Principal) {
+        let call_context = CallContext::new(*principal, TimeInNs(0));
+        let input = SetRecordValueInput::new(
+            name.to_string(),
+            HashMap::from([(
+                RESOLVER_KEY_ICP_PRINCIPAL.to_string(),
+                UpdateRecordInput::Set(principal.to_text()),
+            )]),
+            UpdatePrimaryNameInput::DoNothing,
+        );
+        input.update_state(&call_context).unwrap();
+    }
+
+    fn registry_api_of_owner(
+        mut mock_registry_api: MockRegistryApi,
+        owner: Principal,
+    ) -> Arc<dyn IRegistryApi> {
+        mock_registry_api
+            .expect_get_users()
+            .returning(move |_name| {
+                Ok(RegistryUsers {
+                    owner,
+                    operators: HashSet::new(),
+                })
+            });
+        Arc::new(mock_registry_api)
+    }
+
+    #[rstest]
+    async fn test_set_context_primary_name(
+        _init_test: (),
+        mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+    ) {
+        let context = mock_canister(1);
+        set_principal("nice.ic", &mock_user1);
+        set_principal("app.nice.ic", &mock_user1);
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_primary_name(mock_user1, "nice.ic".to_string());
+        });
+        let caller = must_not_anonymous(&mock_user1).unwrap();
+
+        // act
+        let mut input = SetContextPrimaryNameInput::new("app.nice.ic".to_string(), context);
+        input.registry_api = registry_api_of_owner(mock_registry_api, mock_user1);
+        input.validate_owner(&caller).await.unwrap();
+        input.update_state(&caller).unwrap();
+
+        // assert
+        STATE.with(|s| {
+            assert_eq!(
+                get_verified_primary_name(s, &mock_user1, Some(&context)),
+                Ok("app.nice.ic".to_string())
+            );
+            assert_eq!(
+                get_verified_primary_name(s, &mock_user1, Some(&mock_canister(2))),
+                Ok("nice.ic".to_string())
+            );
+            assert_eq!(
+                get_verified_primary_name(s, &mock_user1, None),
+                Ok("nice.ic".to_string())
+            );
+        });
+    }
+
+    #[rstest]
+    async fn test_set_context_primary_name_permission_denied(
+        _init_test: (),
+        mock_registry_api: MockRegistryApi,
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let mut input = SetContextPrimaryNameInput::new("nice.ic".to_string(), mock_canister(1));
+        input.registry_api = registry_api_of_owner(mock_registry_api, mock_user1);
+
+        let result = input
+            .validate_owner(&must_not_anonymous(&mock_user2).unwrap())
+            .await;
+
+        assert_eq!(result, Err(NamingError::PermissionDenied));
+    }
+
+    #[rstest]
+    fn test_set_context_primary_name_not_verified(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        set_principal("nice.ic", &mock_user2);
+
+        let input = SetContextPrimaryNameInput::new("nice.ic".to_string(), mock_canister(1));
+        let result = input.update_state(&must_not_anonymous(&mock_user1).unwrap());
+
+        assert_eq!(
+            result,
+            Err(NamingError::PrimaryNameNotVerified {
+                name: "nice.ic".to_string()
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_principal_changed_removes_primary_names(
+        _init_test: (),
+        mock_user1: Principal,
+        mock_user2: Principal,
+    ) {
+        let context = mock_canister(1);
+        set_principal("nice.ic", &mock_user1);
+        STATE.with(|s| {
+            let mut store = s.reverse_resolver_store.borrow_mut();
+            store.set_primary_name(mock_user1, "nice.ic".to_string());
+            store.set_context_primary_name(mock_user1, context, "nice.ic".to_string());
+        });
+
+        // act
+        set_principal("nice.ic", &mock_user2);
+
+        // assert
+        STATE.with(|s| {
+            let store = s.reverse_resolver_store.borrow();
+            assert_eq!(store.get_primary_name(&mock_user1), None);
+            assert_eq!(store.get_context_primary_name(&mock_user1, &context), None);
+        });
+    }
+}
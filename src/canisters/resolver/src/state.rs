@@ -5,21 +5,40 @@ use std::collections::HashMap;
 use std::sync::Once;
 
 use candid::{candid_method, decode_args, encode_args, Principal};
-use ic_cdk::{api, storage};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::info;
 
+use common::certified_map::CertifiedMap;
 use common::ic_logger::ICLogger;
 use common::named_canister_ids::{
     ensure_current_canister_id_match, update_dev_named_canister_ids, CanisterNames,
 };
-use common::state::{decode_store, decode_store_or_default, StableState};
+use common::stable_memory::MemoryId;
+use common::state::{
+    encode_entries, restore_state, save_state, StableEntries, StableMemoryState, StableState,
+};
 
+use crate::certification::{certify_all, certify_restored};
+use crate::record_history_store::RecordHistoryStore;
 use crate::resolver_store::ResolverStore;
 use crate::reverse_resolver_store::ReverseResolverStore;
 
+#[cfg(test)]
+mod tests;
+
+/// Memory of stores kept in stable memory, `HEAP_STATE_MEMORY_ID` is taken by the rest of the state
+pub const RESOLVER_STORE_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub const CERTIFIED_MAP_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const PRIMARY_NAMES_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const PRIMARY_NAMES_REVERSE_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const CONTEXT_PRIMARY_NAMES_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub const RECORD_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(6);
+
 thread_local! {
     pub static STATE : State = State::default();
+    pub static CERTIFIED_MAP: RefCell<CertifiedMap> =
+        RefCell::new(CertifiedMap::init(CERTIFIED_MAP_MEMORY_ID));
 }
 
 #[derive(Default)]
@@ -28,35 +47,60 @@ pub struct State {
     // are being persisted in the `replace` method below.
     pub(crate) resolver_store: RefCell<ResolverStore>,
     pub reverse_resolver_store: RefCell<ReverseResolverStore>,
+    pub(crate) record_history_store: RefCell<RecordHistoryStore>,
+    // entries of stores in stable memory decoded by `decode`, they are imported by `replace`
+    resolver_entries: StableEntries<ResolverStore>,
+    reverse_resolver_entries: StableEntries<ReverseResolverStore>,
+    record_history_entries: StableEntries<RecordHistoryStore>,
 }
 
 impl State {
     pub fn replace(&self, new_state: State) {
-        self.resolver_store.replace(new_state.resolver_store.take());
-        self.reverse_resolver_store
-            .replace(new_state.reverse_resolver_store.take());
+        new_state.resolver_entries.import_into(&self.resolver_store);
+        new_state
+            .reverse_resolver_entries
+            .import_into(&self.reverse_resolver_store);
+        new_state
+            .record_history_entries
+            .import_into(&self.record_history_store);
     }
 }
 
-pub type EncodedState = (Vec<u8>, Option<Vec<u8>>);
+pub type EncodedState = (Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>);
 
 impl StableState for State {
     fn encode(&self) -> Vec<u8> {
         encode_args((
-            self.resolver_store.borrow().encode(),
-            self.reverse_resolver_store.borrow().encode(),
+            encode_entries(&self.resolver_store),
+            encode_entries(&self.reverse_resolver_store),
+            encode_entries(&self.record_history_store),
         ))
         .unwrap()
     }
 
     fn decode(bytes: Vec<u8>) -> Result<Self, String> {
-        let (resolver_store_bytes, reverse_resolver_store_bytes): EncodedState =
-            decode_args(&bytes).unwrap();
+        let (resolver_store_bytes, reverse_resolver_store_bytes, record_history_store_bytes): EncodedState =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
+
+        Ok(State {
+            resolver_entries: StableEntries::decode(resolver_store_bytes)?,
+            reverse_resolver_entries: StableEntries::decode_or_default(
+                reverse_resolver_store_bytes,
+            )?,
+            record_history_entries: StableEntries::decode_or_default(record_history_store_bytes)?,
+            ..State::default()
+        })
+    }
+}
 
-        return Ok(State {
-            resolver_store: decode_store(resolver_store_bytes)?,
-            reverse_resolver_store: decode_store_or_default(reverse_resolver_store_bytes)?,
-        });
+/// All stores are kept in stable memory, nothing is saved with the heap state
+impl StableMemoryState for State {
+    fn encode_heap(&self) -> Vec<u8> {
+        encode_args(()).unwrap()
+    }
+
+    fn decode_heap(_bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(State::default())
     }
 }
 
@@ -82,6 +126,7 @@ fn init_function(args: Option<InitArgs>) {
     if let Some(args) = args {
         update_dev_named_canister_ids(&args.dev_named_canister_ids);
     }
+    STATE.with(|s| certify_all(s));
 
     guard_func().unwrap();
 }
@@ -91,32 +136,27 @@ fn init_function(args: Option<InitArgs>) {
 #[cfg(not(feature = "dev_env"))]
 fn init_function() {
     info!("init function called");
+    STATE.with(|s| certify_all(s));
     guard_func().unwrap();
 }
 
 #[pre_upgrade(guard = "guard_func")]
 fn pre_upgrade() {
     STATE.with(|s| {
-        let bytes = s.encode();
-        match storage::stable_save((&bytes,)) {
-            Ok(_) => {
-                info!("Saved state before upgrade");
-                ()
-            }
-            Err(e) => api::trap(format!("Failed to save state before upgrade: {:?}", e).as_str()),
-        };
+        save_state(s);
+        info!("Saved state before upgrade");
     });
 }
 
 #[post_upgrade(guard = "guard_func")]
 fn post_upgrade() {
-    STATE.with(|s| match storage::stable_restore::<(Vec<u8>,)>() {
-        Ok(bytes) => {
-            let new_state = State::decode(bytes.0).expect("Decoding stable memory failed");
-
+    // the legacy state must be taken before STATE initializes the stores in stable memory
+    match restore_state::<State>() {
+        Ok(new_state) => STATE.with(|s| {
             s.replace(new_state);
+            certify_restored(s);
             info!("Loaded state after upgrade");
-        }
+        }),
         Err(e) => api::trap(format!("Failed to restored state after upgrade: {:?}", e).as_str()),
-    });
+    }
 }
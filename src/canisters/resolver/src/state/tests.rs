@@ -0,0 +1,82 @@
+use rstest::*;
+
+use test_common::user::*;
+
+use common::stable_memory::load_heap_state;
+use common::state::StableMemoryStore;
+use common::TimeInNs;
+
+use super::*;
+use crate::record_history_store::RecordChange;
+
+#[rstest]
+fn test_upgrade(mock_user1: Principal, mock_now: u64) {
+    STATE.with(|s| {
+        s.resolver_store.borrow_mut().ensure_created("nice.ic");
+        s.reverse_resolver_store
+            .borrow_mut()
+            .set_primary_name(mock_user1, "nice.ic".to_string());
+        s.record_history_store.borrow_mut().add_entry(
+            "nice.ic",
+            mock_user1,
+            TimeInNs(mock_now),
+            vec![RecordChange {
+                key: "email".to_string(),
+                old_value: None,
+                new_value: Some("a@b.c".to_string()),
+            }],
+        );
+    });
+
+    pre_upgrade();
+    post_upgrade();
+
+    // nothing is saved with the heap state
+    assert_eq!(load_heap_state(), Some(encode_args(()).unwrap()));
+    STATE.with(|s| {
+        assert_eq!(s.resolver_store.borrow().get_resolver_count(), 1);
+        assert_eq!(
+            s.reverse_resolver_store
+                .borrow()
+                .get_primary_name(&mock_user1),
+            Some("nice.ic".to_string())
+        );
+        assert_eq!(s.record_history_store.borrow().get_version("nice.ic"), 1);
+    });
+}
+
+#[rstest]
+fn test_migrate_legacy_state(mock_user1: Principal) {
+    // primary names were encoded without context primary names, and there was no record history
+    let legacy_bytes = STATE.with(|s| {
+        let mut store = s.resolver_store.borrow_mut();
+        store.ensure_created("nice.ic");
+        let reverse_resolver_bytes = encode_args((
+            HashMap::from([(mock_user1, "nice.ic".to_string())]),
+            HashMap::from([("nice.ic".to_string(), mock_user1)]),
+        ))
+        .unwrap();
+        let bytes = encode_args((
+            encode_args(store.export_entries()).unwrap(),
+            Some(reverse_resolver_bytes),
+        ))
+        .unwrap();
+        store.import_entries(Default::default());
+        bytes
+    });
+
+    STATE.with(|s| s.replace(State::decode(legacy_bytes).unwrap()));
+
+    STATE.with(|s| {
+        assert_eq!(s.resolver_store.borrow().get_resolver_count(), 1);
+        let store = s.reverse_resolver_store.borrow();
+        assert_eq!(
+            store.get_primary_name(&mock_user1),
+            Some("nice.ic".to_string())
+        );
+        assert_eq!(
+            store.get_primary_name_reverse(&"nice.ic".to_string()),
+            Some(mock_user1)
+        );
+    });
+}
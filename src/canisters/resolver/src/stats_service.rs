@@ -12,8 +12,7 @@ impl StatsService {
         stats.cycles_balance = api::canister_balance();
         STATE.with(|s| {
             let store = s.resolver_store.borrow();
-            let resolvers = store.get_resolvers();
-            stats.resolver_count = resolvers.len() as u64;
+            stats.resolver_count = store.get_resolver_count();
         });
 
         stats
@@ -14,6 +14,9 @@ use serde::{de, Deserialize, Serialize};
 use crate::cycles_minting_types::IcpXdrConversionRateCertifiedResponse;
 use crate::dto::*;
 use crate::errors::{ActorResult, ErrorInfo, NamingError};
+use crate::icrc::{
+    Icrc1TransferArg, Icrc1TransferError, Icrc2TransferFromArgs, Icrc2TransferFromError, Value,
+};
 use crate::named_canister_ids::{get_named_get_canister_id, CanisterNames};
 use sha2::{Digest, Sha224};
 
@@ -25,22 +28,43 @@ async fn call_core<T, TResult>(
     args: T,
     logging: bool,
 ) -> Result<TResult, NamingError>
+where
+    T: candid::utils::ArgumentEncoder,
+    TResult: for<'a> Deserialize<'a> + CandidType + Debug,
+{
+    let canister_id = get_named_get_canister_id(canister_name);
+    call_core_by_id(
+        canister_id,
+        format!("{:?}", canister_name).as_str(),
+        method,
+        args,
+        logging,
+    )
+    .await
+}
+
+async fn call_core_by_id<T, TResult>(
+    canister_id: Principal,
+    canister_label: &str,
+    method: &str,
+    args: T,
+    logging: bool,
+) -> Result<TResult, NamingError>
 where
     T: candid::utils::ArgumentEncoder,
     TResult: for<'a> Deserialize<'a> + CandidType + Debug,
 {
     if logging {
-        debug!("Calling {:?}::{}", canister_name, method);
+        debug!("Calling {}::{}", canister_label, method);
     }
-    let canister_id = get_named_get_canister_id(canister_name);
     let call_result: Result<(TResult,), (RejectionCode, String)> =
         call(canister_id, method, args).await;
     if call_result.is_err() {
         let (code, message) = call_result.err().unwrap();
         let code_string = format!("{:?}", code);
         error!(
-            "{:?}::{} failed with code {}: {}",
-            canister_name, method, code_string, message
+            "{}::{} failed with code {}: {}",
+            canister_label, method, code_string, message
         );
         return Err(NamingError::CanisterCallError {
             message,
@@ -50,8 +74,8 @@ where
     let result = call_result.unwrap();
     if logging {
         debug!(
-            "Call canister {:?} with method {} result: {:?}",
-            canister_name, method, result
+            "Call canister {} with method {} result: {:?}",
+            canister_label, method, result
         );
     }
     Ok(result.0)
@@ -126,6 +150,8 @@ pub trait IRegistryApi {
         resolver: Principal,
     ) -> ActorResult<bool>;
 
+    async fn remove_name(&self, name: String) -> ActorResult<bool>;
+
     async fn get_resolver(&self, label: &str) -> ActorResult<Principal>;
     async fn get_users(&self, name: &str) -> ActorResult<RegistryUsers>;
 }
@@ -134,17 +160,34 @@ pub trait IRegistryApi {
 pub trait IResolverApi {
     async fn ensure_resolver_created(&self, name: String) -> ActorResult<bool>;
     async fn remove_resolvers(&self, names: Vec<String>) -> ActorResult<bool>;
+    async fn reset_primary_names(&self, names: Vec<String>) -> ActorResult<bool>;
     async fn set_record_value(
         &self,
         name: String,
         patch_values: HashMap<String, String>,
     ) -> ActorResult<bool>;
+    async fn set_ttl(&self, name: String, ttl: u64) -> ActorResult<bool>;
 }
 
 #[async_trait]
 pub trait IRegistrarApi {
     async fn import_quota(&self, request: ImportQuotaRequest) -> ActorResult<ImportQuotaStatus>;
     async fn register_from_gateway(&self, name: String, owner: Principal) -> ActorResult<bool>;
+    async fn add_quota_with_key(
+        &self,
+        quota_owner: Principal,
+        quota_type: QuotaType,
+        diff: u32,
+        key: String,
+    ) -> ActorResult<bool>;
+    async fn transfer_from(&self, name: String, to: Option<Principal>) -> ActorResult<bool>;
+    async fn get_owner(&self, name: String) -> ActorResult<Principal>;
+    async fn is_approved_to(&self, name: String, spender: Principal) -> ActorResult<bool>;
+}
+
+#[async_trait]
+pub trait IManagementApi {
+    async fn raw_rand(&self) -> ActorResult<Vec<u8>>;
 }
 
 #[async_trait]
@@ -410,3 +453,35 @@ pub enum TransferResult {
 pub trait ILedgerApi {
     async fn transfer(&self, args: TransferArgs) -> ActorResult<TransferResult>;
 }
+
+/// Ledgers implementing ICRC-1 and ICRC-2, the ledger canister is given by caller
+/// since more than one token could be accepted.
+#[async_trait]
+pub trait IIcrcLedgerApi {
+    async fn icrc1_transfer(
+        &self,
+        ledger: Principal,
+        arg: Icrc1TransferArg,
+    ) -> ActorResult<Result<Nat, Icrc1TransferError>>;
+
+    async fn icrc2_transfer_from(
+        &self,
+        ledger: Principal,
+        args: Icrc2TransferFromArgs,
+    ) -> ActorResult<Result<Nat, Icrc2TransferFromError>>;
+
+    async fn icrc1_fee(&self, ledger: Principal) -> ActorResult<Nat>;
+}
+
+/// Archive canisters of the registrar block log, the archive canister is given by caller
+/// since blocks are spread over more than one archive.
+#[async_trait]
+pub trait IRegistrarArchiveApi {
+    /// Append blocks from index `start`, returns the index after the last archived block
+    async fn append_blocks(
+        &self,
+        archive: Principal,
+        start: u64,
+        blocks: Vec<Value>,
+    ) -> ActorResult<u64>;
+}
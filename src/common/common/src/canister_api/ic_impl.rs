@@ -18,6 +18,34 @@ impl IRegistrarApi for RegistrarApi {
         )
         .await
     }
+
+    async fn add_quota_with_key(
+        &self,
+        quota_owner: Principal,
+        quota_type: QuotaType,
+        diff: u32,
+        key: String,
+    ) -> ActorResult<bool> {
+        call_canister_as_icns_result(
+            CanisterNames::Registrar,
+            "add_quota_with_key",
+            (quota_owner, quota_type, diff, key),
+        )
+        .await
+    }
+
+    async fn transfer_from(&self, name: String, to: Option<Principal>) -> ActorResult<bool> {
+        call_canister_as_icns_result(CanisterNames::Registrar, "transfer_from", (name, to)).await
+    }
+
+    async fn get_owner(&self, name: String) -> ActorResult<Principal> {
+        call_canister_as_icns_result(CanisterNames::Registrar, "get_owner", (name,)).await
+    }
+
+    async fn is_approved_to(&self, name: String, spender: Principal) -> ActorResult<bool> {
+        call_canister_as_icns_result(CanisterNames::Registrar, "is_approved_to", (name, spender))
+            .await
+    }
 }
 
 #[derive(Default)]
@@ -69,6 +97,10 @@ impl IRegistryApi for RegistryApi {
         .await
     }
 
+    async fn remove_name(&self, name: String) -> ActorResult<bool> {
+        call_canister_as_icns_result(CanisterNames::Registry, "remove_name", (name,)).await
+    }
+
     async fn get_resolver(&self, label: &str) -> ActorResult<Principal> {
         call_canister_as_icns_result(CanisterNames::Registry, "get_resolver", (label,)).await
     }
@@ -92,6 +124,10 @@ impl IResolverApi for ResolverApi {
         call_canister_as_icns_result(CanisterNames::Resolver, "remove_resolvers", (names,)).await
     }
 
+    async fn reset_primary_names(&self, names: Vec<String>) -> ActorResult<bool> {
+        call_canister_as_icns_result(CanisterNames::Resolver, "reset_primary_names", (names,)).await
+    }
+
     async fn set_record_value(
         &self,
         name: String,
@@ -104,6 +140,32 @@ impl IResolverApi for ResolverApi {
         )
         .await
     }
+
+    async fn set_ttl(&self, name: String, ttl: u64) -> ActorResult<bool> {
+        call_canister_as_icns_result(CanisterNames::Resolver, "set_ttl", (name, ttl)).await
+    }
+}
+
+#[derive(Default)]
+pub struct ManagementApi;
+
+#[async_trait]
+impl IManagementApi for ManagementApi {
+    async fn raw_rand(&self) -> ActorResult<Vec<u8>> {
+        let call_result: Result<(Vec<u8>,), (RejectionCode, String)> =
+            call(Principal::management_canister(), "raw_rand", ()).await;
+        match call_result {
+            Ok((bytes,)) => Ok(bytes),
+            Err((code, message)) => {
+                let code_string = format!("{:?}", code);
+                error!("raw_rand failed with code {}: {}", code_string, message);
+                Err(ErrorInfo::from(NamingError::CanisterCallError {
+                    message,
+                    rejection_code: code_string,
+                }))
+            }
+        }
+    }
 }
 
 #[derive(Default)]
@@ -179,3 +241,73 @@ impl ILedgerApi for LedgerApi {
         call_canister_as_result(CanisterNames::Ledger, "transfer", (args,)).await
     }
 }
+
+#[derive(Default)]
+pub struct IcrcLedgerApi;
+
+#[async_trait]
+impl IIcrcLedgerApi for IcrcLedgerApi {
+    async fn icrc1_transfer(
+        &self,
+        ledger: Principal,
+        arg: Icrc1TransferArg,
+    ) -> ActorResult<Result<Nat, Icrc1TransferError>> {
+        call_core_by_id(
+            ledger,
+            ledger.to_text().as_str(),
+            "icrc1_transfer",
+            (arg,),
+            true,
+        )
+        .await
+        .map_err(ErrorInfo::from)
+    }
+
+    async fn icrc2_transfer_from(
+        &self,
+        ledger: Principal,
+        args: Icrc2TransferFromArgs,
+    ) -> ActorResult<Result<Nat, Icrc2TransferFromError>> {
+        call_core_by_id(
+            ledger,
+            ledger.to_text().as_str(),
+            "icrc2_transfer_from",
+            (args,),
+            true,
+        )
+        .await
+        .map_err(ErrorInfo::from)
+    }
+
+    async fn icrc1_fee(&self, ledger: Principal) -> ActorResult<Nat> {
+        call_core_by_id(ledger, ledger.to_text().as_str(), "icrc1_fee", (), true)
+            .await
+            .map_err(ErrorInfo::from)
+    }
+}
+
+#[derive(Default)]
+pub struct RegistrarArchiveApi;
+
+#[async_trait]
+impl IRegistrarArchiveApi for RegistrarArchiveApi {
+    async fn append_blocks(
+        &self,
+        archive: Principal,
+        start: u64,
+        blocks: Vec<Value>,
+    ) -> ActorResult<u64> {
+        let result = call_core_by_id::<_, ActorResult<u64>>(
+            archive,
+            archive.to_text().as_str(),
+            "append_blocks",
+            (start, blocks),
+            false,
+        )
+        .await;
+        match result {
+            Ok(result) => result,
+            Err(error) => Err(ErrorInfo::from(error)),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::borrow::Cow;
+
+use candid::{encode_one, CandidType, Deserialize};
+use ic_certified_map::{
+    fork, fork_hash, labeled, labeled_hash, leaf_hash, AsHashTree, Hash, HashTree, RbTree,
+};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use crate::http::HttpResponse;
+use crate::http_certification::{
+    encode_cbor, get_certificate_header, get_segment, HttpCertificationTree, ResponseEntry,
+    HTTP_EXPR_LABEL, WILDCARD_PATH_LABEL,
+};
+use crate::icrc3::{encode_block_index, LAST_BLOCK_HASH_LABEL, LAST_BLOCK_INDEX_LABEL};
+use crate::stable_memory::{MemoryId, StableMap};
+
+#[cfg(test)]
+mod tests;
+
+/// Label of the tree in certified data, a value is certified at path `["icnaming", key]`
+pub const CERTIFIED_MAP_LABEL: &[u8] = b"icnaming";
+
+/// Value returned by a certified query.
+///
+/// To verify it, a client should:
+/// 1. verify `certificate` with the root key of IC,
+/// 2. check that `certified_data` of the canister in `certificate` is the root hash of `witness`,
+/// 3. look up `["icnaming", key]` in `witness`, if `value` is `Some(v)`,
+///    the leaf should be sha256 of candid encoded `v`, see [`get_value_hash`].
+///
+/// If `value` is `None`, the witness proves that the key is absent.
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct CertifiedValue<T> {
+    pub value: Option<T>,
+    pub certificate: ByteBuf,
+    pub witness: ByteBuf,
+}
+
+impl<T> CertifiedValue<T> {
+    /// Attach the certificate of the data, it is only available in query calls
+    pub fn with_certificate(mut self) -> Self {
+        self.certificate = ByteBuf::from(ic_cdk::api::data_certificate().unwrap_or_default());
+        self
+    }
+}
+
+/// sha256 of candid encoded value, the leaf hash of a key in the map
+pub fn get_value_hash<T: CandidType>(value: &T) -> Hash {
+    let bytes = encode_one(value).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+const VALUE_LEAF_PREFIX: &str = "value:";
+const HTTP_LEAF_PREFIX: &str = "http:";
+
+/// Leaf of the map kept in stable memory, values and responses are kept by their hashes
+/// so that the tree is restored without reading the stores or rendering the responses again.
+#[derive(CandidType, Deserialize)]
+enum CertifiedLeaf {
+    Value {
+        hash: ByteBuf,
+    },
+    HttpResponse {
+        exact: bool,
+        expr_hash: ByteBuf,
+        response_hash: ByteBuf,
+    },
+}
+
+fn to_hash(bytes: &ByteBuf) -> Hash {
+    bytes.as_slice().try_into().unwrap()
+}
+
+/// Merkle tree of hashes of values, the root hash is set as certified data of the canister.
+/// Values are under `icnaming` and certified http responses are under `http_expr`.
+/// The last block of the ICRC-3 transaction log, if any, is under `last_block_index` and `last_block_hash`.
+#[derive(Default)]
+pub struct CertifiedMap {
+    tree: RbTree<String, Hash>,
+    http_tree: HttpCertificationTree,
+    last_block: Option<(u64, Hash)>,
+    /// Leaves kept in stable memory, the map is only kept in heap if it is None
+    leaves: Option<StableMap<String, CertifiedLeaf>>,
+}
+
+impl CertifiedMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map of which leaves are kept in the stable memory, the tree is restored from the leaves.
+    /// The last block is not kept, it should be set again from the transaction log.
+    pub fn init(memory_id: MemoryId) -> Self {
+        let leaves = StableMap::init(memory_id);
+        let mut map = Self::default();
+        for (key, leaf) in leaves.iter() {
+            map.restore_leaf(key, leaf);
+        }
+        map.leaves = Some(leaves);
+        map
+    }
+
+    fn restore_leaf(&mut self, key: String, leaf: CertifiedLeaf) {
+        match leaf {
+            CertifiedLeaf::Value { hash } => {
+                let key = key.strip_prefix(VALUE_LEAF_PREFIX).unwrap().to_string();
+                self.tree.insert(key, to_hash(&hash));
+            }
+            CertifiedLeaf::HttpResponse {
+                exact,
+                expr_hash,
+                response_hash,
+            } => {
+                let segment = key.strip_prefix(HTTP_LEAF_PREFIX).unwrap().to_string();
+                self.http_tree.insert(
+                    segment,
+                    ResponseEntry {
+                        exact,
+                        expr_hash: to_hash(&expr_hash),
+                        response_hash: to_hash(&response_hash),
+                    },
+                );
+            }
+        }
+    }
+
+    /// True if no value or response is certified, e.g. the leaves are not kept in stable memory yet
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty() && self.http_tree.is_empty()
+    }
+
+    /// Remove all values and responses, the leaves in stable memory are removed too
+    pub fn clear(&mut self) {
+        self.tree = RbTree::new();
+        self.http_tree = HttpCertificationTree::default();
+        self.last_block = None;
+        if let Some(leaves) = self.leaves.as_mut() {
+            leaves.clear();
+        }
+    }
+
+    fn put_leaf(&mut self, key: String, leaf: CertifiedLeaf) {
+        if let Some(leaves) = self.leaves.as_mut() {
+            leaves.insert(key, leaf);
+        }
+    }
+
+    fn remove_leaf(&mut self, key: &String) {
+        if let Some(leaves) = self.leaves.as_mut() {
+            leaves.remove(key);
+        }
+    }
+
+    fn put_response_entry(&mut self, segment: String, entry: ResponseEntry) {
+        self.put_leaf(
+            format!("{}{}", HTTP_LEAF_PREFIX, segment),
+            CertifiedLeaf::HttpResponse {
+                exact: entry.exact,
+                expr_hash: ByteBuf::from(entry.expr_hash.to_vec()),
+                response_hash: ByteBuf::from(entry.response_hash.to_vec()),
+            },
+        );
+        self.http_tree.insert(segment, entry);
+    }
+
+    pub fn put<T: CandidType>(&mut self, key: String, value: &T) {
+        let hash = get_value_hash(value);
+        self.put_leaf(
+            format!("{}{}", VALUE_LEAF_PREFIX, key),
+            CertifiedLeaf::Value {
+                hash: ByteBuf::from(hash.to_vec()),
+            },
+        );
+        self.tree.insert(key, hash);
+    }
+
+    pub fn delete(&mut self, key: &str) {
+        self.remove_leaf(&format!("{}{}", VALUE_LEAF_PREFIX, key));
+        self.tree.delete(key.as_bytes());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Hash> {
+        self.tree.get(key.as_bytes())
+    }
+
+    pub fn put_http_response(&mut self, path: &str, response: &HttpResponse) {
+        let entry = HttpCertificationTree::new_entry(true, response);
+        self.put_response_entry(get_segment(path), entry);
+    }
+
+    pub fn delete_http_response(&mut self, path: &str) {
+        self.remove_leaf(&format!("{}{}", HTTP_LEAF_PREFIX, get_segment(path)));
+        self.http_tree.delete(path);
+    }
+
+    pub fn contains_http_response(&self, path: &str) -> bool {
+        self.http_tree.contains(path)
+    }
+
+    /// Response of paths which are not certified, e.g. 404
+    pub fn put_http_fallback_response(&mut self, response: &HttpResponse) {
+        let entry = HttpCertificationTree::new_entry(false, response);
+        self.put_response_entry(WILDCARD_PATH_LABEL.to_string(), entry);
+    }
+
+    /// Index and hash of the last block of the transaction log, it should be set after every appended block
+    pub fn set_last_block(&mut self, index: u64, hash: Hash) {
+        self.last_block = Some((index, hash));
+    }
+
+    fn http_expr_hash(&self) -> Hash {
+        labeled_hash(HTTP_EXPR_LABEL, &self.http_tree.root_hash())
+    }
+
+    fn values_hash(&self) -> Hash {
+        labeled_hash(CERTIFIED_MAP_LABEL, &self.tree.root_hash())
+    }
+
+    fn last_block_tree(&self, index: u64, hash: &Hash) -> HashTree<'_> {
+        fork(
+            labeled(
+                LAST_BLOCK_HASH_LABEL,
+                HashTree::Leaf(Cow::Owned(hash.to_vec())),
+            ),
+            labeled(
+                LAST_BLOCK_INDEX_LABEL,
+                HashTree::Leaf(Cow::Owned(encode_block_index(index))),
+            ),
+        )
+    }
+
+    fn last_block_hash(&self, index: u64, hash: &Hash) -> Hash {
+        fork_hash(
+            &labeled_hash(LAST_BLOCK_HASH_LABEL, &leaf_hash(hash)),
+            &labeled_hash(
+                LAST_BLOCK_INDEX_LABEL,
+                &leaf_hash(&encode_block_index(index)),
+            ),
+        )
+    }
+
+    /// Fork the tree of `http_expr` and `icnaming` with the pruned last block, if any
+    fn with_last_block<'a>(&'a self, tree: HashTree<'a>) -> HashTree<'a> {
+        match &self.last_block {
+            Some((index, hash)) => fork(tree, HashTree::Pruned(self.last_block_hash(*index, hash))),
+            None => tree,
+        }
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        let hash = fork_hash(&self.http_expr_hash(), &self.values_hash());
+        match &self.last_block {
+            Some((index, last_block_hash)) => {
+                fork_hash(&hash, &self.last_block_hash(*index, last_block_hash))
+            }
+            None => hash,
+        }
+    }
+
+    fn witness_tree(&self, key: &str) -> HashTree<'_> {
+        self.with_last_block(fork(
+            HashTree::Pruned(self.http_expr_hash()),
+            labeled(CERTIFIED_MAP_LABEL, self.tree.witness(key.as_bytes())),
+        ))
+    }
+
+    fn http_witness_tree(&self, path: &str) -> HashTree<'_> {
+        self.with_last_block(fork(
+            labeled(HTTP_EXPR_LABEL, self.http_tree.witness(path)),
+            HashTree::Pruned(self.values_hash()),
+        ))
+    }
+
+    fn last_block_witness_tree(&self) -> Option<HashTree<'_>> {
+        self.last_block.as_ref().map(|(index, hash)| {
+            fork(
+                HashTree::Pruned(fork_hash(&self.http_expr_hash(), &self.values_hash())),
+                self.last_block_tree(*index, hash),
+            )
+        })
+    }
+
+    /// CBOR encoded hash tree of `last_block_index` and `last_block_hash`, None if no block is appended
+    pub fn last_block_witness(&self) -> Option<Vec<u8>> {
+        self.last_block_witness_tree()
+            .map(|tree| encode_cbor(&tree))
+    }
+
+    /// CBOR encoded hash tree which proves the presence or absence of the key
+    pub fn witness(&self, key: &str) -> Vec<u8> {
+        encode_cbor(&self.witness_tree(key))
+    }
+
+    /// Add `IC-Certificate` header to the response of the path,
+    /// the response should be the one put for the path or the fallback response.
+    pub fn certify_http_response(
+        &self,
+        path: &str,
+        mut response: HttpResponse,
+        certificate: &[u8],
+    ) -> HttpResponse {
+        let tree = encode_cbor(&self.http_witness_tree(path));
+        let expr_path = encode_cbor(&self.http_tree.get_expr_path(path));
+        response
+            .headers
+            .push(get_certificate_header(certificate, &tree, &expr_path));
+        response
+    }
+
+    /// Value with the witness of the key, the certificate is attached by the query endpoint.
+    /// `value` should be the one put for the key, or None if the key is absent.
+    pub fn get_certified_value<T>(&self, key: &str, value: Option<T>) -> CertifiedValue<T> {
+        CertifiedValue {
+            value,
+            certificate: ByteBuf::new(),
+            witness: ByteBuf::from(self.witness(key)),
+        }
+    }
+
+    /// Set root hash as certified data, it should be called after every update of the map
+    pub fn certify(&self) {
+        set_certified_data(&self.root_hash());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn set_certified_data(hash: &Hash) {
+    ic_cdk::api::set_certified_data(hash);
+}
+
+// certified data is only available in canister
+#[cfg(not(target_arch = "wasm32"))]
+fn set_certified_data(_hash: &Hash) {}
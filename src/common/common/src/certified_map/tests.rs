@@ -0,0 +1,174 @@
+use candid::Principal;
+use rstest::*;
+
+use crate::certified_map::*;
+use crate::http_certification::{get_certifiable_response, IC_CERTIFICATE_HEADER};
+use crate::stable_memory::MemoryId;
+use crate::test_common::test::init_test;
+
+#[fixture]
+pub fn setup() {
+    init_test();
+}
+
+#[rstest]
+fn test_put_and_delete(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    let empty_root_hash = map.root_hash();
+
+    map.put("owner/hello.ic".to_string(), &Principal::anonymous());
+    assert_eq!(
+        map.get("owner/hello.ic"),
+        Some(&get_value_hash(&Principal::anonymous()))
+    );
+    assert_ne!(map.root_hash(), empty_root_hash);
+
+    map.delete("owner/hello.ic");
+    assert_eq!(map.get("owner/hello.ic"), None);
+    assert_eq!(map.root_hash(), empty_root_hash);
+}
+
+#[rstest]
+fn test_root_hash_changed_by_value(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("name".to_string(), &"hello.ic".to_string());
+    let root_hash = map.root_hash();
+
+    map.put("name".to_string(), &"world.ic".to_string());
+    assert_ne!(map.root_hash(), root_hash);
+}
+
+#[rstest]
+fn test_witness_of_present_key(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("a".to_string(), &1u64);
+    map.put("b".to_string(), &2u64);
+    map.put("c".to_string(), &3u64);
+
+    assert_eq!(map.witness_tree("b").reconstruct(), map.root_hash());
+}
+
+#[rstest]
+fn test_witness_of_absent_key(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("a".to_string(), &1u64);
+    map.put("c".to_string(), &3u64);
+
+    assert_eq!(map.witness_tree("b").reconstruct(), map.root_hash());
+}
+
+#[rstest]
+fn test_get_certified_value(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("a".to_string(), &1u64);
+
+    let value = map.get_certified_value("a", Some(1u64));
+    assert_eq!(value.value, Some(1));
+    assert!(value.certificate.is_empty());
+    assert_eq!(value.witness.to_vec(), map.witness("a"));
+    // self-described CBOR tag
+    assert_eq!(&value.witness[..3], &[0xd9, 0xd9, 0xf7]);
+}
+
+#[rstest]
+fn test_http_response_changes_root_hash(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("a".to_string(), &1u64);
+    let root_hash = map.root_hash();
+    let response = get_certifiable_response(200, "text/plain", b"hello".to_vec());
+
+    map.put_http_response("/hello.ic", &response);
+    assert_ne!(map.root_hash(), root_hash);
+    assert_eq!(map.witness_tree("a").reconstruct(), map.root_hash());
+    assert_eq!(
+        map.http_witness_tree("/hello.ic").reconstruct(),
+        map.root_hash()
+    );
+
+    map.delete_http_response("/hello.ic");
+    assert_eq!(map.root_hash(), root_hash);
+}
+
+#[rstest]
+fn test_certify_http_response(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    let response = get_certifiable_response(200, "text/plain", b"hello".to_vec());
+    map.put_http_response("/hello.ic", &response);
+
+    let response = map.certify_http_response("/hello.ic", response, &[]);
+
+    let header = response.headers.last().unwrap();
+    assert_eq!(header.0, IC_CERTIFICATE_HEADER);
+    assert!(header.1.ends_with("version=2"));
+}
+
+#[rstest]
+fn test_last_block(_setup: ()) {
+    let mut map = CertifiedMap::new();
+    map.put("a".to_string(), &1u64);
+    let root_hash = map.root_hash();
+    assert!(map.last_block_witness().is_none());
+
+    map.set_last_block(0, [1; 32]);
+    assert_ne!(map.root_hash(), root_hash);
+    assert_eq!(
+        map.last_block_witness_tree().unwrap().reconstruct(),
+        map.root_hash()
+    );
+    assert_eq!(map.witness_tree("a").reconstruct(), map.root_hash());
+    assert_eq!(
+        map.http_witness_tree("/hello.ic").reconstruct(),
+        map.root_hash()
+    );
+
+    let root_hash = map.root_hash();
+    map.set_last_block(1, [1; 32]);
+    assert_ne!(map.root_hash(), root_hash);
+}
+
+#[rstest]
+fn test_restore_from_stable_memory(_setup: ()) {
+    let memory_id = MemoryId::new(1);
+    let mut map = CertifiedMap::init(memory_id);
+    assert!(map.is_empty());
+    map.put("a".to_string(), &1u64);
+    map.put("b".to_string(), &2u64);
+    map.delete("b");
+    map.put_http_response(
+        "/hello.ic",
+        &get_certifiable_response(200, "text/plain", b"hello".to_vec()),
+    );
+    map.put_http_response(
+        "/world.ic",
+        &get_certifiable_response(200, "text/plain", b"world".to_vec()),
+    );
+    map.delete_http_response("/world.ic");
+    map.put_http_fallback_response(&get_certifiable_response(
+        404,
+        "text/plain",
+        b"Not found".to_vec(),
+    ));
+
+    let restored = CertifiedMap::init(memory_id);
+
+    assert!(!restored.is_empty());
+    assert_eq!(restored.root_hash(), map.root_hash());
+    assert_eq!(restored.get("a"), map.get("a"));
+    assert_eq!(restored.get("b"), None);
+    assert!(restored.contains_http_response("/hello.ic"));
+    assert!(!restored.contains_http_response("/world.ic"));
+}
+
+#[rstest]
+fn test_clear_stable_memory(_setup: ()) {
+    let memory_id = MemoryId::new(1);
+    let mut map = CertifiedMap::init(memory_id);
+    let empty_root_hash = map.root_hash();
+    map.put("a".to_string(), &1u64);
+
+    map.clear();
+
+    assert!(map.is_empty());
+    assert_eq!(map.root_hash(), empty_root_hash);
+    assert!(CertifiedMap::init(memory_id).is_empty());
+}
@@ -20,9 +20,23 @@ pub const RESOLVER_KEY_BTC: &str = "token.btc";
 // obsolete: split into two keys RESOLVER_KEY_ICP_PRINCIPAL and RESOLVER_KEY_ICP_ACCOUNT_ID
 pub const RESOLVER_KEY_ICP: &str = "token.icp";
 pub const RESOLVER_KEY_LTC: &str = "token.ltc";
+pub const RESOLVER_KEY_DOGE: &str = "token.doge";
+pub const RESOLVER_KEY_SOL: &str = "token.sol";
+pub const RESOLVER_KEY_ATOM: &str = "token.atom";
+pub const RESOLVER_KEY_OSMO: &str = "token.osmo";
+pub const RESOLVER_KEY_JUNO: &str = "token.juno";
+pub const RESOLVER_KEY_SCRT: &str = "token.scrt";
+pub const RESOLVER_KEY_LUNA: &str = "token.luna";
+pub const RESOLVER_KEY_KAVA: &str = "token.kava";
 pub const RESOLVER_KEY_ICP_CANISTER: &str = "canister.icp";
 pub const RESOLVER_KEY_ICP_PRINCIPAL: &str = "principal.icp";
 pub const RESOLVER_KEY_ICP_ACCOUNT_ID: &str = "account_id.icp";
+// textual encoding of ICRC-1 account, e.g. `<principal>-<checksum>.<subaccount>`
+pub const RESOLVER_KEY_ICRC1_ACCOUNT: &str = "icrc1_account.icp";
+// ICRC-1 account to receive the token of a ledger, e.g. `icrc1.<ledger canister id>`
+pub const RESOLVER_KEY_ICRC1_LEDGER_PREFIX: &str = "icrc1.";
+// website of the name encoded as ENSIP-7, e.g. `0xe301...` for IPFS
+pub const RESOLVER_KEY_CONTENTHASH: &str = "contenthash";
 pub const RESOLVER_KEY_EMAIL: &str = "email";
 pub const RESOLVER_KEY_URL: &str = "url";
 pub const RESOLVER_KEY_AVATAR: &str = "avatar";
@@ -46,10 +60,19 @@ pub const RESOLVER_KEY_DSCVR: &str = "com.dscvr";
 pub const RESOLVER_KEY_DISTRIKT: &str = "com.distrikt";
 pub const RESOLVER_KEY_RELATION: &str = "com.relation";
 pub const RESOLVER_KEY_OPENCHAT: &str = "com.openchat";
+// url template of the gateway serving records not stored on chain, e.g. `https://example.com/{name}/{key}`
+pub const RESOLVER_KEY_OFFCHAIN_GATEWAY: &str = "offchain.gateway";
+// hex encoded ed25519 public key signing responses of the gateway
+pub const RESOLVER_KEY_OFFCHAIN_SIGNER: &str = "offchain.signer";
 
 pub const RESOLVER_VALUE_MAX_LENGTH: usize = 512;
 pub const RESOLVER_KEY_MAX_LENGTH: usize = 64;
 pub const RESOLVER_ITEM_MAX_COUNT: usize = 30;
+pub const RESOLVER_DNS_RECORD_MAX_COUNT: usize = 30;
+// max count of names in a batch of dns records update
+pub const RESOLVER_DNS_BATCH_MAX_COUNT: usize = 20;
+// max count of record history entries kept per name
+pub const RESOLVER_HISTORY_MAX_COUNT: usize = 50;
 
 #[derive(Eq, PartialEq, Hash, Debug)]
 pub enum WellKnownResolverKey {
@@ -57,9 +80,16 @@ pub enum WellKnownResolverKey {
     Btc,
     Icp,
     Ltc,
+    Doge,
+    Sol,
+    // bech32 address of a chain in Cosmos ecosystem, see COSMOS_ADDRESS_HRPS
+    Cosmos { hrp: &'static str },
     IcpCanister,
     IcpPrincipal,
     IcpAccountId,
+    Icrc1Account,
+    Icrc1LedgerAccount,
+    ContentHash,
     Email,
     Url,
     Avatar,
@@ -81,6 +111,8 @@ pub enum WellKnownResolverKey {
     SettingReverseResolutionPrincipal,
     Location,
     DisplayName,
+    OffchainGateway,
+    OffchainSigner,
 }
 
 impl WellKnownResolverKey {
@@ -90,9 +122,13 @@ impl WellKnownResolverKey {
             RESOLVER_KEY_BTC => Some(WellKnownResolverKey::Btc),
             RESOLVER_KEY_ICP => Some(WellKnownResolverKey::Icp),
             RESOLVER_KEY_LTC => Some(WellKnownResolverKey::Ltc),
+            RESOLVER_KEY_DOGE => Some(WellKnownResolverKey::Doge),
+            RESOLVER_KEY_SOL => Some(WellKnownResolverKey::Sol),
             RESOLVER_KEY_ICP_CANISTER => Some(WellKnownResolverKey::IcpCanister),
             RESOLVER_KEY_ICP_PRINCIPAL => Some(WellKnownResolverKey::IcpPrincipal),
             RESOLVER_KEY_ICP_ACCOUNT_ID => Some(WellKnownResolverKey::IcpAccountId),
+            RESOLVER_KEY_ICRC1_ACCOUNT => Some(WellKnownResolverKey::Icrc1Account),
+            RESOLVER_KEY_CONTENTHASH => Some(WellKnownResolverKey::ContentHash),
             RESOLVER_KEY_EMAIL => Some(WellKnownResolverKey::Email),
             RESOLVER_KEY_URL => Some(WellKnownResolverKey::Url),
             RESOLVER_KEY_AVATAR => Some(WellKnownResolverKey::Avatar),
@@ -116,9 +152,73 @@ impl WellKnownResolverKey {
             RESOLVER_KEY_DISTRIKT => Some(WellKnownResolverKey::Distrikt),
             RESOLVER_KEY_RELATION => Some(WellKnownResolverKey::Relation),
             RESOLVER_KEY_OPENCHAT => Some(WellKnownResolverKey::OpenChat),
-            _ => None,
+            RESOLVER_KEY_OFFCHAIN_GATEWAY => Some(WellKnownResolverKey::OffchainGateway),
+            RESOLVER_KEY_OFFCHAIN_SIGNER => Some(WellKnownResolverKey::OffchainSigner),
+            _ => get_cosmos_hrp_of_key(s)
+                .map(|hrp| WellKnownResolverKey::Cosmos { hrp })
+                .or_else(|| {
+                    get_icrc1_ledger_of_key(s).map(|_| WellKnownResolverKey::Icrc1LedgerAccount)
+                }),
         }
     }
+
+    /// SLIP-44 coin type of the address, see ENSIP-9
+    pub fn get_coin_type(&self) -> Option<u32> {
+        ADDRESS_COIN_TYPES
+            .iter()
+            .find(|(_, key)| WellKnownResolverKey::parse(key).as_ref() == Some(self))
+            .map(|(coin_type, _)| *coin_type)
+    }
+}
+
+pub fn get_icrc1_ledger_key(ledger: &Principal) -> String {
+    format!("{}{}", RESOLVER_KEY_ICRC1_LEDGER_PREFIX, ledger)
+}
+
+/// Ledger of `icrc1.<ledger canister id>` key
+pub fn get_icrc1_ledger_of_key(key: &str) -> Option<Principal> {
+    key.strip_prefix(RESOLVER_KEY_ICRC1_LEDGER_PREFIX)
+        .and_then(|ledger| Principal::from_text(ledger).ok())
+}
+
+/// Resolver keys of chains in Cosmos ecosystem and the bech32 human-readable part of their addresses
+pub const COSMOS_ADDRESS_HRPS: [(&str, &str); 6] = [
+    (RESOLVER_KEY_ATOM, "cosmos"),
+    (RESOLVER_KEY_OSMO, "osmo"),
+    (RESOLVER_KEY_JUNO, "juno"),
+    (RESOLVER_KEY_SCRT, "secret"),
+    (RESOLVER_KEY_LUNA, "terra"),
+    (RESOLVER_KEY_KAVA, "kava"),
+];
+
+/// Bech32 human-readable part of the addresses of a Cosmos chain key, e.g. `cosmos` for `token.atom`
+pub fn get_cosmos_hrp_of_key(key: &str) -> Option<&'static str> {
+    COSMOS_ADDRESS_HRPS
+        .iter()
+        .find(|(value, _)| *value == key)
+        .map(|(_, hrp)| *hrp)
+}
+
+/// SLIP-44 coin types and resolver keys of addresses, see ENSIP-9
+pub const ADDRESS_COIN_TYPES: [(u32, &str); 10] = [
+    (0, RESOLVER_KEY_BTC),
+    (2, RESOLVER_KEY_LTC),
+    (3, RESOLVER_KEY_DOGE),
+    (60, RESOLVER_KEY_ETH),
+    (118, RESOLVER_KEY_ATOM),
+    (223, RESOLVER_KEY_ICP_ACCOUNT_ID),
+    (330, RESOLVER_KEY_LUNA),
+    (459, RESOLVER_KEY_KAVA),
+    (501, RESOLVER_KEY_SOL),
+    (529, RESOLVER_KEY_SCRT),
+];
+
+/// Resolver key of the address of the SLIP-44 coin type
+pub fn get_address_key(coin_type: u32) -> Option<&'static str> {
+    ADDRESS_COIN_TYPES
+        .iter()
+        .find(|(value, _)| *value == coin_type)
+        .map(|(_, key)| *key)
 }
 
 pub const MAX_REGISTRY_OPERATOR_COUNT: usize = 10;
@@ -181,12 +281,53 @@ pub fn get_log_level_env() -> LevelFilter {
 #[from_env]
 pub const NAMING_TOP_LABEL: &str = "";
 
+// max length of each label of a name, e.g. `hello` in `hello.ic`
+pub const NAMING_MAX_LABEL_LENGTH: usize = 63;
+// max count of levels of a name including top level, e.g. `a.b.c.hello.ic`
+pub const NAMING_MAX_LEVEL_COUNT: usize = 5;
+
 #[from_env]
 pub const NAMING_MIN_REGISTRATION_YEAR: u32 = 1;
 
 #[from_env]
 pub const NAMING_MAX_REGISTRATION_YEAR: u32 = 10;
 
+// 90 days after expired, only the owner of the name can renew it
+pub const NAMING_GRACE_PERIOD_NS: u64 = 90 * 24 * 60 * 60 * 1_000_000_000;
+// 30 days after grace period, the owner of the name can renew it with redemption fee
+pub const NAMING_REDEMPTION_PERIOD_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+// 5 XDR
+pub const NAMING_REDEMPTION_FEE_IN_XDR_PERMYRIAD: u64 = 50_000;
+// max count of released names to be cleaned up in one run of periodic tasks
+pub const NAMING_CLEAN_EXPIRED_BATCH_SIZE: usize = 20;
+// max count of release premiums in the price table, the rest are paged by `get_release_premiums`
+pub const NAMING_PRICE_TABLE_RELEASE_PREMIUM_LIMIT: usize = 50;
+
+// premium of re-released names starts from 10,000 XDR and halves every day
+pub const NAMING_RELEASE_PREMIUM_START_IN_XDR_PERMYRIAD: u64 = 100_000_000;
+pub const NAMING_RELEASE_PREMIUM_HALF_LIFE_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+// premium drops to zero 21 days after the name is released
+pub const NAMING_RELEASE_PREMIUM_PERIOD_NS: u64 = 21 * NAMING_RELEASE_PREMIUM_HALF_LIFE_NS;
+
+// commitment of a name must wait at least 1 minute before it can be revealed
+pub const NAMING_COMMITMENT_MIN_AGE_NS: u64 = 60 * 1_000_000_000;
+// commitment of a name expires 24 hours after it is committed
+pub const NAMING_COMMITMENT_MAX_AGE_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+// unexpired commitments kept by a user at the same time
+pub const NAMING_MAX_COMMITMENTS_PER_USER: usize = 10;
+
+// pending reward of mystery box is retried 10 minutes after the box is opened
+pub const NAMING_MYSTERY_BOX_RETRY_DELAY_NS: u64 = 10 * 60 * 1_000_000_000;
+
+// unsettled escrow of marketplace is retried 10 minutes after it is created
+pub const NAMING_MARKETPLACE_RETRY_DELAY_NS: u64 = 10 * 60 * 1_000_000_000;
+
+// offer of marketplace is valid for 30 days at most
+pub const NAMING_MARKETPLACE_OFFER_MAX_DURATION_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+// a year of subdomain lease, leap days are not counted
+pub const NAMING_SUBDOMAIN_LEASE_YEAR_NS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+
 fn load_dev_or_env(name: CanisterNames, env_value: &str) -> Principal {
     if is_dev_env() {
         DEV_NAMED_CANISTER_IDS.with(|ids| {
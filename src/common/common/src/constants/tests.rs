@@ -22,3 +22,52 @@ fn test_accept_multiline_env() {
             .unwrap()
     ));
 }
+
+#[rstest]
+#[case(0, Some(RESOLVER_KEY_BTC))]
+#[case(60, Some(RESOLVER_KEY_ETH))]
+#[case(118, Some(RESOLVER_KEY_ATOM))]
+#[case(223, Some(RESOLVER_KEY_ICP_ACCOUNT_ID))]
+#[case(501, Some(RESOLVER_KEY_SOL))]
+#[case(529, Some(RESOLVER_KEY_SCRT))]
+#[case(9999, None)]
+fn test_get_address_key(#[case] coin_type: u32, #[case] expected: Option<&str>) {
+    let key = get_address_key(coin_type);
+    assert_eq!(key, expected);
+    if let Some(key) = key {
+        let well_known_key = WellKnownResolverKey::parse(key).unwrap();
+        assert_eq!(well_known_key.get_coin_type(), Some(coin_type));
+    }
+}
+
+#[rstest]
+fn test_get_coin_type_of_non_address_key() {
+    assert_eq!(WellKnownResolverKey::Email.get_coin_type(), None);
+    assert_eq!(WellKnownResolverKey::Icp.get_coin_type(), None);
+}
+
+#[rstest]
+#[case(RESOLVER_KEY_ATOM, "cosmos")]
+#[case(RESOLVER_KEY_OSMO, "osmo")]
+#[case(RESOLVER_KEY_SCRT, "secret")]
+fn test_parse_cosmos_key(#[case] key: &str, #[case] hrp: &'static str) {
+    assert_eq!(
+        WellKnownResolverKey::parse(key),
+        Some(WellKnownResolverKey::Cosmos { hrp })
+    );
+}
+
+#[rstest]
+fn test_icrc1_ledger_key() {
+    let ledger = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+    let key = get_icrc1_ledger_key(&ledger);
+
+    assert_eq!(key, "icrc1.ryjl3-tyaaa-aaaaa-aaaba-cai");
+    assert_eq!(get_icrc1_ledger_of_key(&key), Some(ledger));
+    assert_eq!(
+        WellKnownResolverKey::parse(&key),
+        Some(WellKnownResolverKey::Icrc1LedgerAccount)
+    );
+    assert_eq!(get_icrc1_ledger_of_key("icrc1.hello"), None);
+    assert_eq!(WellKnownResolverKey::parse("icrc1.hello"), None);
+}
@@ -2,6 +2,7 @@ use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 
 use std::io::{Read, Write};
+use std::str::FromStr;
 
 use candid::{CandidType, Deserialize, Principal};
 use flate2::read::ZlibDecoder;
@@ -33,19 +34,23 @@ impl GetPageInput {
                 max: max_offset,
             });
         }
-        let max_limit = PAGE_INPUT_MAX_LIMIT;
-        let min_limit = PAGE_INPUT_MIN_LIMIT;
-        if self.limit > max_limit || self.limit < min_limit {
-            return Err(NamingError::ValueShouldBeInRangeError {
-                field: "limit".to_string(),
-                min: min_limit,
-                max: max_limit,
-            });
-        }
-        Ok(())
+        validate_page_limit(self.limit)
     }
 }
 
+fn validate_page_limit(limit: usize) -> ServiceResult<()> {
+    let max_limit = PAGE_INPUT_MAX_LIMIT;
+    let min_limit = PAGE_INPUT_MIN_LIMIT;
+    if limit > max_limit || limit < min_limit {
+        return Err(NamingError::ValueShouldBeInRangeError {
+            field: "limit".to_string(),
+            min: min_limit,
+            max: max_limit,
+        });
+    }
+    Ok(())
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct GetPageOutput<T> {
     pub items: Vec<T>,
@@ -57,6 +62,47 @@ impl<T> GetPageOutput<T> {
     }
 }
 
+/// Page of items after the cursor, the cursor is the `next_cursor` of the previous page,
+/// or None for the first page.
+#[derive(CandidType, Deserialize, Debug)]
+pub struct GetCursorPageInput {
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl GetCursorPageInput {
+    pub fn validate(&self) -> ServiceResult<()> {
+        validate_page_limit(self.limit)
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetCursorPageOutput<T> {
+    pub items: Vec<T>,
+    /// None if there is no more item
+    pub next_cursor: Option<String>,
+}
+
+impl<T> GetCursorPageOutput<T> {
+    /// Page of items fetched with one more than the limit, the extra item tells that there is a next page.
+    pub fn new(mut items: Vec<T>, limit: usize, get_cursor: impl Fn(&T) -> String) -> Self {
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(get_cursor)
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> GetCursorPageOutput<U> {
+        GetCursorPageOutput {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
 pub trait IRegistryUsers {
     fn get_operators(&self) -> Option<&HashSet<Principal>>;
     fn get_owner(&self) -> &Principal;
@@ -97,6 +143,43 @@ pub struct RegistryDto {
     pub resolver: Principal,
 }
 
+/// Quota type to be used for registration
+#[derive(Deserialize, Copy, CandidType, Clone, Hash, Eq, PartialEq, Debug)]
+pub enum QuotaType {
+    /// The length of name's the first part in chars must be equal to the value.
+    /// e.g. LenEq(3) means that the first part of the name must be 3 chars long.
+    LenEq(u8),
+    /// The length of name's the first part in chars must be more than or equal to the value.
+    /// e.g. LenGt(3) means that the first part of the name must be at least 3 chars long.
+    LenGte(u8),
+}
+
+impl FromStr for QuotaType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // find () in s
+        let mut iter = s.splitn(2, '(');
+        let name = iter.next().unwrap();
+        let args = iter.next().unwrap();
+        let args = args.trim_end_matches(')');
+        match name {
+            "LenEq" => Ok(QuotaType::LenEq(u8::from_str(args).unwrap())),
+            "LenGte" => Ok(QuotaType::LenGte(u8::from_str(args).unwrap())),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for QuotaType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaType::LenEq(len) => write!(f, "len_eq({})", len),
+            QuotaType::LenGte(len) => write!(f, "len_gte({})", len),
+        }
+    }
+}
+
 #[derive(Debug, Clone, CandidType, Deserialize)]
 pub struct ImportQuotaItem {
     pub owner: Principal,
@@ -55,6 +55,37 @@ mod get_page_input {
     }
 }
 
+mod get_cursor_page {
+    use super::*;
+
+    #[rstest]
+    fn test_get_cursor_page_input_limit_overflow(_setup: ()) {
+        let input = GetCursorPageInput {
+            cursor: None,
+            limit: PAGE_INPUT_MAX_LIMIT + 1,
+        };
+        assert_eq!(
+            input.validate(),
+            Err(NamingError::ValueShouldBeInRangeError {
+                field: "limit".to_string(),
+                min: 1,
+                max: PAGE_INPUT_MAX_LIMIT,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_get_cursor_page_output(_setup: ()) {
+        let page = GetCursorPageOutput::new(vec![1, 2, 3], 2, |item| item.to_string());
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, Some("2".to_string()));
+
+        let page = GetCursorPageOutput::new(vec![1, 2], 2, |item| item.to_string());
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, None);
+    }
+}
+
 mod owner_can_operate {
     use super::*;
 
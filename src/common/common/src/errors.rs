@@ -84,6 +84,82 @@ pub enum NamingError {
     AccountIdentifierNotSupported,
     #[error("registration name is already indexed")]
     RegistrationNameIsAlreadyIndexed { name: String },
+    #[error("registration of {name:?} has been expired and released")]
+    RegistrationReleased { name: String },
+    #[error("commitment is not found, please commit first")]
+    CommitmentNotFound,
+    #[error("commitment is too new, please reveal it after {seconds:?} seconds")]
+    CommitmentTooNew { seconds: u64 },
+    #[error("commitment is expired, please commit again")]
+    CommitmentExpired,
+    #[error("commitment already exists")]
+    CommitmentAlreadyExists,
+    #[error("invalid mystery box config, reason: {reason:?}")]
+    InvalidMysteryBoxConfig { reason: String },
+    #[error("mystery box is not on sale")]
+    MysteryBoxNotOnSale,
+    #[error("listing of {name:?} is not found")]
+    ListingNotFound { name: String },
+    #[error("price of listing must be greater than 0")]
+    InvalidListingPrice,
+    #[error("offer {id} is not found")]
+    OfferNotFound { id: u64 },
+    #[error("offer is expired")]
+    OfferExpired,
+    #[error("amount of offer must be greater than 0")]
+    InvalidOfferAmount,
+    #[error("registry for {name:?} already exists")]
+    RegistryAlreadyExists { name: String },
+    #[error("parent has given up the control of the subdomain")]
+    FuseBurned,
+    #[error("fuses are invalid, reason: {reason:?}")]
+    InvalidFuses { reason: String },
+    #[error("subdomains of {name:?} are not for sale")]
+    SubdomainNotForSale { name: String },
+    #[error("lease of {name:?} is not found")]
+    LeaseNotFound { name: String },
+    #[error("subdomain sale is invalid, reason: {reason:?}")]
+    InvalidSubdomainSale { reason: String },
+    #[error("payment token {ledger:?} is not supported")]
+    PaymentTokenNotSupported { ledger: String },
+    #[error("payment token is invalid, reason: {reason:?}")]
+    InvalidPaymentToken { reason: String },
+    #[error("ledger transfer failed, reason: {reason:?}")]
+    LedgerTransferFailed { reason: String },
+    #[error("dns record is invalid, reason: {reason:?}")]
+    InvalidDnsRecord { reason: String },
+    #[error("Too many dns records, max is {max:?}")]
+    TooManyDnsRecords { max: u32 },
+    #[error("resolver key {key:?} is invalid")]
+    InvalidResolverKey { key: String },
+    #[error("offchain gateway of {name:?} is not found")]
+    OffchainGatewayNotFound { name: String },
+    #[error("offchain response is invalid, reason: {reason:?}")]
+    InvalidOffchainResponse { reason: String },
+    #[error("offchain response is expired")]
+    OffchainResponseExpired,
+    #[error("record version {version:?} is not found")]
+    RecordVersionNotFound { version: u64 },
+    #[error(
+        "primary name {name:?} is not verified, principal.icp of the name should be the caller"
+    )]
+    PrimaryNameNotVerified { name: String },
+    #[error("archive is full, at most {max:?} blocks can be archived")]
+    ArchiveFull { max: u64 },
+    #[error("invalid block index, expected {expected:?}")]
+    InvalidBlockIndex { expected: u64 },
+    #[error("archive {canister_id:?} already exists")]
+    ArchiveAlreadyExists { canister_id: String },
+    #[error("commitment is invalid, reason: {reason:?}")]
+    InvalidCommitment { reason: String },
+    #[error("price of listing {name:?} has been changed to {price}")]
+    ListingPriceChanged { name: String, price: u64 },
+    #[error("{name:?} should be approved to marketplace before listing")]
+    ListingNotApproved { name: String },
+    #[error("Too many names in a batch, max is {max:?}")]
+    TooManyNames { max: u32 },
+    #[error("too many commitments, at most {max:?} commitments can be kept by a user")]
+    TooManyCommitments { max: usize },
 }
 
 impl NamingError {
@@ -125,6 +201,43 @@ impl NamingError {
             NamingError::InvalidCanisterId => 34,
             NamingError::AccountIdentifierNotSupported => 35,
             NamingError::RegistrationNameIsAlreadyIndexed { .. } => 36,
+            NamingError::RegistrationReleased { .. } => 37,
+            NamingError::CommitmentNotFound => 38,
+            NamingError::CommitmentTooNew { .. } => 39,
+            NamingError::CommitmentExpired => 40,
+            NamingError::CommitmentAlreadyExists => 41,
+            NamingError::InvalidMysteryBoxConfig { .. } => 42,
+            NamingError::MysteryBoxNotOnSale => 43,
+            NamingError::ListingNotFound { .. } => 44,
+            NamingError::InvalidListingPrice => 45,
+            NamingError::OfferNotFound { .. } => 46,
+            NamingError::OfferExpired => 47,
+            NamingError::InvalidOfferAmount => 48,
+            NamingError::RegistryAlreadyExists { .. } => 49,
+            NamingError::FuseBurned => 50,
+            NamingError::InvalidFuses { .. } => 51,
+            NamingError::SubdomainNotForSale { .. } => 52,
+            NamingError::LeaseNotFound { .. } => 53,
+            NamingError::InvalidSubdomainSale { .. } => 54,
+            NamingError::PaymentTokenNotSupported { .. } => 55,
+            NamingError::InvalidPaymentToken { .. } => 56,
+            NamingError::LedgerTransferFailed { .. } => 57,
+            NamingError::InvalidDnsRecord { .. } => 58,
+            NamingError::TooManyDnsRecords { .. } => 59,
+            NamingError::InvalidResolverKey { .. } => 60,
+            NamingError::OffchainGatewayNotFound { .. } => 61,
+            NamingError::InvalidOffchainResponse { .. } => 62,
+            NamingError::OffchainResponseExpired => 63,
+            NamingError::RecordVersionNotFound { .. } => 64,
+            NamingError::PrimaryNameNotVerified { .. } => 65,
+            NamingError::ArchiveFull { .. } => 66,
+            NamingError::InvalidBlockIndex { .. } => 67,
+            NamingError::ArchiveAlreadyExists { .. } => 68,
+            NamingError::InvalidCommitment { .. } => 69,
+            NamingError::ListingPriceChanged { .. } => 70,
+            NamingError::ListingNotApproved { .. } => 71,
+            NamingError::TooManyNames { .. } => 72,
+            NamingError::TooManyCommitments { .. } => 73,
         }
     }
 }
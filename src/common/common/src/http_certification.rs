@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+
+use ic_certified_map::{fork, labeled, AsHashTree, Hash, HashTree, RbTree};
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use crate::http::{HeaderField, HttpResponse};
+
+#[cfg(test)]
+mod tests;
+
+pub const IC_CERTIFICATE_HEADER: &str = "IC-Certificate";
+pub const IC_CERTIFICATE_EXPRESSION_HEADER: &str = "IC-CertificateExpression";
+/// Label of the tree of certified responses in certified data
+pub const HTTP_EXPR_LABEL: &[u8] = b"http_expr";
+const EXACT_PATH_LABEL: &str = "<$>";
+pub(crate) const WILDCARD_PATH_LABEL: &str = "<*>";
+
+/// Only the status code, body and `Content-Type` of responses are certified, requests are not.
+pub const CERTIFICATE_EXPRESSION: &str = "default_certification(ValidationArgs{certification:Certification{no_request_certification:Empty{},response_certification:ResponseCertification{certified_response_headers:ResponseHeaderList{headers:[\"content-type\"]}}}})";
+/// `Location` of redirect responses is certified as well, so that the target can not be replaced.
+pub const REDIRECT_CERTIFICATE_EXPRESSION: &str = "default_certification(ValidationArgs{certification:Certification{no_request_certification:Empty{},response_certification:ResponseCertification{certified_response_headers:ResponseHeaderList{headers:[\"content-type\",\"location\"]}}}})";
+/// Headers certified by any of the expressions above, a header is only present in responses of the expression certifying it
+const CERTIFIED_RESPONSE_HEADERS: [&str; 3] =
+    ["content-type", "location", "ic-certificateexpression"];
+
+fn sha256(bytes: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+fn encode_leb128(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn encode_cbor<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+    value.serialize(&mut serializer).unwrap();
+    serializer.into_inner()
+}
+
+/// Response with the headers required by `CERTIFICATE_EXPRESSION`
+pub fn get_certifiable_response(
+    status_code: u16,
+    content_type: &str,
+    body: Vec<u8>,
+) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![
+            HeaderField("Content-Type".to_string(), content_type.to_string()),
+            HeaderField(
+                IC_CERTIFICATE_EXPRESSION_HEADER.to_string(),
+                CERTIFICATE_EXPRESSION.to_string(),
+            ),
+        ],
+        body: ByteBuf::from(body),
+        streaming_strategy: None,
+    }
+}
+
+/// Temporary redirect response with the headers required by `REDIRECT_CERTIFICATE_EXPRESSION`
+pub fn get_certifiable_redirect_response(location: &str) -> HttpResponse {
+    HttpResponse {
+        status_code: 307,
+        headers: vec![
+            HeaderField("Content-Type".to_string(), "text/plain".to_string()),
+            HeaderField("Location".to_string(), location.to_string()),
+            HeaderField(
+                IC_CERTIFICATE_EXPRESSION_HEADER.to_string(),
+                REDIRECT_CERTIFICATE_EXPRESSION.to_string(),
+            ),
+        ],
+        body: ByteBuf::new(),
+        streaming_strategy: None,
+    }
+}
+
+/// Expression in the `IC-CertificateExpression` header of the response
+fn get_certificate_expression(response: &HttpResponse) -> &str {
+    response
+        .headers
+        .iter()
+        .find(|HeaderField(name, _)| name.eq_ignore_ascii_case(IC_CERTIFICATE_EXPRESSION_HEADER))
+        .map(|HeaderField(_, value)| value.as_str())
+        .unwrap_or(CERTIFICATE_EXPRESSION)
+}
+
+/// Hash of the status code, certified headers and body of the response,
+/// see response verification v2 of HTTP gateway protocol.
+pub fn get_response_hash(response: &HttpResponse) -> Hash {
+    let mut pairs: Vec<Vec<u8>> = response
+        .headers
+        .iter()
+        .filter_map(|HeaderField(name, value)| {
+            let name = name.to_lowercase();
+            if CERTIFIED_RESPONSE_HEADERS.contains(&name.as_str()) {
+                Some([sha256(name.as_bytes()), sha256(value.as_bytes())].concat())
+            } else {
+                None
+            }
+        })
+        .collect();
+    pairs.push(
+        [
+            sha256(b":ic-cert-status"),
+            sha256(&encode_leb128(response.status_code as u64)),
+        ]
+        .concat(),
+    );
+    pairs.sort();
+    let headers_hash = sha256(&pairs.concat());
+    sha256(&[headers_hash, sha256(&response.body)].concat())
+}
+
+pub fn get_certificate_header(certificate: &[u8], tree: &[u8], expr_path: &[u8]) -> HeaderField {
+    HeaderField(
+        IC_CERTIFICATE_HEADER.to_string(),
+        format!(
+            "certificate=:{}:, tree=:{}:, expr_path=:{}:, version=2",
+            base64::encode(certificate),
+            base64::encode(tree),
+            base64::encode(expr_path)
+        ),
+    )
+}
+
+/// Combine two witnesses of the same tree, pruned nodes are replaced by revealed ones
+pub fn merge_hash_trees<'a>(lhs: HashTree<'a>, rhs: HashTree<'a>) -> HashTree<'a> {
+    match (lhs, rhs) {
+        (HashTree::Pruned(_), rhs) => rhs,
+        (lhs, HashTree::Pruned(_)) => lhs,
+        (HashTree::Fork(lhs), HashTree::Fork(rhs)) => {
+            let (lhs_left, lhs_right) = *lhs;
+            let (rhs_left, rhs_right) = *rhs;
+            fork(
+                merge_hash_trees(lhs_left, rhs_left),
+                merge_hash_trees(lhs_right, rhs_right),
+            )
+        }
+        (HashTree::Labeled(label, lhs), HashTree::Labeled(_, rhs)) => {
+            labeled(label, merge_hash_trees(*lhs, *rhs))
+        }
+        (lhs, _) => lhs,
+    }
+}
+
+/// Certified response of a path, the subtree is
+/// `<$>/<expr_hash>/""/<response_hash>` for an exact path and
+/// `<expr_hash>/""/<response_hash>` under `<*>` for the fallback.
+pub(crate) struct ResponseEntry {
+    pub(crate) exact: bool,
+    pub(crate) expr_hash: Hash,
+    pub(crate) response_hash: Hash,
+}
+
+impl AsHashTree for ResponseEntry {
+    fn root_hash(&self) -> Hash {
+        self.as_hash_tree().reconstruct()
+    }
+
+    fn as_hash_tree(&self) -> HashTree<'_> {
+        let tree = labeled(
+            &self.expr_hash,
+            labeled(
+                b"",
+                labeled(&self.response_hash, HashTree::Leaf(Cow::Borrowed(&b""[..]))),
+            ),
+        );
+        if self.exact {
+            labeled(EXACT_PATH_LABEL.as_bytes(), tree)
+        } else {
+            tree
+        }
+    }
+}
+
+/// Path without the leading `/`, only paths with one segment are supported, e.g. `/hello.ic`
+pub(crate) fn get_segment(path: &str) -> String {
+    path.trim_start_matches('/').to_string()
+}
+
+/// Tree of certified responses under `http_expr`, requests are not certified.
+#[derive(Default)]
+pub struct HttpCertificationTree {
+    tree: RbTree<String, ResponseEntry>,
+}
+
+impl HttpCertificationTree {
+    pub(crate) fn new_entry(exact: bool, response: &HttpResponse) -> ResponseEntry {
+        ResponseEntry {
+            exact,
+            expr_hash: sha256(get_certificate_expression(response).as_bytes()),
+            response_hash: get_response_hash(response),
+        }
+    }
+
+    /// Insert the entry of a segment, the fallback is under `*`
+    pub(crate) fn insert(&mut self, segment: String, entry: ResponseEntry) {
+        self.tree.insert(segment, entry);
+    }
+
+    pub fn put(&mut self, path: &str, response: &HttpResponse) {
+        self.insert(get_segment(path), Self::new_entry(true, response));
+    }
+
+    pub fn delete(&mut self, path: &str) {
+        self.tree.delete(get_segment(path).as_bytes());
+    }
+
+    /// Response of all paths which are not certified, e.g. 404
+    pub fn put_fallback(&mut self, response: &HttpResponse) {
+        self.insert(
+            WILDCARD_PATH_LABEL.to_string(),
+            Self::new_entry(false, response),
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        let segment = get_segment(path);
+        segment != WILDCARD_PATH_LABEL && self.tree.get(segment.as_bytes()).is_some()
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        self.tree.root_hash()
+    }
+
+    /// Witness of the path, or the absence of the path and the fallback
+    pub fn witness(&self, path: &str) -> HashTree<'_> {
+        let segment = get_segment(path);
+        let witness = self.tree.witness(segment.as_bytes());
+        if self.contains(path) {
+            witness
+        } else {
+            merge_hash_trees(witness, self.tree.witness(WILDCARD_PATH_LABEL.as_bytes()))
+        }
+    }
+
+    pub fn get_expr_path(&self, path: &str) -> Vec<String> {
+        let label = String::from_utf8(HTTP_EXPR_LABEL.to_vec()).unwrap();
+        if self.contains(path) {
+            vec![label, get_segment(path), EXACT_PATH_LABEL.to_string()]
+        } else {
+            vec![label, WILDCARD_PATH_LABEL.to_string()]
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use rstest::*;
+
+use crate::http_certification::*;
+use crate::test_common::test::init_test;
+
+#[fixture]
+pub fn setup() {
+    init_test();
+}
+
+fn get_html_response(body: &str) -> HttpResponse {
+    get_certifiable_response(200, "text/html; charset=utf-8", body.as_bytes().to_vec())
+}
+
+mod response_hash {
+    use super::*;
+
+    #[rstest]
+    fn test_get_response_hash_by_body(_setup: ()) {
+        assert_eq!(
+            get_response_hash(&get_html_response("hello")),
+            get_response_hash(&get_html_response("hello"))
+        );
+        assert_ne!(
+            get_response_hash(&get_html_response("hello")),
+            get_response_hash(&get_html_response("world"))
+        );
+    }
+
+    #[rstest]
+    fn test_get_response_hash_by_status_code(_setup: ()) {
+        let mut response = get_html_response("hello");
+        let hash = get_response_hash(&response);
+        response.status_code = 404;
+        assert_ne!(get_response_hash(&response), hash);
+    }
+
+    #[rstest]
+    fn test_get_response_hash_ignore_uncertified_headers(_setup: ()) {
+        let mut response = get_html_response("hello");
+        let hash = get_response_hash(&response);
+        response.headers.push(HeaderField(
+            "Cache-Control".to_string(),
+            "no-cache".to_string(),
+        ));
+        assert_eq!(get_response_hash(&response), hash);
+
+        response.headers[0].1 = "application/json".to_string();
+        assert_ne!(get_response_hash(&response), hash);
+    }
+
+    #[rstest]
+    fn test_get_response_hash_of_redirect(_setup: ()) {
+        let response = get_certifiable_redirect_response("https://ipfs.io/ipfs/hello");
+        let hash = get_response_hash(&response);
+
+        assert_ne!(
+            get_response_hash(&get_certifiable_redirect_response("https://evil.com")),
+            hash
+        );
+        assert_eq!(
+            get_certificate_expression(&response),
+            REDIRECT_CERTIFICATE_EXPRESSION
+        );
+        assert_eq!(
+            get_certificate_expression(&get_html_response("hello")),
+            CERTIFICATE_EXPRESSION
+        );
+    }
+
+    #[rstest]
+    fn test_encode_leb128(_setup: ()) {
+        assert_eq!(encode_leb128(0), vec![0]);
+        assert_eq!(encode_leb128(200), vec![0xc8, 0x01]);
+        assert_eq!(encode_leb128(624485), vec![0xe5, 0x8e, 0x26]);
+    }
+}
+
+mod certification_tree {
+    use super::*;
+
+    fn get_tree() -> HttpCertificationTree {
+        let mut tree = HttpCertificationTree::default();
+        tree.put("/hello.ic", &get_html_response("hello"));
+        tree.put("/world.ic", &get_html_response("world"));
+        tree.put_fallback(&get_certifiable_response(
+            404,
+            "text/plain",
+            b"Not found".to_vec(),
+        ));
+        tree
+    }
+
+    #[rstest]
+    fn test_witness_of_exact_path(_setup: ()) {
+        let tree = get_tree();
+
+        assert!(tree.contains("/hello.ic"));
+        assert_eq!(tree.witness("/hello.ic").reconstruct(), tree.root_hash());
+        assert_eq!(
+            tree.get_expr_path("/hello.ic"),
+            vec!["http_expr", "hello.ic", "<$>"]
+        );
+    }
+
+    #[rstest]
+    fn test_witness_of_fallback(_setup: ()) {
+        let tree = get_tree();
+
+        assert!(!tree.contains("/nice.ic"));
+        assert_eq!(tree.witness("/nice.ic").reconstruct(), tree.root_hash());
+        assert_eq!(tree.get_expr_path("/nice.ic"), vec!["http_expr", "<*>"]);
+        assert_eq!(tree.get_expr_path("/<*>"), vec!["http_expr", "<*>"]);
+    }
+
+    #[rstest]
+    fn test_delete(_setup: ()) {
+        let mut tree = get_tree();
+        let root_hash = tree.root_hash();
+
+        tree.delete("/world.ic");
+
+        assert!(!tree.contains("/world.ic"));
+        assert_ne!(tree.root_hash(), root_hash);
+    }
+
+    #[rstest]
+    fn test_certificate_header(_setup: ()) {
+        let header = get_certificate_header(&[1], &[2], &[3]);
+
+        assert_eq!(header.0, IC_CERTIFICATE_HEADER);
+        assert_eq!(
+            header.1,
+            "certificate=:AQ==:, tree=:Ag==:, expr_path=:Aw==:, version=2"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use candid::{CandidType, Deserialize, Int, Nat, Principal};
+use serde_bytes::ByteBuf;
+
+#[cfg(test)]
+mod tests;
+
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Account defined by ICRC-1, names are always owned by the default subaccount of a principal
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<ByteBuf>,
+}
+
+impl Account {
+    pub fn new(owner: Principal) -> Self {
+        Self {
+            owner,
+            subaccount: None,
+        }
+    }
+
+    /// Returns true if subaccount is not set or all zeros
+    pub fn is_default_subaccount(&self) -> bool {
+        is_default_subaccount(&self.subaccount)
+    }
+
+    /// Returns the owner if the account is the default subaccount of it
+    pub fn get_default_owner(&self) -> Option<Principal> {
+        if self.is_default_subaccount() {
+            Some(self.owner)
+        } else {
+            None
+        }
+    }
+}
+
+/// Lowercase base32 of RFC 4648 without padding
+pub fn encode_base32(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+/// Checksum of the textual encoding of an account with non-default subaccount
+fn get_account_checksum(owner: &Principal, subaccount: &[u8]) -> String {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(owner.as_slice());
+    hasher.update(subaccount);
+    encode_base32(&hasher.finalize().to_be_bytes())
+}
+
+/// Textual encoding of account defined by ICRC-1,
+/// e.g. `<principal>` or `<principal>-<checksum>.<subaccount hex without leading zeros>`
+impl Display for Account {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.subaccount {
+            Some(subaccount) if !self.is_default_subaccount() => write!(
+                f,
+                "{}-{}.{}",
+                self.owner,
+                get_account_checksum(&self.owner, subaccount),
+                hex::encode(subaccount).trim_start_matches('0')
+            ),
+            _ => write!(f, "{}", self.owner),
+        }
+    }
+}
+
+impl FromStr for Account {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (owner_and_checksum, subaccount) = match s.rsplit_once('.') {
+            Some(parts) => parts,
+            None => {
+                let owner = Principal::from_text(s).map_err(|e| e.to_string())?;
+                return Ok(Account::new(owner));
+            }
+        };
+        let (owner, checksum) = owner_and_checksum
+            .rsplit_once('-')
+            .ok_or_else(|| "missing checksum".to_string())?;
+        let owner = Principal::from_text(owner).map_err(|e| e.to_string())?;
+        if subaccount.is_empty() || subaccount.starts_with('0') || subaccount.len() > 64 {
+            return Err("subaccount should be non-default hex without leading zeros".to_string());
+        }
+        let subaccount = hex::decode(format!("{:0>64}", subaccount)).map_err(|e| e.to_string())?;
+        if get_account_checksum(&owner, &subaccount) != checksum {
+            return Err("checksum mismatch".to_string());
+        }
+        Ok(Account {
+            owner,
+            subaccount: Some(ByteBuf::from(subaccount)),
+        })
+    }
+}
+
+pub fn is_default_subaccount(subaccount: &Option<ByteBuf>) -> bool {
+    match subaccount {
+        Some(subaccount) => subaccount.iter().all(|b| *b == 0),
+        None => true,
+    }
+}
+
+/// Generic value defined by ICRC-3, it is used in metadata of ICRC standards
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    Blob(ByteBuf),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+/// Standard supported by a canister, returned by `icrc10_supported_standards`
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SupportedStandard {
+    pub name: String,
+    pub url: String,
+}
+
+/// Arguments of `icrc1_transfer` defined by ICRC-1
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Icrc1TransferArg {
+    pub from_subaccount: Option<ByteBuf>,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Icrc1TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Arguments of `icrc2_transfer_from` defined by ICRC-2
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Icrc2TransferFromArgs {
+    pub spender_subaccount: Option<ByteBuf>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Icrc2TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
@@ -0,0 +1,48 @@
+use rstest::*;
+
+use super::*;
+
+const OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+fn subaccount_with_last_byte(byte: u8) -> ByteBuf {
+    let mut subaccount = vec![0u8; 32];
+    subaccount[31] = byte;
+    ByteBuf::from(subaccount)
+}
+
+#[rstest]
+#[case(None, OWNER.to_string())]
+#[case(Some(ByteBuf::from(vec![0u8; 32])), OWNER.to_string())]
+#[case(Some(subaccount_with_last_byte(1)), format!("{}-6cc627i.1", OWNER))]
+#[case(
+    Some(ByteBuf::from((1..=32).collect::<Vec<u8>>())),
+    format!("{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20", OWNER)
+)]
+fn test_account_textual_encoding(#[case] subaccount: Option<ByteBuf>, #[case] text: String) {
+    let account = Account {
+        owner: Principal::from_text(OWNER).unwrap(),
+        subaccount,
+    };
+
+    assert_eq!(account.to_string(), text);
+    let parsed = Account::from_str(&text).unwrap();
+    assert_eq!(parsed.owner, account.owner);
+    assert_eq!(
+        parsed.is_default_subaccount(),
+        account.is_default_subaccount()
+    );
+    if !account.is_default_subaccount() {
+        assert_eq!(parsed, account);
+    }
+}
+
+#[rstest]
+#[case(format!("{}-6cc627j.1", OWNER))]
+#[case(format!("{}-6cc627i.01", OWNER))]
+#[case(format!("{}-6cc627i.", OWNER))]
+#[case(format!("{}.1", OWNER))]
+#[case(format!("{}-6cc627i.1z", OWNER))]
+#[case("hello".to_string())]
+fn test_parse_invalid_account(#[case] text: String) {
+    assert!(Account::from_str(&text).is_err());
+}
@@ -0,0 +1,184 @@
+//! Transaction log defined by ICRC-3.
+//!
+//! Blocks are `Value` maps of `phash`, `btype`, `ts` and `tx`, where `phash` is the hash of the previous block,
+//! so that the whole log can be verified from the hash of the last block.
+//! The index and the hash of the last block are certified under `last_block_index` and `last_block_hash`.
+
+use candid::types::{FuncMode, Function, Serializer, Type};
+use candid::{CandidType, Deserialize, Func, Nat, Principal};
+pub use ic_certified_map::Hash;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use crate::icrc::Value;
+
+#[cfg(test)]
+mod tests;
+
+pub const LAST_BLOCK_INDEX_LABEL: &[u8] = b"last_block_index";
+pub const LAST_BLOCK_HASH_LABEL: &[u8] = b"last_block_hash";
+
+pub const BLOCK_PHASH_KEY: &str = "phash";
+pub const BLOCK_TYPE_KEY: &str = "btype";
+pub const BLOCK_TS_KEY: &str = "ts";
+pub const BLOCK_TX_KEY: &str = "tx";
+
+fn sha256(bytes: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+/// Representation-independent hash of the value defined by ICRC-3
+pub fn hash_value(value: &Value) -> Hash {
+    match value {
+        Value::Blob(bytes) => sha256(bytes),
+        Value::Text(text) => sha256(text.as_bytes()),
+        Value::Nat(nat) => {
+            let mut bytes = vec![];
+            nat.encode(&mut bytes).unwrap();
+            sha256(&bytes)
+        }
+        Value::Int(int) => {
+            let mut bytes = vec![];
+            int.encode(&mut bytes).unwrap();
+            sha256(&bytes)
+        }
+        Value::Array(values) => {
+            let mut hasher = Sha256::new();
+            for value in values {
+                hasher.update(hash_value(value));
+            }
+            hasher.finalize().into()
+        }
+        Value::Map(entries) => {
+            let mut pairs: Vec<Vec<u8>> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let mut pair = sha256(key.as_bytes()).to_vec();
+                    pair.extend_from_slice(&hash_value(value));
+                    pair
+                })
+                .collect();
+            pairs.sort();
+            let mut hasher = Sha256::new();
+            for pair in pairs {
+                hasher.update(pair);
+            }
+            hasher.finalize().into()
+        }
+    }
+}
+
+/// Account of the default subaccount of the principal, in the representation of ICRC-3
+pub fn account_value(owner: &Principal) -> Value {
+    Value::Array(vec![Value::Blob(ByteBuf::from(owner.as_slice().to_vec()))])
+}
+
+/// Block chained to the parent block by its hash, the genesis block has no parent.
+pub fn new_block(
+    parent_hash: Option<Hash>,
+    btype: &str,
+    ts: u64,
+    tx: Vec<(String, Value)>,
+) -> Value {
+    let mut entries = vec![];
+    if let Some(parent_hash) = parent_hash {
+        entries.push((
+            BLOCK_PHASH_KEY.to_string(),
+            Value::Blob(ByteBuf::from(parent_hash.to_vec())),
+        ));
+    }
+    entries.push((BLOCK_TYPE_KEY.to_string(), Value::Text(btype.to_string())));
+    entries.push((BLOCK_TS_KEY.to_string(), Value::Nat(Nat::from(ts))));
+    entries.push((BLOCK_TX_KEY.to_string(), Value::Map(tx)));
+    Value::Map(entries)
+}
+
+/// `leb128` of the index, the leaf of `last_block_index` in the certified tree
+pub fn encode_block_index(index: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    Nat::from(index).encode(&mut bytes).unwrap();
+    bytes
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Value,
+}
+
+/// Query method of an archive canister, `func (vec GetBlocksArgs) -> (GetBlocksResult) query`
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct GetBlocksCallback(pub Func);
+
+impl CandidType for GetBlocksCallback {
+    fn _ty() -> Type {
+        Type::Func(Function {
+            modes: vec![FuncMode::Query],
+            args: vec![Vec::<GetBlocksArgs>::ty()],
+            rets: vec![GetBlocksResult::ty()],
+        })
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.idl_serialize(serializer)
+    }
+}
+
+/// Blocks moved to an archive canister, they should be fetched by calling `callback` with `args`
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: GetBlocksCallback,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+/// Certificate of the canister with the hash tree of `last_block_index` and `last_block_hash`
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct DataCertificate {
+    pub certificate: ByteBuf,
+    pub hash_tree: ByteBuf,
+}
+
+impl DataCertificate {
+    /// Attach the certificate of the data, it is only available in query calls
+    pub fn with_certificate(mut self) -> Self {
+        self.certificate = ByteBuf::from(ic_cdk::api::data_certificate().unwrap_or_default());
+        self
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SupportedBlockType {
+    pub block_type: String,
+    pub url: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct GetArchivesArgs {
+    /// Archives after this one are returned, all archives if it is None
+    pub from: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
@@ -0,0 +1,88 @@
+use candid::Int;
+use rstest::*;
+
+use super::*;
+
+fn blob(bytes: &[u8]) -> Value {
+    Value::Blob(ByteBuf::from(bytes.to_vec()))
+}
+
+// test vectors of ICRC-3
+#[rstest]
+#[case(
+    Value::Nat(Nat::from(42u32)),
+    "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1"
+)]
+#[case(
+    Value::Int(Int::from(-42)),
+    "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc"
+)]
+#[case(
+    Value::Text("Hello, World!".to_string()),
+    "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+)]
+#[case(
+    blob(&[1, 2, 3, 4]),
+    "9f64a747e1b97f131fabb6b447296c9b6f0201e79fb3c5356e6c77e89b6a806a"
+)]
+#[case(
+    Value::Array(vec![
+        Value::Nat(Nat::from(3u32)),
+        Value::Text("foo".to_string()),
+        blob(&[5, 6]),
+    ]),
+    "514a04011caa503990d446b7dec5d79e19c221ae607fb08b2848c67734d468d6"
+)]
+#[case(
+    Value::Map(vec![
+        (
+            "from".to_string(),
+            blob(&hex::decode("00abcdef0012340056789a00bcdef000012345678900abcdef01").unwrap()),
+        ),
+        (
+            "to".to_string(),
+            blob(&hex::decode("00ab0def0012340056789a00bcdef000012345678900abcdef01").unwrap()),
+        ),
+        ("amount".to_string(), Value::Nat(Nat::from(42u32))),
+        ("created_at".to_string(), Value::Nat(Nat::from(1699218263u64))),
+        ("memo".to_string(), Value::Nat(Nat::from(0u32))),
+    ]),
+    "c56ece650e1de4269c5bdeff7875949e3e2033f85b2d193c2ff4f7f78bdcfc75"
+)]
+fn test_hash_value(#[case] value: Value, #[case] hash: &str) {
+    assert_eq!(hex::encode(hash_value(&value)), hash);
+}
+
+#[rstest]
+fn test_new_block() {
+    let tx = vec![("to".to_string(), account_value(&Principal::anonymous()))];
+    let genesis = new_block(None, "7mint", 1, tx.clone());
+    let block = new_block(Some(hash_value(&genesis)), "7mint", 2, tx);
+
+    match block {
+        Value::Map(entries) => {
+            assert_eq!(
+                entries[0],
+                (
+                    BLOCK_PHASH_KEY.to_string(),
+                    Value::Blob(ByteBuf::from(hash_value(&genesis).to_vec()))
+                )
+            );
+            assert_eq!(
+                entries[1],
+                (BLOCK_TYPE_KEY.to_string(), Value::Text("7mint".to_string()))
+            );
+        }
+        _ => panic!("block should be a map"),
+    }
+    match genesis {
+        Value::Map(entries) => assert!(entries.iter().all(|(key, _)| key != BLOCK_PHASH_KEY)),
+        _ => panic!("block should be a map"),
+    }
+}
+
+#[rstest]
+fn test_encode_block_index() {
+    assert_eq!(encode_block_index(0), vec![0]);
+    assert_eq!(encode_block_index(300), vec![0xac, 0x02]);
+}
@@ -8,17 +8,22 @@ use std::fmt::{Display, Formatter};
 use std::ops::{Add, Sub};
 
 pub mod canister_api;
+pub mod certified_map;
 pub mod constants;
 pub mod cycles_minting_types;
 pub mod dto;
 pub mod errors;
 pub mod http;
+pub mod http_certification;
 pub mod ic_logger;
+pub mod icrc;
+pub mod icrc3;
 pub mod metrics_encoder;
 pub mod named_canister_ids;
 pub mod named_principals;
 pub mod naming;
 pub mod permissions;
+pub mod stable_memory;
 pub mod state;
 pub mod timeout_lock;
 
@@ -1,8 +1,14 @@
-use crate::constants::MAX_LENGTH_OF_NAME_QUOTA_TYPE;
+use crate::constants::{
+    MAX_LENGTH_OF_NAME_QUOTA_TYPE, NAMING_MAX_LABEL_LENGTH, NAMING_MAX_LEVEL_COUNT,
+    NAMING_TOP_LABEL,
+};
 use candid::{CandidType, Deserialize};
 use std::cmp::min;
 use std::fmt::Display;
 
+#[cfg(test)]
+mod tests;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, CandidType, Deserialize)]
 #[serde(transparent)]
 pub struct NormalizedName(pub String);
@@ -88,6 +94,15 @@ impl NameParseResult {
         &self.name
     }
 
+    /// Returns parent name, e.g. `hello.ic` for `www.hello.ic`
+    pub fn get_parent_name(&self) -> Option<String> {
+        if self.labels.len() > 1 {
+            Some(self.labels[1..].join("."))
+        } else {
+            None
+        }
+    }
+
     pub fn get_name_len(&self) -> u8 {
         let name_length = self.labels[0].chars().count() as u8;
         name_length
@@ -105,10 +120,35 @@ pub fn parse_name(name: &str) -> Result<NameParseResult, String> {
         if label.len() == 0 {
             return Err("Empty label".to_string());
         }
+        if label.len() > NAMING_MAX_LABEL_LENGTH {
+            return Err(format!(
+                "label must be less than {} characters",
+                NAMING_MAX_LABEL_LENGTH + 1
+            ));
+        }
         if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
             return Err("name must be alphanumeric or -".to_string());
         }
     }
 
+    if result.get_level_count() > NAMING_MAX_LEVEL_COUNT {
+        return Err(format!(
+            "name must be less than {} levels",
+            NAMING_MAX_LEVEL_COUNT + 1
+        ));
+    }
+
     return Ok(result);
 }
+
+/// Parse name of subdomain, it must be third level name or deeper under top level
+pub fn parse_subdomain_name(name: &str) -> Result<NameParseResult, String> {
+    let result = parse_name(name)?;
+    if result.get_level_count() < 3 {
+        return Err("it must be third level name or deeper".to_string());
+    }
+    if result.get_top_level().unwrap() != NAMING_TOP_LABEL {
+        return Err(format!("top level of name must be {}", NAMING_TOP_LABEL));
+    }
+    Ok(result)
+}
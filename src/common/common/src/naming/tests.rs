@@ -0,0 +1,59 @@
+use rstest::*;
+
+use crate::constants::NAMING_TOP_LABEL;
+use crate::naming::*;
+
+fn name_of(prefix: &str) -> String {
+    format!("{}.{}", prefix, NAMING_TOP_LABEL)
+}
+
+mod parse_name {
+    use super::*;
+
+    #[rstest]
+    #[case("hello")]
+    #[case("www.hello")]
+    #[case("a.b.c.hello")]
+    fn test_parse_name(#[case] prefix: &str) {
+        let name = name_of(prefix);
+        let result = parse_name(&name).unwrap();
+        assert_eq!(result.get_name(), &name);
+    }
+
+    #[rstest]
+    #[case("a.b.c.d.hello")]
+    #[case("www..hello")]
+    #[case("w_w.hello")]
+    fn test_parse_name_invalid(#[case] prefix: &str) {
+        let result = parse_name(&name_of(prefix));
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    fn test_parse_name_label_too_long() {
+        assert!(parse_name(&name_of(&"a".repeat(63))).is_ok());
+        assert!(parse_name(&name_of(&"a".repeat(64))).is_err());
+    }
+}
+
+mod parse_subdomain_name {
+    use super::*;
+
+    #[rstest]
+    fn test_parse_subdomain_name() {
+        let result = parse_subdomain_name(&name_of("WWW.hello")).unwrap();
+        assert_eq!(result.get_name(), &name_of("www.hello"));
+        assert_eq!(result.get_current_level(), Some(&"www".to_string()));
+        assert_eq!(result.get_parent_name(), Some(name_of("hello")));
+    }
+
+    #[rstest]
+    fn test_parse_subdomain_name_second_level() {
+        assert!(parse_subdomain_name(&name_of("hello")).is_err());
+    }
+
+    #[rstest]
+    fn test_parse_subdomain_name_other_top_level() {
+        assert!(parse_subdomain_name(&format!("www.hello.{}x", NAMING_TOP_LABEL)).is_err());
+    }
+}
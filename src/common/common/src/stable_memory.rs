@@ -0,0 +1,287 @@
+//! Stores which grow with the number of names are kept in stable memory, so that they are not
+//! serialized on upgrade. Each of them takes a virtual memory of the memory manager by a `MemoryId`,
+//! and the rest of the state is saved as a Candid blob in the memory of `HEAP_STATE_MEMORY_ID`.
+//!
+//! Canisters upgraded from a version saving the whole state with `stable_save` have the Candid blob
+//! at the beginning of stable memory, see `take_legacy_state`.
+
+use std::borrow::Cow;
+use std::ops;
+use std::ops::RangeBounds;
+
+use candid::{decode_one, encode_one, CandidType, Principal};
+use ic_cdk::storage;
+use ic_stable_structures::memory_manager::{MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory, StableBTreeMap, StableCell};
+use serde::de::DeserializeOwned;
+
+pub use ic_stable_structures::memory_manager::MemoryId;
+pub use ic_stable_structures::storable::Bound;
+pub use ic_stable_structures::Storable;
+
+#[cfg(test)]
+mod tests;
+
+pub type StableMemory = VirtualMemory<DefaultMemoryImpl>;
+
+/// Memory of the Candid blob of stores kept in heap, it is saved in `pre_upgrade`
+pub const HEAP_STATE_MEMORY_ID: MemoryId = MemoryId::new(0);
+
+const WASM_PAGE_SIZE: u64 = 65536;
+const HEAP_STATE_LENGTH_SIZE: u64 = 8;
+// `stable_save` writes the Candid blob from the beginning of stable memory
+const LEGACY_STATE_MAGIC: &[u8; 4] = b"DIDL";
+
+thread_local! {
+    static MEMORY_MANAGER: MemoryManager<DefaultMemoryImpl> =
+        MemoryManager::init(DefaultMemoryImpl::default());
+}
+
+pub fn get_memory(id: MemoryId) -> StableMemory {
+    MEMORY_MANAGER.with(|manager| manager.get(id))
+}
+
+/// Candid encoded value of a stable structure
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Candid<T>(pub T);
+
+impl<T> Storable for Candid<T>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(encode_one(&self.0).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Candid(decode_one(&bytes).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Principal as a key of stable structures, keys of the same principal are adjacent
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PrincipalKey(pub Principal);
+
+impl Storable for PrincipalKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        PrincipalKey(Principal::from_slice(&bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 29,
+        is_fixed_size: false,
+    };
+}
+
+/// Key of an owner index, names of an owner are adjacent and ordered by name
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct OwnerNameKey {
+    pub owner: PrincipalKey,
+    pub name: String,
+}
+
+impl OwnerNameKey {
+    pub fn new(owner: Principal, name: &str) -> Self {
+        OwnerNameKey {
+            owner: PrincipalKey(owner),
+            name: name.to_string(),
+        }
+    }
+
+    /// Start of the range of names of the owner after the cursor, from the first name if there is no cursor
+    pub fn start_after(owner: Principal, cursor: Option<&str>) -> ops::Bound<Self> {
+        match cursor {
+            Some(cursor) => ops::Bound::Excluded(OwnerNameKey::new(owner, cursor)),
+            None => ops::Bound::Included(OwnerNameKey::new(owner, "")),
+        }
+    }
+}
+
+/// Names of the owner from entries of an owner index starting at `OwnerNameKey::start_after`,
+/// it stops at the first entry of another owner, so at most one entry of other owners is visited
+pub fn take_owned_names<V>(
+    entries: impl Iterator<Item = (OwnerNameKey, V)>,
+    owner: Principal,
+) -> impl Iterator<Item = String> {
+    let owner = PrincipalKey(owner);
+    entries
+        .take_while(move |(key, _)| key.owner == owner)
+        .map(|(key, _)| key.name)
+}
+
+impl Storable for OwnerNameKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let owner = self.owner.to_bytes();
+        let mut bytes = Vec::with_capacity(1 + owner.len() + self.name.len());
+        bytes.push(owner.len() as u8);
+        bytes.extend_from_slice(&owner);
+        bytes.extend_from_slice(self.name.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let owner_end = 1 + bytes[0] as usize;
+        OwnerNameKey {
+            owner: PrincipalKey::from_bytes(Cow::Borrowed(&bytes[1..owner_end])),
+            name: String::from_utf8(bytes[owner_end..].to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Map in stable memory, values are Candid encoded so that fields can be added as `Option` later.
+pub struct StableMap<K, V>
+where
+    K: Storable + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    inner: StableBTreeMap<K, Candid<V>, StableMemory>,
+}
+
+impl<K, V> StableMap<K, V>
+where
+    K: Storable + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    /// Load the map from the memory, or create an empty one if the memory is not used yet
+    pub fn init(memory_id: MemoryId) -> Self {
+        StableMap {
+            inner: StableBTreeMap::init(get_memory(memory_id)),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.inner.get(key).map(|value| value.0)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.inner.insert(key, Candid(value)).map(|value| value.0)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.inner.remove(key).map(|value| value.0)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Entries ordered by key
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.inner.iter().map(|(key, value)| (key, value.0))
+    }
+
+    /// Entries of keys in the range, ordered by key
+    pub fn range(&self, range: impl RangeBounds<K>) -> impl Iterator<Item = (K, V)> + '_ {
+        self.inner.range(range).map(|(key, value)| (key, value.0))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.inner.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = V> + '_ {
+        self.inner.iter().map(|(_, value)| value.0)
+    }
+
+    pub fn clear(&mut self) {
+        let keys: Vec<K> = self.keys().collect();
+        for key in keys.iter() {
+            self.inner.remove(key);
+        }
+    }
+}
+
+/// Single value in stable memory
+pub struct StableValue<T>
+where
+    T: CandidType + DeserializeOwned + Default,
+{
+    inner: StableCell<Candid<T>, StableMemory>,
+}
+
+impl<T> StableValue<T>
+where
+    T: CandidType + DeserializeOwned + Default,
+{
+    /// Load the value from the memory, or the default value if the memory is not used yet
+    pub fn init(memory_id: MemoryId) -> Self {
+        let inner = StableCell::init(get_memory(memory_id), Candid(T::default()))
+            .expect("Failed to init stable value");
+        StableValue { inner }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.inner.get().0
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.inner
+            .set(Candid(value))
+            .expect("Failed to set stable value");
+    }
+}
+
+/// Save the blob of stores kept in heap, it should be called in `pre_upgrade`
+pub fn save_heap_state(bytes: &[u8]) {
+    let memory = get_memory(HEAP_STATE_MEMORY_ID);
+    let length = bytes.len() as u64;
+    let required_pages = (HEAP_STATE_LENGTH_SIZE + length + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+    let current_pages = memory.size();
+    if current_pages < required_pages && memory.grow(required_pages - current_pages) < 0 {
+        panic!("Failed to grow stable memory for heap state");
+    }
+    memory.write(0, &length.to_le_bytes());
+    memory.write(HEAP_STATE_LENGTH_SIZE, bytes);
+}
+
+/// Blob saved by `save_heap_state`, None if it is never saved
+pub fn load_heap_state() -> Option<Vec<u8>> {
+    let memory = get_memory(HEAP_STATE_MEMORY_ID);
+    if memory.size() == 0 {
+        return None;
+    }
+    let mut length = [0u8; HEAP_STATE_LENGTH_SIZE as usize];
+    memory.read(0, &mut length);
+    let mut bytes = vec![0u8; u64::from_le_bytes(length) as usize];
+    memory.read(HEAP_STATE_LENGTH_SIZE, &mut bytes);
+    Some(bytes)
+}
+
+fn is_legacy_memory<M: Memory>(memory: &M) -> bool {
+    if memory.size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    memory.read(0, &mut magic);
+    &magic == LEGACY_STATE_MAGIC
+}
+
+/// Candid blob of the whole state saved by `stable_save` of the previous version, None if stable memory
+/// is already managed by the memory manager.
+///
+/// It must be called before any stable structure is initialized,
+/// since the memory manager overwrites the beginning of stable memory when it is initialized.
+pub fn take_legacy_state() -> Option<Vec<u8>> {
+    if !is_legacy_memory(&DefaultMemoryImpl::default()) {
+        return None;
+    }
+    let (bytes,): (Vec<u8>,) =
+        storage::stable_restore().expect("Failed to restore legacy state from stable memory");
+    Some(bytes)
+}
@@ -0,0 +1,159 @@
+use candid::{encode_args, Deserialize};
+use ic_stable_structures::VectorMemory;
+use rstest::*;
+
+use super::*;
+
+const TEST_MEMORY_ID: MemoryId = MemoryId::new(1);
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+struct TestValue {
+    name: String,
+    count: u64,
+}
+
+fn value(name: &str, count: u64) -> TestValue {
+    TestValue {
+        name: name.to_string(),
+        count,
+    }
+}
+
+#[rstest]
+fn test_stable_map() {
+    let mut map: StableMap<String, TestValue> = StableMap::init(TEST_MEMORY_ID);
+    assert!(map.is_empty());
+
+    assert_eq!(map.insert("b".to_string(), value("b", 1)), None);
+    assert_eq!(map.insert("a".to_string(), value("a", 1)), None);
+    assert_eq!(
+        map.insert("b".to_string(), value("b", 2)),
+        Some(value("b", 1))
+    );
+
+    assert_eq!(map.len(), 2);
+    assert!(map.contains_key(&"a".to_string()));
+    assert_eq!(map.get(&"b".to_string()), Some(value("b", 2)));
+    assert_eq!(
+        map.keys().collect::<Vec<_>>(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    assert_eq!(map.remove(&"a".to_string()), Some(value("a", 1)));
+    assert_eq!(map.get(&"a".to_string()), None);
+}
+
+#[rstest]
+fn test_stable_map_loaded_from_memory() {
+    {
+        let mut map: StableMap<u64, TestValue> = StableMap::init(TEST_MEMORY_ID);
+        map.insert(1, value("a", 1));
+        map.insert(2, value("b", 2));
+    }
+
+    let mut map: StableMap<u64, TestValue> = StableMap::init(TEST_MEMORY_ID);
+
+    assert_eq!(
+        map.iter().collect::<Vec<_>>(),
+        vec![(1, value("a", 1)), (2, value("b", 2))]
+    );
+    map.clear();
+    assert!(map.is_empty());
+}
+
+#[rstest]
+fn test_stable_map_range() {
+    let mut map: StableMap<u64, TestValue> = StableMap::init(TEST_MEMORY_ID);
+    for i in 1..=5 {
+        map.insert(i, value("a", i));
+    }
+
+    assert_eq!(
+        map.range(2..4).map(|(key, _)| key).collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+    assert_eq!(
+        map.range(4..).map(|(key, _)| key).collect::<Vec<_>>(),
+        vec![4, 5]
+    );
+}
+
+#[rstest]
+fn test_principal_key() {
+    let principal = Principal::from_text("2vxsx-fae").unwrap();
+    let key = PrincipalKey(principal);
+
+    assert_eq!(PrincipalKey::from_bytes(key.to_bytes()), key);
+
+    let mut map: StableMap<PrincipalKey, u32> = StableMap::init(TEST_MEMORY_ID);
+    map.insert(key, 1);
+    assert_eq!(map.get(&key), Some(1));
+}
+
+#[rstest]
+fn test_owner_name_key() {
+    let owner = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+    let other = Principal::anonymous();
+    let mut map: StableMap<OwnerNameKey, ()> = StableMap::init(TEST_MEMORY_ID);
+    for (owner, name) in [
+        (owner, "b.ic"),
+        (other, "a.ic"),
+        (owner, "a.ic"),
+        (owner, "c.ic"),
+    ] {
+        map.insert(OwnerNameKey::new(owner, name), ());
+    }
+
+    let get_names = |cursor: Option<&str>| -> Vec<String> {
+        let entries = map.range((
+            OwnerNameKey::start_after(owner, cursor),
+            ops::Bound::Unbounded,
+        ));
+        take_owned_names(entries, owner).collect()
+    };
+    assert_eq!(get_names(None), vec!["a.ic", "b.ic", "c.ic"]);
+    assert_eq!(get_names(Some("a.ic")), vec!["b.ic", "c.ic"]);
+    assert!(get_names(Some("c.ic")).is_empty());
+}
+
+#[rstest]
+fn test_stable_value() {
+    {
+        let mut stable_value: StableValue<TestValue> = StableValue::init(TEST_MEMORY_ID);
+        assert_eq!(stable_value.get(), &TestValue::default());
+        stable_value.set(value("a", 1));
+    }
+
+    let stable_value: StableValue<TestValue> = StableValue::init(TEST_MEMORY_ID);
+
+    assert_eq!(stable_value.get(), &value("a", 1));
+}
+
+#[rstest]
+fn test_save_load_heap_state() {
+    assert_eq!(load_heap_state(), None);
+
+    let bytes = vec![7u8; WASM_PAGE_SIZE as usize + 1];
+    save_heap_state(&bytes);
+    assert_eq!(load_heap_state(), Some(bytes));
+
+    // a smaller state saved later replaces the previous one
+    save_heap_state(&[1, 2, 3]);
+    assert_eq!(load_heap_state(), Some(vec![1, 2, 3]));
+}
+
+#[rstest]
+fn test_is_legacy_memory() {
+    let memory = VectorMemory::default();
+    assert!(!is_legacy_memory(&memory));
+
+    let bytes = encode_args((vec![1u8, 2, 3],)).unwrap();
+    memory.grow(1);
+    memory.write(0, &bytes);
+    assert!(is_legacy_memory(&memory));
+
+    // stores initialized on the memory by the memory manager
+    let memory = VectorMemory::default();
+    let manager = MemoryManager::init(memory.clone());
+    let _map: StableBTreeMap<u64, u64, _> = StableBTreeMap::init(manager.get(TEST_MEMORY_ID));
+    assert!(!is_legacy_memory(&memory));
+}
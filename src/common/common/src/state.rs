@@ -1,16 +1,168 @@
 use std::cell::RefCell;
 
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::{decode_args, decode_one, encode_args, encode_one, CandidType, Deserialize};
+use log::info;
+use serde_bytes::ByteBuf;
+
+use crate::stable_memory::{load_heap_state, save_heap_state, take_legacy_state};
+
+/// Migration of the encoded form of a store to the next version
+pub type StateMigration = fn(Vec<u8>) -> Result<Vec<u8>, String>;
+
 pub trait StableState: Sized {
+    /// Migrations of the encoded form in order, `MIGRATIONS[i]` migrates version `i` to `i + 1`,
+    /// so the current version is the number of migrations.
+    /// Stores encoded before versioning are version 0.
+    const MIGRATIONS: &'static [StateMigration] = &[];
+
     fn encode(&self) -> Vec<u8>;
     fn decode(bytes: Vec<u8>) -> Result<Self, String>;
 }
 
+// prefix of the envelope, stores encoded before versioning are Candid blobs starting with `DIDL`
+const VERSIONED_STATE_MAGIC: &[u8; 4] = b"ICNV";
+
+/// Encoded form of a store with the version of its layout, it is prefixed with `VERSIONED_STATE_MAGIC`
+#[derive(CandidType, Deserialize)]
+struct VersionedState {
+    version: u32,
+    bytes: ByteBuf,
+}
+
+pub fn get_current_version<T: StableState>() -> u32 {
+    T::MIGRATIONS.len() as u32
+}
+
+/// Encode the store with the current version, it should be used for every store of a state
+pub fn encode_store<T: StableState>(store: &RefCell<T>) -> Vec<u8> {
+    encode_versioned(&*store.borrow())
+}
+
+pub fn encode_versioned<T: StableState>(store: &T) -> Vec<u8> {
+    let envelope = encode_one(VersionedState {
+        version: get_current_version::<T>(),
+        bytes: ByteBuf::from(store.encode()),
+    })
+    .unwrap();
+    let mut bytes = Vec::with_capacity(VERSIONED_STATE_MAGIC.len() + envelope.len());
+    bytes.extend_from_slice(VERSIONED_STATE_MAGIC);
+    bytes.extend_from_slice(&envelope);
+    bytes
+}
+
+/// Decode the store encoded by `encode_versioned`, or by `StableState::encode` before versioning.
+/// The encoded form is migrated to the current version before it is decoded.
+pub fn decode_versioned<T: StableState>(bytes: Vec<u8>) -> Result<T, String> {
+    let (version, mut bytes) = match bytes.strip_prefix(VERSIONED_STATE_MAGIC) {
+        Some(envelope) => {
+            let state = decode_one::<VersionedState>(envelope)
+                .map_err(|e| format!("Failed to decode versioned state: {}", e))?;
+            (state.version, state.bytes.into_vec())
+        }
+        None => (0, bytes),
+    };
+    let current_version = get_current_version::<T>();
+    if version > current_version {
+        return Err(format!(
+            "Encoded version {} is newer than current version {}",
+            version, current_version
+        ));
+    }
+    for (version, migration) in T::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        bytes = migration(bytes)
+            .map_err(|e| format!("Failed to migrate state of version {}: {}", version, e))?;
+        info!("Migrated state from version {} to {}", version, version + 1);
+    }
+    T::decode(bytes)
+}
+
+/// State of which large stores are kept in stable memory.
+/// `encode` and `decode` still cover the whole state, they are used by state export and the migration from
+/// the legacy blob, while only the stores kept in heap are encoded on upgrade.
+/// Stores in stable memory are decoded as `StableEntries`, they are imported by `replace` of the state.
+pub trait StableMemoryState: StableState {
+    fn encode_heap(&self) -> Vec<u8>;
+    fn decode_heap(bytes: Vec<u8>) -> Result<Self, String>;
+}
+
+/// Store of which entries are kept in stable memory.
+///
+/// There is only one copy of the entries, so an encoded store is not decoded into a new store.
+/// The entries are decoded into `StableEntries` instead, and imported into the store in use
+/// when the state is replaced, so that decoding a state has no effect on the stores in use.
+pub trait StableMemoryStore: Default {
+    /// Entries in the encoded form of the store kept in heap before, so that the legacy state is decoded
+    type Entries: ArgumentEncoder + for<'a> ArgumentDecoder<'a>;
+
+    fn export_entries(&self) -> Self::Entries;
+    /// Replace all entries of the store
+    fn import_entries(&mut self, entries: Self::Entries);
+}
+
+/// Encode the entries of a store in stable memory, they are encoded without version
+/// since the layout of entries in stable memory is migrated in place.
+pub fn encode_entries<T: StableMemoryStore>(store: &RefCell<T>) -> Vec<u8> {
+    encode_args(store.borrow().export_entries()).unwrap()
+}
+
+/// Entries of a store in stable memory decoded with a state, see `StableMemoryStore`
+pub struct StableEntries<T: StableMemoryStore>(Option<T::Entries>);
+
+impl<T: StableMemoryStore> Default for StableEntries<T> {
+    fn default() -> Self {
+        StableEntries(None)
+    }
+}
+
+impl<T: StableMemoryStore> StableEntries<T> {
+    pub fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+        let entries = decode_args(&bytes)
+            .map_err(|e| format!("Failed to decode entries of stable store: {}", e))?;
+        Ok(StableEntries(Some(entries)))
+    }
+
+    pub fn decode_or_default(bytes: Option<Vec<u8>>) -> Result<Self, String> {
+        match bytes {
+            Some(bytes) => Self::decode(bytes),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Replace entries of the store with the decoded ones, the store is untouched if nothing is decoded
+    pub fn import_into(self, store: &RefCell<T>) {
+        if let Some(entries) = self.0 {
+            store.borrow_mut().import_entries(entries);
+        }
+    }
+}
+
+/// Save stores kept in heap, it should be called in `pre_upgrade`
+pub fn save_state<T: StableMemoryState>(state: &T) {
+    save_heap_state(&state.encode_heap());
+}
+
+/// Restore the state saved by `save_state`, or migrate the whole state saved by `stable_save` of the previous version.
+/// It should be called in `post_upgrade` before the state is accessed.
+pub fn restore_state<T: StableMemoryState>() -> Result<T, String> {
+    match take_legacy_state() {
+        Some(bytes) => {
+            info!("Migrating legacy state to stable memory");
+            T::decode(bytes)
+        }
+        None => {
+            let bytes = load_heap_state().ok_or("Heap state is not saved")?;
+            T::decode_heap(bytes)
+        }
+    }
+}
+
 pub fn decode_store_or_default<T>(bytes: Option<Vec<u8>>) -> Result<RefCell<T>, String>
 where
     T: Default + StableState,
 {
     let inner = if let Some(bytes) = bytes {
-        T::decode(bytes)?
+        decode_versioned(bytes)?
     } else {
         T::default()
     };
@@ -21,7 +173,7 @@ pub fn decode_store<T>(bytes: Vec<u8>) -> Result<RefCell<T>, String>
 where
     T: Default + StableState,
 {
-    let inner = T::decode(bytes)?;
+    let inner = decode_versioned(bytes)?;
     Ok(RefCell::new(inner))
 }
 
@@ -2,8 +2,14 @@ use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 use std::str::FromStr;
 
-use candid::{decode_args, encode_args, CandidType, Deserialize, Nat, Principal};
+use candid::{decode_args, encode_args, encode_one, CandidType, Deserialize, Nat, Principal};
 use rstest::*;
+use serde_bytes::ByteBuf;
+
+use super::{
+    decode_store, decode_versioned, encode_versioned, get_current_version, StableState,
+    StateMigration, VersionedState, VERSIONED_STATE_MAGIC,
+};
 
 #[derive(CandidType, Deserialize, Eq, PartialEq, Debug)]
 enum TestOrderStatus {
@@ -371,3 +377,201 @@ fn test_encode_decode_option(encoded_state_v1: Vec<u8>) {
     assert_eq!(settings.create_at, 3456);
     assert_eq!(something_option.is_none(), true);
 }
+
+mod versioned_state {
+    use super::*;
+
+    // layouts of users in the test store, version 0 is encoded before versioning
+    #[derive(CandidType, Deserialize)]
+    struct TestUserV0 {
+        name: String,
+    }
+
+    #[derive(CandidType, Deserialize)]
+    struct TestUserV1 {
+        name: String,
+        age: u8,
+    }
+
+    #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+    struct TestUser {
+        name: String,
+        age: u8,
+        emails: Vec<String>,
+    }
+
+    #[derive(Default)]
+    struct TestUserStore {
+        users: HashMap<Principal, TestUser>,
+    }
+
+    // age is required since version 1
+    fn migrate_v0(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        let (users,): (HashMap<Principal, TestUserV0>,) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
+        let users: HashMap<Principal, TestUserV1> = users
+            .into_iter()
+            .map(|(principal, user)| {
+                let user = TestUserV1 {
+                    name: user.name,
+                    age: 0,
+                };
+                (principal, user)
+            })
+            .collect();
+        encode_args((users,)).map_err(|e| e.to_string())
+    }
+
+    // emails are required since version 2
+    fn migrate_v1(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        let (users,): (HashMap<Principal, TestUserV1>,) =
+            decode_args(&bytes).map_err(|e| e.to_string())?;
+        let users: HashMap<Principal, TestUser> = users
+            .into_iter()
+            .map(|(principal, user)| {
+                let user = TestUser {
+                    name: user.name,
+                    age: user.age,
+                    emails: vec![],
+                };
+                (principal, user)
+            })
+            .collect();
+        encode_args((users,)).map_err(|e| e.to_string())
+    }
+
+    impl StableState for TestUserStore {
+        const MIGRATIONS: &'static [StateMigration] = &[migrate_v0, migrate_v1];
+
+        fn encode(&self) -> Vec<u8> {
+            encode_args((&self.users,)).unwrap()
+        }
+
+        fn decode(bytes: Vec<u8>) -> Result<Self, String> {
+            let (users,): (HashMap<Principal, TestUser>,) =
+                decode_args(&bytes).map_err(|e| e.to_string())?;
+            Ok(TestUserStore { users })
+        }
+    }
+
+    #[fixture]
+    fn user_principal() -> Principal {
+        Principal::from_str("zo36k-iqaaa-aaaaj-qahdq-cai").unwrap()
+    }
+
+    fn versioned_snapshot(version: u32, bytes: Vec<u8>) -> Vec<u8> {
+        let envelope = encode_one(VersionedState {
+            version,
+            bytes: ByteBuf::from(bytes),
+        })
+        .unwrap();
+        [VERSIONED_STATE_MAGIC.to_vec(), envelope].concat()
+    }
+
+    #[rstest]
+    fn test_decode_snapshot_before_versioning(user_principal: Principal) {
+        let users = HashMap::from_iter(vec![(
+            user_principal,
+            TestUserV0 {
+                name: "test".to_string(),
+            },
+        )]);
+        let snapshot = encode_args((users,)).unwrap();
+
+        let store = decode_store::<TestUserStore>(snapshot).unwrap();
+
+        assert_eq!(
+            store.borrow().users.get(&user_principal),
+            Some(&TestUser {
+                name: "test".to_string(),
+                age: 0,
+                emails: vec![],
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_decode_snapshot_of_version_1(user_principal: Principal) {
+        let users = HashMap::from_iter(vec![(
+            user_principal,
+            TestUserV1 {
+                name: "test".to_string(),
+                age: 18,
+            },
+        )]);
+        let snapshot = versioned_snapshot(1, encode_args((users,)).unwrap());
+
+        let store = decode_store::<TestUserStore>(snapshot).unwrap();
+
+        assert_eq!(
+            store.borrow().users.get(&user_principal),
+            Some(&TestUser {
+                name: "test".to_string(),
+                age: 18,
+                emails: vec![],
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_encode_decode_current_version(user_principal: Principal) {
+        let user = TestUser {
+            name: "test".to_string(),
+            age: 18,
+            emails: vec!["test@example.com".to_string()],
+        };
+        let store = TestUserStore {
+            users: HashMap::from_iter(vec![(user_principal, user.clone())]),
+        };
+
+        let bytes = encode_versioned(&store);
+
+        assert_eq!(get_current_version::<TestUserStore>(), 2);
+        let decoded = decode_versioned::<TestUserStore>(bytes).unwrap();
+        assert_eq!(decoded.users.get(&user_principal), Some(&user));
+    }
+
+    #[rstest]
+    fn test_decode_newer_version() {
+        let snapshot = versioned_snapshot(3, vec![]);
+
+        let result = decode_versioned::<TestUserStore>(snapshot);
+
+        assert_eq!(
+            result.err(),
+            Some("Encoded version 3 is newer than current version 2".to_string())
+        );
+    }
+
+    #[rstest]
+    fn test_decode_invalid_snapshot() {
+        let snapshot = versioned_snapshot(1, encode_args((1u8,)).unwrap());
+
+        let result = decode_versioned::<TestUserStore>(snapshot);
+
+        assert!(result
+            .err()
+            .unwrap()
+            .starts_with("Failed to migrate state of version 1"));
+    }
+
+    #[rstest]
+    fn test_decode_malformed_envelope(user_principal: Principal) {
+        let users = HashMap::from_iter(vec![(
+            user_principal,
+            TestUserV1 {
+                name: "test".to_string(),
+                age: 18,
+            },
+        )]);
+        let mut snapshot = versioned_snapshot(1, encode_args((users,)).unwrap());
+        snapshot.truncate(snapshot.len() - 1);
+
+        let result = decode_versioned::<TestUserStore>(snapshot);
+
+        assert!(result
+            .err()
+            .unwrap()
+            .starts_with("Failed to decode versioned state"));
+    }
+}
@@ -6,6 +6,12 @@ use std::collections::HashMap;
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum LockId {
     TokenServiceRefund,
+    RegistrarCleanExpired,
+    MysteryBoxRetryReward,
+    MarketplaceRetryEscrow,
+    MarketplaceRefundOffer,
+    RegistryCleanExpiredLease,
+    RegistrarArchiveBlocks,
 }
 
 // 60 seconds
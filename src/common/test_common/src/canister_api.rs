@@ -8,6 +8,7 @@ use common::canister_api::*;
 use common::cycles_minting_types::*;
 use common::dto::*;
 use common::errors::ActorResult;
+use common::icrc::*;
 
 mock! {
     pub RegistryApi {
@@ -35,6 +36,7 @@ impl IRegistryApi for RegistryApi {
         new_owner: Principal,
         resolver: Principal,
     ) -> ActorResult<bool>;
+    async fn remove_name(&self, name: String) -> ActorResult<bool>;
     async fn get_resolver(&self, label: &str) -> ActorResult<Principal>;
     async fn get_users(&self, name: &str) -> ActorResult<RegistryUsers>;
 }
@@ -52,7 +54,9 @@ mock! {
 impl IResolverApi for ResolverApi {
     async fn ensure_resolver_created(&self, name: String) -> ActorResult<bool>;
     async fn remove_resolvers(&self, names: Vec<String>) -> ActorResult<bool>;
+    async fn reset_primary_names(&self, names: Vec<String>) -> ActorResult<bool>;
     async fn set_record_value(&self, name: String, patch_values: HashMap<String, String>) -> ActorResult<bool>;
+    async fn set_ttl(&self, name: String, ttl: u64) -> ActorResult<bool>;
 }
 }
 
@@ -83,6 +87,16 @@ impl IRegistrarApi for RegistrarApi {
     async fn import_quota(&self, request: ImportQuotaRequest)
         -> ActorResult<ImportQuotaStatus>;
     async fn register_from_gateway(&self, name: String, owner: Principal) -> ActorResult<bool>;
+    async fn add_quota_with_key(
+        &self,
+        quota_owner: Principal,
+        quota_type: QuotaType,
+        diff: u32,
+        key: String,
+    ) -> ActorResult<bool>;
+    async fn transfer_from(&self, name: String, to: Option<Principal>) -> ActorResult<bool>;
+    async fn get_owner(&self, name: String) -> ActorResult<Principal>;
+    async fn is_approved_to(&self, name: String, spender: Principal) -> ActorResult<bool>;
 }
 }
 
@@ -91,6 +105,20 @@ pub fn mock_registrar_api() -> MockRegistrarApi {
     MockRegistrarApi::new()
 }
 
+mock! {
+    pub ManagementApi {
+    }
+    #[async_trait]
+impl IManagementApi for ManagementApi {
+    async fn raw_rand(&self) -> ActorResult<Vec<u8>>;
+}
+}
+
+#[fixture]
+pub fn mock_management_api() -> MockManagementApi {
+    MockManagementApi::new()
+}
+
 mock! {
     pub DICPApi {
     }
@@ -121,3 +149,48 @@ impl IDICPApi for DICPApi {
 pub fn mock_dicp_api() -> MockDICPApi {
     MockDICPApi::new()
 }
+
+mock! {
+    pub IcrcLedgerApi {
+    }
+    #[async_trait]
+impl IIcrcLedgerApi for IcrcLedgerApi {
+    async fn icrc1_transfer(
+        &self,
+        ledger: Principal,
+        arg: Icrc1TransferArg,
+    ) -> ActorResult<Result<Nat, Icrc1TransferError>>;
+
+    async fn icrc2_transfer_from(
+        &self,
+        ledger: Principal,
+        args: Icrc2TransferFromArgs,
+    ) -> ActorResult<Result<Nat, Icrc2TransferFromError>>;
+
+    async fn icrc1_fee(&self, ledger: Principal) -> ActorResult<Nat>;
+}
+}
+
+#[fixture]
+pub fn mock_icrc_ledger_api() -> MockIcrcLedgerApi {
+    MockIcrcLedgerApi::new()
+}
+
+mock! {
+    pub RegistrarArchiveApi {
+    }
+    #[async_trait]
+impl IRegistrarArchiveApi for RegistrarArchiveApi {
+    async fn append_blocks(
+        &self,
+        archive: Principal,
+        start: u64,
+        blocks: Vec<Value>,
+    ) -> ActorResult<u64>;
+}
+}
+
+#[fixture]
+pub fn mock_registrar_archive_api() -> MockRegistrarArchiveApi {
+    MockRegistrarArchiveApi::new()
+}
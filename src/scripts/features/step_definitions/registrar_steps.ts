@@ -22,6 +22,9 @@ import {assert_remote_result} from './utils'
 import logger from 'node-color-log'
 import fs from 'fs'
 import {canister, utils} from '@deland-labs/ic-dev-kit'
+import {IDL} from '@dfinity/candid'
+import {Principal} from '@dfinity/principal'
+import crypto from 'crypto'
 import {
     AssignNameResponse,
     ImportQuotaResponse
@@ -38,6 +41,19 @@ let global_renew_name_result: RenewNameResult
 let global_get_name_status_result: GetNameStatueActorResponse
 let global_register_with_payment_result: RegisterWithPaymentResponse
 
+// NAMING_COMMITMENT_MIN_AGE_NS of registrar plus one second
+const COMMITMENT_MIN_AGE_MS = 61000
+
+// commit the name for the owner and wait until the commitment can be revealed, returns the secret
+async function commit_name(registrar: ReturnType<typeof createRegistrar>, name: string, owner: Principal): Promise<number[]> {
+    const secret = Array.from(crypto.randomBytes(32))
+    const bytes = IDL.encode([IDL.Text, IDL.Principal, IDL.Vec(IDL.Nat8)], [name, owner, secret])
+    const commitment = crypto.createHash('sha256').update(Buffer.from(bytes)).digest('hex')
+    await new Result(registrar.commit(commitment)).unwrap()
+    await new Promise(resolve => setTimeout(resolve, COMMITMENT_MIN_AGE_MS))
+    return secret
+}
+
 function diff_less_than(a: bigint, b: bigint, diff: bigint): boolean {
     if (a > b) {
         return (a - b) < diff
@@ -112,7 +128,13 @@ Given(/^Name "([^"]*)" is already taken$/,
         const identityInfo = identities.main
         const registrar = createRegistrar(identityInfo)
         await new Result(registrar.add_quota(identities.main.identity.getPrincipal(), quote_type, 1)).unwrap()
-        await new Result(registrar.register_with_quota(name, quote_type)).unwrap()
+        const secret = await commit_name(registrar, name, identityInfo.identity.getPrincipal())
+        await new Result(registrar.reveal_register_with_quota({
+            name: name,
+            secret: secret,
+            quota_type: quote_type,
+            years: 1
+        })).unwrap()
     })
 Then(/^get_name_expires "([^"]*)" result is about in "([^"]*)" years$/,
     async function (name: string, year: string) {
@@ -244,7 +266,13 @@ When(/^User "([^"]*)" register name "([^"]*)" with quote "([^"]*)"$/,
         const quotaType = to_quota_type(quota_string)
         const identityInfo = identities.getIdentity(user)
         const registrar = createRegistrar(identityInfo)
-        global_register_with_quota_response = await registrar.register_with_quota(name, quotaType)
+        const secret = await commit_name(registrar, name, identityInfo.identity.getPrincipal())
+        global_register_with_quota_response = await registrar.reveal_register_with_quota({
+            name: name,
+            secret: secret,
+            quota_type: quotaType,
+            years: 1
+        })
     })
 Then(/^Register with quota result in status '([^']*)'$/,
     async function (status: string) {
@@ -439,14 +467,18 @@ Then(/^Check get_name_status is$/,
     });
 When(/^User "([^"]*)" register name "([^"]*)" for "([^"]*)" years and pay "([^"]*)"$/,
     async function (user: string, name: string, years: string, approve_amount: string) {
-        const registrar = createRegistrar(identities.getIdentity(user))
-        const dicp = createDicp(identities.getIdentity(user))
+        const identityInfo = identities.getIdentity(user)
+        const registrar = createRegistrar(identityInfo)
+        const dicp = createDicp(identityInfo)
         const amount = utils.toICPe8s(approve_amount)
         await dicp.approve([], canister.get_id('registrar'), amount, [])
-        global_register_with_payment_result = await registrar.register_with_payment({
+        const secret = await commit_name(registrar, name, identityInfo.identity.getPrincipal())
+        global_register_with_payment_result = await registrar.reveal_register_with_payment({
             name: name,
             years: parseInt(years),
-            approve_amount: amount
+            approve_amount: amount,
+            secret: secret,
+            ledger: []
         });
     });
 Then(/^Last register_with_payment result is '([^']*)'$/,
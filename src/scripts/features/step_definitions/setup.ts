@@ -1,3 +1,4 @@
 import {setDefaultTimeout} from '@cucumber/cucumber'
 
-setDefaultTimeout(60000)
+// registering a name waits for the commitment to be revealable
+setDefaultTimeout(120000)